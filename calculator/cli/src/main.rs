@@ -5,12 +5,15 @@
  */
 
 use std::io::{stdin, stdout, Write};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use clap::{Arg, ArgAction, Command};
 use colored::Colorize;
 
-use funcially_core::{data_dir, AccessError, Calculator, ResultData, Settings, Verbosity};
+use funcially_core::{data_dir, AccessError, CALCULATION_STACK_SIZE, Calculator, Capabilities, ColorSegment, Error, ResultData, Settings, Verbosity};
+
+mod serve;
+mod watch;
 
 const NAME: &str = env!("CARGO_PKG_NAME");
 const VERSION: &str = env!("CARGO_PKG_VERSION");
@@ -24,6 +27,14 @@ fn settings_path() -> PathBuf {
 }
 
 fn main() {
+    // Deep recursions need more than the default stack size of the main thread
+    let thread = std::thread::Builder::new().stack_size(CALCULATION_STACK_SIZE);
+    let main = thread.spawn(run).expect("failed to spawn the main thread");
+    // The panic message has already been printed
+    if main.join().is_err() { std::process::exit(101); }
+}
+
+fn run() {
     let matches = Command::new(NAME)
         .about(DESCRIPTION)
         .version(VERSION)
@@ -32,7 +43,7 @@ fn main() {
         .arg(Arg::new("verbosity")
             .short('v')
             .long("verbosity")
-            .help("How much debug info should be printed per calculation. One of 'none', 'tokens', 'ast'")
+            .help("How much debug info should be printed per calculation. One of 'none', 'tokens', 'ast', 'typed_ast', 'json'")
             .action(ArgAction::Set)
             .default_value("none"))
         .arg(Arg::new("thousands_separator")
@@ -40,10 +51,34 @@ fn main() {
             .long("thousands_separator")
             .help("Use a '_' as the thousands separator in the results")
             .action(ArgAction::SetTrue))
+        .arg(Arg::new("watch")
+            .short('w')
+            .long("watch")
+            .help("Calculate a file and recalculate it every time it changes")
+            .value_name("FILE")
+            .action(ArgAction::Set))
+        .arg(Arg::new("on_change")
+            .long("on-change")
+            .help("A command that is run whenever the results of the watched file change. It receives the results as JSON on stdin")
+            .value_name("COMMAND")
+            .requires("watch")
+            .action(ArgAction::Set))
         .arg(Arg::new("input")
             .help("Calculate a single string and print the result")
             .last(true)
             .action(ArgAction::Append))
+        .subcommand(Command::new("serve")
+            .about("Serve the calculator as a REST API on localhost (POST /calculate, GET /functions)")
+            .arg(Arg::new("port")
+                .short('p')
+                .long("port")
+                .help("The port to listen on")
+                .value_parser(clap::value_parser!(u16))
+                .default_value("8080"))
+            .arg(Arg::new("sandbox")
+                .long("sandbox")
+                .help("Calculate the requests without access to files or the network and without defining variables or functions")
+                .action(ArgAction::SetTrue)))
         .get_matches();
 
     let verbosity = match matches.get_one::<String>("verbosity") {
@@ -62,9 +97,28 @@ fn main() {
         .unwrap_or_else(Settings::default);
 
     let mut calculator = Calculator::new(verbosity, settings);
+    for error in calculator.load_prelude() {
+        eprintln!("{}: {error}", "Error in prelude".red());
+    }
+
+    if let Some(serve_matches) = matches.subcommand_matches("serve") {
+        let port = *serve_matches.get_one::<u16>("port").unwrap();
+        // The prelude has already been loaded, so only the requests are restricted
+        if serve_matches.get_flag("sandbox") {
+            calculator.context.borrow_mut().capabilities = Capabilities::sandboxed();
+        }
+        serve::serve(port, &mut calculator, use_thousands_separator);
+        return;
+    }
+
+    if let Some(path) = matches.get_one::<String>("watch") {
+        let on_change_command = matches.get_one::<String>("on_change").map(String::as_str);
+        watch::watch(Path::new(path), &mut calculator, use_thousands_separator, on_change_command);
+        return;
+    }
 
     if let Some(input) = matches.get_many::<String>("input") {
-        let input = input.fold(String::new(), |acc, s| acc + s);
+        let input = input.fold(String::new(), |acc, s| acc + s.as_str());
         calculate_and_print(input, &mut calculator, use_thousands_separator);
         return;
     }
@@ -170,61 +224,114 @@ fn calculate_and_print(
 
     match input.as_str() {
         "quit" | "exit" => return false,
-        _ => match &calculator.calculate(&input)[0].data {
-            Ok((res, _)) => match res {
-                ResultData::Value(value) => {
-                    println!(
-                        "= {}",
-                        value.format(
-                            &calculator.context.borrow().settings,
-                            use_thousands_separator
-                        )
-                    );
-                }
-                ResultData::Boolean(b) => {
-                    println!("=> {}", if *b { "True".green() } else { "False".red() });
-                }
-                ResultData::Function { .. }
-                | ResultData::Nothing
-                | ResultData::FunctionRemoval(_) => {}
-            },
-            Err(ref error) => {
-                let mut error = error.clone();
-                eprintln!("{}: {}", "Error".red(), error.error);
-
-                error.ranges.sort();
-                let ranges = &error.ranges;
-
-                let slice_start =
-                    std::cmp::max(0, ranges.first().unwrap().start_char as isize - 5) as usize;
-                let slice_end = std::cmp::min(input.len(), ranges.last().unwrap().end_char + 5);
-                let slice = &input[slice_start..slice_end];
-                eprintln!("{slice}");
-
-                let mut last_end = 0usize;
-
-                for range in ranges {
-                    // Offset the range so that it is in the range of our slice
-                    let range = range.start_char - slice_start..range.end_char - slice_start;
-
-                    #[allow(clippy::mut_range_bound)]
-                    for _ in last_end..range.start {
-                        eprint!(" ");
-                        last_end += 1;
-                    }
+        _ => {
+            if matches!(calculator.verbosity, Verbosity::Tokens | Verbosity::TypedAst | Verbosity::Json) {
+                println!("{}", calculator.get_debug_info(&input, calculator.verbosity));
+            }
 
-                    eprint!("{}", "^".cyan());
-                    for _ in 0..range.end - range.start - 1 {
-                        eprint!("{}", "-".cyan());
-                        last_end += 1;
+            let results = calculator.calculate(&input);
+            let result = &results[0];
+            match &result.data {
+                Ok(_) if result.suppressed => {}
+                Ok((res, _)) => match res {
+                    ResultData::Value(value) | ResultData::Slider { value, .. } => {
+                        println!(
+                            "= {}",
+                            highlight(&value.format(
+                                &calculator.context.borrow().settings,
+                                use_thousands_separator
+                            ))
+                        );
+                    }
+                    ResultData::Boolean { value, difference } => {
+                        print!("=> {}", if *value { "True".green() } else { "False".red() });
+                        match difference {
+                            Some(difference) => println!(
+                                " ({})",
+                                highlight(&difference.format(&calculator.context.borrow().settings, use_thousands_separator))
+                            ),
+                            None => println!(),
+                        }
+                    }
+                    ResultData::Function { .. }
+                    | ResultData::Nothing
+                    | ResultData::FunctionRemoval(_) => {}
+                },
+                Err(_) => {
+                    for error in result.errors() {
+                        print_error(&input, error);
                     }
-                    last_end += 1;
                 }
-
-                eprintln!(" {}", error.error.to_string().cyan());
             }
-        },
+            for warning in &result.warnings {
+                if warning.warning.is_hint() {
+                    eprintln!("{}: {}", "Hint".dimmed(), warning.warning.to_string().dimmed());
+                } else {
+                    eprintln!("{}: {}", "Warning".yellow(), warning.warning);
+                }
+            }
+        }
     }
 
     true
 }
+
+/// Colors the numbers, units and operators of a result like the GUI does
+fn highlight(result: &str) -> String {
+    let chars = result.chars().collect::<Vec<_>>();
+    let mut highlighted = String::new();
+    let mut end = 0usize;
+    for segment in ColorSegment::output(result) {
+        let start = segment.range.start_char.max(end);
+        if start >= segment.range.end_char { continue; }
+        highlighted.extend(&chars[end..start]);
+        let [r, g, b, _] = segment.color.0;
+        let text = chars[start..segment.range.end_char].iter().collect::<String>();
+        highlighted += &text.truecolor(r, g, b).to_string();
+        end = segment.range.end_char;
+    }
+    highlighted.extend(&chars[end..]);
+    highlighted
+}
+
+/// Prints `error` together with the part of `input` it is about. The range where it occurred is
+/// marked with `^---` and the ranges related to it (e.g. the other vector when the lengths of two
+/// vectors don't match) with `~~~`.
+fn print_error(input: &str, error: &Error) {
+    eprintln!("{}: {}", "Error".red(), error.error);
+
+    // The ranges are in bytes, but the markers have to be below the characters
+    let chars = input.chars().collect::<Vec<_>>();
+    let column = |byte: usize| input.char_indices().take_while(|(i, _)| *i < byte).count();
+    let ranges = error.ranges.iter()
+        .enumerate()
+        .filter(|(_, range)| range.start_line == 0)
+        .map(|(i, range)| {
+            let start = column(range.start_char);
+            // Ranges spanning multiple lines extend to the end of the input
+            let end = if range.end_line <= 1 { column(range.end_char) } else { chars.len() };
+            // Empty ranges (e.g. at the end of the input) still get a marker
+            (i == 0, start..end.max(start + 1))
+        })
+        .collect::<Vec<_>>();
+    let (Some(start), Some(end)) = (ranges.iter().map(|(_, r)| r.start).min(), ranges.iter().map(|(_, r)| r.end).max())
+        else { return; };
+    let slice_start = start.saturating_sub(5);
+    let slice_end = (end + 5).min(chars.len());
+    eprintln!("{}", chars[slice_start..slice_end].iter().collect::<String>());
+
+    let mut markers = vec![' '; end - slice_start];
+    // The range of the error is drawn last, so that it's on top of overlapping related ones
+    for (is_error, range) in ranges.into_iter().rev() {
+        let range = range.start - slice_start..range.end - slice_start;
+        if is_error {
+            markers[range.clone()].fill('-');
+            markers[range.start] = '^';
+        } else {
+            markers[range].fill('~');
+        }
+    }
+
+    let markers = markers.into_iter().collect::<String>();
+    eprintln!("{} {}", markers.trim_end().cyan(), error.error.to_string().cyan());
+}
@@ -0,0 +1,311 @@
+/*
+ * Copyright (c) 2023, david072
+ *
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::hash::{BuildHasher, Hasher};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{Ipv4Addr, TcpListener, TcpStream};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use colored::Colorize;
+use serde_json::{json, Value as JsonValue};
+
+use funcially_core::{Calculator, Environment, FunctionKind, LineResult, ResultData, Settings, Value};
+
+/// The header containing the token of the session whose environment is used
+const SESSION_HEADER: &str = "X-Session-Token";
+/// The number of sessions that are kept. If there are more, the least recently used one is removed.
+const MAX_SESSIONS: usize = 64;
+const MAX_HEADERS: usize = 64;
+const MAX_BODY_SIZE: usize = 1024 * 1024;
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+struct Request {
+    method: String,
+    path: String,
+    /// The headers, with lowercase names
+    headers: HashMap<String, String>,
+    body: String,
+}
+
+struct Response {
+    status: u16,
+    reason: &'static str,
+    session_token: Option<String>,
+    body: JsonValue,
+}
+
+impl Response {
+    fn ok(session_token: Option<String>, body: JsonValue) -> Self {
+        Self { status: 200, reason: "OK", session_token, body }
+    }
+
+    fn error(status: u16, reason: &'static str, message: &str) -> Self {
+        Self { status, reason, session_token: None, body: json!({ "error": message }) }
+    }
+}
+
+struct Session {
+    env: Environment,
+    /// The number of the last request that used this session
+    last_used: u64,
+}
+
+struct Server<'a> {
+    calculator: &'a mut Calculator,
+    use_thousands_separator: bool,
+    sessions: HashMap<String, Session>,
+    request_count: u64,
+}
+
+/// Serves the calculator as a REST API on localhost:
+///
+/// - `POST /calculate` calculates the request's body and responds with the results of its lines
+/// - `GET /functions` lists the functions that can be called
+///
+/// Every session has its own environment, so that variables and functions defined in one request
+/// can be used in the following ones. The session is selected with the `X-Session-Token` header.
+/// Requests without a (known) token start a new session, whose token is sent back in the same
+/// header.
+pub fn serve(port: u16, calculator: &mut Calculator, use_thousands_separator: bool) {
+    let listener = match TcpListener::bind((Ipv4Addr::LOCALHOST, port)) {
+        Ok(listener) => listener,
+        Err(e) => {
+            eprintln!("{}: Failed to listen on port {port}: {e}", "Error".red());
+            return;
+        }
+    };
+    println!("Listening on http://{}:{port}", Ipv4Addr::LOCALHOST);
+
+    let mut server = Server {
+        calculator,
+        use_thousands_separator,
+        sessions: HashMap::new(),
+        request_count: 0,
+    };
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => server.handle_connection(stream),
+            Err(e) => eprintln!("{}: Failed to accept a connection: {e}", "Error".red()),
+        }
+    }
+}
+
+impl Server<'_> {
+    fn handle_connection(&mut self, mut stream: TcpStream) {
+        let _ = stream.set_read_timeout(Some(READ_TIMEOUT));
+        let response = match read_request(&stream) {
+            Ok(request) => self.handle_request(request),
+            Err(response) => response,
+        };
+        // The client might already be gone, in which case there's nobody to tell about it
+        let _ = write_response(&mut stream, response);
+    }
+
+    fn handle_request(&mut self, request: Request) -> Response {
+        self.request_count += 1;
+        match (request.method.as_str(), request.path.as_str()) {
+            ("POST", "/calculate") => self.calculate(&request),
+            ("GET", "/functions") => self.functions(&request),
+            (_, "/calculate" | "/functions") => Response::error(405, "Method Not Allowed", "Method not allowed"),
+            _ => Response::error(404, "Not Found", "Not found"),
+        }
+    }
+
+    fn calculate(&mut self, request: &Request) -> Response {
+        let token = self.session_token(request).unwrap_or_else(|| self.new_session());
+        let session = self.sessions.get_mut(&token).unwrap();
+        session.last_used = self.request_count;
+
+        self.calculator.context.borrow_mut().env = std::mem::take(&mut session.env);
+        let mut lines = vec![];
+        self.calculator.calculate_all(&request.body, |line| lines.push(line));
+        session.env = self.calculator.clone_env();
+
+        let settings = self.calculator.context.borrow().settings;
+        let results = lines.iter()
+            .map(|line| line_json(line, &settings, self.use_thousands_separator))
+            .collect::<Vec<_>>();
+        Response::ok(Some(token), json!({ "results": results }))
+    }
+
+    fn functions(&mut self, request: &Request) -> Response {
+        let token = self.session_token(request);
+        let env = match &token {
+            Some(token) => &self.sessions[token].env,
+            None => &self.calculator.base_env,
+        };
+
+        let functions = env.function_signatures().into_iter()
+            .map(|signature| {
+                let mut function = json!({
+                    "name": signature.name,
+                    "arguments": signature.argument_counts,
+                    "kind": match signature.kind {
+                        FunctionKind::Standard => "standard",
+                        FunctionKind::Custom => "custom",
+                        FunctionKind::Script => "script",
+                    },
+                });
+                if let Some(help) = signature.help {
+                    function["usage"] = help.usage.into();
+                    function["description"] = help.description.into();
+                    function["example"] = help.example.into();
+                }
+                function
+            })
+            .collect::<Vec<_>>();
+        Response::ok(token, json!({ "functions": functions }))
+    }
+
+    /// The token of the request's session, if it has one and the session still exists
+    fn session_token(&self, request: &Request) -> Option<String> {
+        request.headers.get(&SESSION_HEADER.to_lowercase())
+            .filter(|token| self.sessions.contains_key(*token))
+            .cloned()
+    }
+
+    /// Creates a new session starting with the base environment of the calculator and returns its
+    /// token
+    fn new_session(&mut self) -> String {
+        if self.sessions.len() >= MAX_SESSIONS {
+            let least_recently_used = self.sessions.iter()
+                .min_by_key(|(_, session)| session.last_used)
+                .map(|(token, _)| token.clone());
+            if let Some(token) = least_recently_used {
+                self.sessions.remove(&token);
+            }
+        }
+
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u64(self.request_count);
+        hasher.write_u128(SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos());
+        let token = format!("{:016x}", hasher.finish());
+
+        self.sessions.insert(token.clone(), Session {
+            env: self.calculator.base_env.clone(),
+            last_used: self.request_count,
+        });
+        token
+    }
+}
+
+/// Converts the result of a calculation into JSON, containing its line range (zero-based, with an
+/// exclusive end), its result (if it succeeded), its errors and its warnings. `suppressed` is set if
+/// the result is hidden with `;` or `@ignore`.
+fn line_json(line: &LineResult, settings: &Settings, use_thousands_separator: bool) -> JsonValue {
+    let mut entry = json!({
+        "lines": { "start": line.line_range.start, "end": line.line_range.end },
+        "errors": line.result.errors().into_iter()
+            .map(|error| json!({ "message": error.error.to_string(), "ranges": error.ranges }))
+            .collect::<Vec<_>>(),
+        "warnings": line.result.warnings.iter()
+            .map(|warning| json!({ "message": warning.warning.to_string(), "ranges": warning.ranges }))
+            .collect::<Vec<_>>(),
+        "suppressed": line.result.suppressed,
+        "range": line.result.source_range,
+    });
+
+    if let Ok((data, _)) = &line.result.data {
+        entry["result"] = match data {
+            ResultData::Value(value) => {
+                let mut result = json!({
+                    "kind": "value",
+                    "text": value.format(settings, use_thousands_separator).trim(),
+                });
+                if let Value::Number(number) = value {
+                    result["number"] = number.number.into();
+                    result["unit"] = number.unit_symbol().into();
+                    result["format"] = number.format.to_string().into();
+                    result["rounded"] = number.is_rounded(&settings.output).into();
+                }
+                result
+            }
+            ResultData::Boolean { value, difference } => json!({
+                "kind": "boolean",
+                "value": value,
+                "difference": difference.as_ref().map(|difference| json!({
+                    "text": difference.format(settings, use_thousands_separator),
+                    "number": difference.absolute.to_number().map(|number| number.number),
+                    "relative": difference.relative,
+                })),
+            }),
+            ResultData::Function { name, arg_count, .. } =>
+                json!({ "kind": "function", "name": name, "arguments": arg_count }),
+            ResultData::FunctionRemoval(name) => json!({ "kind": "function_removal", "name": name }),
+            ResultData::Slider { name, value, min, max, step } => json!({
+                "kind": "slider",
+                "name": name,
+                "text": value.format(settings, use_thousands_separator).trim(),
+                "number": value.to_number().map(|number| number.number),
+                "min": min,
+                "max": max,
+                "step": step,
+            }),
+            ResultData::Nothing => json!({ "kind": "nothing" }),
+        };
+    }
+    entry
+}
+
+fn read_request(stream: &TcpStream) -> Result<Request, Response> {
+    let bad_request = |message: &str| Response::error(400, "Bad Request", message);
+    let mut reader = BufReader::new(stream);
+
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line).map_err(|_| bad_request("Failed to read the request"))?;
+    let mut parts = request_line.split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        return Err(bad_request("Malformed request line"));
+    };
+    // Query parameters aren't used
+    let path = target.split('?').next().unwrap_or(target).to_string();
+
+    let mut headers = HashMap::new();
+    loop {
+        let mut line = String::new();
+        reader.read_line(&mut line).map_err(|_| bad_request("Failed to read the headers"))?;
+        let line = line.trim_end();
+        if line.is_empty() { break; }
+        if headers.len() >= MAX_HEADERS { return Err(bad_request("Too many headers")); }
+
+        let Some((name, value)) = line.split_once(':') else {
+            return Err(bad_request("Malformed header"));
+        };
+        headers.insert(name.trim().to_lowercase(), value.trim().to_string());
+    }
+
+    let content_length = match headers.get("content-length") {
+        Some(length) => length.parse::<usize>().map_err(|_| bad_request("Invalid Content-Length"))?,
+        None => 0,
+    };
+    if content_length > MAX_BODY_SIZE {
+        return Err(Response::error(413, "Payload Too Large", "The request body is too large"));
+    }
+
+    let mut body = vec![0u8; content_length];
+    reader.read_exact(&mut body).map_err(|_| bad_request("Failed to read the body"))?;
+    let body = String::from_utf8(body).map_err(|_| bad_request("The body is not valid UTF-8"))?;
+
+    Ok(Request { method: method.to_string(), path, headers, body })
+}
+
+fn write_response(stream: &mut TcpStream, response: Response) -> std::io::Result<()> {
+    let body = response.body.to_string();
+    let mut head = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n",
+        response.status, response.reason, body.len(),
+    );
+    if let Some(token) = response.session_token {
+        head += &format!("{SESSION_HEADER}: {token}\r\n");
+    }
+    head += "\r\n";
+
+    stream.write_all(head.as_bytes())?;
+    stream.write_all(body.as_bytes())?;
+    stream.flush()
+}
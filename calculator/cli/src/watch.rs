@@ -0,0 +1,158 @@
+/*
+ * Copyright (c) 2023, david072
+ *
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::thread::sleep;
+use std::time::{Duration, SystemTime};
+
+use colored::Colorize;
+use serde_json::{json, Value as JsonValue};
+
+use funcially_core::{Calculator, Document, DocumentLineKind, ResultData};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Calculates the file at `path` and prints its results every time it is modified. If
+/// `on_change_command` is given, it is run through the shell whenever the results change, with the
+/// JSON transcript of the results on stdin.
+pub fn watch(
+    path: &Path,
+    calculator: &mut Calculator,
+    use_thousands_separator: bool,
+    on_change_command: Option<&str>,
+) {
+    let mut last_modified: Option<SystemTime> = None;
+    let mut last_transcript: Option<JsonValue> = None;
+
+    loop {
+        let modified = std::fs::metadata(path).and_then(|metadata| metadata.modified());
+        match modified {
+            Ok(modified) if Some(modified) != last_modified => {
+                last_modified = Some(modified);
+
+                match std::fs::read_to_string(path) {
+                    Ok(source) => {
+                        let transcript = transcript(&source, calculator, use_thousands_separator);
+                        if last_transcript.as_ref() != Some(&transcript) {
+                            print_transcript(&transcript);
+                            if let Some(command) = on_change_command {
+                                run_on_change_command(command, &transcript);
+                            }
+                            last_transcript = Some(transcript);
+                        }
+                    }
+                    Err(e) => eprintln!("{}: {e}", "Error".red()),
+                }
+            }
+            Err(e) if last_modified.is_some() => {
+                eprintln!("{}: {e}", "Error".red());
+                last_modified = None;
+            }
+            _ => {}
+        }
+
+        sleep(POLL_INTERVAL);
+    }
+}
+
+/// Calculates `source` and returns a JSON array with an entry for every calculation, containing
+/// its (one-based) line number, its input, either its result or its error and its warnings and
+/// hints (if there are any). Failed equality checks additionally contain the difference between
+/// their sides. Calculations whose results are hidden with `;` or `@ignore` are left out unless they failed.
+fn transcript(source: &str, calculator: &mut Calculator, use_thousands_separator: bool) -> JsonValue {
+    let document = Document::new(calculator, source);
+    let settings = calculator.context.borrow().settings;
+
+    let entries = source.split('\n').zip(document.lines()).enumerate()
+        .filter_map(|(i, (input, line))| {
+            let DocumentLineKind::Result(result) = &line.kind else { return None; };
+            if line.suppressed && result.is_ok() { return None; }
+            let mut entry = json!({ "line": i + 1, "input": input.trim() });
+            match result {
+                Ok(ResultData::Value(value) | ResultData::Slider { value, .. }) =>
+                    entry["result"] = value.format(&settings, use_thousands_separator).trim().into(),
+                Ok(ResultData::Boolean { value, difference }) => {
+                    entry["result"] = (*value).into();
+                    if let Some(difference) = difference {
+                        entry["difference"] = difference.format(&settings, use_thousands_separator).into();
+                    }
+                }
+                Ok(_) => entry["result"] = JsonValue::Null,
+                Err(e) => entry["error"] = e.error.to_string().into(),
+            }
+            let (hints, warnings) = line.warnings.iter().partition::<Vec<_>, _>(|warning| warning.warning.is_hint());
+            if !warnings.is_empty() {
+                entry["warnings"] = warnings.iter()
+                    .map(|warning| JsonValue::from(warning.warning.to_string()))
+                    .collect();
+            }
+            if !hints.is_empty() {
+                entry["hints"] = hints.iter()
+                    .map(|hint| JsonValue::from(hint.warning.to_string()))
+                    .collect();
+            }
+            Some(entry)
+        })
+        .collect::<Vec<_>>();
+
+    JsonValue::Array(entries)
+}
+
+fn print_transcript(transcript: &JsonValue) {
+    let Some(entries) = transcript.as_array() else { return; };
+    for entry in entries {
+        let input = entry["input"].as_str().unwrap_or_default();
+        print!("{: >4} | {input}", entry["line"].as_u64().unwrap_or_default());
+        match (&entry["result"], &entry["error"]) {
+            (JsonValue::String(result), _) => println!(" = {}", crate::highlight(result)),
+            (JsonValue::Bool(b), _) => {
+                print!(" => {}", if *b { "True".green() } else { "False".red() });
+                match entry["difference"].as_str() {
+                    Some(difference) => println!(" ({})", crate::highlight(difference)),
+                    None => println!(),
+                }
+            }
+            (_, JsonValue::String(error)) => println!(" {}: {error}", "Error".red()),
+            _ => println!(),
+        }
+        for warning in entry["warnings"].as_array().into_iter().flatten() {
+            println!("     | {}: {}", "Warning".yellow(), warning.as_str().unwrap_or_default());
+        }
+        for hint in entry["hints"].as_array().into_iter().flatten() {
+            println!("     | {}: {}", "Hint".dimmed(), hint.as_str().unwrap_or_default().dimmed());
+        }
+    }
+    println!();
+}
+
+fn run_on_change_command(command: &str, transcript: &JsonValue) {
+    #[cfg(windows)]
+    let mut process = Command::new("cmd");
+    #[cfg(windows)]
+    process.args(["/C", command]);
+    #[cfg(not(windows))]
+    let mut process = Command::new("sh");
+    #[cfg(not(windows))]
+    process.args(["-c", command]);
+
+    let mut child = match process.stdin(Stdio::piped()).spawn() {
+        Ok(child) => child,
+        Err(e) => {
+            eprintln!("{}: Failed to run the on-change command: {e}", "Error".red());
+            return;
+        }
+    };
+
+    if let Some(mut stdin) = child.stdin.take() {
+        // The command doesn't have to read its input
+        let _ = stdin.write_all(transcript.to_string().as_bytes());
+    }
+    if let Err(e) = child.wait() {
+        eprintln!("{}: Failed to run the on-change command: {e}", "Error".red());
+    }
+}
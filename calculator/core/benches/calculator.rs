@@ -0,0 +1,123 @@
+/*
+ * Copyright (c) 2023, david072
+ *
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+use criterion::{BatchSize, black_box, Criterion, criterion_group, criterion_main, Throughput};
+
+use funcially_core::{bench, Calculator, Capabilities, Settings, TokenCache, Verbosity};
+
+const DEMO_TEXT: &str = include_str!("../../demo_text.txt");
+/// How often the demo text is repeated to get a large worksheet
+const REPETITIONS: usize = 30;
+
+fn worksheet() -> String {
+    vec![DEMO_TEXT; REPETITIONS].join("\n")
+}
+
+fn calculator() -> Calculator {
+    let capabilities = Capabilities { environment: true, ..Capabilities::none() };
+    Calculator::with_capabilities(Verbosity::None, Settings::default(), capabilities)
+}
+
+fn stages(c: &mut Criterion) {
+    let source = worksheet();
+    let tokens = bench::tokenize(&source);
+
+    // Calculate the worksheet once, so that the parser knows its variables and functions
+    let mut parse_calculator = calculator();
+    parse_calculator.calculate(&source);
+
+    c.bench_function("tokenize", |b| b.iter(|| bench::tokenize(black_box(&source))));
+    c.bench_function("parse", |b| b.iter(|| bench::parse(black_box(&tokens), &parse_calculator)));
+
+    let mut evaluate_calculator = calculator();
+    c.bench_function("evaluate", |b| b.iter_batched(
+        || bench::parse(&tokens, &parse_calculator),
+        |parser_results| bench::evaluate(parser_results, &mut evaluate_calculator),
+        BatchSize::SmallInput,
+    ));
+}
+
+/// Evaluates calculations made of deeply nested groups and function arguments, whose ASTs are
+/// moved into their evaluation level by level
+fn nested(c: &mut Criterion) {
+    // The parser allows up to 32 levels
+    const DEPTH: usize = 30;
+    let group = format!("{}1{}", "(1 + ".repeat(DEPTH), ")".repeat(DEPTH));
+    let arguments = format!("{}1{}", "sqrt(1 + ".repeat(DEPTH), ")".repeat(DEPTH));
+    let source = vec![format!("{group}\n{arguments}"); 50].join("\n");
+    let tokens = bench::tokenize(&source);
+    let parse_calculator = calculator();
+    let mut evaluate_calculator = calculator();
+
+    c.bench_function("evaluate_nested", |b| b.iter_batched(
+        || bench::parse(&tokens, &parse_calculator),
+        |parser_results| bench::evaluate(parser_results, &mut evaluate_calculator),
+        BatchSize::SmallInput,
+    ));
+}
+
+/// Recalculates the whole worksheet after a line was edited, like the GUI does on every keystroke
+fn keystroke(c: &mut Criterion) {
+    let source = worksheet();
+    let edited_source = source.replacen("3 + 4 * 2", "3 + 4 * 23", 1);
+    let sources = [source, edited_source];
+
+    let mut group = c.benchmark_group("keystroke");
+
+    let mut cached_calculator = calculator();
+    let mut i = 0usize;
+    group.bench_function("cached", |b| b.iter(|| {
+        i += 1;
+        cached_calculator.calculate_all(black_box(&sources[i % 2]), |line| { black_box(line); });
+    }));
+
+    let mut uncached_calculator = calculator();
+    group.bench_function("uncached", |b| b.iter(|| {
+        i += 1;
+        uncached_calculator.token_cache = TokenCache::default();
+        uncached_calculator.calculate_all(black_box(&sources[i % 2]), |line| { black_box(line); });
+    }));
+
+    let mut token_cache = TokenCache::default();
+    group.bench_function("tokenize_cached", |b| b.iter(|| {
+        i += 1;
+        token_cache.tokenize(black_box(&sources[i % 2]))
+    }));
+    group.bench_function("tokenize_uncached", |b| b.iter(|| {
+        i += 1;
+        bench::tokenize(black_box(&sources[i % 2]))
+    }));
+
+    group.finish();
+}
+
+/// Answers many small independent queries in the environment of the demo text, like a server does,
+/// once by calling [Calculator::calculate] in a loop and once with [Calculator::evaluate_batch]
+fn batch(c: &mut Criterion) {
+    const QUERIES: [&str; 8] = ["3 + 4 * 2", "5 km in mi", "sqrt(16) + 2^10", "20% of 150", "x := 5", "1 GiB in MB", "sin(30)", "(3 + 4)^2"];
+    let queries = QUERIES.repeat(100);
+
+    let mut calculator = calculator();
+    calculator.calculate(DEMO_TEXT);
+    let env = calculator.clone_env();
+
+    let mut group = c.benchmark_group("batch");
+    group.throughput(Throughput::Elements(queries.len() as u64));
+    group.bench_function("calculate_loop", |b| b.iter(|| {
+        for query in &queries {
+            // Restoring the environment keeps the queries independent of each other
+            calculator.context.borrow_mut().env = env.clone();
+            black_box(calculator.calculate(black_box(query)));
+        }
+    }));
+    group.bench_function("evaluate_batch", |b| b.iter(|| {
+        black_box(calculator.evaluate_batch(black_box(&queries)))
+    }));
+    group.finish();
+}
+
+criterion_group!(benches, stages, nested, keystroke, batch);
+criterion_main!(benches);
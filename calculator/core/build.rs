@@ -10,6 +10,7 @@ use std::fs;
 const OUTPUT_FILE: &str = "src/environment/default_currencies.rs";
 const UNITS_OUTPUT_FILE: &str = "src/environment/unit_conversion.rs";
 
+#[allow(dead_code)]
 #[derive(serde::Deserialize, Debug)]
 struct ApiResponse {
     base: String,
@@ -349,7 +350,7 @@ use crate::{
 
             file_content += r#"
 fn unit_prefix(unit: &str) -> Option<(char, i32)> {
-    if unit.len() < 2 { return None; }
+    if unit.chars().count() < 2 { return None; }
     if is_unit(unit) { return None; }
 
     let char = unit.chars().next().unwrap();
@@ -368,7 +369,7 @@ pub fn convert_units(
             return Err(ErrorType::UnknownConversion(
                 format!("{src_unit}{}", if src_power != 1.0 { format!("^{src_power}") } else { String::new() }),
                 format!("{dst_unit}{}", if dst_power != 1.0 { format!("^{dst_power}") } else { String::new() })
-            ).with(range.clone()))
+            ).with(*range))
         }
         return Ok(x);
     }
@@ -380,10 +381,12 @@ pub fn convert_units(
     let mut src = src_unit;
     let mut dst = dst_unit;
 
-    let src_prefix_power = unit_prefix(src).map(|x| x.1).unwrap_or(0);
-    if src_prefix_power != 0 { src = &src[1..]; }
-    let dst_prefix_power = unit_prefix(dst).map(|x| x.1).unwrap_or(0);
-    if dst_prefix_power != 0 { dst = &dst[1..]; }
+    let src_prefix = unit_prefix(src);
+    if let Some((prefix, _)) = src_prefix { src = &src[prefix.len_utf8()..]; }
+    let src_prefix_power = src_prefix.map(|x| x.1).unwrap_or(0);
+    let dst_prefix = unit_prefix(dst);
+    if let Some((prefix, _)) = dst_prefix { dst = &dst[prefix.len_utf8()..]; }
+    let dst_prefix_power = dst_prefix.map(|x| x.1).unwrap_or(0);
 
     let factor = 10f64.powi(src_prefix_power - dst_prefix_power);
     x *= factor;
@@ -439,7 +442,10 @@ pub fn format_unit(unit: &str, plural: bool) -> String {
     }
 
     let prefix = unit_prefix(unit).map(|x| x.0);
-    let unit = if prefix.is_some() { &unit[1..] } else { unit };
+    let unit = match prefix {
+        Some(prefix) => &unit[prefix.len_utf8()..],
+        None => unit,
+    };
 
     let mut result = String::new();
 
@@ -461,7 +467,7 @@ pub fn format_unit(unit: &str, plural: bool) -> String {
             _ => unreachable!(),
         };
 
-        if prefix.is_some() { result + &lowercase_first(str) }
+        if prefix.is_some() { result + lowercase_first(str).as_str() }
         else { result + str }
     }
     else {
@@ -473,7 +479,7 @@ pub fn format_unit(unit: &str, plural: bool) -> String {
             _ => unreachable!(),
         };
 
-        if prefix.is_some() { result + &lowercase_first(str) }
+        if prefix.is_some() { result + lowercase_first(str).as_str() }
         else { result + str }
     }
 }
@@ -0,0 +1,21 @@
+/*
+ * Copyright (c) 2023, david072
+ *
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+use funcially_core::{Calculator, Capabilities, Settings, Verbosity};
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(source) = std::str::from_utf8(data) {
+        // Calculating must never panic, whatever the input is. Definitions are allowed, but not
+        // accessing files or the network.
+        let capabilities = Capabilities { environment: true, ..Capabilities::none() };
+        Calculator::with_capabilities(Verbosity::None, Settings::default(), capabilities)
+            .calculate(source);
+    }
+});
@@ -8,11 +8,16 @@ use std::fmt::{Debug, Display, Formatter};
 
 use crate::{common::*, environment::{
     currencies::Currencies,
+    dimensions::Dimension,
+    number_theory,
     units::convert,
-}, error, Format};
+}, error, Format, ModuloMode, Settings, WordSize};
 use crate::astgen::objects::CalculatorObject;
 use crate::environment::units::Unit;
 
+/// The biggest integer up to which all integers can be represented exactly by an f64
+pub(crate) const MAX_EXACT_INTEGER: f64 = 9007199254740992.0;
+
 #[derive(Debug, PartialEq, Eq, Copy, Clone, serde::Serialize, serde::Deserialize)]
 pub enum BooleanOperator {
     Equal,
@@ -21,13 +26,18 @@ pub enum BooleanOperator {
     GreaterThanEqual,
     LessThan,
     LessThanEqual,
+    /// `≈`, which allows the sides to differ by a tolerance (see
+    /// [Engine::check_boolean_operator](crate::engine::Engine::check_boolean_operator))
+    ApproximatelyEqual,
 }
 
 impl BooleanOperator {
+    /// Checks the operator without a tolerance, so [BooleanOperator::ApproximatelyEqual] is the
+    /// same as [BooleanOperator::Equal]
     pub fn check<T: PartialEq + PartialOrd>(&self, lhs: T, rhs: T) -> bool {
         use BooleanOperator::*;
         match self {
-            Equal => lhs == rhs,
+            Equal | ApproximatelyEqual => lhs == rhs,
             NotEqual => lhs != rhs,
             GreaterThan => lhs > rhs,
             GreaterThanEqual => lhs >= rhs,
@@ -47,11 +57,12 @@ impl Display for BooleanOperator {
             GreaterThanEqual => ">=",
             LessThan => "<",
             LessThanEqual => "<=",
+            ApproximatelyEqual => "≈",
         })
     }
 }
 
-#[derive(Debug, PartialEq, Eq, Copy, Clone, serde::Serialize, serde::Deserialize)]
+#[derive(Debug, PartialEq, Hash, Eq, Copy, Clone, serde::Serialize, serde::Deserialize)]
 pub enum Operator {
     Plus,
     Minus,
@@ -61,14 +72,27 @@ pub enum Operator {
     BitwiseAnd,
     BitwiseOr,
     Xor,
+    /// The binomial coefficient (e.g. `5 choose 2`)
+    Choose,
     BitShiftLeft,
     BitShiftRight,
     Of,
     In,
+    /// The date of the exchange rates used for converting currencies (e.g.
+    /// `in EUR at {date 01.06.2023}`)
+    At,
+    /// Uses the semantics of the `modulo` setting
     Modulo,
+    /// The remainder of the truncated division, which has the sign of the dividend
+    Remainder,
+    /// Gives a value an uncertainty (e.g. `5 ± 0.1`)
+    PlusMinus,
     Call,
 }
 
+/// Nested ASTs (groups and arguments) are owned by their node. The engine moves them into their
+/// evaluation, since the node is replaced by the result anyway, so only the ASTs of custom
+/// functions, which are kept in the environment, are copied when they are evaluated.
 #[derive(Debug, PartialEq, Clone, serde::Serialize, serde::Deserialize)]
 pub enum AstNodeData {
     Literal(f64),
@@ -81,14 +105,18 @@ pub enum AstNodeData {
     Arguments(Vec<Vec<AstNode>>),
 }
 
-#[derive(PartialEq, Eq, Debug, Copy, Clone, serde::Serialize, serde::Deserialize)]
+#[derive(PartialEq, Hash, Eq, Debug, Copy, Clone, serde::Serialize, serde::Deserialize)]
 pub enum AstNodeModifier {
     Factorial,
     BitwiseNot,
     Percent,
+    /// `‰`, a thousandth
+    Permille,
     Minus,
     Plus,
     Power(i32),
+    /// Follows a percentage that is added to or subtracted from a value (e.g. `120 + 19% tax`)
+    Adjustment(Adjustment),
 }
 
 impl AstNodeModifier {
@@ -97,6 +125,10 @@ impl AstNodeModifier {
             | AstNodeModifier::Minus
             | AstNodeModifier::Plus)
     }
+
+    pub fn is_percentage(&self) -> bool {
+        matches!(self, AstNodeModifier::Percent | AstNodeModifier::Permille)
+    }
 }
 
 impl Display for AstNodeModifier {
@@ -105,9 +137,34 @@ impl Display for AstNodeModifier {
             AstNodeModifier::Factorial => write!(f, "!"),
             AstNodeModifier::BitwiseNot => write!(f, "!"),
             AstNodeModifier::Percent => write!(f, "%"),
+            AstNodeModifier::Permille => write!(f, "‰"),
             AstNodeModifier::Minus => write!(f, "-"),
             AstNodeModifier::Plus => write!(f, "+"),
             AstNodeModifier::Power(e) => write!(f, "^{e}"),
+            AstNodeModifier::Adjustment(adjustment) => write!(f, " {}", adjustment.name()),
+        }
+    }
+}
+
+/// What a percentage that is added to or subtracted from a value stands for. The percentage is
+/// relative to the value, so `120 + 19% tax` is `120 * 1.19` and `80 - 25% discount` is
+/// `80 * 0.75`.
+#[derive(PartialEq, Hash, Eq, Debug, Copy, Clone, serde::Serialize, serde::Deserialize)]
+pub enum Adjustment {
+    Tax,
+    Tip,
+    Discount,
+}
+
+impl Adjustment {
+    pub const ALL: [Adjustment; 3] = [Adjustment::Tax, Adjustment::Tip, Adjustment::Discount];
+
+    /// The word following the percentage
+    pub const fn name(&self) -> &'static str {
+        match self {
+            Adjustment::Tax => "tax",
+            Adjustment::Tip => "tip",
+            Adjustment::Discount => "discount",
         }
     }
 }
@@ -116,8 +173,15 @@ impl Display for AstNodeModifier {
 pub struct AstNode {
     pub data: AstNodeData,
     pub modifiers: Vec<AstNodeModifier>,
+    /// The maximum error of the value (e.g. `0.1` in `5 ± 0.1`), which is zero for exact values
+    #[serde(default)]
+    pub uncertainty: f64,
     pub unit: Option<Unit>,
     pub format: Format,
+    /// The format of the values this node was calculated from, which the result is shown in if the
+    /// calculation doesn't specify one (e.g. `mask & 0xf0` is shown in hex after `mask := 0xff0`)
+    #[serde(default)]
+    pub preferred_format: Option<Format>,
     pub range: SourceRange,
     #[serde(skip)]
     did_apply_modifiers: bool,
@@ -155,13 +219,58 @@ macro_rules! expect_int {
     }
 }
 
+/// Returns the smallest and biggest integer that can be an operand of integer operations. Besides
+/// fitting into `word_size` as either a signed or an unsigned integer, the integers have to be
+/// represented exactly by an f64.
+pub(crate) fn integer_range(word_size: WordSize) -> (f64, f64) {
+    match word_size.bits() {
+        Some(bits) => (
+            (-2f64.powi(bits as i32 - 1)).max(-MAX_EXACT_INTEGER),
+            (2f64.powi(bits as i32) - 1.0).min(MAX_EXACT_INTEGER),
+        ),
+        None => (-MAX_EXACT_INTEGER, MAX_EXACT_INTEGER),
+    }
+}
+
+/// The maximum error of `result`, which is `a` combined with `b` by `operator`, given the maximum
+/// errors of the operands. The errors are propagated linearly, so they add up in the worst case.
+fn propagated_uncertainty(operator: Operator, is_adjustment: bool, (a, a_error): (f64, f64), (b, b_error): (f64, f64), result: f64) -> f64 {
+    if operator == Operator::PlusMinus { return a_error + b.abs(); }
+    if a_error == 0.0 && b_error == 0.0 { return 0.0; }
+
+    match operator {
+        Operator::Plus | Operator::Minus if is_adjustment => {
+            let factor = if operator == Operator::Plus { 1.0 + b } else { 1.0 - b };
+            factor.abs() * a_error + a.abs() * b_error
+        }
+        Operator::Plus | Operator::Minus => a_error + b_error,
+        Operator::Multiply | Operator::Of => b.abs() * a_error + a.abs() * b_error,
+        Operator::Divide => a_error / b.abs() + a.abs() * b_error / (b * b),
+        Operator::Exponentiation => {
+            let base_error = (b * a.powf(b - 1.0)).abs() * a_error;
+            // The logarithm is only needed (and defined for negative bases) if the exponent is uncertain
+            if b_error == 0.0 { base_error } else { base_error + (result * a.ln()).abs() * b_error }
+        }
+        Operator::Modulo | Operator::Remainder => a_error + (a / b).trunc().abs() * b_error,
+        _ => a_error,
+    }
+}
+
+fn expect_integer_in_range(value: f64, word_size: WordSize, range: SourceRange) -> Result<()> {
+    let (min, max) = integer_range(word_size);
+    expect_args!((min..=max).contains(&value), IntegerOutOfRange(value, min, max), range);
+    Ok(())
+}
+
 impl AstNode {
     pub fn new(data: AstNodeData, range: SourceRange) -> AstNode {
         AstNode {
             data,
             modifiers: Vec::new(),
+            uncertainty: 0.0,
             unit: None,
             format: Format::Decimal,
+            preferred_format: None,
             range,
             did_apply_modifiers: false,
         }
@@ -171,18 +280,29 @@ impl AstNode {
         AstNode {
             data,
             modifiers: other.modifiers.clone(),
+            uncertainty: 0.0,
             unit: other.unit.clone(),
             format: other.format,
-            range: other.range.clone(),
+            preferred_format: other.preferred_format,
+            range: other.range,
             did_apply_modifiers: false,
         }
     }
 
-    pub fn apply(&mut self, operator: &Self, rhs: &mut Self, currencies: &Currencies) -> Result<()> {
-        self.apply_modifiers()?;
-        rhs.apply_modifiers()?;
-
+    /// Applies `operator` to this node and `rhs`, storing the result in this node. Conversions that
+    /// happen implicitly are reported in `warnings`. The operands of integer operations have to be
+    /// in the [integer_range] of the word size of `settings`. The modifiers of both nodes have to be
+    /// applied already.
+    pub fn apply(
+        &mut self,
+        operator: &Self,
+        rhs: &mut Self,
+        currencies: &Currencies,
+        settings: &Settings,
+        warnings: &mut Vec<Warning>,
+    ) -> Result<()> {
         let full_range = self.range.extend(rhs.range);
+        let (data_prefixes, word_size) = (settings.data_prefixes, settings.word_size);
 
         let lhs = match_ast_node!(AstNodeData::Literal(ref mut lhs), lhs, self);
         let op = match_ast_node!(AstNodeData::Operator(op), op, operator);
@@ -194,87 +314,169 @@ impl AstNode {
                 return Ok(());
             }
 
-            *lhs = convert(
-                self.unit.as_ref().unwrap(),
-                rhs_value,
-                *lhs,
-                currencies,
-                full_range,
-            )?;
+            let src_unit = self.unit.as_ref().unwrap();
+            let converted = convert(src_unit, rhs_value, *lhs, currencies, data_prefixes, full_range)?;
+            // Conversions aren't always proportional (e.g. of temperatures), so the error is converted
+            // as the difference to the converted value
+            if self.uncertainty != 0.0 {
+                let upper = convert(src_unit, rhs_value, *lhs + self.uncertainty, currencies, data_prefixes, full_range)?;
+                self.uncertainty = (upper - converted).abs();
+            }
+            *lhs = converted;
             self.unit = Some(rhs_value.clone());
             return Ok(());
         }
 
         let mut rhs_value = match_ast_node!(AstNodeData::Literal(rhs), rhs, rhs);
+        let mut rhs_uncertainty = rhs.uncertainty;
 
         self.format = rhs.format;
+        self.preferred_format = rhs.preferred_format.or(self.preferred_format);
 
         if rhs.unit.is_some() && self.unit.is_none() {
             self.unit = rhs.unit.clone();
-        } else if rhs.unit.is_some() && rhs.unit != self.unit {
-            if let Ok(rhs) = convert(
-                rhs.unit.as_ref().unwrap(),
-                self.unit.as_ref().unwrap(),
-                rhs_value,
-                currencies,
-                full_range,
-            ) {
-                rhs_value = rhs;
-            } else {
-                let rhs_unit = rhs.unit.take().unwrap();
-                let lhs_unit = self.unit.as_mut().unwrap();
-                match op {
-                    Operator::Multiply => lhs_unit.push_unit(rhs_unit),
-                    Operator::Divide => {
-                        if let Unit::Fraction(rhs_num, rhs_denom) = rhs_unit {
-                            // Multiply by the inverse of the fraction
-                            lhs_unit.push_unit(Unit::Fraction(rhs_denom, rhs_num));
-                        } else {
-                            *lhs_unit = Unit::Fraction(Box::new(lhs_unit.clone()), Box::new(rhs_unit));
+        } else if rhs.unit.is_some() {
+            if rhs.unit != self.unit {
+                if let Ok(converted) = convert(
+                    rhs.unit.as_ref().unwrap(),
+                    self.unit.as_ref().unwrap(),
+                    rhs_value,
+                    currencies,
+                    data_prefixes,
+                    full_range,
+                ) {
+                    let from = rhs.unit.as_ref().unwrap().format(false, false);
+                    let to = self.unit.as_ref().unwrap().format(false, false);
+                    // The units are also different if they are only at different positions. The parts of
+                    // mixed measures (e.g. `5ft 11in`), whose additions have the range of the next part,
+                    // are converted implicitly.
+                    let is_mixed_measure = op == Operator::Plus && operator.range == rhs.range;
+                    if from != to && op != Operator::PlusMinus && !is_mixed_measure {
+                        warnings.push(WarningType::UnitConverted(from, to).with(rhs.range));
+                    }
+                    if rhs_uncertainty != 0.0 {
+                        rhs_uncertainty = convert(
+                            rhs.unit.as_ref().unwrap(),
+                            self.unit.as_ref().unwrap(),
+                            rhs_value + rhs_uncertainty,
+                            currencies,
+                            data_prefixes,
+                            full_range,
+                        ).map_or(f64::NAN, |upper| (upper - converted).abs());
+                    }
+                    rhs_value = converted;
+                    rhs.unit = self.unit.clone();
+                } else if !matches!(op, Operator::Multiply | Operator::Divide) {
+                    let rhs_unit = rhs.unit.as_ref().unwrap();
+                    let lhs_unit = self.unit.as_ref().unwrap();
+                    match (Dimension::of(lhs_unit), Dimension::of(rhs_unit)) {
+                        (Some(lhs_dimension), Some(rhs_dimension)) if lhs_dimension != rhs_dimension => {
+                            error!(IncompatibleDimensions(lhs_dimension.to_string(), rhs_dimension.to_string()): full_range)
                         }
+                        _ => error!(UnknownConversion(rhs_unit.format(false, false), lhs_unit.format(false, false)): full_range),
                     }
-                    _ => error!(UnknownConversion(rhs_unit.format(false, false), lhs_unit.format(false, false)): full_range),
                 }
+            }
+
+            if matches!(op, Operator::Multiply | Operator::Divide) {
+                let rhs_unit = rhs.unit.take().unwrap();
+                let lhs_unit = self.unit.as_mut().unwrap();
+                if op == Operator::Multiply {
+                    lhs_unit.push_unit(rhs_unit);
+                } else if let Unit::Fraction(rhs_num, rhs_denom) = rhs_unit {
+                    // Multiply by the inverse of the fraction
+                    lhs_unit.push_unit(Unit::Fraction(rhs_denom, rhs_num));
+                } else {
+                    *lhs_unit = Unit::Fraction(Box::new(lhs_unit.clone()), Box::new(rhs_unit));
+                }
+
+                // Units of the same dimension cancel each other out (e.g. `km/h * min` is `km`)
+                let factor = lhs_unit.unify_dimensions(currencies, data_prefixes);
+                *lhs *= factor;
+                self.uncertainty *= factor;
 
                 if !lhs_unit.simplify() { self.unit = None; }
             }
         }
 
+        let operands = ((*lhs, self.uncertainty), (rhs_value, rhs_uncertainty));
         match op {
             Operator::Multiply => *lhs *= rhs_value,
             Operator::Divide => {
                 expect!(rhs_value != 0.0, DivideByZero, rhs.range);
                 *lhs /= rhs_value;
             }
+            Operator::Plus | Operator::Minus if rhs.adjustment().is_some() => {
+                *lhs *= if op == Operator::Plus { 1.0 + rhs_value } else { 1.0 - rhs_value };
+            }
             Operator::Plus => *lhs += rhs_value,
             Operator::Minus => *lhs -= rhs_value,
             Operator::Exponentiation => *lhs = lhs.powf(rhs_value),
             Operator::BitwiseAnd | Operator::BitwiseOr | Operator::Xor | Operator::BitShiftLeft | Operator::BitShiftRight => {
+                expect!(self.uncertainty == 0.0 && rhs_uncertainty == 0.0, UncertainInteger, full_range);
                 expect_int!(lhs, self.range, op);
                 expect_int!(rhs_value, self.range, op);
+                expect_integer_in_range(*lhs, word_size, self.range)?;
+                expect_integer_in_range(rhs_value, word_size, rhs.range)?;
 
                 match op {
                     Operator::BitwiseAnd => *lhs = (*lhs as i64 & rhs_value as i64) as f64,
                     Operator::BitwiseOr => *lhs = (*lhs as i64 | rhs_value as i64) as f64,
                     Operator::Xor => *lhs = (*lhs as i64 ^ rhs_value as i64) as f64,
-                    Operator::BitShiftLeft => *lhs = ((*lhs as i64) << (rhs_value as i64)) as f64,
-                    Operator::BitShiftRight => *lhs = ((*lhs as i64) >> (rhs_value as i64)) as f64,
+                    Operator::BitShiftLeft | Operator::BitShiftRight => {
+                        let Ok(shift) = u32::try_from(rhs_value as i64) else {
+                            error!(NotU32(rhs_value as i32): rhs.range);
+                        };
+                        let value = *lhs as i64;
+                        // Shifting by more than the number of bits shifts out all bits
+                        *lhs = if op == Operator::BitShiftLeft {
+                            value.checked_shl(shift).unwrap_or(0)
+                        } else {
+                            value.checked_shr(shift).unwrap_or(if value < 0 { -1 } else { 0 })
+                        } as f64;
+                    }
                     _ => unreachable!(),
                 }
             }
+            Operator::Choose => {
+                expect!(self.uncertainty == 0.0 && rhs_uncertainty == 0.0, UncertainInteger, full_range);
+                expect_int!(lhs, self.range, op);
+                expect_int!(rhs_value, rhs.range, op);
+                expect_args!((0.0..=MAX_EXACT_INTEGER).contains(lhs), IntegerOutOfRange(*lhs, 0.0, MAX_EXACT_INTEGER), self.range);
+                expect_args!((0.0..=MAX_EXACT_INTEGER).contains(&rhs_value), IntegerOutOfRange(rhs_value, 0.0, MAX_EXACT_INTEGER), rhs.range);
+                *lhs = number_theory::combinations(*lhs as u64, rhs_value as u64);
+            }
             Operator::Of => {
-                expect!(self.modifiers.contains(&AstNodeModifier::Percent),
+                expect!(self.modifiers.iter().any(AstNodeModifier::is_percentage),
                     ExpectedPercentage, self.range);
                 *lhs *= rhs_value;
             }
-            Operator::Modulo => *lhs %= rhs_value,
-            Operator::In | Operator::Call => {}
+            Operator::Modulo | Operator::Remainder => {
+                if lhs.fract() == 0.0 && rhs_value.fract() == 0.0 {
+                    expect_integer_in_range(*lhs, word_size, self.range)?;
+                    expect_integer_in_range(rhs_value, word_size, rhs.range)?;
+                }
+                let mode = if op == Operator::Modulo { settings.modulo } else { ModuloMode::Truncated };
+                *lhs = mode.apply(*lhs, rhs_value);
+            }
+            Operator::PlusMinus | Operator::In | Operator::At | Operator::Call => {}
         }
 
+        self.uncertainty = propagated_uncertainty(op, rhs.adjustment().is_some(), operands.0, operands.1, *lhs);
         Ok(())
     }
 
-    pub fn apply_modifiers(&mut self) -> Result<()> {
+    /// What this node stands for if it is a percentage like `19% tax`
+    pub fn adjustment(&self) -> Option<Adjustment> {
+        self.modifiers.iter().find_map(|m| match m {
+            AstNodeModifier::Adjustment(adjustment) => Some(*adjustment),
+            _ => None,
+        })
+    }
+
+    /// Applies the modifiers to the value of this node. Factorials can only be calculated for
+    /// numbers up to `max_factorial`.
+    pub fn apply_modifiers(&mut self, max_factorial: u64) -> Result<()> {
         if self.modifiers.is_empty() || self.did_apply_modifiers {
             return Ok(());
         }
@@ -283,10 +485,15 @@ impl AstNode {
         for m in &self.modifiers {
             match m {
                 AstNodeModifier::Factorial => {
+                    expect!(self.uncertainty == 0.0, UncertainInteger, self.range);
                     expect_int!(value, self.range, m);
+                    if value.abs() > max_factorial as f64 {
+                        return Err(ErrorType::LimitExceeded(format!("factorial argument of {max_factorial}")).with(self.range));
+                    }
                     *value = math::factorial(*value);
                 }
                 AstNodeModifier::BitwiseNot => {
+                    expect!(self.uncertainty == 0.0, UncertainInteger, self.range);
                     expect_int!(value, self.range, m);
                     let inverted = format!("{:b}", *value as i64)
                         .chars()
@@ -301,10 +508,21 @@ impl AstNode {
                         Err(_) => unreachable!(),
                     } as f64;
                 }
-                AstNodeModifier::Percent => *value /= 100.0,
+                AstNodeModifier::Percent => {
+                    *value /= 100.0;
+                    self.uncertainty /= 100.0;
+                }
+                AstNodeModifier::Permille => {
+                    *value /= 1000.0;
+                    self.uncertainty /= 1000.0;
+                }
                 AstNodeModifier::Minus => *value *= -1.0,
                 AstNodeModifier::Plus => *value *= 1.0,
-                AstNodeModifier::Power(e) => *value *= 10f64.powi(*e),
+                AstNodeModifier::Power(e) => {
+                    *value *= 10f64.powi(*e);
+                    self.uncertainty *= 10f64.powi(*e);
+                }
+                AstNodeModifier::Adjustment(_) => {}
             }
         }
 
@@ -7,12 +7,12 @@
 use std::cmp::Ordering;
 use std::fmt::Debug;
 
-use chrono::{Duration, Local, NaiveDate};
+use chrono::{DateTime, Duration, FixedOffset, Local, NaiveDate, NaiveDateTime, NaiveTime, Utc};
 
-use crate::{Context, DateFormat, error, NumberValue, range, Settings};
-use crate::astgen::ast::{AstNode, AstNodeData, Operator};
-use crate::common::{ErrorType, Result, SourceRange};
-use crate::engine::{Engine, Value};
+use crate::{Capability, Context, DataPrefixes, DateFormat, error, NumberValue, range, Settings, TimeZone};
+use crate::astgen::ast::{AstNode, AstNodeData, BooleanOperator, Operator};
+use crate::common::{ErrorType, math, Result, SourceRange};
+use crate::engine::{DECIMAL_PLACES, Engine, Format, Value};
 use crate::environment::currencies::Currencies;
 use crate::environment::units;
 use crate::environment::units::Unit;
@@ -38,7 +38,13 @@ impl ObjectArgument {
 #[derive(Debug, PartialEq, PartialOrd, Clone, serde::Serialize, serde::Deserialize)]
 pub enum CalculatorObject {
     Date(DateObject),
+    DateTime(DateTimeObject),
+    TimeZone(TimeZone),
     Vector(Vector),
+    Interval(Interval),
+    MixedMeasure(MixedMeasure),
+    Function(FunctionReference),
+    String(StringObject),
 }
 
 impl CalculatorObject {
@@ -50,39 +56,74 @@ impl CalculatorObject {
     ) -> Result<Self> {
         match name.as_str() {
             "date" => Ok(Self::Date(DateObject::parse(args, context, range)?)),
+            "datetime" => Ok(Self::DateTime(DateTimeObject::parse(args, context, range)?)),
+            "timestamp" => Ok(Self::DateTime(DateTimeObject::parse_timestamp(args, context, range)?)),
             _ => Err(ErrorType::UnknownObject(name).with(name_range))
         }
     }
 
     pub fn is_valid_object(name: &str) -> bool {
-        matches!(name, "date")
+        matches!(name, "date" | "datetime" | "timestamp")
+    }
+
+    /// The name of the kind of object, as shown in the typed AST debug output
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Self::Date(_) => "date",
+            Self::DateTime(_) => "date time",
+            Self::TimeZone(_) => "time zone",
+            Self::Vector(_) => "vector",
+            Self::Interval(_) => "interval",
+            Self::MixedMeasure(_) => "mixed measure",
+            Self::Function(_) => "function",
+            Self::String(_) => "string",
+        }
     }
 
     pub fn is_callable(&self) -> bool {
         match self {
-            Self::Date(_) => false,
-            Self::Vector(_) => true,
+            Self::Date(_) | Self::DateTime(_) | Self::TimeZone(_) | Self::Interval(_) | Self::MixedMeasure(_)
+            | Self::String(_) => false,
+            Self::Vector(_) | Self::Function(_) => true,
         }
     }
 
     pub fn apply(&self, self_range: SourceRange, op: (Operator, SourceRange), other: &AstNode, self_in_rhs: bool) -> Result<AstNode> {
         match self {
             Self::Date(date) => date.apply(self_range, op, other, self_in_rhs),
+            Self::DateTime(datetime) => datetime.apply(self_range, op, other, self_in_rhs),
+            Self::TimeZone(zone) => zone.apply(self_range, op, other, self_in_rhs),
             Self::Vector(vec) => vec.apply(self_range, op, other, self_in_rhs),
+            Self::Interval(interval) => interval.apply(self_range, op, other, self_in_rhs),
+            Self::MixedMeasure(measure) => measure.apply(self_range, op, other, self_in_rhs),
+            Self::Function(function) => function.apply(self_range, op, other, self_in_rhs),
+            Self::String(string) => string.apply(self_range, op, other, self_in_rhs),
         }
     }
 
     pub fn call(&self, self_range: SourceRange, args: &[(NumberValue, SourceRange)], args_range: SourceRange) -> Result<AstNode> {
         match self {
             Self::Date(date) => date.call(self_range, args, args_range),
+            Self::DateTime(datetime) => datetime.call(self_range, args, args_range),
+            Self::TimeZone(zone) => zone.call(self_range, args, args_range),
             Self::Vector(vec) => vec.call(self_range, args, args_range),
+            Self::Interval(interval) => interval.call(self_range, args, args_range),
+            Self::MixedMeasure(measure) => measure.call(self_range, args, args_range),
+            Self::Function(function) => function.call(self_range, args, args_range),
+            Self::String(string) => string.call(self_range, args, args_range),
         }
     }
 
     pub fn to_string(&self, settings: &Settings) -> String {
         match self {
             Self::Date(date) => date.to_string(settings),
+            Self::DateTime(datetime) => datetime.to_string(settings),
+            Self::TimeZone(zone) => Object::to_string(zone, settings),
             Self::Vector(vec) => vec.to_string(settings),
+            Self::Interval(interval) => interval.to_string(settings),
+            Self::MixedMeasure(measure) => measure.to_string(settings),
+            Self::Function(function) => function.to_string(settings),
+            Self::String(string) => string.to_string(settings),
         }
     }
 }
@@ -102,14 +143,31 @@ pub struct DateObject {
     pub(crate) date: NaiveDate,
 }
 
+/// The format string of dates with the format and delimiter of the settings
+fn date_format(settings: &Settings) -> String {
+    match settings.date.format {
+        DateFormat::Dmy => format!("%d{d}%m{d}%Y", d = settings.date.delimiter),
+        DateFormat::Mdy => format!("%m{d}%d{d}%Y", d = settings.date.delimiter),
+        DateFormat::Ymd => format!("%Y{d}%m{d}%d", d = settings.date.delimiter),
+    }
+}
+
+fn as_nanoseconds(unit: Option<&Unit>, n: f64, range: SourceRange) -> Result<f64> {
+    unit.and_then(|unit| {
+        units::convert(
+            unit,
+            &Unit::from("ns"),
+            n,
+            &Currencies::none(),
+            DataPrefixes::default(),
+            range,
+        ).ok()
+    }).map_or_else(|| Err(ErrorType::ExpectedTimeValue.with(range)), Ok)
+}
+
 impl Object for DateObject {
     fn to_string(&self, settings: &Settings) -> String {
-        let fmt = match settings.date.format {
-            DateFormat::Dmy => format!("%d{d}%m{d}%Y", d = settings.date.delimiter),
-            DateFormat::Mdy => format!("%m{d}%d{d}%Y", d = settings.date.delimiter),
-            DateFormat::Ymd => format!("%Y{d}%m{d}%d", d = settings.date.delimiter),
-        };
-        self.date.format(&fmt).to_string()
+        self.date.format(&date_format(settings)).to_string()
     }
 
     fn parse(
@@ -133,6 +191,8 @@ impl Object for DateObject {
                     error!(UnexpectedElements: given_args[1].range().extend(*given_args.last().unwrap().range()));
                 }
 
+                context.borrow().capabilities.require(Capability::Clock)
+                    .map_err(|ty| ty.with(*range))?;
                 return Ok(Self { date: Local::now().date_naive() });
             }
         }
@@ -244,18 +304,6 @@ impl Object for DateObject {
     }
 
     fn apply(&self, self_range: SourceRange, op: (Operator, SourceRange), other: &AstNode, self_is_rhs: bool) -> Result<AstNode> {
-        fn as_nanoseconds(unit: Option<&Unit>, n: f64, range: SourceRange) -> Result<f64> {
-            unit.and_then(|unit| {
-                units::convert(
-                    unit,
-                    &Unit::from("ns"),
-                    n,
-                    &Currencies::none(),
-                    range,
-                ).ok()
-            }).map_or_else(|| Err(ErrorType::ExpectedTimeValue.with(range)), Ok)
-        }
-
         match op.0 {
             Operator::Plus => match other.data {
                 AstNodeData::Literal(n) => {
@@ -295,6 +343,148 @@ impl Object for DateObject {
     fn call(&self, _: SourceRange, _: &[(NumberValue, SourceRange)], _: SourceRange) -> Result<AstNode> { unreachable!(); }
 }
 
+/// A point in time, shown in a time zone
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, serde::Serialize, serde::Deserialize)]
+pub struct DateTimeObject {
+    pub(crate) datetime: DateTime<FixedOffset>,
+}
+
+impl DateTimeObject {
+    /// Creates the object from a time in UTC, which is shown in `zone`
+    pub(crate) fn new(utc: NaiveDateTime, zone: TimeZone) -> Self {
+        Self { datetime: DateTime::from_utc(utc, zone.offset_at(utc)) }
+    }
+
+    /// Parses a unix timestamp in seconds (e.g. `{timestamp 1700000000}`)
+    pub(crate) fn parse_timestamp(args: Vec<ObjectArgument>, context: Context, full_range: SourceRange) -> Result<Self> {
+        match args.len() {
+            0 => error!(ExpectedElements: full_range),
+            1 => {}
+            _ => error!(UnexpectedElements: args[1].range().extend(*args.last().unwrap().range())),
+        }
+
+        let (seconds, range) = match &args[0] {
+            ObjectArgument::String(s, range) => {
+                let seconds = s.parse::<f64>().map_err(|err| ErrorType::InvalidNumber(err.to_string()).with(*range))?;
+                (seconds, *range)
+            }
+            ObjectArgument::Ast(ast, range) => (Engine::evaluate_to_number(ast.clone(), context.clone())?.number, *range),
+        };
+
+        let nanoseconds = (seconds.fract().rem_euclid(1.0) * 1e9).round() as u32;
+        let Some(utc) = NaiveDateTime::from_timestamp_opt(seconds.floor() as i64, nanoseconds.min(999_999_999)) else {
+            error!(DateTooBig: range);
+        };
+        let zone = context.borrow().settings.date.timezone;
+        Ok(Self::new(utc, zone))
+    }
+
+    /// The seconds since the unix epoch
+    pub(crate) fn timestamp(&self) -> f64 {
+        self.datetime.timestamp() as f64 + self.datetime.timestamp_subsec_nanos() as f64 / 1e9
+    }
+}
+
+impl Object for DateTimeObject {
+    fn to_string(&self, settings: &Settings) -> String {
+        let zone = TimeZone::Utc(self.datetime.offset().local_minus_utc() / 60);
+        format!("{} {zone}", self.datetime.format(&format!("{} %H:%M:%S", date_format(settings))))
+    }
+
+    fn parse(
+        mut given_args: Vec<ObjectArgument>,
+        context: Context,
+        full_range: SourceRange,
+    ) -> Result<Self> {
+        let zone = context.borrow().settings.date.timezone;
+        if let Some(ObjectArgument::String(s, range)) = given_args.first() {
+            if s.trim().eq_ignore_ascii_case("now") {
+                if given_args.len() > 1 {
+                    error!(UnexpectedElements: given_args[1].range().extend(*given_args.last().unwrap().range()));
+                }
+                context.borrow().capabilities.require(Capability::Clock)
+                    .map_err(|ty| ty.with(*range))?;
+                return Ok(Self::new(Utc::now().naive_utc(), zone));
+            }
+        }
+
+        // The time is optional and follows the date (e.g. `{datetime 14.11.2023 22:13}`)
+        let mut time = NaiveTime::default();
+        if let Some(ObjectArgument::String(s, range)) = given_args.last().filter(|_| given_args.len() > 1) {
+            if s.contains(':') {
+                let Ok(parsed) = NaiveTime::parse_from_str(s, "%H:%M:%S")
+                    .or_else(|_| NaiveTime::parse_from_str(s, "%H:%M")) else {
+                    error!(InvalidTime: *range);
+                };
+                time = parsed;
+                given_args.pop();
+            }
+        }
+
+        let date = DateObject::parse(given_args, context, full_range)?.date;
+        let Some(utc) = zone.to_utc(date.and_time(time)) else {
+            error!(InvalidTime: full_range);
+        };
+        Ok(Self::new(utc, zone))
+    }
+
+    fn apply(&self, self_range: SourceRange, op: (Operator, SourceRange), other: &AstNode, self_is_rhs: bool) -> Result<AstNode> {
+        let full_range = self_range.extend(other.range);
+        let new_node = |datetime| AstNode::new(AstNodeData::Object(CalculatorObject::DateTime(Self { datetime })), full_range);
+
+        match (op.0, &other.data) {
+            (Operator::Plus | Operator::Minus, AstNodeData::Literal(n)) => {
+                if op.0 == Operator::Minus && self_is_rhs {
+                    return Err(ErrorType::WrongOrder.with_multiple(vec![other.range, self_range]));
+                }
+
+                let n = Duration::nanoseconds(as_nanoseconds(other.unit.as_ref(), *n, other.range)? as i64);
+                let datetime = if op.0 == Operator::Plus {
+                    self.datetime.checked_add_signed(n)
+                } else {
+                    self.datetime.checked_sub_signed(n)
+                };
+                let Some(datetime) = datetime else { error!(DateTooBig: full_range); };
+                Ok(new_node(datetime))
+            }
+            (Operator::Minus, AstNodeData::Object(CalculatorObject::DateTime(other_datetime))) => {
+                let seconds = self.timestamp() - other_datetime.timestamp();
+                let seconds = if self_is_rhs { -seconds } else { seconds };
+                let mut result = AstNode::new(AstNodeData::Literal(seconds), full_range);
+                result.unit = Some(Unit::from("s"));
+                Ok(result)
+            }
+            (Operator::In, AstNodeData::Object(CalculatorObject::TimeZone(zone))) if !self_is_rhs => {
+                Ok(new_node(Self::new(self.datetime.naive_utc(), *zone).datetime))
+            }
+            (Operator::Plus | Operator::Minus | Operator::In, _) => Err(ErrorType::InvalidSide.with(other.range)),
+            _ => Err(ErrorType::UnsupportedOperation.with(op.1)),
+        }
+    }
+
+    fn call(&self, _: SourceRange, _: &[(NumberValue, SourceRange)], _: SourceRange) -> Result<AstNode> { unreachable!(); }
+}
+
+/// The target of a conversion into a time zone (e.g. `{datetime now} in UTC+2`)
+impl Object for TimeZone {
+    fn to_string(&self, _: &Settings) -> String {
+        format!("{self}")
+    }
+
+    fn parse(_: Vec<ObjectArgument>, _: Context, _: SourceRange) -> Result<Self> {
+        // This object cannot be constructed using the object syntax
+        unreachable!()
+    }
+
+    fn apply(&self, _: SourceRange, op: (Operator, SourceRange), other: &AstNode, _: bool) -> Result<AstNode> {
+        // Date times are always on the left side of conversions, so they handle them themselves
+        if op.0 != Operator::In { error!(UnsupportedOperation: op.1); }
+        error!(ExpectedDate: other.range);
+    }
+
+    fn call(&self, _: SourceRange, _: &[(NumberValue, SourceRange)], _: SourceRange) -> Result<AstNode> { unreachable!(); }
+}
+
 #[derive(Debug, PartialEq, PartialOrd, Clone, serde::Serialize, serde::Deserialize)]
 pub struct Vector {
     pub(crate) numbers: Vec<f64>,
@@ -361,9 +551,292 @@ impl Object for Vector {
         let (number, range) = &args[0];
         if number.number.fract() != 0.0 { error!(ExpectedInteger(number.number): *range); }
         if number.number.is_sign_negative() { return Ok(AstNode::new(AstNodeData::Literal(f64::NAN), self_range)); }
-        return match self.numbers.get(number.number as usize) {
+        match self.numbers.get(number.number as usize) {
             Some(n) => Ok(AstNode::new(AstNodeData::Literal(*n), self_range)),
             None => Ok(AstNode::new(AstNodeData::Literal(f64::NAN), self_range)),
+        }
+    }
+}
+
+/// All values between two bounds (e.g. `[1, 2]`), which are combined so that the result contains
+/// every possible result of the values
+#[derive(Debug, PartialEq, PartialOrd, Clone, Copy, serde::Serialize, serde::Deserialize)]
+pub struct Interval {
+    pub(crate) lower: f64,
+    pub(crate) upper: f64,
+}
+
+impl Interval {
+    /// The standard functions that can be applied to intervals
+    const FUNCTIONS: [&'static str; 14] = [
+        "sin", "cos", "tan", "cot", "asin", "acos", "atan", "ln", "sqrt", "cbrt", "abs", "floor", "ceil", "round",
+    ];
+
+    pub(crate) fn new(lower: f64, upper: f64) -> std::result::Result<Self, ErrorType> {
+        if lower > upper { return Err(ErrorType::InvalidInterval); }
+        Ok(Self { lower, upper })
+    }
+
+    /// The smallest interval containing all `values`
+    fn hull(values: &[f64]) -> Self {
+        Self {
+            lower: values.iter().copied().fold(f64::INFINITY, f64::min),
+            upper: values.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+        }
+    }
+
+    fn contains(&self, n: f64) -> bool {
+        self.lower <= n && n <= self.upper
+    }
+
+    /// Numbers without a unit are intervals with a single value
+    pub(crate) fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::Number(number) if number.unit.is_none() => Some(Self { lower: number.number, upper: number.number }),
+            Value::Object(CalculatorObject::Interval(interval)) => Some(*interval),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn supports_function(name: &str) -> bool {
+        Self::FUNCTIONS.contains(&name)
+    }
+
+    /// Applies the standard function `name` to all values of the interval. Besides at the bounds,
+    /// `f` is evaluated at the extrema of the function inside the interval, since the function is
+    /// only monotonic between them.
+    pub(crate) fn map(&self, name: &str, f: impl Fn(f64) -> std::result::Result<f64, ErrorType>) -> std::result::Result<Self, ErrorType> {
+        use std::f64::consts::{FRAC_PI_2, PI};
+
+        // The first extremum or pole and the distance to the next one
+        let points = |offset: f64, period: f64| {
+            let first = ((self.lower - offset) / period).ceil() * period + offset;
+            // After a maximum and a minimum, the others don't change the result
+            [first, first + period].into_iter().filter(|x| self.contains(*x)).collect::<Vec<_>>()
         };
+        let extrema = match name {
+            "sin" => points(FRAC_PI_2, PI),
+            "cos" => points(0.0, PI),
+            "abs" if self.contains(0.0) => vec![0.0],
+            _ => vec![],
+        };
+        let has_pole = match name {
+            "tan" => !points(FRAC_PI_2, PI).is_empty(),
+            "cot" => !points(0.0, PI).is_empty(),
+            _ => false,
+        };
+        if has_pole { return Err(ErrorType::UndefinedInInterval); }
+
+        let mut values = vec![f(self.lower)?, f(self.upper)?];
+        for x in extrema { values.push(f(x)?); }
+        // The bounds are outside of the function's domain (e.g. `sqrt([-1, 4])`)
+        if values.iter().any(|n| !n.is_finite()) { return Err(ErrorType::UndefinedInInterval); }
+        Ok(Self::hull(&values))
+    }
+
+    /// Whether `operator` holds for all values of both intervals. Intervals are equal if their
+    /// bounds are equal.
+    pub(crate) fn check(&self, other: &Self, operator: BooleanOperator) -> bool {
+        match operator {
+            BooleanOperator::Equal | BooleanOperator::ApproximatelyEqual => self == other,
+            BooleanOperator::NotEqual => self != other,
+            BooleanOperator::GreaterThan => self.lower > other.upper,
+            BooleanOperator::GreaterThanEqual => self.lower >= other.upper,
+            BooleanOperator::LessThan => self.upper < other.lower,
+            BooleanOperator::LessThanEqual => self.upper <= other.lower,
+        }
+    }
+
+    fn pow(&self, exponent: &Self) -> std::result::Result<Self, ErrorType> {
+        let is_integer_exponent = exponent.lower == exponent.upper && exponent.lower.fract() == 0.0;
+        if is_integer_exponent {
+            let n = exponent.lower;
+            let mut values = vec![self.lower.powf(n), self.upper.powf(n)];
+            if self.contains(0.0) {
+                if n < 0.0 { return Err(ErrorType::DivideByZero); }
+                values.push(0f64.powf(n));
+            }
+            return Ok(Self::hull(&values));
+        }
+
+        // Powers of non-negative bases are monotonic in both the base and the exponent
+        if self.lower < 0.0 { return Err(ErrorType::UndefinedInInterval); }
+        Ok(Self::hull(&[
+            self.lower.powf(exponent.lower), self.lower.powf(exponent.upper),
+            self.upper.powf(exponent.lower), self.upper.powf(exponent.upper),
+        ]))
+    }
+}
+
+impl Object for Interval {
+    fn to_string(&self, settings: &Settings) -> String {
+        let format = |n: f64| Format::Decimal.format(n, &settings.output, false);
+        format!("[{}, {}]", format(self.lower), format(self.upper))
+    }
+
+    fn parse(_: Vec<ObjectArgument>, _: Context, _: SourceRange) -> Result<Self> {
+        // This object cannot be constructed using the object syntax
+        unreachable!()
     }
+
+    fn apply(&self, self_range: SourceRange, op: (Operator, SourceRange), other: &AstNode, self_is_rhs: bool) -> Result<AstNode> {
+        let other_interval = match &other.data {
+            AstNodeData::Literal(n) => Self { lower: *n, upper: *n },
+            AstNodeData::Object(CalculatorObject::Interval(interval)) => *interval,
+            _ => error!(ExpectedNumber: other.range),
+        };
+        let (lhs, rhs) = if self_is_rhs { (other_interval, *self) } else { (*self, other_interval) };
+        let full_range = self_range.extend(other.range);
+
+        let result = match op.0 {
+            Operator::Plus => Self { lower: lhs.lower + rhs.lower, upper: lhs.upper + rhs.upper },
+            Operator::Minus => Self { lower: lhs.lower - rhs.upper, upper: lhs.upper - rhs.lower },
+            Operator::Multiply => Self::hull(&[
+                lhs.lower * rhs.lower, lhs.lower * rhs.upper,
+                lhs.upper * rhs.lower, lhs.upper * rhs.upper,
+            ]),
+            Operator::Divide => {
+                if rhs.contains(0.0) { error!(DivideByZero: full_range); }
+                Self::hull(&[
+                    lhs.lower / rhs.lower, lhs.lower / rhs.upper,
+                    lhs.upper / rhs.lower, lhs.upper / rhs.upper,
+                ])
+            }
+            Operator::Exponentiation => lhs.pow(&rhs).map_err(|ty| ty.with(full_range))?,
+            _ => error!(UnsupportedOperation: op.1),
+        };
+        Ok(AstNode::new(AstNodeData::Object(CalculatorObject::Interval(result)), self_range))
+    }
+
+    fn call(&self, _: SourceRange, _: &[(NumberValue, SourceRange)], _: SourceRange) -> Result<AstNode> { unreachable!(); }
+}
+
+/// A value split up into multiple units of the same dimension (e.g. `5ft 10.9in`), which is the
+/// result of converting into units like `ft_in`
+#[derive(Debug, PartialEq, PartialOrd, Clone, serde::Serialize, serde::Deserialize)]
+pub struct MixedMeasure {
+    /// The units from the largest to the smallest with the amount of each
+    pub(crate) parts: Vec<(f64, String)>,
+}
+
+impl MixedMeasure {
+    /// The target of a conversion into `units`, which are joined with underscores (e.g. `ft_in`).
+    /// Returns [None] if they aren't at least two units.
+    pub(crate) fn parse_units(units: &str) -> Option<Self> {
+        let parts = units.split('_')
+            .map(|unit| if unit == "in" { "inch" } else { unit })
+            .map(|unit| (!unit.is_empty() && units::is_unit_with_prefix(unit)).then(|| (0.0, unit.to_string())))
+            .collect::<Option<Vec<_>>>()?;
+        if parts.len() < 2 { return None; }
+        Some(Self { parts })
+    }
+}
+
+impl Object for MixedMeasure {
+    fn to_string(&self, settings: &Settings) -> String {
+        let sign = if self.parts.iter().any(|(n, _)| *n < 0.0) { "-" } else { "" };
+        let parts = self.parts.iter()
+            .map(|(n, unit)| {
+                let unit = if unit == "inch" { "in" } else { unit };
+                format!("{}{unit}", Format::Decimal.format(n.abs(), &settings.output, false))
+            })
+            .collect::<Vec<_>>();
+        format!("{sign}{}", parts.join(" "))
+    }
+
+    fn parse(_: Vec<ObjectArgument>, _: Context, _: SourceRange) -> Result<Self> {
+        // This object cannot be constructed using the object syntax
+        unreachable!()
+    }
+
+    fn apply(&self, self_range: SourceRange, op: (Operator, SourceRange), other: &AstNode, self_is_rhs: bool) -> Result<AstNode> {
+        if op.0 != Operator::In || !self_is_rhs { error!(UnsupportedOperation: op.1); }
+        let AstNodeData::Literal(n) = other.data else { error!(ExpectedNumber: other.range); };
+        let Some(unit) = &other.unit else { error!(ExpectedUnit: other.range); };
+
+        let full_range = other.range.extend(self_range);
+        let convert = |src: &Unit, dst: &Unit, n: f64| units::convert(
+            src,
+            dst,
+            n,
+            &Currencies::none(),
+            DataPrefixes::default(),
+            full_range,
+        );
+
+        let smallest = Unit::from(self.parts.last().unwrap().1.as_str());
+        let mut remaining = convert(unit, &smallest, n.abs())?;
+        let mut parts = vec![];
+        for (i, (_, name)) in self.parts.iter().enumerate() {
+            let amount = if i == self.parts.len() - 1 {
+                remaining
+            } else {
+                let size = convert(&Unit::from(name.as_str()), &smallest, 1.0)?;
+                // Rounding prevents e.g. `5ft 12in` for values that are slightly less than 6ft
+                let amount = math::round(remaining / size, DECIMAL_PLACES).floor();
+                remaining = (remaining - amount * size).max(0.0);
+                amount
+            };
+            parts.push((if n < 0.0 { -amount } else { amount }, name.clone()));
+        }
+
+        Ok(AstNode::new(AstNodeData::Object(CalculatorObject::MixedMeasure(Self { parts })), full_range))
+    }
+
+    fn call(&self, _: SourceRange, _: &[(NumberValue, SourceRange)], _: SourceRange) -> Result<AstNode> { unreachable!(); }
+}
+
+/// A function passed by its name instead of being called (e.g. the `f` in `map(f, [1; 2])`). The
+/// engine calls it, since it needs the environment to do so.
+#[derive(Debug, PartialEq, PartialOrd, Clone, serde::Serialize, serde::Deserialize)]
+pub struct FunctionReference {
+    pub(crate) name: String,
+}
+
+impl Object for FunctionReference {
+    fn to_string(&self, _: &Settings) -> String {
+        self.name.clone()
+    }
+
+    fn parse(_: Vec<ObjectArgument>, _: Context, _: SourceRange) -> Result<Self> {
+        // This object cannot be constructed using the object syntax
+        unreachable!()
+    }
+
+    fn apply(&self, _: SourceRange, op: (Operator, SourceRange), _: &AstNode, _: bool) -> Result<AstNode> {
+        error!(UnsupportedOperation: op.1);
+    }
+
+    fn call(&self, _: SourceRange, _: &[(NumberValue, SourceRange)], _: SourceRange) -> Result<AstNode> { unreachable!(); }
+}
+
+/// Text in quotes (e.g. `"speed"`), which is e.g. used as a label or created with `fmt`
+#[derive(Debug, PartialEq, PartialOrd, Clone, serde::Serialize, serde::Deserialize)]
+pub struct StringObject {
+    pub(crate) text: String,
+}
+
+impl Object for StringObject {
+    fn to_string(&self, _: &Settings) -> String {
+        self.text.clone()
+    }
+
+    fn parse(_: Vec<ObjectArgument>, _: Context, _: SourceRange) -> Result<Self> {
+        // This object cannot be constructed using the object syntax
+        unreachable!()
+    }
+
+    fn apply(&self, self_range: SourceRange, op: (Operator, SourceRange), other: &AstNode, self_is_rhs: bool) -> Result<AstNode> {
+        if op.0 != Operator::Plus { error!(UnsupportedOperation: op.1); }
+        let AstNodeData::Object(CalculatorObject::String(other_string)) = &other.data else { error!(ExpectedString: other.range); };
+
+        let text = if self_is_rhs {
+            format!("{}{}", other_string.text, self.text)
+        } else {
+            format!("{}{}", self.text, other_string.text)
+        };
+        Ok(AstNode::new(AstNodeData::Object(CalculatorObject::String(Self { text })), self_range.extend(other.range)))
+    }
+
+    fn call(&self, _: SourceRange, _: &[(NumberValue, SourceRange)], _: SourceRange) -> Result<AstNode> { unreachable!(); }
 }
@@ -6,14 +6,16 @@
 
 use std::ops::Range;
 
-use crate::{Context, error, Format, Function};
-use crate::astgen::ast::{AstNode, AstNodeData, AstNodeModifier, BooleanOperator, Operator};
-use crate::astgen::objects::{CalculatorObject, ObjectArgument, Vector};
+use crate::{Context, error, Format, Function, InputMode, Precedence, TimeZone};
+use crate::astgen::ast::{Adjustment, AstNode, AstNodeData, AstNodeModifier, BooleanOperator, Operator};
+use crate::astgen::objects::{CalculatorObject, FunctionReference, Interval, MixedMeasure, ObjectArgument, StringObject, Vector};
 use crate::astgen::tokenizer::{Token, TokenType, TokenType::*};
-use crate::common::{Error, ErrorType::*, ErrorType, Result, SourceRange};
+use crate::common::{Error, ErrorType::*, ErrorType, Result, SourceRange, WarningType};
 use crate::engine::{Engine, Value};
 use crate::environment::{ArgCount, FunctionArgument, FunctionVariantType};
-use crate::environment::units::{get_prefix_power, is_unit_with_prefix, Unit};
+use crate::environment::units::{convert, get_prefix_power, is_unit_with_prefix, resolve_unit_alias, Unit};
+
+mod rpn;
 
 macro_rules! parse_f64_radix {
     ($text:expr, $radix:expr, $range:expr) => {
@@ -26,6 +28,10 @@ macro_rules! parse_f64_radix {
     }
 }
 
+/// How deeply brackets and arguments can be nested, so that parsing and evaluating them doesn't
+/// overflow the stack
+const MAX_NESTING_LEVEL: usize = 32;
+
 macro_rules! operator {
     ($ty:ident) => {
         AstNodeData::Operator(Operator::$ty)
@@ -60,6 +66,8 @@ pub enum ParserResultData {
         lhs: Vec<AstNode>,
         rhs: Vec<AstNode>,
         operator: BooleanOperator,
+        /// The tolerance of an approximate equality check (e.g. `0.01` in `a ≈ b within 0.01`)
+        tolerance: Option<Vec<AstNode>>,
     },
     VariableDefinition(String, Option<Vec<AstNode>>),
     FunctionDefinition {
@@ -106,7 +114,8 @@ macro_rules! result {
 
 #[derive(Debug)]
 enum DefinitionInfo {
-    Variable(String),
+    /// A variable definition, or an update of the variable using the operator (e.g. `x += 5`)
+    Variable(String, Option<Operator>),
     Function(String, Vec<FunctionArgument>),
 }
 
@@ -121,6 +130,8 @@ pub struct BooleanOperatorInfo {
     operator: BooleanOperator,
     ast_index: usize,
     token_index: usize,
+    /// The index in the AST at which the tolerance after `within` starts
+    tolerance_index: Option<usize>,
 }
 
 pub struct Parser<'a> {
@@ -129,10 +140,23 @@ pub struct Parser<'a> {
     nesting_level: usize,
     context: Context,
     extra_allowed_variables: Option<Vec<String>>,
+    /// The name and the number of arguments of the function whose definition is being parsed, so
+    /// that it can call itself
+    defined_function: Option<(String, usize)>,
+    /// Whether a variable definition is being parsed, whose value keeps the format of its hex and
+    /// binary literals
+    is_variable_definition: bool,
     allow_question_mark: bool,
     question_mark: Option<QuestionMarkInfo>,
     did_find_equals_sign: bool,
     skip_newline_stack: Vec<bool>,
+    input_mode: InputMode,
+    precedence: Precedence,
+    /// Errors the parser recovered from to keep looking for more errors in the same line
+    recovered_errors: Vec<Error>,
+    /// Whether the line may start with an operator, which applies it to the result of the previous
+    /// line (e.g. `* 1.19`). Only whole lines (see [Parser::next]) can do this.
+    allow_leading_operator: bool,
 }
 
 impl<'a> Parser<'a> {
@@ -144,20 +168,29 @@ impl<'a> Parser<'a> {
         question_mark: Option<QuestionMarkInfo>,
         skip_newline_stack: Vec<bool>,
     ) -> Self {
+        let precedence = context.borrow().settings.precedence;
         Self {
             tokens,
             index: 0,
             nesting_level,
-            context,
             extra_allowed_variables: None,
+            defined_function: None,
+            is_variable_definition: false,
             allow_question_mark,
             question_mark,
             did_find_equals_sign: false,
             skip_newline_stack,
+            input_mode: InputMode::Infix,
+            precedence,
+            context,
+            recovered_errors: vec![],
+            allow_leading_operator: false,
         }
     }
 
+    /// Creates a parser using the input mode from the context's settings
     pub(crate) fn from_tokens(tokens: &'a [Token], context: Context) -> Self {
+        let input_mode = context.borrow().settings.input_mode;
         Self::new(
             tokens,
             context,
@@ -165,7 +198,12 @@ impl<'a> Parser<'a> {
             true,
             None,
             vec![false],
-        )
+        ).with_input_mode(input_mode)
+    }
+
+    pub(crate) fn with_input_mode(mut self, input_mode: InputMode) -> Self {
+        self.input_mode = input_mode;
+        self
     }
 
     fn new_sub(&self, tokens: &'a [Token], allow_question_mark: bool) -> Self {
@@ -180,7 +218,13 @@ impl<'a> Parser<'a> {
             did_find_equals_sign: false,
             question_mark: self.question_mark.clone(),
             extra_allowed_variables: self.extra_allowed_variables.clone(),
+            defined_function: self.defined_function.clone(),
+            is_variable_definition: self.is_variable_definition,
             skip_newline_stack,
+            input_mode: InputMode::Infix,
+            precedence: self.precedence,
+            recovered_errors: vec![],
+            allow_leading_operator: false,
         }
     }
 
@@ -188,6 +232,10 @@ impl<'a> Parser<'a> {
         self.extra_allowed_variables = Some(variables);
     }
 
+    fn is_defined_function(&self, name: &str) -> bool {
+        self.defined_function.as_ref().map_or(false, |(defined, _)| defined == name)
+    }
+
     fn skip_newline(&self) -> bool { *self.skip_newline_stack.last().unwrap() }
 
     fn set_skip_newline(&mut self, skip: bool) {
@@ -213,6 +261,19 @@ impl<'a> Parser<'a> {
         last_range
     }
 
+    /// Like [Parser::error_range_at_end], but for the end of the current line, so that errors are
+    /// not reported on one of the following lines.
+    fn error_range_at_line_end(&self) -> SourceRange {
+        match self.tokens.get(self.index) {
+            Some(token) if token.ty == Newline && self.index > 0 => {
+                let mut range = self.tokens[self.index - 1].range;
+                range.start_char = range.end_char.saturating_sub(1);
+                range
+            }
+            _ => self.error_range_at_end(),
+        }
+    }
+
     fn accept<Predicate>(&mut self, predicate: Predicate, error_type: ErrorType) -> Result<&Token>
         where Predicate: Fn(&TokenType) -> bool {
         let token = self.tokens.get(self.index).ok_or_else(|| error_type.clone().with(self.error_range_at_end()))?;
@@ -265,21 +326,41 @@ impl<'a> Parser<'a> {
         }
     }
 
-    fn try_accept_variable_definition_head(&mut self, expect_definition_sign: bool) -> Option<Result<String>> {
+    /// Tries to accept the head of a variable definition or update.
+    ///
+    /// **Returns:** The name of the variable and, if it is updated (e.g. `x += 5`), the operator
+    /// used for the update
+    fn try_accept_variable_definition_head(&mut self, expect_definition_sign: bool) -> Option<Result<(String, Option<Operator>)>> {
         let identifier = self.try_accept(is(Identifier))?;
         let identifier_range = identifier.range;
-        let name = identifier.text.clone();
+        let name = identifier.text.to_string();
 
-        if expect_definition_sign && self.try_accept(is(DefinitionSign)).is_none() {
-            self.index = self.index.saturating_sub(1);
-            return None;
+        let mut update_operator = None;
+        if expect_definition_sign {
+            let Some(sign) = self.try_accept(any(&[DefinitionSign, CompoundDefinitionSign])) else {
+                self.index = self.index.saturating_sub(1);
+                return None;
+            };
+            if sign.ty == CompoundDefinitionSign {
+                update_operator = Some(match sign.text.as_bytes()[0] {
+                    b'+' => Operator::Plus,
+                    b'-' => Operator::Minus,
+                    b'*' => Operator::Multiply,
+                    b'/' => Operator::Divide,
+                    _ => Operator::Exponentiation,
+                });
+            }
         }
 
-        if self.context.borrow().env.is_standard_variable(&name) {
+        let env = &self.context.borrow().env;
+        if env.is_standard_variable(&name) {
             return Some(Err(ReservedVariable(name).with(identifier_range)));
         }
+        if update_operator.is_some() && !env.is_valid_variable(&name) {
+            return Some(Err(UndefinedVariableUpdate(name).with(identifier_range)));
+        }
 
-        Some(Ok(name))
+        Some(Ok((name, update_operator)))
     }
 
     /// Tries to accept a function definition head. To do this, the function tries to parse the
@@ -318,7 +399,7 @@ impl<'a> Parser<'a> {
         let _identifier = Token::empty_from_type(Identifier);
         let identifier = try_token!(self.accept(is(Identifier), ExpectedIdentifier), _identifier);
         let identifier_range = identifier.range;
-        let name = identifier.text.clone();
+        let name = identifier.text.to_string();
 
         if self.context.borrow().env.is_standard_function(&name) && first_error.is_none() {
             first_error = Some(ReservedFunction(name.clone()).with(identifier_range));
@@ -328,7 +409,7 @@ impl<'a> Parser<'a> {
         self.push_skip_newline(true);
 
         let first_arg = try_token!(self.accept(is(Identifier), ExpectedIdentifier), _identifier);
-        let first_arg_text = first_arg.text.clone();
+        let first_arg_text = first_arg.text.to_string();
         let first_arg_range = first_arg.range;
 
         let unit = self.try_accept_unit()
@@ -347,12 +428,12 @@ impl<'a> Parser<'a> {
             match next.ty {
                 Comma => {
                     let next_arg = try_token!(self.accept(is(Identifier), ExpectedIdentifier), _identifier);
-                    let next_arg_text = next_arg.text.clone();
+                    let next_arg_text = next_arg.text.to_string();
                     let next_arg_range = next_arg.range;
 
                     if first_error.is_none() {
                         if let Some((_, _, first_occurrence_range)) = args.iter().find(|(arg, ..)| *arg == next_arg_text) {
-                            first_error = Some(DuplicateArgument(next_arg.text.clone()).with_multiple(vec![next_arg.range, *first_occurrence_range]));
+                            first_error = Some(DuplicateArgument(next_arg.text.to_string()).with_multiple(vec![next_arg.range, *first_occurrence_range]));
                             continue;
                         }
                     }
@@ -394,8 +475,9 @@ impl<'a> Parser<'a> {
     }
 
     fn accept_definition_info(&mut self, expect_definition_sign: bool) -> Result<Option<DefinitionInfo>> {
-        if let Some(name) = self.try_accept_variable_definition_head(expect_definition_sign) {
-            Ok(Some(DefinitionInfo::Variable(name?)))
+        if let Some(result) = self.try_accept_variable_definition_head(expect_definition_sign) {
+            let (name, update_operator) = result?;
+            Ok(Some(DefinitionInfo::Variable(name, update_operator)))
         } else if let Some(result) = self.try_accept_function_definition_head(expect_definition_sign) {
             let (name, args) = result?;
             Ok(Some(DefinitionInfo::Function(name, args)))
@@ -404,7 +486,9 @@ impl<'a> Parser<'a> {
         }
     }
 
-    pub(crate) fn next(&mut self) -> Option<Result<ParserResult>> {
+    /// Parses the next calculation. If it contains errors, all errors the parser could find in it
+    /// are returned, sorted by their position.
+    pub(crate) fn next(&mut self) -> Option<std::result::Result<ParserResult, Vec<Error>>> {
         self.set_skip_newline(false);
         while self.index < self.tokens.len() {
             if self.try_accept(is(Newline)).is_some() { continue; }
@@ -412,9 +496,12 @@ impl<'a> Parser<'a> {
         }
         if self.index >= self.tokens.len() { return None; }
 
-        let new = self.parse_single();
+        self.allow_leading_operator = true;
+        let new = self.parse_line();
+        self.allow_leading_operator = false;
         if new.is_err() {
-            // Skip to next line since we can't recover from errors (yet)
+            // Skip to the next line, since the rest of this one can't be parsed after an error
+            // that the parser couldn't recover from
             while self.try_accept(all_except_newline()).is_some() {}
         }
         Some(new)
@@ -422,38 +509,86 @@ impl<'a> Parser<'a> {
 
     fn current_tokens_end_line(&self) -> usize {
         self.tokens.get(self.index)
-            .map(|t| t.range.end_line)
-            .unwrap_or_else(|| self.tokens.last().unwrap().range.end_line)
+            .or_else(|| self.tokens.last())
+            .map_or(0, |t| t.range.end_line)
     }
 
     fn has_reached_end(&self) -> bool {
         self.index >= self.tokens.len() || (!self.skip_newline() && self.tokens[self.index].ty == Newline)
     }
 
+    /// Parses a single calculation, only returning the first error in it
+    pub(crate) fn parse_single(&mut self) -> Result<ParserResult> {
+        self.parse_line().map_err(|mut errors| errors.remove(0))
+    }
+
+    /// Parses a single calculation and returns all errors found in it
+    fn parse_line(&mut self) -> std::result::Result<ParserResult, Vec<Error>> {
+        let result = self.accept_line();
+        let mut errors = std::mem::take(&mut self.recovered_errors);
+        match result {
+            Ok(result) if errors.is_empty() => return Ok(result),
+            Ok(_) => {}
+            Err(e) => errors.push(e),
+        }
+        errors.sort_by_key(|e| e.ranges.first().copied());
+        Err(errors)
+    }
+
     // Extract into separate function to allow the use of the error!() macro and easier handling
     // with `return`.
-    pub(crate) fn parse_single(&mut self) -> Result<ParserResult> {
+    fn accept_line(&mut self) -> Result<ParserResult> {
         let start_line = self.tokens.get(self.index).map(|t| t.range.start_line).unwrap_or_default();
         let start_token_index = self.index;
 
+        if self.nesting_level >= MAX_NESTING_LEVEL {
+            let range = self.tokens.get(self.index).map_or_else(SourceRange::empty, |token| token.range);
+            error!(TooDeeplyNested: range);
+        }
+
         let mut definition_info = self.accept_definition_info(true)?;
 
         if self.has_reached_end() {
             let line_range = start_line..self.current_tokens_end_line();
             let token_range = start_token_index..self.index;
             return match definition_info {
-                Some(DefinitionInfo::Variable(name)) =>
+                Some(DefinitionInfo::Variable(name, None)) =>
                     Ok(result!(VariableDefinition(name, None) with tr: token_range, lr: line_range)),
+                Some(DefinitionInfo::Variable(_, Some(_))) => error!(ExpectedElements: self.error_range_at_end()),
                 Some(DefinitionInfo::Function(name, _)) =>
                     Ok(result!(FunctionDefinition { name: name, function: None } with tr: token_range, lr: line_range)),
                 None => error!(ExpectedElements: self.error_range_at_end()),
             };
         }
 
-        if let Some(DefinitionInfo::Function(_, args)) = &definition_info {
+        if let Some(DefinitionInfo::Function(name, args)) = &definition_info {
+            self.defined_function = Some((name.clone(), args.len()));
             let args = args.iter().map(|arg| arg.0.clone()).collect::<Vec<_>>();
             self.set_extra_allowed_variables(args);
         }
+        self.is_variable_definition = matches!(definition_info, Some(DefinitionInfo::Variable(..)));
+        if let Some(DefinitionInfo::Variable(name, _)) = &definition_info {
+            let mut context = self.context.borrow_mut();
+            let range = self.tokens[start_token_index].range;
+            if context.env.is_constant(name) {
+                context.env.warnings.push(WarningType::ConstantShadowed(name.clone()).with(range));
+            } else if is_unit_with_prefix(name) {
+                // Variables are used instead of units with the same name (e.g. `2m` is `2 * m`)
+                context.env.warnings.push(WarningType::UnitShadowed(name.clone()).with(range));
+            }
+        }
+
+        if self.input_mode == InputMode::Rpn {
+            let (result, boolean_operator) = self.accept_rpn_expression()?;
+            return self.finish_parsing(
+                definition_info,
+                result,
+                boolean_operator,
+                vec![],
+                start_line,
+                start_token_index,
+            );
+        }
 
         #[derive(Default)]
         struct GroupStackEntry {
@@ -481,7 +616,10 @@ impl<'a> Parser<'a> {
             () => { group_stack.last_mut().unwrap().ast }
         }
 
+        // Whether the variants are written as `if a then b else c` rather than `for a: b, else: c`
+        let mut is_conditional = false;
         if matches!(definition_info, Some(DefinitionInfo::Function(..))) {
+            is_conditional = self.peek(is(If)).is_some();
             if let Some(variant) = self.try_accept_function_variant_head() {
                 if self.has_reached_end() {
                     let mut last = self.tokens[self.index - 1].clone();
@@ -494,11 +632,18 @@ impl<'a> Parser<'a> {
         }
 
         let mut accept_expression_beginning = true;
+        if self.allow_leading_operator && definition_info.is_none() && self.input_mode == InputMode::Infix
+            && starts_with_continuation_operator(&self.tokens[self.index..]) {
+            let operator_range = self.tokens[self.index].range;
+            let range = SourceRange::line(operator_range.start_line, operator_range.start_char, operator_range.start_char);
+            ast!().push(AstNode::new(AstNodeData::Identifier("last".to_owned()), range));
+            accept_expression_beginning = false;
+        }
 
         while !self.has_reached_end() {
             self.set_skip_newline(group_stack.len() > 1);
             let additional_break_types: &[TokenType] = if !function_variants.is_empty() && group_stack.len() == 1 {
-                &[Comma]
+                if is_conditional { &[Else] } else { &[Comma] }
             } else {
                 &[]
             };
@@ -513,7 +658,7 @@ impl<'a> Parser<'a> {
             accept_expression_beginning = false;
 
             // The accept_expression() function will exit on these tokens:
-            let token = self.try_accept(any(&[CloseBracket, PostfixDefinitionSign, Comma]));
+            let token = self.try_accept(any(&[CloseBracket, PostfixDefinitionSign, Comma, Else]));
             match token.map(|t| t.ty) {
                 Some(CloseBracket) => {
                     if group_stack.len() == 1 {
@@ -563,6 +708,31 @@ impl<'a> Parser<'a> {
 
                     accept_expression_beginning = true;
                 }
+                Some(Else) => {
+                    let else_range = token.unwrap().range;
+                    if matches!(function_variants.last(), Some((FunctionVariantType::Else, _))) {
+                        error!(UnexpectedElements: else_range);
+                    }
+
+                    function_variants.last_mut().unwrap().1 = group_stack.pop().unwrap().ast;
+                    group_stack = vec![GroupStackEntry::default()];
+
+                    // `else if` adds another condition
+                    let variant = if self.peek(is(If)).is_some() {
+                        self.try_accept_function_variant_head().unwrap()?
+                    } else {
+                        FunctionVariantType::Else
+                    };
+                    function_variants.push((variant, vec![]));
+
+                    if self.has_reached_end() {
+                        let mut last = self.tokens[self.index - 1].clone();
+                        last.range.end_char -= 1;
+                        error!(ExpectedElements: last.range);
+                    }
+
+                    accept_expression_beginning = true;
+                }
                 Some(PostfixDefinitionSign) => {
                     if definition_info.is_some() {
                         error!(DisallowedPostfixDefinitionNormalDefinitionWasUsed: token.unwrap().range);
@@ -588,8 +758,11 @@ impl<'a> Parser<'a> {
                     // The function exited because of an open bracket. We can't directly accept()
                     // it though, because there might be prefix modifiers.
                     let modifiers = self.accept_prefix_modifiers();
-                    let open_bracket = self.accept(is(OpenBracket), ExpectedElements)?;
-                    group_stack.push(GroupStackEntry::new(modifiers, open_bracket.range));
+                    let open_bracket_range = self.accept(is(OpenBracket), ExpectedElements)?.range;
+                    if self.nesting_level >= MAX_NESTING_LEVEL {
+                        error!(TooDeeplyNested: open_bracket_range);
+                    }
+                    group_stack.push(GroupStackEntry::new(modifiers, open_bracket_range));
                     self.nesting_level += 1;
                     accept_expression_beginning = true;
                 }
@@ -601,38 +774,84 @@ impl<'a> Parser<'a> {
         }
 
         let GroupStackEntry { ast: result, .. } = group_stack.pop().unwrap();
+        self.finish_parsing(
+            definition_info,
+            result,
+            boolean_operator,
+            function_variants,
+            start_line,
+            start_token_index,
+        )
+    }
 
+    /// Turns the parsed data of a line into a [ParserResult]
+    fn finish_parsing(
+        &mut self,
+        definition_info: Option<DefinitionInfo>,
+        result: Vec<AstNode>,
+        boolean_operator: Option<BooleanOperatorInfo>,
+        mut function_variants: Vec<(FunctionVariantType, Vec<AstNode>)>,
+        start_line: usize,
+        start_token_index: usize,
+    ) -> Result<ParserResult> {
         let line_range = start_line..self.current_tokens_end_line();
         let token_range = start_token_index..self.index;
 
+        for (_, ast) in &mut function_variants {
+            *ast = self.apply_precedence(std::mem::take(ast));
+        }
+
         if let Some(BooleanOperatorInfo {
                         operator,
                         ast_index,
                         token_index,
+                        tolerance_index,
                     }) = boolean_operator {
             if definition_info.is_some() {
                 error!(DisallowedBooleanOperator: self.tokens[token_index].range);
             }
 
             let (lhs, rhs) = result.split_at(ast_index);
+            let (rhs, tolerance) = match tolerance_index {
+                Some(index) => {
+                    let (rhs, tolerance) = rhs.split_at(index - ast_index);
+                    (rhs, Some(self.apply_precedence(tolerance.to_vec())))
+                }
+                None => (rhs, None),
+            };
+            let (lhs, rhs) = (self.apply_precedence(lhs.to_vec()), self.apply_precedence(rhs.to_vec()));
             if let Some(info) = std::mem::take(&mut self.question_mark) {
                 Ok(result!(Equation {
-                    lhs: lhs.to_vec(),
-                    rhs: rhs.to_vec(),
+                    lhs: lhs,
+                    rhs: rhs,
                     is_question_mark_in_lhs: info.is_in_lhs,
                     output_variable: info.variable
                 } with tr: token_range, lr: line_range))
             } else {
                 Ok(result!(BooleanExpression {
-                    lhs: lhs.to_vec(),
-                    rhs: rhs.to_vec(),
-                    operator: operator
+                    lhs: lhs,
+                    rhs: rhs,
+                    operator: operator,
+                    tolerance: tolerance
                 } with tr: token_range, lr: line_range))
             }
         } else {
+            let result = self.apply_precedence(result);
             match definition_info {
-                Some(DefinitionInfo::Variable(name)) =>
+                Some(DefinitionInfo::Variable(name, None)) =>
                     Ok(result!(VariableDefinition(name, Some(result)) with tr: token_range, lr: line_range)),
+                Some(DefinitionInfo::Variable(name, Some(operator))) => {
+                    // `x += 5` is `x := x + (5)`
+                    let name_range = self.tokens[token_range.start].range;
+                    let sign_range = self.tokens[token_range.start + 1].range;
+                    let rhs_range = crate::engine::full_range(&result);
+                    let result = vec![
+                        AstNode::new(AstNodeData::Identifier(name.clone()), name_range),
+                        AstNode::new(AstNodeData::Operator(operator), sign_range),
+                        AstNode::new(AstNodeData::Group(result), rhs_range),
+                    ];
+                    Ok(result!(VariableDefinition(name, Some(result)) with tr: token_range, lr: line_range))
+                }
                 Some(DefinitionInfo::Function(name, args)) => {
                     if !function_variants.is_empty() {
                         function_variants.last_mut().unwrap().1 = result;
@@ -660,6 +879,28 @@ impl<'a> Parser<'a> {
         }
     }
 
+    /// Groups the operations in `ast` according to the precedence settings. Only the parser of the
+    /// whole line does this, since the precedence can still be changed at the end of it.
+    fn apply_precedence(&self, ast: Vec<AstNode>) -> Vec<AstNode> {
+        if self.nesting_level != 0 { return ast; }
+        self.group_operations(ast)
+    }
+
+    /// Groups implicit multiplications if they should bind tighter than division (e.g. `1/2x` is
+    /// `1/(2x)`) and groups the operations so that they are evaluated from left to right if the
+    /// precedence is [Precedence::Sequential]
+    fn group_operations(&self, mut ast: Vec<AstNode>) -> Vec<AstNode> {
+        if self.input_mode == InputMode::Rpn { return ast; }
+
+        if self.context.borrow().settings.tight_implicit_multiplication {
+            ast = map_groups(ast, group_implicit_multiplications);
+        }
+        if self.precedence == Precedence::Sequential {
+            ast = map_groups(ast, sequential);
+        }
+        ast
+    }
+
     fn accept_expression(
         &mut self,
         ast: &mut Vec<AstNode>,
@@ -702,21 +943,64 @@ impl<'a> Parser<'a> {
                     let AstNodeData::Operator(operator) = op.data else { unreachable!(); };
                     // RHS of `in` (unit / format)
                     if operator == Operator::In {
+                        self.try_accept_trailing_operator_newline();
                         let start = self.tokens.get(self.index).map(|t| t.range);
 
                         let mut found_rhs = false;
+                        let precedence = self.peek(is(Identifier))
+                            .and_then(|token| token.text.parse::<Precedence>().ok());
+                        if let Some(precedence) = precedence {
+                            self.index += 1;
+                            self.precedence = precedence;
+                            continue;
+                        }
+                        // `in auto` only affects how the result is displayed, which is
+                        // handled after the line is calculated
+                        if self.peek(|ty| ty == &Identifier).map_or(false, |token| token.text == "auto") {
+                            self.index += 1;
+                            continue;
+                        }
+
                         if let Some(format) = self.try_accept(|ty| ty.is_format()) {
                             let format = match format.ty {
                                 Decimal => Format::Decimal,
                                 Binary => Format::Binary,
                                 Hex => Format::Hex,
                                 Scientific => Format::Scientific,
+                                Dms => Format::Dms,
                                 _ => unreachable!(),
                             };
-                            ast.last_mut().unwrap().format = format;
+                            // After a unit conversion (e.g. `5mi in km in sci`), the format
+                            // applies to the value that is converted
+                            let mut target = ast.len() - 1;
+                            while target >= 2 && matches!(
+                                (&ast[target - 1].data, &ast[target].data),
+                                (AstNodeData::Operator(Operator::In), AstNodeData::Unit(_))
+                            ) {
+                                target -= 2;
+                            }
+                            ast[target].format = format;
+                            ast[target].preferred_format = Some(format);
                             found_rhs = true;
                         }
 
+                        // Time zones (e.g. `in UTC+2`)
+                        if let Some((zone, range)) = self.try_accept_time_zone() {
+                            ast.push(op);
+                            ast.push(AstNode::new(AstNodeData::Object(CalculatorObject::TimeZone(zone)), range));
+                            continue;
+                        }
+
+                        // Mixed measures (e.g. `in ft_in`)
+                        let mixed_measure = self.peek(is(Identifier))
+                            .and_then(|token| MixedMeasure::parse_units(&token.text).map(|measure| (measure, token.range)));
+                        if let Some((measure, range)) = mixed_measure {
+                            self.index += 1;
+                            ast.push(op);
+                            ast.push(AstNode::new(AstNodeData::Object(CalculatorObject::MixedMeasure(measure)), range));
+                            continue;
+                        }
+
                         if let Some(unit) = self.try_accept_unit() {
                             let end = self.tokens[self.index - 1].range;
                             let unit = unit?;
@@ -726,6 +1010,14 @@ impl<'a> Parser<'a> {
                                 start.unwrap().extend(end),
                             ));
                             found_rhs = true;
+
+                            // The date of the exchange rates (e.g. `in EUR at {date 01.06.2023}`)
+                            if let Some(at) = self.peek(is(Identifier)).filter(|token| token.text == "at") {
+                                let range = at.range;
+                                self.index += 1;
+                                ast.push(AstNode::new(AstNodeData::Operator(Operator::At), range));
+                                ast.push(self.accept_number()?);
+                            }
                         }
 
                         if found_rhs { continue; }
@@ -745,7 +1037,7 @@ impl<'a> Parser<'a> {
                 }
                 Err(error) => {
                     // Try to infer multiplication
-                    if self.peek(any(&[OpenBracket, Identifier])).is_some() {
+                    if self.peek(any(&[OpenBracket, Identifier, SquareRoot])).is_some() {
                         ast.push(AstNode::new(AstNodeData::Operator(Operator::Multiply), SourceRange::empty()));
                     } else if let Some((op, range)) = self.try_accept_boolean_operator() {
                         if self.nesting_level != 0 {
@@ -762,7 +1054,16 @@ impl<'a> Parser<'a> {
                             operator: op,
                             ast_index: ast.len(),
                             token_index: self.index - 1,
+                            tolerance_index: None,
                         });
+                    } else if let Some(within) = self.try_accept(is(Within)) {
+                        let range = within.range;
+                        match boolean_operator {
+                            Some(info) if self.nesting_level == 0
+                                && info.operator == BooleanOperator::ApproximatelyEqual
+                                && info.tolerance_index.is_none() => info.tolerance_index = Some(ast.len()),
+                            _ => error!(UnexpectedWithin: range),
+                        }
                     } else {
                         return Err(error);
                     }
@@ -770,6 +1071,7 @@ impl<'a> Parser<'a> {
             }
 
             self.try_accept_line_continuation()?;
+            self.try_accept_trailing_operator_newline();
 
             // Check if we need to exit because of an open bracket. To do this, we need to try to
             // accept prefix modifiers and check after them. We then reset our index back to where
@@ -789,12 +1091,27 @@ impl<'a> Parser<'a> {
             // NOTE: () = 1
             if self.peek(is(CloseBracket)).is_some() &&
                 self.tokens[self.index - 1].ty == OpenBracket { break; }
-            ast.push(self.accept_number()?);
+            ast.push(self.accept_operand()?);
         }
 
         Ok(())
     }
 
+    /// Accepts the operand after an operator. If it is missing before a closing bracket or another
+    /// operator (e.g. `(1 + )` or `1 + * 2`), the error is recorded and a placeholder is used, so
+    /// that the rest of the line can still be checked. The line won't be evaluated anyway.
+    fn accept_operand(&mut self) -> Result<AstNode> {
+        match self.accept_number() {
+            Err(error) if matches!(error.error, ExpectedNumber)
+                && self.peek(|ty| ty == &CloseBracket || ty.is_operator()).is_some() => {
+                let range = error.ranges[0];
+                self.recovered_errors.push(error);
+                Ok(AstNode::new(AstNodeData::Literal(1.0), range))
+            }
+            result => result,
+        }
+    }
+
     fn try_accept_line_continuation(&mut self) -> Result<()> {
         self.push_skip_newline(false);
         if self.try_accept(is(LineContinuation)).is_some() {
@@ -804,15 +1121,27 @@ impl<'a> Parser<'a> {
         Ok(())
     }
 
+    /// Lets the expression continue on the next line if the current line ends with an operator
+    /// (e.g. `1 +\n2`). An empty line ends the expression, so that the error is reported at the
+    /// operator instead.
+    fn try_accept_trailing_operator_newline(&mut self) {
+        let is_newline = |index: usize| self.tokens.get(index).map_or(false, |t| t.ty == Newline);
+        if is_newline(self.index) && self.index + 1 < self.tokens.len() && !is_newline(self.index + 1) {
+            self.index += 1;
+        }
+    }
+
     fn try_accept_function_variant_head(&mut self) -> Option<Result<FunctionVariantType>> {
-        match self.try_accept(any(&[For, Else]))?.ty {
-            For => {
+        match self.try_accept(any(&[For, If, Else]))?.ty {
+            ty @ (For | If) => {
+                // `for condition:` or `if condition then`
+                let terminator = if ty == For { Colon } else { Then };
                 let start_i = self.index;
                 let mut end_i = 0usize;
                 while !self.has_reached_end() {
                     match self.try_accept(all()) {
                         Some(t) => {
-                            if t.ty == Colon {
+                            if t.ty == terminator {
                                 end_i = self.index - 1;
                                 break;
                             }
@@ -822,7 +1151,8 @@ impl<'a> Parser<'a> {
                 }
 
                 if end_i == 0 {
-                    return Some(Err(ExpectedColon.with(self.error_range_at_end())));
+                    let error = if ty == For { ExpectedColon } else { ExpectedThen };
+                    return Some(Err(error.with(self.error_range_at_end())));
                 } else if end_i == start_i {
                     return Some(Err(ExpectedElements.with(self.tokens[start_i].range)));
                 }
@@ -840,6 +1170,7 @@ impl<'a> Parser<'a> {
                 if let Some(vars) = self.extra_allowed_variables.clone() {
                     parser.set_extra_allowed_variables(vars);
                 }
+                parser.defined_function = self.defined_function.clone();
 
                 match parser.parse_single() {
                     Ok(res) => {
@@ -847,8 +1178,9 @@ impl<'a> Parser<'a> {
                             lhs,
                             rhs,
                             operator,
+                            tolerance,
                         } = res.data {
-                            Some(Ok(FunctionVariantType::BooleanVariant { lhs, rhs, operator }))
+                            Some(Ok(FunctionVariantType::BooleanVariant { lhs, rhs, operator, tolerance }))
                         } else {
                             Some(Err(ExpectedBooleanExpression(res.data.to_string()).with(range)))
                         }
@@ -877,6 +1209,7 @@ impl<'a> Parser<'a> {
             GreaterThanEqual => BooleanOperator::GreaterThanEqual,
             LessThan => BooleanOperator::LessThan,
             LessThanEqual => BooleanOperator::LessThanEqual,
+            ApproxEqualsSign => BooleanOperator::ApproximatelyEqual,
             _ => unreachable!(),
         };
 
@@ -889,16 +1222,29 @@ impl<'a> Parser<'a> {
         match next {
             Some(OpenCurlyBracket) => self.accept_object(),
             Some(OpenSquareBracket) => self.accept_vector(),
+            Some(StringLiteral) => self.accept_string(),
             _ => {
                 let mut modifiers = self.accept_prefix_modifiers();
 
+                // Currency symbols can be written before the amount (e.g. `$5`)
+                if let Some(currency) = self.try_accept_currency_symbol() {
+                    let mut number = self.accept_literal()?;
+                    let Unit::Unit(.., currency_range) = &currency else { unreachable!(); };
+                    number.range = currency_range.extend(number.range);
+                    number.unit = Some(currency);
+                    modifiers.append(&mut self.accept_suffix_modifiers());
+                    number.modifiers.append(&mut modifiers);
+                    return Ok(number);
+                }
+
                 let next = self.peek(all_except_newline());
                 let mut number = match next.map(|token| token.ty) {
                     Some(ty) if ty.is_literal() => self.accept_literal()?,
                     Some(Identifier) => self.accept_identifier()?,
                     Some(QuestionMark) => self.accept_question_mark()?,
+                    Some(SquareRoot) => self.accept_square_root()?,
                     Some(_) => error!(ExpectedNumber: next.unwrap().range),
-                    None => error!(ExpectedNumber: self.error_range_at_end()),
+                    None => error!(ExpectedNumber: self.error_range_at_line_end()),
                 };
 
                 modifiers.append(&mut self.accept_suffix_modifiers());
@@ -906,7 +1252,7 @@ impl<'a> Parser<'a> {
 
                 if let Some(token) = self.peek(is(Identifier)) {
                     if token.text != "e" && token.text != "E" &&
-                        (self.context.borrow().env.is_valid_variable(&token.text) ||
+                        (self.is_variable_rather_than_unit(&token.text) ||
                             self.context.borrow().env.is_valid_function(&token.text)) {
                         return Ok(number);
                     }
@@ -954,6 +1300,7 @@ impl<'a> Parser<'a> {
 
                 if let Some(unit) = self.try_accept_unit() {
                     number.unit = Some(unit?);
+                    return Ok(self.accept_mixed_measure(number));
                 } else if let Some(power) = self.try_accept_unit_prefix() {
                     number.modifiers.push(AstNodeModifier::Power(power));
                 }
@@ -963,6 +1310,48 @@ impl<'a> Parser<'a> {
         }
     }
 
+    /// Numbers with units of the same dimension that directly follow each other are added up (e.g.
+    /// `5ft 11in` or `3lb 4oz`). They are turned into the group `(5ft + 11in)`, where the additions
+    /// have the range of the number after them. Right after a number, `in` means inches.
+    fn accept_mixed_measure(&mut self, first: AstNode) -> AstNode {
+        let mut parts = vec![first];
+        while self.peek(|ty| ty.is_literal()).is_some() {
+            let start = self.index;
+            let Ok(mut part) = self.accept_literal() else { break; };
+            let unit = match self.peek(is(In)) {
+                Some(token) if token.range.start_char == part.range.end_char => {
+                    let unit = Unit::new("inch", 1.0, token.range);
+                    self.index += 1;
+                    Some(unit)
+                }
+                _ => self.try_accept_unit().and_then(|unit| unit.ok()),
+            };
+
+            let context = self.context.borrow();
+            let is_same_dimension = unit.as_ref().map_or(false, |unit| convert(
+                unit,
+                parts[0].unit.as_ref().unwrap(),
+                1.0,
+                &context.currencies,
+                context.settings.data_prefixes,
+                part.range,
+            ).is_ok());
+            drop(context);
+            if !is_same_dimension {
+                self.index = start;
+                break;
+            }
+
+            part.unit = unit;
+            parts.push(AstNode::new(AstNodeData::Operator(Operator::Plus), part.range));
+            parts.push(part);
+        }
+
+        if parts.len() == 1 { return parts.remove(0); }
+        let range = parts[0].range.extend(self.tokens[self.index - 1].range);
+        AstNode::new(AstNodeData::Group(parts), range)
+    }
+
     fn accept_prefix_modifiers(&mut self) -> Vec<AstNodeModifier> {
         let mut result = Vec::new();
         while let Some(token) = self.try_accept(any(&[ExclamationMark, Plus, Minus])) {
@@ -982,11 +1371,22 @@ impl<'a> Parser<'a> {
         while let Some(modifier) = self.try_accept(any(&[ExclamationMark, PercentSign])) {
             let modifier = match modifier.ty {
                 ExclamationMark => AstNodeModifier::Factorial,
+                PercentSign if modifier.text == "‰" => AstNodeModifier::Permille,
                 PercentSign => AstNodeModifier::Percent,
                 _ => unreachable!(),
             };
             result.push(modifier);
         }
+
+        // A percentage can be followed by what it stands for (e.g. `19% tax`)
+        if result.iter().any(AstNodeModifier::is_percentage) {
+            let adjustment = self.peek(is(Identifier))
+                .and_then(|token| Adjustment::ALL.into_iter().find(|adjustment| adjustment.name() == token.text));
+            if let Some(adjustment) = adjustment {
+                self.index += 1;
+                result.push(AstNodeModifier::Adjustment(adjustment));
+            }
+        }
         result
     }
 
@@ -1010,7 +1410,14 @@ impl<'a> Parser<'a> {
             _ => unreachable!(),
         };
 
-        Ok(AstNode::new(data, literal.range))
+        let preferred_format = match literal.ty {
+            HexLiteral => Some(Format::Hex),
+            BinaryLiteral => Some(Format::Binary),
+            _ => None,
+        };
+        let mut node = AstNode::new(data, literal.range);
+        if self.is_variable_definition { node.preferred_format = preferred_format; }
+        Ok(node)
     }
 
     fn accept_question_mark(&mut self) -> Result<AstNode> {
@@ -1029,9 +1436,20 @@ impl<'a> Parser<'a> {
         Ok(AstNode::new(AstNodeData::QuestionMark, range))
     }
 
+    /// Whether the identifier `name` should be parsed as a variable in a place where it could also
+    /// be a unit (e.g. after a number). Constants don't shadow units and unit prefixes, so that
+    /// e.g. `2h` still means two hours.
+    fn is_variable_rather_than_unit(&self, name: &str) -> bool {
+        let env = &self.context.borrow().env;
+        if !env.is_valid_variable(name) { return false; }
+
+        let is_prefix = name.chars().count() == 1 && get_prefix_power(name.chars().next().unwrap()).is_some();
+        !(env.is_constant(name) && (is_unit_with_prefix(name) || is_prefix))
+    }
+
     fn try_accept_unit_prefix(&mut self) -> Option<i32> {
-        let Some(prefix) = self.peek(is(Identifier)) else { return None; };
-        if prefix.text.len() > 1 { return None; }
+        let prefix = self.peek(is(Identifier))?;
+        if prefix.text.chars().count() > 1 { return None; }
         let char = prefix.text.chars().next().unwrap();
         if let Some(power) = get_prefix_power(char) {
             self.index += 1;
@@ -1046,7 +1464,7 @@ impl<'a> Parser<'a> {
             return self.accept_complex_unit().map(|res| res.map(|(unit, ..)| unit));
         }
 
-        let Some(numerator) = self.try_accept_single_unit() else { return None; };
+        let numerator = self.try_accept_single_unit()?;
         let numerator = match numerator {
             Ok(v) => v,
             Err(e) => return Some(Err(e)),
@@ -1056,7 +1474,7 @@ impl<'a> Parser<'a> {
             if self.peek(is(Divide)).is_some() {
                 self.index += 1;
                 if let Some(identifier) = self.peek(is(Identifier)) {
-                    if self.context.borrow().env.is_valid_variable(&identifier.text) {
+                    if self.is_variable_rather_than_unit(&identifier.text) {
                         self.index -= 1;
                         break 'blk;
                     }
@@ -1191,12 +1609,45 @@ impl<'a> Parser<'a> {
         Ok((result, result_range))
     }
 
+    /// Accepts a time zone as the target of a conversion (e.g. `UTC+5:30` or `local`)
+    fn try_accept_time_zone(&mut self) -> Option<(TimeZone, SourceRange)> {
+        let name = self.peek(is(Identifier))?;
+        if !matches!(name.text.to_lowercase().as_str(), "utc" | "local") { return None; }
+
+        // The offset is made up of the tokens directly after the name
+        let mut range = name.range;
+        let mut text = name.text.to_string();
+        let mut end = self.index + 1;
+        while let Some(token) = self.tokens.get(end) {
+            let is_offset = matches!(token.ty, Plus | Minus | DecimalLiteral | Colon);
+            if !is_offset || token.range.start_char != range.end_char { break; }
+            text += &token.text;
+            range = range.extend(token.range);
+            end += 1;
+        }
+
+        let zone = text.parse::<TimeZone>().ok()?;
+        self.index = end;
+        Some((zone, range))
+    }
+
+    /// Accepts a currency symbol (e.g. `$`) as the currency it stands for
+    fn try_accept_currency_symbol(&mut self) -> Option<Unit> {
+        let symbol = self.peek(is(Identifier))?;
+        let currency = self.context.borrow().settings.currency.currency_of_symbol(&symbol.text)?;
+        let range = symbol.range;
+        self.index += 1;
+        Some(Unit::new(currency.as_str(), 1.0, range))
+    }
+
     fn try_accept_single_unit(&mut self) -> Option<Result<Unit>> {
-        let Some(unit) = self.peek(is(Identifier)) else { return None; };
+        if let Some(currency) = self.try_accept_currency_symbol() { return Some(Ok(currency)); }
+
+        let unit = self.peek(is(Identifier))?;
         if !is_unit_with_prefix(&unit.text) { return None; }
 
         let mut unit_range = unit.range;
-        let unit = unit.text.clone();
+        let unit = resolve_unit_alias(&unit.text);
         self.index += 1;
 
         let mut power = 1.0f64;
@@ -1218,14 +1669,15 @@ impl<'a> Parser<'a> {
 
     fn accept_identifier(&mut self) -> Result<AstNode> {
         let identifier = self.accept(is(Identifier), ExpectedIdentifier)?;
-        let name = identifier.text.clone();
+        let name = identifier.text.to_string();
         let range = identifier.range;
 
         if self.context.borrow().env.is_valid_variable(&name) {
-            let ctx = self.context.borrow();
-            let v = ctx.env.resolve_variable(&name).unwrap();
-            let is_callable_object = matches!(&v.0, Value::Object(object) if object.is_callable());
-            drop(ctx);
+            // `last` has no value without a result above, which the engine reports
+            let is_callable_object = matches!(
+                self.context.borrow().env.resolve_variable(&name),
+                Ok(crate::Variable(Value::Object(object))) if object.is_callable()
+            );
 
             let node = AstNode::new(AstNodeData::Identifier(name.clone()), range);
 
@@ -1244,7 +1696,13 @@ impl<'a> Parser<'a> {
                 return question_mark;
             }
             return Ok(node);
-        } else if self.context.borrow().env.is_valid_function(&name) {
+        } else if self.context.borrow().env.is_valid_function(&name) || self.is_defined_function(&name) {
+            // Functions that aren't called are passed as values (e.g. `map(f, [1; 2])`)
+            if self.has_reached_end() || self.peek(any(&[Comma, CloseBracket])).is_some() {
+                let reference = FunctionReference { name };
+                return Ok(AstNode::new(AstNodeData::Object(CalculatorObject::Function(reference)), range));
+            }
+
             let open_bracket_token = self.peek(is(OpenBracket));
             let open_bracket_range = open_bracket_token.map(|t| t.range).unwrap_or_default();
             let arguments = self.accept_call_arguments(&name)?;
@@ -1268,7 +1726,21 @@ impl<'a> Parser<'a> {
             return question_mark;
         }
 
-        error!(UnknownIdentifier(name): range)
+        // Keep parsing the line as if the identifier was a number, to find the errors after it.
+        // The line won't be evaluated anyway.
+        let is_call = self.peek(is(OpenBracket)).map_or(false, |bracket| bracket.range.start_char == range.end_char);
+        let error = {
+            let env = &self.context.borrow().env;
+            if is_call {
+                let suggestions = env.similar_functions(&name);
+                UnknownFunction(name, suggestions)
+            } else {
+                let suggestions = env.similar_identifiers(&name);
+                UnknownIdentifier(name, suggestions)
+            }
+        };
+        self.recovered_errors.push(error.with(range));
+        Ok(AstNode::new(AstNodeData::Literal(1.0), range))
     }
 
     fn try_accept_question_mark_after_identifier(&mut self, identifier: &str, range: SourceRange) -> Option<Result<AstNode>> {
@@ -1283,6 +1755,29 @@ impl<'a> Parser<'a> {
         Some(Ok(question_mark))
     }
 
+    /// `√x` and `√(x + 1)` are turned into calls of `sqrt`
+    fn accept_square_root(&mut self) -> Result<AstNode> {
+        let range = self.accept(is(SquareRoot), ExpectedNumber)?.range;
+        let arguments = if self.peek(is(OpenBracket)).is_some() {
+            self.accept_call_arguments("sqrt")?
+        } else {
+            vec![vec![self.accept_number()?]]
+        };
+        let full_range = range.extend(self.tokens[self.index - 1].range);
+
+        Ok(AstNode::new(AstNodeData::Group(vec![
+            AstNode::new(AstNodeData::Identifier("sqrt".to_string()), range),
+            AstNode::new(AstNodeData::Operator(Operator::Call), range),
+            AstNode::new(AstNodeData::Arguments(arguments), full_range),
+        ]), full_range))
+    }
+
+    fn accept_string(&mut self) -> Result<AstNode> {
+        let token = self.accept(is(StringLiteral), ExpectedString)?;
+        let text = token.text[1..token.text.len() - 1].to_owned();
+        Ok(AstNode::new(AstNodeData::Object(CalculatorObject::String(StringObject { text })), token.range))
+    }
+
     fn accept_object(&mut self) -> Result<AstNode> {
         let open_bracket = self.accept(is(OpenCurlyBracket), ExpectedOpenCurlyBracket)?;
         let full_range_start = open_bracket.range;
@@ -1290,12 +1785,22 @@ impl<'a> Parser<'a> {
 
         let name = self.accept(is(Identifier), ExpectedObjectName)?;
         if !CalculatorObject::is_valid_object(&name.text) {
-            error!(UnknownObject(name.text.clone()): name.range);
+            error!(UnknownObject(name.text.to_string()): name.range);
         }
-        let name = (name.text.clone(), name.range);
+        let name = (name.text.to_string(), name.range);
 
-        let range_start = self.index;
         let mut args = vec![];
+        // The name can be followed by a colon (e.g. `{timestamp: 1700000000}`), which is part of
+        // the first argument if there's no space after it
+        if self.peek(is(ObjectArgs)).map_or(false, |token| token.text.starts_with(':')) {
+            let token = self.accept(is(ObjectArgs), Nothing).unwrap();
+            if token.text.len() > 1 {
+                let mut range = token.range;
+                range.start_char += 1;
+                args.push(ObjectArgument::String(token.text[1..].to_string(), range));
+            }
+        }
+
         while !self.has_reached_end() {
             let Some(token) = self.peek(all_except_newline()) else {
                 error!(ExpectedCloseCurlyBracket: self.error_range_at_end());
@@ -1310,7 +1815,7 @@ impl<'a> Parser<'a> {
                 CloseSquareBracket => error!(UnexpectedCloseBracket: token.range),
                 ObjectArgs => {
                     let token = self.accept(is(ObjectArgs), Nothing).unwrap();
-                    args.push(ObjectArgument::String(token.text.clone(), token.range))
+                    args.push(ObjectArgument::String(token.text.to_string(), token.range))
                 }
                 _ => error!(InvalidToken: token.range),
             }
@@ -1320,7 +1825,7 @@ impl<'a> Parser<'a> {
         let close_bracket_range = close_bracket.range;
         self.pop_skip_newline();
 
-        if self.index - 1 == range_start {
+        if args.is_empty() {
             error!(ExpectedElements: close_bracket_range);
         }
 
@@ -1343,7 +1848,11 @@ impl<'a> Parser<'a> {
         let mut nesting_level = 1usize;
         let start = self.index;
         while !self.has_reached_end() {
-            let Some(token) = self.try_accept(all_except_newline()) else { continue; };
+            let Some(token) = self.try_accept(all_except_newline()) else {
+                // Newlines that can't be skipped (e.g. empty lines) are ignored as well
+                self.index += 1;
+                continue;
+            };
             match token.ty {
                 OpenSquareBracket => nesting_level += 1,
                 CloseSquareBracket => {
@@ -1359,13 +1868,15 @@ impl<'a> Parser<'a> {
         }
 
         let tokens = &self.tokens[start..self.index - 1];
-        let mut parser = self.new_sub(tokens, false);
-        let ParserResultData::Calculation(ast) = parser.parse_single()?.data else { unreachable!(); };
-        self.question_mark = parser.question_mark;
-        Ok(ast)
+        let ast = self.parse_sub_calculation(tokens, false)?;
+        // Objects are created right away, so they can't wait for the parser of the line to apply
+        // the precedence
+        Ok(self.group_operations(ast))
     }
 
     fn accept_vector(&mut self) -> Result<AstNode> {
+        if self.is_interval() { return self.accept_interval(); }
+
         let opening_bracket = self.accept(is(OpenSquareBracket), ExpectedOpenSquareBracket)?;
         let open_bracket_range = opening_bracket.range;
         self.push_skip_newline(true);
@@ -1378,9 +1889,17 @@ impl<'a> Parser<'a> {
         let mut numbers = vec![];
         for tokens in tokens {
             // parse
-            let mut parser = self.new_sub(tokens, false);
-            let ParserResultData::Calculation(ast) = parser.parse_single()?.data else { unreachable!(); };
-            self.question_mark = parser.question_mark;
+            let ast = match self.parse_sub_calculation(tokens, false) {
+                Ok(ast) => ast,
+                Err(e) => {
+                    // The elements are independent of each other, so the others can still be checked
+                    self.recovered_errors.push(e);
+                    continue;
+                }
+            };
+            // The elements are evaluated right away, so they can't wait for the parser of the line
+            // to apply the precedence
+            let ast = self.group_operations(ast);
 
             // evaluate
             let _full_range = crate::engine::full_range(&ast);
@@ -1394,6 +1913,56 @@ impl<'a> Parser<'a> {
         self.maybe_with_call(vector, full_range)
     }
 
+    /// Whether the square brackets at the current token contain a comma that isn't inside of other
+    /// brackets, which makes them an interval (e.g. `[1, 2]`) instead of a vector
+    fn is_interval(&self) -> bool {
+        let mut nesting_level = 0usize;
+        for token in &self.tokens[self.index..] {
+            match token.ty {
+                OpenBracket | OpenSquareBracket | OpenCurlyBracket => nesting_level += 1,
+                CloseBracket | CloseSquareBracket | CloseCurlyBracket => {
+                    nesting_level -= 1;
+                    if nesting_level == 0 { return false; }
+                }
+                Comma if nesting_level == 1 => return true,
+                _ => {}
+            }
+        }
+        false
+    }
+
+    fn accept_interval(&mut self) -> Result<AstNode> {
+        let opening_bracket = self.accept(is(OpenSquareBracket), ExpectedOpenSquareBracket)?;
+        let open_bracket_range = opening_bracket.range;
+        self.push_skip_newline(true);
+
+        let tokens = self.accept_separated(open_bracket_range, Comma, CloseSquareBracket)?;
+        self.pop_skip_newline();
+
+        let full_range = open_bracket_range.extend(self.tokens[self.index - 1].range);
+        if tokens.len() > 2 {
+            let range = tokens[2].first().unwrap().range.extend(tokens.last().unwrap().last().unwrap().range);
+            error!(UnexpectedElements: range);
+        }
+
+        let mut bounds = [0.0; 2];
+        for (bound, tokens) in bounds.iter_mut().zip(tokens) {
+            let ast = self.parse_sub_calculation(tokens, false)?;
+            // The bounds are evaluated right away, so they can't wait for the parser of the line to
+            // apply the precedence
+            let ast = self.group_operations(ast);
+
+            let _full_range = crate::engine::full_range(&ast);
+            let Ok(crate::engine::NumberValue { number, .. }) =
+                Engine::evaluate_to_number(ast, self.context.clone())
+                else { error!(ExpectedNumber: _full_range); };
+            *bound = number;
+        }
+
+        let interval = Interval::new(bounds[0], bounds[1]).map_err(|ty| ty.with(full_range))?;
+        Ok(AstNode::new(AstNodeData::Object(CalculatorObject::Interval(interval)), full_range))
+    }
+
     fn accept_call_arguments(&mut self, function_name: &str) -> Result<Vec<Vec<AstNode>>> {
         let open_bracket_token = self.accept(is(OpenBracket), MissingOpeningBracket)?;
         let open_bracket_range = open_bracket_token.range;
@@ -1405,11 +1974,15 @@ impl<'a> Parser<'a> {
         let range_end = self.tokens[self.index - 1].range;
         let full_range = open_bracket_range.extend(range_end);
 
-        let function_args_count = self.context.borrow().env.function_argument_count(function_name).unwrap();
+        let function_args_count = match &self.defined_function {
+            Some((name, count)) if name == function_name => ArgCount::Single(*count),
+            _ => self.context.borrow().env.function_argument_count(function_name).unwrap(),
+        };
         if !function_args_count.is_valid_count(arguments.len()) {
             match function_args_count {
                 ArgCount::Single(count) => error!(WrongNumberOfArguments(count): full_range),
                 ArgCount::Multiple(options) => error!(WrongNumberOfArgumentsMultiple(options): full_range),
+                ArgCount::AtLeast(min) => error!(WrongNumberOfArgumentsAtLeast(min): full_range),
             }
         }
 
@@ -1452,30 +2025,66 @@ impl<'a> Parser<'a> {
     fn parse_arguments(&mut self, arguments: Vec<&'a [Token]>, allow_question_mark: bool) -> Result<Vec<Vec<AstNode>>> {
         let mut result = Vec::new();
         for tokens in arguments {
-            let mut parser = self.new_sub(tokens, allow_question_mark);
-            let ParserResultData::Calculation(ast) = parser.parse_single()?.data else { unreachable!(); };
-            self.question_mark = parser.question_mark;
-            result.push(ast);
+            match self.parse_sub_calculation(tokens, allow_question_mark) {
+                Ok(ast) => result.push(ast),
+                // The arguments are independent of each other, so the others can still be checked
+                Err(e) => {
+                    self.recovered_errors.push(e);
+                    result.push(vec![]);
+                }
+            }
         }
         Ok(result)
     }
 
+    /// Parses `tokens` (e.g. an argument) with a sub parser. Definitions are not allowed there.
+    fn parse_sub_calculation(&mut self, tokens: &'a [Token], allow_question_mark: bool) -> Result<Vec<AstNode>> {
+        let mut parser = self.new_sub(tokens, allow_question_mark);
+        let result = parser.parse_line();
+        self.question_mark = parser.question_mark;
+        self.precedence = parser.precedence;
+        let result = result.map_err(|mut errors| {
+            let error = errors.remove(0);
+            self.recovered_errors.append(&mut errors);
+            error
+        })?;
+
+        let ParserResultData::Calculation(ast) = result.data else {
+            let range = tokens.first().unwrap().range.extend(tokens.last().unwrap().range);
+            error!(UnexpectedDefinition: range);
+        };
+        Ok(ast)
+    }
+
     fn accept_separated<'b>(&mut self, open_bracket_range: SourceRange, separator: TokenType, end: TokenType) -> Result<Vec<&'b [Token]>>
         where 'a: 'b {
         let mut tokens: Vec<&'b [Token]> = vec![];
 
         let mut nesting_level = 1usize;
+        // Square brackets (e.g. of intervals) inside of the elements
+        let mut square_nesting_level = 0usize;
         let mut argument_start = self.index;
         while !self.has_reached_end() {
             let token = self.accept(all_except_newline(), ExpectedElements)?;
             let ty = token.ty;
+            if ty == OpenSquareBracket {
+                square_nesting_level += 1;
+                continue;
+            } else if ty == CloseSquareBracket && square_nesting_level != 0 {
+                square_nesting_level -= 1;
+                continue;
+            }
+
             if ty == OpenBracket {
                 nesting_level += 1
             } else if ty == CloseBracket {
+                if nesting_level == 1 && end != CloseBracket {
+                    error!(UnexpectedCloseBracket: token.range);
+                }
                 nesting_level -= 1;
                 // Ignore brackets that aren't on the base level
                 if nesting_level != 0 { continue; }
-            } else if ty == separator && nesting_level == 1 {
+            } else if ty == separator && nesting_level == 1 && square_nesting_level == 0 {
                 if argument_start == self.index - 1 {
                     let range = self.tokens[argument_start].range.extend(self.tokens[self.index - 1].range);
                     error!(ExpectedElements: range);
@@ -1523,11 +2132,14 @@ impl<'a> Parser<'a> {
             BitwiseAnd => operator!(BitwiseAnd),
             BitwiseOr => operator!(BitwiseOr),
             Xor => operator!(Xor),
+            Choose => operator!(Choose),
             BitShiftLeft => operator!(BitShiftLeft),
             BitShiftRight => operator!(BitShiftRight),
             Of => operator!(Of),
             In => operator!(In),
             Modulo => operator!(Modulo),
+            Remainder => operator!(Remainder),
+            PlusMinus => operator!(PlusMinus),
             _ => unreachable!(),
         };
 
@@ -1535,13 +2147,122 @@ impl<'a> Parser<'a> {
     }
 }
 
+/// Whether the line starting with `tokens` begins with an operator (e.g. `* 1.19`), which applies it
+/// to the result of the previous line. `+` and `-` only do this if a space follows them, so that
+/// e.g. `-3` is still a negative number.
+pub(crate) fn starts_with_continuation_operator(tokens: &[Token]) -> bool {
+    match tokens {
+        [operator, next, ..] if matches!(operator.ty, Plus | Minus) =>
+            next.range.start_line == operator.range.start_line && next.range.start_char > operator.range.end_char,
+        [operator, ..] => operator.ty.is_operator() && !matches!(operator.ty, Plus | Minus),
+        [] => false,
+    }
+}
+
+/// Applies `f` to `ast` and to the ASTs of all groups and arguments in it. Numbers in scientific
+/// notation are left alone, even though the parser turns them into groups.
+fn map_groups(ast: Vec<AstNode>, f: fn(Vec<AstNode>) -> Vec<AstNode>) -> Vec<AstNode> {
+    let ast = ast.into_iter()
+        .map(|mut node| {
+            node.data = match node.data {
+                AstNodeData::Group(group) if is_scientific_notation(&group) => AstNodeData::Group(group),
+                AstNodeData::Group(group) => AstNodeData::Group(map_groups(group, f)),
+                AstNodeData::Arguments(arguments) =>
+                    AstNodeData::Arguments(arguments.into_iter().map(|ast| map_groups(ast, f)).collect()),
+                data => data,
+            };
+            node
+        })
+        .collect();
+    f(ast)
+}
+
+/// Whether `group` is a number in scientific notation, which the parser turns into the group
+/// `(mantissa * 10 ^ exponent)`, where every node has the range of the mantissa
+fn is_scientific_notation(group: &[AstNode]) -> bool {
+    matches!(group, [mantissa, multiply, ..]
+        if multiply.data == AstNodeData::Operator(Operator::Multiply) && multiply.range == mantissa.range)
+}
+
+/// Whether `node` is a multiplication the parser inferred (e.g. in `2x`)
+fn is_implicit_multiplication(node: &AstNode) -> bool {
+    node.data == AstNodeData::Operator(Operator::Multiply) && node.range == SourceRange::empty()
+}
+
+/// Whether `node` is the operator `^`
+fn is_exponentiation(node: &AstNode) -> bool {
+    node.data == AstNodeData::Operator(Operator::Exponentiation)
+}
+
+/// Groups the implicit multiplications in `ast` together with their operands (e.g. `1 / 2x`
+/// becomes `1 / (2x)`). Powers are kept in the group as a whole, so that they still bind tighter
+/// than the multiplication (e.g. `2x^2` is `2(x^2)`).
+fn group_implicit_multiplications(ast: Vec<AstNode>) -> Vec<AstNode> {
+    let length = ast.len();
+    let mut result = vec![];
+    let mut multiplication: Vec<AstNode> = vec![];
+
+    let finish = |multiplication: &mut Vec<AstNode>, result: &mut Vec<AstNode>| {
+        if multiplication.is_empty() { return; }
+        let range = multiplication[0].range.extend(multiplication.last().unwrap().range);
+        result.push(AstNode::new(AstNodeData::Group(std::mem::take(multiplication)), range));
+    };
+
+    for node in ast {
+        if is_implicit_multiplication(&node) {
+            if multiplication.is_empty() {
+                // The first factor is the last operand, together with the power it's in
+                let mut start = result.len().saturating_sub(1);
+                while start >= 2 && is_exponentiation(&result[start - 1]) {
+                    start -= 2;
+                }
+                multiplication.extend(result.drain(start..));
+            }
+            multiplication.push(node);
+        } else if multiplication.last().map_or(false, |last| is_implicit_multiplication(last) || is_exponentiation(last))
+            || (!multiplication.is_empty() && is_exponentiation(&node)) {
+            multiplication.push(node);
+        } else {
+            finish(&mut multiplication, &mut result);
+            result.push(node);
+        }
+    }
+
+    // Don't put everything into another group if the whole AST is one multiplication
+    if multiplication.len() == length { return multiplication; }
+    finish(&mut multiplication, &mut result);
+    result
+}
+
+/// Groups the operations in `ast` so that they are evaluated from left to right (e.g.
+/// `2 + 3 * 4` becomes `(2 + 3) * 4`). `of`, `in` and `at` keep their low precedence, so that they
+/// still apply to everything before them.
+fn sequential(ast: Vec<AstNode>) -> Vec<AstNode> {
+    let mut result = vec![];
+    let mut current: Vec<AstNode> = vec![];
+    for node in ast {
+        match node.data {
+            AstNodeData::Operator(Operator::Of | Operator::In | Operator::At) => {
+                result.append(&mut current);
+                result.push(node);
+            }
+            AstNodeData::Operator(_) if current.len() == 3 => {
+                let range = current[0].range.extend(current[2].range);
+                current = vec![AstNode::new(AstNodeData::Group(std::mem::take(&mut current)), range), node];
+            }
+            _ => current.push(node),
+        }
+    }
+    result.append(&mut current);
+    result
+}
+
 #[cfg(test)]
 mod tests {
     use std::cell::RefCell;
     use std::rc::Rc;
-    use std::sync::Arc;
 
-    use crate::{Currencies, Environment, NumberValue, range, Settings};
+    use crate::{Environment, NumberValue, range, Settings};
     use crate::astgen::tokenizer::tokenize;
     use crate::ContextData;
     use crate::engine::Value;
@@ -1551,17 +2272,29 @@ mod tests {
 
     macro_rules! parse {
         ($input:expr) => {
-            Parser::from_tokens(&tokenize($input)?, Rc::new(RefCell::new(ContextData {
-                env: Environment::new(),
-                currencies: Arc::new(Currencies::none()),
-                settings: Settings::default(),
-            }))).parse_single()
+            Parser::from_tokens(&tokenize($input)?, Rc::new(RefCell::new(ContextData::default()))).parse_single()
         };
         ($input:expr, $context:expr) => {
             Parser::from_tokens(&tokenize($input)?, $context).parse_single()
         }
     }
 
+    /// All errors of the first line, including the ones the parser recovered from
+    macro_rules! parse_errors {
+        ($input:expr) => {
+            Parser::from_tokens(&tokenize($input)?, Rc::new(RefCell::new(ContextData::default()))).next().unwrap().err().unwrap()
+        };
+    }
+
+    macro_rules! rpn {
+        ($input:expr) => {
+            parse!($input, Rc::new(RefCell::new(ContextData {
+                settings: Settings { input_mode: InputMode::Rpn, ..Settings::default() },
+                ..ContextData::default()
+            })))
+        }
+    }
+
     macro_rules! assert_error_type {
         ($result:expr, $variant:pat) => {
             assert!(matches!($result.err().unwrap().error, $variant))
@@ -1587,7 +2320,7 @@ mod tests {
 
     macro_rules! boolean_expression {
         ($input:expr) => {
-            if let ParserResultData::BooleanExpression { lhs, rhs, operator } = parse!($input)?.data {
+            if let ParserResultData::BooleanExpression { lhs, rhs, operator, .. } = parse!($input)?.data {
                 (lhs, rhs, operator)
             } else {
                 panic!("Expected ParserResult::BooleanExpression");
@@ -1677,6 +2410,66 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn trailing_operator_continuation() -> Result<()> {
+        let tokens = tokenize("1 +\n2 *\n(3 -\n4) in\nm\n5")?;
+        let mut parser = Parser::from_tokens(&tokens, Rc::new(RefCell::new(ContextData::default())));
+
+        let result = parser.next().unwrap().map_err(|mut errors| errors.remove(0))?;
+        assert_eq!(result.line_range, 0..5);
+        let ParserResultData::Calculation(ast) = result.data else { panic!("Expected ParserResult::Calculation"); };
+        assert_eq!(ast.len(), 7);
+
+        let result = parser.next().unwrap().map_err(|mut errors| errors.remove(0))?;
+        assert_eq!(result.line_range, 5..6);
+        assert!(parser.next().is_none());
+
+        // Empty lines end the expression
+        let error = parse!("1 +\n\n2").err().unwrap();
+        assert!(matches!(error.error, ExpectedNumber));
+        assert_eq!(error.ranges, vec![range!(line 0 => 2..3)]);
+        assert_error_type!(parse!("1 +"), ExpectedNumber);
+        Ok(())
+    }
+
+    #[test]
+    fn error_recovery() -> Result<()> {
+        let tokens = tokenize("foo + sin(bar) * [2; baz] +\n\n1")?;
+        let mut parser = Parser::from_tokens(&tokens, Rc::new(RefCell::new(ContextData::default())));
+
+        let errors = parser.next().unwrap().err().unwrap();
+        assert_eq!(errors.len(), 4);
+        assert!(matches!(&errors[0].error, UnknownIdentifier(name, _) if name == "foo"));
+        assert!(matches!(&errors[1].error, UnknownIdentifier(name, _) if name == "bar"));
+        assert!(matches!(&errors[2].error, UnknownIdentifier(name, _) if name == "baz"));
+        assert!(matches!(errors[3].error, ExpectedNumber));
+        assert_eq!(errors[1].ranges, vec![range!(line 0 => 10..13)]);
+
+        assert!(parser.next().unwrap().is_ok());
+        assert_error_type!(parse!("1 + foo + bar"), UnknownIdentifier(..));
+        Ok(())
+    }
+
+    #[test]
+    fn missing_operand_recovery() -> Result<()> {
+        let errors = parse_errors!("(1 + ) * (2 + )");
+        assert_eq!(errors.len(), 2);
+        assert!(errors.iter().all(|error| matches!(error.error, ExpectedNumber)));
+        assert_eq!(errors[0].ranges, vec![range!(line 0 => 5..6)]);
+        assert_eq!(errors[1].ranges, vec![range!(line 0 => 14..15)]);
+
+        let errors = parse_errors!("1 + * 2 + sqrt( + 3");
+        assert_eq!(errors.len(), 2);
+        assert!(matches!(errors[0].error, ExpectedNumber));
+        assert_eq!(errors[0].ranges, vec![range!(line 0 => 4..5)]);
+        assert!(matches!(errors[1].error, MissingClosingBracket));
+
+        let errors = parse_errors!("1 + * 2 + foo");
+        assert_eq!(errors.len(), 2);
+        assert!(matches!(&errors[1].error, UnknownIdentifier(name, _) if name == "foo"));
+        Ok(())
+    }
+
     #[test]
     fn group_with_unit() -> Result<()> {
         let ast = calculation!("(3)km");
@@ -1855,6 +2648,20 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn conditional_function() -> Result<()> {
+        let (name, function) = func_definition!("fib(n) := if n < 2 then n else fib(n - 1) + fib(n - 2)");
+        assert_eq!(name, "fib");
+        let function = function.unwrap();
+        assert_eq!(function.variants.len(), 2);
+        assert!(matches!(function.variants[0].0, FunctionVariantType::BooleanVariant {..}));
+        assert!(matches!(function.variants[1].0, FunctionVariantType::Else));
+
+        let (_, function) = func_definition!("f(x) := if x < 0 then -1 else if x > 0 then 1 else 0");
+        assert_eq!(function.unwrap().variants.len(), 3);
+        Ok(())
+    }
+
     #[test]
     fn scientific_notation() -> Result<()> {
         fn test(ast: Vec<AstNode>) -> Result<()> {
@@ -1924,7 +2731,7 @@ mod tests {
     #[test]
     fn unknown_identifier() -> Result<()> {
         let ast = parse!("something");
-        assert_error_type!(ast, UnknownIdentifier(_));
+        assert_error_type!(ast, UnknownIdentifier(..));
         Ok(())
     }
 
@@ -1935,6 +2742,15 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn undefined_variable_update() -> Result<()> {
+        let err = parse!("x += 1");
+        assert_error_type!(err, UndefinedVariableUpdate(_));
+        let err = parse!("pi *= 2");
+        assert_error_type!(err, ReservedVariable(_));
+        Ok(())
+    }
+
     #[test]
     fn reserved_function() -> Result<()> {
         let err = parse!("sin(x) :=");
@@ -1949,6 +2765,25 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn mixed_measure() -> Result<()> {
+        let result = calculation!("5ft 11in in cm");
+        assert_eq!(result.len(), 3);
+        let AstNodeData::Group(group) = &result[0].data else { panic!("Expected a group"); };
+        assert_eq!(group.len(), 3);
+        assert_eq!(group[1].data, AstNodeData::Operator(Operator::Plus));
+        assert_eq!(group[2].unit.as_ref().map(|unit| unit.to_string()).as_deref(), Some("inch"));
+
+        assert_eq!(calculation!("1h 30min 15s").len(), 1);
+        // Only units of the same dimension are added up
+        assert_error_type!(parse!("2m 3s"), ExpectedOperator);
+        assert_error_type!(parse!("5ft 11 in cm"), ExpectedOperator);
+
+        let result = calculation!("180cm in ft_in");
+        assert!(matches!(&result[2].data, AstNodeData::Object(CalculatorObject::MixedMeasure(measure)) if measure.parts.len() == 2));
+        Ok(())
+    }
+
     #[test]
     fn expected_unit() -> Result<()> {
         let err = parse!("3km in k");
@@ -1967,6 +2802,23 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn interval() -> Result<()> {
+        let result = calculation!("[1, 2 * 3]");
+        assert_eq!(result.len(), 1);
+        assert!(matches!(result[0].data, AstNodeData::Object(CalculatorObject::Interval(Interval { lower, upper })) if lower == 1.0 && upper == 6.0));
+        assert!(matches!(calculation!("[1; 2]")[0].data, AstNodeData::Object(CalculatorObject::Vector(_))));
+
+        // The comma of an interval doesn't separate arguments
+        let result = calculation!("sin([1, 2])");
+        let AstNodeData::Group(group) = &result[0].data else { panic!("Expected a function call"); };
+        assert!(matches!(&group[2].data, AstNodeData::Arguments(args) if args.len() == 1));
+
+        assert_error_type!(parse!("[2, 1]"), InvalidInterval);
+        assert_error_type!(parse!("[1, 2, 3]"), UnexpectedElements);
+        Ok(())
+    }
+
     #[test]
     fn unknown_object() -> Result<()> {
         let err = parse!("{asdf}");
@@ -1981,8 +2833,7 @@ mod tests {
 
         let result = calculation!("4a", Rc::new(RefCell::new(ContextData {
             env,
-            currencies: Arc::new(Currencies::none()),
-            settings: Settings::default(),
+            ..ContextData::default()
         })));
         assert_eq!(result.len(), 3);
         assert!(matches!(result[2].data, AstNodeData::Identifier(_)));
@@ -2021,4 +2872,64 @@ mod tests {
         boolean_expression!("5 = 4.5 + sin(30°)");
         Ok(())
     }
+
+    #[test]
+    fn rpn() -> Result<()> {
+        let ParserResultData::Calculation(ast) = rpn!("3 4 + 2 *")?.data else { panic!("Expected ParserResult::Calculation"); };
+        assert_eq!(ast.len(), 3);
+        let AstNodeData::Group(group) = &ast[0].data else { panic!("Expected group"); };
+        assert_eq!(group.iter().map(|n| n.data.clone()).collect::<Vec<_>>(), vec![
+            AstNodeData::Literal(3.0),
+            AstNodeData::Operator(Operator::Plus),
+            AstNodeData::Literal(4.0),
+        ]);
+        assert_eq!(ast[1].data, AstNodeData::Operator(Operator::Multiply));
+
+        let ParserResultData::Calculation(ast) = rpn!("5 ! neg 2 m cm in *")?.data else { panic!("Expected ParserResult::Calculation"); };
+        assert_eq!(ast.len(), 3);
+        assert_eq!(ast[0].modifiers, vec![AstNodeModifier::Factorial, AstNodeModifier::Minus]);
+        let AstNodeData::Group(group) = &ast[2].data else { panic!("Expected group"); };
+        assert!(group[0].unit.is_some());
+        assert!(matches!(group[2].data, AstNodeData::Unit(_)));
+        Ok(())
+    }
+
+    #[test]
+    fn rpn_function_call() -> Result<()> {
+        let ParserResultData::Calculation(ast) = rpn!("2 3 + sqrt (1+1) *")?.data else { panic!("Expected ParserResult::Calculation"); };
+        assert_eq!(ast.len(), 3);
+        let AstNodeData::Group(call) = &ast[0].data else { panic!("Expected group"); };
+        assert_eq!(call[0].data, AstNodeData::Identifier("sqrt".to_string()));
+        let AstNodeData::Arguments(arguments) = &call[2].data else { panic!("Expected arguments"); };
+        assert_eq!(arguments.len(), 1);
+        assert_eq!(arguments[0].len(), 3);
+        assert!(matches!(ast[2].data, AstNodeData::Group(_)));
+        Ok(())
+    }
+
+    #[test]
+    fn rpn_boolean_expression_and_definition() -> Result<()> {
+        let ParserResultData::BooleanExpression { lhs, rhs, operator, .. } = rpn!("1 2 + 3 =")?.data else {
+            panic!("Expected ParserResult::BooleanExpression");
+        };
+        assert_eq!(lhs.len(), 3);
+        assert_eq!(rhs.len(), 1);
+        assert_eq!(operator, BooleanOperator::Equal);
+
+        let ParserResultData::VariableDefinition(name, Some(ast)) = rpn!("x := 1 2 -")?.data else {
+            panic!("Expected ParserResult::VariableDefinition");
+        };
+        assert_eq!(name, "x");
+        assert_eq!(ast.len(), 3);
+        Ok(())
+    }
+
+    #[test]
+    fn rpn_errors() -> Result<()> {
+        assert_error_type!(rpn!("3 +"), NotEnoughOperands);
+        assert_error_type!(rpn!("3 4"), TooManyOperands);
+        assert_error_type!(rpn!("1 = 2"), UnexpectedElements);
+        assert_error_type!(rpn!("f(x) := for x > 0: x"), UnsupportedInRpn);
+        Ok(())
+    }
 }
@@ -0,0 +1,263 @@
+/*
+ * Copyright (c) 2023, david072
+ *
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+//! Front-end for entering calculations in reverse polish notation (e.g. `3 4 + 2 *`).
+//!
+//! The input is split into *atoms*, which are runs of tokens that are not separated by
+//! whitespace (or that are enclosed in brackets). Atoms are either operators, which are applied to
+//! the values on the stack, or operands, which are parsed like normal (infix) input and pushed
+//! onto the stack. This way, the resulting AST is the same as the one of the equivalent infix
+//! input.
+
+use std::ops::Range;
+
+use super::*;
+
+impl<'a> Parser<'a> {
+    /// Accepts the rest of the line as an RPN expression.
+    ///
+    /// **Returns:** The AST and, if the expression ends with a boolean operator, information about
+    /// it. In that case, the AST contains both sides of the boolean expression.
+    pub(super) fn accept_rpn_expression(&mut self) -> Result<(Vec<AstNode>, Option<BooleanOperatorInfo>)> {
+        let atoms = self.accept_rpn_atoms()?;
+        if atoms.is_empty() { error!(ExpectedElements: self.error_range_at_end()); }
+
+        let mut stack: Vec<AstNode> = vec![];
+        for (i, atom) in atoms.iter().enumerate() {
+            let tokens = &self.tokens[atom.clone()];
+            let token = &tokens[0];
+
+            if let Some(unit) = self.try_accept_rpn_unit(tokens) {
+                let unit = unit?;
+                let range = token.range.extend(tokens.last().unwrap().range);
+                // A unit that is followed by `in` is the target of the conversion
+                let is_conversion_target = atoms.get(i + 1)
+                    .map_or(false, |next| self.tokens[next.start].ty == In);
+                if is_conversion_target {
+                    stack.push(AstNode::new(AstNodeData::Unit(unit), range));
+                    continue;
+                }
+
+                let Some(last) = stack.last_mut() else { error!(NotEnoughOperands: range); };
+                if last.unit.is_some() { error!(UnexpectedUnit: range); }
+                last.unit = Some(unit);
+                continue;
+            }
+
+            if tokens.len() > 1 || !self.try_apply_rpn_operator(token, &mut stack)? {
+                // Operand
+                let ast = self.parse_sub_calculation(tokens, true)?;
+                stack.push(ast_to_node(ast));
+                continue;
+            }
+
+            if let Some((operator, range)) = boolean_operator(token) {
+                if i != atoms.len() - 1 {
+                    error!(UnexpectedElements: self.tokens[atoms[i + 1].start].range);
+                }
+                if stack.len() < 2 { error!(NotEnoughOperands: range); }
+                if stack.len() > 2 { error!(TooManyOperands: stack[stack.len() - 3].range); }
+
+                let rhs = node_to_ast(stack.pop().unwrap());
+                let mut ast = node_to_ast(stack.pop().unwrap());
+                let ast_index = ast.len();
+
+                if let Some(question_mark) = &mut self.question_mark {
+                    question_mark.is_in_lhs = contains_question_mark(&ast);
+                }
+
+                ast.extend(rhs);
+                return Ok((ast, Some(BooleanOperatorInfo {
+                    operator,
+                    ast_index,
+                    token_index: atom.start,
+                    tolerance_index: None,
+                })));
+            }
+        }
+
+        if stack.len() > 1 { error!(TooManyOperands: stack[stack.len() - 2].range); }
+        Ok((node_to_ast(stack.remove(0)), None))
+    }
+
+    /// Splits the rest of the line into atoms and returns their token ranges
+    fn accept_rpn_atoms(&mut self) -> Result<Vec<Range<usize>>> {
+        let mut atoms = vec![];
+
+        while !self.has_reached_end() {
+            self.try_accept_line_continuation()?;
+            if self.has_reached_end() { break; }
+
+            let start = self.index;
+            let mut nesting_level = 0usize;
+            loop {
+                let token = &self.tokens[self.index];
+                match token.ty {
+                    // Postfix definitions and function variants only exist in infix notation
+                    PostfixDefinitionSign | For | If | Then | Else => error!(UnsupportedInRpn: token.range),
+                    OpenBracket | OpenSquareBracket | OpenCurlyBracket => nesting_level += 1,
+                    CloseBracket | CloseSquareBracket | CloseCurlyBracket => nesting_level = nesting_level.saturating_sub(1),
+                    _ => {}
+                }
+                self.index += 1;
+
+                let Some(next) = self.tokens.get(self.index) else { break; };
+                if nesting_level != 0 { continue; }
+                let is_adjacent = next.range.start_line == token.range.start_line
+                    && next.range.start_char == token.range.end_char;
+                if next.ty == Newline || next.ty == LineContinuation || !is_adjacent { break; }
+            }
+
+            atoms.push(start..self.index);
+        }
+
+        Ok(atoms)
+    }
+
+    /// Tries to parse `tokens` as a unit. Identifiers that are variables or functions take
+    /// precedence over units.
+    fn try_accept_rpn_unit(&self, tokens: &'a [Token]) -> Option<Result<Unit>> {
+        let first = &tokens[0];
+        match first.ty {
+            Identifier => {
+                if self.is_variable(&first.text) || self.context.borrow().env.is_valid_function(&first.text) {
+                    return None;
+                }
+            }
+            OpenSquareBracket => {}
+            _ => return None,
+        }
+
+        let mut parser = self.new_sub(tokens, false);
+        let unit = parser.try_accept_unit()?;
+        if parser.index != tokens.len() { return None; }
+        Some(unit)
+    }
+
+    /// Applies the operator in `token` to the values on the stack. Boolean operators are not
+    /// applied, since they have to be handled by the caller.
+    ///
+    /// **Returns:** Whether `token` is an operator
+    fn try_apply_rpn_operator(&self, token: &'a Token, stack: &mut Vec<AstNode>) -> Result<bool> {
+        let range = token.range;
+
+        macro_rules! pop {
+            () => {
+                match stack.pop() {
+                    Some(node) => node,
+                    None => error!(NotEnoughOperands: range),
+                }
+            }
+        }
+
+        macro_rules! last {
+            () => {
+                match stack.last_mut() {
+                    Some(node) => node,
+                    None => error!(NotEnoughOperands: range),
+                }
+            }
+        }
+
+        match token.ty {
+            ty if ty.is_boolean_operator() => return Ok(true),
+            ty if ty.is_operator() => {
+                let rhs = pop!();
+                let lhs = pop!();
+                let operator = Self::new_sub(self, std::slice::from_ref(token), false).accept_operator()?;
+                if operator.data == AstNodeData::Operator(Operator::In) && !matches!(rhs.data, AstNodeData::Unit(_)) {
+                    error!(ExpectedUnit: rhs.range);
+                }
+
+                let group_range = lhs.range.extend(range);
+                stack.push(AstNode::new(AstNodeData::Group(vec![lhs, operator, rhs]), group_range));
+            }
+            ExclamationMark => last!().modifiers.push(AstNodeModifier::Factorial),
+            PercentSign if token.text == "‰" => last!().modifiers.push(AstNodeModifier::Permille),
+            PercentSign => last!().modifiers.push(AstNodeModifier::Percent),
+            ty if ty.is_format() => last!().format = Format::from(ty),
+            Identifier => {
+                let name = token.text.as_str();
+                if self.is_variable(name) { return Ok(false); }
+
+                match name {
+                    "neg" => last!().modifiers.push(AstNodeModifier::Minus),
+                    "not" => last!().modifiers.push(AstNodeModifier::BitwiseNot),
+                    _ => {
+                        let Some(arg_count) = self.context.borrow().env.function_argument_count(name) else {
+                            return Ok(false);
+                        };
+                        let arg_count = match arg_count {
+                            ArgCount::Single(count) => count,
+                            ArgCount::Multiple(options) => options[0],
+                            ArgCount::AtLeast(min) => min,
+                        };
+                        if stack.len() < arg_count { error!(NotEnoughOperands: range); }
+
+                        let arguments = stack.split_off(stack.len() - arg_count);
+                        let group_range = arguments.first().map_or(range, |arg| arg.range.extend(range));
+                        let arguments = arguments.into_iter().map(node_to_ast).collect::<Vec<_>>();
+                        stack.push(AstNode::new(AstNodeData::Group(vec![
+                            AstNode::new(AstNodeData::Identifier(name.to_string()), range),
+                            AstNode::new(AstNodeData::Operator(Operator::Call), range),
+                            AstNode::new(AstNodeData::Arguments(arguments), range),
+                        ]), group_range));
+                    }
+                }
+            }
+            _ => return Ok(false),
+        }
+
+        Ok(true)
+    }
+
+    fn is_variable(&self, name: &str) -> bool {
+        self.context.borrow().env.is_valid_variable(name)
+            || self.extra_allowed_variables.as_ref().map_or(false, |vars| vars.iter().any(|v| v == name))
+    }
+}
+
+fn boolean_operator(token: &Token) -> Option<(BooleanOperator, SourceRange)> {
+    let operator = match token.ty {
+        EqualsSign => BooleanOperator::Equal,
+        NotEqualsSign => BooleanOperator::NotEqual,
+        GreaterThan => BooleanOperator::GreaterThan,
+        GreaterThanEqual => BooleanOperator::GreaterThanEqual,
+        LessThan => BooleanOperator::LessThan,
+        LessThanEqual => BooleanOperator::LessThanEqual,
+        ApproxEqualsSign => BooleanOperator::ApproximatelyEqual,
+        _ => return None,
+    };
+    Some((operator, token.range))
+}
+
+/// Wraps `ast` into a group, if it consists of more than one node
+fn ast_to_node(mut ast: Vec<AstNode>) -> AstNode {
+    if ast.len() == 1 { return ast.remove(0); }
+    let range = crate::engine::full_range(&ast);
+    AstNode::new(AstNodeData::Group(ast), range)
+}
+
+/// Unwraps groups without modifiers, so that the top level doesn't consist of a single group
+fn node_to_ast(node: AstNode) -> Vec<AstNode> {
+    let is_plain_group = node.modifiers.is_empty() && node.unit.is_none() && node.format == Format::Decimal
+        && matches!(&node.data, AstNodeData::Group(group)
+            if !matches!(group.get(1).map(|n| &n.data), Some(AstNodeData::Operator(Operator::Call))));
+
+    match node.data {
+        AstNodeData::Group(group) if is_plain_group => group,
+        _ => vec![node],
+    }
+}
+
+fn contains_question_mark(ast: &[AstNode]) -> bool {
+    ast.iter().any(|node| match &node.data {
+        AstNodeData::QuestionMark => true,
+        AstNodeData::Group(group) => contains_question_mark(group),
+        AstNodeData::Arguments(arguments) => arguments.iter().any(|arg| contains_question_mark(arg)),
+        _ => false,
+    })
+}
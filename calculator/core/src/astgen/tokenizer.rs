@@ -4,10 +4,18 @@
  * SPDX-License-Identifier: Apache-2.0
  */
 
+use std::collections::{HashMap, HashSet};
+use std::fmt::{Debug, Display, Formatter};
+use std::ops::{Deref, Range};
+use std::sync::Arc;
+use std::time::Duration;
+
 use crate::common::*;
+use crate::environment::currencies::CURRENCY_SYMBOLS;
+use crate::profiling::Stopwatch;
 use crate::range;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
 pub enum TokenType {
     Whitespace,
     Newline,
@@ -21,6 +29,7 @@ pub enum TokenType {
     DecimalLiteral,
     HexLiteral,
     BinaryLiteral,
+    StringLiteral,
     // Brackets
     OpenBracket,
     CloseBracket,
@@ -37,24 +46,33 @@ pub enum TokenType {
     BitwiseAnd,
     BitwiseOr,
     Xor,
+    /// `choose`, the binomial coefficient
+    Choose,
     BitShiftLeft,
     BitShiftRight,
     Of,
     In,
     Modulo,
+    Remainder,
+    PlusMinus,
     // Modifiers
     ExclamationMark,
     PercentSign,
+    /// `√`, which takes the square root of what follows it
+    SquareRoot,
     // Formats
     Decimal,
     Hex,
     Binary,
     Scientific,
+    Dms,
     // Identifier
     Identifier,
     ObjectArgs,
     DefinitionSign,
     PostfixDefinitionSign,
+    /// `+=`, `-=`, `*=`, `/=` or `^=`, which update the value of a variable
+    CompoundDefinitionSign,
     QuestionMark,
     // Boolean operators
     EqualsSign,
@@ -63,9 +81,17 @@ pub enum TokenType {
     GreaterThanEqual,
     LessThan,
     LessThanEqual,
+    /// `≈` or `~=`
+    ApproxEqualsSign,
     // Keywords
     For,
+    If,
+    Then,
     Else,
+    /// The tolerance of an approximate equality check (e.g. `within 0.01`)
+    Within,
+    /// `@ignore` at the end of a line, which hides its result like a trailing `;`
+    Ignore,
 }
 
 impl TokenType {
@@ -94,11 +120,14 @@ impl TokenType {
             | Self::BitwiseAnd
             | Self::BitwiseOr
             | Self::Xor
+            | Self::Choose
             | Self::BitShiftLeft
             | Self::BitShiftRight
             | Self::Of
             | Self::In
-            | Self::Modulo)
+            | Self::Modulo
+            | Self::Remainder
+            | Self::PlusMinus)
     }
 
     pub fn is_boolean_operator(&self) -> bool {
@@ -107,22 +136,83 @@ impl TokenType {
             | Self::GreaterThan
             | Self::GreaterThanEqual
             | Self::LessThan
-            | Self::LessThanEqual)
+            | Self::LessThanEqual
+            | Self::ApproxEqualsSign)
     }
 
     pub fn is_format(&self) -> bool {
-        matches!(self, Self::Decimal | Self::Hex | Self::Binary | Self::Scientific)
+        matches!(self, Self::Decimal | Self::Hex | Self::Binary | Self::Scientific | Self::Dms)
     }
 
     pub fn is_keyword(&self) -> bool {
-        matches!(self, Self::For | Self::Else)
+        matches!(self, Self::For | Self::If | Self::Then | Self::Else | Self::Within | Self::Ignore)
     }
 }
 
-#[derive(PartialEq, Eq, Debug, Clone)]
+/// A coarse classification of tokens for syntax highlighting. Unlike [TokenType], the kinds don't
+/// change when tokens are added to or split up in the tokenizer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenKind {
+    /// A decimal, hexadecimal or binary literal
+    Number,
+    /// A quoted string (e.g. `"speed: {} km/h"`)
+    String,
+    /// A variable, function, unit or object name
+    Identifier,
+    Operator,
+    BooleanOperator,
+    Bracket,
+    /// Dots, commas, colons, semicolons and line continuations
+    Punctuation,
+    /// A factorial, percent or root sign
+    Modifier,
+    /// An output format (e.g. `hex` in `255 in hex`)
+    Format,
+    Keyword,
+    /// The arguments of an object (e.g. `01.01.2023` in `{date 01.01.2023}`)
+    ObjectArguments,
+    /// The `:=` and `=:` of definitions and the `+=`, `-=` etc. of variable updates
+    Definition,
+    QuestionMark,
+    /// Characters that aren't valid in the input
+    Invalid,
+}
+
+impl From<TokenType> for TokenKind {
+    fn from(ty: TokenType) -> Self {
+        use TokenType::*;
+
+        if ty.is_literal() {
+            TokenKind::Number
+        } else if ty.is_operator() {
+            TokenKind::Operator
+        } else if ty.is_boolean_operator() {
+            TokenKind::BooleanOperator
+        } else if ty.is_format() {
+            TokenKind::Format
+        } else if ty.is_keyword() {
+            TokenKind::Keyword
+        } else {
+            match ty {
+                OpenBracket | CloseBracket | OpenSquareBracket | CloseSquareBracket
+                | OpenCurlyBracket | CloseCurlyBracket => TokenKind::Bracket,
+                Dot | Comma | LineContinuation | Colon | Semicolon | Whitespace | Newline => TokenKind::Punctuation,
+                ExclamationMark | PercentSign | SquareRoot => TokenKind::Modifier,
+                Identifier => TokenKind::Identifier,
+                StringLiteral => TokenKind::String,
+                ObjectArgs => TokenKind::ObjectArguments,
+                DefinitionSign | PostfixDefinitionSign | CompoundDefinitionSign => TokenKind::Definition,
+                QuestionMark => TokenKind::QuestionMark,
+                _ => unreachable!(),
+            }
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, serde::Serialize)]
 pub struct Token {
     pub ty: TokenType,
-    pub text: String,
+    pub text: TokenText,
     pub range: SourceRange,
 }
 
@@ -130,12 +220,88 @@ impl Token {
     pub fn empty_from_type(ty: TokenType) -> Self {
         Self {
             ty,
-            text: String::new(),
+            text: TokenText::from_static(""),
             range: SourceRange::empty(),
         }
     }
 }
 
+/// The text of a [Token]. The tokens of a source share it and only store where their text is in
+/// it, so that tokenizing (and cloning the tokens of the [TokenCache]) doesn't allocate a string
+/// for every token.
+#[derive(Clone)]
+pub struct TokenText(TokenTextRepr);
+
+#[derive(Clone)]
+enum TokenTextRepr {
+    Slice(Arc<str>, Range<u32>),
+    Static(&'static str),
+    /// Text that isn't in the source, e.g. the ASCII digits of superscript digits
+    Owned(Arc<str>),
+}
+
+impl TokenText {
+    pub const fn from_static(text: &'static str) -> Self {
+        Self(TokenTextRepr::Static(text))
+    }
+
+    fn slice(source: &Arc<str>, range: Range<usize>) -> Self {
+        Self(TokenTextRepr::Slice(source.clone(), range.start as u32..range.end as u32))
+    }
+
+    pub fn as_str(&self) -> &str {
+        match &self.0 {
+            TokenTextRepr::Slice(source, range) => &source[range.start as usize..range.end as usize],
+            TokenTextRepr::Static(text) => text,
+            TokenTextRepr::Owned(text) => text,
+        }
+    }
+}
+
+impl From<String> for TokenText {
+    fn from(text: String) -> Self {
+        Self(TokenTextRepr::Owned(text.into()))
+    }
+}
+
+impl Deref for TokenText {
+    type Target = str;
+
+    fn deref(&self) -> &str { self.as_str() }
+}
+
+impl PartialEq for TokenText {
+    fn eq(&self, other: &Self) -> bool { self.as_str() == other.as_str() }
+}
+
+impl Eq for TokenText {}
+
+impl PartialEq<str> for TokenText {
+    fn eq(&self, other: &str) -> bool { self.as_str() == other }
+}
+
+impl PartialEq<&str> for TokenText {
+    fn eq(&self, other: &&str) -> bool { self.as_str() == *other }
+}
+
+impl PartialEq<TokenText> for &str {
+    fn eq(&self, other: &TokenText) -> bool { *self == other.as_str() }
+}
+
+impl Debug for TokenText {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result { Debug::fmt(self.as_str(), f) }
+}
+
+impl Display for TokenText {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result { f.write_str(self.as_str()) }
+}
+
+impl serde::Serialize for TokenText {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
 pub fn tokenize(input: &str) -> Result<Vec<Token>> {
     let mut tokenizer = Tokenizer::new(input);
     let mut result = Vec::new();
@@ -150,12 +316,166 @@ pub fn tokenize(input: &str) -> Result<Vec<Token>> {
     Ok(result)
 }
 
+/// Tokenizes `input` like [tokenize], but continues after invalid characters, which are returned
+/// as [TokenKind::Invalid]. Whitespace and newlines are left out and the ranges are byte offsets
+/// into `input`.
+pub fn tokenize_with_kinds(input: &str) -> Vec<(Range<usize>, TokenKind)> {
+    let mut tokenizer = Tokenizer::new(input);
+    let mut result = Vec::new();
+
+    loop {
+        let start = tokenizer.index;
+        match tokenizer.next() {
+            Ok(Some(token)) => {
+                if !matches!(token.ty, TokenType::Whitespace | TokenType::Newline) {
+                    result.push((start..tokenizer.index, token.ty.into()));
+                }
+            }
+            Ok(None) => break,
+            Err(_) => {
+                while !input.is_char_boundary(tokenizer.index) {
+                    tokenizer.index += 1;
+                    tokenizer.line_index += 1;
+                }
+                result.push((start..tokenizer.index, TokenKind::Invalid));
+            }
+        }
+    }
+
+    result
+}
+
+/// Caches the tokens of single lines, so that lines that didn't change don't have to be tokenized
+/// again when the whole source is recalculated (e.g. after every keystroke in the GUI).
+#[derive(Debug, Default)]
+pub struct TokenCache {
+    lines: HashMap<String, Vec<Token>>,
+    /// How long tokenizing each line of the last source took. If the source had to be tokenized as
+    /// a whole, all of the time is attributed to the first line.
+    pub(crate) line_durations: Vec<Duration>,
+}
+
+impl TokenCache {
+    /// Tokenizes `source` like [tokenize], reusing the tokens of lines that were already tokenized
+    /// the last time. Only the lines of `source` are kept in the cache afterwards.
+    pub fn tokenize(&mut self, source: &str) -> Result<Vec<Token>> {
+        let mut used_lines = HashSet::new();
+        let mut result = Vec::new();
+        self.line_durations.clear();
+
+        let mut previous_line: Option<&str> = None;
+        for (i, line) in source.split('\n').enumerate() {
+            if let Some(previous_line) = previous_line {
+                let start = previous_line.len();
+                result.push(Token {
+                    ty: TokenType::Newline,
+                    text: TokenText::from_static("\n"),
+                    range: range!(line i - 1 => start..start + 1),
+                });
+            }
+            previous_line = Some(line);
+
+            let stopwatch = Stopwatch::start();
+            if !self.lines.contains_key(line) {
+                match tokenize_line(line) {
+                    Some(tokens) => { self.lines.insert(line.to_owned(), tokens); }
+                    // Let the tokenizer handle errors and objects spanning multiple lines
+                    None => {
+                        self.lines.clear();
+                        let stopwatch = Stopwatch::start();
+                        let result = tokenize(source);
+                        self.line_durations = vec![stopwatch.elapsed()];
+                        return result;
+                    }
+                }
+            }
+            self.line_durations.push(stopwatch.elapsed());
+
+            result.extend(self.lines[line].iter().map(|token| {
+                let mut token = token.clone();
+                token.range.start_line += i;
+                token.range.end_line += i;
+                token
+            }));
+            used_lines.insert(line);
+        }
+
+        self.lines.retain(|line, _| used_lines.contains(line.as_str()));
+        Ok(result)
+    }
+}
+
+/// Tokenizes a single line on its own. Returns `None` if the line can't be tokenized without the
+/// rest of the source, i.e. if it contains an error or an unclosed object.
+fn tokenize_line(line: &str) -> Option<Vec<Token>> {
+    let mut tokenizer = Tokenizer::new(line);
+    let mut result = Vec::new();
+
+    while let Some(token) = tokenizer.next().ok()? {
+        if token.ty != TokenType::Whitespace {
+            result.push(token);
+        }
+    }
+
+    if !tokenizer.current_object_stack.is_empty() { return None; }
+    Some(result)
+}
+
 const NUMBERS: &str = "0123456789_";
 const LETTERS: &str = "abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ_'‘’`\"";
 const HEXADECIMAL_CHARS: &str = "0123456789abcdefABCDEF_";
 const BINARY_DIGITS: &str = "01_";
 const WHITESPACE: &str = " \t\r";
 
+/// The identifiers that are keywords, which are case-insensitive
+const KEYWORDS: [(&str, TokenType); 19] = [
+    ("of", TokenType::Of),
+    ("in", TokenType::In),
+    ("mod", TokenType::Modulo),
+    ("rem", TokenType::Remainder),
+    ("xor", TokenType::Xor),
+    ("choose", TokenType::Choose),
+    ("decimal", TokenType::Decimal),
+    ("dec", TokenType::Decimal),
+    ("hex", TokenType::Hex),
+    ("binary", TokenType::Binary),
+    ("bin", TokenType::Binary),
+    ("scientific", TokenType::Scientific),
+    ("sci", TokenType::Scientific),
+    ("dms", TokenType::Dms),
+    ("for", TokenType::For),
+    ("if", TokenType::If),
+    ("then", TokenType::Then),
+    ("else", TokenType::Else),
+    ("within", TokenType::Within),
+];
+
+/// Unicode symbols of formulas copied from elsewhere and the tokens they stand for
+const UNICODE_SYMBOLS: [(&str, TokenType); 9] = [
+    ("π", TokenType::Identifier),
+    ("τ", TokenType::Identifier),
+    ("×", TokenType::Multiply),
+    ("·", TokenType::Multiply),
+    ("⋅", TokenType::Multiply),
+    ("÷", TokenType::Divide),
+    ("−", TokenType::Minus),
+    ("‰", TokenType::PercentSign),
+    ("≈", TokenType::ApproxEqualsSign),
+];
+
+/// Returns the ASCII digit or minus a superscript character stands for (e.g. `2` for `²`)
+fn from_superscript(c: char) -> Option<char> {
+    match c {
+        '⁰' => Some('0'),
+        '¹' => Some('1'),
+        '²' => Some('2'),
+        '³' => Some('3'),
+        '⁴'..='⁹' => char::from_digit(c as u32 - '⁰' as u32, 10),
+        '⁻' => Some('-'),
+        _ => None,
+    }
+}
+
 fn any_of(chars: &str) -> impl Fn(u8) -> bool + '_ {
     move |c| chars.contains(c as char)
 }
@@ -171,22 +491,28 @@ enum ObjectInformation {
 
 struct Tokenizer<'a> {
     source: &'a str,
+    /// The source the texts of the tokens point into
+    shared_source: Arc<str>,
     string: &'a [u8],
     index: usize,
     line_index: usize,
     current_line: usize,
     current_object_stack: Vec<ObjectInformation>,
+    /// Whether the exponentiation in front of superscript digits was already returned
+    is_in_superscript: bool,
 }
 
 impl<'a> Tokenizer<'a> {
-    pub fn new(source: &'a str) -> Tokenizer {
+    pub fn new(source: &'a str) -> Tokenizer<'a> {
         Tokenizer {
             source,
+            shared_source: source.into(),
             string: source.as_bytes(),
             index: 0,
             line_index: 0,
             current_line: 0,
             current_object_stack: vec![],
+            is_in_superscript: false,
         }
     }
 
@@ -196,6 +522,14 @@ impl<'a> Tokenizer<'a> {
         }
 
         let (start, start_char) = (self.index, self.line_index);
+        if self.is_unterminated_string() {
+            // The error covers the rest of the line, which would have been the string
+            self.index += self.string[start..].iter().take_while(|c| **c != b'\n').count();
+            self.line_index += self.index - start;
+            return Err(ErrorType::UnterminatedString
+                .with(range!(line self.current_line => start_char..self.line_index)));
+        }
+
         let next_ty = self.next_type();
         let end = self.index;
         self.line_index += end - start;
@@ -203,27 +537,28 @@ impl<'a> Tokenizer<'a> {
 
         match next_ty {
             Some(mut ty) => {
-                let slice = self.string[start..end].to_owned();
-                let slice = match String::from_utf8(slice) {
-                    Ok(v) => v,
-                    Err(e) => panic!("Failed to parse string '{:?}' ({}..{} in {:?}) ({})",
-                                     &self.string[start..end], start, end, self.string, e),
+                // Tokens should always end on char boundaries, but don't rely on it
+                let Some(slice) = self.source.get(start..end) else {
+                    let text = String::from_utf8_lossy(&self.string[start..end]).into_owned();
+                    return Err(ErrorType::InvalidCharacter(text)
+                        .with(range!(line self.current_line => start_char..end_char)));
+                };
+                let text = match (ty, slice) {
+                    // The exponentiation in front of superscript digits doesn't have any text
+                    (TokenType::Exponentiation, "") => TokenText::from_static("^"),
+                    (TokenType::Identifier, "π") => TokenText::from_static("pi"),
+                    (TokenType::Identifier, "τ") => TokenText::from_static("tau"),
+                    (TokenType::DecimalLiteral | TokenType::Minus, slice) if slice.chars().any(|c| from_superscript(c).is_some()) => {
+                        slice.chars().map(|c| from_superscript(c).unwrap_or(c)).collect::<String>().into()
+                    }
+                    _ => TokenText::slice(&self.shared_source, start..end),
                 };
 
                 if ty == TokenType::Identifier {
-                    ty = match slice.to_lowercase().as_str() {
-                        "of" => TokenType::Of,
-                        "in" => TokenType::In,
-                        "mod" => TokenType::Modulo,
-                        "xor" => TokenType::Xor,
-                        "decimal" | "dec" => TokenType::Decimal,
-                        "hex" => TokenType::Hex,
-                        "binary" | "bin" => TokenType::Binary,
-                        "scientific" | "sci" => TokenType::Scientific,
-                        "for" => TokenType::For,
-                        "else" => TokenType::Else,
-                        _ => ty,
-                    };
+                    // Compared without converting the identifier to lowercase, which would allocate
+                    if let Some((_, keyword)) = KEYWORDS.iter().find(|(text, _)| text.eq_ignore_ascii_case(slice)) {
+                        ty = *keyword;
+                    }
                 }
 
                 if let Some(ObjectInformation::TokensLeftUntilObject(counter)) = self.current_object_stack.last_mut() {
@@ -240,7 +575,7 @@ impl<'a> Tokenizer<'a> {
 
                 Ok(Some(Token {
                     ty,
-                    text: slice,
+                    text,
                     range,
                 }))
             }
@@ -286,7 +621,33 @@ impl<'a> Tokenizer<'a> {
         }
     }
 
+    /// Whether the character at `index` follows a value on the same line, ignoring whitespace
+    /// Whether a string starts at the current index, but there's no closing quote on the same line.
+    /// Right after a value, `"` is the unit of arc seconds or inches instead.
+    fn is_unterminated_string(&self) -> bool {
+        self.string.get(self.index) == Some(&b'"')
+            && !self.is_tokenizing_object_args()
+            && !self.follows_value(self.index)
+            && !self.string[self.index + 1..].iter()
+            .take_while(|c| **c != b'\n')
+            .any(|c| *c == b'"')
+    }
+
+    fn is_tokenizing_object_args(&self) -> bool {
+        matches!(self.current_object_stack.last(), Some(ObjectInformation::TokensLeftUntilObject(0))
+            | Some(ObjectInformation::IsTokenizingObjectArgs(true)))
+    }
+
+    fn follows_value(&self, index: usize) -> bool {
+        self.string[..index].iter()
+            .rev()
+            .find(|c| !WHITESPACE.contains(**c as char))
+            .map_or(false, |c| c.is_ascii_alphanumeric() || !c.is_ascii() || matches!(c, b')' | b']' | b'_' | b'\''))
+    }
+
     fn next_type(&mut self) -> Option<TokenType> {
+        let was_in_superscript = std::mem::take(&mut self.is_in_superscript);
+
         if self.accept(any_of(WHITESPACE)) {
             while self.accept(any_of(WHITESPACE)) {}
             return Some(TokenType::Whitespace);
@@ -354,6 +715,24 @@ impl<'a> Tokenizer<'a> {
             _ => {}
         }
 
+        // Superscript digits are an exponentiation (e.g. `m²` is `m^2`), so an exponentiation is
+        // returned before them without consuming anything
+        let superscript = |index: usize| self.source.get(index..)
+            .and_then(|str| str.chars().next())
+            .filter(|c| from_superscript(*c).is_some());
+        if let Some(c) = superscript(self.index) {
+            self.is_in_superscript = true;
+            if !was_in_superscript { return Some(TokenType::Exponentiation); }
+            if c == '⁻' {
+                self.index += c.len_utf8();
+                return Some(TokenType::Minus);
+            }
+            while let Some(c) = superscript(self.index).filter(|c| *c != '⁻') {
+                self.index += c.len_utf8();
+            }
+            return Some(TokenType::DecimalLiteral);
+        }
+
         let c = self.string[self.index];
         self.index += 1;
         let res = match c {
@@ -405,9 +784,19 @@ impl<'a> Tokenizer<'a> {
                     }
                 }
             }
-            b'+' => Some(TokenType::Plus),
+            b'+' | b'-' | b'*' | b'/' | b'^' if self.try_accept(b'=') => Some(TokenType::CompoundDefinitionSign),
+            b'+' => if self.try_accept(b'-') {
+                Some(TokenType::PlusMinus)
+            } else {
+                Some(TokenType::Plus)
+            },
             b'-' => Some(TokenType::Minus),
-            b'*' => Some(TokenType::Multiply),
+            // `**` is an alternative to `^`
+            b'*' => if self.try_accept(b'*') {
+                Some(TokenType::Exponentiation)
+            } else {
+                Some(TokenType::Multiply)
+            },
             b'/' => Some(TokenType::Divide),
             b'^' => Some(TokenType::Exponentiation),
             b'&' => Some(TokenType::BitwiseAnd),
@@ -437,6 +826,11 @@ impl<'a> Tokenizer<'a> {
                     Some(TokenType::ExclamationMark)
                 }
             }
+            b'~' => if self.try_accept(b'=') {
+                Some(TokenType::ApproxEqualsSign)
+            } else {
+                None
+            },
             b'%' => Some(TokenType::PercentSign),
             b'(' => Some(TokenType::OpenBracket),
             b')' => Some(TokenType::CloseBracket),
@@ -469,16 +863,48 @@ impl<'a> Tokenizer<'a> {
                 }
             }
             b';' => Some(TokenType::Semicolon),
+            b'@' if self.string[self.index..].starts_with(b"ignore")
+                && !self.string.get(self.index + 6).map_or(false, u8::is_ascii_alphanumeric) => {
+                self.index += 6;
+                Some(TokenType::Ignore)
+            }
             b'?' => Some(TokenType::QuestionMark),
+            // Right after a value, `"` is the unit of arc seconds or inches
+            // Unterminated strings are already reported in `next`
+            b'"' if !self.follows_value(self.index - 1) => {
+                while self.accept(all_but("\"\n")) {}
+                self.index += 1;
+                Some(TokenType::StringLiteral)
+            }
             _ => None
         };
 
         if res.is_some() { return res; }
 
-        if c == 0xC2 { // First byte of "°"
-            if self.try_accept(0xB0) { // Second byte of "°"
+        if let Some((symbol, ty)) = UNICODE_SYMBOLS.iter()
+            .find(|(symbol, _)| self.string[self.index - 1..].starts_with(symbol.as_bytes())) {
+            self.index += symbol.len() - 1;
+            return Some(*ty);
+        }
+
+        // Currency symbols are identifiers on their own, so that they can be written right before
+        // the amount (e.g. `$5`)
+        if let Some(symbol) = CURRENCY_SYMBOLS.iter()
+            .find(|symbol| self.string[self.index - 1..].starts_with(symbol.as_bytes())) {
+            self.index += symbol.len() - 1;
+            return Some(TokenType::Identifier);
+        }
+
+        if self.string[self.index - 1..].starts_with("√".as_bytes()) {
+            self.index += "√".len() - 1;
+            Some(TokenType::SquareRoot)
+        } else if c == 0xC2 { // First byte of "°", "µ" and "±"
+            // Second byte of "°" or of "µ" (the prefix micro)
+            if self.try_accept(0xB0) || self.try_accept(0xB5) {
                 while self.accept(any_of(LETTERS)) {}
                 Some(TokenType::Identifier)
+            } else if self.try_accept(0xB1) { // Second byte of "±"
+                Some(TokenType::PlusMinus)
             } else {
                 None
             }
@@ -511,7 +937,7 @@ mod tests {
         fn new(ty: TokenType, text: &str, range: Range<usize>) -> Token {
             Token {
                 ty,
-                text: text.to_owned(),
+                text: text.to_owned().into(),
                 range: SourceRange::line(0, range.start, range.end),
             }
         }
@@ -553,6 +979,38 @@ mod tests {
             Token::new(TokenType::PercentSign, "%", 11..12),
             Token::new(TokenType::EqualsSign, "=", 13..14),
         ]);
+
+        let tokens = tokenize("2**3 * √4")?;
+        assert_eq!(tokens, vec![
+            Token::new(TokenType::DecimalLiteral, "2", 0..1),
+            Token::new(TokenType::Exponentiation, "**", 1..3),
+            Token::new(TokenType::DecimalLiteral, "3", 3..4),
+            Token::new(TokenType::Multiply, "*", 5..6),
+            Token::new(TokenType::SquareRoot, "√", 7..10),
+            Token::new(TokenType::DecimalLiteral, "4", 10..11),
+        ]);
+        Ok(())
+    }
+
+    #[test]
+    fn unicode_symbols() -> Result<()> {
+        let tokens = tokenize("2π × 3 − m²÷4‰")?;
+        assert_eq!(tokens, vec![
+            Token::new(TokenType::DecimalLiteral, "2", 0..1),
+            Token::new(TokenType::Identifier, "pi", 1..3),
+            Token::new(TokenType::Multiply, "×", 4..6),
+            Token::new(TokenType::DecimalLiteral, "3", 7..8),
+            Token::new(TokenType::Minus, "−", 9..12),
+            Token::new(TokenType::Identifier, "m", 13..14),
+            Token::new(TokenType::Exponentiation, "^", 14..14),
+            Token::new(TokenType::DecimalLiteral, "2", 14..16),
+            Token::new(TokenType::Divide, "÷", 16..18),
+            Token::new(TokenType::DecimalLiteral, "4", 18..19),
+            Token::new(TokenType::PercentSign, "‰", 19..22),
+        ]);
+
+        let tokens = tokenize("x⁻¹²")?;
+        assert_eq!(tokens.iter().map(|token| token.text.as_str()).collect::<Vec<_>>(), ["x", "^", "-", "12"]);
         Ok(())
     }
 
@@ -586,6 +1044,16 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn keywords() -> Result<()> {
+        let tokens = tokenize("5 km IN Mi xor Hex inch")?;
+        let types = tokens.iter().map(|token| token.ty).collect::<Vec<_>>();
+        assert_eq!(types[2..], [TokenType::In, TokenType::Identifier, TokenType::Xor, TokenType::Hex, TokenType::Identifier]);
+        // The texts of the tokens are still the ones in the source
+        assert_eq!(tokens[2].text, "IN");
+        Ok(())
+    }
+
     #[test]
     fn non_ascii_chars() -> Result<()> {
         let tokens = tokenize("°")?;
@@ -595,7 +1063,7 @@ mod tests {
 
     #[test]
     fn boolean_operators() -> Result<()> {
-        let tokens = tokenize("= != < > <= >= > =")?;
+        let tokens = tokenize("= != < > <= >= > = ≈ ~=")?;
         assert_eq!(tokens.iter().map(|t| t.ty).collect::<Vec<_>>(), vec![
             TokenType::EqualsSign,
             TokenType::NotEqualsSign,
@@ -605,7 +1073,115 @@ mod tests {
             TokenType::GreaterThanEqual,
             TokenType::GreaterThan,
             TokenType::EqualsSign,
+            TokenType::ApproxEqualsSign,
+            TokenType::ApproxEqualsSign,
+        ]);
+        Ok(())
+    }
+
+    #[test]
+    fn plus_minus() -> Result<()> {
+        let tokens = tokenize("5 ± 0.1 +- 1 + -2")?;
+        assert_eq!(tokens.iter().map(|t| t.ty).collect::<Vec<_>>(), vec![
+            TokenType::DecimalLiteral,
+            TokenType::PlusMinus,
+            TokenType::DecimalLiteral,
+            TokenType::PlusMinus,
+            TokenType::DecimalLiteral,
+            TokenType::Plus,
+            TokenType::Minus,
+            TokenType::DecimalLiteral,
+        ]);
+        Ok(())
+    }
+
+    #[test]
+    fn compound_definition_signs() -> Result<()> {
+        let tokens = tokenize("x += 1 -= *= /= ^= ** +-")?;
+        assert_eq!(tokens.iter().map(|t| t.ty).collect::<Vec<_>>(), vec![
+            TokenType::Identifier,
+            TokenType::CompoundDefinitionSign,
+            TokenType::DecimalLiteral,
+            TokenType::CompoundDefinitionSign,
+            TokenType::CompoundDefinitionSign,
+            TokenType::CompoundDefinitionSign,
+            TokenType::CompoundDefinitionSign,
+            TokenType::Exponentiation,
+            TokenType::PlusMinus,
+        ]);
+        Ok(())
+    }
+
+    #[test]
+    fn strings() -> Result<()> {
+        let tokens = tokenize("fmt(\"{} # a\", 5\")")?;
+        assert_eq!(tokens, vec![
+            Token::new(TokenType::Identifier, "fmt", 0..3),
+            Token::new(TokenType::OpenBracket, "(", 3..4),
+            Token::new(TokenType::StringLiteral, "\"{} # a\"", 4..12),
+            Token::new(TokenType::Comma, ",", 12..13),
+            Token::new(TokenType::DecimalLiteral, "5", 14..15),
+            Token::new(TokenType::Identifier, "\"", 15..16),
+            Token::new(TokenType::CloseBracket, ")", 16..17),
         ]);
+
+        // Right after a value, it's the unit of arc seconds
+        let tokens = tokenize("5\"")?;
+        assert_eq!(tokens, vec![
+            Token::new(TokenType::DecimalLiteral, "5", 0..1),
+            Token::new(TokenType::Identifier, "\"", 1..2),
+        ]);
+        Ok(())
+    }
+
+    #[test]
+    fn unterminated_string() {
+        let error = tokenize("1 + \"abc").unwrap_err();
+        assert!(matches!(error.error, ErrorType::UnterminatedString));
+        assert_eq!(error.ranges, vec![SourceRange::line(0, 4, 8)]);
+
+        let error = tokenize("\"\n2").unwrap_err();
+        assert!(matches!(error.error, ErrorType::UnterminatedString));
+        assert_eq!(error.ranges, vec![SourceRange::line(0, 0, 1)]);
+
+        assert_eq!(tokenize_with_kinds("\"abc\n1"), vec![
+            (0..4, TokenKind::Invalid),
+            (5..6, TokenKind::Number),
+        ]);
+    }
+
+    #[test]
+    fn kinds() {
+        assert_eq!(tokenize_with_kinds("sin(2) @ 3 in hex"), vec![
+            (0..3, TokenKind::Identifier),
+            (3..4, TokenKind::Bracket),
+            (4..5, TokenKind::Number),
+            (5..6, TokenKind::Bracket),
+            (7..8, TokenKind::Invalid),
+            (9..10, TokenKind::Number),
+            (11..13, TokenKind::Operator),
+            (14..17, TokenKind::Format),
+        ]);
+        assert_eq!(tokenize_with_kinds("ä := 2"), vec![
+            (0..2, TokenKind::Invalid),
+            (3..5, TokenKind::Definition),
+            (6..7, TokenKind::Number),
+        ]);
+    }
+
+    #[test]
+    fn token_cache() -> Result<()> {
+        let mut cache = TokenCache::default();
+        for source in [
+            "1 + 2\nx := 3 # comment\n\n1 + 2",
+            "1 + 2\nx := 4 # comment\n\n1 + 2\t",
+            "{date 01.01.2023\n} + 3\n1 + 2",
+            "1 + 2\n°\ny := [2] ...\n3",
+        ] {
+            assert_eq!(cache.tokenize(source)?, tokenize(source)?);
+        }
+
+        assert!(cache.tokenize("1\n2 @").is_err());
         Ok(())
     }
 }
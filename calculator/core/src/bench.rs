@@ -0,0 +1,40 @@
+/*
+ * Copyright (c) 2023, david072
+ *
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+//! Exposes the individual stages of a calculation, so that they can be benchmarked on their own.
+//! This is not part of the public API.
+
+use crate::astgen::parser::{Parser, ParserResult};
+use crate::astgen::tokenizer::{tokenize as tokenize_source, Token};
+use crate::Calculator;
+
+pub struct Tokens(Vec<Token>);
+
+pub struct ParserResults(Vec<ParserResult>);
+
+pub fn tokenize(source: &str) -> Tokens {
+    Tokens(tokenize_source(source).unwrap_or_default())
+}
+
+/// Parses all calculations in `tokens`, ignoring the ones that fail to parse
+pub fn parse(tokens: &Tokens, calculator: &Calculator) -> ParserResults {
+    let mut parser = Parser::from_tokens(&tokens.0, calculator.context());
+    let mut results = vec![];
+    while let Some(result) = parser.next() {
+        if let Ok(result) = result {
+            results.push(result);
+        }
+    }
+    ParserResults(results)
+}
+
+/// Evaluates all calculations in `parser_results` and returns how many of them succeeded
+pub fn evaluate(parser_results: ParserResults, calculator: &mut Calculator) -> usize {
+    parser_results.0.into_iter()
+        .map(|result| calculator.handle_parser_result(result))
+        .filter(Result::is_ok)
+        .count()
+}
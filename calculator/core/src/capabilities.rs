@@ -0,0 +1,93 @@
+/*
+ * Copyright (c) 2023, david072
+ *
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+use std::fmt::{Display, Formatter};
+
+use crate::common::ErrorType;
+
+/// A kind of access to the outside world, which an embedding can deny the calculator
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Capability {
+    /// Accessing the network (e.g. for updating the currency exchange rates)
+    Network,
+    /// Reading and writing files (e.g. for caching the currency exchange rates)
+    Filesystem,
+    /// Reading the current date and time (e.g. for `{date now}`)
+    Clock,
+    /// Changing the environment by defining or removing variables and functions
+    Environment,
+}
+
+impl Display for Capability {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Network => write!(f, "network"),
+            Self::Filesystem => write!(f, "filesystem"),
+            Self::Clock => write!(f, "clock"),
+            Self::Environment => write!(f, "environment"),
+        }
+    }
+}
+
+/// The capabilities the calculator is allowed to use. By default, everything is allowed.
+/// Embeddings that need to lock the calculator down (e.g. a server) can use
+/// [Capabilities::none] and only allow what they need.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Capabilities {
+    pub network: bool,
+    pub filesystem: bool,
+    pub clock: bool,
+    pub environment: bool,
+}
+
+impl Default for Capabilities {
+    fn default() -> Self {
+        Self {
+            network: true,
+            filesystem: true,
+            clock: true,
+            environment: true,
+        }
+    }
+}
+
+impl Capabilities {
+    pub const fn none() -> Self {
+        Self {
+            network: false,
+            filesystem: false,
+            clock: false,
+            environment: false,
+        }
+    }
+
+    /// The capabilities for calculating untrusted documents (e.g. ones sent to a server), which
+    /// can't access files or the network or change the environment that other documents use
+    pub const fn sandboxed() -> Self {
+        Self {
+            clock: true,
+            ..Self::none()
+        }
+    }
+
+    pub fn allows(&self, capability: Capability) -> bool {
+        match capability {
+            Capability::Network => self.network,
+            Capability::Filesystem => self.filesystem,
+            Capability::Clock => self.clock,
+            Capability::Environment => self.environment,
+        }
+    }
+
+    /// Returns [ErrorType::MissingCapability] if `capability` is not allowed
+    pub(crate) fn require(&self, capability: Capability) -> Result<(), ErrorType> {
+        if self.allows(capability) {
+            Ok(())
+        } else {
+            Err(ErrorType::MissingCapability(capability))
+        }
+    }
+}
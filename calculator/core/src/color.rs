@@ -4,7 +4,7 @@
  * SPDX-License-Identifier: Apache-2.0
  */
 
-use crate::astgen::tokenizer::{Token, TokenType};
+use crate::astgen::tokenizer::{tokenize_with_kinds, Token, TokenKind, TokenType};
 use crate::common::SourceRange;
 
 use self::TokenType::*;
@@ -75,6 +75,8 @@ impl ColorSegment {
             IDENTIFIER_COLOR
         } else if ty.is_keyword() {
             Color::PINK
+        } else if *ty == StringLiteral {
+            Color::LIGHT_GREEN
         } else {
             match token.ty {
                 Whitespace | Newline => Color::TRANSPARENT,
@@ -86,19 +88,49 @@ impl ColorSegment {
                 | CloseCurlyBracket
                 | ExclamationMark
                 | PercentSign
+                | SquareRoot
                 | Comma
                 | LineContinuation
                 | Colon
                 | Semicolon
                 | EqualsSign
                 | DefinitionSign
-                | PostfixDefinitionSign => Color::WHITE,
+                | PostfixDefinitionSign
+                | CompoundDefinitionSign => Color::WHITE,
                 _ => unreachable!(),
             }
         };
 
         ColorSegment::new(token.range, color)
     }
+
+    /// Colors the text of a result (e.g. `600 cm` or `True (-1)`) the way its parts are colored in
+    /// the input, so that frontends can highlight their results consistently with it. Brackets,
+    /// punctuation and other characters are left to the frontend's color of results. The ranges
+    /// are in characters on line `0`.
+    pub fn output(text: &str) -> Vec<ColorSegment> {
+        let char_index = |byte: usize| text[..byte].chars().count();
+        tokenize_with_kinds(text).into_iter()
+            .filter_map(|(range, kind)| {
+                let color = match (kind, &text[range.clone()]) {
+                    (TokenKind::Identifier, "True") => Color::GREEN,
+                    (TokenKind::Identifier, "False") => Color::RED,
+                    (TokenKind::Number | TokenKind::QuestionMark, _) => Color::KHAKI,
+                    (TokenKind::Operator, _) => Color::GOLD,
+                    (TokenKind::String, _) => Color::LIGHT_GREEN,
+                    (TokenKind::Identifier | TokenKind::Format | TokenKind::ObjectArguments, _) => IDENTIFIER_COLOR,
+                    (TokenKind::Keyword, _) => Color::PINK,
+                    _ => return None,
+                };
+                Some(ColorSegment::new(SourceRange::line(0, char_index(range.start), char_index(range.end)), color))
+            })
+            .collect()
+    }
+
+    /// Colors the text of an error as a whole
+    pub fn error(text: &str) -> Vec<ColorSegment> {
+        vec![ColorSegment::new(SourceRange::line(0, 0, text.chars().count()), Color::RED)]
+    }
 }
 
 /// [egui](https://github.com/emilk/egui/blob/master/crates/epaint/src/color.rs)'s Color32
@@ -149,3 +181,21 @@ impl Color {
         Color::from_rgba_premultiplied(f(0), f(1), f(2), self.0[3])
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn output() {
+        let segments = |text: &str| ColorSegment::output(text).into_iter()
+            .map(|segment| (segment.range.start_char..segment.range.end_char, segment.color.0))
+            .collect::<Vec<_>>();
+        assert_eq!(segments("(5 ± 0.1) km"), [
+            (1..2, Color::KHAKI.0), (3..4, Color::GOLD.0), (5..8, Color::KHAKI.0), (10..12, IDENTIFIER_COLOR.0),
+        ]);
+        assert_eq!(segments("False (-1)"), [(0..5, Color::RED.0), (7..8, Color::GOLD.0), (8..9, Color::KHAKI.0)]);
+        assert_eq!(segments("True")[0].1, Color::GREEN.0);
+        assert_eq!(ColorSegment::error("Division by zero")[0].range, SourceRange::line(0, 0, 16));
+    }
+}
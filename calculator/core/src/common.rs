@@ -9,6 +9,8 @@ use std::path::PathBuf;
 
 use thiserror::Error;
 
+use crate::Capability;
+
 const CRATE_NAME: &str = "funcially";
 
 #[macro_export]
@@ -32,6 +34,8 @@ pub enum ErrorType {
     InvalidCharacter(String),
     #[error("Could not parse number ({0})")]
     InvalidNumber(String),
+    #[error("Missing closing quote")]
+    UnterminatedString,
 
     // parser
     #[error("Expected Number")]
@@ -48,10 +52,11 @@ pub enum ErrorType {
     MissingClosingBracket,
     #[error("Expected an identifier")]
     ExpectedIdentifier,
-    #[error("Unknown Identifier \"{0}\"")]
-    UnknownIdentifier(String),
-    #[error("Unknown Variable \"{0}\"")]
-    UnknownVariable(String),
+    /// The name together with similar names which may be meant instead
+    #[error("Unknown Identifier \"{0}\"{}", did_you_mean(.1))]
+    UnknownIdentifier(String, Vec<String>),
+    #[error("Unknown Variable \"{0}\"{}", did_you_mean(.1))]
+    UnknownVariable(String, Vec<String>),
     #[error("Missing equals sign")]
     MissingEqualsSign,
     #[error("Boolean operators are only allowed at the top level")]
@@ -60,12 +65,16 @@ pub enum ErrorType {
     DisallowedBooleanOperator,
     #[error("Second boolean operator")]
     UnexpectedSecondBooleanOperator,
-    #[error("Unknown function \"{0}\"")]
-    UnknownFunction(String),
+    #[error("\"within\" is only allowed once after ≈")]
+    UnexpectedWithin,
+    #[error("Unknown function \"{0}\"{}", did_you_mean(.1))]
+    UnknownFunction(String, Vec<String>),
     #[error("Wrong number of arguments (expected {0} arguments)")]
     WrongNumberOfArguments(usize),
     #[error("Wrong number of arguments (expected one of {0:?} arguments)")]
     WrongNumberOfArgumentsMultiple(&'static [usize]),
+    #[error("Wrong number of arguments (expected at least {0} arguments)")]
+    WrongNumberOfArgumentsAtLeast(usize),
     #[error("Expected unit")]
     ExpectedUnit,
     #[error("Unexpected unit")]
@@ -102,8 +111,12 @@ pub enum ErrorType {
     ExpectedComma,
     #[error("Expected a colon")]
     ExpectedColon,
+    #[error("Expected \"then\"")]
+    ExpectedThen,
     #[error("Can't redefine standard variable \"{0}\"")]
     ReservedVariable(String),
+    #[error("Can't update \"{0}\", since it is not defined")]
+    UndefinedVariableUpdate(String),
     #[error("Can't redefine standard function \"{0}\"")]
     ReservedFunction(String),
     #[error("Argument \"{0}\" already given")]
@@ -120,6 +133,8 @@ pub enum ErrorType {
     ExpectedObjectName,
     #[error("Invalid date")]
     InvalidDate,
+    #[error("Invalid time")]
+    InvalidTime,
     #[error("Expected a dot")]
     ExpectedDot,
     #[error("This number is too big")]
@@ -128,6 +143,12 @@ pub enum ErrorType {
     ExpectedNewline,
     #[error("Expected a function condition")]
     ExpectedFunctionVariantHead,
+    #[error("Not enough operands")]
+    NotEnoughOperands,
+    #[error("Too many operands")]
+    TooManyOperands,
+    #[error("This can't be written in RPN")]
+    UnsupportedInRpn,
     // Stupid
     #[error("The number is too big, or negative (found {0})")]
     NotU32(i32),
@@ -139,18 +160,34 @@ pub enum ErrorType {
     ExpectedIntegerWithOperator(String),
     #[error("Expected an integer, found {0}")]
     ExpectedInteger(f64),
+    #[error("{0} is outside of the supported integer range ({1} to {2})")]
+    IntegerOutOfRange(f64, f64, f64),
     #[error("Expected percentage for 'of' operator")]
     ExpectedPercentage,
     #[error("Expected a vector")]
     ExpectedVector,
+    #[error("Expected a function")]
+    ExpectedFunction,
+    #[error("Expected a string")]
+    ExpectedString,
+    #[error("Integer operations can't be applied to values with uncertainties")]
+    UncertainInteger,
+    #[error("There is no internal rate of return for these cash flows")]
+    NoInternalRateOfReturn,
     #[error("The lengths don't match")]
     VectorLengthsNotMatching,
+    #[error("The lower bound of an interval can't be greater than its upper bound")]
+    InvalidInterval,
+    #[error("The result isn't defined for all values of the interval")]
+    UndefinedInInterval,
     #[error("Argument 1 must be less than argument 2")]
     Arg1GreaterThanArg2,
     #[error("Unknown conversion ({0} -> {1})")]
     UnknownConversion(String, String),
     #[error("The units don't match")]
     UnitsNotMatching,
+    #[error("Incompatible dimensions ({0} and {1})")]
+    IncompatibleDimensions(String, String),
     #[error("Not a number")]
     NotANumber,
     #[error("Powers can't be used around the unknown variable")]
@@ -173,6 +210,40 @@ pub enum ErrorType {
     NotCallable,
     #[error("Cannot use multiple variants")]
     CannotUseQuestionMarkWithMultipleVariants,
+    #[error("Line {0} has no result")]
+    NoLineResult(usize),
+    #[error("No line above has a result")]
+    NoPreviousLineResult,
+    #[error("Sliders can only be assigned to variables (e.g. a := slider(0, 10, 1))")]
+    SliderOutsideDefinition,
+    #[error("The step of a slider must be positive")]
+    InvalidSliderStep,
+    #[error("The alignment must be positive")]
+    InvalidAlignment,
+    #[error("The width of a float must be 32 or 64 bits")]
+    InvalidFloatWidth,
+    #[error("Expected the bits of a float, found {0}")]
+    InvalidFloatBits(f64),
+    #[error("The exchange rates of {0} are not available (are you offline?)")]
+    ExchangeRatesUnavailable(String),
+    #[error("Only currencies can be converted with the exchange rates of a date")]
+    ExpectedCurrency,
+    #[error("Expected a date")]
+    ExpectedDate,
+    #[error("Unknown chemical element \"{0}\"")]
+    UnknownElement(String),
+    #[error("Invalid chemical formula \"{0}\"")]
+    InvalidChemicalFormula(String),
+    #[error("This needs access to the {0}, which is not allowed")]
+    MissingCapability(Capability),
+    #[error("This is nested too deeply (is a function calling itself?)")]
+    TooDeeplyNested,
+    #[error("Exceeded the maximum {0} (see the limits in the settings)")]
+    LimitExceeded(String),
+    #[error("The calculation was cancelled")]
+    Cancelled,
+    #[error("Error in script: {0}")]
+    ScriptError(String),
     /// This should never happen
     #[error("Invalid AST (this is a bug!)")]
     InvalidAst,
@@ -180,6 +251,21 @@ pub enum ErrorType {
     InvalidToken,
 }
 
+/// Problems that don't stop the calculation, but that the user should know about
+#[derive(Error, Debug, Clone)]
+pub enum WarningType {
+    #[error("Precision might be lost, since the number is too big to be represented exactly")]
+    PrecisionLost,
+    #[error("Converted {0} to {1}")]
+    UnitConverted(String, String),
+    #[error("\"{0}\" shadows the built-in constant with the same name")]
+    ConstantShadowed(String),
+    #[error("\"{0}\" shadows the unit with the same name")]
+    UnitShadowed(String),
+    #[error("\"{0}\" is never used")]
+    UnusedDefinition(String),
+}
+
 #[derive(
     Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, serde::Serialize, serde::Deserialize,
 )]
@@ -274,14 +360,47 @@ impl ErrorType {
     }
 }
 
+impl WarningType {
+    /// Whether the warning is only a hint about the input (e.g. a variable that is never used),
+    /// which frontends show less prominently than the other warnings
+    pub fn is_hint(&self) -> bool {
+        matches!(self, Self::ConstantShadowed(_) | Self::UnitShadowed(_) | Self::UnusedDefinition(_))
+    }
+
+    pub fn with(self, range: SourceRange) -> Warning {
+        Warning {
+            warning: self,
+            ranges: vec![range],
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Error {
     pub error: ErrorType,
+    /// Where the error occurred, followed by the ranges related to it (e.g. the other vector when
+    /// the lengths of two vectors don't match)
+    pub ranges: Vec<SourceRange>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Warning {
+    pub warning: WarningType,
     pub ranges: Vec<SourceRange>,
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
 
+/// The end of the message of an unknown name (e.g. `, did you mean "sqrt" or "sqr"?`)
+fn did_you_mean(suggestions: &[String]) -> String {
+    let quoted = suggestions.iter().map(|name| format!("\"{name}\"")).collect::<Vec<_>>();
+    match quoted.split_last() {
+        None => String::new(),
+        Some((last, [])) => format!(", did you mean {last}?"),
+        Some((last, rest)) => format!(", did you mean {} or {last}?", rest.join(", ")),
+    }
+}
+
 pub fn round_dp(n: f64, dp: i32) -> String {
     if n.is_nan() {
         return "NaN".to_owned();
@@ -317,12 +436,69 @@ pub mod math {
         (num * multiplier).round() / multiplier
     }
 
+    /// Calculates the factorial of `|num|`. `num` is expected to be an integer.
     pub fn factorial(num: f64) -> f64 {
-        if num == 0.0 || num == 1.0 {
-            1.0
-        } else {
-            let factor = if num.is_sign_negative() { -1.0 } else { 1.0 };
-            factor * factorial(num.abs() - 1.0) * num
+        let mut result = 1f64;
+        let mut factor = num.abs();
+        // The result is infinite for anything larger than 170, so there's no need to go on
+        while factor > 1.0 && result.is_finite() {
+            result *= factor;
+            factor -= 1.0;
+        }
+        result
+    }
+
+    /// The future value of an investment with the interest `rate` per period after `n` periods,
+    /// in which `pmt` is paid, starting with the present value `pv`. Like in spreadsheets, money that
+    /// is paid out is negative.
+    pub fn fv(rate: f64, n: f64, pmt: f64, pv: f64) -> f64 {
+        if rate == 0.0 { return -(pv + pmt * n); }
+        let growth = (1.0 + rate).powf(n);
+        -(pv * growth + pmt * (growth - 1.0) / rate)
+    }
+
+    /// The payment per period that pays off the present value `pv` in `n` periods with the
+    /// interest `rate` per period
+    pub fn pmt(rate: f64, n: f64, pv: f64) -> f64 {
+        if rate == 0.0 { return -pv / n; }
+        let growth = (1.0 + rate).powf(n);
+        -pv * rate * growth / (growth - 1.0)
+    }
+
+    /// The net present value of `cash_flows` at the end of consecutive periods with the discount
+    /// `rate` per period. Like in spreadsheets, the first cash flow is discounted as well.
+    pub fn npv(rate: f64, cash_flows: &[f64]) -> f64 {
+        cash_flows.iter()
+            .enumerate()
+            .map(|(i, cash_flow)| cash_flow / (1.0 + rate).powi(i as i32 + 1))
+            .sum()
+    }
+
+    /// The internal rate of return of `cash_flows`, i.e. the rate at which their net present value
+    /// is zero. Returns [None] if there is no such rate, e.g. because all cash flows have the same
+    /// sign.
+    pub fn irr(cash_flows: &[f64]) -> Option<f64> {
+        const ITERATIONS: usize = 100;
+        const TOLERANCE: f64 = 1e-10;
+        if !cash_flows.iter().any(|n| *n > 0.0) || !cash_flows.iter().any(|n| *n < 0.0) { return None; }
+
+        // Newton's method, starting at 10% like spreadsheets do
+        let mut rate = 0.1f64;
+        for _ in 0..ITERATIONS {
+            let (value, derivative) = cash_flows.iter()
+                .enumerate()
+                .fold((0.0, 0.0), |(value, derivative), (i, cash_flow)| (
+                    value + cash_flow / (1.0 + rate).powi(i as i32),
+                    derivative - i as f64 * cash_flow / (1.0 + rate).powi(i as i32 + 1),
+                ));
+            if value.abs() < TOLERANCE { return Some(rate); }
+
+            let next = rate - value / derivative;
+            // Rates of -100% or less don't make sense
+            if !next.is_finite() || next <= -1.0 { return None; }
+            if (next - rate).abs() < TOLERANCE { return Some(next); }
+            rate = next;
         }
+        None
     }
 }
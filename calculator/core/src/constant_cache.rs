@@ -0,0 +1,185 @@
+/*
+ * Copyright (c) 2023, david072
+ *
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::rc::Rc;
+
+use crate::astgen::ast::{AstNode, AstNodeData, AstNodeModifier, Operator};
+use crate::astgen::objects::CalculatorObject;
+use crate::engine::{Format, Value};
+use crate::environment::currencies::is_currency;
+use crate::environment::units::Unit;
+use crate::environment::Environment;
+use crate::Settings;
+
+/// How many results are kept at most. The cache is cleared when it's full.
+const MAX_ENTRIES: usize = 4096;
+
+/// The variables that can't be changed
+const CONSTANTS: [&str; 3] = ["pi", "e", "tau"];
+
+/// Standard functions whose results depend on more than their arguments
+const IMPURE_FUNCTIONS: [&str; 1] = ["line"];
+
+/// The results of calculations that don't depend on the environment (e.g. `factorize(2^40 - 1)`),
+/// which are kept across calculations, so that they aren't calculated again when a later line is
+/// changed. Since they don't use variables, custom functions or other lines, changes of the
+/// environment don't affect them. Changing the settings clears the cache.
+///
+/// Every calculator has its own cache, which is shared by the clones of its context.
+#[derive(Clone, Default)]
+pub struct ConstantCache(Rc<RefCell<Entries>>);
+
+#[derive(Default)]
+struct Entries {
+    /// The settings the results were calculated with
+    settings: Option<Settings>,
+    results: HashMap<Key, Value>,
+}
+
+impl ConstantCache {
+    /// Clears the cache if the results in it were calculated with other settings
+    pub(crate) fn check_settings(&self, settings: &Settings) {
+        let mut entries = self.0.borrow_mut();
+        if entries.settings.as_ref() != Some(settings) {
+            entries.settings = Some(*settings);
+            entries.results.clear();
+        }
+    }
+
+    pub(crate) fn get(&self, key: &Key) -> Option<Value> {
+        self.0.borrow().results.get(key).cloned()
+    }
+
+    pub(crate) fn insert(&self, key: Key, value: Value) {
+        let results = &mut self.0.borrow_mut().results;
+        if results.len() >= MAX_ENTRIES { results.clear(); }
+        results.insert(key, value);
+    }
+}
+
+/// The canonical form of an AST, which leaves out where it is in the source. Since the whole form
+/// is compared on lookup, different ASTs never share a result, even if their hashes collide.
+#[derive(Debug, PartialEq)]
+pub(crate) struct Key(Vec<Part>);
+
+// Literals are compared by their bits. Only objects containing NaN are not equal to themselves,
+// which just means that their results are not found again.
+impl Eq for Key {}
+
+impl Hash for Key {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        for part in &self.0 {
+            std::mem::discriminant(part).hash(state);
+            match part {
+                Part::Literal(bits) => bits.hash(state),
+                Part::Operator(operator) => operator.hash(state),
+                Part::Identifier(name) => name.hash(state),
+                Part::Unit(name, power) => (name, power).hash(state),
+                Part::UnitProduct(length) => length.hash(state),
+                // Objects only differ in their hash by their type and are compared on lookup
+                Part::Object(object) => std::mem::discriminant(object).hash(state),
+                Part::Node { modifiers, format, preferred_format, uncertainty, has_unit } =>
+                    (modifiers, format, preferred_format, uncertainty, has_unit).hash(state),
+                Part::GroupStart | Part::GroupEnd | Part::ArgumentStart | Part::ArgumentEnd | Part::UnitFraction => {}
+            }
+        }
+    }
+}
+
+/// A part of a [Key]. Nodes are written as their data, followed by [Part::Node] and their unit (if
+/// they have one). Units are written in prefix order.
+#[derive(Debug, PartialEq)]
+enum Part {
+    Literal(u64),
+    Operator(Operator),
+    GroupStart,
+    GroupEnd,
+    ArgumentStart,
+    ArgumentEnd,
+    Identifier(String),
+    Object(CalculatorObject),
+    Node {
+        modifiers: Vec<AstNodeModifier>,
+        format: Format,
+        preferred_format: Option<Format>,
+        uncertainty: u64,
+        has_unit: bool,
+    },
+    /// A product of as many units
+    UnitProduct(usize),
+    /// A fraction of the next two units
+    UnitFraction,
+    /// The name and the bits of the power of a unit
+    Unit(String, u64),
+}
+
+/// The key of the result of `ast`. Returns [None] if its result can't be cached, i.e. it uses
+/// variables, custom functions, other lines, currencies or `?`.
+pub(crate) fn key(ast: &[AstNode], env: &Environment) -> Option<Key> {
+    let mut parts = Vec::new();
+    write_ast(ast, env, &mut parts).then_some(Key(parts))
+}
+
+fn write_ast(ast: &[AstNode], env: &Environment, parts: &mut Vec<Part>) -> bool {
+    for node in ast {
+        match &node.data {
+            AstNodeData::Literal(n) => parts.push(Part::Literal(n.to_bits())),
+            AstNodeData::Operator(operator) => parts.push(Part::Operator(*operator)),
+            AstNodeData::Group(group) => {
+                parts.push(Part::GroupStart);
+                if !write_ast(group, env, parts) { return false; }
+                parts.push(Part::GroupEnd);
+            }
+            AstNodeData::Identifier(name) => {
+                let is_pure = CONSTANTS.contains(&name.as_str())
+                    || (env.is_standard_function(name) && !IMPURE_FUNCTIONS.contains(&name.as_str()));
+                if !is_pure { return false; }
+                parts.push(Part::Identifier(name.clone()));
+            }
+            AstNodeData::Unit(unit) => if !write_unit(unit, parts) { return false; },
+            AstNodeData::QuestionMark | AstNodeData::Object(CalculatorObject::Function(_)) => return false,
+            AstNodeData::Object(object) => parts.push(Part::Object(object.clone())),
+            AstNodeData::Arguments(arguments) => for argument in arguments {
+                parts.push(Part::ArgumentStart);
+                if !write_ast(argument, env, parts) { return false; }
+                parts.push(Part::ArgumentEnd);
+            },
+        }
+
+        parts.push(Part::Node {
+            modifiers: node.modifiers.clone(),
+            format: node.format,
+            preferred_format: node.preferred_format,
+            uncertainty: node.uncertainty.to_bits(),
+            has_unit: node.unit.is_some(),
+        });
+        if let Some(unit) = &node.unit {
+            if !write_unit(unit, parts) { return false; }
+        }
+    }
+    true
+}
+
+/// Returns false for currencies, whose exchange rates can change
+fn write_unit(unit: &Unit, parts: &mut Vec<Part>) -> bool {
+    match unit {
+        Unit::Product(units) => {
+            parts.push(Part::UnitProduct(units.len()));
+            units.iter().all(|unit| write_unit(unit, parts))
+        }
+        Unit::Fraction(numerator, denominator) => {
+            parts.push(Part::UnitFraction);
+            write_unit(numerator, parts) && write_unit(denominator, parts)
+        }
+        Unit::Unit(name, power, _) => {
+            parts.push(Part::Unit(name.clone(), power.to_bits()));
+            !is_currency(name)
+        }
+    }
+}
@@ -0,0 +1,196 @@
+/*
+ * Copyright (c) 2023, david072
+ *
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+//! Tracks which lines of a source use the results of which other lines. This is what's needed to
+//! only recalculate the lines affected by a change, and to show the structure of a worksheet.
+
+use std::collections::HashMap;
+
+use crate::astgen::parser::starts_with_continuation_operator;
+use crate::astgen::tokenizer::{Token, TokenType};
+use crate::references::{references_in_lines, tokenize_lines, IdentifierReference};
+
+/// Why a line depends on another one
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum DependencyKind {
+    /// The line uses the variable or function with this name, which the other line defines
+    Identifier(String),
+    /// The line uses the other line's result with `line(n)`
+    LineReference,
+    /// The line uses `ans`, which is the result of the other line
+    Ans,
+    /// The line uses `last` or starts with an operator (e.g. `* 1.19`), which both stand for the
+    /// result of the other line
+    Last,
+}
+
+/// An edge of the [DependencyGraph]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Dependency {
+    /// The (zero-based) line that is used, e.g. the one defining a variable
+    pub line: usize,
+    /// The (zero-based) line using `line`
+    pub dependent: usize,
+    pub kind: DependencyKind,
+}
+
+/// The dependencies between the lines of a source. It's built from the tokens of the lines alone,
+/// so it doesn't need the source to be calculated.
+///
+/// Since lines are calculated from top to bottom, a line only depends on lines above it. A usage of
+/// a name that is defined multiple times refers to the closest definition above it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DependencyGraph {
+    /// The dependencies, sorted by their dependent and then by the line they depend on
+    pub dependencies: Vec<Dependency>,
+    pub line_count: usize,
+}
+
+impl DependencyGraph {
+    pub fn new(source: &str) -> Self {
+        let lines = tokenize_lines(source);
+        let references = references_in_lines(&lines);
+        Self::from_references(&lines, &references)
+    }
+
+    /// Builds the graph from the tokens of the lines of a source and the references in them (see
+    /// [references_in_lines])
+    pub(crate) fn from_references(lines: &[Vec<Token>], references: &[IdentifierReference]) -> Self {
+        let mut dependencies = vec![];
+        // The line of the closest definition above the current line of every name
+        let mut definitions = HashMap::<&str, usize>::new();
+        let mut last_line_with_result = None;
+        let mut references = references.iter().peekable();
+
+        for (i, tokens) in lines.iter().enumerate() {
+            let is_update = tokens.iter().any(|token| token.ty == TokenType::CompoundDefinitionSign);
+            let mut defined_names = vec![];
+
+            while let Some(reference) = references.next_if(|reference| reference.range.start_line == i) {
+                // Updates (e.g. `x += 1`) use the previous value of the variable
+                if !reference.is_definition || is_update {
+                    if let Some(&line) = definitions.get(reference.name.as_str()) {
+                        dependencies.push(Dependency { line, dependent: i, kind: DependencyKind::Identifier(reference.name.clone()) });
+                    }
+                }
+                if reference.is_definition { defined_names.push(reference.name.as_str()); }
+            }
+
+            for number in line_references(tokens) {
+                if number == 0 || number > lines.len() || number - 1 == i { continue; }
+                dependencies.push(Dependency { line: number - 1, dependent: i, kind: DependencyKind::LineReference });
+            }
+
+            let uses = |name: &str| tokens.iter().any(|token| token.ty == TokenType::Identifier && token.text == name);
+            if let Some(line) = last_line_with_result.filter(|_| uses("ans")) {
+                dependencies.push(Dependency { line, dependent: i, kind: DependencyKind::Ans });
+            }
+            let uses_last = uses("last") || starts_with_continuation_operator(tokens);
+            if let Some(line) = last_line_with_result.filter(|_| uses_last) {
+                dependencies.push(Dependency { line, dependent: i, kind: DependencyKind::Last });
+            }
+
+            for name in defined_names {
+                definitions.insert(name, i);
+            }
+            if !tokens.is_empty() { last_line_with_result = Some(i); }
+        }
+
+        dependencies.sort_by_key(|dependency| (dependency.dependent, dependency.line));
+        dependencies.dedup();
+        Self { dependencies, line_count: lines.len() }
+    }
+
+    /// The dependencies of the (zero-based) line `line`, i.e. the lines it uses
+    pub fn dependencies_of(&self, line: usize) -> impl Iterator<Item=&Dependency> {
+        self.dependencies.iter().filter(move |dependency| dependency.dependent == line)
+    }
+
+    /// The dependencies on the (zero-based) line `line`, i.e. the lines using it
+    pub fn dependents_of(&self, line: usize) -> impl Iterator<Item=&Dependency> {
+        self.dependencies.iter().filter(move |dependency| dependency.line == line)
+    }
+
+    /// The lines whose results can change when the (zero-based) line `line` changes, i.e. the lines
+    /// that directly or indirectly depend on it (not including `line` itself), in ascending order
+    pub fn affected_lines(&self, line: usize) -> Vec<usize> {
+        let mut is_affected = vec![false; self.line_count];
+        if let Some(affected) = is_affected.get_mut(line) { *affected = true; }
+
+        // Dependents are always below the line they depend on, so a single pass is enough
+        for dependency in &self.dependencies {
+            if is_affected[dependency.line] { is_affected[dependency.dependent] = true; }
+        }
+
+        is_affected.into_iter()
+            .enumerate()
+            .filter(|(i, affected)| *affected && *i != line)
+            .map(|(i, _)| i)
+            .collect()
+    }
+}
+
+/// Finds the (one-based) line numbers of the line references (`line(<number>)`) in `tokens`
+fn line_references(tokens: &[Token]) -> Vec<usize> {
+    tokens.windows(4)
+        .filter_map(|window| match window {
+            [name, open, number, close] if name.ty == TokenType::Identifier && name.text == "line"
+                && open.ty == TokenType::OpenBracket
+                && number.ty == TokenType::DecimalLiteral
+                && close.ty == TokenType::CloseBracket => number.text.parse().ok(),
+            _ => None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn edges(source: &str) -> Vec<(usize, usize)> {
+        DependencyGraph::new(source).dependencies.into_iter()
+            .map(|dependency| (dependency.line, dependency.dependent))
+            .collect()
+    }
+
+    #[test]
+    fn identifiers() {
+        assert_eq!(edges("a := 5\nb := a * 2\na := 3\nf(x) := x + a\nf(b)"), [(0, 1), (2, 3), (1, 4), (3, 4)]);
+    }
+
+    #[test]
+    fn updates() {
+        assert_eq!(edges("x := 1\nx += x\nx * 2"), [(0, 1), (1, 2)]);
+    }
+
+    #[test]
+    fn line_references_and_ans() {
+        let graph = DependencyGraph::new("1 + 2\n\n# comment\nans * 2\nline(1) + line(4) + line(9)");
+        assert_eq!(graph.dependencies, [
+            Dependency { line: 0, dependent: 3, kind: DependencyKind::Ans },
+            Dependency { line: 0, dependent: 4, kind: DependencyKind::LineReference },
+            Dependency { line: 3, dependent: 4, kind: DependencyKind::LineReference },
+        ]);
+    }
+
+    #[test]
+    fn last_result() {
+        let graph = DependencyGraph::new("100\n* 1.19\n\nlast + 1\n-3\n- 3");
+        assert_eq!(graph.dependencies, [
+            Dependency { line: 0, dependent: 1, kind: DependencyKind::Last },
+            Dependency { line: 1, dependent: 3, kind: DependencyKind::Last },
+            Dependency { line: 4, dependent: 5, kind: DependencyKind::Last },
+        ]);
+    }
+
+    #[test]
+    fn affected_lines() {
+        let graph = DependencyGraph::new("a := 1\nb := 2\nc := a + 1\nd := c * b\n5");
+        assert_eq!(graph.affected_lines(0), [2, 3]);
+        assert_eq!(graph.affected_lines(1), [3]);
+        assert_eq!(graph.affected_lines(4), Vec::<usize>::new());
+    }
+}
@@ -0,0 +1,357 @@
+/*
+ * Copyright (c) 2023, david072
+ *
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+use std::ops::Range;
+
+use crate::astgen::tokenizer::{tokenize, Token, TokenType};
+use crate::common::WarningType;
+use crate::references::{references_in_lines, IdentifierReference};
+use crate::{Calculator, DependencyGraph, CalculatorResult, Color, ColorSegment, LineResult, Result, ResultData, SourceRange, Warning};
+
+const COMMENT_COLOR: Color = Color::GRAY;
+
+/// A complete input consisting of multiple lines, together with the results of its calculations.
+///
+/// This splits the results of [Calculator::calculate] into the lines of the source, so that
+/// frontends can show them next to the lines they belong to.
+#[derive(Debug, Default)]
+pub struct Document {
+    lines: Vec<DocumentLine>,
+}
+
+#[derive(Debug)]
+pub enum DocumentLineKind {
+    /// A line without a calculation (e.g. an empty line or one that only contains a comment)
+    Empty,
+    /// The first line of a calculation. Calculations can span multiple lines, in which case the
+    /// following lines are [DocumentLineKind::Continuation]s.
+    Result(Result<ResultData>),
+    /// A line belonging to the calculation that started on a previous line
+    Continuation,
+}
+
+#[derive(Debug)]
+pub struct DocumentLine {
+    pub kind: DocumentLineKind,
+    /// The color segments of the tokens in this line
+    pub color_segments: Vec<ColorSegment>,
+    /// The ranges of this line where an error occurred. A range covering multiple lines is split
+    /// up so that every line gets its own part.
+    pub error_ranges: Vec<SourceRange>,
+    /// The ranges of this line that are related to an error, but aren't where it occurred (e.g. the
+    /// second vector when the lengths of two vectors don't match). They are split up like
+    /// [DocumentLine::error_ranges].
+    pub related_error_ranges: Vec<SourceRange>,
+    /// The warnings of the calculation starting in this line
+    pub warnings: Vec<Warning>,
+    /// The notes the postprocessors added to the result of the calculation starting in this line
+    pub notes: Vec<String>,
+    /// Whether the result of the calculation starting in this line is hidden (see
+    /// [CalculatorResult::suppressed])
+    pub suppressed: bool,
+    /// The range of the comment (including the `#`) in this line
+    pub comment: Option<Range<usize>>,
+}
+
+/// What [Document::from_cached_results] finds out about a source from its tokens alone (the
+/// comments, references and dependencies of its lines). Frontends that split the results of the
+/// same source repeatedly (e.g. while they are still coming in) keep it, so that the source is only
+/// analyzed again once it changes.
+#[derive(Debug, Default)]
+pub struct DocumentCache {
+    source: Option<String>,
+    /// The tokens of every line on its own. Lines that cannot be tokenized are empty.
+    line_tokens: Vec<Vec<Token>>,
+    references: Vec<IdentifierReference>,
+    dependencies: DependencyGraph,
+    /// The start of the comment in every line
+    comments: Vec<Option<usize>>,
+}
+
+impl DocumentCache {
+    fn update(&mut self, source: &str) {
+        if self.source.as_deref() == Some(source) { return; }
+
+        let line_results = source.split('\n').map(tokenize).collect::<Vec<_>>();
+        // The lines of multi-line objects can only be tokenized together, and on their own, a `#`
+        // inside of them would start a comment
+        let source_tokens = line_results.iter().any(|result| result.is_err())
+            .then(|| tokenize(source).unwrap_or_default());
+        self.line_tokens = line_results.into_iter().map(|result| result.unwrap_or_default()).collect();
+
+        self.comments = source.split('\n').enumerate()
+            .map(|(i, line)| match &source_tokens {
+                Some(tokens) => comment_start(tokens.iter().filter(|token| token.range.start_line == i), line),
+                None => comment_start(self.line_tokens[i].iter(), line),
+            })
+            .collect();
+        self.references = references_in_lines(&self.line_tokens);
+        self.dependencies = DependencyGraph::from_references(&self.line_tokens, &self.references);
+        self.source = Some(source.to_owned());
+    }
+}
+
+impl Document {
+    /// Calculates `source` with `calculator`, starting with a fresh environment.
+    pub fn new(calculator: &mut Calculator, source: &str) -> Self {
+        calculator.reset();
+        let mut results = vec![];
+        calculator.calculate_all(source, |line| results.push(line));
+        Self::from_results(source, &results)
+    }
+
+    /// Splits `results`, which were calculated from `source` by [Calculator::calculate_all], into
+    /// the lines of the source. The results don't have to be complete (e.g. while they are still
+    /// being calculated on another thread), lines without a result are empty.
+    pub fn from_results(source: &str, results: &[LineResult]) -> Self {
+        Self::from_cached_results(&mut DocumentCache::default(), source, results)
+    }
+
+    /// Like [Document::from_results], but only analyzes `source` if `cache` belongs to another one
+    pub fn from_cached_results(cache: &mut DocumentCache, source: &str, results: &[LineResult]) -> Self {
+        cache.update(source);
+
+        // The first result starting in every line
+        let mut line_results = vec![None; cache.line_tokens.len()];
+        for result in results {
+            if let Some(line_result @ None) = line_results.get_mut(result.line_range.start) {
+                *line_result = Some(result);
+            }
+        }
+
+        let mut lines = vec![];
+        let mut current_result: Option<&LineResult> = None;
+
+        for (i, line) in source.split('\n').enumerate() {
+            let mut warnings = vec![];
+            let mut notes = vec![];
+            let mut suppressed = false;
+            let kind = if let Some(result) = line_results[i] {
+                let kind = DocumentLineKind::Result(result.result.data.clone().map(|(data, _)| data));
+                warnings = result.result.warnings.clone();
+                if result.result.data.is_ok() {
+                    warnings.extend(unused_definition(i, &cache.line_tokens[i], &cache.references, &cache.dependencies));
+                }
+                notes = result.result.notes.clone();
+                suppressed = result.result.suppressed;
+                current_result = Some(result);
+                kind
+            } else if current_result.as_ref().map_or(false, |res| res.line_range.contains(&i)) {
+                DocumentLineKind::Continuation
+            } else {
+                current_result = None;
+                DocumentLineKind::Empty
+            };
+
+            let (mut color_segments, error_ranges, related_error_ranges) = match (&kind, &current_result) {
+                (DocumentLineKind::Empty, _) | (_, None) => (vec![], vec![], vec![]),
+                (_, Some(result)) => (
+                    result.result.color_segments.iter()
+                        .filter(|seg| seg.range.start_line == i)
+                        .cloned()
+                        .collect(),
+                    error_ranges(&result.result, i, line.len(), false),
+                    error_ranges(&result.result, i, line.len(), true),
+                ),
+            };
+
+            let comment = cache.comments[i].map(|start| start..line.len());
+            if let Some(comment) = &comment {
+                color_segments.push(ColorSegment::new(SourceRange::line(i, comment.start, comment.end), COMMENT_COLOR));
+            }
+
+            lines.push(DocumentLine { kind, color_segments, error_ranges, related_error_ranges, warnings, notes, suppressed, comment });
+        }
+
+        Self { lines }
+    }
+
+    pub fn lines(&self) -> &[DocumentLine] { &self.lines }
+}
+
+/// A warning if the line `line` defines a variable or function that isn't used by any other line.
+/// Names used above the line count as used, since they can be in functions which are called after
+/// the line.
+fn unused_definition(line: usize, tokens: &[Token], references: &[IdentifierReference], dependencies: &DependencyGraph) -> Option<Warning> {
+    // Updates (e.g. `x += 1`) change variables which are already used
+    if tokens.iter().any(|token| token.ty == TokenType::CompoundDefinitionSign) { return None; }
+    let definition = references.iter().find(|reference| reference.is_definition && reference.range.start_line == line)?;
+    if dependencies.dependents_of(line).next().is_some() { return None; }
+
+    let is_used_above = references.iter().any(|reference| !reference.is_definition
+        && reference.name == definition.name
+        && reference.range.start_line <= line);
+    (!is_used_above).then(|| WarningType::UnusedDefinition(definition.name.clone()).with(definition.range))
+}
+
+/// The parts of the ranges of all errors in `result` that lie in the line `line`. These are either
+/// the ranges where the errors occurred or, if `related` is true, the other ones.
+fn error_ranges(result: &CalculatorResult, line: usize, line_length: usize, related: bool) -> Vec<SourceRange> {
+    result.errors().into_iter()
+        .flat_map(|error| if related { error.ranges.get(1..) } else { error.ranges.get(..1) }.unwrap_or_default())
+        .filter(|range| range.contains_line(line))
+        .map(|range| {
+            let start = if range.start_line == line { range.start_char } else { 0 };
+            // Ranges spanning multiple lines extend to the end of the line
+            let end = if range.end_line == line + 1 { range.end_char } else { line_length };
+            SourceRange::line(line, start, end.max(start))
+        })
+        .collect()
+}
+
+/// Finds the start of the comment in `line`, whose tokens are `tokens`. `#` characters that are
+/// part of a token (e.g. inside of objects) don't start a comment.
+fn comment_start<'a>(tokens: impl Iterator<Item = &'a Token> + Clone, line: &str) -> Option<usize> {
+    line.char_indices()
+        .filter(|(_, c)| *c == '#')
+        .map(|(i, _)| i)
+        .find(|i| !tokens.clone().any(|token| (token.range.start_char..token.range.end_char).contains(i)))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::calculator;
+    use crate::Value;
+
+    use super::*;
+
+    fn document(source: &str) -> Document {
+        Document::new(&mut calculator(), source)
+    }
+
+    #[test]
+    fn lines() {
+        let document = document("1 + 2 # three\n\n# comment\n(3 +\n4)\n5 +");
+        let lines = document.lines();
+        assert_eq!(lines.len(), 6);
+
+        assert!(matches!(&lines[0].kind, DocumentLineKind::Result(Ok(ResultData::Value(_)))));
+        assert_eq!(lines[0].comment, Some(6..13));
+        assert!(matches!(lines[1].kind, DocumentLineKind::Empty));
+        assert!(matches!(lines[2].kind, DocumentLineKind::Empty));
+        assert_eq!(lines[2].comment, Some(0..9));
+        assert!(matches!(&lines[3].kind, DocumentLineKind::Result(Ok(_))));
+        assert!(matches!(lines[4].kind, DocumentLineKind::Continuation));
+        assert!(!lines[4].color_segments.is_empty());
+
+        assert!(matches!(&lines[5].kind, DocumentLineKind::Result(Err(_))));
+        assert!(!lines[5].error_ranges.is_empty());
+    }
+
+    #[test]
+    fn suppressed_results() {
+        let document = document("x := 3;\nx * 2 @ignore\nx\nfoo;");
+        let suppressed = document.lines().iter().map(|line| line.suppressed).collect::<Vec<_>>();
+        assert_eq!(suppressed, [true, true, false, false]);
+        assert!(document.lines()[1].color_segments.iter().any(|seg| seg.range.start_char == 6));
+    }
+
+    #[test]
+    fn multiple_errors() {
+        let document = document("foo + 2 * bar");
+        let line = &document.lines()[0];
+        assert!(matches!(&line.kind, DocumentLineKind::Result(Err(_))));
+        assert_eq!(line.error_ranges, vec![SourceRange::line(0, 0, 3), SourceRange::line(0, 10, 13)]);
+        assert!(line.related_error_ranges.is_empty());
+    }
+
+    #[test]
+    fn unused_definitions() {
+        let document = document("a := 1\nb := a * 2\nf(x) := x * k\nk := 3\nf(2)\nc := 4\nc := 5\nc += 1\nfoo := bar");
+        let unused = document.lines().iter()
+            .map(|line| line.warnings.iter()
+                .filter_map(|warning| match &warning.warning {
+                    WarningType::UnusedDefinition(name) => Some((name.as_str(), warning.ranges[0])),
+                    _ => None,
+                })
+                .collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        assert_eq!(unused, [
+            vec![],
+            vec![("b", SourceRange::line(1, 0, 1))],
+            vec![],
+            // `k` is used in `f`, which is called later
+            vec![],
+            vec![],
+            vec![("c", SourceRange::line(5, 0, 1))],
+            // Updates use the variable
+            vec![],
+            vec![],
+            // Failed definitions don't define anything
+            vec![],
+        ]);
+    }
+
+    #[test]
+    fn related_error_ranges() {
+        let document = document("[1; 2] + [1; 2; 3]");
+        let line = &document.lines()[0];
+        assert_eq!(line.error_ranges, vec![SourceRange::line(0, 0, 6)]);
+        assert_eq!(line.related_error_ranges, vec![SourceRange::line(0, 9, 18)]);
+    }
+
+    #[test]
+    fn line_references() {
+        let document = document("1 + 2\n\nline(1) * 2\nline(2)\nline(5)\nline(1.5)");
+        let number = |line: &DocumentLine| match &line.kind {
+            DocumentLineKind::Result(Ok(ResultData::Value(Value::Number(number)))) => number.number,
+            kind => panic!("Expected a number, found {kind:?}"),
+        };
+
+        let lines = document.lines();
+        assert_eq!(number(&lines[2]), 6.0);
+        for line in &lines[3..] {
+            assert!(matches!(&line.kind, DocumentLineKind::Result(Err(_))));
+        }
+    }
+
+    #[test]
+    fn cached_results() {
+        let results = |source: &str| {
+            let mut results = vec![];
+            calculator().calculate_all(source, |line| results.push(line));
+            results
+        };
+        let is_unused = |line: &DocumentLine| line.warnings.iter()
+            .any(|warning| matches!(warning.warning, WarningType::UnusedDefinition(_)));
+        let mut cache = DocumentCache::default();
+
+        // While the results are still coming in, the source is only analyzed once
+        let source = "x := 2 # two\nx * 3";
+        let source_results = results(source);
+        let document = Document::from_cached_results(&mut cache, source, &source_results[..1]);
+        assert!(matches!(&document.lines()[0].kind, DocumentLineKind::Result(Ok(_))));
+        assert!(matches!(document.lines()[1].kind, DocumentLineKind::Empty));
+        let document = Document::from_cached_results(&mut cache, source, &source_results);
+        assert_eq!(document.lines()[0].comment, Some(7..12));
+        assert!(!is_unused(&document.lines()[0]));
+        assert!(matches!(&document.lines()[1].kind, DocumentLineKind::Result(Ok(_))));
+
+        // Another source is analyzed again
+        let source = "y := 1\n2 # comment";
+        let document = Document::from_cached_results(&mut cache, source, &results(source));
+        assert!(is_unused(&document.lines()[0]));
+        assert_eq!(document.lines()[0].comment, None);
+        assert_eq!(document.lines()[1].comment, Some(2..11));
+    }
+
+    #[test]
+    fn trailing_operator_continuation() {
+        let document = document("2 *\n3 + # comment\n4\n5 +\n\n6");
+        let lines = document.lines();
+        assert_eq!(lines.len(), 6);
+
+        assert!(matches!(&lines[0].kind, DocumentLineKind::Result(Ok(ResultData::Value(_)))));
+        assert!(matches!(lines[1].kind, DocumentLineKind::Continuation));
+        assert_eq!(lines[1].comment, Some(4..13));
+        assert!(matches!(lines[2].kind, DocumentLineKind::Continuation));
+
+        assert!(matches!(&lines[3].kind, DocumentLineKind::Result(Err(_))));
+        assert_eq!(lines[3].error_ranges, vec![SourceRange::line(3, 2, 3)]);
+        assert!(matches!(lines[4].kind, DocumentLineKind::Empty));
+        assert!(matches!(&lines[5].kind, DocumentLineKind::Result(Ok(_))));
+    }
+}
@@ -4,78 +4,159 @@
  * SPDX-License-Identifier: Apache-2.0
  */
 
+use std::cell::Cell;
 use std::fmt::{Display, Formatter};
 use std::mem::{replace, take};
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::{Duration, Instant};
 
-use crate::{astgen::ast::{AstNode, AstNodeData, Operator}, astgen::tokenizer::TokenType, common::*, Context, Currencies, environment::{Environment, units::convert as convert_units, Variable}, error, match_ast_node, Settings};
-use crate::astgen::ast::BooleanOperator;
-use crate::astgen::objects::CalculatorObject;
+use crate::{astgen::ast::{AstNode, AstNodeData, Operator}, astgen::tokenizer::TokenType, common::*, Context, Currencies, Difference, environment::{currencies::is_currency, Environment, units::convert as convert_units, Variable}, error, match_ast_node, DataPrefixes, OutputSettings, ResultDisplay, Settings, ToleranceSettings, WordSize, MAX_DECIMAL_PLACES};
+use crate::astgen::ast::{BooleanOperator, MAX_EXACT_INTEGER};
+use crate::constant_cache;
+use crate::astgen::objects::{CalculatorObject, DateTimeObject, Interval, StringObject, Vector};
 use crate::common::ErrorType::CannotUseQuestionMarkWithMultipleVariants;
-use crate::environment::FunctionVariantType;
-use crate::environment::units::Unit;
+use crate::environment::{ArgCount, FunctionVariantType};
+use crate::environment::dimensions::{compatible_units, Dimension};
+use crate::environment::chemistry::molar_mass;
+use crate::environment::number_theory;
+use crate::environment::units::{auto_prefix, Unit};
+
+#[derive(PartialEq, Hash, Eq, Debug, Copy, Clone, serde::Serialize, serde::Deserialize)]
+pub enum Format { Decimal, Hex, Binary, Scientific, Dms }
+
+pub(crate) const DECIMAL_PLACES: i32 = 10;
+/// Decimal numbers outside of this range are shown in scientific notation if
+/// [OutputSettings::auto_scientific] is enabled
+const SCIENTIFIC_LOWER_THRESHOLD: f64 = 1e-6;
+const SCIENTIFIC_UPPER_THRESHOLD: f64 = 1e15;
+/// The smallest number that is too big for an i64 (2^63)
+const I64_LIMIT: f64 = 9223372036854775808.0;
+
+/// How deeply evaluations can be nested (e.g. through groups or function calls), so that deeply
+/// nested or recursive calculations don't overflow the stack. Every call of a custom function
+/// nests two evaluations, so this leaves room for [crate::LimitSettings::max_recursion_depth].
+const MAX_EVALUATION_DEPTH: usize = 1500;
+/// The stack size threads need to calculate evaluations nested [MAX_EVALUATION_DEPTH] levels deep,
+/// including in debug builds, whose stack frames are much larger
+pub const CALCULATION_STACK_SIZE: usize = 256 * 1024 * 1024;
+
+thread_local! {
+    static EVALUATION_DEPTH: Cell<usize> = const { Cell::new(0) };
+    /// When the outermost evaluation exceeds [crate::LimitSettings::max_evaluation_time]
+    #[cfg(not(target_arch = "wasm32"))]
+    static EVALUATION_DEADLINE: Cell<Option<Instant>> = const { Cell::new(None) };
+}
 
-#[derive(PartialEq, Eq, Debug, Copy, Clone, serde::Serialize, serde::Deserialize)]
-pub enum Format { Decimal, Hex, Binary, Scientific }
+impl Format {
+    /// Formats `n`. A decimal number is shown in scientific notation instead if
+    /// [OutputSettings::auto_scientific] is enabled and it's too large or too small to be read
+    /// easily. With [ResultDisplay::FullPrecision], decimal and scientific numbers aren't rounded.
+    pub fn format(&self, n: f64, settings: &OutputSettings, use_thousands_separator: bool) -> String {
+        self.format_with_word_size(n, settings, WordSize::Unlimited, use_thousands_separator)
+    }
 
-const DECIMAL_PLACES: i32 = 10;
+    /// Formats `n` like [Format::format], padding hex and binary numbers to `word_size`
+    fn format_with_word_size(&self, n: f64, settings: &OutputSettings, word_size: WordSize, use_thousands_separator: bool) -> String {
+        let decimal_places = settings.decimal_places.min(MAX_DECIMAL_PLACES) as i32;
+        let is_full_precision = settings.display == ResultDisplay::FullPrecision && n.is_finite();
+        let format = match self {
+            Format::Decimal if settings.auto_scientific && Self::is_outside_decimal_range(n) => Format::Scientific,
+            format => *format,
+        };
 
-impl Format {
-    pub fn format(&self, n: f64, use_thousands_separator: bool) -> String {
-        let mut res = match self {
-            Format::Decimal => round_dp(n, DECIMAL_PLACES),
-            Format::Hex => format!("{:#X}", n as i64),
-            Format::Binary => format!("{:#b}", n as i64),
-            Format::Scientific => Self::format_scientific(n),
+        let mut res = match format {
+            // The shortest representations that are read back as the same f64
+            Format::Decimal if is_full_precision => n.to_string(),
+            Format::Scientific if is_full_precision => format!("{n:e}"),
+            Format::Decimal => Self::format_decimal(n, decimal_places, settings.pad_trailing_zeros),
+            Format::Hex | Format::Binary => Self::format_integer(n, format, word_size),
+            Format::Scientific => Self::format_scientific(n, decimal_places),
+            Format::Dms => Self::format_dms(n, decimal_places),
         };
-        if *self != Format::Scientific && use_thousands_separator && !n.is_infinite() {
-            if *self == Format::Decimal {
-                Self::add_thousands_separator(&mut res, 3);
-            } else {
-                let mut temp = res[2..].to_string();
-                Self::add_thousands_separator(
-                    &mut temp,
-                    4,
-                );
-                res.replace_range(2.., &temp);
-            }
+        if format == Format::Decimal && use_thousands_separator && !n.is_infinite() {
+            Self::add_thousands_separator(&mut res, 3);
+        } else if matches!(format, Format::Hex | Format::Binary) && (use_thousands_separator || settings.group_digits) && !n.is_infinite() {
+            let mut temp = res[2..].to_string();
+            Self::add_thousands_separator(
+                &mut temp,
+                4,
+            );
+            res.replace_range(2.., &temp);
         }
         res
     }
 
-    fn format_scientific(mut n: f64) -> String {
-        let is_negative = n.is_sign_negative();
-        if is_negative { n *= -1.0; }
-
-        let n_str = round_dp(n, DECIMAL_PLACES);
-        let mut n_str = n_str.trim_matches('0').chars().collect::<Vec<_>>();
-
-        fn count_decimal_places(chars: &[char]) -> isize {
-            if chars.iter().any(|c| *c == '.') {
-                chars.rsplit(|c| *c == '.').next().unwrap().len() as isize
-            } else {
-                0
+    /// Formats `n` in hex or binary. Numbers that fit into `word_size` are padded with zeros to it,
+    /// with negative numbers in two's complement (e.g. `0xFF` for `-1` with 8 bits).
+    fn format_integer(n: f64, format: Format, word_size: WordSize) -> String {
+        // Bit patterns with the highest bit set (e.g. of negative doubles) don't fit into an i64
+        let mut bits = if n >= I64_LIMIT { n as u64 } else { n as i64 as u64 };
+        let mut digits = 0;
+        if let Some(size) = word_size.bits() {
+            // As either a signed or an unsigned integer
+            if n >= -2f64.powi(size as i32 - 1) && n < 2f64.powi(size as i32) {
+                if size < u64::BITS { bits &= (1 << size) - 1; }
+                digits = if format == Format::Hex { size / 4 } else { size } as usize;
             }
         }
 
-        let decimal_places = count_decimal_places(&n_str);
-
-        n_str.retain(|c| *c != '.');
-
-        // trim '0'
-        if let Some(first) = n_str.iter().position(|c| *c != '0') {
-            let Some(last) = n_str.iter().rposition(|c| *c != '0') else { unreachable!() };
-            n_str = n_str[first..last + 1].to_vec();
+        if format == Format::Hex {
+            format!("0x{bits:0digits$X}")
         } else {
-            // We have removed everything. This only happens if "n == 0".
-            return "0e0".to_string();
+            format!("0b{bits:0digits$b}")
         }
+    }
 
-        n_str.insert(1, '.');
+    fn is_outside_decimal_range(n: f64) -> bool {
+        let n = n.abs();
+        n.is_finite() && n != 0.0 && !(SCIENTIFIC_LOWER_THRESHOLD..SCIENTIFIC_UPPER_THRESHOLD).contains(&n)
+    }
 
-        let decimal_places_after_move = count_decimal_places(&n_str);
-        let exponent = decimal_places_after_move - decimal_places;
+    fn format_decimal(n: f64, decimal_places: i32, pad_trailing_zeros: bool) -> String {
+        let mut res = round_dp(n, decimal_places);
+        if !pad_trailing_zeros || !n.is_finite() || decimal_places == 0 { return res; }
 
-        format!("{}{}e{exponent}", if is_negative { "-" } else { "" }, n_str.into_iter().collect::<String>())
+        let current_decimal_places = match res.find('.') {
+            Some(i) => res.len() - i - 1,
+            None => {
+                res.push('.');
+                0
+            }
+        };
+        res.extend(std::iter::repeat('0').take(decimal_places as usize - current_decimal_places));
+        res
+    }
+
+    fn format_scientific(n: f64, decimal_places: i32) -> String {
+        if !n.is_finite() { return round_dp(n, decimal_places); }
+        if n == 0.0 { return "0e0".to_string(); }
+
+        let sign = if n.is_sign_negative() { "-" } else { "" };
+        let n = n.abs();
+        // The power of ten is split, since it underflows for subnormal numbers (e.g. 10^-324)
+        let mantissa_of = |exponent: i32| n / 10f64.powi(exponent / 2) / 10f64.powi(exponent - exponent / 2);
+        let mut exponent = n.log10().floor() as i32;
+        let mut mantissa = round_dp(mantissa_of(exponent), decimal_places);
+        // Rounding can carry over into the next power of ten (e.g. 9.99999999999 -> 10)
+        if mantissa.starts_with("10") {
+            exponent += 1;
+            mantissa = round_dp(mantissa_of(exponent), decimal_places);
+        }
+
+        format!("{sign}{mantissa}e{exponent}")
+    }
+
+    /// Formats an angle in degrees as degrees, minutes and seconds (e.g. `45° 30' 16.2"`)
+    fn format_dms(n: f64, decimal_places: i32) -> String {
+        if !n.is_finite() { return round_dp(n, decimal_places); }
+
+        let sign = if n.is_sign_negative() && n != 0.0 { "-" } else { "" };
+        // The seconds are rounded first, so that they can carry over into the minutes and degrees
+        let seconds = math::round(n.abs() * 3600.0, decimal_places);
+        let degrees = (seconds / 3600.0).floor();
+        let minutes = ((seconds - degrees * 3600.0) / 60.0).floor();
+        let seconds = seconds - degrees * 3600.0 - minutes * 60.0;
+        format!("{sign}{degrees}° {minutes}' {}\"", round_dp(seconds, decimal_places))
     }
 
     fn add_thousands_separator(str: &mut String, packet_size: usize) {
@@ -130,6 +211,9 @@ pub struct NumberValue {
     pub(crate) unit: Option<Unit>,
     is_long_unit: bool,
     pub format: Format,
+    /// The absolute uncertainty of [NumberValue::number] (e.g. `0.1` for `5 ± 0.1`)
+    #[serde(default)]
+    pub uncertainty: f64,
 }
 
 impl NumberValue {
@@ -139,6 +223,7 @@ impl NumberValue {
             unit: None,
             is_long_unit: false,
             format: Format::Decimal,
+            uncertainty: 0.0,
         }
     }
 
@@ -151,6 +236,65 @@ impl NumberValue {
             .map(|unit| unit.format(self.is_long_unit(), self.number != 1.0))
             .unwrap_or_default()
     }
+
+    /// The abbreviated unit (e.g. `km/h`), if there is one
+    pub fn unit_symbol(&self) -> Option<String> {
+        self.unit.as_ref().map(|unit| unit.format(false, false))
+    }
+
+    /// Gives the unit the SI prefix that makes the number easiest to read (e.g. `1.5 km` instead of
+    /// `1500 m`). Numbers whose unit doesn't take prefixes are returned unchanged.
+    pub fn with_auto_prefix(mut self) -> Self {
+        if let Some((unit, factor)) = self.unit.as_ref().and_then(|unit| auto_prefix(unit, self.number)) {
+            self.number *= factor;
+            self.uncertainty *= factor;
+            self.unit = Some(unit);
+        }
+        self
+    }
+
+    /// The units without prefixes this number can be converted into (see
+    /// [compatible_units](crate::environment::dimensions::compatible_units))
+    pub fn compatible_units(&self) -> Vec<&'static str> {
+        self.unit.as_ref().map(compatible_units).unwrap_or_default()
+    }
+
+    /// Whether the number is shown rounded with `settings`, so that frontends can mark it or show
+    /// more decimal places without calculating it again
+    pub fn is_rounded(&self, settings: &OutputSettings) -> bool {
+        if !self.number.is_finite() { return false; }
+        let decimal_places = settings.decimal_places.min(MAX_DECIMAL_PLACES) as i32;
+        match self.format {
+            Format::Hex | Format::Binary => self.number.fract() != 0.0,
+            Format::Dms => {
+                let seconds = self.number.abs() * 3600.0;
+                math::round(seconds, decimal_places) != seconds
+            }
+            // Decimal and scientific numbers are read back the way they're shown
+            Format::Decimal | Format::Scientific =>
+                self.format.format(self.number, settings, false).parse::<f64>() != Ok(self.number),
+        }
+    }
+
+    /// Formats amounts of money with two decimal places and, if there is one, the currency's symbol
+    /// (e.g. `$1,234.50`). Returns [None] if this isn't an amount of money or money isn't formatted
+    /// specially.
+    fn format_money(&self, settings: &Settings, use_thousands_separator: bool) -> Option<String> {
+        if !settings.currency.money_format || self.format != Format::Decimal || !self.number.is_finite()
+            || self.uncertainty != 0.0 {
+            return None;
+        }
+        let Some(Unit::Unit(currency, power, _)) = &self.unit else { return None; };
+        if *power != 1.0 || !is_currency(currency) { return None; }
+
+        let output = OutputSettings { decimal_places: 2, pad_trailing_zeros: true, auto_scientific: false, ..settings.output };
+        let amount = Format::Decimal.format(self.number.abs(), &output, use_thousands_separator);
+        let sign = if self.number < 0.0 && amount.chars().any(|c| c.is_ascii_digit() && c != '0') { "-" } else { "" };
+        Some(match settings.currency.symbol_of(currency) {
+            Some(symbol) => format!("{sign}{symbol}{amount}"),
+            None => format!("{sign}{amount} {currency}"),
+        })
+    }
 }
 
 #[derive(Debug, PartialEq, Clone, serde::Serialize, serde::Deserialize)]
@@ -161,7 +305,13 @@ pub enum Value {
 
 impl Value {
     pub fn number(number: f64, unit: Option<Unit>, is_long_unit: bool, format: Format) -> Value {
-        Value::Number(NumberValue { number, unit, is_long_unit, format })
+        Value::Number(NumberValue { number, unit, is_long_unit, format, uncertainty: 0.0 })
+    }
+
+    /// Sets the uncertainty of a [Value::Number]. Objects are left untouched.
+    pub fn with_uncertainty(mut self, uncertainty: f64) -> Value {
+        if let Value::Number(number) = &mut self { number.uncertainty = uncertainty; }
+        self
     }
 
     pub const fn only_number(number: f64) -> Value {
@@ -170,15 +320,33 @@ impl Value {
             unit: None,
             is_long_unit: false,
             format: Format::Decimal,
+            uncertainty: 0.0,
         })
     }
 
     pub fn format(&self, settings: &Settings, use_thousands_separator: bool) -> String {
+        let display = settings.output.display;
+        if display == ResultDisplay::Debug { return format!("{self:?}"); }
+
         match self {
             Value::Number(number) => {
-                let mut result = number.format.format(number.number, use_thousands_separator);
-                if !matches!(number.unit, Some(Unit::Unit(..))) || number.is_long_unit() { result.push(' '); }
-                result + &number.unit_string()
+                if display == ResultDisplay::Rounded {
+                    if let Some(money) = number.format_money(settings, use_thousands_separator) { return money; }
+                }
+
+                let word_size = if settings.output.pad_to_word_size { settings.word_size } else { WordSize::Unlimited };
+                let mut result = number.format.format_with_word_size(number.number, &settings.output, word_size, use_thousands_separator);
+                if number.uncertainty != 0.0 {
+                    let uncertainty = number.format.format(number.uncertainty, &settings.output, use_thousands_separator);
+                    result = format!("{result} ± {uncertainty}");
+                    if number.unit.is_some() { result = format!("({result})"); }
+                }
+                // Degrees, minutes and seconds already contain their units
+                if number.format == Format::Dms { return result; }
+                let is_long_unit = number.is_long_unit() || display == ResultDisplay::LongUnit;
+                if !matches!(number.unit, Some(Unit::Unit(..))) || is_long_unit { result.push(' '); }
+                let unit = number.unit.as_ref().map(|unit| unit.format(is_long_unit, number.number != 1.0));
+                result + unit.unwrap_or_default().as_str()
             }
             Value::Object(object) => object.to_string(settings),
         }
@@ -186,9 +354,11 @@ impl Value {
 
     pub fn to_ast_node_from(&self, src: &AstNode) -> AstNode {
         match self {
-            Value::Number(NumberValue { number, unit, .. }) => {
+            Value::Number(NumberValue { number, unit, uncertainty, format, .. }) => {
                 let mut new_node = AstNode::from(src, AstNodeData::Literal(*number));
                 if new_node.unit.is_none() { new_node.unit = unit.clone(); }
+                if *format != Format::Decimal { new_node.preferred_format = Some(*format); }
+                new_node.uncertainty = *uncertainty;
                 new_node
             }
             Value::Object(object) => AstNode::from(src, AstNodeData::Object(object.clone())),
@@ -225,38 +395,111 @@ impl<'a> Engine<'a> {
         Ok(result.clone())
     }
 
-    pub(crate) fn evaluate(mut ast: Vec<AstNode>, context: Context) -> Result<Value> {
+    pub(crate) fn evaluate(ast: Vec<AstNode>, context: Context) -> Result<Value> {
+        let depth = EVALUATION_DEPTH.with(Cell::get);
+        if let Err(error) = Self::check_limits(depth, &context) {
+            return Err(error.with(full_range(&ast)));
+        }
+
+        let key = Self::constant_cache_key(depth, &ast, &context);
+        if let Some(value) = key.as_ref().and_then(|key| context.borrow().constant_cache.get(key)) { return Ok(value); }
+        let side_effects = |context: &Context| {
+            let env = &context.borrow().env;
+            (env.warnings.len(), env.breakdown.len())
+        };
+        let side_effects_before = side_effects(&context);
+
+        EVALUATION_DEPTH.with(|d| d.set(depth + 1));
+        let result = Self::evaluate_ast(ast, context.clone());
+        EVALUATION_DEPTH.with(|d| d.set(depth));
+
+        // Warnings and breakdowns wouldn't be reported again for cached results
+        if let (Some(key), Ok(value)) = (key, &result) {
+            if side_effects(&context) == side_effects_before {
+                context.borrow().constant_cache.insert(key, value.clone());
+            }
+        }
+        result
+    }
+
+    /// The key of the result of `ast` in the [ConstantCache](crate::ConstantCache), if it can be
+    /// cached. Single nodes aren't worth caching.
+    #[inline(never)]
+    fn constant_cache_key(depth: usize, ast: &[AstNode], context: &Context) -> Option<constant_cache::Key> {
+        let context = context.borrow();
+        if depth == 0 { context.constant_cache.check_settings(&context.settings); }
+        if !context.settings.cache_constants || ast.len() < 2 { return None; }
+        constant_cache::key(ast, &context.env)
+    }
+
+    /// Checks whether an evaluation at `depth` can go on. Kept out of [Engine::evaluate] so that
+    /// its stack frame stays small, since it's part of every nesting level.
+    #[inline(never)]
+    fn check_limits(depth: usize, context: &Context) -> std::result::Result<(), ErrorType> {
+        if depth >= MAX_EVALUATION_DEPTH { return Err(ErrorType::TooDeeplyNested); }
+        if context.borrow().cancellation.is_cancelled() { return Err(ErrorType::Cancelled); }
+
+        // Time isn't available on the web
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            let max_time = context.borrow().settings.limits.max_evaluation_time;
+            if depth == 0 {
+                EVALUATION_DEADLINE.with(|d| d.set(Some(Instant::now() + Duration::from_millis(max_time))));
+            } else if EVALUATION_DEADLINE.with(Cell::get).map_or(false, |deadline| Instant::now() > deadline) {
+                return Err(ErrorType::LimitExceeded(format!("evaluation time of {max_time} ms")));
+            }
+        }
+        Ok(())
+    }
+
+    /// How much of [crate::LimitSettings::max_evaluation_time] is left, if time is available
+    fn remaining_time() -> Option<std::time::Duration> {
+        #[cfg(not(target_arch = "wasm32"))]
+        return EVALUATION_DEADLINE.with(Cell::get).map(|deadline| deadline.saturating_duration_since(Instant::now()));
+        #[cfg(target_arch = "wasm32")]
+        None
+    }
+
+    fn evaluate_ast(mut ast: Vec<AstNode>, context: Context) -> Result<Value> {
+        if ast.is_empty() { return Err(ErrorType::InvalidAst.with(SourceRange::empty())); }
+
         if ast.len() == 1 {
             if matches!(ast[0].data, AstNodeData::Literal(_)) {
-                ast[0].apply_modifiers()?;
+                ast[0].apply_modifiers(context.borrow().settings.limits.max_factorial)?;
                 let result = match_ast_node!(AstNodeData::Literal(res), res, ast[0]);
                 let unit = take(&mut ast[0].unit);
-                return Ok(Value::number(result, unit, true, ast[0].format));
+                let format = Self::result_format(&ast[0], result);
+                let value = Value::number(result, unit, true, format).with_uncertainty(ast[0].uncertainty);
+                return Self::convert_to_degrees(value, &context, ast[0].range);
             } else if let AstNodeData::Object(object) = &ast[0].data {
                 return Ok(Value::Object(object.clone()));
             }
         }
 
-        let mut engine = Engine::new(&mut ast, context);
+        let mut engine = Engine::new(&mut ast, context.clone());
         engine.eval_variables()?;
         engine.eval_functions()?;
         engine.eval_groups()?;
+        engine.eval_operators(&[Operator::PlusMinus])?;
         // extended operators
         engine.eval_operators(&[
             Operator::Exponentiation, Operator::BitwiseAnd, Operator::BitwiseOr, Operator::Xor,
-            Operator::BitShiftLeft, Operator::BitShiftRight, Operator::Modulo
+            Operator::Choose, Operator::BitShiftLeft, Operator::BitShiftRight, Operator::Modulo,
+            Operator::Remainder
         ])?;
         engine.eval_operators(&[Operator::Multiply, Operator::Divide])?;
         engine.eval_operators(&[Operator::Plus, Operator::Minus])?;
+        engine.eval_historic_conversions()?;
         engine.eval_operators(&[Operator::Of, Operator::In])?;
 
         if matches!(ast[0].data, AstNodeData::Literal(_)) {
-            ast[0].apply_modifiers()?;
+            ast[0].apply_modifiers(context.borrow().settings.limits.max_factorial)?;
             let mut result = match_ast_node!(AstNodeData::Literal(res), res, ast[0]);
-            let format = ast[0].format;
-            if format != Format::Decimal && format != Format::Scientific { result = result.trunc(); }
+            let format = Self::result_format(&ast[0], result);
+            if matches!(format, Format::Hex | Format::Binary) { result = result.trunc(); }
 
-            Ok(Value::number(result, take(&mut ast[0].unit), false, format))
+            let value = Value::number(result, take(&mut ast[0].unit), false, format).with_uncertainty(ast[0].uncertainty);
+            Self::convert_to_degrees(value, &context, ast[0].range)
         } else if let AstNodeData::Object(object) = &ast[0].data {
             Ok(Value::Object(object.clone()))
         } else {
@@ -265,6 +508,34 @@ impl<'a> Engine<'a> {
         }
     }
 
+    /// Results without a format of their own are shown in the format of the values they were
+    /// calculated from. Hex and binary are only kept for integers, which they can show exactly.
+    fn result_format(node: &AstNode, result: f64) -> Format {
+        match (node.format, node.preferred_format) {
+            (Format::Decimal, Some(Format::Hex | Format::Binary)) if result.fract() != 0.0 => Format::Decimal,
+            (Format::Decimal, Some(preferred)) => preferred,
+            (format, _) => format,
+        }
+    }
+
+    /// Angles that are shown in degrees, minutes and seconds are converted to degrees first
+    fn convert_to_degrees(value: Value, context: &Context, range: SourceRange) -> Result<Value> {
+        let Value::Number(mut number) = value else { return Ok(value); };
+        if number.format != Format::Dms { return Ok(Value::Number(number)); }
+
+        if let Some(unit) = number.unit.take() {
+            let context = context.borrow();
+            let degrees = Unit::from("°");
+            let convert = |n| convert_units(&unit, &degrees, n, &context.currencies, context.settings.data_prefixes, range);
+            let converted = convert(number.number)?;
+            if number.uncertainty != 0.0 {
+                number.uncertainty = (convert(number.number + number.uncertainty)? - converted).abs();
+            }
+            number.number = converted;
+        }
+        Ok(Value::Number(number))
+    }
+
     /// Solves a linear equation
     pub(crate) fn solve(
         lhs: Vec<AstNode>,
@@ -340,7 +611,8 @@ impl<'a> Engine<'a> {
                         let AstNodeData::Arguments(args) = &ast[i + 2].data else { break 'blk; };
                         i += 2;
 
-                        let f = env.get_function(name).unwrap();
+                        // Standard functions don't allow question marks in their arguments
+                        let Some(f) = env.get_function(name) else { break 'blk; };
 
                         let mut question_mark_arg_name: Option<&str> = None;
                         let mut question_mark_range: Option<SourceRange> = None;
@@ -386,10 +658,7 @@ impl<'a> Engine<'a> {
                             return Ok(None);
                         }
                     }
-                    AstNodeData::Identifier(name) => {
-                        if question_mark_variable_name.is_none() ||
-                            question_mark_variable_name.unwrap() != name { continue; }
-
+                    AstNodeData::Identifier(name) if question_mark_variable_name == Some(name.as_str()) => {
                         if is_surrounded_by_exponentiation {
                             // TODO: Better error range (power sign)
                             return Err(ErrorType::ForbiddenExponentiation.with(ast[i].range));
@@ -407,29 +676,32 @@ impl<'a> Engine<'a> {
             Ok(None)
         }
 
-        fn replace_question_mark(mut ast: Vec<AstNode>, value: f64) -> Vec<AstNode> {
-            fn replace(ast: &mut [AstNode], value: f64) {
+        /// Replaces the question mark in `ast` with `value`. If the question mark doesn't have a
+        /// unit, `unit` is used instead.
+        fn replace_question_mark(mut ast: Vec<AstNode>, value: f64, unit: Option<&Unit>) -> Vec<AstNode> {
+            fn replace(ast: &mut [AstNode], value: f64, unit: Option<&Unit>) {
                 for i in 0..ast.len() {
                     match ast[i].data {
                         AstNodeData::QuestionMark => {
                             ast[i] = AstNode::from(&ast[i], AstNodeData::Literal(value));
+                            if ast[i].unit.is_none() { ast[i].unit = unit.cloned(); }
                             return;
                         }
-                        AstNodeData::Group(ref mut ast) => replace(ast, value),
+                        AstNodeData::Group(ref mut ast) => replace(ast, value, unit),
                         AstNodeData::Identifier(_) => 'blk: {
                             if ast.len() <= i + 2 { break 'blk; }
                             if ast[i + 1].data != AstNodeData::Operator(Operator::Call) { break 'blk; }
 
                             let AstNodeData::Arguments(args) = &mut ast[i + 2].data else { break 'blk; };
 
-                            for arg in args { replace(arg, value); }
+                            for arg in args { replace(arg, value, unit); }
                         }
                         _ => {}
                     }
                 }
             }
 
-            replace(&mut ast, value);
+            replace(&mut ast, value, unit);
             ast
         }
 
@@ -452,32 +724,61 @@ impl<'a> Engine<'a> {
         const X1: f64 = 1.0;
         const X2: f64 = 2.0;
 
-        let first_ast = replace_question_mark(unknown_side.clone(), X1);
-
-        let y1_result = Self::evaluate_to_number(first_ast, context.clone())?;
-
-        if y1_result.unit.is_some() && target_result.unit.is_none() {
-            return Err(ErrorType::WrongUnit(y1_result.unit.unwrap().to_string()).with(rhs_range));
-        } else if y1_result.unit.is_none() && target_result.unit.is_some() {
-            return Err(ErrorType::WrongUnit("none".to_string()).with(rhs_range));
-        } else if y1_result.unit.is_some() && target_result.unit.is_some() {
-            let y1_unit = y1_result.unit.clone().unwrap();
-
-            target_value = match convert_units(
-                &target_result.unit.unwrap(),
-                &y1_unit,
-                target_value,
-                &context.borrow().currencies,
-                rhs_range,
-            ) {
-                Ok(n) => n,
-                Err(_) => return Err(ErrorType::WrongUnit(y1_unit.to_string()).with(rhs_range)),
-            };
+        let first_ast = replace_question_mark(unknown_side.clone(), X1, None);
+
+        let mut y1_result = Self::evaluate_to_number(first_ast, context.clone())?;
+
+        // Infer the unit of the question mark from the units on both sides of the equation. A
+        // candidate fits if the unknown side then has the dimension of the target.
+        let mut inferred_unit: Option<Unit> = None;
+        let target_dimension = Dimension::of_optional(target_result.unit.as_ref());
+        if question_mark_unit.is_none() {
+            let (y1_unit, target_unit) = (y1_result.unit.as_ref(), target_result.unit.as_ref());
+            let candidates = [
+                // `? + 2km = 5mi`: If the question mark is just added to something with a unit,
+                // giving it the same unit doesn't change the result. `? * 2 = 10km`: Only the
+                // question mark can have the unit.
+                y1_unit.or(target_unit).cloned(),
+                // `? * 2km = 10km^2`
+                Unit::combine(target_unit, y1_unit, -1.0),
+                // `? / 2h = 50km/h`, where the unknown side has the unit `h` without a unit for
+                // the question mark (like `1 / 2h`)
+                Unit::combine(target_unit, y1_unit, 1.0),
+            ];
+
+            for candidate in candidates.into_iter().flatten() {
+                let ast = replace_question_mark(unknown_side.clone(), X1, Some(&candidate));
+                let Ok(result) = Self::evaluate_to_number(ast, context.clone()) else { continue; };
+                if target_dimension.is_some() && Dimension::of_optional(result.unit.as_ref()) == target_dimension {
+                    y1_result = result;
+                    inferred_unit = Some(candidate);
+                    break;
+                }
+            }
+        }
+
+        match (&y1_result.unit, &target_result.unit) {
+            (Some(y1_unit), None) => return Err(ErrorType::WrongUnit(y1_unit.to_string()).with(rhs_range)),
+            (None, Some(_)) => return Err(ErrorType::WrongUnit("none".to_string()).with(rhs_range)),
+            (Some(y1_unit), Some(target_unit)) => {
+                target_value = match convert_units(
+                    target_unit,
+                    y1_unit,
+                    target_value,
+                    &context.borrow().currencies,
+                    context.borrow().settings.data_prefixes,
+                    rhs_range,
+                ) {
+                    Ok(n) => n,
+                    Err(_) => return Err(ErrorType::WrongUnit(y1_unit.to_string()).with(rhs_range)),
+                };
+            }
+            (None, None) => {}
         }
 
         let y1 = y1_result.number - target_value;
 
-        let second_ast = replace_question_mark(unknown_side, X2);
+        let second_ast = replace_question_mark(unknown_side, X2, inferred_unit.as_ref());
         let y2 = Self::evaluate_to_number(second_ast, context)?.number - target_value;
 
         // Calculate the variables for formula y = mx + c
@@ -496,12 +797,61 @@ impl<'a> Engine<'a> {
             y1_result.format
         };
 
-        Ok(Value::number(result, question_mark_unit, false, format))
+        Ok(Value::number(result, question_mark_unit.or(inferred_unit), false, format))
     }
 
-    pub fn check_boolean_operator(lhs: &Value, rhs: &Value, operator: BooleanOperator, currencies: &Currencies) -> bool {
+    /// The difference between the numbers `lhs` and `rhs` (see [Difference]). Returns [None] if
+    /// they aren't numbers or their units can't be converted into each other.
+    pub fn difference(lhs: &Value, rhs: &Value, currencies: &Currencies, data_prefixes: DataPrefixes) -> Option<Difference> {
+        let (Value::Number(lhs), Value::Number(rhs)) = (lhs, rhs) else { return None; };
+        let rhs_number = match (&lhs.unit, &rhs.unit) {
+            (None, None) => rhs.number,
+            (Some(lhs_unit), Some(rhs_unit)) =>
+                convert_units(rhs_unit, lhs_unit, rhs.number, currencies, data_prefixes, SourceRange::empty()).ok()?,
+            _ => return None,
+        };
+
+        let absolute = lhs.number - rhs_number;
+        Some(Difference {
+            absolute: Value::Number(NumberValue { number: absolute, uncertainty: 0.0, ..lhs.clone() }),
+            relative: (rhs_number != 0.0).then(|| absolute / rhs_number.abs()),
+        })
+    }
+
+    /// The tolerance of an approximate equality check whose left-hand side is `lhs`: the value of
+    /// `tolerance` (the expression after `within`) in the unit of `lhs`, or the one from the
+    /// settings if there is none
+    pub(crate) fn tolerance(lhs: &Value, tolerance: Option<Vec<AstNode>>, context: Context) -> Result<ToleranceSettings> {
+        let Some(ast) = tolerance else { return Ok(context.borrow().settings.tolerance); };
+        let range = full_range(&ast);
+        let tolerance = Self::evaluate_to_number(ast, context.clone())?;
+        let absolute = match (&tolerance.unit, lhs.to_number().and_then(|lhs| lhs.unit.as_ref())) {
+            (Some(unit), Some(lhs_unit)) => {
+                let context = context.borrow();
+                convert_units(unit, lhs_unit, tolerance.number, &context.currencies, context.settings.data_prefixes, range)?
+            }
+            (Some(_), None) => return Err(ErrorType::UnexpectedUnit.with(range)),
+            (None, _) => tolerance.number,
+        };
+        Ok(ToleranceSettings { absolute: absolute.abs(), relative: 0.0 })
+    }
+
+    /// Checks `operator` for `lhs` and `rhs`. [BooleanOperator::ApproximatelyEqual] allows numbers
+    /// to differ by `tolerance`, other values have to be equal.
+    pub fn check_boolean_operator(
+        lhs: &Value,
+        rhs: &Value,
+        operator: BooleanOperator,
+        tolerance: &ToleranceSettings,
+        currencies: &Currencies,
+        data_prefixes: DataPrefixes,
+    ) -> bool {
         use crate::common::math::round;
 
+        let check = |lhs: f64, rhs: f64| match operator {
+            BooleanOperator::ApproximatelyEqual => tolerance.are_close(lhs, rhs),
+            _ => operator.check(lhs, rhs),
+        };
         match (lhs, rhs) {
             (Value::Number(lhs), Value::Number(rhs)) => {
                 let lhs_number = round(lhs.number, DECIMAL_PLACES);
@@ -517,16 +867,27 @@ impl<'a> Engine<'a> {
                         lhs_unit.as_ref().unwrap(),
                         rhs_number,
                         currencies,
+                        data_prefixes,
                         SourceRange::empty(), // this doesn't matter since we discard the error
                     ) {
                         Ok(mut rhs) => {
                             rhs = round(rhs, DECIMAL_PLACES);
-                            operator.check(lhs_number, rhs)
+                            check(lhs_number, rhs)
                         }
                         Err(_) => false,
                     }
                 } else {
-                    operator.check(lhs_number, rhs_number)
+                    check(lhs_number, rhs_number)
+                }
+            }
+            (Value::Object(CalculatorObject::Interval(_)), _) | (_, Value::Object(CalculatorObject::Interval(_))) => {
+                let rounded = |interval: Interval| Interval {
+                    lower: round(interval.lower, DECIMAL_PLACES),
+                    upper: round(interval.upper, DECIMAL_PLACES),
+                };
+                match (Interval::from_value(lhs), Interval::from_value(rhs)) {
+                    (Some(lhs), Some(rhs)) => rounded(lhs).check(&rounded(rhs), operator),
+                    _ => false,
                 }
             }
             (Value::Object(lhs), Value::Object(rhs)) => operator.check(lhs, rhs),
@@ -551,7 +912,8 @@ impl<'a> Engine<'a> {
                 i += 2;
                 continue;
             }
-            let AstNodeData::Arguments(arg_asts) = &args_node.data else {
+            // The arguments are moved out of their node, since it's replaced by the result anyway
+            let AstNodeData::Arguments(arg_asts) = &mut args_node.data else {
                 i += 2;
                 continue;
             };
@@ -560,10 +922,22 @@ impl<'a> Engine<'a> {
             if let AstNodeData::Identifier(func_name) = &receiver.data {
                 // TODO: Make this generic!?
                 let mut first_arg: Option<NumberValue> = None;
-                if func_name == "abs" && arg_asts.len() == 1 {
-                    match Self::evaluate(arg_asts[0].clone(), self.context.clone())? {
+                if (func_name == "abs" || Interval::supports_function(func_name)) && arg_asts.len() == 1 {
+                    let range = full_range(&arg_asts[0]);
+                    match Self::evaluate(take(&mut arg_asts[0]), self.context.clone())? {
                         Value::Number(number) => first_arg = Some(number),
-                        Value::Object(CalculatorObject::Vector(vector)) => {
+                        Value::Object(CalculatorObject::Interval(interval)) => {
+                            let env = &self.context.borrow().env;
+                            let result = interval
+                                .map(func_name, |x| env.resolve_function(func_name, &[NumberValue::new(x)]).map(|res| res.0))
+                                .map_err(|ty| ty.with(receiver.range))?;
+                            let new_node = AstNode::from(receiver, AstNodeData::Object(CalculatorObject::Interval(result)));
+                            let _ = replace(receiver, new_node);
+                            self.ast.remove(i + 1);
+                            self.ast.remove(i + 1);
+                            continue;
+                        }
+                        Value::Object(CalculatorObject::Vector(vector)) if func_name == "abs" => {
                             let result = vector.length();
                             let new_node = AstNode::from(receiver, AstNodeData::Literal(result));
                             let _ = replace(receiver, new_node);
@@ -571,26 +945,176 @@ impl<'a> Engine<'a> {
                             self.ast.remove(i + 1);
                             continue;
                         }
-                        _ => {}
+                        _ => error!(ExpectedNumber: range),
                     }
                 }
 
-                let mut args = if let Some(arg) = first_arg { vec![arg] } else { vec![] };
-                for ast in arg_asts {
-                    args.push(Self::evaluate_to_number(ast.clone(), self.context.clone())?);
+                if func_name == "bits" && arg_asts.len() == 1 {
+                    let range = full_range(&arg_asts[0]);
+                    let number = Self::evaluate_to_number(take(&mut arg_asts[0]), self.context.clone())?.number;
+                    if number.fract() != 0.0 { error!(ExpectedInteger(number): range); }
+
+                    let bits = number as i64;
+                    let numbers = (0..i64::BITS).filter(|i| bits & (1 << i) != 0).map(f64::from).collect();
+                    let new_node = AstNode::from(receiver, AstNodeData::Object(CalculatorObject::Vector(Vector { numbers })));
+                    let _ = replace(receiver, new_node);
+                    self.ast.remove(i + 1);
+                    self.ast.remove(i + 1);
+                    continue;
                 }
 
-                new_node = match self.context.borrow().env.resolve_function(func_name, &args) {
+                if func_name == "npv" || func_name == "irr" {
+                    let cash_flows_ast = arg_asts.last_mut().unwrap();
+                    let range = full_range(cash_flows_ast);
+                    let Value::Object(CalculatorObject::Vector(cash_flows)) = Self::evaluate(take(cash_flows_ast), self.context.clone())? else {
+                        error!(ExpectedVector: range);
+                    };
+
+                    let result = if func_name == "npv" {
+                        let rate = Self::evaluate_to_number(take(&mut arg_asts[0]), self.context.clone())?.number;
+                        math::npv(rate, &cash_flows.numbers)
+                    } else {
+                        let Some(rate) = math::irr(&cash_flows.numbers) else {
+                            error!(NoInternalRateOfReturn: receiver.range);
+                        };
+                        rate
+                    };
+                    let new_node = AstNode::from(receiver, AstNodeData::Literal(result));
+                    let _ = replace(receiver, new_node);
+                    self.ast.remove(i + 1);
+                    self.ast.remove(i + 1);
+                    continue;
+                }
+
+                // `map` with five arguments maps a number from one range to another
+                let is_range_map = func_name == "map" && arg_asts.len() == 5;
+                if matches!(func_name.as_str(), "map" | "filter" | "reduce" | "apply") && !is_range_map {
+                    let new_node = Self::eval_higher_order_function(&self.context, func_name, arg_asts, receiver)?;
+                    let _ = replace(receiver, new_node);
+                    self.ast.remove(i + 1);
+                    self.ast.remove(i + 1);
+                    continue;
+                }
+
+                if func_name == "fmt" {
+                    let new_node = Self::eval_fmt(&self.context, arg_asts, receiver)?;
+                    let _ = replace(receiver, new_node);
+                    self.ast.remove(i + 1);
+                    self.ast.remove(i + 1);
+                    continue;
+                }
+
+                if func_name == "unixtime" {
+                    let range = full_range(&arg_asts[0]);
+                    let timestamp = match Self::evaluate(take(&mut arg_asts[0]), self.context.clone())? {
+                        Value::Object(CalculatorObject::DateTime(datetime)) => datetime.timestamp(),
+                        // Dates start at midnight in the time zone of the settings
+                        Value::Object(CalculatorObject::Date(date)) => {
+                            let zone = self.context.borrow().settings.date.timezone;
+                            let Some(utc) = zone.to_utc(date.date.and_hms_opt(0, 0, 0).unwrap()) else {
+                                error!(InvalidTime: range);
+                            };
+                            DateTimeObject::new(utc, zone).timestamp()
+                        }
+                        _ => error!(ExpectedDate: range),
+                    };
+                    let new_node = AstNode::from(receiver, AstNodeData::Literal(timestamp));
+                    let _ = replace(receiver, new_node);
+                    self.ast.remove(i + 1);
+                    self.ast.remove(i + 1);
+                    continue;
+                }
+
+                if func_name == "factorize" {
+                    let range = full_range(&arg_asts[0]);
+                    let number = Self::evaluate_to_number(take(&mut arg_asts[0]), self.context.clone())?.number;
+                    if number.fract() != 0.0 { error!(ExpectedInteger(number): range); }
+                    if !(1.0..=MAX_EXACT_INTEGER).contains(&number) {
+                        error!(IntegerOutOfRange(number, 1.0, MAX_EXACT_INTEGER): range);
+                    }
+
+                    let numbers = number_theory::factorize(number as u64).into_iter().map(|factor| factor as f64).collect();
+                    let new_node = AstNode::from(receiver, AstNodeData::Object(CalculatorObject::Vector(Vector { numbers })));
+                    let _ = replace(receiver, new_node);
+                    self.ast.remove(i + 1);
+                    self.ast.remove(i + 1);
+                    continue;
+                }
+
+                if func_name == "molarmass" {
+                    let range = full_range(&arg_asts[0]);
+                    let Value::Object(CalculatorObject::String(formula)) = Self::evaluate(take(&mut arg_asts[0]), self.context.clone())? else {
+                        error!(ExpectedString: range);
+                    };
+                    let mass = molar_mass(&formula.text).map_err(|ty| ty.with(range))?;
+                    let mut new_node = AstNode::from(receiver, AstNodeData::Literal(mass));
+                    new_node.unit = Some(Unit::Fraction(
+                        Box::new(Unit::new("g", 1.0, receiver.range)),
+                        Box::new(Unit::new("mol", 1.0, receiver.range)),
+                    ));
+                    let _ = replace(receiver, new_node);
+                    self.ast.remove(i + 1);
+                    self.ast.remove(i + 1);
+                    continue;
+                }
+
+                if func_name == "line" {
+                    let range = full_range(&arg_asts[0]);
+                    let line = Self::evaluate_to_number(take(&mut arg_asts[0]), self.context.clone())?.number;
+                    if line.fract() != 0.0 { error!(ExpectedInteger(line): range); }
+
+                    let Variable(value) = self.context.borrow().env.resolve_line(line.max(0.0) as usize)
+                        .map_err(|ty| ty.with(receiver.range))?
+                        .clone();
+                    let new_node = value.to_ast_node_from(receiver);
+                    let _ = replace(receiver, new_node);
+                    self.ast.remove(i + 1);
+                    self.ast.remove(i + 1);
+                    continue;
+                }
+
+                let ranges = arg_asts.iter().map(|ast| full_range(ast)).collect::<Vec<_>>();
+                // The first argument of `abs` and the interval functions was already evaluated
+                let evaluated = usize::from(first_arg.is_some());
+                let mut args = first_arg.into_iter().collect::<Vec<_>>();
+                for ast in &mut arg_asts[evaluated..] {
+                    args.push(Self::evaluate_to_number(take(ast), self.context.clone())?);
+                }
+                if matches!(func_name.as_str(), "tax" | "net") && args.len() == 1 {
+                    let vat_rate = self.context.borrow().settings.currency.vat_rate;
+                    args.push(NumberValue::new(vat_rate / 100.0));
+                }
+
+                let result = self.context.borrow().env.resolve_function(func_name, &args);
+                new_node = match result {
                     Ok(res) => {
                         let mut new_node = AstNode::from(receiver, AstNodeData::Literal(res.0));
                         if new_node.unit.is_none() { new_node.unit = res.1; }
+                        match func_name.as_str() {
+                            "hexdump" | "float_bits" => new_node.format = Format::Hex,
+                            // ULPs are generally too small for the decimal places
+                            "ulp" => new_node.format = Format::Scientific,
+                            _ => {}
+                        }
+
+                        if args.iter().any(|arg| arg.uncertainty != 0.0) {
+                            new_node.uncertainty = Self::function_uncertainty(&self.context, func_name, &args, res.0);
+                        }
+
+                        if matches!(func_name.as_str(), "tax" | "net" | "tip" | "discount") {
+                            Self::push_breakdown(&self.context, func_name, args[1].number, args[0].number, res.0);
+                        }
+
+                        // The bits of doubles often have too many significant digits to be
+                        // represented exactly
+                        if func_name == "float_bits" && res.0 > MAX_EXACT_INTEGER && res.0 as u64 != args[0].number.to_bits() {
+                            self.context.borrow_mut().env.warnings.push(WarningType::PrecisionLost.with(receiver.range));
+                        }
                         new_node
                     }
                     Err(ty) => match ty {
-                        ErrorType::UnknownFunction(_) => {
-                            let args = args.into_iter()
-                                .zip(arg_asts.iter().map(|ast| full_range(ast)))
-                                .collect::<Vec<_>>();
+                        ErrorType::UnknownFunction(..) => {
+                            let args = args.into_iter().zip(ranges).collect::<Vec<_>>();
                             let res = self.context.borrow().env.resolve_custom_function(
                                 func_name,
                                 &args,
@@ -604,11 +1128,14 @@ impl<'a> Engine<'a> {
                         }
                     }
                 };
+            } else if let AstNodeData::Object(CalculatorObject::Function(function)) = &receiver.data {
+                new_node = Self::eval_function_reference_call(&self.context, &function.name, arg_asts, receiver)?;
             } else if let AstNodeData::Object(object) = &receiver.data {
                 if !object.is_callable() { error!(NotCallable: receiver.range); }
                 let mut args = vec![];
                 for ast in arg_asts {
-                    args.push((Self::evaluate_to_number(ast.clone(), self.context.clone())?, full_range(ast)));
+                    let range = full_range(ast);
+                    args.push((Self::evaluate_to_number(take(ast), self.context.clone())?, range));
                 }
 
                 new_node = object.call(
@@ -650,58 +1177,309 @@ impl<'a> Engine<'a> {
 
     fn eval_groups(&mut self) -> Result<()> {
         for node in self.ast.iter_mut() {
-            let group_ast = match &node.data {
-                AstNodeData::Group(ast) => ast,
+            let group_ast = match &mut node.data {
+                AstNodeData::Group(ast) => take(ast),
                 _ => continue,
             };
 
-            let group_result = Self::evaluate(group_ast.clone(), self.context.clone())?;
+            let group_result = Self::evaluate(group_ast, self.context.clone())?;
             // Construct Literal node with the evaluated result
-            let new_node = group_result.to_ast_node_from(node);
+            let mut new_node = group_result.to_ast_node_from(node);
+            // Keep the format of the result (e.g. of `(255 in hex)`), unless the group has its own
+            if let (Value::Number(number), Format::Decimal) = (&group_result, new_node.format) {
+                new_node.format = number.format;
+            }
             let _ = replace(node, new_node);
         }
 
         Ok(())
     }
 
+    /// Converts currencies with the exchange rates of a date (e.g.
+    /// `100 USD in EUR at {date 01.06.2023}`)
+    fn eval_historic_conversions(&mut self) -> Result<()> {
+        let mut i = 0usize;
+        while i + 4 < self.ast.len() {
+            let [lhs, _, unit, at, date] = &mut self.ast[i..=i + 4] else { unreachable!() };
+            if at.data != AstNodeData::Operator(Operator::At) {
+                i += 2;
+                continue;
+            }
+
+            let AstNodeData::Object(CalculatorObject::Date(date_object)) = &date.data else {
+                error!(ExpectedDate: date.range);
+            };
+            let (AstNodeData::Unit(Unit::Unit(dst, 1.0, _)), Some(Unit::Unit(src, 1.0, _))) = (&unit.data, &lhs.unit) else {
+                error!(ExpectedCurrency: lhs.range, unit.range);
+            };
+            if !is_currency(src) || !is_currency(dst) {
+                error!(ExpectedCurrency: lhs.range, unit.range);
+            }
+            let (src, dst) = (src.clone(), dst.clone());
+
+            lhs.apply_modifiers(self.context.borrow().settings.limits.max_factorial)?;
+            let AstNodeData::Literal(n) = &mut lhs.data else { error!(ExpectedNumber: lhs.range); };
+            let context = self.context.borrow();
+            let converted = context.currencies.convert_at(
+                &src, lhs.range,
+                &dst, unit.range,
+                date_object.date, date.range,
+                *n,
+                &context.capabilities,
+                Self::remaining_time(),
+                &context.cancellation,
+            )?;
+            // Exchange rates are linear, so the uncertainty scales the same way
+            if *n != 0.0 { lhs.uncertainty *= (converted / *n).abs(); }
+            *n = converted;
+            lhs.unit = Some(Unit::Unit(dst, 1.0, unit.range));
+            drop(context);
+
+            self.ast.drain(i + 1..=i + 4);
+        }
+
+        Ok(())
+    }
+
+    /// Evaluates `map`, `filter`, `reduce` and `apply`, whose first argument is a function. It's
+    /// kept out of [Engine::eval_functions] so that the stack frame of the recursion stays small.
+    #[inline(never)]
+    fn eval_higher_order_function(context: &Context, func_name: &str, arg_asts: &mut [Vec<AstNode>], receiver: &AstNode) -> Result<AstNode> {
+        let range = full_range(&arg_asts[0]);
+        let Value::Object(CalculatorObject::Function(function)) = Self::evaluate(take(&mut arg_asts[0]), context.clone())? else {
+            error!(ExpectedFunction: range);
+        };
+        let call = |args: &[f64]| -> Result<f64> {
+            let args = args.iter().map(|arg| NumberValue::new(*arg)).collect();
+            match Self::call_function(context, &function.name, args, receiver.range)? {
+                Value::Number(number) => Ok(number.number),
+                Value::Object(_) => error!(ExpectedNumber: receiver.range),
+            }
+        };
+
+        Ok(if func_name == "apply" {
+            let mut args = vec![];
+            for ast in &mut arg_asts[1..] {
+                args.push(Self::evaluate_to_number(take(ast), context.clone())?);
+            }
+            Self::call_function(context, &function.name, args, receiver.range)?.to_ast_node_from(receiver)
+        } else {
+            let range = full_range(&arg_asts[1]);
+            let Value::Object(CalculatorObject::Vector(vector)) = Self::evaluate(take(&mut arg_asts[1]), context.clone())? else {
+                error!(ExpectedVector: range);
+            };
+
+            let data = match func_name {
+                "map" => {
+                    let numbers = vector.numbers.iter().map(|n| call(&[*n])).collect::<Result<_>>()?;
+                    AstNodeData::Object(CalculatorObject::Vector(Vector { numbers }))
+                }
+                "filter" => {
+                    let mut numbers = vec![];
+                    for n in vector.numbers {
+                        if call(&[n])? != 0.0 { numbers.push(n); }
+                    }
+                    AstNodeData::Object(CalculatorObject::Vector(Vector { numbers }))
+                }
+                _ => {
+                    // Without an initial value, the first element is used
+                    let (initial, numbers) = match arg_asts.get_mut(2) {
+                        Some(initial) => (Self::evaluate_to_number(take(initial), context.clone())?.number, &vector.numbers[..]),
+                        None => match vector.numbers.split_first() {
+                            Some((first, rest)) => (*first, rest),
+                            None => (f64::NAN, &[][..]),
+                        },
+                    };
+                    let mut result = initial;
+                    for n in numbers {
+                        result = call(&[result, *n])?;
+                    }
+                    AstNodeData::Literal(result)
+                }
+            };
+            AstNode::from(receiver, data)
+        })
+    }
+
+    /// Evaluates `fmt(template, values...)`, which replaces the `{}`s in the template with the values
+    /// formatted like results
+    #[inline(never)]
+    fn eval_fmt(context: &Context, arg_asts: &mut [Vec<AstNode>], receiver: &AstNode) -> Result<AstNode> {
+        let range = full_range(&arg_asts[0]);
+        let Value::Object(CalculatorObject::String(template)) = Self::evaluate(take(&mut arg_asts[0]), context.clone())? else {
+            error!(ExpectedString: range);
+        };
+        let parts = template.text.split("{}").collect::<Vec<_>>();
+        if parts.len() != arg_asts.len() {
+            error!(WrongNumberOfArguments(parts.len()): receiver.range.extend(full_range(arg_asts.last().unwrap())));
+        }
+
+        let mut text = parts[0].to_owned();
+        for (part, ast) in parts[1..].iter().zip(&mut arg_asts[1..]) {
+            let value = Self::evaluate(take(ast), context.clone())?;
+            text += value.format(&context.borrow().settings, false).trim_end();
+            text += part;
+        }
+        Ok(AstNode::from(receiver, AstNodeData::Object(CalculatorObject::String(StringObject { text }))))
+    }
+
+    /// Evaluates the call of a function that was passed as a value (e.g. `g(16)` after `g := sqrt`)
+    #[inline(never)]
+    fn eval_function_reference_call(context: &Context, name: &str, arg_asts: &mut [Vec<AstNode>], receiver: &AstNode) -> Result<AstNode> {
+        let mut args = vec![];
+        for ast in arg_asts {
+            args.push(Self::evaluate_to_number(take(ast), context.clone())?);
+        }
+        Ok(Self::call_function(context, name, args, receiver.range)?.to_ast_node_from(receiver))
+    }
+
+    /// Calls the standard or custom function `name`, e.g. when it was passed as a value
+    fn call_function(context: &Context, name: &str, args: Vec<NumberValue>, range: SourceRange) -> Result<Value> {
+        match context.borrow().env.function_argument_count(name) {
+            Some(ArgCount::Single(count)) if count != args.len() => error!(WrongNumberOfArguments(count): range),
+            Some(ArgCount::Multiple(options)) if !options.contains(&args.len()) => {
+                error!(WrongNumberOfArgumentsMultiple(options): range);
+            }
+            Some(ArgCount::AtLeast(min)) if args.len() < min => error!(WrongNumberOfArgumentsAtLeast(min): range),
+            _ => {}
+        }
+
+        let result = context.borrow().env.resolve_function(name, &args);
+        match result {
+            Ok((number, unit)) => Ok(Value::number(number, unit, false, Format::Decimal)),
+            Err(ErrorType::UnknownFunction(..)) => {
+                let args = args.into_iter().map(|arg| (arg, range)).collect::<Vec<_>>();
+                context.borrow().env.resolve_custom_function(name, &args, range, context.clone())
+            }
+            Err(ty) => Err(ty.with(range)),
+        }
+    }
+
+    /// Propagates the uncertainties of `args` through the standard function `name`, by taking the
+    /// largest deviation from `result` when varying each argument by its uncertainty
+    fn function_uncertainty(context: &Context, name: &str, args: &[NumberValue], result: f64) -> f64 {
+        let env = &context.borrow().env;
+        let mut varied_args = args.to_vec();
+        let mut uncertainty = 0.0;
+        for (i, arg) in args.iter().enumerate() {
+            if arg.uncertainty == 0.0 { continue; }
+
+            let mut deviation = 0.0f64;
+            for varied in [arg.number + arg.uncertainty, arg.number - arg.uncertainty] {
+                varied_args[i].number = varied;
+                if let Ok((varied_result, _)) = env.resolve_function(name, &varied_args) {
+                    if varied_result.is_finite() { deviation = deviation.max((varied_result - result).abs()); }
+                }
+            }
+            varied_args[i].number = arg.number;
+            uncertainty += deviation;
+        }
+        uncertainty
+    }
+
+    /// Adds a step like `tax (19%): 120 + 22.8 = 142.8` to the breakdown of the calculation
+    fn push_breakdown(context: &Context, description: &str, rate: f64, before: f64, after: f64) {
+        let settings = context.borrow().settings;
+        let format = |n: f64| Format::Decimal.format(n, &settings.output, false);
+        let sign = if after < before { '-' } else { '+' };
+        let step = format!(
+            "{description} ({}%): {} {sign} {} = {}",
+            format(rate * 100.0), format(before), format((after - before).abs()), format(after),
+        );
+        context.borrow_mut().env.breakdown.push(step);
+    }
+
+    /// Adds a step like `-7 mod 3 = 2 (floored)` to the breakdown of the calculation, since the
+    /// result depends on the modulo setting
+    fn push_modulo_breakdown(context: &Context, dividend: f64, divisor: f64, result: f64) {
+        let settings = context.borrow().settings;
+        let format = |n: f64| Format::Decimal.format(n, &settings.output, false);
+        let mode = settings.modulo.to_string().to_lowercase();
+        let step = format!("{} mod {} = {} ({mode})", format(dividend), format(divisor), format(result));
+        context.borrow_mut().env.breakdown.push(step);
+    }
+
     fn eval_operators(&mut self, operators: &[Operator]) -> Result<()> {
         let mut i = 0usize;
         while i < self.ast.len() - 1 {
-            let [lhs, operator, rhs] = &mut self.ast[i..=i + 2] else { unreachable!() };
-            let op = match_ast_node!(AstNodeData::Operator(op), op, operator);
-
-            if operators.contains(&op) {
-                if let AstNodeData::Object(object) = &lhs.data {
-                    let new_lhs = object.apply(lhs.range, (op, operator.range), rhs, false)?;
-                    let _ = replace(lhs, new_lhs);
-                } else if let AstNodeData::Object(object) = &rhs.data {
-                    let new_lhs = object.apply(rhs.range, (op, operator.range), lhs, true)?;
-                    let _ = replace(lhs, new_lhs);
-                } else {
-                    lhs.apply(operator, rhs, &self.context.borrow().currencies)?;
-                }
+            let op = match_ast_node!(AstNodeData::Operator(op), op, self.ast[i + 1]);
 
-                // remove operator and rhs
-                self.ast.remove(i + 1);
-                self.ast.remove(i + 1);
-            } else {
+            if !operators.contains(&op) {
                 i += 2;
+            } else if op == Operator::Exponentiation {
+                // Exponentiation is right-associative (e.g. `2^3^2` is `2^(3^2)`), so chains of it
+                // are evaluated from the right
+                let mut last = i;
+                while self.ast.get(last + 3).map_or(false, |node| node.data == AstNodeData::Operator(Operator::Exponentiation)) {
+                    last += 2;
+                }
+                for j in (i..=last).rev().step_by(2) {
+                    self.apply_operator(j)?;
+                }
+            } else {
+                self.apply_operator(i)?;
             }
         }
 
         Ok(())
     }
+
+    /// Applies the operator after the node at `i` to it and the node after the operator, replacing
+    /// the three nodes with the result
+    fn apply_operator(&mut self, i: usize) -> Result<()> {
+        let [lhs, operator, rhs] = &mut self.ast[i..=i + 2] else { unreachable!() };
+        let op = match_ast_node!(AstNodeData::Operator(op), op, operator);
+
+        let max_factorial = self.context.borrow().settings.limits.max_factorial;
+        if let AstNodeData::Object(object) = &lhs.data {
+            rhs.apply_modifiers(max_factorial)?;
+            let new_lhs = object.apply(lhs.range, (op, operator.range), rhs, false)?;
+            let _ = replace(lhs, new_lhs);
+        } else if let AstNodeData::Object(object) = &rhs.data {
+            lhs.apply_modifiers(max_factorial)?;
+            let new_lhs = object.apply(rhs.range, (op, operator.range), lhs, true)?;
+            let _ = replace(lhs, new_lhs);
+        } else {
+            let mut warnings = vec![];
+            let settings = self.context.borrow().settings;
+            let adjustment = rhs.adjustment().filter(|_| matches!(op, Operator::Plus | Operator::Minus));
+            lhs.apply_modifiers(max_factorial)?;
+            rhs.apply_modifiers(max_factorial)?;
+            let before = if let AstNodeData::Literal(n) = lhs.data { n } else { f64::NAN };
+            let divisor = if let AstNodeData::Literal(n) = rhs.data { n } else { f64::NAN };
+
+            lhs.apply(operator, rhs, &self.context.borrow().currencies, &settings, &mut warnings)?;
+            self.context.borrow_mut().env.warnings.append(&mut warnings);
+
+            if let (Operator::Modulo, AstNodeData::Literal(after)) = (op, &lhs.data) {
+                let after = *after;
+                Self::push_modulo_breakdown(&self.context, before, divisor, after);
+            }
+
+            if let (Some(adjustment), AstNodeData::Literal(after), AstNodeData::Literal(rate)) = (adjustment, &lhs.data, &rhs.data) {
+                let (after, rate) = (*after, *rate);
+                Self::push_breakdown(&self.context, adjustment.name(), rate, before, after);
+            }
+        }
+
+        // remove operator and rhs
+        self.ast.remove(i + 1);
+        self.ast.remove(i + 1);
+        Ok(())
+    }
 }
 
 pub fn full_range(ast: &[AstNode]) -> SourceRange {
-    ast.first().unwrap().range.extend(ast.last().unwrap().range)
+    match (ast.first(), ast.last()) {
+        (Some(first), Some(last)) => first.range.extend(last.range),
+        _ => SourceRange::empty(),
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use std::cell::RefCell;
     use std::rc::Rc;
-    use std::sync::Arc;
 
     use chrono::NaiveDate;
 
@@ -715,11 +1493,7 @@ mod tests {
     macro_rules! eval {
         ($str:expr) => {
             {
-                let context = Rc::new(RefCell::new(ContextData {
-                    env: Environment::new(),
-                    currencies: Arc::new(Currencies::none()),
-                    settings: Settings::default(),
-                }));
+                let context = Rc::new(RefCell::new(ContextData::default()));
                 Engine::evaluate(
                     if let ParserResultData::Calculation(ast) = Parser::from_tokens(&tokenize($str)?, context.clone()).parse_single()?.data { ast }
                     else { panic!("Expected ParserResultData::Calculation"); },
@@ -732,11 +1506,7 @@ mod tests {
     macro_rules! eval_obj {
         ($str:expr) => {
             {
-                let context = Rc::new(RefCell::new(ContextData {
-                    env: Environment::new(),
-                    currencies: Arc::new(Currencies::none()),
-                    settings: Settings::default(),
-                }));
+                let context = Rc::new(RefCell::new(ContextData::default()));
                 Engine::evaluate(
                     if let ParserResultData::Calculation(ast) = Parser::from_tokens(&tokenize($str)?, context.clone()).parse_single()?.data { ast }
                     else { panic!("Expected ParserResultData::Calculation"); },
@@ -752,6 +1522,17 @@ mod tests {
         }
     }
 
+    macro_rules! solve {
+        ($str:expr) => {
+            {
+                let context = Rc::new(RefCell::new(ContextData::default()));
+                let ParserResultData::Equation { lhs, rhs, is_question_mark_in_lhs, .. } = Parser::from_tokens(&tokenize($str)?, context.clone()).parse_single()?.data
+                    else { panic!("Expected ParserResultData::Equation"); };
+                Engine::solve(lhs, rhs, is_question_mark_in_lhs, context)?.to_number().cloned().unwrap()
+            }
+        }
+    }
+
     macro_rules! expect_obj {
         ($str:expr, $res:expr) => {
             assert_eq!(eval_obj!($str)?, $res)
@@ -804,6 +1585,20 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn exponentiation() -> Result<()> {
+        expect!("2^3^2", 512.0);
+        expect!("2 ** 3 ** 2", 512.0);
+        expect!("(2^3)^2", 64.0);
+        expect!("2^3^2 * 2", 1024.0);
+        expect!("2 & 3^2", 4.0);
+        expect!("√16", 4.0);
+        expect!("-√(9 + 7) + 2√4", 0.0);
+        expect!("root(3, 27)", 3.0);
+        expect!("root(3, -8)", -2.0);
+        Ok(())
+    }
+
     #[test]
     fn groups() -> Result<()> {
         expect!("2 * (2 + 2)", 8.0);
@@ -854,6 +1649,22 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn chained_conversions() -> Result<()> {
+        let res = eval!("5mi in km in sci")?;
+        assert_eq!((res.number, res.format), (8.04672, Format::Scientific));
+        assert_eq!(res.unit.unwrap().to_string(), "km");
+
+        let res = eval!("0xff in binary in kb")?;
+        assert_eq!((res.number, res.format), (255.0, Format::Binary));
+        assert_eq!(res.unit.unwrap().to_string(), "kb");
+
+        let res = eval!("1 + 2mi in km in hex in m")?;
+        assert_eq!(res.format, Format::Hex);
+        assert_eq!(res.unit.unwrap().to_string(), "m");
+        Ok(())
+    }
+
     #[test]
     fn convert_complex_units() -> Result<()> {
         expect!("60km/h in km/min", 1.0);
@@ -861,6 +1672,195 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn angles() -> Result<()> {
+        expect!("1grad in deg", 0.9);
+        expect!("30arcmin + 1800arcsec in deg", 1.0);
+
+        let res = eval!("1rad in dms")?;
+        assert_eq!((res.number, res.format), (1f64.to_degrees(), Format::Dms));
+        assert_eq!(res.unit, None);
+        Ok(())
+    }
+
+    #[test]
+    fn dimensions() -> Result<()> {
+        let res = eval!("10km/h * 30min")?;
+        assert_eq!((res.number, res.unit.unwrap().to_string()), (5.0, "km".to_string()));
+        let res = eval!("3m * 50cm")?;
+        assert_eq!((res.number, res.unit.unwrap().to_string()), (1.5, "m^2".to_string()));
+        let res = eval!("6m / 2m")?;
+        assert_eq!((res.number, res.unit), (3.0, None));
+
+        let error = eval!("5m + 3s").unwrap_err().error;
+        assert_eq!(error.to_string(), "Incompatible dimensions (length and time)");
+        let error = eval!("1kg * 9.81m/s^2 - 2J").unwrap_err().error;
+        assert_eq!(error.to_string(), "Incompatible dimensions (force and energy)");
+
+        assert_eq!(eval!("5km")?.compatible_units(), ["m", "mi", "ft", "inch", "yd", "nmi"]);
+        assert_eq!(eval!("2m")?.compatible_units(), ["mi", "ft", "inch", "yd", "nmi"]);
+        assert!(eval!("3")?.compatible_units().is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn higher_order_functions() -> Result<()> {
+        expect_obj!("map(sqrt, [1; 4; 9])", CalculatorObject::Vector(Vector { numbers: vec![1.0, 2.0, 3.0] }));
+        expect_obj!("filter(floor, [0.5; 1; 2.5])", CalculatorObject::Vector(Vector { numbers: vec![1.0, 2.5] }));
+        expect!("reduce(log, [2; 8])", 3.0);
+        expect!("reduce(log, [8], 2)", 3.0);
+        expect!("apply(sqrt, 16)", 4.0);
+        expect!("map(5, 0, 10, 20, 100)", 60.0);
+        expect_error!("map(2, [1])", ExpectedFunction);
+        expect_error!("map(sqrt, 2)", ExpectedVector);
+        assert!(matches!(eval!("apply(root, 1)").unwrap_err().error, ErrorType::WrongNumberOfArguments(2)));
+        expect!("apply(log, 1000)", 3.0);
+        expect!("apply(map, 5, 0, 10, 20, 100)", 60.0);
+        Ok(())
+    }
+
+    #[test]
+    fn strings() -> Result<()> {
+        let string = |text: &str| CalculatorObject::String(StringObject { text: text.to_owned() });
+        expect_obj!("\"speed\"", string("speed"));
+        expect_obj!("\"a\" + \"b\"", string("ab"));
+        expect_obj!("fmt(\"speed: {} km/h\", 25 * 2)", string("speed: 50 km/h"));
+        expect_obj!("fmt(\"{} in {}\", 2, \"total\")", string("2 in total"));
+        expect_error!("\"a\" + 1", ExpectedString);
+        expect_error!("fmt(5)", ExpectedString);
+        expect_error!("\"a\" * 2", UnsupportedOperation);
+        Ok(())
+    }
+
+    #[test]
+    fn programmer_functions() -> Result<()> {
+        expect!("popcount(0xff)", 8.0);
+        expect!("align(13, 8)", 16.0);
+        expect!("align(16, 8)", 16.0);
+        expect_error!("align(13, 0)", InvalidAlignment);
+        expect_obj!("bits(0b1010)", CalculatorObject::Vector(Vector { numbers: vec![1.0, 3.0] }));
+
+        let res = eval!("hexdump(48879)")?;
+        assert_eq!((res.number, res.format), (48879.0, Format::Hex));
+        Ok(())
+    }
+
+    #[test]
+    fn float_functions() -> Result<()> {
+        let res = eval!("float_bits(3.14)")?;
+        assert_eq!((res.number, res.format), (0x4048f5c3 as f64, Format::Hex));
+        expect!("float_bits(-1, 64)", 0xbff0000000000000u64 as f64);
+        expect!("from_bits(0x4048f5c3)", f32::from_bits(0x4048f5c3) as f64);
+        expect!("from_bits(0x3ff0000000000000, 64)", 1.0);
+        expect_error!("float_bits(1, 16)", InvalidFloatWidth);
+
+        let res = eval!("ulp(1)")?;
+        assert_eq!((res.number, res.format), (f64::EPSILON, Format::Scientific));
+        expect!("nextafter(1, 2)", 1.0 + f64::EPSILON);
+        expect!("nextafter(1, 0)", 1.0 - f64::EPSILON / 2.0);
+        expect!("nextafter(0, -1)", -f64::from_bits(1));
+        Ok(())
+    }
+
+    #[test]
+    fn adjustments() -> Result<()> {
+        expect!("120 + 19% tax", 120.0 * 1.19);
+        expect!("80 - 25% discount", 60.0);
+        expect!("2 * 50% tip", 1.0);
+        expect!("tax(100, 7%)", 107.0);
+        expect!("net(119)", 100.0);
+        expect!("tip(40, 15%)", 46.0);
+        expect!("discount(80, 25%)", 60.0);
+        Ok(())
+    }
+
+    #[test]
+    fn finance_functions() -> Result<()> {
+        // The expected values are the results of the same functions in spreadsheets
+        let approx = |input: &str, expected: f64| -> Result<()> {
+            let number = eval!(input)?.number;
+            assert!((number - expected).abs() < 1e-6, "{input} = {number}, expected {expected}");
+            Ok(())
+        };
+
+        approx("fv(5%, 10, -100, -1000)", 2886.683880332)?;
+        approx("fv(0.06 / 12, 10, -200, -500)", 2571.175347652)?;
+        approx("fv(0, 12, -100, 0)", 1200.0)?;
+        approx("pmt(5% / 12, 360, 200000)", -1073.643246024)?;
+        approx("pmt(8% / 12, 10, 10000)", -1037.032089359)?;
+        approx("pmt(0, 10, 1000)", -100.0)?;
+        approx("npv(10%, [-10000; 3000; 4200; 6800])", 1188.443412335)?;
+        approx("npv(8%, [8000; 9200; 10000; 12000; 14500]) - 40000", 1922.061554932)?;
+        approx("irr([-70000; 12000; 15000; 18000; 21000; 26000])", 0.086630948)?;
+        approx("irr([-70000; 12000; 15000; 18000; 21000])", -0.021244848)?;
+        approx("irr([-100; 60; 60])", 0.130662386)?;
+
+        let res = eval!("pmt(0, 10, 1000 EUR)")?;
+        assert_eq!((res.number, res.unit.map(|unit| unit.to_string())), (-100.0, Some("EUR".to_string())));
+        expect_error!("irr([1; 2])", NoInternalRateOfReturn);
+        expect_error!("npv(10%, 5)", ExpectedVector);
+        Ok(())
+    }
+
+    #[test]
+    fn uncertainties() -> Result<()> {
+        let uncertainty = |input: &str| -> Result<f64> { Ok(eval!(input)?.uncertainty) };
+        let approx = |input: &str, expected: f64| -> Result<()> {
+            let actual = uncertainty(input)?;
+            assert!((actual - expected).abs() < 1e-9, "{input} = ± {actual}, expected ± {expected}");
+            Ok(())
+        };
+
+        expect!("(5.0 ± 0.1) * (2.0 ± 0.05)", 10.0);
+        approx("(5.0 ± 0.1) * (2.0 ± 0.05)", 0.45)?;
+        approx("(5 +- 1) + (3 +- 2)", 3.0)?;
+        approx("(5 ± 1) - (3 ± 2)", 3.0)?;
+        approx("(10 ± 1) / (2 ± 0.1)", 0.75)?;
+        approx("(3 ± 0.1)^2", 0.6)?;
+        approx("2 * (3 ± 0.1) + 1", 0.2)?;
+        approx("(100 ± 10) + 19% tax", 11.9)?;
+        approx("(10 ± 1)%", 0.01)?;
+        approx("5m ± 1cm in cm", 1.0)?;
+        approx("sqrt(4 ± 0.44)", 2.0 - 3.56f64.sqrt())?;
+        approx("abs(-2 ± 0.5)", 0.5)?;
+        assert_eq!(uncertainty("2 * 3 + 1")?, 0.0);
+
+        expect_error!("(5 ± 1) & 1", UncertainInteger);
+        expect_error!("(3 ± 1)!", UncertainInteger);
+        Ok(())
+    }
+
+    #[test]
+    fn intervals() -> Result<()> {
+        let interval = |lower: f64, upper: f64| CalculatorObject::Interval(Interval { lower, upper });
+
+        expect_obj!("[1, 2] + [3, 4]", interval(4.0, 6.0));
+        expect_obj!("10 - [1, 2]", interval(8.0, 9.0));
+        expect_obj!("[-1, 2] * [-3, 4]", interval(-6.0, 8.0));
+        expect_obj!("[2, 4] / [-2, -1]", interval(-4.0, -1.0));
+        expect_obj!("[-3, 2]^2", interval(0.0, 9.0));
+        expect_obj!("[-3, 2]^3", interval(-27.0, 8.0));
+        expect_obj!("[1, 4]^0.5", interval(1.0, 2.0));
+        expect_obj!("2 * [1, 2] + 1", interval(3.0, 5.0));
+        expect_obj!("[(1 + 1), 3 * 2]", interval(2.0, 6.0));
+
+        // Non-monotonic functions have their extrema inside the interval
+        expect_obj!("sin([0, 3])", interval(0.0, 1.0));
+        expect_obj!("cos([-1, 4])", interval(-1.0, 1.0));
+        expect_obj!("abs([-3, 1])", interval(0.0, 3.0));
+        expect_obj!("ln([1, 1])", interval(0.0, 0.0));
+        expect_obj!("floor([1.5, 3.5])", interval(1.0, 3.0));
+
+        expect_error!("[1, 2] / [-1, 1]", DivideByZero);
+        expect_error!("[-1, 1]^-1", DivideByZero);
+        expect_error!("[-1, 1]^0.5", UndefinedInInterval);
+        expect_error!("tan([1, 2])", UndefinedInInterval);
+        expect_error!("sqrt([-1, 4])", UndefinedInInterval);
+        expect_error!("ln([-1, 1])", UndefinedInInterval);
+        expect_error!("[1, 2] mod 2", UnsupportedOperation);
+        Ok(())
+    }
+
     #[test]
     fn divide_by_zero() -> Result<()> {
         expect_error!("3 / 0", DivideByZero);
@@ -872,6 +1872,54 @@ mod tests {
         expect_obj!("{date 01.01.2023}", CalculatorObject::Date(DateObject { date: NaiveDate::from_ymd_opt(2023, 1, 1).unwrap() }));
         expect_obj!("{date 01.01.2023} + 3d", CalculatorObject::Date(DateObject { date: NaiveDate::from_ymd_opt(2023, 1, 4).unwrap() }));
         expect!("{date 05.01.2023} - {date 01.01.2023}", 4.0);
+        expect_obj!("{date: 01.01.2023}", CalculatorObject::Date(DateObject { date: NaiveDate::from_ymd_opt(2023, 1, 1).unwrap() }));
+        expect_obj!("{date:01.01.2023}", CalculatorObject::Date(DateObject { date: NaiveDate::from_ymd_opt(2023, 1, 1).unwrap() }));
+        Ok(())
+    }
+
+    #[test]
+    fn date_time_object() -> Result<()> {
+        expect!("unixtime({timestamp 1700000000})", 1_700_000_000.0);
+        expect!("unixtime({timestamp 1700000000} in UTC+2)", 1_700_000_000.0);
+        expect!("unixtime({timestamp 1700000000} + 1h)", 1_700_003_600.0);
+        expect!("{timestamp 1700000000} - {timestamp 1699990000}", 10_000.0);
+        expect!("unixtime({timestamp: 1700000000})", 1_700_000_000.0);
+
+        let datetime = eval_obj!("{timestamp 1700000000} in UTC-5:30")?;
+        assert_eq!(datetime.to_string(&Settings::default()), "14.11.2023 16:43:20 UTC-5:30");
+        expect_error!("unixtime(5)", ExpectedDate);
+        Ok(())
+    }
+
+    #[test]
+    fn solve_infers_unit() -> Result<()> {
+        let result = solve!("? + 2km = 5km");
+        assert!((result.number - 3.0).abs() < 1e-9);
+        assert_eq!(result.unit.map(|u| u.to_string()), Some("km".to_string()));
+
+        let result = solve!("? * 2 = 10m");
+        assert!((result.number - 5.0).abs() < 1e-9);
+        assert_eq!(result.unit.map(|u| u.to_string()), Some("m".to_string()));
+
+        let result = solve!("? + 2km = 5000m");
+        assert!((result.number - 3.0).abs() < 1e-9);
+        assert_eq!(result.unit.map(|u| u.to_string()), Some("km".to_string()));
+
+        let result = solve!("? + 2 = 5");
+        assert!((result.number - 3.0).abs() < 1e-9);
+        assert!(result.unit.is_none());
+
+        let result = solve!("? * 2 km = 10 km^2");
+        assert!((result.number - 5.0).abs() < 1e-9);
+        assert_eq!(result.unit.map(|u| u.to_string()), Some("km".to_string()));
+
+        let result = solve!("? / 2 h = 50 km/h");
+        assert!((result.number - 100.0).abs() < 1e-9);
+        assert_eq!(result.unit.map(|u| u.to_string()), Some("km".to_string()));
+
+        let result = solve!("? * 2km = 10km");
+        assert!((result.number - 5.0).abs() < 1e-9);
+        assert!(result.unit.is_none());
         Ok(())
     }
 }
@@ -0,0 +1,286 @@
+/*
+ * Copyright (c) 2023, david072
+ *
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+//! Numerical analysis of single argument functions (e.g. for finding the roots of a plotted
+//! function) and of relations between `x` and `y`
+
+use std::cell::RefCell;
+use std::ops::RangeInclusive;
+use std::rc::Rc;
+
+use crate::{Context, ContextData, Engine, Format};
+use crate::astgen::ast::{AstNode, BooleanOperator};
+use crate::engine::Value;
+use crate::environment::{Environment, Function, FunctionArgument, Variable};
+
+/// How often found points are refined. Each iteration halves (or, for extrema, shrinks by the
+/// golden ratio) the interval containing the point, which is plenty for f64 precision.
+const REFINEMENT_ITERATIONS: usize = 64;
+
+/// The free variables of a [Relation]
+pub const RELATION_VARIABLES: [&str; 2] = ["x", "y"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExtremumKind {
+    Minimum,
+    Maximum,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Extremum {
+    pub x: f64,
+    pub y: f64,
+    pub kind: ExtremumKind,
+}
+
+/// Calculates a single argument function for arbitrary arguments. Unlike calling
+/// [Environment::resolve_specific_function] for every argument, the environment is only cloned
+/// once.
+pub struct FunctionEvaluator<'a> {
+    function: &'a Function,
+    argument: &'a FunctionArgument,
+    context: Context,
+}
+
+impl<'a> FunctionEvaluator<'a> {
+    /// Returns [None] if `f` doesn't take exactly one argument
+    pub fn new(f: &'a Function, env: &Environment, context: &Context) -> Option<Self> {
+        let [argument] = f.arguments.as_slice() else { return None; };
+        let context = Rc::new(RefCell::new(ContextData {
+            env: env.clone(),
+            ..context.borrow().clone()
+        }));
+        Some(Self { function: f, argument, context })
+    }
+
+    /// Returns the function's value for `x`, or NaN if it can't be calculated or doesn't result
+    /// in a number
+    pub fn evaluate(&self, x: f64) -> f64 {
+        let (name, unit) = self.argument;
+        let argument = Variable(Value::number(x, unit.clone(), false, Format::Decimal));
+        if self.context.borrow_mut().env.set_variable(name, argument).is_err() {
+            return f64::NAN;
+        }
+
+        Environment::evaluate_function(self.function, self.context.clone()).ok()
+            .and_then(|value| value.to_number().map(|number| number.number))
+            .unwrap_or(f64::NAN)
+    }
+
+    /// Finds the arguments in `range` for which the function is zero. Roots at which the
+    /// function only touches zero without changing its sign are only found if they happen to be
+    /// one of the `samples` evenly spaced arguments.
+    pub fn roots(&self, range: RangeInclusive<f64>, samples: usize) -> Vec<f64> {
+        roots(|x| self.evaluate(x), range, samples)
+    }
+
+    /// Finds the local minima and maxima in `range` by comparing `samples` evenly spaced arguments
+    pub fn extrema(&self, range: RangeInclusive<f64>, samples: usize) -> Vec<Extremum> {
+        extrema(|x| self.evaluate(x), range, samples)
+    }
+
+    /// Finds the points in `range` at which this function and `other` have the same value
+    pub fn intersections(&self, other: &FunctionEvaluator, range: RangeInclusive<f64>, samples: usize) -> Vec<(f64, f64)> {
+        roots(|x| self.evaluate(x) - other.evaluate(x), range, samples)
+            .into_iter()
+            .map(|x| (x, self.evaluate(x)))
+            .collect()
+    }
+}
+
+/// An equation or inequality in `x` and `y` (e.g. `x^2 + y^2 = 25` or `y > 2x + 1`), whose
+/// solutions can be plotted
+#[derive(Debug, Clone)]
+pub struct Relation {
+    pub(crate) lhs: Vec<AstNode>,
+    pub(crate) rhs: Vec<AstNode>,
+    pub(crate) operator: BooleanOperator,
+}
+
+impl Relation {
+    pub fn operator(&self) -> BooleanOperator {
+        self.operator
+    }
+}
+
+/// Calculates both sides of a [Relation] for arbitrary values of `x` and `y`, only cloning the
+/// environment once
+pub struct RelationEvaluator<'a> {
+    relation: &'a Relation,
+    context: Context,
+}
+
+impl<'a> RelationEvaluator<'a> {
+    pub fn new(relation: &'a Relation, env: &Environment, context: &Context) -> Self {
+        let context = Rc::new(RefCell::new(ContextData {
+            env: env.clone(),
+            ..context.borrow().clone()
+        }));
+        Self { relation, context }
+    }
+
+    /// Returns the difference between the left and the right hand side for `x` and `y`, or NaN
+    /// if either side can't be calculated or doesn't result in a number. The relation holds where
+    /// the difference compares to zero like the sides are compared (see [Self::holds]).
+    pub fn difference(&self, x: f64, y: f64) -> f64 {
+        for (name, value) in RELATION_VARIABLES.iter().zip([x, y]) {
+            let variable = Variable(Value::only_number(value));
+            if self.context.borrow_mut().env.set_variable(name, variable).is_err() {
+                return f64::NAN;
+            }
+        }
+
+        let side = |ast: &Vec<AstNode>| Engine::evaluate(ast.clone(), self.context.clone()).ok()
+            .and_then(|value| value.to_number().map(|number| number.number));
+        match (side(&self.relation.lhs), side(&self.relation.rhs)) {
+            (Some(lhs), Some(rhs)) => lhs - rhs,
+            _ => f64::NAN,
+        }
+    }
+
+    /// Returns whether a [difference](Self::difference) satisfies the relation's operator
+    pub fn holds(&self, difference: f64) -> bool {
+        !difference.is_nan() && self.relation.operator.check(difference, 0.0)
+    }
+}
+
+/// Returns `samples` evenly spaced arguments from the start to the end of `range`
+pub(crate) fn arguments(range: RangeInclusive<f64>, samples: usize) -> impl Iterator<Item=f64> {
+    let (start, end) = range.into_inner();
+    let step = if samples > 1 { (end - start) / (samples - 1) as f64 } else { 0.0 };
+    (0..samples).map(move |i| start + i as f64 * step)
+}
+
+/// Finds the zeros of `f` by looking for sign changes between `samples` evenly spaced arguments in
+/// `range` and refining them using bisection
+fn roots(f: impl Fn(f64) -> f64, range: RangeInclusive<f64>, samples: usize) -> Vec<f64> {
+    let points = arguments(range, samples).map(|x| (x, f(x))).collect::<Vec<_>>();
+
+    let mut roots = vec![];
+    for (i, &(x, y)) in points.iter().enumerate() {
+        if y == 0.0 {
+            roots.push(x);
+            continue;
+        }
+
+        let Some(&(next_x, next_y)) = points.get(i + 1) else { break; };
+        if !y.is_finite() || !next_y.is_finite() || next_y == 0.0 || y.signum() == next_y.signum() {
+            continue;
+        }
+
+        let root = bisect(&f, x, next_x, y);
+        // At a pole (e.g. of 1/x) the sign changes as well, but the function doesn't approach zero
+        if f(root).abs() <= y.abs().min(next_y.abs()) {
+            roots.push(root);
+        }
+    }
+    roots
+}
+
+/// Finds the local minima and maxima of `f` by comparing `samples` evenly spaced arguments in
+/// `range` with their neighbours and refining them using golden-section search
+fn extrema(f: impl Fn(f64) -> f64, range: RangeInclusive<f64>, samples: usize) -> Vec<Extremum> {
+    let points = arguments(range, samples).map(|x| (x, f(x))).collect::<Vec<_>>();
+
+    points.windows(3)
+        .filter_map(|window| {
+            let [(start, y0), (_, y1), (end, y2)] = [window[0], window[1], window[2]];
+            if !y0.is_finite() || !y1.is_finite() || !y2.is_finite() { return None; }
+
+            let kind = if y1 < y0 && y1 <= y2 {
+                ExtremumKind::Minimum
+            } else if y1 > y0 && y1 >= y2 {
+                ExtremumKind::Maximum
+            } else {
+                return None;
+            };
+
+            let x = match kind {
+                ExtremumKind::Minimum => golden_section_minimum(&f, start, end),
+                ExtremumKind::Maximum => golden_section_minimum(|x| -f(x), start, end),
+            };
+            let y = f(x);
+            // Refining a "maximum" next to a pole (e.g. of 1/x²) runs off towards infinity
+            if !y.is_finite() || (y - y1).abs() > (y1 - y0).abs() + (y1 - y2).abs() { return None; }
+            Some(Extremum { x, y, kind })
+        })
+        .collect()
+}
+
+fn bisect(f: impl Fn(f64) -> f64, mut low: f64, mut high: f64, mut low_y: f64) -> f64 {
+    for _ in 0..REFINEMENT_ITERATIONS {
+        let middle = (low + high) / 2.0;
+        let y = f(middle);
+        if y == 0.0 || !y.is_finite() { return middle; }
+
+        if y.signum() == low_y.signum() {
+            low = middle;
+            low_y = y;
+        } else {
+            high = middle;
+        }
+    }
+    (low + high) / 2.0
+}
+
+fn golden_section_minimum(f: impl Fn(f64) -> f64, mut low: f64, mut high: f64) -> f64 {
+    let ratio = (5f64.sqrt() - 1.0) / 2.0;
+    let mut left = high - ratio * (high - low);
+    let mut right = low + ratio * (high - low);
+    let (mut left_y, mut right_y) = (f(left), f(right));
+
+    for _ in 0..REFINEMENT_ITERATIONS {
+        if left_y < right_y {
+            high = right;
+            (right, right_y) = (left, left_y);
+            left = high - ratio * (high - low);
+            left_y = f(left);
+        } else {
+            low = left;
+            (left, left_y) = (right, right_y);
+            right = low + ratio * (high - low);
+            right_y = f(right);
+        }
+    }
+    (low + high) / 2.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPSILON: f64 = 1e-9;
+
+    #[test]
+    fn roots() {
+        let roots = super::roots(|x| x * x - 2.0, -3.0..=3.0, 100);
+        assert_eq!(roots.len(), 2);
+        assert!((roots[0] + 2f64.sqrt()).abs() < EPSILON);
+        assert!((roots[1] - 2f64.sqrt()).abs() < EPSILON);
+
+        // Sampled exactly
+        assert_eq!(super::roots(|x| x, -1.0..=1.0, 3), vec![0.0]);
+        // Poles aren't roots
+        assert!(super::roots(|x| 1.0 / x, -1.0..=1.0, 10).is_empty());
+        assert!(super::roots(|x| x.sqrt() + 1.0, -1.0..=1.0, 10).is_empty());
+    }
+
+    #[test]
+    fn extrema() {
+        let extrema = super::extrema(|x| x * x * x - 3.0 * x, -3.0..=3.0, 100);
+        assert_eq!(extrema.len(), 2);
+        assert_eq!(extrema[0].kind, ExtremumKind::Maximum);
+        assert!((extrema[0].x + 1.0).abs() < 1e-6);
+        assert!((extrema[0].y - 2.0).abs() < EPSILON);
+        assert_eq!(extrema[1].kind, ExtremumKind::Minimum);
+        assert!((extrema[1].x - 1.0).abs() < 1e-6);
+        assert!((extrema[1].y + 2.0).abs() < EPSILON);
+
+        assert!(super::extrema(|x| 2.0 * x, -1.0..=1.0, 10).is_empty());
+        assert!(super::extrema(|_| 1.0, -1.0..=1.0, 10).is_empty());
+        assert!(super::extrema(|x| 1.0 / (x * x), -1.0..=1.0, 10).is_empty());
+    }
+}
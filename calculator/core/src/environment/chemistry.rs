@@ -0,0 +1,149 @@
+/*
+ * Copyright (c) 2022-2023, david072
+ *
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+use crate::common::ErrorType;
+
+/// The chemical elements with their standard atomic weights in g/mol. Elements without a stable
+/// isotope have the mass number of their longest-lived isotope.
+const ELEMENTS: [(&str, f64); 118] = [
+    ("H", 1.008), ("He", 4.0026), ("Li", 6.94), ("Be", 9.0122), ("B", 10.81), ("C", 12.011),
+    ("N", 14.007), ("O", 15.999), ("F", 18.998), ("Ne", 20.180), ("Na", 22.990), ("Mg", 24.305),
+    ("Al", 26.982), ("Si", 28.085), ("P", 30.974), ("S", 32.06), ("Cl", 35.45), ("Ar", 39.948),
+    ("K", 39.098), ("Ca", 40.078), ("Sc", 44.956), ("Ti", 47.867), ("V", 50.942), ("Cr", 51.996),
+    ("Mn", 54.938), ("Fe", 55.845), ("Co", 58.933), ("Ni", 58.693), ("Cu", 63.546), ("Zn", 65.38),
+    ("Ga", 69.723), ("Ge", 72.630), ("As", 74.922), ("Se", 78.971), ("Br", 79.904), ("Kr", 83.798),
+    ("Rb", 85.468), ("Sr", 87.62), ("Y", 88.906), ("Zr", 91.224), ("Nb", 92.906), ("Mo", 95.95),
+    ("Tc", 98.0), ("Ru", 101.07), ("Rh", 102.91), ("Pd", 106.42), ("Ag", 107.87), ("Cd", 112.41),
+    ("In", 114.82), ("Sn", 118.71), ("Sb", 121.76), ("Te", 127.60), ("I", 126.90), ("Xe", 131.29),
+    ("Cs", 132.91), ("Ba", 137.33), ("La", 138.91), ("Ce", 140.12), ("Pr", 140.91), ("Nd", 144.24),
+    ("Pm", 145.0), ("Sm", 150.36), ("Eu", 151.96), ("Gd", 157.25), ("Tb", 158.93), ("Dy", 162.50),
+    ("Ho", 164.93), ("Er", 167.26), ("Tm", 168.93), ("Yb", 173.05), ("Lu", 174.97), ("Hf", 178.49),
+    ("Ta", 180.95), ("W", 183.84), ("Re", 186.21), ("Os", 190.23), ("Ir", 192.22), ("Pt", 195.08),
+    ("Au", 196.97), ("Hg", 200.59), ("Tl", 204.38), ("Pb", 207.2), ("Bi", 208.98), ("Po", 209.0),
+    ("At", 210.0), ("Rn", 222.0), ("Fr", 223.0), ("Ra", 226.0), ("Ac", 227.0), ("Th", 232.04),
+    ("Pa", 231.04), ("U", 238.03), ("Np", 237.0), ("Pu", 244.0), ("Am", 243.0), ("Cm", 247.0),
+    ("Bk", 247.0), ("Cf", 251.0), ("Es", 252.0), ("Fm", 257.0), ("Md", 258.0), ("No", 259.0),
+    ("Lr", 266.0), ("Rf", 267.0), ("Db", 268.0), ("Sg", 269.0), ("Bh", 270.0), ("Hs", 277.0),
+    ("Mt", 278.0), ("Ds", 281.0), ("Rg", 282.0), ("Cn", 285.0), ("Nh", 286.0), ("Fl", 289.0),
+    ("Mc", 290.0), ("Lv", 293.0), ("Ts", 294.0), ("Og", 294.0),
+];
+
+/// Calculates the molar mass of a chemical formula in g/mol. Formulas consist of element symbols
+/// with optional counts, which can be grouped with parentheses or square brackets (e.g.
+/// `Ca(OH)2`). Parts of hydrates are separated by `·`, `.` or `*` and can start with a count (e.g.
+/// `CuSO4·5H2O`).
+pub fn molar_mass(formula: &str) -> Result<f64, ErrorType> {
+    let mut parser = FormulaParser {
+        formula,
+        chars: formula.chars().filter(|c| !c.is_whitespace()).collect(),
+        index: 0,
+    };
+
+    let mut mass = 0.0;
+    loop {
+        let count = parser.accept_count()?.unwrap_or(1);
+        mass += count as f64 * parser.group()?;
+        match parser.next() {
+            None => break,
+            Some('·' | '.' | '*') => {}
+            Some(_) => return Err(parser.invalid()),
+        }
+    }
+    Ok(mass)
+}
+
+struct FormulaParser<'a> {
+    formula: &'a str,
+    chars: Vec<char>,
+    index: usize,
+}
+
+impl FormulaParser<'_> {
+    fn peek(&self) -> Option<char> { self.chars.get(self.index).copied() }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.index += 1;
+        Some(c)
+    }
+
+    fn invalid(&self) -> ErrorType { ErrorType::InvalidChemicalFormula(self.formula.to_owned()) }
+
+    /// The mass of the elements and groups up to the end of the current group
+    fn group(&mut self) -> Result<f64, ErrorType> {
+        let mut mass = 0.0;
+        let mut is_empty = true;
+        while let Some(c) = self.peek() {
+            let part = match c {
+                'A'..='Z' => {
+                    self.index += 1;
+                    let mut symbol = c.to_string();
+                    if let Some(c @ 'a'..='z') = self.peek() {
+                        self.index += 1;
+                        symbol.push(c);
+                    }
+                    ELEMENTS.iter()
+                        .find(|(element, _)| *element == symbol)
+                        .ok_or(ErrorType::UnknownElement(symbol))?
+                        .1
+                }
+                '(' | '[' => {
+                    self.index += 1;
+                    let mass = self.group()?;
+                    let close = if c == '(' { ')' } else { ']' };
+                    if self.next() != Some(close) { return Err(self.invalid()); }
+                    mass
+                }
+                _ => break,
+            };
+            mass += part * self.accept_count()?.unwrap_or(1) as f64;
+            is_empty = false;
+        }
+
+        if is_empty { return Err(self.invalid()); }
+        Ok(mass)
+    }
+
+    fn accept_count(&mut self) -> Result<Option<u32>, ErrorType> {
+        let start = self.index;
+        while self.peek().map_or(false, |c| c.is_ascii_digit()) { self.index += 1; }
+        if start == self.index { return Ok(None); }
+
+        let count = self.chars[start..self.index].iter().collect::<String>();
+        match count.parse::<u32>() {
+            Ok(count) if count != 0 => Ok(Some(count)),
+            _ => Err(self.invalid()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_mass(formula: &str, expected: f64) {
+        let mass = molar_mass(formula).unwrap();
+        assert!((mass - expected).abs() < 1e-9, "{formula}: {mass} != {expected}");
+    }
+
+    #[test]
+    fn formulas() {
+        assert_mass("H2O", 2.0 * 1.008 + 15.999);
+        assert_mass("H2SO4", 2.0 * 1.008 + 32.06 + 4.0 * 15.999);
+        assert_mass("Ca(OH)2", 40.078 + 2.0 * (15.999 + 1.008));
+        assert_mass("K4[Fe(CN)6]", 4.0 * 39.098 + 55.845 + 6.0 * (12.011 + 14.007));
+        assert_mass("CuSO4·5H2O", 63.546 + 32.06 + 4.0 * 15.999 + 5.0 * (2.0 * 1.008 + 15.999));
+        assert_mass("Na Cl", 22.990 + 35.45);
+    }
+
+    #[test]
+    fn invalid_formulas() {
+        assert!(matches!(molar_mass("Xy"), Err(ErrorType::UnknownElement(symbol)) if symbol == "Xy"));
+        for formula in ["h2o", "", "Ca(OH2", "H0", "H2O)"] {
+            assert!(matches!(molar_mass(formula), Err(ErrorType::InvalidChemicalFormula(_))), "{formula}");
+        }
+    }
+}
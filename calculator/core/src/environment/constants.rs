@@ -0,0 +1,118 @@
+/*
+ * Copyright (c) 2023, david072
+ *
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+use std::fmt::{Display, Formatter};
+
+use crate::common::SourceRange;
+use crate::engine::{Format, Value};
+use crate::environment::units::Unit;
+use crate::environment::Variable;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConstantCategory {
+    Universal,
+    Electromagnetic,
+    AtomicAndNuclear,
+    PhysicoChemical,
+}
+
+impl ConstantCategory {
+    pub const ALL: [ConstantCategory; 4] = [
+        Self::Universal,
+        Self::Electromagnetic,
+        Self::AtomicAndNuclear,
+        Self::PhysicoChemical,
+    ];
+}
+
+impl Display for ConstantCategory {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Universal => write!(f, "Universal"),
+            Self::Electromagnetic => write!(f, "Electromagnetic"),
+            Self::AtomicAndNuclear => write!(f, "Atomic and nuclear"),
+            Self::PhysicoChemical => write!(f, "Physico-chemical"),
+        }
+    }
+}
+
+/// A scientific constant (CODATA 2018), which can be used like a variable. User variables with the
+/// same name take precedence over it.
+#[derive(Debug)]
+pub struct Constant {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub category: ConstantCategory,
+    pub value: f64,
+    /// The units in the numerator of the constant's unit, together with their powers
+    numerator: &'static [(&'static str, f64)],
+    /// The units in the denominator of the constant's unit. Must be empty if `numerator` is.
+    denominator: &'static [(&'static str, f64)],
+}
+
+impl Constant {
+    pub fn unit(&self) -> Option<Unit> {
+        fn product(units: &[(&str, f64)]) -> Option<Unit> {
+            let mut units = units.iter()
+                .map(|(unit, power)| Unit::new(unit, *power, SourceRange::empty()))
+                .collect::<Vec<_>>();
+            match units.len() {
+                0 => None,
+                1 => units.pop(),
+                _ => Some(Unit::Product(units)),
+            }
+        }
+
+        match (product(self.numerator), product(self.denominator)) {
+            (Some(numerator), Some(denominator)) =>
+                Some(Unit::Fraction(Box::new(numerator), Box::new(denominator))),
+            (numerator, _) => numerator,
+        }
+    }
+
+    pub(crate) fn variable(&self) -> Variable {
+        Variable(Value::number(self.value, self.unit(), false, Format::Decimal))
+    }
+}
+
+macro_rules! constant {
+    ($name:literal, $description:literal, $category:ident, $value:expr, [$($num:literal $(^ $num_power:expr)?),*] / [$($denom:literal $(^ $denom_power:expr)?),*]) => {
+        Constant {
+            name: $name,
+            description: $description,
+            category: ConstantCategory::$category,
+            value: $value,
+            numerator: &[$(($num, constant!(@power $($num_power)?))),*],
+            denominator: &[$(($denom, constant!(@power $($denom_power)?))),*],
+        }
+    };
+    (@power $power:expr) => { $power };
+    (@power) => { 1.0 };
+}
+
+pub const CONSTANTS: [Constant; 17] = [
+    constant!("c", "Speed of light in vacuum", Universal, 299_792_458.0, ["m"] / ["s"]),
+    constant!("h", "Planck constant", Universal, 6.626_070_15e-34, ["J", "s"] / []),
+    constant!("hbar", "Reduced Planck constant", Universal, 1.054_571_817e-34, ["J", "s"] / []),
+    constant!("G", "Newtonian constant of gravitation", Universal, 6.674_30e-11, ["m" ^ 3.0] / ["kg", "s" ^ 2.0]),
+    constant!("g_n", "Standard acceleration of gravity", Universal, 9.806_65, ["m"] / ["s" ^ 2.0]),
+    constant!("e_charge", "Elementary charge", Electromagnetic, 1.602_176_634e-19, ["C"] / []),
+    constant!("epsilon_0", "Vacuum electric permittivity", Electromagnetic, 8.854_187_812_8e-12, ["F"] / ["m"]),
+    constant!("mu_0", "Vacuum magnetic permeability", Electromagnetic, 1.256_637_062_12e-6, ["H"] / ["m"]),
+    constant!("m_e", "Electron mass", AtomicAndNuclear, 9.109_383_701_5e-31, ["kg"] / []),
+    constant!("m_p", "Proton mass", AtomicAndNuclear, 1.672_621_923_69e-27, ["kg"] / []),
+    constant!("m_n", "Neutron mass", AtomicAndNuclear, 1.674_927_498_04e-27, ["kg"] / []),
+    constant!("a_0", "Bohr radius", AtomicAndNuclear, 5.291_772_109_03e-11, ["m"] / []),
+    constant!("alpha", "Fine-structure constant", AtomicAndNuclear, 7.297_352_569_3e-3, [] / []),
+    constant!("N_A", "Avogadro constant", PhysicoChemical, 6.022_140_76e23, ["mol" ^ -1.0] / []),
+    constant!("k_B", "Boltzmann constant", PhysicoChemical, 1.380_649e-23, ["J"] / ["K"]),
+    constant!("R", "Molar gas constant", PhysicoChemical, 8.314_462_618, ["J"] / ["mol", "K"]),
+    constant!("sigma", "Stefan-Boltzmann constant", PhysicoChemical, 5.670_374_419e-8, ["W"] / ["m" ^ 2.0, "K" ^ 4.0]),
+];
+
+pub(crate) fn get_constant(name: &str) -> Option<&'static Constant> {
+    CONSTANTS.iter().find(|constant| constant.name == name)
+}
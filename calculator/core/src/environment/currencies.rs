@@ -6,19 +6,35 @@
 
 use std::collections::HashMap;
 use std::sync::Mutex;
+use std::time::Duration;
 
+use chrono::NaiveDate;
+
+use crate::{Capabilities, CancellationToken, Capability};
 use crate::common::ErrorType;
 use crate::environment::default_currencies;
 use crate::Result;
 use crate::SourceRange;
 
+/// The symbols that can be written instead of currency codes (see
+/// [CurrencySettings](crate::CurrencySettings) for the currencies they stand for)
+pub const CURRENCY_SYMBOLS: [&str; 4] = ["$", "€", "£", "¥"];
+
 pub fn is_currency(str: &str) -> bool {
     default_currencies::CURRENCIES.contains_key(str)
 }
 
+/// The exchange rates of a past day: the day, the base currency and the rates relative to it
+pub type HistoricRates = (NaiveDate, String, HashMap<String, f64>);
+
 pub struct Currencies {
     pub base: Mutex<Option<String>>,
     pub currencies: Mutex<Option<HashMap<String, f64>>>,
+    /// The base currency and exchange rates of past days, which are loaded when they are first
+    /// needed
+    pub historic: Mutex<Vec<HistoricRates>>,
+    /// The days whose exchange rates couldn't be fetched, so that they aren't requested again
+    pub unavailable_historic: Mutex<Vec<NaiveDate>>,
 }
 
 impl Currencies {
@@ -32,6 +48,8 @@ impl Currencies {
         Self {
             base: Mutex::new(base),
             currencies: Mutex::new(currencies),
+            historic: Mutex::new(vec![]),
+            unavailable_historic: Mutex::new(vec![]),
         }
     }
 
@@ -46,6 +64,8 @@ impl Currencies {
         let res = std::sync::Arc::new(Currencies {
             base: Mutex::new(base),
             currencies: Mutex::new(currencies),
+            historic: Mutex::new(vec![]),
+            unavailable_historic: Mutex::new(vec![]),
         });
         updating::update_currencies(Some(res.clone()));
         res
@@ -55,6 +75,8 @@ impl Currencies {
         Currencies {
             base: Mutex::new(None),
             currencies: Mutex::new(None),
+            historic: Mutex::new(vec![]),
+            unavailable_historic: Mutex::new(vec![]),
         }
     }
 
@@ -84,41 +106,112 @@ impl Currencies {
             base.as_ref().unwrap()
         };
 
-        let get_currency = |curr| {
+        let get_currency = |curr: &str| {
             if use_default {
-                default_currencies::CURRENCIES.get(curr)
+                default_currencies::CURRENCIES.get(curr).copied()
             } else {
-                currencies.as_ref().unwrap().get(curr)
+                currencies.as_ref().unwrap().get(curr).copied()
             }
         };
 
-        let mut value = n;
-        // Convert to base currency if needed
-        if src_curr != base {
-            value /= match get_currency(src_curr) {
-                Some(v) => v,
-                None => {
-                    return Err(ErrorType::UnknownIdentifier(src_curr.to_owned()).with(src_range))
-                }
-            };
+        convert_with_rates(base, get_currency, src_curr, src_range, dst_curr, dst_range, n)
+    }
+
+    /// Converts `n` using the exchange rates of `date`. If they haven't been loaded yet, they are
+    /// read from the cache or fetched, as far as `capabilities` allow it. Fetching takes at most
+    /// `time_limit` and is given up if `cancellation` is cancelled in the meantime. `date_range` is
+    /// where errors about the rates not being available are shown.
+    #[allow(clippy::too_many_arguments)]
+    pub fn convert_at(
+        &self,
+        src_curr: &str,
+        src_range: SourceRange,
+        dst_curr: &str,
+        dst_range: SourceRange,
+        date: NaiveDate,
+        date_range: SourceRange,
+        n: f64,
+        capabilities: &Capabilities,
+        time_limit: Option<Duration>,
+        cancellation: &CancellationToken,
+    ) -> Result<f64> {
+        if src_curr == dst_curr {
+            return Ok(n);
         }
-        // Convert from base currency to dst currency if needed
-        if dst_curr != base {
-            value *= match get_currency(dst_curr) {
-                Some(v) => v,
-                None => {
-                    return Err(ErrorType::UnknownIdentifier(dst_curr.to_owned()).with(dst_range))
-                }
-            };
+
+        let convert = |historic: &[HistoricRates]| {
+            let (_, base, rates) = historic.iter().find(|(d, ..)| *d == date)?;
+            Some(convert_with_rates(base, |curr| rates.get(curr).copied(), src_curr, src_range, dst_curr, dst_range, n))
+        };
+        if let Some(result) = convert(&self.historic.lock().unwrap()) { return result; }
+
+        let unavailable = || Err(ErrorType::ExchangeRatesUnavailable(date.format("%d.%m.%Y").to_string()).with(date_range));
+        if self.unavailable_historic.lock().unwrap().contains(&date) { return unavailable(); }
+        if !capabilities.filesystem && !capabilities.network {
+            return Err(ErrorType::MissingCapability(Capability::Network).with(date_range));
         }
 
-        Ok(value)
+        // The lock isn't held while loading, since other calculations share the exchange rates
+        let loaded = updating::load_historic_currencies(date, capabilities, time_limit);
+        if cancellation.is_cancelled() { return Err(ErrorType::Cancelled.with(date_range)); }
+        let (base, rates) = match loaded {
+            Ok(rates) => rates,
+            Err(updating::LoadError::TimedOut) => return unavailable(),
+            Err(updating::LoadError::Unavailable) => {
+                // Without network access, the rates might still be fetched later on
+                if capabilities.network { self.unavailable_historic.lock().unwrap().push(date); }
+                return unavailable();
+            }
+        };
+
+        let mut historic = self.historic.lock().unwrap();
+        if !historic.iter().any(|(d, ..)| *d == date) { historic.push((date, base, rates)); }
+        convert(&historic).unwrap()
+    }
+}
+
+/// Converts `n` using the exchange rates relative to `base`, which are returned by `get_currency`
+fn convert_with_rates(
+    base: &str,
+    get_currency: impl Fn(&str) -> Option<f64>,
+    src_curr: &str,
+    src_range: SourceRange,
+    dst_curr: &str,
+    dst_range: SourceRange,
+    n: f64,
+) -> Result<f64> {
+    let mut value = n;
+    // Convert to base currency if needed
+    if src_curr != base {
+        value /= match get_currency(src_curr) {
+            Some(v) => v,
+            None => {
+                return Err(ErrorType::UnknownIdentifier(src_curr.to_owned(), Vec::new()).with(src_range))
+            }
+        };
+    }
+    // Convert from base currency to dst currency if needed
+    if dst_curr != base {
+        value *= match get_currency(dst_curr) {
+            Some(v) => v,
+            None => {
+                return Err(ErrorType::UnknownIdentifier(dst_curr.to_owned(), Vec::new()).with(dst_range))
+            }
+        };
     }
+
+    Ok(value)
 }
 
 mod updating {
     use std::collections::HashMap;
 
+    use std::time::Duration;
+
+    use chrono::NaiveDate;
+
+    use crate::Capabilities;
+
     #[cfg(not(target_arch = "wasm32"))]
     use crate::common::cache_dir;
 
@@ -128,8 +221,26 @@ mod updating {
     const CURRENCIES_FILE_NAME: &str = "currencies.txt";
     #[cfg(target_arch = "wasm32")]
     const LOCAL_STORAGE_KEY: &str = "currencies";
+    #[allow(dead_code)]
     const CURRENCY_API_URL: &str = "https://api.exchangerate.host/latest?base=EUR";
+    /// Followed by the date (e.g. `2023-06-01`)
+    #[cfg(not(target_arch = "wasm32"))]
+    const HISTORIC_CURRENCY_API_URL: &str = "https://api.frankfurter.app/";
+    #[cfg(not(target_arch = "wasm32"))]
+    const HISTORIC_CURRENCIES_DIR_NAME: &str = "historic_currencies";
+    /// How long fetching the exchange rates of a day may take at most
+    #[cfg(not(target_arch = "wasm32"))]
+    const HISTORIC_CURRENCIES_TIMEOUT: Duration = Duration::from_secs(10);
+
+    /// Why the exchange rates of a day couldn't be loaded
+    pub enum LoadError {
+        /// They aren't cached and couldn't be fetched
+        Unavailable,
+        /// Fetching them took longer than allowed
+        TimedOut,
+    }
 
+    #[allow(dead_code)]
     #[derive(serde::Deserialize, Debug)]
     struct ApiResponse {
         base: String,
@@ -141,9 +252,52 @@ mod updating {
         cache_dir().join(CURRENCIES_FILE_NAME)
     }
 
+    /// Loads the exchange rates of `date` from the cache or, if they aren't cached yet, fetches
+    /// them within `time_limit` and caches them
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load_historic_currencies(
+        date: NaiveDate,
+        capabilities: &Capabilities,
+        time_limit: Option<Duration>,
+    ) -> Result<(String, HashMap<String, f64>), LoadError> {
+        let file = cache_dir().join(HISTORIC_CURRENCIES_DIR_NAME).join(format!("{}.txt", date.format("%Y-%m-%d")));
+        if capabilities.filesystem {
+            if let Ok(contents) = std::fs::read_to_string(&file) {
+                if !contents.is_empty() { return Ok(decode_currencies(&contents)); }
+            }
+        }
+
+        if !capabilities.network { return Err(LoadError::Unavailable); }
+        let timeout = time_limit.map_or(HISTORIC_CURRENCIES_TIMEOUT, |limit| limit.min(HISTORIC_CURRENCIES_TIMEOUT));
+        let url = format!("{HISTORIC_CURRENCY_API_URL}{}?from=EUR", date.format("%Y-%m-%d"));
+        let response: ApiResponse = reqwest::blocking::Client::builder()
+            .timeout(timeout)
+            .build()
+            .and_then(|client| client.get(url).send())
+            .and_then(|response| response.json())
+            .map_err(|err| if err.is_timeout() { LoadError::TimedOut } else { LoadError::Unavailable })?;
+
+        if capabilities.filesystem {
+            if let Some(dir) = file.parent() { let _ = std::fs::create_dir_all(dir); }
+            let _ = std::fs::write(&file, encode_currencies(&response));
+        }
+        Ok((response.base, response.rates))
+    }
+
+    /// Requests can't be blocking on the web, so historic exchange rates aren't available there
+    #[cfg(target_arch = "wasm32")]
+    pub fn load_historic_currencies(
+        _date: NaiveDate,
+        _capabilities: &Capabilities,
+        _time_limit: Option<Duration>,
+    ) -> Result<(String, HashMap<String, f64>), LoadError> {
+        Err(LoadError::Unavailable)
+    }
+
     /// Update currency file, and optionally update `Currencies` struct
     // TODO: enable this again with another exachange rates API!
     #[cfg(not(target_arch = "wasm32"))]
+    #[allow(unused_variables)]
     pub fn update_currencies(currencies: Option<std::sync::Arc<Currencies>>) {
         // std::thread::spawn(move || {
         //     let response: ApiResponse = reqwest::blocking::get(CURRENCY_API_URL)
@@ -168,6 +322,7 @@ mod updating {
 
     // TODO: enable this again with another exachange rates API!
     #[cfg(target_arch = "wasm32")]
+    #[allow(unused_variables)]
     pub fn update_currencies(currencies: Option<std::sync::Arc<Currencies>>) {
         // wasm_bindgen_futures::spawn_local(async {
         //     async fn get() -> reqwest::Result<ApiResponse> {
@@ -189,6 +344,7 @@ mod updating {
         // });
     }
 
+    #[allow(dead_code)]
     fn encode_currencies(response: &ApiResponse) -> String {
         let mut result = String::new();
         result += &response.base;
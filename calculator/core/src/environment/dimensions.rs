@@ -0,0 +1,226 @@
+/*
+ * Copyright (c) 2023, david072
+ *
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+use crate::common::SourceRange;
+use crate::environment::currencies::is_currency;
+use crate::environment::unit_conversion::UNITS;
+use crate::environment::units::{get_prefix_power, split_binary_prefix, Unit};
+
+const LENGTH: usize = 0;
+const MASS: usize = 1;
+const TIME: usize = 2;
+const CURRENT: usize = 3;
+const TEMPERATURE: usize = 4;
+const AMOUNT: usize = 5;
+const LUMINOSITY: usize = 6;
+const ANGLE: usize = 7;
+const DATA: usize = 8;
+const MONEY: usize = 9;
+
+/// The names of the base quantities, in the order of their indices
+const BASE_QUANTITIES: [&str; 10] = [
+    "length", "mass", "time", "current", "temperature", "amount of substance",
+    "luminous intensity", "angle", "data", "money",
+];
+
+/// Names of derived quantities with the powers of the base quantities they are made of
+const DERIVED_QUANTITIES: [(&str, &[(usize, f64)]); 22] = [
+    ("area", &[(LENGTH, 2.0)]),
+    ("volume", &[(LENGTH, 3.0)]),
+    ("velocity", &[(LENGTH, 1.0), (TIME, -1.0)]),
+    ("acceleration", &[(LENGTH, 1.0), (TIME, -2.0)]),
+    ("frequency", &[(TIME, -1.0)]),
+    ("density", &[(MASS, 1.0), (LENGTH, -3.0)]),
+    ("force", &[(MASS, 1.0), (LENGTH, 1.0), (TIME, -2.0)]),
+    ("pressure", &[(MASS, 1.0), (LENGTH, -1.0), (TIME, -2.0)]),
+    ("energy", &[(MASS, 1.0), (LENGTH, 2.0), (TIME, -2.0)]),
+    ("power", &[(MASS, 1.0), (LENGTH, 2.0), (TIME, -3.0)]),
+    ("charge", &[(CURRENT, 1.0), (TIME, 1.0)]),
+    ("voltage", &[(MASS, 1.0), (LENGTH, 2.0), (TIME, -3.0), (CURRENT, -1.0)]),
+    ("capacitance", &[(MASS, -1.0), (LENGTH, -2.0), (TIME, 4.0), (CURRENT, 2.0)]),
+    ("resistance", &[(MASS, 1.0), (LENGTH, 2.0), (TIME, -3.0), (CURRENT, -2.0)]),
+    ("conductance", &[(MASS, -1.0), (LENGTH, -2.0), (TIME, 3.0), (CURRENT, 2.0)]),
+    ("magnetic flux", &[(MASS, 1.0), (LENGTH, 2.0), (TIME, -2.0), (CURRENT, -1.0)]),
+    ("magnetic flux density", &[(MASS, 1.0), (TIME, -2.0), (CURRENT, -1.0)]),
+    ("inductance", &[(MASS, 1.0), (LENGTH, 2.0), (TIME, -2.0), (CURRENT, -2.0)]),
+    ("solid angle", &[(ANGLE, 2.0)]),
+    ("luminous flux", &[(LUMINOSITY, 1.0), (ANGLE, 2.0)]),
+    ("illuminance", &[(LUMINOSITY, 1.0), (ANGLE, 2.0), (LENGTH, -2.0)]),
+    ("catalytic activity", &[(AMOUNT, 1.0), (TIME, -1.0)]),
+];
+
+/// The units that conversions are usually made into, which are suggested for results with units of
+/// the same dimension
+pub const COMMON_UNITS: [&str; 48] = [
+    "m", "km", "cm", "mm", "mi", "ft", "inch", "yd",
+    "g", "kg", "mg", "t", "lb", "oz",
+    "s", "ms", "min", "h", "d", "w", "y",
+    "l", "ml", "cup", "floz", "tbsp", "tsp",
+    "°C", "°F", "K",
+    "°", "rad",
+    "B", "kB", "MB", "GB", "TB",
+    "J", "kJ", "cal", "kcal",
+    "W", "kW",
+    "Pa", "bar", "psi",
+    "km/h", "m/s",
+];
+
+/// The physical dimension of a unit, stored as the powers of the base quantities (e.g. `m/s` is
+/// length^1 * time^-1)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Dimension([f64; BASE_QUANTITIES.len()]);
+
+impl Dimension {
+    fn new(powers: &[(usize, f64)]) -> Self {
+        let mut dimension = Self([0.0; BASE_QUANTITIES.len()]);
+        for &(quantity, power) in powers {
+            dimension.0[quantity] += power;
+        }
+        dimension
+    }
+
+    /// Returns the dimension of a unit, or `None` if it contains an unknown unit
+    pub fn of(unit: &Unit) -> Option<Self> {
+        match unit {
+            Unit::Unit(name, power, _) => Some(Self::of_str(name)?.pow(*power)),
+            Unit::Product(units) => units.iter()
+                .try_fold(Self::new(&[]), |dimension, unit| Some(dimension.mul(Self::of(unit)?))),
+            Unit::Fraction(num, denom) => Some(Self::of(num)?.mul(Self::of(denom)?.pow(-1.0))),
+        }
+    }
+
+    /// Like [Dimension::of], but values without a unit have no dimension
+    pub(crate) fn of_optional(unit: Option<&Unit>) -> Option<Self> {
+        unit.map_or(Some(Self::new(&[])), Self::of)
+    }
+
+    /// Returns the dimension of a single unit with an optional prefix (e.g. `km`)
+    pub fn of_str(unit: &str) -> Option<Self> {
+        if let Some(dimension) = base_dimension(unit) {
+            return Some(dimension);
+        }
+        if let Some((_, unit)) = split_binary_prefix(unit) {
+            return base_dimension(unit);
+        }
+
+        let mut chars = unit.chars();
+        get_prefix_power(chars.next()?)?;
+        base_dimension(chars.as_str())
+    }
+
+    fn pow(mut self, power: f64) -> Self {
+        self.0.iter_mut().for_each(|p| *p *= power);
+        self
+    }
+
+    fn mul(mut self, other: Self) -> Self {
+        self.0.iter_mut().zip(other.0).for_each(|(p, other)| *p += other);
+        self
+    }
+}
+
+impl std::fmt::Display for Dimension {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some((name, _)) = DERIVED_QUANTITIES.iter().find(|(_, powers)| Self::new(powers) == *self) {
+            return write!(f, "{name}");
+        }
+
+        let format_part = |(name, power): (&str, f64)| if power == 1.0 {
+            name.to_string()
+        } else {
+            format!("{name}^{power}")
+        };
+        let (num, denom): (Vec<_>, Vec<_>) = BASE_QUANTITIES.into_iter()
+            .zip(self.0)
+            .filter(|(_, power)| *power != 0.0)
+            .partition(|(_, power)| *power > 0.0);
+        let num = num.into_iter().map(format_part).collect::<Vec<_>>();
+        let denom = denom.into_iter().map(|(name, power)| format_part((name, -power))).collect::<Vec<_>>();
+
+        match (num.is_empty(), denom.is_empty()) {
+            (true, true) => write!(f, "no dimension"),
+            (false, true) => write!(f, "{}", num.join("*")),
+            (true, false) => write!(f, "1/{}", denom.join("*")),
+            (false, false) => write!(f, "{}/{}", num.join("*"), denom.join("*")),
+        }
+    }
+}
+
+/// Returns the units without prefixes that a value with `unit` can be converted into (e.g. `mi` and
+/// `ft` for `km`), except for `unit` itself
+pub fn compatible_units(unit: &Unit) -> Vec<&'static str> {
+    let Some(dimension) = Dimension::of(unit) else { return vec![]; };
+    let name = unit.format(false, false);
+    UNITS.into_iter()
+        .filter(|other| *other != name && base_dimension(other) == Some(dimension))
+        .collect()
+}
+
+/// Returns the common units (see [COMMON_UNITS]) that a value with `unit` can be converted into,
+/// except for `unit` itself
+pub fn common_units(unit: &Unit) -> Vec<Unit> {
+    let Some(dimension) = Dimension::of(unit) else { return vec![]; };
+    let name = unit.format(false, false);
+    COMMON_UNITS.into_iter()
+        .filter(|other| *other != name)
+        .map(|other| match other.split_once('/') {
+            Some((num, denom)) => Unit::Fraction(
+                Box::new(Unit::new(num, 1.0, SourceRange::empty())),
+                Box::new(Unit::new(denom, 1.0, SourceRange::empty())),
+            ),
+            None => Unit::new(other, 1.0, SourceRange::empty()),
+        })
+        .filter(|other| Dimension::of(other) == Some(dimension))
+        .collect()
+}
+
+/// Returns the dimension of a unit without a prefix
+fn base_dimension(unit: &str) -> Option<Dimension> {
+    if is_currency(unit) { return Some(Dimension::new(&[(MONEY, 1.0)])); }
+    if !UNITS.contains(&unit) { return None; }
+
+    let powers: &[(usize, f64)] = match unit {
+        "m" | "mi" | "ft" | "inch" | "yd" | "nmi" => &[(LENGTH, 1.0)],
+        "a" => &[(LENGTH, 2.0)],
+        "l" | "tsp" | "tbsp" | "floz" | "cup" => &[(LENGTH, 3.0)],
+        "°" | "rad" | "'" | "\"" | "gon" => &[(ANGLE, 1.0)],
+        "s" | "min" | "h" | "d" | "w" | "mo" | "y" => &[(TIME, 1.0)],
+        "g" | "lb" | "t" | "u" | "oz" | "ct" => &[(MASS, 1.0)],
+        "°C" | "°F" | "K" => &[(TEMPERATURE, 1.0)],
+        "b" | "B" => &[(DATA, 1.0)],
+        "mol" => &[(AMOUNT, 1.0)],
+        // Gray and Sievert are J/kg
+        "Gy" | "Sv" => &[(LENGTH, 2.0), (TIME, -2.0)],
+        _ => DERIVED_QUANTITIES.iter()
+            .find(|(name, _)| *name == derived_quantity(unit))
+            .map(|(_, powers)| *powers)?,
+    };
+    Some(Dimension::new(powers))
+}
+
+/// Returns the name of the derived quantity that a unit measures
+fn derived_quantity(unit: &str) -> &'static str {
+    match unit {
+        "sr" => "solid angle",
+        "Pa" | "bar" | "psi" | "ba" => "pressure",
+        "cal" | "J" | "eV" | "erg" => "energy",
+        "N" | "dyn" => "force",
+        "Hz" | "Bq" => "frequency",
+        "W" => "power",
+        "C" => "charge",
+        "V" => "voltage",
+        "F" => "capacitance",
+        "Ohm" => "resistance",
+        "S" => "conductance",
+        "Wb" => "magnetic flux",
+        "T" => "magnetic flux density",
+        "H" => "inductance",
+        "lm" => "luminous flux",
+        "lx" => "illuminance",
+        "kat" => "catalytic activity",
+        _ => "",
+    }
+}
@@ -4,17 +4,33 @@
  * SPDX-License-Identifier: Apache-2.0
  */
 
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
 use std::f64::consts::{E, PI, TAU};
+use std::ops::RangeInclusive;
 use std::rc::Rc;
+#[cfg(feature = "scripting")]
+use std::sync::Arc;
 
-use crate::astgen::ast::BooleanOperator;
-use crate::common::SourceRange;
+use crate::astgen::ast::{AstNodeData, BooleanOperator, MAX_EXACT_INTEGER};
+use crate::common::{math, SourceRange, Warning};
 use crate::engine::{NumberValue, Value};
+use crate::environment::analysis::FunctionEvaluator;
+use crate::environment::constants::get_constant;
+#[cfg(feature = "scripting")]
+use crate::environment::scripts::{ScriptError, Scripts};
 use crate::environment::units::{convert, Unit};
 use crate::{astgen::ast::AstNode, common::ErrorType, Context, ContextData, Engine, Format};
 
+pub mod analysis;
+pub mod chemistry;
+pub mod constants;
 pub mod currencies;
+pub mod dimensions;
+pub mod number_theory;
+#[cfg(feature = "scripting")]
+pub mod scripts;
+mod suggestions;
 pub mod units;
 
 // These files are generated in build.rs during build time
@@ -24,11 +40,20 @@ mod unit_conversion;
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct Variable(pub Value);
 
-const STANDARD_VARIABLES: [&str; 4] = ["pi", "e", "tau", "ans"];
+const STANDARD_VARIABLES: [&str; 5] = ["pi", "e", "tau", "ans", "last"];
 const VAR_PI: &Variable = &Variable(Value::only_number(PI));
 const VAR_E: &Variable = &Variable(Value::only_number(E));
 const VAR_TAU: &Variable = &Variable(Value::only_number(TAU));
 
+thread_local! {
+    /// How deeply calls of custom functions are currently nested
+    static FUNCTION_DEPTH: Cell<usize> = const { Cell::new(0) };
+    /// The results of the calls of custom functions in the current calculation, by the function's
+    /// name and its arguments. It's cleared before every calculation, since the functions may be
+    /// redefined in between.
+    static FUNCTION_CACHE: RefCell<HashMap<(String, String), Value>> = RefCell::new(HashMap::new());
+}
+
 pub type FunctionArgument = (String, Option<Unit>);
 
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
@@ -37,6 +62,8 @@ pub(crate) enum FunctionVariantType {
         lhs: Vec<AstNode>,
         rhs: Vec<AstNode>,
         operator: BooleanOperator,
+        #[serde(default)]
+        tolerance: Option<Vec<AstNode>>,
     },
     Else,
 }
@@ -51,6 +78,8 @@ pub struct Function {
 pub(crate) enum ArgCount {
     Single(usize),
     Multiple(&'static [usize]),
+    /// Any number of arguments, but at least this many
+    AtLeast(usize),
 }
 
 impl ArgCount {
@@ -58,38 +87,150 @@ impl ArgCount {
         match self {
             Self::Single(n) => count == *n,
             Self::Multiple(options) => options.contains(&count),
+            Self::AtLeast(min) => count >= *min,
         }
     }
+
+    fn counts(&self) -> Vec<usize> {
+        match self {
+            Self::Single(n) => vec![*n],
+            Self::Multiple(options) => options.to_vec(),
+            Self::AtLeast(min) => vec![*min],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FunctionKind {
+    Standard,
+    Custom,
+    Script,
+}
+
+/// A function that can be called in calculations
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FunctionSignature {
+    pub name: String,
+    /// The numbers of arguments the function can be called with. For functions taking any number
+    /// of arguments (e.g. `apply`), this is only the smallest one.
+    pub argument_counts: Vec<usize>,
+    pub kind: FunctionKind,
+    /// How to use the function, which only standard functions have
+    pub help: Option<FunctionHelp>,
+}
+
+/// The documentation of a standard function, which is e.g. shown in the help
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FunctionHelp {
+    /// The function's name together with the names of its arguments (e.g. `log(base, n)`)
+    pub usage: &'static str,
+    pub description: &'static str,
+    pub example: &'static str,
 }
 
-const STANDARD_FUNCTIONS: [(&str, ArgCount); 20] = [
-    ("sin", ArgCount::Single(1)),
-    ("asin", ArgCount::Single(1)),
-    ("cos", ArgCount::Single(1)),
-    ("acos", ArgCount::Single(1)),
-    ("tan", ArgCount::Single(1)),
-    ("atan", ArgCount::Single(1)),
-    ("cot", ArgCount::Single(1)),
-    ("acot", ArgCount::Single(1)),
-    ("ln", ArgCount::Single(1)),
-    ("log", ArgCount::Single(2)), // log arg2 to base arg1
-    ("sqrt", ArgCount::Single(1)),
-    ("cbrt", ArgCount::Single(1)),
-    ("root", ArgCount::Single(2)), // root with "index" arg1 of arg2
-    ("abs", ArgCount::Single(1)),
-    ("floor", ArgCount::Single(1)),
-    ("ceil", ArgCount::Single(1)),
-    ("lerp", ArgCount::Single(3)),
-    ("clamp", ArgCount::Single(3)),
-    ("map", ArgCount::Single(5)), // map arg1 from range arg2..arg3 to range arg4..arg5
-    ("round", ArgCount::Multiple(&[1, 2])),
+const STANDARD_FUNCTIONS: [(&str, ArgCount, FunctionHelp); 66] = [
+    ("sin", ArgCount::Single(1), FunctionHelp { usage: "sin(x)", description: "Sine", example: "sin(1)" }),
+    ("asin", ArgCount::Single(1), FunctionHelp { usage: "asin(x)", description: "Arcsine", example: "asin(.5)" }),
+    ("cos", ArgCount::Single(1), FunctionHelp { usage: "cos(x)", description: "Cosine", example: "cos(1)" }),
+    ("acos", ArgCount::Single(1), FunctionHelp { usage: "acos(x)", description: "Arccosine", example: "acos(.5)" }),
+    ("tan", ArgCount::Single(1), FunctionHelp { usage: "tan(x)", description: "Tangent", example: "tan(1)" }),
+    ("atan", ArgCount::Single(1), FunctionHelp { usage: "atan(x)", description: "Arctangent", example: "atan(.5)" }),
+    ("cot", ArgCount::Single(1), FunctionHelp { usage: "cot(x)", description: "Cotangent", example: "cot(1)" }),
+    ("acot", ArgCount::Single(1), FunctionHelp { usage: "acot(x)", description: "Arccotangent", example: "acot(.5)" }),
+    ("sec", ArgCount::Single(1), FunctionHelp { usage: "sec(x)", description: "Secant", example: "sec(1)" }),
+    ("csc", ArgCount::Single(1), FunctionHelp { usage: "csc(x)", description: "Cosecant", example: "csc(1)" }),
+    ("atan2", ArgCount::Single(2), FunctionHelp { usage: "atan2(y, x)", description: "Angle of the point (x, y)", example: "atan2(1, -1)" }),
+    ("sinh", ArgCount::Single(1), FunctionHelp { usage: "sinh(x)", description: "Hyperbolic sine", example: "sinh(1)" }),
+    ("asinh", ArgCount::Single(1), FunctionHelp { usage: "asinh(x)", description: "Inverse hyperbolic sine", example: "asinh(1)" }),
+    ("cosh", ArgCount::Single(1), FunctionHelp { usage: "cosh(x)", description: "Hyperbolic cosine", example: "cosh(1)" }),
+    ("acosh", ArgCount::Single(1), FunctionHelp { usage: "acosh(x)", description: "Inverse hyperbolic cosine", example: "acosh(2)" }),
+    ("tanh", ArgCount::Single(1), FunctionHelp { usage: "tanh(x)", description: "Hyperbolic tangent", example: "tanh(1)" }),
+    ("atanh", ArgCount::Single(1), FunctionHelp { usage: "atanh(x)", description: "Inverse hyperbolic tangent", example: "atanh(.5)" }),
+    ("ln", ArgCount::Single(1), FunctionHelp { usage: "ln(x)", description: "Natural logarithm", example: "ln(3)" }),
+    ("log", ArgCount::Multiple(&[1, 2]), FunctionHelp { usage: "log(n) / log(base, n)", description: "Logarithm (base 10 by default)", example: "log(2, 8)" }),
+    ("log2", ArgCount::Single(1), FunctionHelp { usage: "log2(n)", description: "Binary logarithm", example: "log2(1024)" }),
+    ("log10", ArgCount::Single(1), FunctionHelp { usage: "log10(n)", description: "Common logarithm", example: "log10(1000)" }),
+    ("ln1p", ArgCount::Single(1), FunctionHelp { usage: "ln1p(x)", description: "ln(1 + x), precise for small x", example: "ln1p(1e-10)" }),
+    ("expm1", ArgCount::Single(1), FunctionHelp { usage: "expm1(x)", description: "e^x - 1, precise for small x", example: "expm1(1e-10)" }),
+    ("sqrt", ArgCount::Single(1), FunctionHelp { usage: "sqrt(x)", description: "Square root", example: "sqrt(25)" }),
+    ("cbrt", ArgCount::Single(1), FunctionHelp { usage: "cbrt(x)", description: "Cube root", example: "cbrt(1000)" }),
+    ("root", ArgCount::Single(2), FunctionHelp { usage: "root(index, n)", description: "Root", example: "root(2, 8)" }),
+    ("hypot", ArgCount::Single(2), FunctionHelp { usage: "hypot(a, b)", description: "Length of the hypotenuse", example: "hypot(3, 4)" }),
+    ("abs", ArgCount::Single(1), FunctionHelp { usage: "abs(x)", description: "Absolute value", example: "abs(-10)" }),
+    ("floor", ArgCount::Single(1), FunctionHelp { usage: "floor(x)", description: "Flooring", example: "floor(3.5)" }),
+    ("ceil", ArgCount::Single(1), FunctionHelp { usage: "ceil(x)", description: "Ceiling", example: "ceil(20.2)" }),
+    ("lerp", ArgCount::Single(3), FunctionHelp { usage: "lerp(a, b, t)", description: "Lerping", example: "lerp(0, 50, .5)" }),
+    ("clamp", ArgCount::Single(3), FunctionHelp { usage: "clamp(n, start, end)", description: "Clamping", example: "clamp(5, 0, 2)" }),
+    ("map", ArgCount::Multiple(&[2, 5]), FunctionHelp { usage: "map(f, vector) / map(n, start1, end1, start2, end2)", description: "Apply a function to every element / Map from one range to another", example: "map(sqrt, [1; 4; 9])" }),
+    ("filter", ArgCount::Single(2), FunctionHelp { usage: "filter(f, vector)", description: "Elements for which the function isn't 0", example: "filter(floor, [0.5; 1; 2.5])" }),
+    ("reduce", ArgCount::Multiple(&[2, 3]), FunctionHelp { usage: "reduce(f, vector) / reduce(f, vector, initial)", description: "Combine the elements with a function of two arguments", example: "reduce(log, [2; 8])" }),
+    ("apply", ArgCount::AtLeast(2), FunctionHelp { usage: "apply(f, x...)", description: "Call a function", example: "apply(sqrt, 16)" }),
+    ("fmt", ArgCount::Multiple(&[1, 2, 3, 4, 5, 6]), FunctionHelp { usage: "fmt(text, values...)", description: "Text with the values in place of the {}s", example: "fmt(\"speed: {} km/h\", 50)" }),
+    ("round", ArgCount::Multiple(&[1, 2]), FunctionHelp { usage: "round(n) / round(n, decimal places)", description: "Rounding (optional decimal places)", example: "round(5.2)" }),
+    ("hexdump", ArgCount::Single(1), FunctionHelp { usage: "hexdump(n)", description: "Integer in hexadecimal", example: "hexdump(48879)" }),
+    ("bits", ArgCount::Single(1), FunctionHelp { usage: "bits(n)", description: "Positions of the set bits", example: "bits(0b1010)" }),
+    ("popcount", ArgCount::Single(1), FunctionHelp { usage: "popcount(n)", description: "Number of set bits", example: "popcount(0xff)" }),
+    ("align", ArgCount::Single(2), FunctionHelp { usage: "align(n, alignment)", description: "Round up to a multiple of the alignment", example: "align(13, 8)" }),
+    ("isprime", ArgCount::Single(1), FunctionHelp { usage: "isprime(n)", description: "1 if the integer is prime, 0 otherwise", example: "isprime(97)" }),
+    ("nextprime", ArgCount::Single(1), FunctionHelp { usage: "nextprime(n)", description: "Smallest prime greater than the integer", example: "nextprime(100)" }),
+    ("gcd", ArgCount::Single(2), FunctionHelp { usage: "gcd(a, b)", description: "Greatest common divisor", example: "gcd(12, 18)" }),
+    ("lcm", ArgCount::Single(2), FunctionHelp { usage: "lcm(a, b)", description: "Least common multiple", example: "lcm(4, 6)" }),
+    ("factorize", ArgCount::Single(1), FunctionHelp { usage: "factorize(n)", description: "Prime factors of a positive integer", example: "factorize(360)" }),
+    ("ncr", ArgCount::Single(2), FunctionHelp { usage: "ncr(n, k)", description: "Combinations of k out of n elements (binomial coefficient)", example: "ncr(5, 2)" }),
+    ("npr", ArgCount::Single(2), FunctionHelp { usage: "npr(n, k)", description: "Arrangements of k out of n elements", example: "npr(5, 2)" }),
+    ("phi", ArgCount::Single(1), FunctionHelp { usage: "phi(n)", description: "Number of coprime integers up to n (Euler's totient)", example: "phi(36)" }),
+    ("float_bits", ArgCount::Multiple(&[1, 2]), FunctionHelp { usage: "float_bits(x) / float_bits(x, width)", description: "Bits of a float (32 bits by default)", example: "float_bits(3.14)" }),
+    ("from_bits", ArgCount::Multiple(&[1, 2]), FunctionHelp { usage: "from_bits(bits) / from_bits(bits, width)", description: "Float from its bits (32 bits by default)", example: "from_bits(0x4048f5c3)" }),
+    ("ulp", ArgCount::Single(1), FunctionHelp { usage: "ulp(x)", description: "Distance to the next larger float", example: "ulp(1)" }),
+    ("nextafter", ArgCount::Single(2), FunctionHelp { usage: "nextafter(x, y)", description: "Next float after x towards y", example: "nextafter(1, 2)" }),
+    ("tax", ArgCount::Multiple(&[1, 2]), FunctionHelp { usage: "tax(net) / tax(net, rate)", description: "Price with tax (the VAT rate by default)", example: "tax(100)" }),
+    ("net", ArgCount::Multiple(&[1, 2]), FunctionHelp { usage: "net(gross) / net(gross, rate)", description: "Price without tax (the VAT rate by default)", example: "net(119)" }),
+    ("tip", ArgCount::Single(2), FunctionHelp { usage: "tip(n, rate)", description: "Amount with tip", example: "tip(40, 15%)" }),
+    ("discount", ArgCount::Single(2), FunctionHelp { usage: "discount(n, rate)", description: "Discounted price", example: "discount(80, 25%)" }),
+    ("fv", ArgCount::Single(4), FunctionHelp { usage: "fv(rate, n, pmt, pv)", description: "Future value of an investment", example: "fv(5%, 10, -100, -1000)" }),
+    ("pmt", ArgCount::Single(3), FunctionHelp { usage: "pmt(rate, n, pv)", description: "Payment per period of a loan", example: "pmt(5% / 12, 360, 200000)" }),
+    ("npv", ArgCount::Single(2), FunctionHelp { usage: "npv(rate, cash flows)", description: "Net present value", example: "npv(10%, [-10000; 3000; 4200; 6800])" }),
+    ("irr", ArgCount::Single(1), FunctionHelp { usage: "irr(cash flows)", description: "Internal rate of return", example: "irr([-100; 60; 60])" }),
+    ("unixtime", ArgCount::Single(1), FunctionHelp { usage: "unixtime(date)", description: "Seconds since 01.01.1970 (UTC)", example: "unixtime({date 14.11.2023})" }),
+    ("molarmass", ArgCount::Single(1), FunctionHelp { usage: "molarmass(formula)", description: "Molar mass of a chemical formula", example: "molarmass(\"H2SO4\")" }),
+    ("line", ArgCount::Single(1), FunctionHelp { usage: "line(n)", description: "Result of another line", example: "line(1)" }),
+    ("slider", ArgCount::Single(3), FunctionHelp { usage: "slider(min, max, step)", description: "Slider (only as a variable's definition)", example: "a := slider(0, 10, 1)" }),
 ];
 
+/// The float closest to `x` in the direction of `y`
+fn next_after(x: f64, y: f64) -> f64 {
+    if x.is_nan() || y.is_nan() { return f64::NAN; }
+    if x == y { return y; }
+    if x == 0.0 { return f64::from_bits(1).copysign(y); }
+
+    // Increasing the bits increases the magnitude
+    let bits = x.to_bits();
+    f64::from_bits(if (y > x) == (x > 0.0) { bits + 1 } else { bits - 1 })
+}
+
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct Environment {
     pub ans: Variable,
     pub variables: Vec<(String, Variable)>,
     pub functions: Vec<(String, Function)>,
+    /// The results of the lines of the current input, by their (zero-based) index
+    #[serde(skip)]
+    pub(crate) line_results: Vec<(usize, Variable)>,
+    /// The warnings of the calculation that is currently being evaluated
+    #[serde(skip)]
+    pub(crate) warnings: Vec<Warning>,
+    /// The steps of taxes, tips and discounts in the calculation that is currently being evaluated
+    /// (e.g. `tax (19%): 120 + 22.8 = 142.8`), which are shown in the debug information
+    #[serde(skip)]
+    pub(crate) breakdown: Vec<String>,
+    /// The functions defined in scripts
+    #[cfg(feature = "scripting")]
+    #[serde(skip)]
+    scripts: Option<Arc<Scripts>>,
+    /// Counts the changes of the variables and functions, so that it can be told whether a
+    /// calculation changed them
+    #[serde(skip)]
+    pub(crate) revision: u64,
 }
 
 impl Default for Environment {
@@ -104,13 +245,21 @@ impl Environment {
             ans: Variable(Value::only_number(0.0)),
             variables: Vec::new(),
             functions: Vec::new(),
+            line_results: Vec::new(),
+            warnings: Vec::new(),
+            breakdown: Vec::new(),
+            #[cfg(feature = "scripting")]
+            scripts: None,
+            revision: 0,
         }
     }
 
-    pub(crate) fn clear(&mut self) {
+    /// Resets everything but the variables and functions
+    pub(crate) fn clear_results(&mut self) {
         self.ans = Variable(Value::only_number(0.0));
-        self.variables.clear();
-        self.functions.clear();
+        self.line_results.clear();
+        self.warnings.clear();
+        self.breakdown.clear();
     }
 
     pub(crate) fn get_debug_info(&self) -> String {
@@ -136,32 +285,42 @@ impl Environment {
                     return true;
                 }
             }
-            false
+            get_constant(var).is_some()
         }
     }
 
+    /// Whether `var` refers to a scientific constant, i.e. there is a constant with that name and
+    /// no custom variable shadows it
+    pub(crate) fn is_constant(&self, var: &str) -> bool {
+        get_constant(var).is_some() && !self.variables.iter().any(|(name, _)| name == var)
+    }
+
     pub(crate) fn is_standard_variable(&self, var: &str) -> bool {
         STANDARD_VARIABLES.contains(&var)
     }
 
-    pub(crate) fn resolve_variable(&self, var: &str) -> Result<&Variable, ErrorType> {
+    pub(crate) fn resolve_variable(&self, var: &str) -> Result<Variable, ErrorType> {
         match var {
-            "pi" => Ok(VAR_PI),
-            "e" => Ok(VAR_E),
-            "tau" => Ok(VAR_TAU),
-            "ans" => Ok(&self.ans),
+            "pi" => Ok(VAR_PI.clone()),
+            "e" => Ok(VAR_E.clone()),
+            "tau" => Ok(VAR_TAU.clone()),
+            "ans" => Ok(self.ans.clone()),
+            "last" => self.last_result().cloned(),
             _ => {
                 for (name, variable) in &self.variables {
                     if name == var {
-                        return Ok(variable);
+                        return Ok(variable.clone());
                     }
                 }
-                Err(ErrorType::UnknownVariable(var.to_owned()))
+                get_constant(var)
+                    .map(|constant| constant.variable())
+                    .ok_or_else(|| ErrorType::UnknownVariable(var.to_owned(), self.similar_identifiers(var)))
             }
         }
     }
 
     pub(crate) fn set_variable(&mut self, var: &str, value: Variable) -> Result<(), ErrorType> {
+        self.revision += 1;
         if var == "ans" {
             self.ans = value;
             return Ok(());
@@ -184,7 +343,30 @@ impl Environment {
         self.ans = value;
     }
 
+    pub(crate) fn set_line_result(&mut self, line: usize, value: Variable) {
+        match self.line_results.iter_mut().find(|(l, _)| *l == line) {
+            Some((_, result)) => *result = value,
+            None => self.line_results.push((line, value)),
+        }
+    }
+
+    /// The result of the closest line above the current one that has a result, which can also be a
+    /// definition. `ans` isn't used instead, since it can be left over from an earlier calculation.
+    fn last_result(&self) -> Result<&Variable, ErrorType> {
+        // The lines are calculated from top to bottom, so the last result is the closest one
+        self.line_results.last().map(|(_, result)| result).ok_or(ErrorType::NoPreviousLineResult)
+    }
+
+    /// Resolves the result of the line with the (one-based) line number `line`
+    pub(crate) fn resolve_line(&self, line: usize) -> Result<&Variable, ErrorType> {
+        self.line_results.iter()
+            .find(|(l, _)| *l + 1 == line)
+            .map(|(_, result)| result)
+            .ok_or(ErrorType::NoLineResult(line))
+    }
+
     pub(crate) fn remove_variable(&mut self, var: &str) -> Result<(), ErrorType> {
+        self.revision += 1;
         if var == "ans" {
             self.ans = Variable(Value::only_number(0.0));
             return Ok(());
@@ -203,7 +385,7 @@ impl Environment {
     }
 
     pub(crate) fn is_valid_function(&self, name: &str) -> bool {
-        for (f, _) in STANDARD_FUNCTIONS {
+        for (f, ..) in STANDARD_FUNCTIONS {
             if f == name {
                 return true;
             }
@@ -213,11 +395,49 @@ impl Environment {
                 return true;
             }
         }
+        #[cfg(feature = "scripting")]
+        if self.scripts.as_ref().map_or(false, |scripts| scripts.argument_count(name).is_some()) {
+            return true;
+        }
         false
     }
 
+    /// The signatures of all functions that can be called, i.e. the standard functions, the custom
+    /// functions and the functions of scripts (in that order)
+    pub fn function_signatures(&self) -> Vec<FunctionSignature> {
+        #[cfg_attr(not(feature = "scripting"), allow(unused_mut))]
+        let mut signatures = STANDARD_FUNCTIONS.iter()
+            .map(|(name, arg_count, help)| FunctionSignature {
+                name: name.to_string(),
+                argument_counts: arg_count.counts(),
+                kind: FunctionKind::Standard,
+                help: Some(*help),
+            })
+            .chain(self.functions.iter().map(|(name, function)| FunctionSignature {
+                name: name.clone(),
+                argument_counts: vec![function.arguments.len()],
+                kind: FunctionKind::Custom,
+                help: None,
+            }))
+            .collect::<Vec<_>>();
+
+        #[cfg(feature = "scripting")]
+        if let Some(scripts) = &self.scripts {
+            signatures.extend(scripts.functions()
+                // Custom functions take precedence over script functions with the same name
+                .filter(|(name, _)| !self.functions.iter().any(|(f, _)| f == name))
+                .map(|(name, count)| FunctionSignature {
+                    name: name.to_string(),
+                    argument_counts: vec![count],
+                    kind: FunctionKind::Script,
+                    help: None,
+                }));
+        }
+        signatures
+    }
+
     pub(crate) fn is_standard_function(&self, f: &str) -> bool {
-        for (name, _) in STANDARD_FUNCTIONS {
+        for (name, ..) in STANDARD_FUNCTIONS {
             if name == f {
                 return true;
             }
@@ -235,7 +455,7 @@ impl Environment {
     }
 
     pub(crate) fn function_argument_count(&self, name: &str) -> Option<ArgCount> {
-        for (f, arg_count) in STANDARD_FUNCTIONS {
+        for (f, arg_count, _) in STANDARD_FUNCTIONS {
             if f == name {
                 return Some(arg_count);
             }
@@ -245,6 +465,10 @@ impl Environment {
                 return Some(ArgCount::Single(arguments.len()));
             }
         }
+        #[cfg(feature = "scripting")]
+        if let Some(count) = self.scripts.as_ref().and_then(|scripts| scripts.argument_count(name)) {
+            return Some(ArgCount::Single(count));
+        }
         None
     }
 
@@ -265,6 +489,25 @@ impl Environment {
         };
 
         let unit_0 = &arg_results[0].unit;
+        let integer = |i: usize| {
+            if args[i].fract() != 0.0 { return Err(ErrorType::ExpectedInteger(args[i])); }
+            Ok(args[i])
+        };
+        // Number theory works with integers that are represented exactly
+        let exact_integer = |i: usize, min: f64| {
+            let n = integer(i)?;
+            if n < min || n > MAX_EXACT_INTEGER {
+                return Err(ErrorType::IntegerOutOfRange(n, min, MAX_EXACT_INTEGER));
+            }
+            Ok(n)
+        };
+        // Whether an optional float width argument is 64 instead of 32 bits
+        let is_double = |i: usize| match args.get(i) {
+            None => Ok(false),
+            Some(width) if *width == 32.0 => Ok(false),
+            Some(width) if *width == 64.0 => Ok(true),
+            Some(_) => Err(ErrorType::InvalidFloatWidth),
+        };
 
         match f {
             "sin" => Ok((as_radians(0).sin(), None)),
@@ -288,7 +531,31 @@ impl Environment {
                 Ok((rad.cos() / rad.sin(), None))
             }
             "acot" => Ok(((1.0 / args[0]).atan(), Some(Unit::from("rad")))),
+            "sec" => Ok((1.0 / as_radians(0).cos(), None)),
+            "csc" => Ok((1.0 / as_radians(0).sin(), None)),
+            "atan2" => Ok((args[0].atan2(args[1]), Some(Unit::from("rad")))),
+            "sinh" => Ok((args[0].sinh(), None)),
+            "asinh" => Ok((args[0].asinh(), None)),
+            "cosh" => Ok((args[0].cosh(), None)),
+            "acosh" => {
+                if args[0] < 1.0 {
+                    return Err(ErrorType::NotANumber);
+                }
+                Ok((args[0].acosh(), None))
+            }
+            "tanh" => Ok((args[0].tanh(), None)),
+            "atanh" => {
+                if args[0] < -1.0 || args[0] > 1.0 {
+                    return Err(ErrorType::NotANumber);
+                }
+                Ok((args[0].atanh(), None))
+            }
             "ln" => Ok((args[0].ln(), unit_0.clone())),
+            "log" if args.len() == 1 => Ok((args[0].log10(), unit_0.clone())),
+            "log2" => Ok((args[0].log2(), unit_0.clone())),
+            "log10" => Ok((args[0].log10(), unit_0.clone())),
+            "ln1p" => Ok((args[0].ln_1p(), unit_0.clone())),
+            "expm1" => Ok((args[0].exp_m1(), unit_0.clone())),
             "log" => Ok((
                 if args[0] == 2.0 {
                     args[1].log2()
@@ -301,7 +568,12 @@ impl Environment {
             )),
             "sqrt" => Ok((args[0].sqrt(), unit_0.clone())),
             "cbrt" => Ok((args[0].cbrt(), unit_0.clone())),
-            "root" => Ok((args[1].powf(1.0 / args[0]), unit_0.clone())),
+            // Odd roots of negative numbers are real (e.g. `root(3, -8)` is -2)
+            "root" if args[1] < 0.0 && args[0].fract() == 0.0 && args[0] % 2.0 != 0.0 =>
+                Ok((-(-args[1]).powf(1.0 / args[0]), arg_results[1].unit.clone())),
+            "root" => Ok((args[1].powf(1.0 / args[0]), arg_results[1].unit.clone())),
+            // Both sides are expected to have the same unit
+            "hypot" => Ok((args[0].hypot(args[1]), unit_0.clone())),
             "abs" => Ok((args[0].abs(), unit_0.clone())),
             "floor" => Ok((args[0].floor(), unit_0.clone())),
             "ceil" => Ok((args[0].ceil(), unit_0.clone())),
@@ -317,7 +589,7 @@ impl Environment {
                 let t = args[2];
                 Ok((a + (b - a) * t, None))
             }
-            "map" => {
+            "map" if args.len() == 5 => {
                 let a1 = args[1];
                 let b1 = args[2];
                 let a2 = args[3];
@@ -336,7 +608,71 @@ impl Environment {
                 };
                 Ok((result, unit_0.clone()))
             }
-            _ => Err(ErrorType::UnknownFunction(f.to_owned())),
+            // Shown in hexadecimal by the engine
+            "hexdump" => Ok((integer(0)?, unit_0.clone())),
+            "popcount" => Ok(((integer(0)? as i64).count_ones() as f64, None)),
+            "align" => {
+                let (n, alignment) = (integer(0)?, integer(1)?);
+                if alignment <= 0.0 { return Err(ErrorType::InvalidAlignment); }
+                Ok(((n / alignment).ceil() * alignment, unit_0.clone()))
+            }
+            "isprime" => {
+                let n = exact_integer(0, -MAX_EXACT_INTEGER)?;
+                Ok((if n >= 0.0 && number_theory::is_prime(n as u64) { 1.0 } else { 0.0 }, None))
+            }
+            "nextprime" => {
+                let n = exact_integer(0, -MAX_EXACT_INTEGER)?;
+                Ok((number_theory::next_prime(n.max(0.0) as u64) as f64, None))
+            }
+            "gcd" => {
+                let (a, b) = (exact_integer(0, -MAX_EXACT_INTEGER)?, exact_integer(1, -MAX_EXACT_INTEGER)?);
+                Ok((number_theory::gcd(a.abs() as u64, b.abs() as u64) as f64, None))
+            }
+            "lcm" => {
+                let (a, b) = (exact_integer(0, -MAX_EXACT_INTEGER)?.abs(), exact_integer(1, -MAX_EXACT_INTEGER)?.abs());
+                if a == 0.0 || b == 0.0 { return Ok((0.0, None)); }
+                Ok((a / number_theory::gcd(a as u64, b as u64) as f64 * b, None))
+            }
+            "ncr" => Ok((number_theory::combinations(exact_integer(0, 0.0)? as u64, exact_integer(1, 0.0)? as u64), None)),
+            "npr" => Ok((number_theory::permutations(exact_integer(0, 0.0)? as u64, exact_integer(1, 0.0)? as u64), None)),
+            "phi" => Ok((number_theory::totient(exact_integer(0, 1.0)? as u64) as f64, None)),
+            "float_bits" => Ok((
+                if is_double(1)? { args[0].to_bits() as f64 } else { (args[0] as f32).to_bits() as f64 },
+                None,
+            )),
+            "from_bits" => {
+                let bits = args[0];
+                let max = if is_double(1)? { u64::MAX as f64 } else { u32::MAX as f64 };
+                if bits.fract() != 0.0 || bits < 0.0 || bits > max {
+                    return Err(ErrorType::InvalidFloatBits(bits));
+                }
+                Ok((if is_double(1)? { f64::from_bits(bits as u64) } else { f32::from_bits(bits as u32) as f64 }, None))
+            }
+            "ulp" => {
+                let x = args[0].abs();
+                Ok((next_after(x, f64::INFINITY) - x, unit_0.clone()))
+            }
+            "nextafter" => Ok((next_after(args[0], args[1]), unit_0.clone())),
+            // The default VAT rate is added by the engine
+            "tax" | "tip" => Ok((args[0] * (1.0 + args[1]), unit_0.clone())),
+            "net" => Ok((args[0] / (1.0 + args[1]), unit_0.clone())),
+            "discount" => Ok((args[0] * (1.0 - args[1]), unit_0.clone())),
+            // The results have the unit of the amounts of money (e.g. a currency)
+            "fv" => Ok((
+                math::fv(args[0], args[1], args[2], args[3]),
+                arg_results[3].unit.clone().or_else(|| arg_results[2].unit.clone()),
+            )),
+            "pmt" => Ok((math::pmt(args[0], args[1], args[2]), arg_results[2].unit.clone())),
+            // The cash flows are vectors, so these are handled by the engine
+            "npv" | "irr" => Err(ErrorType::ExpectedVector),
+            "map" | "filter" | "reduce" | "apply" => Err(ErrorType::ExpectedFunction),
+            "fmt" => Err(ErrorType::ExpectedString),
+            // Dates are objects, so this is handled by the engine
+            "unixtime" => Err(ErrorType::ExpectedDate),
+            "molarmass" => Err(ErrorType::ExpectedString),
+            // Sliders are handled when defining variables
+            "slider" => Err(ErrorType::SliderOutsideDefinition),
+            _ => Err(ErrorType::UnknownFunction(f.to_owned(), Vec::new())),
         }
     }
 
@@ -349,11 +685,62 @@ impl Environment {
     ) -> crate::common::Result<Value> {
         for (name, func) in &self.functions {
             if name == f {
-                return self.resolve_specific_function(func, args, full_range, context);
+                let depth = FUNCTION_DEPTH.with(Cell::get);
+                let max_depth = context.borrow().settings.limits.max_recursion_depth;
+                if depth >= max_depth {
+                    return Err(ErrorType::LimitExceeded(format!("recursion depth of {max_depth}")).with(full_range));
+                }
+
+                if depth == 0 {
+                    FUNCTION_CACHE.with(|cache| cache.borrow_mut().clear());
+                }
+                // Functions that use variables other than their arguments may return something
+                // else when called from another function, where the variables can be shadowed
+                let key = (context.borrow().settings.memoize_functions && self.is_pure(func))
+                    .then(|| (f.to_owned(), format!("{:?}", args.iter().map(|(arg, _)| arg).collect::<Vec<_>>())));
+                if let Some(value) = key.as_ref().and_then(|key| FUNCTION_CACHE.with(|cache| cache.borrow().get(key).cloned())) {
+                    return Ok(value);
+                }
+
+                FUNCTION_DEPTH.with(|d| d.set(depth + 1));
+                let result = self.resolve_specific_function(func, args, full_range, context);
+                FUNCTION_DEPTH.with(|d| d.set(depth));
+
+                if let (Some(key), Ok(value)) = (key, &result) {
+                    FUNCTION_CACHE.with(|cache| cache.borrow_mut().insert(key, value.clone()));
+                }
+                return result;
             }
         }
 
-        Err(ErrorType::UnknownFunction(f.to_owned()).with(full_range))
+        #[cfg(feature = "scripting")]
+        if let Some(scripts) = self.scripts.as_ref().filter(|scripts| scripts.argument_count(f).is_some()) {
+            let args = args.iter().map(|(arg, _)| arg.number).collect::<Vec<_>>();
+            return scripts.call(f, &args)
+                .map(Value::only_number)
+                .map_err(|ty| ty.with(full_range));
+        }
+
+        Err(ErrorType::UnknownFunction(f.to_owned(), self.similar_functions(f)).with(full_range))
+    }
+
+    /// Whether the result of `f` only depends on its arguments, i.e. it doesn't use any variables
+    fn is_pure(&self, f: &Function) -> bool {
+        fn uses_variables(env: &Environment, f: &Function, ast: &[AstNode]) -> bool {
+            ast.iter().any(|node| match &node.data {
+                AstNodeData::Identifier(name) => !f.arguments.iter().any(|(arg, _)| arg == name)
+                    && (name == "ans" || name == "last" || env.variables.iter().any(|(var, _)| var == name)),
+                AstNodeData::Group(group) => uses_variables(env, f, group),
+                AstNodeData::Arguments(arguments) => arguments.iter().any(|arg| uses_variables(env, f, arg)),
+                _ => false,
+            })
+        }
+
+        !f.variants.iter().any(|(variant, ast)| {
+            uses_variables(self, f, ast) || matches!(variant, FunctionVariantType::BooleanVariant { lhs, rhs, tolerance, .. }
+                if uses_variables(self, f, lhs) || uses_variables(self, f, rhs)
+                    || tolerance.as_ref().map_or(false, |tolerance| uses_variables(self, f, tolerance)))
+        })
     }
 
     pub fn resolve_specific_function(
@@ -367,16 +754,22 @@ impl Environment {
         for (i, (arg, range)) in call_side_args.iter().enumerate() {
             let definition_arg = &f.arguments[i];
 
-            let call_side_arg_value = if arg.unit.is_some() && definition_arg.1.is_some() {
-                convert(
-                    arg.unit.as_ref().unwrap(),
-                    definition_arg.1.as_ref().unwrap(),
-                    arg.number,
+            let (call_side_arg_value, uncertainty) = if let (Some(arg_unit), Some(definition_unit)) = (&arg.unit, &definition_arg.1) {
+                let convert = |n: f64| convert(
+                    arg_unit,
+                    definition_unit,
+                    n,
                     &context.borrow().currencies,
+                    context.borrow().settings.data_prefixes,
                     *range,
-                )?
+                );
+                let value = convert(arg.number)?;
+                let uncertainty = if arg.uncertainty != 0.0 {
+                    (convert(arg.number + arg.uncertainty)? - value).abs()
+                } else { 0.0 };
+                (value, uncertainty)
             } else {
-                arg.number
+                (arg.number, arg.uncertainty)
             };
 
             let value = Variable(Value::number(
@@ -384,7 +777,7 @@ impl Environment {
                 definition_arg.1.clone(),
                 false,
                 Format::Decimal,
-            ));
+            ).with_uncertainty(uncertainty));
             temp_env
                 .set_variable(&definition_arg.0, value)
                 .map_err(|e| e.with(full_range))?;
@@ -395,20 +788,40 @@ impl Environment {
             ..context.borrow().clone()
         }));
 
-        self.evaluate_function(f, context)
+        Self::evaluate_function(f, context)
             .map_err(|e| e.error.with(full_range))
     }
 
-    fn evaluate_function(&self, f: &Function, context: Context) -> crate::common::Result<Value> {
+    /// Calculates the single argument function `f` for `samples` evenly spaced arguments from the
+    /// start to the end of `range`. Unlike calling [Self::resolve_specific_function] for every
+    /// argument, the environment is only cloned once. The values of arguments for which the
+    /// function can't be calculated or doesn't result in a number are NaN.
+    pub fn sample_function(
+        &self,
+        f: &Function,
+        range: RangeInclusive<f64>,
+        samples: usize,
+        context: Context,
+    ) -> Vec<(f64, f64)> {
+        let Some(evaluator) = FunctionEvaluator::new(f, self, &context) else { return vec![]; };
+        analysis::arguments(range, samples)
+            .map(|x| (x, evaluator.evaluate(x)))
+            .collect()
+    }
+
+    pub(crate) fn evaluate_function(f: &Function, context: Context) -> crate::common::Result<Value> {
         for (variant, ast) in &f.variants {
-            if let FunctionVariantType::BooleanVariant { lhs, rhs, operator } = variant {
+            if let FunctionVariantType::BooleanVariant { lhs, rhs, operator, tolerance } = variant {
                 let lhs = Engine::evaluate(lhs.clone(), context.clone())?;
                 let rhs = Engine::evaluate(rhs.clone(), context.clone())?;
+                let tolerance = Engine::tolerance(&lhs, tolerance.clone(), context.clone())?;
                 if Engine::check_boolean_operator(
                     &lhs,
                     &rhs,
                     *operator,
+                    &tolerance,
                     &context.borrow().currencies,
+                    context.borrow().settings.data_prefixes,
                 ) {
                     return Engine::evaluate(ast.clone(), context.clone());
                 }
@@ -426,7 +839,16 @@ impl Environment {
         }
     }
 
+    /// Compiles the [Rhai](https://rhai.rs) script `source` and makes the functions defined in it
+    /// callable like custom functions. Custom functions with the same name take precedence.
+    #[cfg(feature = "scripting")]
+    pub fn add_script(&mut self, source: &str) -> Result<(), ScriptError> {
+        self.scripts = Some(Arc::new(Scripts::with_script(self.scripts.as_deref(), source)?));
+        Ok(())
+    }
+
     pub(crate) fn set_function(&mut self, f: &str, value: Function) -> Result<(), ErrorType> {
+        self.revision += 1;
         if self.is_standard_function(f) {
             return Err(ErrorType::ReservedFunction(f.to_owned()));
         }
@@ -443,6 +865,7 @@ impl Environment {
     }
 
     pub(crate) fn remove_function(&mut self, f: &str) -> Result<(), ErrorType> {
+        self.revision += 1;
         if self.is_standard_function(f) {
             return Err(ErrorType::ReservedFunction(f.to_owned()));
         }
@@ -0,0 +1,213 @@
+/*
+ * Copyright (c) 2022-2023, david072
+ *
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+/// Witnesses for which the Miller-Rabin test is deterministic for all 64-bit integers
+const MILLER_RABIN_BASES: [u64; 12] = [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37];
+
+fn mul_mod(a: u64, b: u64, modulus: u64) -> u64 {
+    ((a as u128 * b as u128) % modulus as u128) as u64
+}
+
+fn pow_mod(mut base: u64, mut exponent: u64, modulus: u64) -> u64 {
+    let mut result = 1;
+    base %= modulus;
+    while exponent > 0 {
+        if exponent & 1 == 1 { result = mul_mod(result, base, modulus); }
+        base = mul_mod(base, base, modulus);
+        exponent >>= 1;
+    }
+    result
+}
+
+pub fn gcd(mut a: u64, mut b: u64) -> u64 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+/// Tests whether `n` is prime with the Miller-Rabin test, using witnesses for which the test
+/// is deterministic.
+pub fn is_prime(n: u64) -> bool {
+    if n < 2 { return false; }
+    for p in MILLER_RABIN_BASES {
+        if n % p == 0 { return n == p; }
+    }
+
+    // n - 1 = d * 2^s
+    let s = (n - 1).trailing_zeros();
+    let d = (n - 1) >> s;
+    'witnesses: for a in MILLER_RABIN_BASES {
+        let mut x = pow_mod(a, d, n);
+        if x == 1 || x == n - 1 { continue; }
+        for _ in 1..s {
+            x = mul_mod(x, x, n);
+            if x == n - 1 { continue 'witnesses; }
+        }
+        return false;
+    }
+    true
+}
+
+/// The smallest prime that is greater than `n`
+pub fn next_prime(n: u64) -> u64 {
+    if n < 2 { return 2; }
+    let mut candidate = if n % 2 == 0 { n + 1 } else { n + 2 };
+    while !is_prime(candidate) { candidate += 2; }
+    candidate
+}
+
+/// Finds a non-trivial divisor of the composite number `n` with Pollard's rho algorithm
+fn pollard_rho(n: u64) -> u64 {
+    if n % 2 == 0 { return 2; }
+    // The sequence can cycle without finding a divisor, in which case another one is tried
+    for c in 1.. {
+        let f = |x: u64| (mul_mod(x, x, n) + c) % n;
+        let (mut x, mut y, mut divisor) = (2, 2, 1);
+        while divisor == 1 {
+            x = f(x);
+            y = f(f(y));
+            divisor = gcd(x.abs_diff(y), n);
+        }
+        if divisor != n { return divisor; }
+    }
+    unreachable!()
+}
+
+/// The prime factors of `n` in ascending order, repeated according to their multiplicity (e.g.
+/// `[2, 2, 3]` for 12). `1` has no prime factors.
+pub fn factorize(mut n: u64) -> Vec<u64> {
+    let mut factors = vec![];
+    // Small factors are found a lot faster by trial division
+    for p in MILLER_RABIN_BASES {
+        while n % p == 0 {
+            factors.push(p);
+            n /= p;
+        }
+    }
+
+    let mut remaining = vec![n];
+    while let Some(n) = remaining.pop() {
+        if n == 1 { continue; }
+        if is_prime(n) {
+            factors.push(n);
+        } else {
+            let divisor = pollard_rho(n);
+            remaining.push(divisor);
+            remaining.push(n / divisor);
+        }
+    }
+    factors.sort_unstable();
+    factors
+}
+
+/// Euler's totient function, the number of integers from 1 to `n` that are coprime to `n`
+pub fn totient(n: u64) -> u64 {
+    let mut factors = factorize(n);
+    factors.dedup();
+    factors.into_iter().fold(n, |result, p| result / p * (p - 1))
+}
+
+/// The number of ways to choose `k` of `n` elements without regard to their order. The result is
+/// exact as long as it fits into a u128, since the intermediate results never get larger than it.
+pub fn combinations(n: u64, k: u64) -> f64 {
+    if k > n { return 0.0; }
+    let k = k.min(n - k);
+
+    let mut result = 1u128;
+    for i in 1..=k {
+        // `result * factor` is divisible by `i`, so dividing both by their common divisors first
+        // keeps them integers
+        let factor = (n - k + i) as u128;
+        let divisor = gcd_u128(result, i as u128);
+        let (reduced, i_reduced) = (result / divisor, i as u128 / divisor);
+        match reduced.checked_mul(factor / i_reduced) {
+            Some(product) => result = product,
+            None => {
+                let mut result = reduced as f64 * (factor / i_reduced) as f64;
+                for j in i + 1..=k {
+                    if result.is_infinite() { break; }
+                    result *= (n - k + j) as f64 / j as f64;
+                }
+                return result;
+            }
+        }
+    }
+    result as f64
+}
+
+/// The number of ways to arrange `k` of `n` elements, i.e. `n! / (n - k)!`
+pub fn permutations(n: u64, k: u64) -> f64 {
+    if k > n { return 0.0; }
+
+    let mut result = 1u128;
+    for factor in n - k + 1..=n {
+        match result.checked_mul(factor as u128) {
+            Some(product) => result = product,
+            None => {
+                let mut result = result as f64;
+                for factor in factor..=n {
+                    if result.is_infinite() { break; }
+                    result *= factor as f64;
+                }
+                return result;
+            }
+        }
+    }
+    result as f64
+}
+
+fn gcd_u128(mut a: u128, mut b: u128) -> u128 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn primes() {
+        let primes = (0..50).filter(|n| is_prime(*n)).collect::<Vec<_>>();
+        assert_eq!(primes, [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47]);
+        // Carmichael numbers and strong pseudoprimes to small bases
+        assert!(!is_prime(561));
+        assert!(!is_prime(3215031751));
+        assert!(is_prime(9007199254740881));
+        assert_eq!(next_prime(13), 17);
+        assert_eq!(next_prime(0), 2);
+        assert_eq!(next_prime(2), 3);
+    }
+
+    #[test]
+    fn factors() {
+        assert_eq!(factorize(1), Vec::<u64>::new());
+        assert_eq!(factorize(360), [2, 2, 2, 3, 3, 5]);
+        assert_eq!(factorize(600851475143), [71, 839, 1471, 6857]);
+        // Two large primes, which trial division would take long for
+        assert_eq!(factorize(1000000007 * 998244353), [998244353, 1000000007]);
+        assert_eq!(totient(1), 1);
+        assert_eq!(totient(36), 12);
+        assert_eq!(totient(97), 96);
+        assert_eq!(gcd(12, 18), 6);
+        assert_eq!(gcd(0, 5), 5);
+    }
+
+    #[test]
+    fn combinatorics() {
+        assert_eq!(combinations(5, 2), 10.0);
+        assert_eq!(combinations(5, 0), 1.0);
+        assert_eq!(combinations(2, 5), 0.0);
+        assert_eq!(combinations(60, 30), 118264581564861424.0);
+        assert!((combinations(1000, 500) / 2.7028824094543656e299 - 1.0).abs() < 1e-12);
+        assert_eq!(combinations(1 << 53, 1 << 52), f64::INFINITY);
+        assert_eq!(permutations(5, 2), 20.0);
+        assert_eq!(permutations(5, 5), 120.0);
+        assert_eq!(permutations(200, 200), f64::INFINITY);
+    }
+}
@@ -0,0 +1,94 @@
+/*
+ * Copyright (c) 2023, david072
+ *
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+use std::fmt::{Debug, Formatter};
+
+use rhai::{CallFnOptions, Dynamic, Engine, FnAccess, Scope, AST};
+use thiserror::Error;
+
+use crate::common::ErrorType;
+
+/// The maximum number of operations a single call of a script function can perform, so that e.g.
+/// an endless loop in a script can't freeze the calculator
+const MAX_OPERATIONS: u64 = 1_000_000;
+const MAX_CALL_LEVELS: usize = 64;
+const MAX_STRING_SIZE: usize = 64 * 1024;
+const MAX_COLLECTION_SIZE: usize = 64 * 1024;
+
+/// An error in the source of a script
+#[derive(Error, Debug, Clone)]
+#[error("{0}")]
+pub struct ScriptError(String);
+
+/// The functions defined in [Rhai](https://rhai.rs) scripts, which can be called like custom
+/// functions. They receive their arguments as numbers (without units) and have to return a number.
+/// Private functions can only be called by other functions in the scripts.
+pub(crate) struct Scripts {
+    engine: Engine,
+    ast: AST,
+}
+
+impl Debug for Scripts {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(self.functions().map(|(name, _)| name)).finish()
+    }
+}
+
+impl Scripts {
+    /// Creates an engine that can't do anything outside of the calculation (scripts can't access
+    /// files anyway) and that aborts scripts that exceed its execution budget
+    fn engine() -> Engine {
+        let mut engine = Engine::new();
+        engine.set_max_operations(MAX_OPERATIONS);
+        engine.set_max_call_levels(MAX_CALL_LEVELS);
+        engine.set_max_string_size(MAX_STRING_SIZE);
+        engine.set_max_array_size(MAX_COLLECTION_SIZE);
+        engine.set_max_map_size(MAX_COLLECTION_SIZE);
+        engine.on_print(|_| {});
+        engine.on_debug(|_, _, _| {});
+        engine
+    }
+
+    /// Compiles `source` and returns scripts containing the functions of `scripts` (if given)
+    /// together with the ones defined in `source`. Functions in `source` replace existing ones
+    /// with the same name and number of arguments.
+    pub(crate) fn with_script(scripts: Option<&Scripts>, source: &str) -> Result<Scripts, ScriptError> {
+        let engine = Self::engine();
+        let ast = engine.compile(source).map_err(|e| ScriptError(e.to_string()))?;
+        let ast = match scripts {
+            Some(scripts) => scripts.ast.merge(&ast),
+            None => ast,
+        };
+        Ok(Scripts { engine, ast })
+    }
+
+    /// The names of the public functions together with their argument counts. Functions without
+    /// arguments are left out, since calculations can't call them.
+    pub(crate) fn functions(&self) -> impl Iterator<Item=(&str, usize)> {
+        self.ast.iter_functions()
+            .filter(|function| function.access != FnAccess::Private && !function.params.is_empty())
+            .map(|function| (function.name, function.params.len()))
+    }
+
+    pub(crate) fn argument_count(&self, name: &str) -> Option<usize> {
+        self.functions().find(|(f, _)| *f == name).map(|(_, count)| count)
+    }
+
+    pub(crate) fn call(&self, name: &str, args: &[f64]) -> Result<f64, ErrorType> {
+        let args = args.iter().map(|arg| Dynamic::from_float(*arg)).collect::<Vec<_>>();
+        let options = CallFnOptions::new().eval_ast(false);
+        let result = self.engine.call_fn_with_options::<Dynamic>(options, &mut Scope::new(), &self.ast, name, args)
+            .map_err(|e| ErrorType::ScriptError(e.to_string()))?;
+
+        if let Ok(number) = result.as_float() {
+            Ok(number)
+        } else if let Ok(number) = result.as_int() {
+            Ok(number as f64)
+        } else {
+            Err(ErrorType::ScriptError(format!("Expected the function to return a number, found {}", result.type_name())))
+        }
+    }
+}
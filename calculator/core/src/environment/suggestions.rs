@@ -0,0 +1,113 @@
+/*
+ * Copyright (c) 2023, david072
+ *
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+use crate::environment::constants::CONSTANTS;
+use crate::environment::unit_conversion::UNITS;
+use crate::environment::units::UNIT_ALIASES;
+use crate::environment::{Environment, STANDARD_VARIABLES};
+
+/// How many names are suggested at most
+const MAX_SUGGESTIONS: usize = 3;
+
+impl Environment {
+    /// The names of functions which are similar to the unknown function `name`
+    pub(crate) fn similar_functions(&self, name: &str) -> Vec<String> {
+        let functions = self.function_signatures();
+        similar_names(name, functions.iter().map(|f| f.name.as_str()))
+    }
+
+    /// The names of variables, constants, units and functions which are similar to the unknown
+    /// identifier `name`
+    pub(crate) fn similar_identifiers(&self, name: &str) -> Vec<String> {
+        let functions = self.function_signatures();
+        let names = self.variables.iter().map(|(name, _)| name.as_str())
+            .chain(STANDARD_VARIABLES)
+            .chain(CONSTANTS.iter().map(|constant| constant.name))
+            .chain(UNITS)
+            .chain(UNIT_ALIASES.iter().map(|(alias, _)| *alias))
+            .chain(functions.iter().map(|f| f.name.as_str()));
+        similar_names(name, names)
+    }
+}
+
+/// The candidates with the smallest edit distance to `name`, if it's small enough for them to be
+/// likely meant instead of `name`. Differences in case are ignored, so that e.g. `PI` suggests `pi`.
+fn similar_names<'a>(name: &str, candidates: impl Iterator<Item = &'a str>) -> Vec<String> {
+    // Short names are similar to too many other names (e.g. `x` to most units)
+    let max_distance = match name.chars().count() {
+        0..=2 => 0,
+        3..=5 => 1,
+        _ => 2,
+    };
+    let lowercase_name = name.to_lowercase();
+
+    let mut best_distance = max_distance;
+    let mut result = Vec::<String>::new();
+    for candidate in candidates {
+        if candidate == name || result.iter().any(|n| n == candidate) { continue; }
+
+        let distance = edit_distance(&lowercase_name, &candidate.to_lowercase());
+        if distance < best_distance {
+            best_distance = distance;
+            result.clear();
+        }
+        if distance == best_distance && result.len() < MAX_SUGGESTIONS {
+            result.push(candidate.to_owned());
+        }
+    }
+    result
+}
+
+/// The number of insertions, deletions, substitutions and transpositions of adjacent characters
+/// needed to turn `a` into `b`
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a = a.chars().collect::<Vec<_>>();
+    let b = b.chars().collect::<Vec<_>>();
+
+    // The distances of the prefixes of `a` to the prefixes of `b`, for the last two rows
+    let mut before_previous = vec![0; b.len() + 1];
+    let mut previous = (0..=b.len()).collect::<Vec<_>>();
+    let mut current = vec![0; b.len() + 1];
+    for i in 1..=a.len() {
+        current[0] = i;
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            current[j] = (previous[j] + 1)
+                .min(current[j - 1] + 1)
+                .min(previous[j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                current[j] = current[j].min(before_previous[j - 2] + 1);
+            }
+        }
+        std::mem::swap(&mut before_previous, &mut previous);
+        std::mem::swap(&mut previous, &mut current);
+    }
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn distance() {
+        assert_eq!(edit_distance("sqr", "sqrt"), 1);
+        assert_eq!(edit_distance("sin", "sni"), 1);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(edit_distance("abc", "abc"), 0);
+    }
+
+    #[test]
+    fn suggestions() {
+        let env = Environment::new();
+        assert_eq!(env.similar_functions("sqr"), vec!["sqrt"]);
+        assert_eq!(env.similar_identifiers("pie"), vec!["pi"]);
+        assert_eq!(env.similar_identifiers("PI"), vec!["pi"]);
+        assert!(env.similar_identifiers("x").is_empty());
+        assert!(env.similar_identifiers("something").is_empty());
+    }
+}
@@ -6,7 +6,10 @@
 
 use crate::{common::{ErrorType, Result}, environment::currencies::{Currencies, is_currency}, environment::unit_conversion::{convert_units, format_unit, UNITS}, error};
 use crate::common::SourceRange;
+use crate::environment::dimensions::Dimension;
+use crate::DataPrefixes;
 
+#[allow(clippy::enum_variant_names)]
 #[derive(Debug, PartialEq, Clone, serde::Serialize, serde::Deserialize)]
 pub enum Unit {
     Product(Vec<Unit>),
@@ -156,14 +159,12 @@ impl Unit {
                         }
                     }
                     (Self::Unit(num_str, num_pow, num_range),
-                        Self::Unit(denom_str, denom_pow, ..)) => {
-                        if num_str == denom_str {
-                            if num_pow == denom_pow {
-                                return false;
-                            } else if denom_pow < num_pow {
-                                *num_pow -= *denom_pow;
-                                *self = Self::Unit(num_str.clone(), *num_pow, num_range.clone());
-                            }
+                        Self::Unit(denom_str, denom_pow, ..)) if num_str == denom_str => {
+                        if num_pow == denom_pow {
+                            return false;
+                        } else if denom_pow < num_pow {
+                            *num_pow -= *denom_pow;
+                            *self = Self::Unit(num_str.clone(), *num_pow, *num_range);
                         }
                     }
                     _ => {}
@@ -214,6 +215,89 @@ impl Unit {
         true
     }
 
+    /// Converts units of the numerator into units of the same dimension in the denominator (e.g.
+    /// `km*min/h` into `km*h/h`), so that [`Unit::simplify`] can reduce them.
+    ///
+    /// Returns the factor that the value has to be multiplied with.
+    pub fn unify_dimensions(&mut self, currencies: &Currencies, data_prefixes: DataPrefixes) -> f64 {
+        let Self::Fraction(num, denom) = self else { return 1.0; };
+        let denom_units = match &**denom {
+            Self::Product(units) => units.clone(),
+            unit => vec![unit.clone()],
+        };
+        let num_units = match &mut **num {
+            Self::Product(units) => units.iter_mut().collect::<Vec<_>>(),
+            unit => vec![unit],
+        };
+
+        let mut factor = 1.0;
+        for num_unit in num_units {
+            let Self::Unit(num_name, num_power, _) = num_unit else { continue; };
+            if denom_units.iter().any(|u| matches!(u, Self::Unit(name, ..) if name == num_name)) {
+                continue;
+            }
+            let Some(dimension) = Dimension::of_str(num_name) else { continue; };
+
+            for denom_unit in &denom_units {
+                let Self::Unit(denom_name, ..) = denom_unit else { continue; };
+                if Dimension::of_str(denom_name) != Some(dimension) { continue; }
+
+                let (src, dst) = (Unit::from(num_name.as_str()), Unit::from(denom_name.as_str()));
+                let convert = |n| convert(&src, &dst, n, currencies, data_prefixes, SourceRange::empty());
+                // Only proportional conversions can be applied as a factor (e.g. not °C to K)
+                let (Ok(unit_factor), Ok(zero)) = (convert(1.0), convert(0.0)) else { continue; };
+                if zero != 0.0 { continue; }
+                factor *= unit_factor.powf(*num_power);
+                *num_name = denom_name.clone();
+                break;
+            }
+        }
+
+        factor
+    }
+
+    /// The units this unit is made of with their powers, where the units of the denominator have
+    /// negative powers (e.g. `km^2` and `h^-1` for `km^2/h`)
+    fn powers(&self) -> Vec<(String, f64)> {
+        match self {
+            Self::Unit(name, power, _) => vec![(name.clone(), *power)],
+            Self::Product(units) => units.iter().flat_map(Unit::powers).collect(),
+            Self::Fraction(num, denom) => num.powers().into_iter()
+                .chain(denom.powers().into_iter().map(|(name, power)| (name, -power)))
+                .collect(),
+        }
+    }
+
+    /// Multiplies `lhs` with `rhs` raised to `rhs_power` (e.g. `-1` to divide by it), combining the
+    /// powers of equal units. Returns [None] if all units cancel out.
+    pub(crate) fn combine(lhs: Option<&Unit>, rhs: Option<&Unit>, rhs_power: f64) -> Option<Unit> {
+        let mut powers: Vec<(String, f64)> = lhs.map(Unit::powers).unwrap_or_default();
+        for (name, power) in rhs.map(Unit::powers).unwrap_or_default() {
+            match powers.iter_mut().find(|(n, _)| *n == name) {
+                Some((_, p)) => *p += power * rhs_power,
+                None => powers.push((name, power * rhs_power)),
+            }
+        }
+
+        let product = |units: Vec<Unit>| match units.len() {
+            0 => None,
+            1 => units.into_iter().next(),
+            _ => Some(Unit::Product(units)),
+        };
+        let (num, denom): (Vec<_>, Vec<_>) = powers.into_iter()
+            .filter(|(_, power)| *power != 0.0)
+            .partition(|(_, power)| *power > 0.0);
+        let num = product(num.into_iter().map(|(name, power)| Unit::new(&name, power, SourceRange::empty())).collect());
+        match num {
+            Some(num) => match product(denom.into_iter().map(|(name, power)| Unit::new(&name, -power, SourceRange::empty())).collect()) {
+                Some(denom) => Some(Unit::Fraction(Box::new(num), Box::new(denom))),
+                None => Some(num),
+            },
+            // Without a numerator, the units keep their negative powers (e.g. `h^-1`)
+            None => product(denom.into_iter().map(|(name, power)| Unit::new(&name, power, SourceRange::empty())).collect()),
+        }
+    }
+
     pub fn format(&self, full_unit: bool, plural: bool) -> String {
         if !full_unit {
             match self {
@@ -276,7 +360,17 @@ impl Unit {
                 Self::Fraction(numerator, denominator) => {
                     format!("{} per {}", numerator.format(full_unit, plural), denominator.format(full_unit, false))
                 }
-                Self::Unit(str, power, ..) => format!("{}{}", format_unit(str, plural), format_unit_power(*power)),
+                Self::Unit(str, power, ..) => {
+                    let name = match split_binary_prefix(str) {
+                        Some(((_, _, prefix_name), unit)) => {
+                            let mut unit_name = format_unit(unit, plural);
+                            unit_name[..1].make_ascii_lowercase();
+                            format!("{prefix_name}{unit_name}")
+                        }
+                        None => format_unit(str, plural),
+                    };
+                    format!("{name}{}", format_unit_power(*power))
+                }
             }
         }
     }
@@ -305,15 +399,59 @@ impl std::fmt::Display for Unit {
 }
 
 // Stores prefix with its power (e.g. k => * 10^3)
-pub const PREFIXES: [(char, i32); 14] = [
-    ('n', -9), ('m', -3), ('c', -2), ('d', -1),
+pub const PREFIXES: [(char, i32); 15] = [
+    ('n', -9), ('µ', -6), ('m', -3), ('c', -2), ('d', -1),
     ('\0', 0),
     ('h', 2), ('k', 3), ('M', 6), ('G', 9), ('T', 12), ('P', 15), ('E', 18), ('Z', 21), ('Y', 24),
 ];
 
+/// Binary prefixes with their power of two and their name (e.g. Ki => * 2^10). They can only be
+/// used with data units.
+pub const BINARY_PREFIXES: [(&str, i32, &str); 8] = [
+    ("Ki", 10, "Kibi"), ("Mi", 20, "Mebi"), ("Gi", 30, "Gibi"), ("Ti", 40, "Tebi"),
+    ("Pi", 50, "Pebi"), ("Ei", 60, "Exbi"), ("Zi", 70, "Zebi"), ("Yi", 80, "Yobi"),
+];
+
+/// Alternative names of units with the unit they stand for
+pub const UNIT_ALIASES: [(&str, &str); 4] = [
+    ("deg", "°"), ("grad", "gon"), ("arcmin", "'"), ("arcsec", "\""),
+];
+
+/// Bytes and bits
+const DATA_UNITS: [&str; 2] = ["b", "B"];
+
+/// Splits a data unit with a binary prefix (e.g. `Kib`) into the prefix and the unit
+pub(crate) fn split_binary_prefix(unit: &str) -> Option<((&'static str, i32, &'static str), &str)> {
+    BINARY_PREFIXES.into_iter().find_map(|prefix| {
+        unit.strip_prefix(prefix.0)
+            .filter(|unit| DATA_UNITS.contains(unit))
+            .map(|unit| (prefix, unit))
+    })
+}
+
+/// If `unit` is a data unit, returns it without its prefix together with the factor of the prefix
+fn data_unit_factor(unit: &str, data_prefixes: DataPrefixes) -> Option<(&str, f64)> {
+    if let Some(((_, power, _), unit)) = split_binary_prefix(unit) {
+        return Some((unit, 2f64.powi(power)));
+    }
+    if DATA_UNITS.contains(&unit) { return Some((unit, 1.0)); }
+
+    let mut chars = unit.chars();
+    let power = get_prefix_power(chars.next()?)?;
+    let unit = chars.as_str();
+    if !DATA_UNITS.contains(&unit) { return None; }
+
+    let factor = match data_prefixes {
+        DataPrefixes::Binary if power > 0 && power % 3 == 0 => 1024f64.powi(power / 3),
+        _ => 10f64.powi(power),
+    };
+    Some((unit, factor))
+}
+
 pub fn prefix_to_string(prefix: char) -> Option<&'static str> {
     match prefix {
         'n' => Some("Nano"),
+        'µ' => Some("Micro"),
         'm' => Some("Milli"),
         'c' => Some("Centi"),
         'd' => Some("Deci"),
@@ -335,7 +473,23 @@ pub fn is_unit(str: &str) -> bool {
 }
 
 pub fn is_unit_with_prefix(str: &str) -> bool {
-    is_unit(str) || (is_prefix(str.chars().next().unwrap()) && is_unit(&str[1..]))
+    let str = resolve_unit_alias(str);
+    is_unit(&str)
+        || str.chars().next().map_or(false, |prefix| is_prefix(prefix) && is_unit(&str[prefix.len_utf8()..]))
+        || split_binary_prefix(&str).is_some()
+}
+
+/// Replaces an alternative name of a unit (e.g. `deg` or `kdeg`) with the name that is used for
+/// the unit everywhere else (e.g. `°` or `k°`)
+pub fn resolve_unit_alias(str: &str) -> String {
+    let find_alias = |str: &str| UNIT_ALIASES.iter().find(|(alias, _)| *alias == str).map(|(_, unit)| *unit);
+    if let Some(unit) = find_alias(str) { return unit.to_string(); }
+
+    let mut chars = str.chars();
+    match (chars.next().filter(|c| is_prefix(*c)), find_alias(chars.as_str())) {
+        (Some(prefix), Some(unit)) => format!("{prefix}{unit}"),
+        _ => str.to_string(),
+    }
 }
 
 pub fn is_prefix(c: char) -> bool {
@@ -352,24 +506,87 @@ pub fn get_prefix_power(c: char) -> Option<i32> {
     None
 }
 
-pub fn convert(src_unit: &Unit, dst_unit: &Unit, n: f64, currencies: &Currencies, range: SourceRange) -> Result<f64> {
+/// The units that [auto_prefix] gives prefixes to. Times only get smaller prefixes, since larger
+/// amounts are given in minutes, hours etc. instead.
+const AUTO_PREFIX_UNITS: [&str; 26] = [
+    "m", "l", "s", "g", "Pa", "K", "J", "eV", "N", "Hz", "W", "C", "V", "F", "Ohm", "S", "Wb", "T",
+    "H", "lm", "lx", "Bq", "Gy", "Sv", "kat", "mol",
+];
+
+/// Picks the prefix of `unit` that makes `n` easiest to read, so that it's at least 1 and below
+/// 1000 if possible (e.g. `1.5 km` for `1500 m`). Only the prefixes that are powers of 1000 are
+/// used. Returns [None] if `unit` isn't a single unit with a positive power that is usually
+/// written with prefixes.
+///
+/// **Returns:** The unit with the new prefix and the factor to multiply `n` by
+pub(crate) fn auto_prefix(unit: &Unit, n: f64) -> Option<(Unit, f64)> {
+    let Unit::Unit(name, power, range) = unit else { return None; };
+    if !n.is_finite() || n == 0.0 || power.fract() != 0.0 || *power <= 0.0 { return None; }
+
+    let (current_power, base) = if is_unit(name) {
+        (0, name.as_str())
+    } else {
+        let prefix = name.chars().next()?;
+        (get_prefix_power(prefix)?, &name[prefix.len_utf8()..])
+    };
+    if !AUTO_PREFIX_UNITS.contains(&base) { return None; }
+
+    // The exponent of the number without a prefix, per power of the unit (e.g. 6 for 1000000 m
+    // and 3 for 1000000 m^2)
+    let exponent = ((n.abs().log10() + (current_power as f64) * power) / power).floor() as i32;
+    let mut new_power = exponent.div_euclid(3) * 3;
+    if base == "s" { new_power = new_power.min(0); }
+    let (prefix, new_power) = PREFIXES.into_iter()
+        .filter(|(_, p)| p % 3 == 0)
+        .min_by_key(|(_, p)| (p - new_power).abs())?;
+
+    let name = if new_power == 0 { base.to_string() } else { format!("{prefix}{base}") };
+    let factor = 10f64.powf((current_power - new_power) as f64 * power);
+    Some((Unit::Unit(name, *power, *range), factor))
+}
+
+pub fn convert(
+    src_unit: &Unit,
+    dst_unit: &Unit,
+    n: f64,
+    currencies: &Currencies,
+    data_prefixes: DataPrefixes,
+    range: SourceRange,
+) -> Result<f64> {
     match src_unit {
         Unit::Product(src_units) => {
             let Unit::Product(dst_units) = dst_unit else { error!(UnitsNotMatching: range); };
             src_units.iter()
                 .zip(dst_units)
                 .try_fold(n, |n, (src, dst)| {
-                    convert(src, dst, n, currencies, range)
+                    convert(src, dst, n, currencies, data_prefixes, range)
                 })
         }
         Unit::Fraction(src_numerator, src_denominator) => {
             let Unit::Fraction(dst_numerator, dst_denominator) = dst_unit else { error!(UnitsNotMatching: range); };
-            let numerator = convert(src_numerator, dst_numerator, n, currencies, range)?;
-            let denominator = convert(src_denominator, dst_denominator, 1.0, currencies, range)?;
+            let numerator = convert(src_numerator, dst_numerator, n, currencies, data_prefixes, range)?;
+            let denominator = convert(src_denominator, dst_denominator, 1.0, currencies, data_prefixes, range)?;
             Ok(numerator / denominator)
         }
         Unit::Unit(src, power, range) => {
             let Unit::Unit(dst, dst_power, dst_range) = dst_unit else { error!(UnitsNotMatching: *range); };
+
+            // The prefixes of data units depend on the settings and can be binary prefixes, so
+            // they are applied here
+            if let (Some((src, src_factor)), Some((dst, dst_factor)), true) = (
+                data_unit_factor(src, data_prefixes),
+                data_unit_factor(dst, data_prefixes),
+                power == dst_power,
+            ) {
+                return convert_units(
+                    (src, *power, *range),
+                    (dst, *dst_power, *dst_range),
+                    n * (src_factor / dst_factor).powf(*power),
+                    currencies,
+                    range,
+                );
+            }
+
             convert_units(
                 (src, *power, *range),
                 (dst, *dst_power, *dst_range),
@@ -0,0 +1,788 @@
+/*
+ * Copyright (c) 2023, david072
+ *
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+use crate::astgen::ast::{AstNode, AstNodeData, AstNodeModifier, BooleanOperator, Operator};
+use crate::astgen::parser::{Parser, ParserResultData};
+use crate::astgen::tokenizer::{tokenize, Token, TokenType};
+use crate::common::{ErrorType, Result, SourceRange};
+use crate::environment::units::{Unit, PREFIXES};
+use crate::environment::{ArgCount, FunctionArgument, FunctionVariantType};
+use crate::{Context, Format, FormatStyle, InputMode};
+
+/// Formats a single line of input by parsing it as `from` and printing the resulting AST as `to`
+/// according to `style`. A trailing comment is kept as it is.
+///
+/// Returns an error if the line cannot be parsed or cannot be written as `to`, in which case the
+/// line should be left untouched.
+pub(crate) fn format_line(
+    line: &str,
+    context: Context,
+    style: FormatStyle,
+    from: InputMode,
+    to: InputMode,
+) -> Result<String> {
+    let tokens = tokenize(line)?;
+    let Some(last_token) = tokens.last() else {
+        // Empty line or a line consisting only of a comment
+        return Ok(line.trim().to_string());
+    };
+
+    let comment = line.get(last_token.range.end_char..)
+        .map(str::trim)
+        .filter(|rest| rest.starts_with('#'));
+
+    let parser_result = Parser::from_tokens(&tokens, context.clone())
+        .with_input_mode(from)
+        .parse_single()?;
+    let formatter = AstFormatter {
+        source: line,
+        tokens: &tokens,
+        style,
+        context: context.clone(),
+        // Every operation is its own group in RPN, most of which are not needed in infix notation
+        minimal_parentheses: from == InputMode::Rpn,
+        output_variable: None,
+    };
+    let mut result = match to {
+        InputMode::Infix => formatter.format(parser_result.data),
+        InputMode::Rpn => formatter.format_rpn(parser_result.data)?,
+    };
+
+    // Make sure the formatted line can still be parsed. This doesn't check that the meaning of the
+    // line stayed the same, but if it fails, something went wrong and we keep the line the way it was.
+    let new_tokens = tokenize(&result)?;
+    Parser::from_tokens(&new_tokens, context)
+        .with_input_mode(to)
+        .parse_single()?;
+
+    if let Some(comment) = comment {
+        result.push(' ');
+        result += comment;
+    }
+
+    Ok(result)
+}
+
+struct AstFormatter<'a> {
+    source: &'a str,
+    tokens: &'a [Token],
+    style: FormatStyle,
+    context: Context,
+    minimal_parentheses: bool,
+    output_variable: Option<String>,
+}
+
+impl<'a> AstFormatter<'a> {
+    fn format(mut self, data: ParserResultData) -> String {
+        match data {
+            ParserResultData::Calculation(ast) => self.ast(&ast),
+            ParserResultData::BooleanExpression { lhs, rhs, operator, tolerance } => {
+                self.boolean_expression(&lhs, &rhs, operator, &tolerance)
+            }
+            ParserResultData::VariableDefinition(name, ast) => match ast {
+                Some(ast) => {
+                    if let Some((sign, rhs)) = self.update(&ast) {
+                        format!("{name} {sign} {}", self.ast(rhs))
+                    } else if self.tokens.iter().any(|t| t.ty == TokenType::PostfixDefinitionSign) {
+                        format!("{} =: {name}", self.ast(&ast))
+                    } else {
+                        format!("{name} := {}", self.ast(&ast))
+                    }
+                }
+                None => format!("{name} :="),
+            },
+            ParserResultData::FunctionDefinition { name, function } => match function {
+                Some(function) => {
+                    let head = self.function_head(&name, &function.arguments);
+                    let is_only_else = function.variants.len() == 1
+                        && matches!(function.variants[0].0, FunctionVariantType::Else);
+                    let is_conditional = self.tokens.iter().any(|t| t.ty == TokenType::If);
+                    let body = if is_only_else {
+                        self.ast(&function.variants[0].1)
+                    } else if is_conditional {
+                        function.variants.iter()
+                            .map(|(variant, ast)| match variant {
+                                FunctionVariantType::BooleanVariant { lhs, rhs, operator, tolerance } => {
+                                    let condition = self.boolean_expression(lhs, rhs, *operator, tolerance);
+                                    format!("if {condition} then {}", self.ast(ast))
+                                }
+                                FunctionVariantType::Else => self.ast(ast),
+                            })
+                            .collect::<Vec<_>>()
+                            .join(" else ")
+                    } else {
+                        function.variants.iter()
+                            .map(|(variant, ast)| match variant {
+                                FunctionVariantType::BooleanVariant { lhs, rhs, operator, tolerance } => {
+                                    let condition = self.boolean_expression(lhs, rhs, *operator, tolerance);
+                                    format!("for {condition}: {}", self.ast(ast))
+                                }
+                                FunctionVariantType::Else => format!("else: {}", self.ast(ast)),
+                            })
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    };
+                    format!("{head} := {body}")
+                }
+                None => {
+                    // The arguments of removed functions are not part of the AST, so we take them
+                    // from the tokens instead.
+                    let mut head = String::new();
+                    for token in self.tokens.iter().take_while(|t| t.ty != TokenType::DefinitionSign) {
+                        head += &token.text;
+                        if token.ty == TokenType::Comma { head.push(' '); }
+                    }
+                    format!("{head} :=")
+                }
+            },
+            ParserResultData::Equation { lhs, rhs, output_variable, .. } => {
+                self.output_variable = output_variable.map(|(name, _)| name);
+                format!("{} = {}", self.ast(&lhs), self.ast(&rhs))
+            }
+        }
+    }
+
+    fn format_rpn(mut self, data: ParserResultData) -> Result<String> {
+        Ok(match data {
+            ParserResultData::Calculation(ast) => self.rpn(&ast)?,
+            ParserResultData::BooleanExpression { tolerance: Some(_), .. } => return Err(self.unsupported_in_rpn()),
+            ParserResultData::BooleanExpression { lhs, rhs, operator, tolerance: None } => {
+                format!("{} {} {operator}", self.rpn(&lhs)?, self.rpn(&rhs)?)
+            }
+            ParserResultData::VariableDefinition(name, Some(ast)) => match self.update(&ast) {
+                Some((sign, rhs)) => format!("{name} {sign} {}", self.rpn(rhs)?),
+                None => format!("{name} := {}", self.rpn(&ast)?),
+            },
+            ParserResultData::FunctionDefinition { name, function: Some(function) } => {
+                let [(FunctionVariantType::Else, ast)] = function.variants.as_slice() else {
+                    return Err(self.unsupported_in_rpn());
+                };
+                format!("{} := {}", self.function_head(&name, &function.arguments), self.rpn(ast)?)
+            }
+            ParserResultData::Equation { lhs, rhs, output_variable, .. } => {
+                self.output_variable = output_variable.map(|(name, _)| name);
+                format!("{} {} =", self.rpn(&lhs)?, self.rpn(&rhs)?)
+            }
+            // Removals look the same in both notations
+            data @ (ParserResultData::VariableDefinition(_, None)
+            | ParserResultData::FunctionDefinition { function: None, .. }) => self.format(data),
+        })
+    }
+
+    /// Returns the sign and the right-hand side of a variable update (e.g. `+=` and `5` in
+    /// `x += 5`), if the line is one
+    fn update<'b>(&self, ast: &'b [AstNode]) -> Option<(&'a str, &'b [AstNode])> {
+        let sign = self.tokens.iter().find(|t| t.ty == TokenType::CompoundDefinitionSign)?;
+        let [_, _, AstNode { data: AstNodeData::Group(rhs), .. }] = ast else { return None; };
+        Some((&sign.text, rhs))
+    }
+
+    fn boolean_expression(&self, lhs: &[AstNode], rhs: &[AstNode], operator: BooleanOperator, tolerance: &Option<Vec<AstNode>>) -> String {
+        let mut result = format!("{} {operator} {}", self.ast(lhs), self.ast(rhs));
+        if let Some(tolerance) = tolerance {
+            result += &format!(" within {}", self.ast(tolerance));
+        }
+        result
+    }
+
+    fn function_head(&self, name: &str, arguments: &[FunctionArgument]) -> String {
+        let arguments = arguments.iter()
+            .map(|(arg, unit)| match unit {
+                Some(unit) => format!("{arg} {}", self.unit(unit, None)),
+                None => arg.clone(),
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        format!("{name}({arguments})")
+    }
+
+    fn ast(&self, ast: &[AstNode]) -> String {
+        let mut result = String::new();
+
+        let mut i = 0usize;
+        while i < ast.len() {
+            let node = &ast[i];
+            i += 1;
+
+            let AstNodeData::Operator(operator) = node.data else {
+                let surrounding_operators = (
+                    i.checked_sub(2).and_then(|i| operator_of(&ast[i])),
+                    ast.get(i).and_then(operator_of),
+                );
+                match self.ungrouped(node, surrounding_operators) {
+                    Some(group) => push_juxtaposed(&mut result, &self.ast(group)),
+                    None => push_juxtaposed(&mut result, &self.node(node)),
+                }
+                if node.format != Format::Decimal {
+                    result += &format!(" in {}", node.format);
+                    // Merge the unit conversion into the format conversion (e.g. `in sci mi`)
+                    if let (Some(AstNodeData::Operator(Operator::In)), Some(unit_node)) = (ast.get(i).map(|n| &n.data), ast.get(i + 1)) {
+                        result.push(' ');
+                        result += &self.node(unit_node);
+                        i += 2;
+                    }
+                }
+                continue;
+            };
+
+            // Inferred multiplication
+            if operator == Operator::Multiply && node.range == SourceRange::empty() {
+                continue;
+            }
+
+            let text = operator_text(operator);
+            let is_word = text.chars().all(char::is_alphabetic);
+            if is_word || (self.style.spaces_around_operators && operator != Operator::Exponentiation) {
+                result += &format!(" {text} ");
+            } else {
+                result += text;
+            }
+        }
+
+        result
+    }
+
+    /// If parentheses should be kept to a minimum, returns the contents of `node` if it is a group
+    /// whose parentheses can be left out without changing the order of evaluation.
+    /// `surrounding_operators` are the operators before and after `node`.
+    fn ungrouped<'b>(&self, node: &'b AstNode, surrounding_operators: (Option<Operator>, Option<Operator>)) -> Option<&'b [AstNode]> {
+        if !self.minimal_parentheses || !is_plain_group(node) || self.written_as_literal(node).is_some() {
+            return None;
+        }
+        let AstNodeData::Group(group) = &node.data else { return None; };
+
+        // Operators of the same precedence are evaluated from left to right, except for chains of
+        // exponentiations, which are evaluated from the right
+        let loosest = group.iter().filter_map(operator_of).map(precedence).max()?;
+        let (before, after) = surrounding_operators;
+        let mut operators = group.iter().filter_map(operator_of);
+        let is_exponent = before == Some(Operator::Exponentiation)
+            && operators.clone().all(|op| op == Operator::Exponentiation);
+        let is_base = after == Some(Operator::Exponentiation)
+            && operators.any(|op| op == Operator::Exponentiation);
+        if before.map_or(false, |op| precedence(op) <= loosest) && !is_exponent
+            || after.map_or(false, |op| precedence(op) < loosest) || is_base {
+            return None;
+        }
+        Some(group)
+    }
+
+    /// Prints `ast` in reverse polish notation. Since the AST is flat, the operators are reordered
+    /// according to the precedence the engine uses.
+    fn rpn(&self, ast: &[AstNode]) -> Result<String> {
+        let mut output = vec![];
+        let mut operators: Vec<Operator> = vec![];
+
+        for node in ast {
+            let Some(operator) = operator_of(node) else {
+                output.push(self.rpn_operand(node)?);
+                continue;
+            };
+
+            while let Some(&last) = operators.last() {
+                if precedence(last) > precedence(operator) { break; }
+                // Exponentiations are right-associative
+                if last == Operator::Exponentiation && operator == Operator::Exponentiation { break; }
+                output.push(operator_text(last).to_string());
+                operators.pop();
+            }
+            operators.push(operator);
+        }
+
+        output.extend(operators.into_iter().rev().map(|op| operator_text(op).to_string()));
+        Ok(output.join(" "))
+    }
+
+    fn rpn_operand(&self, node: &AstNode) -> Result<String> {
+        let AstNodeData::Group(group) = &node.data else {
+            return Ok(self.rpn_with_format(self.node(node), node));
+        };
+        if self.written_as_literal(node).is_some() {
+            return Ok(self.rpn_with_format(self.node(node), node));
+        }
+
+        let mut result = match group.as_slice() {
+            [callee, AstNode { data: AstNodeData::Operator(Operator::Call), .. }, arguments] => {
+                let (AstNodeData::Identifier(name), AstNodeData::Arguments(arguments)) = (&callee.data, &arguments.data) else {
+                    return Err(self.unsupported_in_rpn());
+                };
+                // Objects can't be called in RPN and functions always take their default
+                // number of arguments
+                let arg_count = self.context.borrow().env.function_argument_count(name);
+                let arg_count = match arg_count {
+                    Some(ArgCount::Single(count)) => count,
+                    Some(ArgCount::Multiple(options)) => options[0],
+                    Some(ArgCount::AtLeast(min)) => min,
+                    None => return Err(self.unsupported_in_rpn()),
+                };
+                if self.context.borrow().env.is_valid_variable(name) || arguments.len() != arg_count {
+                    return Err(self.unsupported_in_rpn());
+                }
+
+                let mut result = vec![];
+                for argument in arguments {
+                    result.push(self.rpn(argument)?);
+                }
+                result.push(name.clone());
+                result.join(" ")
+            }
+            _ => self.rpn(group)?,
+        };
+
+        for modifier in &node.modifiers {
+            let modifier = match modifier {
+                AstNodeModifier::Factorial => "!",
+                AstNodeModifier::Percent => "%",
+                AstNodeModifier::Permille => "‰",
+                AstNodeModifier::Minus => "neg",
+                AstNodeModifier::BitwiseNot => "not",
+                AstNodeModifier::Plus => continue,
+                AstNodeModifier::Power(_) | AstNodeModifier::Adjustment(_) => return Err(self.unsupported_in_rpn()),
+            };
+            result.push(' ');
+            result += modifier;
+        }
+        if let Some(unit) = &node.unit {
+            result.push(' ');
+            result += &self.unit(unit, None);
+        }
+        Ok(self.rpn_with_format(result, node))
+    }
+
+    fn rpn_with_format(&self, mut text: String, node: &AstNode) -> String {
+        if node.format != Format::Decimal {
+            text += &format!(" {}", node.format);
+        }
+        text
+    }
+
+    fn unsupported_in_rpn(&self) -> crate::common::Error {
+        ErrorType::UnsupportedInRpn.with(SourceRange::line(0, 0, self.source.trim_end().len()))
+    }
+
+    fn node(&self, node: &AstNode) -> String {
+        if let Some(literal) = self.written_as_literal(node) {
+            return literal;
+        }
+
+        let mut result = String::new();
+        for modifier in node.modifiers.iter().filter(|m| m.is_prefix()) {
+            result += &modifier.to_string();
+        }
+
+        result += &self.node_data(node);
+
+        for modifier in &node.modifiers {
+            if matches!(modifier, AstNodeModifier::Factorial | AstNodeModifier::Percent | AstNodeModifier::Permille
+                | AstNodeModifier::Adjustment(_)) {
+                result += &modifier.to_string();
+            }
+        }
+
+        self.push_unit_or_prefix(&mut result, node);
+        result
+    }
+
+    fn push_unit_or_prefix(&self, result: &mut String, node: &AstNode) {
+        if let Some(unit) = &node.unit {
+            push_juxtaposed(result, &self.unit(unit, None));
+        }
+        for modifier in &node.modifiers {
+            let AstNodeModifier::Power(power) = modifier else { continue; };
+            if let Some((prefix, _)) = PREFIXES.iter().find(|(_, p)| p == power) {
+                push_juxtaposed(result, &prefix.to_string());
+            }
+        }
+    }
+
+    fn node_data(&self, node: &AstNode) -> String {
+        match &node.data {
+            AstNodeData::Literal(_) => self.literal(self.slice(node.range)),
+            AstNodeData::Operator(operator) => operator_text(*operator).to_string(),
+            AstNodeData::Group(group) => {
+                if let [callee, AstNode { data: AstNodeData::Operator(Operator::Call), .. }, arguments] = group.as_slice() {
+                    // The parser turns `√x` into a call of `sqrt`
+                    if self.slice(callee.range) == "√" {
+                        let is_bracketed = self.slice(arguments.range).get("√".len()..).unwrap_or_default().trim_start().starts_with('(');
+                        return if is_bracketed {
+                            format!("√({})", self.node(arguments))
+                        } else {
+                            format!("√{}", self.node(arguments))
+                        };
+                    }
+                    format!("{}({})", self.node(callee), self.node(arguments))
+                } else {
+                    format!("({})", self.ast(group))
+                }
+            }
+            AstNodeData::Identifier(name) => name.clone(),
+            AstNodeData::Unit(unit) => self.unit(unit, Some(node.range)),
+            AstNodeData::QuestionMark => match &self.output_variable {
+                Some(name) => format!("{name}?"),
+                None => "?".to_string(),
+            },
+            AstNodeData::Object(_) => self.slice(node.range).trim().to_string(),
+            AstNodeData::Arguments(arguments) => arguments.iter()
+                .map(|arg| self.ast(arg))
+                .collect::<Vec<_>>()
+                .join(", "),
+        }
+    }
+
+    /// Groups that the parser creates for what is written as a single number
+    fn written_as_literal(&self, node: &AstNode) -> Option<String> {
+        self.scientific_notation(node).or_else(|| self.mixed_measure(node))
+    }
+
+    /// The parser turns mixed measures (e.g. `5ft 11in`) into the group `(5ft + 11in)`, where the
+    /// additions have the range of the number after them. This turns such groups back into the
+    /// original notation.
+    fn mixed_measure(&self, node: &AstNode) -> Option<String> {
+        let AstNodeData::Group(group) = &node.data else { return None; };
+        if group.len() < 3 || group.len() % 2 == 0 { return None; }
+        let is_mixed_measure = group[1..].chunks(2).all(|pair| {
+            pair[0].data == AstNodeData::Operator(Operator::Plus) && pair[0].range == pair[1].range
+        }) && group.iter().step_by(2).all(|part| matches!(part.data, AstNodeData::Literal(_)) && part.unit.is_some());
+        if !is_mixed_measure { return None; }
+
+        Some(group.iter().step_by(2).map(|part| self.node(part)).collect::<Vec<_>>().join(" "))
+    }
+
+    /// The parser turns numbers in scientific notation (e.g. `3e5`) into the group `(3 * 10 ^ 5)`,
+    /// where every node has the range of the mantissa. This turns such groups back into the
+    /// original notation.
+    fn scientific_notation(&self, node: &AstNode) -> Option<String> {
+        let AstNodeData::Group(group) = &node.data else { return None; };
+        let [mantissa, multiply, ten, _, exponent] = group.as_slice() else { return None; };
+        if multiply.data != AstNodeData::Operator(Operator::Multiply)
+            || ten.data != AstNodeData::Literal(10.0)
+            || multiply.range != mantissa.range
+            || ten.range != mantissa.range {
+            return None;
+        }
+
+        let mut result = String::new();
+        for modifier in mantissa.modifiers.iter().filter(|m| m.is_prefix()) {
+            result += &modifier.to_string();
+        }
+        result += &self.node_data(mantissa);
+        result.push('e');
+        result += &self.node(exponent);
+        self.push_unit_or_prefix(&mut result, mantissa);
+        Some(result)
+    }
+
+    fn literal(&self, text: &str) -> String {
+        let text = text.trim();
+        // Empty groups are parsed into a literal (`() = 1`)
+        if text.starts_with('(') { return "()".to_string(); }
+
+        let lowercase = text.to_lowercase();
+        if lowercase.starts_with("0x") || lowercase.starts_with("0b") {
+            let mut digits = &text[2..];
+            if !self.style.keep_literal_width {
+                digits = digits.trim_start_matches(['0', '_']);
+                if digits.is_empty() { digits = "0"; }
+            }
+
+            let digits = if lowercase.starts_with("0b") {
+                digits.to_string()
+            } else if self.style.lowercase_hex {
+                digits.to_lowercase()
+            } else {
+                digits.to_uppercase()
+            };
+            return format!("{}{digits}", &lowercase[..2]);
+        }
+
+        // Only the leading zeros of the integer part and the trailing zeros of the fraction are
+        // redundant, but at least one digit has to stay in front of the point
+        let (integer, fraction) = text.split_once('.').unwrap_or((text, ""));
+        let integer = match integer.trim_start_matches('0') {
+            "" => "0",
+            integer => integer,
+        };
+        match fraction.trim_end_matches('0') {
+            "" => integer.to_string(),
+            fraction => format!("{integer}.{fraction}"),
+        }
+    }
+
+    /// Formats `unit`. If canonical units are disabled, the unit is taken from the source.
+    /// `range` is the full range of the unit, if it is known.
+    fn unit(&self, unit: &Unit, range: Option<SourceRange>) -> String {
+        if !self.style.canonical_units {
+            let range = range.unwrap_or_else(|| self.unit_source_range(unit));
+            return self.slice(range).trim().to_string();
+        }
+
+        match unit {
+            Unit::Unit(..) => unit.to_string(),
+            Unit::Fraction(numerator, denominator)
+            if matches!(**numerator, Unit::Unit(..)) && matches!(**denominator, Unit::Unit(..)) => unit.to_string(),
+            _ => format!("[{unit}]"),
+        }
+    }
+
+    /// Finds the source range of `unit`, including the square brackets around complex units.
+    fn unit_source_range(&self, unit: &Unit) -> SourceRange {
+        fn collect_ranges(unit: &Unit, ranges: &mut Vec<SourceRange>) {
+            match unit {
+                Unit::Product(units) => units.iter().for_each(|u| collect_ranges(u, ranges)),
+                Unit::Fraction(numerator, denominator) => {
+                    collect_ranges(numerator, ranges);
+                    collect_ranges(denominator, ranges);
+                }
+                Unit::Unit(.., range) => ranges.push(*range),
+            }
+        }
+
+        let mut ranges = vec![];
+        collect_ranges(unit, &mut ranges);
+        let (Some(start), Some(end)) = (
+            ranges.iter().map(|r| r.start_char).min(),
+            ranges.iter().map(|r| r.end_char).max(),
+        ) else { return SourceRange::empty(); };
+
+        let bytes = self.source.as_bytes();
+        let mut start = start;
+        while start > 0 && b" \t([".contains(&bytes[start - 1]) { start -= 1; }
+        let mut end = end;
+        while end < bytes.len() && b" \t)]".contains(&bytes[end]) { end += 1; }
+
+        // Only keep the brackets that belong to the unit
+        let count = |start: usize, end: usize, c: u8| bytes[start..end].iter().filter(|b| **b == c).count();
+        for (open, close) in [(b'(', b')'), (b'[', b']')] {
+            while start < end && count(start, end, open) > count(start, end, close) {
+                while start < end && bytes[start] != open { start += 1; }
+                start += 1;
+            }
+            while start < end && count(start, end, close) > count(start, end, open) {
+                while start < end && bytes[end - 1] != close { end -= 1; }
+                end -= 1;
+            }
+        }
+
+        SourceRange::line(0, start, end)
+    }
+
+    fn slice(&self, range: SourceRange) -> &'a str {
+        self.source.get(range.start_char..range.end_char).unwrap_or_default()
+    }
+}
+
+fn operator_text(operator: Operator) -> &'static str {
+    match operator {
+        Operator::Plus => "+",
+        Operator::Minus => "-",
+        Operator::Multiply => "*",
+        Operator::Divide => "/",
+        Operator::Exponentiation => "^",
+        Operator::BitwiseAnd => "&",
+        Operator::BitwiseOr => "|",
+        Operator::Xor => "xor",
+        Operator::Choose => "choose",
+        Operator::BitShiftLeft => "<<",
+        Operator::BitShiftRight => ">>",
+        Operator::Of => "of",
+        Operator::In => "in",
+        Operator::At => "at",
+        Operator::Modulo => "mod",
+        Operator::Remainder => "rem",
+        Operator::PlusMinus => "±",
+        Operator::Call => "",
+    }
+}
+
+fn operator_of(node: &AstNode) -> Option<Operator> {
+    match node.data {
+        AstNodeData::Operator(operator) => Some(operator),
+        _ => None,
+    }
+}
+
+/// The precedence of `operator` as it is used by the engine. Lower values bind tighter.
+fn precedence(operator: Operator) -> u8 {
+    match operator {
+        Operator::PlusMinus => 0,
+        Operator::Exponentiation | Operator::BitwiseAnd | Operator::BitwiseOr | Operator::Xor | Operator::Choose
+        | Operator::BitShiftLeft | Operator::BitShiftRight | Operator::Modulo
+        | Operator::Remainder => 1,
+        Operator::Multiply | Operator::Divide => 2,
+        Operator::Plus | Operator::Minus => 3,
+        Operator::Of | Operator::In | Operator::At | Operator::Call => 4,
+    }
+}
+
+/// Whether `node` is a group that isn't a function call and has no modifiers, unit or format
+fn is_plain_group(node: &AstNode) -> bool {
+    let AstNodeData::Group(group) = &node.data else { return false; };
+    node.modifiers.is_empty() && node.unit.is_none() && node.format == Format::Decimal
+        && !group.iter().any(|n| n.data == AstNodeData::Operator(Operator::Call))
+}
+
+/// Appends `text` to `result`, separating them with a space if they would otherwise be merged into
+/// a single identifier (e.g. `pi e` instead of `pie`).
+fn push_juxtaposed(result: &mut String, text: &str) {
+    let is_identifier_char = |c: char| c.is_alphabetic() || c == '_';
+    if result.ends_with(is_identifier_char) && text.starts_with(is_identifier_char) {
+        result.push(' ');
+    }
+    *result += text;
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use crate::ContextData;
+    use crate::engine::Value;
+    use crate::environment::Variable;
+
+    use super::*;
+
+    macro_rules! fmt {
+        ($input:expr) => {
+            fmt!($input, FormatStyle::default())
+        };
+        ($input:expr, $style:expr) => {
+            format_line($input, Rc::new(RefCell::new(ContextData::default())), $style, InputMode::Infix, InputMode::Infix)?
+        };
+    }
+
+    macro_rules! convert {
+        ($input:expr, $from:ident -> $to:ident) => {
+            format_line($input, Rc::new(RefCell::new(ContextData::default())), FormatStyle::default(), InputMode::$from, InputMode::$to)
+        };
+    }
+
+    #[test]
+    fn operators() -> Result<()> {
+        assert_eq!(fmt!("3+4 *2"), "3 + 4 * 2");
+        assert_eq!(fmt!("2^3 mod 3"), "2^3 mod 3");
+        assert_eq!(fmt!("(10+(10^2))*2"), "(10 + (10^2)) * 2");
+        assert_eq!(fmt!("20%of 50"), "20% of 50");
+        assert_eq!(fmt!("120+19 %tax"), "120 + 19% tax");
+        assert_eq!(fmt!("- 3 - -2 + !0b101"), "-3 - -2 + !0b101");
+        assert_eq!(fmt!("(5+-1)*2"), "(5 ± 1) * 2");
+        assert_eq!(fmt!("2**3**2"), "2^3^2");
+        assert_eq!(fmt!("5 CHOOSE 2"), "5 choose 2");
+        assert_eq!(fmt!("√16+√(2)"), "√16 + √(2)");
+        Ok(())
+    }
+
+    #[test]
+    fn no_spaces_around_operators() -> Result<()> {
+        let style = FormatStyle { spaces_around_operators: false, ..FormatStyle::default() };
+        assert_eq!(fmt!("3 + 4 * 2", style), "3+4*2");
+        assert_eq!(fmt!("20 % of 50 xor 3", style), "20% of 50 xor 3");
+        Ok(())
+    }
+
+    #[test]
+    fn inferred_multiplication() -> Result<()> {
+        assert_eq!(fmt!("2 pi"), "2pi");
+        assert_eq!(fmt!("2 (3+4)(1+2)"), "2(3 + 4)(1 + 2)");
+        assert_eq!(fmt!("pi  e"), "pi e");
+        assert_eq!(fmt!("2sin( 30 )"), "2sin(30)");
+        Ok(())
+    }
+
+    #[test]
+    fn literals() -> Result<()> {
+        assert_eq!(fmt!("0x00ff + 0b0010"), "0xFF + 0b10");
+        assert_eq!(fmt!("1.50 + 003 + 1e3"), "1.5 + 3 + 1e3");
+        assert_eq!(fmt!("0.50 + 1"), "0.5 + 1");
+        assert_eq!(fmt!("00.5"), "0.5");
+        assert_eq!(fmt!("10.0"), "10");
+        assert_eq!(fmt!("100"), "100");
+        assert_eq!(fmt!("0.0 + .5"), "0 + 0.5");
+        assert_eq!(fmt!("0x00ff", FormatStyle { lowercase_hex: true, ..FormatStyle::default() }), "0xff");
+        assert_eq!(fmt!("0x00ff", FormatStyle { keep_literal_width: true, ..FormatStyle::default() }), "0x00FF");
+        Ok(())
+    }
+
+    #[test]
+    fn units() -> Result<()> {
+        assert_eq!(fmt!("10 km/h in mi/h"), "10km/h in mi/h");
+        assert_eq!(fmt!("3 [ m * s ]"), "3[m*s]");
+        assert_eq!(fmt!("255km in sci mi"), "255km in scientific mi");
+        assert_eq!(fmt!("5mi in km in sci"), "5mi in scientific km");
+        assert_eq!(fmt!("5ft  11in in cm"), "5ft 11inch in cm");
+        assert_eq!(fmt!("180cm in ft_in"), "180cm in ft_in");
+        assert_eq!(fmt!("(1m + 3cm)*2"), "(1m + 3cm) * 2");
+        let style = FormatStyle { canonical_units: false, ..FormatStyle::default() };
+        assert_eq!(fmt!("3 [ m * s ]+2", style), "3[ m * s ] + 2");
+        Ok(())
+    }
+
+    #[test]
+    fn definitions() -> Result<()> {
+        assert_eq!(fmt!("x:=3+4"), "x := 3 + 4");
+        assert_eq!(fmt!("3+4=:x"), "3 + 4 =: x");
+        assert_eq!(fmt!("f(x,y):=x*y"), "f(x, y) := x * y");
+        assert_eq!(fmt!("f(x):=for x<=-2: -2,else: 10"), "f(x) := for x <= -2: -2, else: 10");
+        assert_eq!(fmt!("f(x):=if x<0 then -1 else if x>0 then 1 else 0"), "f(x) := if x < 0 then -1 else if x > 0 then 1 else 0");
+        assert_eq!(fmt!("f(x):=if x≈0 within 1e-3 then 0 else x"), "f(x) := if x ≈ 0 within 1e-3 then 0 else x");
+        assert_eq!(fmt!("f(x,y):="), "f(x, y) :=");
+        Ok(())
+    }
+
+    #[test]
+    fn updates() -> Result<()> {
+        let context = Rc::new(RefCell::new(ContextData::default()));
+        let value = Value::number(1.0, None, false, Format::Decimal);
+        context.borrow_mut().env.set_variable("x", Variable(value)).unwrap();
+
+        let format = |line, to| format_line(line, context.clone(), FormatStyle::default(), InputMode::Infix, to);
+        assert_eq!(format("x+=3*(1+2)", InputMode::Infix)?, "x += 3 * (1 + 2)");
+        assert_eq!(format("x ^= 2", InputMode::Infix)?, "x ^= 2");
+        assert_eq!(format("x-=1+2", InputMode::Rpn)?, "x -= 1 2 +");
+        Ok(())
+    }
+
+    #[test]
+    fn equations() -> Result<()> {
+        assert_eq!(fmt!("20+x?=100.5"), "20 + x? = 100.5");
+        assert_eq!(fmt!("3*2>=5"), "3 * 2 >= 5");
+        assert_eq!(fmt!("1/3~=1 within 1%"), "1 / 3 ≈ 1 within 1%");
+        Ok(())
+    }
+
+    #[test]
+    fn comments() -> Result<()> {
+        assert_eq!(fmt!("20%of 100   # a comment"), "20% of 100 # a comment");
+        assert_eq!(fmt!("  # only a comment"), "# only a comment");
+        Ok(())
+    }
+
+    #[test]
+    fn infix_to_rpn() -> Result<()> {
+        assert_eq!(convert!("(3 + 4) * 2", Infix -> Rpn)?, "3 4 + 2 *");
+        assert_eq!(convert!("1 - 2 + 3 * 4 ^ 2", Infix -> Rpn)?, "1 2 - 3 4 2 ^ * +");
+        assert_eq!(convert!("-(2 + 3)! + sqrt(16) in hex", Infix -> Rpn)?, "2 3 + neg ! 16 sqrt hex +");
+        assert_eq!(convert!("10 km/h in mi/h", Infix -> Rpn)?, "10km/h mi/h in");
+        assert_eq!(convert!("f(x) := x^2 # square", Infix -> Rpn)?, "f(x) := x 2 ^ # square");
+        assert_eq!(convert!("20 + x? = 100", Infix -> Rpn)?, "20 x? + 100 =");
+        assert_eq!(convert!("2^3^2 & 1", Infix -> Rpn)?, "2 3 2 ^ ^ 1 &");
+        assert!(convert!("f(x) := for x > 0: x, else: 0", Infix -> Rpn).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn rpn_to_infix() -> Result<()> {
+        assert_eq!(convert!("3 4 + 2 *", Rpn -> Infix)?, "(3 + 4) * 2");
+        assert_eq!(convert!("1 2 - 3 4 2 ^ * +", Rpn -> Infix)?, "1 - 2 + 3 * 4^2");
+        assert_eq!(convert!("1 2 3 - -", Rpn -> Infix)?, "1 - (2 - 3)");
+        assert_eq!(convert!("2 3 ^ 2 ^", Rpn -> Infix)?, "(2^3)^2");
+        assert_eq!(convert!("2 3 2 ^ ^", Rpn -> Infix)?, "2^3^2");
+        assert_eq!(convert!("5 ! neg 2 m cm in *", Rpn -> Infix)?, "-5! * (2m in cm)");
+        assert_eq!(convert!("x := 2 pi *", Rpn -> Infix)?, "x := 2 * pi");
+        assert_eq!(convert!("3  4 +", Rpn -> Rpn)?, "3 4 +");
+        Ok(())
+    }
+}
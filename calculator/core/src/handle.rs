@@ -0,0 +1,95 @@
+/*
+ * Copyright (c) 2023, david072
+ *
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, Sender};
+use std::sync::{Arc, Mutex};
+
+use crate::{CALCULATION_STACK_SIZE, Calculator, CalculatorResult, LineResult, Settings, Verbosity};
+
+type Job = Box<dyn FnOnce(&mut Calculator) + Send>;
+
+/// A handle to a [Calculator] that lives on its own thread.
+///
+/// The calculator itself can't be shared between threads, since its environment isn't thread-safe.
+/// The handle is `Send` and `Sync` instead and can be cloned, so that multiple threads (e.g. the
+/// connections of a server) calculate in the same environment. Calculations from different threads
+/// are run one after another. The calculator's thread stops once all handles are dropped.
+#[derive(Clone)]
+pub struct CalculatorHandle {
+    jobs: Arc<Mutex<Sender<Job>>>,
+}
+
+impl CalculatorHandle {
+    pub fn new(verbosity: Verbosity, settings: Settings) -> Self {
+        Self::spawn(move || Calculator::new(verbosity, settings))
+    }
+
+    /// Creates the calculator using `create` on a new thread (e.g. to also load the prelude)
+    pub fn spawn(create: impl FnOnce() -> Calculator + Send + 'static) -> Self {
+        let (jobs, receiver) = channel::<Job>();
+        std::thread::Builder::new()
+            .name("calculator".to_owned())
+            .stack_size(CALCULATION_STACK_SIZE)
+            .spawn(move || {
+                let mut calculator = create();
+                for job in receiver {
+                    job(&mut calculator);
+                }
+            })
+            .expect("failed to spawn the calculator thread");
+
+        Self { jobs: Arc::new(Mutex::new(jobs)) }
+    }
+
+    /// Runs `f` with the calculator on its thread and waits for the result.
+    ///
+    /// # Panics
+    /// Panics if the calculator's thread stopped, because an earlier call panicked.
+    pub fn with<R: Send + 'static>(&self, f: impl FnOnce(&mut Calculator) -> R + Send + 'static) -> R {
+        let (result_sender, result) = channel();
+        self.jobs
+            .lock()
+            .unwrap()
+            .send(Box::new(move |calculator| {
+                let _ = result_sender.send(f(calculator));
+            }))
+            .expect("the calculator thread stopped");
+        result.recv().expect("the calculator thread stopped")
+    }
+
+    /// Like [Calculator::calculate], but callable from any thread
+    pub fn calculate(&self, input: &str) -> Vec<CalculatorResult> {
+        let input = input.to_owned();
+        self.with(move |calculator| calculator.calculate(&input))
+    }
+
+    /// Like [Calculator::calculate_all], but returns the results of all lines at once
+    pub fn calculate_all(&self, source: &str) -> Vec<LineResult> {
+        let source = source.to_owned();
+        self.with(move |calculator| {
+            let mut results = Vec::new();
+            calculator.calculate_all(&source, |line| results.push(line));
+            results
+        })
+    }
+}
+
+/// Cancels calculations running on another thread (e.g. when their input changed in the meantime).
+/// Clones of a token share their state, so the thread that started a calculation can keep a clone
+/// and cancel it. Cancelled calculations fail with an error.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
@@ -5,45 +5,106 @@
  */
 
 use std::cell::RefCell;
+use std::collections::HashMap;
 use std::fmt::Write;
-use std::ops::Range;
+use std::ops::{Range, RangeInclusive};
 use std::rc::Rc;
 use std::str::FromStr;
 use std::sync::Arc;
 
 use astgen::{
     parser::Parser,
-    tokenizer::{tokenize, TokenType},
+    tokenizer::{tokenize, Token, TokenType},
 };
+pub use astgen::tokenizer::{TokenCache, TokenKind};
+pub use capabilities::{Capabilities, Capability};
 pub use color::{Color, ColorSegment};
-pub use common::Result;
+pub use constant_cache::ConstantCache;
+use common::ErrorType;
+pub use common::{Error, Result, Warning};
 pub use common::{data_dir, SourceRange};
+pub use dependencies::{Dependency, DependencyGraph, DependencyKind};
+pub use document::{Document, DocumentCache, DocumentLine, DocumentLineKind};
+pub use handle::{CalculatorHandle, CancellationToken};
+pub use paste::{extract_values, paste_values, PasteMode, PastedValue};
+pub use postprocessor::{Postprocessor, Postprocessors};
+pub use profiling::LineTiming;
+pub use references::{identifier_references, IdentifierReference};
 use engine::Engine;
 pub use environment::{currencies::Currencies, Variable};
-pub use environment::{Environment, Function};
+use astgen::ast::{AstNode, AstNodeData, Operator};
+pub use astgen::ast::BooleanOperator;
+pub use environment::analysis::{Extremum, ExtremumKind, FunctionEvaluator, Relation, RelationEvaluator, RELATION_VARIABLES};
+pub use environment::constants::{Constant, ConstantCategory, CONSTANTS};
+pub use environment::dimensions::COMMON_UNITS;
+pub use environment::{Environment, Function, FunctionHelp, FunctionKind, FunctionSignature};
+pub use prelude::{PreludeError, PRELUDE_DIR};
+#[cfg(feature = "scripting")]
+pub use environment::scripts::ScriptError;
 
 use crate::astgen::parser::{ParserResult, ParserResultData};
-pub use crate::engine::Format;
+pub use crate::engine::{CALCULATION_STACK_SIZE, Format};
 pub use crate::engine::NumberValue;
 pub use crate::engine::Value;
-use crate::environment::units::is_unit_with_prefix;
 use crate::environment::FunctionVariantType;
+use crate::environment::dimensions::common_units;
+use crate::environment::units::convert as convert_units;
+use crate::profiling::Stopwatch;
 pub use crate::settings::*;
 
 mod astgen;
+#[doc(hidden)]
+pub mod bench;
+mod capabilities;
 mod color;
 mod common;
+mod constant_cache;
+mod dependencies;
+mod document;
 mod engine;
 mod environment;
+mod formatter;
+mod handle;
+mod paste;
+mod postprocessor;
+mod prelude;
+mod profiling;
+mod references;
 mod settings;
 
 const CRASH_REPORTS_DIR: &str = "crash_reports";
 
-#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+#[derive(PartialEq, Eq, Debug, Clone, Copy, serde::Serialize, serde::Deserialize)]
 pub enum Verbosity {
     None,
     Tokens,
     Ast,
+    /// The AST with the type and the resolved unit of every node
+    TypedAst,
+    /// The tokens and the AST as JSON, for tools built around the parser
+    Json,
+}
+
+impl Verbosity {
+    pub const ALL: [Verbosity; 5] = [
+        Verbosity::None,
+        Verbosity::Tokens,
+        Verbosity::Ast,
+        Verbosity::TypedAst,
+        Verbosity::Json,
+    ];
+}
+
+impl std::fmt::Display for Verbosity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", match self {
+            Verbosity::None => "none",
+            Verbosity::Tokens => "tokens",
+            Verbosity::Ast => "ast",
+            Verbosity::TypedAst => "typed_ast",
+            Verbosity::Json => "json",
+        })
+    }
 }
 
 impl FromStr for Verbosity {
@@ -53,17 +114,45 @@ impl FromStr for Verbosity {
         Ok(match s {
             "tokens" => Verbosity::Tokens,
             "ast" => Verbosity::Ast,
+            "typed_ast" => Verbosity::TypedAst,
+            "json" => Verbosity::Json,
             _ => Verbosity::None,
         })
     }
 }
 
+/// How far the left-hand side of a failed equality check is away from the right-hand side
+#[derive(Debug, Clone)]
+pub struct Difference {
+    /// The left-hand side minus the right-hand side, in the unit of the left-hand side
+    pub absolute: Value,
+    /// The difference relative to the right-hand side, unless it is zero
+    pub relative: Option<f64>,
+}
+
+impl Difference {
+    /// Formats the difference like `Δ = 0.02, 0.4%`
+    pub fn format(&self, settings: &Settings, use_thousands_separator: bool) -> String {
+        let mut result = format!("Δ = {}", self.absolute.format(settings, use_thousands_separator).trim());
+        if let Some(relative) = self.relative {
+            let percent = Format::Decimal.format(relative * 100.0, &settings.output, use_thousands_separator);
+            write!(result, ", {percent}%").unwrap();
+        }
+        result
+    }
+}
+
 /// A struct containing information about the calculated result
 #[derive(Debug, Clone)]
 pub enum ResultData {
     Nothing,
     Value(Value),
-    Boolean(bool),
+    /// The result of an equality check or inequality. For equality checks of numbers that don't
+    /// hold, `difference` tells how far apart the sides are.
+    Boolean {
+        value: bool,
+        difference: Option<Difference>,
+    },
     /// `name`, `argument count`
     Function {
         name: String,
@@ -71,12 +160,111 @@ pub enum ResultData {
         function: Function,
     },
     FunctionRemoval(String),
+    /// A variable defined as `name := slider(min, max, step)`, whose `value` can be changed using
+    /// [Calculator::set_slider_value]
+    Slider {
+        name: String,
+        value: Value,
+        min: f64,
+        max: f64,
+        step: f64,
+    },
 }
 
 #[derive(Debug)]
 pub struct CalculatorResult {
     pub data: Result<(ResultData, Range<usize>)>,
+    /// The errors in the calculation after the one in `data`. The parser recovers from some errors
+    /// (e.g. unknown identifiers or errors in arguments) to find all errors in a line at once.
+    pub additional_errors: Vec<Error>,
+    /// Problems that didn't stop the calculation (e.g. implicit unit conversions)
+    pub warnings: Vec<Warning>,
     pub color_segments: Vec<ColorSegment>,
+    /// Text the [Postprocessor]s added to the result (e.g. CO₂ equivalents), which frontends show
+    /// together with it
+    pub notes: Vec<String>,
+    /// Whether the calculation ends with `;` or `@ignore`. Frontends hide the results of these
+    /// calculations, but still show their errors.
+    pub suppressed: bool,
+    /// The part of the source the calculation was made of, so that frontends can refer to it
+    /// without parsing it again (e.g. to add a conversion to it). Empty if parsing failed.
+    pub source_range: SourceRange,
+}
+
+impl CalculatorResult {
+    fn new(data: Result<(ResultData, Range<usize>)>, color_segments: Vec<ColorSegment>) -> Self {
+        Self {
+            data,
+            additional_errors: vec![],
+            warnings: vec![],
+            color_segments,
+            notes: vec![],
+            suppressed: false,
+            source_range: SourceRange::empty(),
+        }
+    }
+
+    /// All errors in the calculation, sorted by their position
+    pub fn errors(&self) -> Vec<&Error> {
+        self.data.as_ref().err().into_iter().chain(&self.additional_errors).collect()
+    }
+}
+
+/// The result of a single calculation, which is passed to the callback of
+/// [Calculator::calculate_all]
+#[derive(Debug)]
+pub struct LineResult {
+    /// The lines of the source the calculation spans
+    pub line_range: Range<usize>,
+    pub result: CalculatorResult,
+    /// How long the calculation took, if [Calculator::profiling] is enabled
+    pub timing: Option<LineTiming>,
+}
+
+impl LineResult {
+    fn new(result: CalculatorResult) -> Self {
+        let line_range = match &result.data {
+            Ok((_, range)) => range.clone(),
+            Err(_) => {
+                let lines = result.errors().into_iter()
+                    .flat_map(|e| &e.ranges)
+                    .flat_map(|r| [r.start_line, r.end_line]);
+                let start = lines.clone().min().unwrap_or_default();
+                let end = lines.max().unwrap_or_default();
+                start..end
+            }
+        };
+        Self { line_range, result, timing: None }
+    }
+}
+
+/// Removes the `;`s and `@ignore`s at the ends of lines, which hide the results of their
+/// calculations, from `tokens`. Semicolons in brackets (e.g. of vectors spanning multiple lines)
+/// are kept.
+///
+/// **Returns:** The removed tokens together with the index of the token that followed them
+fn take_suppressions(tokens: &mut Vec<Token>) -> Vec<(usize, Token)> {
+    let mut suppressions = vec![];
+    let mut remaining = Vec::with_capacity(tokens.len());
+    let mut depth = 0usize;
+    let mut tokens_iter = std::mem::take(tokens).into_iter().peekable();
+    while let Some(token) = tokens_iter.next() {
+        match token.ty {
+            TokenType::OpenBracket | TokenType::OpenSquareBracket | TokenType::OpenCurlyBracket => depth += 1,
+            TokenType::CloseBracket | TokenType::CloseSquareBracket | TokenType::CloseCurlyBracket => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+
+        let is_line_end = tokens_iter.peek().map_or(true, |next| next.ty == TokenType::Newline);
+        let is_suppression = token.ty == TokenType::Ignore || (token.ty == TokenType::Semicolon && depth == 0);
+        if is_suppression && is_line_end {
+            suppressions.push((remaining.len(), token));
+        } else {
+            remaining.push(token);
+        }
+    }
+    *tokens = remaining;
+    suppressions
 }
 
 pub fn colorize_text(input: &str) -> Option<Vec<ColorSegment>> {
@@ -91,6 +279,25 @@ pub struct ContextData {
     pub env: Environment,
     pub currencies: Arc<Currencies>,
     pub settings: Settings,
+    pub capabilities: Capabilities,
+    /// Stops the calculations in this context when cancelled from another thread
+    pub cancellation: CancellationToken,
+    /// The results of calculations that don't depend on the environment
+    pub constant_cache: ConstantCache,
+}
+
+/// A context with an empty environment and without any exchange rates (e.g. for tests)
+impl Default for ContextData {
+    fn default() -> Self {
+        ContextData {
+            env: Environment::new(),
+            currencies: Arc::new(Currencies::none()),
+            settings: Settings::default(),
+            capabilities: Capabilities::default(),
+            cancellation: CancellationToken::default(),
+            constant_cache: ConstantCache::default(),
+        }
+    }
 }
 
 pub type Context = Rc<RefCell<ContextData>>;
@@ -98,6 +305,18 @@ pub type Context = Rc<RefCell<ContextData>>;
 pub struct Calculator {
     pub context: Context,
     pub verbosity: Verbosity,
+    /// The tokens of the lines calculated last, which are reused by [Calculator::calculate_all]
+    pub token_cache: TokenCache,
+    /// The environment [Calculator::reset] goes back to, containing the definitions of the prelude
+    pub base_env: Environment,
+    /// The values the sliders were set to, by the name of their variable. They are kept across
+    /// [Calculator::reset], so that calculating again doesn't move the sliders back.
+    pub slider_values: HashMap<String, f64>,
+    /// Whether [Calculator::calculate_all] records how long the calculations took (see
+    /// [LineResult::timing])
+    pub profiling: bool,
+    /// The hooks that are called with every result (see [Calculator::add_postprocessor])
+    pub postprocessors: Postprocessors,
 }
 
 impl Default for Calculator {
@@ -109,8 +328,16 @@ impl Default for Calculator {
                 env: Environment::new(),
                 currencies: Currencies::new_with_update(),
                 settings: Settings::default(),
+                capabilities: Capabilities::default(),
+                cancellation: CancellationToken::default(),
+                constant_cache: ConstantCache::default(),
             })),
             verbosity: Verbosity::None,
+            token_cache: TokenCache::default(),
+            base_env: Environment::new(),
+            slider_values: HashMap::new(),
+            profiling: false,
+            postprocessors: Postprocessors::default(),
         }
     }
 }
@@ -120,16 +347,44 @@ impl Calculator {
         Currencies::update();
     }
 
+    /// Splits `input` into tokens without parsing or evaluating it, e.g. for syntax highlighting
+    /// in editors. Invalid characters don't stop the tokenization, but are returned as
+    /// [TokenKind::Invalid]. The ranges are byte offsets into `input`.
+    pub fn tokenize_with_kinds(input: &str) -> Vec<(Range<usize>, TokenKind)> {
+        astgen::tokenizer::tokenize_with_kinds(input)
+    }
+
     pub fn new(verbosity: Verbosity, settings: Settings) -> Calculator {
-        Calculator::set_panic_hook();
+        Calculator::with_capabilities(verbosity, settings, Capabilities::default())
+    }
+
+    /// Creates a calculator that only uses the given `capabilities`. Without network access, the
+    /// currency exchange rates are not updated and without filesystem access, neither the cached
+    /// exchange rates nor crash reports are used.
+    pub fn with_capabilities(verbosity: Verbosity, settings: Settings, capabilities: Capabilities) -> Calculator {
+        if capabilities.filesystem { Calculator::set_panic_hook(); }
+
+        let currencies = match (capabilities.network, capabilities.filesystem) {
+            (true, true) => Currencies::new_with_update(),
+            (false, true) => Arc::new(Currencies::new_load_only()),
+            _ => Arc::new(Currencies::none()),
+        };
 
         Calculator {
             context: Rc::new(RefCell::new(ContextData {
                 env: Environment::new(),
-                currencies: Currencies::new_with_update(),
+                currencies,
                 settings,
+                capabilities,
+                cancellation: CancellationToken::default(),
+                constant_cache: ConstantCache::default(),
             })),
             verbosity,
+            token_cache: TokenCache::default(),
+            base_env: Environment::new(),
+            slider_values: HashMap::new(),
+            profiling: false,
+            postprocessors: Postprocessors::default(),
         }
     }
 
@@ -160,8 +415,61 @@ impl Calculator {
         }));
     }
 
+    /// Creates a new calculator with an empty environment (apart from the definitions of the
+    /// prelude), which uses the same currencies and settings as this one.
+    pub fn with_empty_environment(&self) -> Calculator {
+        let context = self.context.borrow();
+        Calculator {
+            context: Rc::new(RefCell::new(ContextData {
+                env: self.base_env.clone(),
+                currencies: context.currencies.clone(),
+                settings: context.settings,
+                capabilities: context.capabilities,
+                cancellation: context.cancellation.clone(),
+                constant_cache: ConstantCache::default(),
+            })),
+            verbosity: self.verbosity,
+            token_cache: TokenCache::default(),
+            base_env: self.base_env.clone(),
+            slider_values: self.slider_values.clone(),
+            profiling: self.profiling,
+            postprocessors: self.postprocessors.clone(),
+        }
+    }
+
+    /// Loads the prelude from [PRELUDE_DIR] in the [data_dir]. Does nothing if the calculator
+    /// isn't allowed to access the filesystem.
+    pub fn load_prelude(&mut self) -> Vec<PreludeError> {
+        if !self.context.borrow().capabilities.filesystem { return vec![]; }
+        self.load_prelude_from(&data_dir().join(PRELUDE_DIR))
+    }
+
+    /// Calculates the files in `dir` (in the order of their names) and makes the variables and
+    /// functions they define available to everything calculated afterwards, even after
+    /// [Calculator::reset]. This replaces a previously loaded prelude.
+    pub fn load_prelude_from(&mut self, dir: &std::path::Path) -> Vec<PreludeError> {
+        self.base_env = Environment::new();
+        self.reset();
+        let errors = prelude::load(self, dir);
+
+        let mut env = self.clone_env();
+        env.clear_results();
+        self.base_env = env;
+        self.reset();
+        errors
+    }
+
     pub fn reset(&mut self) {
-        self.context.borrow_mut().env.clear();
+        self.context.borrow_mut().env = self.base_env.clone();
+    }
+
+    /// Makes the functions defined in the [Rhai](https://rhai.rs) script `source` available to
+    /// all following calculations, even after [Calculator::reset]. Loading the prelude replaces
+    /// them.
+    #[cfg(feature = "scripting")]
+    pub fn add_script(&mut self, source: &str) -> std::result::Result<(), ScriptError> {
+        self.base_env.add_script(source)?;
+        self.context.borrow_mut().env.add_script(source)
     }
 
     pub fn clone_env(&self) -> Environment {
@@ -172,36 +480,241 @@ impl Calculator {
         self.context.clone()
     }
 
+    /// Calculates the single argument function `f` in the current environment for `samples`
+    /// evenly spaced arguments in `range` (see [Environment::sample_function])
+    pub fn sample_function(&self, f: &Function, range: RangeInclusive<f64>, samples: usize) -> Vec<(f64, f64)> {
+        self.context.borrow().env.sample_function(f, range, samples, self.context())
+    }
+
+    /// Returns an evaluator for the single argument function `f` in the current environment, which
+    /// can e.g. find its roots. Returns [None] if `f` doesn't take exactly one argument.
+    pub fn function_evaluator<'a>(&self, f: &'a Function) -> Option<FunctionEvaluator<'a>> {
+        FunctionEvaluator::new(f, &self.context.borrow().env, &self.context)
+    }
+
+    /// Parses `line` as a [Relation] between `x` and `y` (even if they are not defined). Returns
+    /// [None] if the line isn't an equality check or inequality mentioning them.
+    pub fn relation(&self, line: &str) -> Option<Relation> {
+        let tokens = tokenize(line).ok()?;
+        let mentions_variables = tokens.iter()
+            .any(|token| token.ty == TokenType::Identifier && RELATION_VARIABLES.contains(&token.text.as_str()));
+        if !mentions_variables { return None; }
+
+        let mut parser = Parser::from_tokens(&tokens, self.context());
+        parser.set_extra_allowed_variables(RELATION_VARIABLES.map(String::from).to_vec());
+        match parser.parse_single().ok()?.data {
+            ParserResultData::BooleanExpression { lhs, rhs, operator, .. } => Some(Relation { lhs, rhs, operator }),
+            _ => None,
+        }
+    }
+
+    /// Returns an evaluator for `relation` in the current environment
+    pub fn relation_evaluator<'a>(&self, relation: &'a Relation) -> RelationEvaluator<'a> {
+        RelationEvaluator::new(relation, &self.context.borrow().env, &self.context)
+    }
+
+    /// Suggests up to `count` common units that `number` can be converted into (e.g. `mi`, `m` and
+    /// `ft` for `5 km`). Units in which the number is easy to read (from 1 to 1000) come first.
+    pub fn suggest_conversions(&self, number: &NumberValue, count: usize) -> Vec<String> {
+        let Some(unit) = &number.unit else { return vec![]; };
+        let context = self.context.borrow();
+        let data_prefixes = context.settings.data_prefixes;
+
+        // How many orders of magnitude the converted number is away from being easy to read
+        let mut suggestions = common_units(unit).into_iter()
+            .filter_map(|target| {
+                let n = convert_units(unit, &target, number.number, &context.currencies, data_prefixes, SourceRange::empty()).ok()?;
+                let magnitude = n.abs().log10();
+                let distance = if magnitude.is_finite() { (-magnitude).max(magnitude - 3.0).max(0.0) } else { 0.0 };
+                Some((target.format(false, false), distance))
+            })
+            .collect::<Vec<_>>();
+        suggestions.sort_by(|(_, a), (_, b)| a.total_cmp(b));
+        suggestions.into_iter().take(count).map(|(unit, _)| unit).collect()
+    }
+
+    /// Sets the value of the slider assigned to the variable `name`. It's used (clamped to the
+    /// slider's range and rounded to its step) from the next calculation on.
+    pub fn set_slider_value(&mut self, name: &str, value: f64) {
+        self.slider_values.insert(name.to_owned(), value);
+    }
+
+    /// Installs `postprocessor`, which is called with every successful result after the ones
+    /// installed before it (see [Postprocessor])
+    pub fn add_postprocessor(&mut self, postprocessor: impl Postprocessor + Send + 'static) {
+        self.postprocessors.add(postprocessor);
+    }
+
     pub fn calculate(&mut self, input: &str) -> Vec<CalculatorResult> {
-        let tokens = match tokenize(input) {
+        let mut results = vec![];
+        self.calculate_all(input, |line| results.push(line.result));
+        results
+    }
+
+    /// Calculates `source` and calls `on_line` with the result of every calculation as soon as it
+    /// is available, so that frontends can show the results progressively.
+    pub fn calculate_all(&mut self, source: &str, mut on_line: impl FnMut(LineResult)) {
+        let tokens = match self.token_cache.tokenize(source) {
             Ok(v) => v,
             Err(e) => {
-                return vec![CalculatorResult {
-                    data: Err(e),
-                    color_segments: vec![],
-                }]
+                let mut line = LineResult::new(CalculatorResult::new(Err(e), vec![]));
+                if self.profiling {
+                    let tokenization = self.token_cache.line_durations.iter().sum();
+                    line.timing = Some(LineTiming { tokenization, ..LineTiming::default() });
+                }
+                on_line(line);
+                return;
             }
         };
 
-        let mut results = vec![];
+        self.calculate_tokens(tokens, on_line);
+    }
+
+    /// Calculates `inputs` independently of each other, i.e. every input is calculated in the
+    /// environment the calculator had before, and returns the result of the last calculation of
+    /// every input. This is faster than calling [Calculator::calculate] for every input, since the
+    /// inputs don't go through the [TokenCache] and the environment only has to be restored after
+    /// inputs that changed it (e.g. by defining a variable).
+    ///
+    /// Inputs that can't be tokenized result in an error, since nothing of them was calculated.
+    pub fn evaluate_batch(&mut self, inputs: &[&str]) -> Vec<Result<CalculatorResult>> {
+        let snapshot = self.clone_env();
+        let mut results = Vec::with_capacity(inputs.len());
+        for input in inputs {
+            results.push(tokenize(input).map(|tokens| {
+                let mut result = None;
+                self.calculate_tokens(tokens, |line| result = Some(line.result));
+                result.unwrap_or_else(|| CalculatorResult::new(Ok((ResultData::Nothing, 0..1)), vec![]))
+            }));
+
+            let env = &mut self.context.borrow_mut().env;
+            if env.revision == snapshot.revision {
+                env.ans = snapshot.ans.clone();
+            } else {
+                *env = snapshot.clone();
+            }
+        }
+        results
+    }
+
+    /// Calculates the tokens of a source (see [Calculator::calculate_all])
+    fn calculate_tokens(&mut self, mut tokens: Vec<Token>, mut on_line: impl FnMut(LineResult)) {
+        self.context.borrow_mut().env.line_results.clear();
+
+        let suppressions = take_suppressions(&mut tokens);
         let mut parser = Parser::from_tokens(&tokens, self.context());
-        while let Some(parser_result) = parser.next() {
-            match parser_result {
+        loop {
+            // The parser can already report warnings for the line (e.g. when a variable shadows a
+            // constant)
+            self.context.borrow_mut().env.warnings.clear();
+            self.context.borrow_mut().env.breakdown.clear();
+            let stopwatch = Stopwatch::start();
+            let Some(parser_result) = parser.next() else { break; };
+            let parsing = stopwatch.elapsed();
+
+            let stopwatch = Stopwatch::start();
+            let result = match parser_result {
                 Ok(v) => {
-                    let color_segments = ColorSegment::all(&tokens[v.token_range.clone()]);
-                    results.push(CalculatorResult {
-                        data: self.handle_parser_result(v),
-                        color_segments,
-                    });
+                    let suppression = suppressions.iter().find(|(i, _)| *i == v.token_range.end);
+                    let mut color_segments = ColorSegment::all(&tokens[v.token_range.clone()]);
+                    if let Some((_, token)) = suppression {
+                        color_segments.extend(ColorSegment::all(std::slice::from_ref(token)));
+                    }
+                    let source_range = match (tokens.get(v.token_range.start), tokens[..v.token_range.end].last()) {
+                        (Some(first), Some(last)) if !v.token_range.is_empty() => first.range.extend(last.range),
+                        _ => SourceRange::empty(),
+                    };
+                    let has_format = tokens[v.token_range.clone()].iter().any(|token| token.ty.is_format());
+                    let auto_prefix = self.uses_auto_prefix(&tokens[v.token_range.clone()]);
+                    let mut data = self.check_environment_capability(&v, &tokens)
+                        .and_then(|_| self.handle_parser_result(v));
+                    if !has_format { self.apply_default_format(&mut data); }
+                    if auto_prefix { Self::apply_auto_prefix(&mut data); }
+                    if let Ok((ResultData::Value(value) | ResultData::Slider { value, .. }, line_range)) = &data {
+                        self.context.borrow_mut().env.set_line_result(line_range.start, Variable(value.clone()));
+                    }
+                    // After setting the line result, so that only the shown result is changed
+                    let notes = match &mut data {
+                        Ok((data, line_range)) => self.postprocessors.process(data, line_range),
+                        Err(_) => vec![],
+                    };
+
+                    let warnings = std::mem::take(&mut self.context.borrow_mut().env.warnings);
+                    CalculatorResult {
+                        // Warnings don't matter if the calculation failed anyway
+                        warnings: if data.is_ok() { warnings } else { vec![] },
+                        notes,
+                        suppressed: suppression.is_some(),
+                        source_range,
+                        ..CalculatorResult::new(data, color_segments)
+                    }
                 }
-                Err(e) => results.push(CalculatorResult {
-                    data: Err(e),
-                    color_segments: vec![],
-                }),
+                Err(mut errors) => CalculatorResult {
+                    additional_errors: errors.split_off(1),
+                    ..CalculatorResult::new(Err(errors.remove(0)), vec![])
+                },
+            };
+            let evaluation = stopwatch.elapsed();
+
+            let mut line = LineResult::new(result);
+            if self.profiling {
+                let lines = line.line_range.start..=line.line_range.end;
+                let tokenization = self.token_cache.line_durations.get(lines).unwrap_or_default().iter().sum();
+                line.timing = Some(LineTiming { tokenization, parsing, evaluation });
             }
+            on_line(line);
+            // The following lines would only fail as well
+            if self.context.borrow().cancellation.is_cancelled() { break; }
         }
+    }
 
-        results
+    /// Shows decimal results in the default format of the settings. Only used for lines without
+    /// an explicit format (e.g. `in hex`).
+    fn apply_default_format(&self, data: &mut Result<(ResultData, Range<usize>)>) {
+        let Ok((ResultData::Value(Value::Number(number)), _)) = data else { return; };
+        if number.format == Format::Decimal {
+            number.format = self.context.borrow().settings.output.default_format;
+        }
+    }
+
+    /// Whether the result of the line should get a prefix picked automatically, either because of
+    /// an explicit `in auto` or because of the settings. The setting doesn't apply to lines that
+    /// are converted into a specific unit.
+    fn uses_auto_prefix(&self, tokens: &[Token]) -> bool {
+        let mut has_conversion = false;
+        for (i, _) in tokens.iter().enumerate().filter(|(_, token)| token.ty == TokenType::In) {
+            if tokens.get(i + 1).map_or(false, |token| token.ty == TokenType::Identifier && token.text == "auto") {
+                return true;
+            }
+            has_conversion = true;
+        }
+        !has_conversion && self.context.borrow().settings.output.auto_prefix
+    }
+
+    fn apply_auto_prefix(data: &mut Result<(ResultData, Range<usize>)>) {
+        let Ok((ResultData::Value(Value::Number(number)), _)) = data else { return; };
+        *number = number.clone().with_auto_prefix();
+    }
+
+    /// Returns [ErrorType::MissingCapability] for lines that define or remove variables or
+    /// functions if the calculator isn't allowed to change the environment
+    fn check_environment_capability(&self, parser_result: &ParserResult, tokens: &[Token]) -> Result<()> {
+        let changes_environment = matches!(
+            parser_result.data,
+            ParserResultData::VariableDefinition(..) |
+            ParserResultData::FunctionDefinition { .. } |
+            ParserResultData::Equation { output_variable: Some(_), .. }
+        );
+        if !changes_environment { return Ok(()); }
+
+        self.context.borrow().capabilities.require(Capability::Environment).map_err(|ty| {
+            let tokens = &tokens[parser_result.token_range.clone()];
+            let range = match (tokens.first(), tokens.last()) {
+                (Some(first), Some(last)) => first.range.extend(last.range),
+                _ => SourceRange::empty(),
+            };
+            ty.with(range)
+        })
     }
 
     fn handle_parser_result(
@@ -219,6 +732,13 @@ impl Calculator {
                 }
 
                 let result = Engine::evaluate(ast, self.context())?;
+                if self.verbosity == Verbosity::Ast && !self.context.borrow().env.breakdown.is_empty() {
+                    println!("Breakdown:");
+                    for step in &self.context.borrow().env.breakdown {
+                        println!("{step}");
+                    }
+                    println!();
+                }
                 self.context
                     .borrow_mut()
                     .env
@@ -226,7 +746,7 @@ impl Calculator {
 
                 ResultData::Value(result)
             }
-            ParserResultData::BooleanExpression { lhs, rhs, operator } => {
+            ParserResultData::BooleanExpression { lhs, rhs, operator, tolerance } => {
                 if self.verbosity == Verbosity::Ast {
                     println!("Equality check:\nLHS:");
                     for node in &lhs {
@@ -241,14 +761,19 @@ impl Calculator {
 
                 let lhs = Engine::evaluate(lhs, self.context())?;
                 let rhs = Engine::evaluate(rhs, self.context())?;
-                ResultData::Boolean(Engine::check_boolean_operator(
-                    &lhs,
-                    &rhs,
-                    operator,
-                    &self.context.borrow().currencies,
-                ))
+                let tolerance = Engine::tolerance(&lhs, tolerance, self.context())?;
+                let context = self.context.borrow();
+                let data_prefixes = context.settings.data_prefixes;
+                let value = Engine::check_boolean_operator(&lhs, &rhs, operator, &tolerance, &context.currencies, data_prefixes);
+                let difference = if !value && matches!(operator, BooleanOperator::Equal | BooleanOperator::ApproximatelyEqual) {
+                    Engine::difference(&lhs, &rhs, &context.currencies, data_prefixes)
+                } else {
+                    None
+                };
+                ResultData::Boolean { value, difference }
             }
             ParserResultData::VariableDefinition(name, ast) => match ast {
+                Some(ast) if slider_arguments(&ast).is_some() => self.define_slider(name, &ast)?,
                 Some(ast) => {
                     let res = Engine::evaluate(ast, self.context())?;
                     self.context
@@ -340,175 +865,52 @@ impl Calculator {
         Ok((result_data, parser_result.line_range))
     }
 
-    pub fn format(&self, line: &str) -> Result<String> {
-        use TokenType::*;
-
-        let tokens = tokenize(line)?;
-
-        let mut is_in_unit = false;
-        let mut is_in_object = false;
-
-        let mut new_line = String::new();
-        for (i, token) in tokens.iter().enumerate() {
-            let text = &token.text;
-
-            if token.ty.is_number()
-                || matches!(token.ty, ExclamationMark | PercentSign | QuestionMark)
-            {
-                let mut text = text.to_owned();
-                if token.ty == DecimalLiteral {
-                    text = if text.contains('.') {
-                        text.trim_matches('0').to_owned()
-                    } else if text.len() > 1 {
-                        text.trim_start_matches('0').to_owned()
-                    } else {
-                        text
-                    };
-
-                    if text.len() == 1 && text == "." {
-                        text = "0.0".to_owned();
-                    } else if text.ends_with('.') {
-                        text.remove(text.len() - 1);
-                    }
-                } else if token.ty == HexLiteral || token.ty == BinaryLiteral {
-                    text = text[2..].trim_start_matches('0').to_owned();
-                    text.insert(0, if token.ty == HexLiteral { 'x' } else { 'b' });
-                    text.insert(0, '0');
-                    if text.len() == 2 {
-                        text.push('0');
-                    }
-                    if token.ty == HexLiteral {
-                        // Uppercase letters in hex numbers
-                        text.replace_range(
-                            2..,
-                            text[2..]
-                                .chars()
-                                .flat_map(|c| {
-                                    if !c.is_numeric() {
-                                        c.to_uppercase().collect::<Vec<char>>()
-                                    } else {
-                                        vec![c]
-                                    }
-                                })
-                                .collect::<String>()
-                                .as_str(),
-                        );
-                    }
-                }
-
-                if i != 0 && token.ty == Identifier {
-                    if let Some(previous) = tokens.get(i - 1) {
-                        if previous.ty == Identifier {
-                            new_line.push(' ');
-                        }
-                    }
-                }
-
-                if (i != 0
-                    && token.ty.is_literal()
-                    && tokens[i - 1].ty == Identifier
-                    && !matches!(tokens[i - 1].text.as_str(), "e" | "E"))
-                    || (token.ty == OpenSquareBracket
-                        && i != 0
-                        && tokens[i - 1].ty != ObjectArgs
-                        && is_in_object)
-                {
-                    new_line.push(' ');
-                }
-
-                if token.ty == OpenSquareBracket && i != 0 && !tokens[i - 1].ty.is_operator() {
-                    is_in_unit = true;
-                } else if token.ty == CloseSquareBracket {
-                    is_in_unit = false;
-                }
-
-                if token.ty == OpenCurlyBracket {
-                    is_in_object = true;
-                } else if token.ty == CloseCurlyBracket {
-                    is_in_object = false;
-                }
+    /// Defines the variable `name` as the slider `ast` (see [slider_arguments]). Its value is the
+    /// one set using [Calculator::set_slider_value] or the slider's minimum.
+    fn define_slider(&mut self, name: String, ast: &[AstNode]) -> Result<ResultData> {
+        let range = ast[0].range;
+        let arguments = slider_arguments(ast).unwrap();
+        let min = Engine::evaluate_to_number(arguments[0].clone(), self.context())?;
+        let max = Engine::evaluate_to_number(arguments[1].clone(), self.context())?.number;
+        let step = Engine::evaluate_to_number(arguments[2].clone(), self.context())?.number;
+        if min.number >= max {
+            return Err(ErrorType::Arg1GreaterThanArg2.with(range));
+        }
+        if step <= 0.0 || !step.is_finite() {
+            return Err(ErrorType::InvalidSliderStep.with(range));
+        }
 
-                new_line += &text;
-            } else if token.ty.is_operator()
-                || token.ty.is_boolean_operator()
-                || token.ty.is_format()
-                || token.ty == DefinitionSign
-                || token.ty == PostfixDefinitionSign
-            {
-                if token.ty == Plus || token.ty == Minus {
-                    if i == 0 {
-                        new_line += text;
-                        continue;
-                    } else if tokens[i - 1].ty.is_operator()
-                        || matches!(
-                            tokens[i - 1].ty,
-                            OpenBracket | Comma | DefinitionSign | EqualsSign | Identifier
-                        )
-                    {
-                        // Check if we're a sign
-                        // Check if we're a sign after the 'e' of scientific notation
-                        if tokens[i - 1].ty != Identifier
-                            || (tokens[i - 1].text == "e" || tokens[i - 1].text == "E")
-                        {
-                            if let Some(next) = tokens.get(i + 1) {
-                                if next.ty.is_number() {
-                                    new_line += text;
-                                    continue;
-                                }
-                            }
-                        }
-                    }
-                }
-                // Format complex units without spaces (e.g. "km/h")
-                else if token.ty == Divide || token.ty == Multiply {
-                    let is_prev_ident_and_unit = tokens
-                        .get(i - 1)
-                        .map(|t| t.ty == Identifier && is_unit_with_prefix(&t.text))
-                        .unwrap_or(false);
-                    let is_next_ident_and_unit = tokens
-                        .get(i + 1)
-                        .map(|t| t.ty == Identifier && is_unit_with_prefix(&t.text))
-                        .unwrap_or(false);
-                    if is_prev_ident_and_unit && is_next_ident_and_unit {
-                        new_line += text;
-                        continue;
-                    }
-                }
+        let value = self.slider_values.get(&name).copied().unwrap_or(min.number).clamp(min.number, max);
+        let value = (min.number + ((value - min.number) / step).round() * step).min(max);
+        let value = Value::number(value, min.unit, false, Format::Decimal);
+        self.context
+            .borrow_mut()
+            .env
+            .set_variable(&name, Variable(value.clone()))
+            .map_err(|ty| ty.with(range))?;
+        Ok(ResultData::Slider { name, value, min: min.number, max, step })
+    }
 
-                if !(token.ty.is_format()
-                    && tokens
-                        .get(i.saturating_sub(1))
-                        .map_or(false, |t| t.ty == In))
-                    && token.ty != Exponentiation
-                    && !is_in_unit
-                {
-                    new_line.push(' ');
-                }
-                new_line += text;
-                if i != tokens.len() - 1 && token.ty != Exponentiation && !is_in_unit {
-                    new_line.push(' ');
-                }
-            } else if matches!(token.ty, Comma | Semicolon) {
-                new_line += text;
-                if i != tokens.len() - 1 {
-                    new_line.push(' ');
-                }
-            } else if token.ty == ObjectArgs {
-                if tokens
-                    .get(i - 1)
-                    .map(|t| t.ty == Identifier)
-                    .unwrap_or_default()
-                {
-                    new_line.push(' ');
-                }
-                new_line += text;
-            }
-        }
+    /// Formats `line` using the [FormatStyle] from the calculator's settings.
+    /// Returns an error if the line could not be parsed.
+    pub fn format(&self, line: &str) -> Result<String> {
+        let input_mode = self.context.borrow().settings.input_mode;
+        self.convert(line, input_mode, input_mode)
+    }
 
-        Ok(new_line.to_string())
+    /// Formats `line`, which is written in the notation `from`, and writes it in the notation `to`
+    /// (e.g. `3 4 + 2 *` to `(3 + 4) * 2`).
+    /// Returns an error if the line could not be parsed or cannot be written in the notation `to`.
+    pub fn convert(&self, line: &str, from: InputMode, to: InputMode) -> Result<String> {
+        let style = self.context.borrow().settings.format;
+        formatter::format_line(line, self.context(), style, from, to)
     }
 
     pub fn get_debug_info(&self, input: &str, verbosity: Verbosity) -> String {
+        if verbosity == Verbosity::Json {
+            return self.get_json_debug_info(input);
+        }
+
         let mut output = "Line:\n".to_string();
 
         let tokens = match tokenize(input) {
@@ -523,7 +925,7 @@ impl Calculator {
             }
         };
 
-        if matches!(verbosity, Verbosity::Tokens | Verbosity::Ast) {
+        if matches!(verbosity, Verbosity::Tokens | Verbosity::Ast | Verbosity::TypedAst) {
             writeln!(&mut output, "Tokens:").unwrap();
             for token in &tokens {
                 writeln!(&mut output, "{} => {:?}", token.text, token.ty).unwrap();
@@ -531,37 +933,44 @@ impl Calculator {
             writeln!(&mut output).unwrap();
         }
 
-        if verbosity == Verbosity::Ast {
+        if matches!(verbosity, Verbosity::Ast | Verbosity::TypedAst) {
             match Parser::from_tokens(&tokens, self.context()).parse_single() {
                 Ok(parser_result) => match parser_result.data {
                     ParserResultData::Calculation(ast) => {
                         writeln!(&mut output, "AST:").unwrap();
-                        for node in &ast {
-                            writeln!(&mut output, "{}", node).unwrap();
-                        }
+                        self.write_nodes(&mut output, &ast, verbosity);
                         writeln!(&mut output).unwrap();
+
+                        // Calculated in a copy of the context, so that the environment isn't changed
+                        let context = Rc::new(RefCell::new(self.context.borrow().clone()));
+                        context.borrow_mut().env.breakdown.clear();
+                        if Engine::evaluate(ast, context.clone()).is_ok() && !context.borrow().env.breakdown.is_empty() {
+                            writeln!(&mut output, "Breakdown:").unwrap();
+                            for step in &context.borrow().env.breakdown {
+                                writeln!(&mut output, "{step}").unwrap();
+                            }
+                            writeln!(&mut output).unwrap();
+                        }
                     }
-                    ParserResultData::BooleanExpression { lhs, rhs, operator } => {
+                    ParserResultData::BooleanExpression { lhs, rhs, operator, tolerance } => {
                         writeln!(
                             &mut output,
                             "Boolean expression:\nOperator: {operator:?}\nLHS:"
                         )
                         .unwrap();
-                        for node in &lhs {
-                            writeln!(&mut output, "{}", node).unwrap();
-                        }
+                        self.write_nodes(&mut output, &lhs, verbosity);
                         writeln!(&mut output, "RHS:").unwrap();
-                        for node in &rhs {
-                            writeln!(&mut output, "{}", node).unwrap();
+                        self.write_nodes(&mut output, &rhs, verbosity);
+                        if let Some(tolerance) = tolerance {
+                            writeln!(&mut output, "Tolerance:").unwrap();
+                            self.write_nodes(&mut output, &tolerance, verbosity);
                         }
                         writeln!(&mut output).unwrap();
                     }
                     ParserResultData::VariableDefinition(name, ast) => {
                         if let Some(ast) = ast {
                             writeln!(&mut output, "Variable Definition: {}\nAST:", name).unwrap();
-                            for node in &ast {
-                                writeln!(&mut output, "{}", node).unwrap();
-                            }
+                            self.write_nodes(&mut output, &ast, verbosity);
                         } else {
                             writeln!(&mut output, "Variable removal: {}", name).unwrap();
                         }
@@ -573,7 +982,7 @@ impl Calculator {
                                 .unwrap();
                             for (variant, ast) in &function.variants {
                                 match variant {
-                                    FunctionVariantType::BooleanVariant { lhs, rhs, operator } => {
+                                    FunctionVariantType::BooleanVariant { lhs, rhs, operator, tolerance } => {
                                         write!(&mut output, "Boolean Variant: ").unwrap();
                                         for node in lhs {
                                             write!(&mut output, "{}", node).unwrap();
@@ -582,6 +991,12 @@ impl Calculator {
                                         for node in rhs {
                                             write!(&mut output, "{}", node).unwrap();
                                         }
+                                        if let Some(tolerance) = tolerance {
+                                            write!(&mut output, " within ").unwrap();
+                                            for node in tolerance {
+                                                write!(&mut output, "{}", node).unwrap();
+                                            }
+                                        }
                                         write!(&mut output, ": ").unwrap();
                                     }
                                     FunctionVariantType::Else => {
@@ -589,9 +1004,7 @@ impl Calculator {
                                     }
                                 }
 
-                                for node in ast {
-                                    writeln!(&mut output, "{}", node).unwrap();
-                                }
+                                self.write_nodes(&mut output, ast, verbosity);
                             }
                         } else {
                             writeln!(&mut output, "Function removal: {}", name).unwrap();
@@ -615,13 +1028,9 @@ impl Calculator {
                             output_variable
                         )
                         .unwrap();
-                        for node in &lhs {
-                            writeln!(&mut output, "{}", node).unwrap();
-                        }
+                        self.write_nodes(&mut output, &lhs, verbosity);
                         writeln!(&mut output, "RHS:").unwrap();
-                        for node in &rhs {
-                            writeln!(&mut output, "{}", node).unwrap();
-                        }
+                        self.write_nodes(&mut output, &rhs, verbosity);
                         writeln!(&mut output).unwrap();
                     }
                 },
@@ -637,9 +1046,1035 @@ impl Calculator {
         }
 
         output
-            + &format!(
+            + format!(
                 "\nEnvironment:\n{}",
                 self.context.borrow().env.get_debug_info()
-            )
+            ).as_str()
+    }
+
+    /// Writes the nodes of `ast`, preceded by their types if `verbosity` is [Verbosity::TypedAst]
+    fn write_nodes(&self, output: &mut String, ast: &[AstNode], verbosity: Verbosity) {
+        for node in ast {
+            if verbosity == Verbosity::TypedAst {
+                writeln!(output, "[{}] {node}", self.node_type(node)).unwrap();
+            } else {
+                writeln!(output, "{node}").unwrap();
+            }
+        }
+    }
+
+    /// Describes the value that `node` evaluates to on its own, including its resolved unit
+    fn node_type(&self, node: &AstNode) -> String {
+        match &node.data {
+            AstNodeData::Operator(_) => return "operator".to_string(),
+            AstNodeData::Unit(unit) => return format!("unit {unit}"),
+            AstNodeData::Arguments(_) => return "arguments".to_string(),
+            AstNodeData::QuestionMark => return "unknown".to_string(),
+            _ => {}
+        }
+
+        // Evaluated in a copy of the context, so that the environment isn't changed
+        let context = Rc::new(RefCell::new(self.context.borrow().clone()));
+        match Engine::evaluate(vec![node.clone()], context) {
+            Ok(Value::Number(NumberValue { unit: Some(unit), .. })) => format!("number in {unit}"),
+            Ok(Value::Number(_)) => "number".to_string(),
+            Ok(Value::Object(object)) => object.type_name().to_string(),
+            Err(_) => match &node.data {
+                AstNodeData::Identifier(name) if self.context.borrow().env.is_valid_function(name) => "function".to_string(),
+                _ => "unknown".to_string(),
+            },
+        }
+    }
+
+    fn get_json_debug_info(&self, input: &str) -> String {
+        let error_json = |e: Error| serde_json::json!({
+            "message": e.error.to_string(),
+            "ranges": e.ranges,
+        });
+
+        let tokens = match tokenize(input) {
+            Ok(tokens) => tokens,
+            Err(e) => return serde_json::json!({ "error": error_json(e) }).to_string(),
+        };
+
+        let mut json = match Parser::from_tokens(&tokens, self.context()).parse_single() {
+            Ok(parser_result) => match parser_result.data {
+                ParserResultData::Calculation(ast) => serde_json::json!({
+                    "kind": "calculation",
+                    "ast": ast,
+                }),
+                ParserResultData::BooleanExpression { lhs, rhs, operator, tolerance } => serde_json::json!({
+                    "kind": "boolean_expression",
+                    "operator": operator,
+                    "lhs": lhs,
+                    "rhs": rhs,
+                    "tolerance": tolerance,
+                }),
+                ParserResultData::VariableDefinition(name, ast) => serde_json::json!({
+                    "kind": "variable_definition",
+                    "name": name,
+                    "ast": ast,
+                }),
+                ParserResultData::FunctionDefinition { name, function } => serde_json::json!({
+                    "kind": "function_definition",
+                    "name": name,
+                    "function": function,
+                }),
+                ParserResultData::Equation { lhs, rhs, is_question_mark_in_lhs, output_variable } => serde_json::json!({
+                    "kind": "equation",
+                    "is_question_mark_in_lhs": is_question_mark_in_lhs,
+                    "output_variable": output_variable,
+                    "lhs": lhs,
+                    "rhs": rhs,
+                }),
+            },
+            Err(e) => serde_json::json!({ "error": error_json(e) }),
+        };
+
+        json["tokens"] = serde_json::json!(tokens);
+        json.to_string()
+    }
+}
+
+/// Returns the arguments if `ast` is a call of `slider(min, max, step)` and nothing else
+fn slider_arguments(ast: &[AstNode]) -> Option<&Vec<Vec<AstNode>>> {
+    let [node] = ast else { return None; };
+    let AstNodeData::Group(group) = &node.data else { return None; };
+    if !node.modifiers.is_empty() || node.unit.is_some() { return None; }
+
+    match group.as_slice() {
+        [
+            AstNode { data: AstNodeData::Identifier(name), .. },
+            AstNode { data: AstNodeData::Operator(Operator::Call), .. },
+            AstNode { data: AstNodeData::Arguments(arguments), .. },
+        ] if name == "slider" => Some(arguments),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+
+    /// A calculator without exchange rates, which is used by the tests of all modules
+    pub(crate) fn calculator() -> Calculator {
+        Calculator {
+            context: Rc::new(RefCell::new(ContextData::default())),
+            verbosity: Verbosity::None,
+            token_cache: TokenCache::default(),
+            base_env: Environment::new(),
+            slider_values: HashMap::new(),
+            profiling: false,
+            postprocessors: Postprocessors::default(),
+        }
+    }
+
+    #[test]
+    fn debug_info() {
+        let calculator = calculator();
+        let typed_ast = calculator.get_debug_info("3km + (2m * 4)", Verbosity::TypedAst);
+        assert!(typed_ast.contains("[number in km] Number: 3"));
+        assert!(typed_ast.contains("[operator] Operator: Plus"));
+        assert!(typed_ast.contains("[number in m] Group"));
+
+        let json = calculator.get_debug_info("x := 3 + 4", Verbosity::Json);
+        let json = serde_json::from_str::<serde_json::Value>(&json).unwrap();
+        assert_eq!(json["kind"], "variable_definition");
+        assert_eq!(json["name"], "x");
+        assert_eq!(json["tokens"][0]["text"], "x");
+        assert_eq!(json["ast"][1]["data"]["Operator"], "Plus");
+
+        let json = calculator.get_debug_info("3 +", Verbosity::Json);
+        let json = serde_json::from_str::<serde_json::Value>(&json).unwrap();
+        assert!(json["error"]["message"].is_string());
+    }
+
+    #[test]
+    fn handle() {
+        let handle = CalculatorHandle::spawn(calculator);
+        handle.calculate("x := 3");
+
+        let threads = (1..=4).map(|i| {
+            let handle = handle.clone();
+            std::thread::spawn(move || handle.calculate(&format!("x * {i}")).remove(0))
+        }).collect::<Vec<_>>();
+        for (i, thread) in threads.into_iter().enumerate() {
+            let result = thread.join().unwrap();
+            let Ok((ResultData::Value(Value::Number(number)), _)) = result.data else { panic!(); };
+            assert_eq!(number.number, 3.0 * (i + 1) as f64);
+        }
+
+        let lines = handle.calculate_all("1 + 2\nx");
+        assert_eq!(lines.len(), 2);
+        assert!(handle.with(|calculator| calculator.context.borrow().env.is_valid_variable("x")));
+    }
+
+    #[test]
+    fn cancellation() {
+        let mut calculator = calculator();
+        let cancellation = calculator.context.borrow().cancellation.clone();
+        assert!(calculator.calculate("2 + 3").remove(0).data.is_ok());
+
+        cancellation.cancel();
+        let error = calculator.calculate("2 + 3").remove(0).data.unwrap_err();
+        assert!(matches!(error.error, common::ErrorType::Cancelled));
+
+        let mut lines = 0;
+        calculator.calculate_all("1\n2\n3", |_| lines += 1);
+        assert_eq!(lines, 1);
+    }
+
+    #[test]
+    fn calculate_all() {
+        let mut line_ranges = vec![];
+        let mut errors = 0usize;
+        calculator().calculate_all("1 + 2\n\n(3 +\n4)\n5 +\n\nx", |line| {
+            line_ranges.push(line.line_range);
+            if line.result.data.is_err() { errors += 1; }
+        });
+
+        assert_eq!(line_ranges, vec![0..1, 2..4, 4..5, 6..7]);
+        assert_eq!(errors, 2);
+    }
+
+    #[test]
+    fn missing_capability() {
+        let mut calculator = calculator();
+        calculator.context.borrow_mut().capabilities.clock = false;
+
+        let error = calculator.calculate("{date now}").remove(0).data.err().unwrap();
+        assert!(matches!(error.error, common::ErrorType::MissingCapability(Capability::Clock)));
+        assert!(calculator.calculate("{date 01.01.2023}").remove(0).data.is_ok());
+    }
+
+    #[test]
+    fn batch() {
+        let mut calculator = calculator();
+        calculator.calculate("x := 2");
+        let results = calculator.evaluate_batch(&["x * 3", "x := 5", "x + 1", "", "ans + 1", "5 +", "f(y) := y\nf(x)", "5 §"]);
+        let results = results.iter()
+            .map(|result| match result.as_ref().map(|result| &result.data) {
+                Ok(Ok((ResultData::Value(value), _))) => value.format(&Settings::default(), false).trim().to_string(),
+                Ok(Ok((ResultData::Nothing, _))) => "nothing".to_string(),
+                Ok(Ok(data)) => format!("{data:?}"),
+                Ok(Err(_)) => "error".to_string(),
+                Err(_) => "invalid".to_string(),
+            })
+            .collect::<Vec<_>>();
+        // The definition and `ans` of an input don't affect the following ones
+        assert_eq!(results, ["6", "5", "3", "nothing", "1", "error", "2", "invalid"]);
+        assert!(!calculator.context.borrow().env.is_valid_function("f"));
+        assert!(calculator.context.borrow().env.resolve_variable("x").is_ok());
+    }
+
+    #[test]
+    fn result_metadata() {
+        let mut calculator = calculator();
+        let results = calculator.calculate("1 / 3\n\n  2 + 3 in hex;\n5 +");
+        assert_eq!(results.iter().map(|result| result.source_range).collect::<Vec<_>>(), [
+            SourceRange::line(0, 0, 5),
+            SourceRange::line(2, 2, 14),
+            SourceRange::empty(),
+        ]);
+
+        let settings = Settings::default().output;
+        let mut is_rounded = |input: &str| match calculator.calculate(input).remove(0).data {
+            Ok((ResultData::Value(Value::Number(number)), _)) => number.is_rounded(&settings),
+            data => panic!("{data:?}"),
+        };
+        assert!(is_rounded("1 / 3"));
+        assert!(!is_rounded("0.25"));
+        assert!(!is_rounded("255 in hex"));
+        assert!(is_rounded("1 / 3 in scientific"));
+        assert!(!is_rounded("1e20"));
+    }
+
+    #[test]
+    fn sandboxed_capabilities() {
+        let mut calculator = calculator();
+        calculator.context.borrow_mut().capabilities = Capabilities::sandboxed();
+
+        let error = |calculator: &mut Calculator, input: &str| calculator.calculate(input).remove(0).data.err().map(|e| e.error);
+        for input in ["x := 5", "x :=", "f(x) := x * 2", "f(x) :=", "20 + x? = 100"] {
+            assert!(matches!(error(&mut calculator, input), Some(ErrorType::MissingCapability(Capability::Environment))), "{input}");
+        }
+        assert!(calculator.context.borrow().env.resolve_variable("x").is_err());
+        assert!(error(&mut calculator, "{date now}").is_none());
+        assert!(error(&mut calculator, "20 + ? = 100").is_none());
+        assert!(matches!(
+            error(&mut calculator, "100 USD in EUR at {date 02.06.2023}"),
+            Some(ErrorType::MissingCapability(Capability::Network)),
+        ));
+    }
+
+    /// Runs `f` on a thread with enough stack for deep recursions, which the default stack size of
+    /// test threads isn't, at least in debug builds
+    fn with_calculation_stack<R: Send + 'static>(f: impl FnOnce() -> R + Send + 'static) -> R {
+        std::thread::Builder::new().stack_size(CALCULATION_STACK_SIZE).spawn(f).unwrap().join().unwrap()
+    }
+
+    #[test]
+    fn recursive_function() {
+        let error = with_calculation_stack(|| {
+            let mut calculator = self::calculator();
+            calculator.calculate("f(x) := x\nf(x) := f(x + 1)\nf(1)").remove(2).data.err().unwrap()
+        });
+        assert!(matches!(error.error, common::ErrorType::LimitExceeded(_)));
+
+        // Nesting the evaluations too deeply still fails without a limit for the recursion
+        let error = with_calculation_stack(|| {
+            let mut calculator = self::calculator();
+            calculator.context.borrow_mut().settings.limits.max_recursion_depth = usize::MAX;
+            calculator.calculate("f(x) := f(x + 1)\nf(1)").remove(1).data.err().unwrap()
+        });
+        assert!(matches!(error.error, common::ErrorType::TooDeeplyNested));
+    }
+
+    #[test]
+    fn deep_recursion() {
+        let results = with_calculation_stack(|| {
+            let mut calculator = self::calculator();
+            calculator.calculate("fact(n) := if n < 2 then 1 else n * fact(n - 1)\nfact(40)\n\
+                sum(n) := if n < 1 then 0 else n + sum(n - 1)\nsum(100)\n\
+                fib(n) := if n < 2 then n else fib(n - 1) + fib(n - 2)\nfib(35)")
+                .into_iter()
+                .map(|result| match result.data {
+                    Ok((ResultData::Value(Value::Number(number)), _)) => Some(number.number),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+        });
+        assert_eq!(results[1], Some((1..=40).map(|n| n as f64).product()));
+        assert_eq!(results[3], Some(5050.0));
+        assert_eq!(results[5], Some(9227465.0));
+    }
+
+    #[test]
+    fn memoized_recursion() {
+        let number = |result: &CalculatorResult| {
+            let Ok((ResultData::Value(Value::Number(number)), _)) = &result.data else { panic!(); };
+            number.number
+        };
+
+        let mut calculator = calculator();
+        let results = calculator.calculate("fib(n) := if n < 2 then n else fib(n - 1) + fib(n - 2)\nfib(25)");
+        assert_eq!(number(&results[1]), 75025.0);
+
+        // The remembered results don't outlive a calculation
+        let results = calculator.calculate("a := 1\ng(x) := x + a\ng(1)\na := 2\ng(1)");
+        assert_eq!(number(&results[2]), 2.0);
+        assert_eq!(number(&results[4]), 3.0);
+    }
+
+    #[test]
+    fn constant_cache() {
+        let mut calculator = calculator();
+        let env = calculator.context.borrow().env.clone();
+        let key = |input: &str| {
+            let ParserResultData::Calculation(ast) = Parser::from_tokens(&tokenize(input).unwrap(), calculator.context())
+                .parse_single().unwrap().data else { panic!(); };
+            constant_cache::key(&ast, &env)
+        };
+        assert!(key("factorize(2^40 - 1) * pi").is_some());
+        assert_eq!(key("  (1 + 2) * 3 m"), key("(1+2)*3m"));
+        assert_ne!(key("(1 + 2) * 3 m"), key("(1 + 2) * 3 cm"));
+        assert_ne!(key("1 + 2"), key("2 + 1"));
+        assert_ne!(key("{date 01.01.2023} + 1"), key("{date 02.01.2023} + 1"));
+        for input in ["5 + ans", "line(1) * 2", "5 USD in EUR"] {
+            assert!(key(input).is_none(), "{input}");
+        }
+        calculator.calculate("x := 2\nf(y) := y");
+        let env = calculator.context.borrow().env.clone();
+        let key = |input: &str| {
+            let ParserResultData::Calculation(ast) = Parser::from_tokens(&tokenize(input).unwrap(), calculator.context())
+                .parse_single().unwrap().data else { panic!(); };
+            constant_cache::key(&ast, &env)
+        };
+        assert!(key("x + 1").is_none());
+        assert!(key("f(3) + 1").is_none());
+
+        // Cached results aren't affected by redefinitions and the settings
+        let result = |calculator: &mut Calculator, input: &str| match calculator.calculate(input).pop().unwrap().data {
+            Ok((ResultData::Value(value), _)) => value.format(&Settings::default(), false).trim().to_string(),
+            data => panic!("{data:?}"),
+        };
+        assert_eq!(result(&mut calculator, "(5! + 1) * 2"), "242");
+        assert_eq!(result(&mut calculator, "(5! + 1) * 2"), "242");
+        // Every calculator has its own cache
+        let ParserResultData::Calculation(ast) = Parser::from_tokens(&tokenize("(5! + 1) * 2").unwrap(), calculator.context())
+            .parse_single().unwrap().data else { panic!(); };
+        let cached = constant_cache::key(&ast, &env).unwrap();
+        assert!(calculator.context.borrow().constant_cache.get(&cached).is_some());
+        assert!(self::calculator().context.borrow().constant_cache.get(&cached).is_none());
+        calculator.context.borrow_mut().settings.limits.max_factorial = 3;
+        assert!(calculator.calculate("(5! + 1) * 2").remove(0).data.is_err());
+        assert_eq!(result(&mut calculator, "f(y) := y * 2\n(f(3) + 1) * 2"), "14");
+        assert_eq!(result(&mut calculator, "f(y) := y * 3\n(f(3) + 1) * 2"), "20");
+    }
+
+    #[test]
+    fn limits() {
+        let mut calculator = calculator();
+        assert!(calculator.calculate("1000!").remove(0).data.is_ok());
+        let error = calculator.calculate("(10^8)!").remove(0).data.err().unwrap();
+        assert!(matches!(error.error, common::ErrorType::LimitExceeded(_)));
+
+        calculator.context.borrow_mut().settings.limits.max_evaluation_time = 0;
+        let error = calculator.calculate("f(x) := x + 1\nf(f(f(2)))").remove(1).data.err().unwrap();
+        assert!(matches!(error.error, common::ErrorType::LimitExceeded(_)));
+    }
+
+    #[test]
+    fn deep_nesting() {
+        let mut calculator = calculator();
+        for input in [
+            format!("{}1{}", "(".repeat(1000), ")".repeat(1000)),
+            format!("{}1{}", "sin(".repeat(1000), ")".repeat(1000)),
+            format!("{}1{}", "[".repeat(1000), "]".repeat(1000)),
+        ] {
+            let error = calculator.calculate(&input).remove(0).data.err().unwrap();
+            assert!(matches!(error.error, common::ErrorType::TooDeeplyNested | common::ErrorType::MissingClosingBracket));
+        }
+
+        let nested = format!("{}1{}", "sin((".repeat(15), "))".repeat(15));
+        assert!(calculator.calculate(&nested).remove(0).data.is_ok());
+    }
+
+    #[test]
+    fn warnings() {
+        let mut calculator = calculator();
+        let result = calculator.calculate("1 m + 3 cm").remove(0);
+        assert!(result.data.is_ok());
+        assert_eq!(result.warnings.len(), 1);
+        assert!(matches!(&result.warnings[0].warning, common::WarningType::UnitConverted(from, to) if from == "cm" && to == "m"));
+        assert_eq!(result.warnings[0].ranges, vec![range!(line 0 => 6..7)]);
+
+        let result = calculator.calculate("float_bits(0.1, 64)").remove(0);
+        assert!(matches!(result.warnings[0].warning, common::WarningType::PrecisionLost));
+
+        assert!(calculator.calculate("1 m + 3 m").remove(0).warnings.is_empty());
+        assert!(calculator.calculate("1 m + 3 s").remove(0).warnings.is_empty());
+    }
+
+    #[test]
+    fn prelude() {
+        let dir = std::env::temp_dir().join(format!("funcially_prelude_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("1_variables"), "speed := 3 m/s").unwrap();
+        std::fs::write(dir.join("2_functions"), "double(x) := x * 2\n\nspeed + unknown").unwrap();
+
+        let mut calculator = calculator();
+        let errors = calculator.load_prelude_from(&dir);
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(errors.len(), 1);
+        assert!(matches!(&errors[0], PreludeError::Calculation(path, e)
+            if path.ends_with("2_functions") && e.ranges[0].start_line == 2));
+
+        let number = |calculator: &mut Calculator, input: &str| match calculator.calculate(input).remove(0).data {
+            Ok((ResultData::Value(Value::Number(number)), _)) => number.number,
+            data => panic!("Expected a number, found {data:?}"),
+        };
+        assert_eq!(number(&mut calculator, "double(speed)"), 6.0);
+        assert_eq!(number(&mut calculator, "speed := 4"), 4.0);
+        calculator.reset();
+        assert_eq!(number(&mut calculator, "ans + speed"), 3.0);
+
+        assert!(calculator.load_prelude_from(&dir).is_empty());
+        assert!(calculator.calculate("speed").remove(0).data.is_err());
+    }
+
+    #[cfg(feature = "scripting")]
+    #[test]
+    fn scripts() {
+        let mut calculator = calculator();
+        calculator.add_script(r#"
+            fn fib(n) {
+                let a = 0;
+                let b = 1;
+                for i in 0..n.to_int() {
+                    let c = a + b;
+                    a = b;
+                    b = c;
+                }
+                a
+            }
+            fn forever(x) { loop {} }
+            fn text(x) { "text" }
+            private fn helper(x) { x }
+        "#).unwrap();
+        assert!(calculator.add_script("fn broken(").is_err());
+
+        calculator.reset();
+        let result = calculator.calculate("fib(10) + 1").remove(0);
+        assert!(matches!(result.data, Ok((ResultData::Value(Value::Number(number)), _)) if number.number == 56.0));
+
+        let result = calculator.calculate("1 + forever(1)").remove(0);
+        let error = result.data.unwrap_err();
+        assert!(matches!(error.error, common::ErrorType::ScriptError(_)));
+        assert_eq!(error.ranges, vec![range!(line 0 => 4..11)]);
+
+        let error = calculator.calculate("text(1)").remove(0).data.unwrap_err();
+        assert!(matches!(error.error, common::ErrorType::ScriptError(_)));
+        assert!(calculator.calculate("helper(1)").remove(0).data.is_err());
+    }
+
+    #[test]
+    fn sample_function() {
+        let mut calculator = calculator();
+        let Ok((ResultData::Function { function, .. }, _)) = calculator.calculate("f(x) := 2x + sqrt(x)").remove(0).data else {
+            panic!("Expected a function");
+        };
+
+        let samples = calculator.sample_function(&function, -1.0..=4.0, 6);
+        assert_eq!(samples.iter().map(|(x, _)| *x).collect::<Vec<_>>(), vec![-1.0, 0.0, 1.0, 2.0, 3.0, 4.0]);
+        assert!(samples[0].1.is_nan());
+        assert_eq!(samples[1].1, 0.0);
+        assert_eq!(samples[5].1, 10.0);
+        assert_eq!(calculator.sample_function(&function, 2.0..=5.0, 1), vec![(2.0, 2.0 * 2.0 + 2f64.sqrt())]);
+
+        let Ok((ResultData::Function { function, .. }, _)) = calculator.calculate("g(x, y) := x + y").remove(0).data else {
+            panic!("Expected a function");
+        };
+        assert!(calculator.sample_function(&function, 0.0..=1.0, 10).is_empty());
+        assert!(calculator.calculate("x").remove(0).data.is_err());
+    }
+
+    #[test]
+    fn function_evaluator() {
+        let mut calculator = calculator();
+        let Ok((ResultData::Function { function: f, .. }, _)) = calculator.calculate("f(x) := x^2 - 4").remove(0).data else {
+            panic!("Expected a function");
+        };
+        let Ok((ResultData::Function { function: g, .. }, _)) = calculator.calculate("g(x) := x + 2").remove(0).data else {
+            panic!("Expected a function");
+        };
+
+        let f = calculator.function_evaluator(&f).unwrap();
+        let g = calculator.function_evaluator(&g).unwrap();
+        assert_eq!(f.evaluate(3.0), 5.0);
+        assert!(f.evaluate(f64::NAN).is_nan());
+
+        let roots = f.roots(-5.0..=5.0, 100);
+        assert_eq!(roots.len(), 2);
+        assert!((roots[0] + 2.0).abs() < 1e-9 && (roots[1] - 2.0).abs() < 1e-9);
+
+        let extrema = f.extrema(-5.0..=5.0, 100);
+        assert_eq!(extrema.len(), 1);
+        assert_eq!(extrema[0].kind, ExtremumKind::Minimum);
+        assert!((extrema[0].y + 4.0).abs() < 1e-9);
+
+        let intersections = f.intersections(&g, -5.0..=5.0, 100);
+        assert_eq!(intersections.len(), 2);
+        assert!((intersections[1].0 - 3.0).abs() < 1e-9 && (intersections[1].1 - 5.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn relation() {
+        let mut calculator = calculator();
+        calculator.calculate("r := 5");
+
+        let relation = calculator.relation("x^2 + y^2 = r^2").unwrap();
+        assert_eq!(relation.operator(), BooleanOperator::Equal);
+        let evaluator = calculator.relation_evaluator(&relation);
+        assert_eq!(evaluator.difference(3.0, 4.0), 0.0);
+        assert_eq!(evaluator.difference(1.0, 1.0), -23.0);
+        assert!(evaluator.holds(0.0));
+
+        let relation = calculator.relation("y > 2x + 1").unwrap();
+        let evaluator = calculator.relation_evaluator(&relation);
+        assert!(evaluator.holds(evaluator.difference(0.0, 2.0)));
+        assert!(!evaluator.holds(evaluator.difference(0.0, 1.0)));
+        assert!(!evaluator.holds(f64::NAN));
+
+        assert!(calculator.relation("x + 1").is_none());
+        assert!(calculator.relation("1 + 2 = 3").is_none());
+        assert!(calculator.relation("x = z").is_none());
+        // x and y aren't defined by parsing a relation
+        assert!(calculator.calculate("x").remove(0).data.is_err());
+    }
+
+    #[test]
+    fn slider() {
+        let mut calculator = calculator();
+        let calculate = |calculator: &mut Calculator| {
+            calculator.reset();
+            let mut results = calculator.calculate("a := slider(0, 10, 0.5)\na * 2");
+            let Ok((ResultData::Value(Value::Number(number)), _)) = results.remove(1).data else { panic!("Expected a number"); };
+            let Ok((ResultData::Slider { name, value: Value::Number(value), min, max, step }, _)) = results.remove(0).data
+                else { panic!("Expected a slider"); };
+            assert_eq!((name.as_str(), min, max, step), ("a", 0.0, 10.0, 0.5));
+            (value.number, number.number)
+        };
+
+        assert_eq!(calculate(&mut calculator), (0.0, 0.0));
+        calculator.set_slider_value("a", 3.6);
+        assert_eq!(calculate(&mut calculator), (3.5, 7.0));
+        calculator.set_slider_value("a", 20.0);
+        assert_eq!(calculate(&mut calculator), (10.0, 20.0));
+
+        let error = |calculator: &mut Calculator, input: &str| calculator.calculate(input).remove(0).data.unwrap_err().error;
+        assert!(matches!(error(&mut calculator, "2 * slider(0, 1, 1)"), ErrorType::SliderOutsideDefinition));
+        assert!(matches!(error(&mut calculator, "b := slider(1, 0, 1)"), ErrorType::Arg1GreaterThanArg2));
+        assert!(matches!(error(&mut calculator, "b := slider(0, 1, 0)"), ErrorType::InvalidSliderStep));
+    }
+
+    #[test]
+    fn settings_missing_fields() {
+        // Settings saved before newer fields were added
+        let settings = serde_json::from_str::<Settings>(r#"{ "date": { "delimiter": "/" }, "tight_implicit_multiplication": true }"#).unwrap();
+        assert_eq!(settings.date.delimiter, '/');
+        assert_eq!(settings.date.timezone, Settings::default().date.timezone);
+        assert!(settings.tight_implicit_multiplication);
+        assert!(settings.output == OutputSettings::default());
+        assert!(serde_json::from_str::<Settings>("{}").unwrap() == Settings::default());
+    }
+
+    #[test]
+    fn output_settings() {
+        let mut calculator = calculator();
+        let format = |calculator: &mut Calculator, input: &str| match calculator.calculate(input).remove(0).data {
+            Ok((ResultData::Value(value), _)) => value.format(&calculator.context.borrow().settings, false).trim().to_string(),
+            data => panic!("Expected a value, found {data:?}"),
+        };
+
+        assert_eq!(format(&mut calculator, "1000 in sci"), "1e3");
+        assert_eq!(format(&mut calculator, "-2500 in sci"), "-2.5e3");
+        assert_eq!(format(&mut calculator, "0.00012 in sci"), "1.2e-4");
+        assert_eq!(format(&mut calculator, "1/3"), "0.3333333333");
+
+        calculator.context.borrow_mut().settings.output = OutputSettings {
+            default_format: Format::Hex,
+            decimal_places: 3,
+            ..OutputSettings::default()
+        };
+        assert_eq!(format(&mut calculator, "255"), "0xFF");
+        assert_eq!(format(&mut calculator, "255 in decimal"), "255");
+        assert_eq!(format(&mut calculator, "255 in sci"), "2.55e2");
+
+        calculator.context.borrow_mut().settings.output = OutputSettings {
+            group_digits: true,
+            ..OutputSettings::default()
+        };
+        assert_eq!(format(&mut calculator, "0xFFFFFFFF in hex"), "0xFFFF_FFFF");
+        assert_eq!(format(&mut calculator, "0b1010_0101 in bin"), "0b1010_0101");
+        assert_eq!(format(&mut calculator, "0xFF_FF + 1 in hex"), "0x1_0000");
+        assert_eq!(format(&mut calculator, "65536"), "65536");
+
+        calculator.context.borrow_mut().settings.output.pad_to_word_size = true;
+        calculator.context.borrow_mut().settings.word_size = WordSize::Bits16;
+        assert_eq!(format(&mut calculator, "0xFF in hex"), "0x00FF");
+        assert_eq!(format(&mut calculator, "-1 in hex"), "0xFFFF");
+        assert_eq!(format(&mut calculator, "5 in bin"), "0b0000_0000_0000_0101");
+        // Numbers that don't fit into the word size aren't padded
+        assert_eq!(format(&mut calculator, "0x10000 in hex"), "0x1_0000");
+        calculator.context.borrow_mut().settings.word_size = WordSize::Unlimited;
+        calculator.context.borrow_mut().settings.output.group_digits = false;
+
+        calculator.context.borrow_mut().settings.output = OutputSettings {
+            decimal_places: 3,
+            pad_trailing_zeros: true,
+            ..OutputSettings::default()
+        };
+        assert_eq!(format(&mut calculator, "1/3"), "0.333");
+        assert_eq!(format(&mut calculator, "1.5"), "1.500");
+        assert_eq!(format(&mut calculator, "2"), "2.000");
+
+        calculator.context.borrow_mut().settings.output.auto_scientific = true;
+        assert_eq!(format(&mut calculator, "2 * 10^20"), "2e20");
+        assert_eq!(format(&mut calculator, "0.000000015"), "1.5e-8");
+        assert_eq!(format(&mut calculator, "0"), "0.000");
+
+        let mut settings = Settings::default();
+        assert!(settings.set(&["output", "default_format"], "scientific").is_ok());
+        assert!(settings.set(&["output", "default_format"], "octal").is_err());
+        assert_eq!(settings.get(&["output", "default_format"]).ok().as_deref(), Some("scientific"));
+    }
+
+    #[test]
+    fn data_units() {
+        let mut calculator = calculator();
+        let number = |calculator: &mut Calculator, input: &str| match calculator.calculate(input).remove(0).data {
+            Ok((ResultData::Value(Value::Number(number)), _)) => number.number,
+            data => panic!("Expected a number, found {data:?}"),
+        };
+
+        assert_eq!(number(&mut calculator, "1Kib in b"), 1024.0);
+        assert_eq!(number(&mut calculator, "1kb in b"), 1000.0);
+        assert_eq!(number(&mut calculator, "1GiB in Mib"), 128.0);
+        assert_eq!(number(&mut calculator, "3Mib in Kib"), 3072.0);
+
+        calculator.context.borrow_mut().settings.data_prefixes = DataPrefixes::Binary;
+        assert_eq!(number(&mut calculator, "1kb in b"), 1024.0);
+        assert_eq!(number(&mut calculator, "1Mb in Kib"), 1024.0);
+        assert_eq!(number(&mut calculator, "2kb in Kib"), 2.0);
+    }
+
+    #[test]
+    fn currency_symbols() {
+        let mut calculator = calculator();
+        calculator.context.borrow_mut().settings.currency.money_format = true;
+        let format = |calculator: &mut Calculator, input: &str, use_thousands_separator: bool| match calculator.calculate(input).remove(0).data {
+            Ok((ResultData::Value(value), _)) => value.format(&calculator.context.borrow().settings, use_thousands_separator).trim().to_string(),
+            data => panic!("Expected a value, found {data:?}"),
+        };
+
+        assert_eq!(format(&mut calculator, "$5", false), "$5.00");
+        assert_eq!(format(&mut calculator, "5$ + 0.5", false), "$5.50");
+        assert_eq!(format(&mut calculator, "-€3", false), "-€3.00");
+        assert_eq!(format(&mut calculator, "1234.5 USD", true), "$1_234.50");
+        assert_eq!(format(&mut calculator, "2.555 CHF", false), "2.56 CHF");
+        assert_eq!(format(&mut calculator, "$5 in hex", false), "0x5 USD");
+
+        {
+            let settings = &mut calculator.context.borrow_mut().settings;
+            assert!(settings.set(&["currency", "dollar"], "chf").is_ok());
+            assert!(settings.set(&["currency", "dollar"], "ABC").is_err());
+        }
+        assert_eq!(format(&mut calculator, "$5", false), "$5.00");
+        assert_eq!(format(&mut calculator, "5 CHF", false), "$5.00");
+        assert_eq!(format(&mut calculator, "5 USD", false), "5.00 USD");
+
+        calculator.context.borrow_mut().settings.currency.money_format = false;
+        assert_eq!(format(&mut calculator, "$5 + 0.5", false), "5.5CHF");
+    }
+
+    #[test]
+    fn uncertainties() {
+        let mut calculator = calculator();
+        let format = |calculator: &mut Calculator, input: &str| match calculator.calculate(input).remove(0).data {
+            Ok((ResultData::Value(value), _)) => value.format(&calculator.context.borrow().settings, false).trim().to_string(),
+            data => panic!("Expected a value, found {data:?}"),
+        };
+
+        assert_eq!(format(&mut calculator, "(5.0 ± 0.1) * (2.0 ± 0.05)"), "10 ± 0.45");
+        assert_eq!(format(&mut calculator, "2m ± 5cm"), "(2 ± 0.05)m");
+        assert_eq!(format(&mut calculator, "$5 ± 0.5"), "(5 ± 0.5)USD");
+
+        // Uncertainties are kept in variables and passed to custom functions
+        calculator.calculate("x := 3 ± 0.1\nf(a) := a * 2");
+        assert_eq!(format(&mut calculator, "x + 1"), "4 ± 0.1");
+        assert_eq!(format(&mut calculator, "f(x)"), "6 ± 0.2");
+    }
+
+    #[test]
+    fn mixed_measures() {
+        let mut calculator = calculator();
+        let format = |calculator: &mut Calculator, input: &str| match calculator.calculate(input).remove(0).data {
+            Ok((ResultData::Value(value), _)) => value.format(&calculator.context.borrow().settings, false).trim().to_string(),
+            data => panic!("Expected a value, found {data:?}"),
+        };
+
+        assert_eq!(format(&mut calculator, "3lb 4oz"), "3.25lb");
+        assert_eq!(format(&mut calculator, "1h 30min in min"), "90min");
+        assert_eq!(format(&mut calculator, "5ft 11in in inch"), "71inch");
+        assert_eq!(format(&mut calculator, "6ft in ft_in"), "6ft 0in");
+        assert_eq!(format(&mut calculator, "100000s in d_h_min_s"), "1d 3h 46min 40s");
+        assert_eq!(format(&mut calculator, "-90min in h_min"), "-1h 30min");
+        // The parts are converted implicitly
+        assert!(calculator.calculate("5ft 11in").remove(0).warnings.is_empty());
+    }
+
+    #[test]
+    fn historic_exchange_rates() {
+        let mut calculator = calculator();
+        calculator.context.borrow_mut().capabilities = Capabilities::none();
+        let date = chrono::NaiveDate::from_ymd_opt(2023, 6, 1).unwrap();
+        calculator.context.borrow().currencies.historic.lock().unwrap()
+            .push((date, "EUR".to_string(), HashMap::from([("USD".to_string(), 1.25), ("GBP".to_string(), 0.5)])));
+
+        let result = |calculator: &mut Calculator, input: &str| calculator.calculate(input).remove(0).data.map(|(data, _)| data);
+        match result(&mut calculator, "100 USD in EUR at {date 01.06.2023}") {
+            Ok(ResultData::Value(value)) => {
+                let number = value.to_number().unwrap();
+                assert_eq!(number.number, 80.0);
+                assert_eq!(number.unit.as_ref().unwrap().to_string(), "EUR");
+            }
+            data => panic!("Expected a value, found {data:?}"),
+        }
+        match result(&mut calculator, "$10 in £ at {date 01.06.2023}") {
+            Ok(ResultData::Value(value)) => assert_eq!(value.to_number().unwrap().number, 4.0),
+            data => panic!("Expected a value, found {data:?}"),
+        }
+
+        let error = |calculator: &mut Calculator, input: &str| result(calculator, input).unwrap_err().error;
+        assert!(matches!(error(&mut calculator, "100 USD in EUR at {date 02.06.2023}"), ErrorType::MissingCapability(Capability::Network)));
+        assert!(matches!(error(&mut calculator, "5m in km at {date 01.06.2023}"), ErrorType::ExpectedCurrency));
+        assert!(matches!(error(&mut calculator, "100 USD in EUR at 5"), ErrorType::ExpectedDate));
+
+        // Days that couldn't be fetched before aren't requested again
+        calculator.context.borrow_mut().capabilities.network = true;
+        let date = chrono::NaiveDate::from_ymd_opt(2023, 6, 3).unwrap();
+        calculator.context.borrow().currencies.unavailable_historic.lock().unwrap().push(date);
+        assert!(matches!(error(&mut calculator, "100 USD in EUR at {date 03.06.2023}"), ErrorType::ExchangeRatesUnavailable(_)));
+    }
+
+    #[test]
+    fn adjustment_breakdown() {
+        let calculator = calculator();
+        let debug_info = calculator.get_debug_info("120 + 19% tax - 10% discount", Verbosity::Ast);
+        assert!(debug_info.contains("Breakdown:\ntax (19%): 120 + 22.8 = 142.8\ndiscount (10%): 142.8 - 14.28 = 128.52\n"));
+        assert!(!calculator.get_debug_info("120 + 19%", Verbosity::Ast).contains("Breakdown:"));
+    }
+
+    #[test]
+    fn conversion_suggestions() {
+        let mut calculator = calculator();
+        let mut suggestions = |input: &str| match calculator.calculate(input).remove(0).data {
+            Ok((ResultData::Value(value), _)) => calculator.suggest_conversions(value.to_number().unwrap(), 3),
+            data => panic!("Expected a value, found {data:?}"),
+        };
+
+        assert_eq!(suggestions("5 km"), ["mi", "m", "yd"]);
+        assert_eq!(suggestions("3 mm"), ["cm", "inch", "ft"]);
+        assert_eq!(suggestions("100 km/h"), ["m/s"]);
+        assert_eq!(suggestions("20 °C"), ["°F", "K"]);
+        assert!(suggestions("5").is_empty());
+    }
+
+    #[test]
+    fn modulo_breakdown() {
+        let calculator = calculator();
+        calculator.context.borrow_mut().settings.modulo = ModuloMode::Floored;
+        assert!(calculator.get_debug_info("-7 mod 3", Verbosity::Ast).contains("Breakdown:\n-7 mod 3 = 2 (floored)\n"));
+        assert!(!calculator.get_debug_info("-7 rem 3", Verbosity::Ast).contains("Breakdown:"));
+    }
+
+    #[test]
+    fn integer_range() {
+        let mut calculator = calculator();
+        let error = |calculator: &mut Calculator, input: &str| match calculator.calculate(input).remove(0).data {
+            Err(error) => error,
+            data => panic!("Expected an error, found {data:?}"),
+        };
+
+        let result = error(&mut calculator, "2^60 | 1");
+        assert!(matches!(result.error, ErrorType::IntegerOutOfRange(value, _, max) if value == 2f64.powi(60) && max == 2f64.powi(53)));
+        assert!(matches!(error(&mut calculator, "1e20 mod 3").error, ErrorType::IntegerOutOfRange(..)));
+        assert!(calculator.calculate("2^53 & 1").remove(0).data.is_ok());
+        assert!(calculator.calculate("1e20 mod 2.5").remove(0).data.is_ok());
+
+        calculator.context.borrow_mut().settings.word_size = WordSize::Bits8;
+        assert!(calculator.calculate("0xff & 0x0f").remove(0).data.is_ok());
+        assert!(calculator.calculate("-128 | 1").remove(0).data.is_ok());
+        let result = error(&mut calculator, "1 | 0x100");
+        assert!(matches!(result.error, ErrorType::IntegerOutOfRange(value, min, max) if value == 256.0 && min == -128.0 && max == 255.0));
+        assert_eq!(result.ranges, vec![range!(line 0 => 4..9)]);
+        assert!(matches!(error(&mut calculator, "-129 mod 2").error, ErrorType::IntegerOutOfRange(..)));
+    }
+
+    #[test]
+    fn scientific_constants() {
+        let mut calculator = calculator();
+        let mut value = |input: &str| match calculator.calculate(input).remove(0).data {
+            Ok((ResultData::Value(Value::Number(number)), _)) => number,
+            data => panic!("Expected a number, found {data:?}"),
+        };
+        let speed = value("c in km/h");
+        assert!((speed.number - 1_079_252_848.8).abs() < 1e-3);
+        assert_eq!(speed.unit.unwrap().to_string(), "km/h");
+        assert_eq!(value("2h").unit.unwrap().to_string(), "h");
+        assert_eq!(value("3G").number, 3e9);
+        assert_eq!(value("2N_A").number, 2.0 * 6.022_140_76e23);
+
+        let mut results = calculator.calculate("c := 3\nc * 2");
+        assert!(matches!(&results[0].warnings[0].warning, common::WarningType::ConstantShadowed(name) if name == "c"));
+        assert_eq!(results[0].warnings[0].ranges, vec![range!(line 0 => 0..1)]);
+        assert!(results[1].warnings.is_empty());
+        assert!(matches!(results.remove(1).data, Ok((ResultData::Value(Value::Number(number)), _)) if number.number == 6.0));
+
+        let mut results = calculator.calculate("m := 5\n2m\nx := 1");
+        assert!(matches!(&results[0].warnings[0].warning, common::WarningType::UnitShadowed(name) if name == "m"));
+        assert!(results[0].warnings[0].warning.is_hint());
+        assert!(results[2].warnings.is_empty());
+        assert!(matches!(results.remove(1).data, Ok((ResultData::Value(Value::Number(number)), _)) if number.number == 10.0));
+    }
+
+    #[test]
+    fn sequential_precedence() {
+        let mut calculator = calculator();
+        let mut number = |input: &str| match calculator.calculate(input).remove(0).data {
+            Ok((ResultData::Value(Value::Number(number)), _)) => number.number,
+            data => panic!("Expected a number, found {data:?}"),
+        };
+        assert_eq!(number("2 + 3 * 4"), 14.0);
+        assert_eq!(number("2 + 3 * 4 in sequential"), 20.0);
+        assert_eq!(number("2 * (1 + 2 * 3) - sin(0 + 2 * 0) in sequential"), 18.0);
+        assert_eq!(number("50% of 2 + 3 * 2 in sequential"), 5.0);
+
+        calculator.context.borrow_mut().settings.precedence = Precedence::Sequential;
+        let mut number = |input: &str| match calculator.calculate(input).remove(0).data {
+            Ok((ResultData::Value(Value::Number(number)), _)) => number.number,
+            data => panic!("Expected a number, found {data:?}"),
+        };
+        assert_eq!(number("2 + 3 * 4"), 20.0);
+        assert_eq!(number("2 + 3 * 4 in standard"), 14.0);
+        assert_eq!(number("[1 + 1 * 3; 2](0)"), 6.0);
+        assert_eq!(number("3e2 + 1"), 301.0);
+    }
+
+    #[test]
+    fn tight_implicit_multiplication() {
+        let mut calculator = calculator();
+        let mut number = |input: &str| match calculator.calculate(input).remove(0).data {
+            Ok((ResultData::Value(Value::Number(number)), _)) => number.number,
+            data => panic!("Expected a number, found {data:?}"),
+        };
+        assert_eq!(number("1/2pi"), std::f64::consts::PI / 2.0);
+        calculator.calculate("x := 4");
+
+        calculator.context.borrow_mut().settings.tight_implicit_multiplication = true;
+        let mut number = |input: &str| match calculator.calculate(input).remove(0).data {
+            Ok((ResultData::Value(Value::Number(number)), _)) => number.number,
+            data => panic!("Expected a number, found {data:?}"),
+        };
+        assert_eq!(number("1/2pi"), 1.0 / (2.0 * std::f64::consts::PI));
+        assert_eq!(number("6/2(1 + 2)"), 1.0);
+        assert_eq!(number("6/2 * (1 + 2)"), 9.0);
+        assert_eq!(number("2(3)"), 6.0);
+        assert_eq!(number("1/2e1"), 0.05);
+        assert_eq!(number("sin(0) + 8/2(2)"), 2.0);
+        assert_eq!(number("1/2x"), 0.125);
+        assert_eq!(number("2x^2"), 32.0);
+        assert_eq!(number("2 x^2"), 32.0);
+        assert_eq!(number("-2x^2"), -32.0);
+        assert_eq!(number("x^2(2)"), 32.0);
+        assert_eq!(number("1/2x^2"), 1.0 / 32.0);
+        assert_eq!(number("2^3x"), 32.0);
+        assert_eq!(number("2x!"), 48.0);
+    }
+
+    #[test]
+    fn malformed_input() {
+        let mut calculator = calculator();
+        for input in ["{date [", "[x := 2]", "sin(x := 2)", "[1) + 2]", "1 << -1", "1 << 100", "x ? = 2"] {
+            calculator.calculate(input);
+        }
+        assert!(calculator.calculate("1 << 100").remove(0).data.is_ok());
+    }
+
+    #[test]
+    fn profiling() {
+        let mut calculator = calculator();
+        let mut timings = vec![];
+        calculator.calculate_all("1 + 2\nfactorial(170)", |line| timings.push(line.timing));
+        assert_eq!(timings, [None, None]);
+
+        calculator.profiling = true;
+        let mut lines = vec![];
+        calculator.calculate_all("1 + 2\n\n3 *\n...4", |line| lines.push(line));
+        assert_eq!(lines.len(), 2);
+        assert!(lines.iter().all(|line| line.timing.is_some()));
+        assert_eq!(calculator.token_cache.line_durations.len(), 4);
+    }
+
+    #[test]
+    fn equality_check_difference() {
+        let mut calculator = calculator();
+        let settings = calculator.context.borrow().settings;
+        let mut difference = |input: &str| match calculator.calculate(input).remove(0).data {
+            Ok((ResultData::Boolean { difference, .. }, _)) => difference.map(|difference| difference.format(&settings, false)),
+            data => panic!("Expected a boolean, found {data:?}"),
+        };
+        assert_eq!(difference("5.02 = 5").as_deref(), Some("Δ = 0.02, 0.4%"));
+        assert_eq!(difference("1km = 1010m").as_deref(), Some("Δ = -0.01 Kilometers, -0.9900990099%"));
+        assert_eq!(difference("2 = 0").as_deref(), Some("Δ = 2"));
+        assert_eq!(difference("2 = 2"), None);
+        assert_eq!(difference("2 != 3"), None);
+        assert_eq!(difference("2m = 3"), None);
+    }
+
+    #[test]
+    fn postprocessors() {
+        let mut calculator = calculator();
+        let processed = Arc::new(Mutex::new(vec![]));
+        calculator.add_postprocessor(|data: &mut ResultData, _: &Range<usize>, notes: &mut Vec<String>| {
+            if let ResultData::Value(Value::Number(number)) = data {
+                number.number = number.number.round();
+                notes.push("rounded".to_owned());
+            }
+        });
+        let logged = processed.clone();
+        calculator.add_postprocessor(move |_: &mut ResultData, line_range: &Range<usize>, _: &mut Vec<String>| {
+            logged.lock().unwrap().push(line_range.start);
+        });
+
+        let results = calculator.calculate("1.4\nans * 2\n5 > 3\nx +");
+        let numbers = results.iter()
+            .map(|result| match &result.data {
+                Ok((ResultData::Value(Value::Number(number)), _)) => Some(number.number),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        // The following calculations use the result before it was rounded
+        assert_eq!(numbers, [Some(1.0), Some(3.0), None, None]);
+        assert_eq!(results[0].notes, ["rounded"]);
+        assert!(results[2].notes.is_empty());
+        // Failed calculations are not postprocessed
+        assert_eq!(*processed.lock().unwrap(), [0, 1, 2]);
+    }
+
+    #[test]
+    fn apply_custom_functions() {
+        let mut calculator = calculator();
+        let results = calculator.calculate("f(a, b, c, d) := a + b * c - d\napply(f, 1, 2, 3, 4)\napply(f, 1, 2, 3)");
+        assert!(matches!(&results[1].data, Ok((ResultData::Value(Value::Number(number)), _)) if number.number == 3.0));
+        assert!(matches!(&results[2].data, Err(error) if matches!(error.error, ErrorType::WrongNumberOfArguments(4))));
+        assert!(matches!(calculator.calculate("apply(sqrt)")[0].data, Err(ref error) if matches!(error.error, ErrorType::WrongNumberOfArgumentsAtLeast(2))));
+    }
+
+    #[test]
+    fn suppressed_results() {
+        let mut calculator = calculator();
+        let results = calculator.calculate("x := 3;\ny := [1; 2];\nx * (2 + ...\n1) @ignore\nx + 1");
+        let suppressed = results.iter().map(|result| result.suppressed).collect::<Vec<_>>();
+        assert_eq!(suppressed, [true, true, true, false]);
+        // The definitions still count
+        assert!(matches!(&results[3].data, Ok((ResultData::Value(Value::Number(number)), _)) if number.number == 4.0));
+        assert!(calculator.calculate("foo;")[0].data.is_err());
+        assert!(calculator.calculate("2 @ignore 3")[0].data.is_err());
+    }
+
+    #[test]
+    fn unknown_name_suggestions() {
+        let mut calculator = calculator();
+        let messages = calculator.calculate("sqr(4)\nradius := 2\n2 * radus\nfoo")
+            .into_iter()
+            .map(|result| result.data.err().map(|e| e.error.to_string()))
+            .collect::<Vec<_>>();
+        assert_eq!(messages, [
+            Some("Unknown function \"sqr\", did you mean \"sqrt\"?".to_owned()),
+            None,
+            Some("Unknown Identifier \"radus\", did you mean \"radius\"?".to_owned()),
+            Some("Unknown Identifier \"foo\"".to_owned()),
+        ]);
+    }
+
+    #[test]
+    fn leading_operators() {
+        let mut calculator = calculator();
+        let numbers = calculator.calculate("100\n* 1.19\n+ 5\n-3\n- 3\nx := 10\n/ 2\nlast + 1\n1 km\nin m\nabs(- 3)")
+            .into_iter()
+            .map(|result| match result.data {
+                Ok((ResultData::Value(Value::Number(number)), _)) => Some(number.number),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(numbers, [
+            Some(100.0), Some(119.0), Some(124.0),
+            // Signs without a space after them still start a number
+            Some(-3.0), Some(-6.0),
+            Some(10.0), Some(5.0), Some(6.0),
+            Some(1.0), Some(1000.0),
+            // Only lines can start with an operator, not arguments
+            Some(3.0),
+        ]);
+
+        // Without a result above, `last` isn't `ans`, which is left over from the last calculation
+        let error = |results: &[CalculatorResult], i: usize| results[i].data.as_ref().err().map(|error| error.error.clone());
+        assert!(matches!(error(&calculator.calculate("* 2"), 0), Some(ErrorType::NoPreviousLineResult)));
+        let results = calculator.calculate("1 / 0\nlast * 2");
+        assert!(matches!(error(&results, 1), Some(ErrorType::NoPreviousLineResult)));
     }
 }
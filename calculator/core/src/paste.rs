@@ -0,0 +1,189 @@
+/*
+ * Copyright (c) 2023, david072
+ *
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+use std::ops::Range;
+
+use crate::astgen::tokenizer::{tokenize_with_kinds, TokenKind};
+use crate::environment::currencies::CURRENCY_SYMBOLS;
+use crate::environment::dimensions::Dimension;
+
+/// A number found in arbitrary text by [extract_values]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PastedValue {
+    /// The number without thousands separators (e.g. `-1234.5`)
+    pub number: String,
+    /// The unit or currency of the number, including a percent sign (e.g. `km/h`, `$` or `%`)
+    pub unit: Option<String>,
+    /// The byte range of the value in the text
+    pub range: Range<usize>,
+}
+
+impl PastedValue {
+    /// Returns the value as an expression that can be calculated (e.g. `5 km/h` or `$5`)
+    pub fn expression(&self) -> String {
+        match self.unit.as_deref() {
+            Some(unit) if CURRENCY_SYMBOLS.contains(&unit) => match self.number.strip_prefix('-') {
+                Some(number) => format!("-{unit}{number}"),
+                None => format!("{unit}{}", self.number),
+            },
+            Some("%") => format!("{}%", self.number),
+            Some(unit) => format!("{} {unit}", self.number),
+            None => self.number.clone(),
+        }
+    }
+}
+
+/// How [paste_values] inserts the values it finds
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PasteMode {
+    /// Every value on its own line, keeping its unit
+    Lines,
+    /// All numbers in a single vector. Vectors only contain numbers, so units are left out.
+    Vector,
+}
+
+/// Finds all numbers in `text` (e.g. a copied table or e-mail), together with their units and
+/// currency symbols. Thousands separators are removed and everything that isn't a number is
+/// skipped, so that the values can be inserted into the calculator.
+pub fn extract_values(text: &str) -> Vec<PastedValue> {
+    let tokens = tokenize_with_kinds(text);
+    let token = |i: usize| tokens.get(i).map(|(range, kind)| (range.clone(), *kind, &text[range.clone()]));
+    let is_unit = |str: &str| CURRENCY_SYMBOLS.contains(&str) || Dimension::of_str(str).is_some();
+
+    let mut result = Vec::new();
+    let mut i = 0;
+    while i < tokens.len() {
+        let first = i;
+        let (range, kind, number) = token(i).unwrap();
+        i += 1;
+        if kind != TokenKind::Number { continue; }
+
+        let mut start = range.start;
+        let mut end = range.end;
+        let mut number = number.to_owned();
+
+        // Thousands separators (e.g. `1,234,567.8`)
+        if number.len() <= 3 && number.bytes().all(|b| b.is_ascii_digit()) {
+            while let (Some((comma, _, ",")), Some((group, TokenKind::Number, digits))) = (token(i), token(i + 1)) {
+                let integer_part = digits.split('.').next().unwrap_or_default();
+                if comma.start != end || group.start != comma.end || integer_part.len() != 3
+                    || !integer_part.bytes().all(|b| b.is_ascii_digit()) {
+                    break;
+                }
+                number += digits;
+                end = group.end;
+                i += 2;
+                if integer_part.len() != digits.len() { break; }
+            }
+        }
+
+        // A currency symbol in front of the number (e.g. `$5`)
+        let mut unit = None;
+        let mut before = first.checked_sub(1);
+        if let Some((symbol, TokenKind::Identifier, name)) = before.and_then(token) {
+            if symbol.end == start && CURRENCY_SYMBOLS.contains(&name) {
+                unit = Some(name.to_owned());
+                start = symbol.start;
+                before = before.and_then(|i| i.checked_sub(1));
+            }
+        }
+        // A minus right in front of the value, which isn't part of a range or date (e.g. `2023-01-05`)
+        if let Some((minus, _, "-")) = before.and_then(token) {
+            let is_separator = text[..minus.start].chars().next_back()
+                .map_or(false, |c| c.is_alphanumeric());
+            if minus.end == start && !is_separator {
+                number.insert(0, '-');
+                start = minus.start;
+            }
+        }
+
+        if unit.is_none() {
+            match token(i) {
+                Some((percent, TokenKind::Modifier, "%")) if percent.start == end => {
+                    unit = Some("%".to_owned());
+                    end = percent.end;
+                    i += 1;
+                }
+                // The unit is allowed to be separated by spaces, but not by line breaks
+                Some((range, TokenKind::Identifier, name)) if is_unit(name)
+                    && text[end..range.start].chars().all(|c| c == ' ') => {
+                    let unit_start = range.start;
+                    end = range.end;
+                    i += 1;
+
+                    // Compound units (e.g. `km/h`, `m^2` or `m²`, where the exponentiation is empty)
+                    loop {
+                        match (token(i), token(i + 1)) {
+                            (Some((op, TokenKind::Operator, "/" | "*")), Some((next, TokenKind::Identifier, name)))
+                            if op.start == end && next.start == op.end && is_unit(name) => end = next.end,
+                            (Some((op, TokenKind::Operator, "^" | "")), Some((next, TokenKind::Number, _)))
+                            if op.start == end && next.start == op.end => end = next.end,
+                            _ => break,
+                        }
+                        i += 2;
+                    }
+                    unit = Some(text[unit_start..end].to_owned());
+                }
+                _ => {}
+            }
+        }
+
+        result.push(PastedValue { number, unit, range: start..end });
+    }
+
+    result
+}
+
+/// Extracts the values from `text` using [extract_values] and returns them as text that can be
+/// inserted into the calculator's input
+pub fn paste_values(text: &str, mode: PasteMode) -> String {
+    let values = extract_values(text);
+    match mode {
+        PasteMode::Lines => values.iter()
+            .map(PastedValue::expression)
+            .collect::<Vec<_>>()
+            .join("\n"),
+        PasteMode::Vector if values.is_empty() => String::new(),
+        PasteMode::Vector => format!("[{}]", values.iter()
+            .map(|value| value.number.as_str())
+            .collect::<Vec<_>>()
+            .join("; ")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expressions(text: &str) -> Vec<String> {
+        extract_values(text).iter().map(PastedValue::expression).collect()
+    }
+
+    #[test]
+    fn numbers() {
+        assert_eq!(expressions("We sold 3 items for 1,234.50 each, -2 were returned"), ["3", "1234.50", "-2"]);
+        assert_eq!(expressions("-$5"), ["-$5"]);
+        assert_eq!(expressions("Meeting on 2023-01-05"), ["2023", "01", "05"]);
+        assert_eq!(expressions("1,2,3"), ["1", "2", "3"]);
+        assert!(expressions("no numbers here").is_empty());
+    }
+
+    #[test]
+    fn units() {
+        assert_eq!(expressions("drove 120 km/h for 2h, it cost $45 and 12€"), ["120 km/h", "2 h", "$45", "€12"]);
+        assert_eq!(expressions("a 20% discount on 3 apples"), ["20%", "3"]);
+        assert_eq!(expressions("5\nkm"), ["5"]);
+        assert_eq!(expressions("an area of 20 m² and 3 m^2"), ["20 m²", "3 m^2"]);
+    }
+
+    #[test]
+    fn modes() {
+        let text = "Prices: $3, $4.5 and 7 kg";
+        assert_eq!(paste_values(text, PasteMode::Lines), "$3\n$4.5\n7 kg");
+        assert_eq!(paste_values(text, PasteMode::Vector), "[3; 4.5; 7]");
+        assert_eq!(paste_values("none", PasteMode::Vector), "");
+    }
+}
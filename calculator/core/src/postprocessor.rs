@@ -0,0 +1,66 @@
+/*
+ * Copyright (c) 2023, david072
+ *
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+use std::ops::Range;
+use std::sync::{Arc, Mutex, PoisonError};
+
+use crate::ResultData;
+
+/// A hook that frontends install on a [Calculator](crate::Calculator) (using
+/// [Calculator::add_postprocessor](crate::Calculator::add_postprocessor)) to change or inspect
+/// results before they are formatted, e.g. to apply a custom rounding policy, add CO₂ equivalents
+/// or log the results.
+///
+/// It's implemented for closures taking the same arguments as [Postprocessor::process].
+pub trait Postprocessor {
+    /// Called with every successful result, which spans the (zero-based) lines `line_range` of
+    /// the source. Changing `data` only changes the result that is shown, not the value used by
+    /// the following calculations (e.g. through `ans` or line references). Text added to `notes`
+    /// is shown together with the result.
+    fn process(&mut self, data: &mut ResultData, line_range: &Range<usize>, notes: &mut Vec<String>);
+}
+
+impl<F: FnMut(&mut ResultData, &Range<usize>, &mut Vec<String>)> Postprocessor for F {
+    fn process(&mut self, data: &mut ResultData, line_range: &Range<usize>, notes: &mut Vec<String>) {
+        self(data, line_range, notes)
+    }
+}
+
+/// The postprocessors installed on a calculator, in the order they are called in. They are shared
+/// with the calculators created from it (e.g. by
+/// [Calculator::with_empty_environment](crate::Calculator::with_empty_environment)), which may be
+/// on other threads (e.g. the one the GUI calculates on).
+#[derive(Clone, Default)]
+pub struct Postprocessors(Vec<Arc<Mutex<dyn Postprocessor + Send>>>);
+
+impl Postprocessors {
+    pub fn add(&mut self, postprocessor: impl Postprocessor + Send + 'static) {
+        self.0.push(Arc::new(Mutex::new(postprocessor)));
+    }
+
+    pub fn clear(&mut self) { self.0.clear(); }
+
+    pub fn is_empty(&self) -> bool { self.0.is_empty() }
+
+    /// Calls all postprocessors with `data`
+    ///
+    /// **Returns:** The notes they added
+    pub(crate) fn process(&self, data: &mut ResultData, line_range: &Range<usize>) -> Vec<String> {
+        let mut notes = vec![];
+        for postprocessor in &self.0 {
+            // A postprocessor that panicked before is still called
+            let mut postprocessor = postprocessor.lock().unwrap_or_else(PoisonError::into_inner);
+            postprocessor.process(data, line_range, &mut notes);
+        }
+        notes
+    }
+}
+
+impl std::fmt::Debug for Postprocessors {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Postprocessors({})", self.0.len())
+    }
+}
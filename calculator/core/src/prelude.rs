@@ -0,0 +1,71 @@
+/*
+ * Copyright (c) 2023, david072
+ *
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+use thiserror::Error;
+
+use crate::{Calculator, Error};
+
+/// The directory in [crate::data_dir] containing the files of the prelude
+pub const PRELUDE_DIR: &str = "prelude";
+const SCRIPT_EXTENSION: &str = "rhai";
+
+/// An error that occurred while loading the prelude
+#[derive(Error, Debug)]
+pub enum PreludeError {
+    #[error("Failed to read {}: {1}", .0.display())]
+    Io(PathBuf, std::io::Error),
+    #[error("{}, line {}: {}", .0.display(), .1.ranges.first().map_or(0, |range| range.start_line) + 1, .1.error)]
+    Calculation(PathBuf, Error),
+    #[cfg(feature = "scripting")]
+    #[error("{}: {1}", .0.display())]
+    Script(PathBuf, crate::ScriptError),
+}
+
+/// Calculates every file in `dir` (in the order of their names) with `calculator`, so that their
+/// definitions end up in its environment. Files ending in `.rhai` are scripts, whose functions are
+/// added to the environment instead (if scripting is enabled). A missing directory is not an
+/// error, since the prelude is optional.
+pub(crate) fn load(calculator: &mut Calculator, dir: &Path) -> Vec<PreludeError> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == ErrorKind::NotFound => return vec![],
+        Err(e) => return vec![PreludeError::Io(dir.to_path_buf(), e)],
+    };
+
+    let mut paths = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.is_file())
+        .collect::<Vec<_>>();
+    paths.sort();
+
+    let mut errors = vec![];
+    for path in paths {
+        let source = match std::fs::read_to_string(&path) {
+            Ok(source) => source,
+            Err(e) => {
+                errors.push(PreludeError::Io(path, e));
+                continue;
+            }
+        };
+
+        if path.extension().map_or(false, |extension| extension == SCRIPT_EXTENSION) {
+            #[cfg(feature = "scripting")]
+            if let Err(e) = calculator.context.borrow_mut().env.add_script(&source) {
+                errors.push(PreludeError::Script(path, e));
+            }
+            continue;
+        }
+
+        for result in calculator.calculate(&source) {
+            errors.extend(result.errors().into_iter()
+                .map(|error| PreludeError::Calculation(path.clone(), error.clone())));
+        }
+    }
+    errors
+}
@@ -0,0 +1,51 @@
+/*
+ * Copyright (c) 2023, david072
+ *
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+use std::time::Duration;
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Instant;
+
+/// How long the steps of a calculation took, which [Calculator::calculate_all] records if
+/// [Calculator::profiling] is enabled
+///
+/// [Calculator::calculate_all]: crate::Calculator::calculate_all
+/// [Calculator::profiling]: crate::Calculator::profiling
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LineTiming {
+    /// Lines taken from the [TokenCache](crate::TokenCache) take (almost) no time
+    pub tokenization: Duration,
+    pub parsing: Duration,
+    pub evaluation: Duration,
+}
+
+impl LineTiming {
+    pub fn total(&self) -> Duration {
+        self.tokenization + self.parsing + self.evaluation
+    }
+}
+
+/// Measures the time since it was started. Time isn't available on the web, so nothing is
+/// measured there.
+pub(crate) struct Stopwatch {
+    #[cfg(not(target_arch = "wasm32"))]
+    start: Instant,
+}
+
+impl Stopwatch {
+    pub fn start() -> Self {
+        Self {
+            #[cfg(not(target_arch = "wasm32"))]
+            start: Instant::now(),
+        }
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        #[cfg(not(target_arch = "wasm32"))]
+        return self.start.elapsed();
+        #[cfg(target_arch = "wasm32")]
+        return Duration::ZERO;
+    }
+}
@@ -0,0 +1,129 @@
+/*
+ * Copyright (c) 2023, david072
+ *
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+use std::collections::HashSet;
+
+use crate::astgen::tokenizer::{tokenize, Token, TokenType};
+use crate::SourceRange;
+
+/// An identifier in a source that refers to a variable or function defined in the same source
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IdentifierReference {
+    pub name: String,
+    pub range: SourceRange,
+    /// Whether this is the name in the definition (e.g. `x` in `x := 5`) instead of a usage
+    pub is_definition: bool,
+}
+
+/// A definition of a variable or function in a line
+struct Definition<'a> {
+    name: &'a Token,
+    /// The names of the function's parameters, which refer to the arguments inside of the
+    /// definition instead of variables of the same name
+    parameters: Vec<&'a str>,
+}
+
+/// Finds the names of the variables and functions that are defined in `source` (using `:=`, `=:`
+/// or updates like `+=`) together with all of their usages, in the order they appear in. Lines
+/// that cannot be tokenized are skipped.
+pub fn identifier_references(source: &str) -> Vec<IdentifierReference> {
+    references_in_lines(&tokenize_lines(source))
+}
+
+/// Tokenizes every line of `source` on its own. Lines that cannot be tokenized are empty.
+pub(crate) fn tokenize_lines(source: &str) -> Vec<Vec<Token>> {
+    source.split('\n')
+        .map(|line| tokenize(line).unwrap_or_default())
+        .collect()
+}
+
+/// Like [identifier_references], but for lines that are already tokenized
+pub(crate) fn references_in_lines(lines: &[Vec<Token>]) -> Vec<IdentifierReference> {
+    let definitions = lines.iter().map(|tokens| definition(tokens)).collect::<Vec<_>>();
+    let defined_names = definitions.iter()
+        .flatten()
+        .map(|definition| definition.name.text.as_str())
+        .collect::<HashSet<_>>();
+
+    let mut references = vec![];
+    for (i, (tokens, definition)) in lines.iter().zip(&definitions).enumerate() {
+        for token in tokens {
+            if token.ty != TokenType::Identifier || !defined_names.contains(token.text.as_str()) { continue; }
+
+            let is_definition = definition.as_ref().map_or(false, |definition| std::ptr::eq(definition.name, token));
+            let is_parameter = definition.as_ref().map_or(false, |definition| definition.parameters.contains(&token.text.as_str()));
+            if is_parameter && !is_definition { continue; }
+
+            references.push(IdentifierReference {
+                name: token.text.to_string(),
+                range: SourceRange::line(i, token.range.start_char, token.range.end_char),
+                is_definition,
+            });
+        }
+    }
+    references
+}
+
+/// Finds the definition in the line consisting of `tokens`, if it is one
+fn definition(tokens: &[Token]) -> Option<Definition<'_>> {
+    let sign = tokens.iter().position(|token| matches!(token.ty,
+        TokenType::DefinitionSign | TokenType::PostfixDefinitionSign | TokenType::CompoundDefinitionSign))?;
+    let name_index = if tokens[sign].ty == TokenType::PostfixDefinitionSign { sign + 1 } else { 0 };
+    let name = tokens.get(name_index).filter(|token| token.ty == TokenType::Identifier)?;
+
+    let mut parameters = vec![];
+    if tokens.get(name_index + 1).map_or(false, |token| token.ty == TokenType::OpenBracket) {
+        parameters = tokens[name_index + 2..].iter()
+            .take_while(|token| token.ty != TokenType::CloseBracket)
+            .filter(|token| token.ty == TokenType::Identifier)
+            .map(|token| token.text.as_str())
+            .collect();
+    }
+    Some(Definition { name, parameters })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn references(source: &str) -> Vec<(String, usize, usize, bool)> {
+        identifier_references(source).into_iter()
+            .map(|reference| (reference.name, reference.range.start_line, reference.range.start_char, reference.is_definition))
+            .collect()
+    }
+
+    #[test]
+    fn variables() {
+        assert_eq!(references("a := 5\nb := a * 2 # a\n3 + a =: c\nc + d"), [
+            ("a".to_string(), 0, 0, true),
+            ("b".to_string(), 1, 0, true),
+            ("a".to_string(), 1, 5, false),
+            ("a".to_string(), 2, 4, false),
+            ("c".to_string(), 2, 9, true),
+            ("c".to_string(), 3, 0, false),
+        ]);
+    }
+
+    #[test]
+    fn updates() {
+        assert_eq!(references("a := 5\na += a"), [
+            ("a".to_string(), 0, 0, true),
+            ("a".to_string(), 1, 0, true),
+            ("a".to_string(), 1, 5, false),
+        ]);
+    }
+
+    #[test]
+    fn functions() {
+        assert_eq!(references("x := 2\nf(x) := x^2\nf(x) + x"), [
+            ("x".to_string(), 0, 0, true),
+            ("f".to_string(), 1, 0, true),
+            ("f".to_string(), 2, 0, false),
+            ("x".to_string(), 2, 2, false),
+            ("x".to_string(), 2, 7, false),
+        ]);
+    }
+}
@@ -8,6 +8,11 @@ use std::error::Error;
 use std::fmt::{Display, Formatter};
 use std::str::FromStr;
 
+use chrono::{FixedOffset, Local, NaiveDateTime, Offset, TimeZone as _};
+
+use crate::environment::currencies::{CURRENCY_SYMBOLS, is_currency};
+use crate::Format;
+
 pub enum AccessError {
     InvalidPath(&'static [&'static str]),
     Error(Box<dyn Error>),
@@ -26,11 +31,17 @@ macro_rules! settable {
     ($name:ident {
         $($field:ident: $field_ty:ty),+
     }) => {
-        #[derive(Clone, Copy, serde::Serialize, serde::Deserialize)]
+        // Fields missing from settings saved by older versions get their default values
+        #[derive(Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+        #[serde(default)]
         pub struct $name {
             $(pub $field: $field_ty),+
         }
 
+        impl Default for $name {
+            fn default() -> Self { $name::default() }
+        }
+
         impl $name {
             pub fn set(&mut self, path: &[&str], value: &str) -> Result<(), AccessError> {
                 if path.is_empty() { return Err(AccessError::InvalidPath(&[$(stringify!($field)),+])); }
@@ -57,15 +68,21 @@ macro_rules! settable {
         $($field:ident: $field_ty:ty,)*
         $([end] $end_field:ident: $end_field_ty:ty,)+
     }) => {
-        #[derive(Clone, Copy, serde::Serialize, serde::Deserialize)]
+        // Fields missing from settings saved by older versions get their default values
+        #[derive(Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+        #[serde(default)]
         pub struct $name {
             $(pub $field: $field_ty,)*
             $(pub $end_field: $end_field_ty),+
         }
 
+        impl Default for $name {
+            fn default() -> Self { $name::default() }
+        }
+
         impl $name {
             pub fn set(&mut self, path: &[&str], value: &str) -> Result<(), AccessError> {
-                const OPTIONS: &[&str] = &[$(stringify!($field)),* $(stringify!($end_field)),+];
+                const OPTIONS: &[&str] = &[$(stringify!($field),)* $(stringify!($end_field)),+];
                 if path.is_empty() { return Err(AccessError::InvalidPath(OPTIONS)); }
                 match path[0] {
                     $(
@@ -86,7 +103,7 @@ macro_rules! settable {
             }
 
             pub fn get(&self, path: &[&str]) -> Result<String, AccessError> {
-                const OPTIONS: &[&str] = &[$(stringify!($field)),* $(stringify!($end_field)),+];
+                const OPTIONS: &[&str] = &[$(stringify!($field),)* $(stringify!($end_field)),+];
                 if path.is_empty() { return Err(AccessError::InvalidPath(OPTIONS)); }
                 match path[0] {
                     $(
@@ -176,10 +193,101 @@ impl DateFormat {
     }
 }
 
+#[derive(Debug)]
+pub struct ParseTimeZoneError;
+
+impl Error for ParseTimeZoneError {}
+
+impl Display for ParseTimeZoneError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Invalid input. Expected \"local\" or an offset from UTC (e.g. \"UTC+2\" or \"UTC-5:30\")")
+    }
+}
+
+/// The time zone in which times are shown
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, serde::Serialize, serde::Deserialize)]
+pub enum TimeZone {
+    /// The time zone of the system
+    Local,
+    /// A fixed offset from UTC in minutes (e.g. `120` for UTC+2)
+    Utc(i32),
+}
+
+impl Display for TimeZone {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Local => write!(f, "Local"),
+            Self::Utc(0) => write!(f, "UTC"),
+            Self::Utc(offset) => {
+                let sign = if *offset < 0 { '-' } else { '+' };
+                let (hours, minutes) = (offset.abs() / 60, offset.abs() % 60);
+                if minutes == 0 {
+                    write!(f, "UTC{sign}{hours}")
+                } else {
+                    write!(f, "UTC{sign}{hours}:{minutes:02}")
+                }
+            }
+        }
+    }
+}
+
+impl FromStr for TimeZone {
+    type Err = ParseTimeZoneError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim().to_lowercase();
+        if s == "local" { return Ok(Self::Local); }
+
+        let offset = s.strip_prefix("utc").ok_or(ParseTimeZoneError)?;
+        if offset.is_empty() { return Ok(Self::Utc(0)); }
+
+        let (sign, offset) = match offset.split_at(1) {
+            ("+", offset) => (1, offset),
+            ("-", offset) => (-1, offset),
+            _ => return Err(ParseTimeZoneError),
+        };
+        let (hours, minutes) = offset.split_once(':').unwrap_or((offset, "0"));
+        let (Ok(hours), Ok(minutes)) = (hours.parse::<i32>(), minutes.parse::<i32>()) else {
+            return Err(ParseTimeZoneError);
+        };
+        Self::from_minutes(sign * (hours * 60 + minutes)).ok_or(ParseTimeZoneError)
+    }
+}
+
+impl TimeZone {
+    pub const fn default() -> Self {
+        Self::Local
+    }
+
+    /// Returns the time zone that is `offset` minutes ahead of UTC, if it's between UTC-14 and
+    /// UTC+14
+    pub fn from_minutes(offset: i32) -> Option<Self> {
+        (-14 * 60..=14 * 60).contains(&offset).then_some(Self::Utc(offset))
+    }
+
+    /// Converts a time in this time zone into UTC. Returns [None] if the time doesn't exist (e.g.
+    /// when skipped by daylight saving time).
+    pub fn to_utc(&self, time: NaiveDateTime) -> Option<NaiveDateTime> {
+        match self {
+            Self::Local => Local.from_local_datetime(&time).earliest().map(|time| time.naive_utc()),
+            Self::Utc(offset) => time.checked_sub_signed(chrono::Duration::minutes(*offset as i64)),
+        }
+    }
+
+    /// The offset from UTC at the time `utc`
+    pub fn offset_at(&self, utc: NaiveDateTime) -> FixedOffset {
+        match self {
+            Self::Local => Local.offset_from_utc_datetime(&utc).fix(),
+            Self::Utc(offset) => FixedOffset::east_opt(offset * 60).unwrap(),
+        }
+    }
+}
+
 settable!(
     DateSettings {
         [end] format: DateFormat,
         [end] delimiter: char,
+        [end] timezone: TimeZone,
     }
 );
 
@@ -188,16 +296,590 @@ impl DateSettings {
         Self {
             format: DateFormat::default(),
             delimiter: '.',
+            timezone: TimeZone::default(),
+        }
+    }
+}
+
+settable!(
+    FormatStyle {
+        [end] spaces_around_operators: bool,
+        [end] lowercase_hex: bool,
+        [end] canonical_units: bool,
+        [end] keep_literal_width: bool,
+    }
+);
+
+impl FormatStyle {
+    pub const fn default() -> Self {
+        Self {
+            spaces_around_operators: true,
+            lowercase_hex: false,
+            canonical_units: true,
+            keep_literal_width: false,
         }
     }
 }
 
-settable!(Settings { date: DateSettings });
+#[derive(Debug)]
+pub struct ParseInputModeError(&'static [&'static str]);
+
+impl Error for ParseInputModeError {}
+
+impl Display for ParseInputModeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Invalid input. Options: {:?}", self.0)
+    }
+}
+
+/// The notation used to enter calculations
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum InputMode {
+    /// Normal notation (e.g. `(3 + 4) * 2`)
+    Infix,
+    /// Reverse polish notation (e.g. `3 4 + 2 *`)
+    Rpn,
+}
+
+impl Display for InputMode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Infix => write!(f, "Infix"),
+            Self::Rpn => write!(f, "RPN"),
+        }
+    }
+}
+
+impl FromStr for InputMode {
+    type Err = ParseInputModeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "infix" => Ok(Self::Infix),
+            "rpn" => Ok(Self::Rpn),
+            _ => Err(ParseInputModeError(&["infix", "rpn"])),
+        }
+    }
+}
+
+impl InputMode {
+    pub const fn default() -> Self {
+        Self::Infix
+    }
+}
+
+#[derive(Debug)]
+pub struct ParsePrecedenceError(&'static [&'static str]);
+
+impl Error for ParsePrecedenceError {}
+
+impl Display for ParsePrecedenceError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Invalid input. Options: {:?}", self.0)
+    }
+}
+
+/// The order in which the operators of a calculation are evaluated
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum Precedence {
+    /// Operators are evaluated by their precedence (e.g. `2 + 3 * 4` = 14)
+    Standard,
+    /// Operators are evaluated strictly from left to right, like on a basic calculator
+    /// (e.g. `2 + 3 * 4` = 20). `of` and `in` are still evaluated last.
+    Sequential,
+}
+
+impl Display for Precedence {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Standard => write!(f, "Standard"),
+            Self::Sequential => write!(f, "Sequential"),
+        }
+    }
+}
+
+impl FromStr for Precedence {
+    type Err = ParsePrecedenceError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "standard" => Ok(Self::Standard),
+            "sequential" => Ok(Self::Sequential),
+            _ => Err(ParsePrecedenceError(&["standard", "sequential"])),
+        }
+    }
+}
+
+impl Precedence {
+    pub const fn default() -> Self {
+        Self::Standard
+    }
+}
+
+#[derive(Debug)]
+pub struct ParseFormatError(&'static [&'static str]);
+
+impl Error for ParseFormatError {}
+
+impl Display for ParseFormatError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Invalid input. Options: {:?}", self.0)
+    }
+}
+
+impl FromStr for Format {
+    type Err = ParseFormatError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "decimal" => Ok(Self::Decimal),
+            "hex" => Ok(Self::Hex),
+            "binary" => Ok(Self::Binary),
+            "scientific" => Ok(Self::Scientific),
+            _ => Err(ParseFormatError(&["decimal", "hex", "binary", "scientific"])),
+        }
+    }
+}
+
+/// More decimal places than this are beyond the precision of an f64
+pub const MAX_DECIMAL_PLACES: u8 = 15;
+
+#[derive(Debug)]
+pub struct ParseResultDisplayError(&'static [&'static str]);
+
+impl Error for ParseResultDisplayError {}
+
+impl Display for ParseResultDisplayError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Invalid input. Options: {:?}", self.0)
+    }
+}
+
+/// What results show of their values
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum ResultDisplay {
+    /// The number rounded to the decimal places, together with its abbreviated unit (e.g. `5 km/h`)
+    Rounded,
+    /// All digits of the number, which shows floating point errors (e.g. `0.30000000000000004`)
+    FullPrecision,
+    /// The rounded number with its unit spelled out (e.g. `5 Kilometers per Hour`)
+    LongUnit,
+    /// The value as it is stored internally, for debugging
+    Debug,
+}
+
+impl Display for ResultDisplay {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Rounded => write!(f, "Rounded"),
+            Self::FullPrecision => write!(f, "Full precision"),
+            Self::LongUnit => write!(f, "Long unit"),
+            Self::Debug => write!(f, "Debug"),
+        }
+    }
+}
+
+impl FromStr for ResultDisplay {
+    type Err = ParseResultDisplayError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "rounded" => Ok(Self::Rounded),
+            "full_precision" | "full precision" => Ok(Self::FullPrecision),
+            "long_unit" | "long unit" => Ok(Self::LongUnit),
+            "debug" => Ok(Self::Debug),
+            _ => Err(ParseResultDisplayError(&["rounded", "full_precision", "long_unit", "debug"])),
+        }
+    }
+}
+
+impl ResultDisplay {
+    pub const ALL: [ResultDisplay; 4] = [Self::Rounded, Self::FullPrecision, Self::LongUnit, Self::Debug];
+
+    pub const fn default() -> Self {
+        Self::Rounded
+    }
+}
+
+settable!(
+    OutputSettings {
+        [end] default_format: Format,
+        [end] decimal_places: u8,
+        [end] pad_trailing_zeros: bool,
+        [end] auto_scientific: bool,
+        [end] auto_prefix: bool,
+        [end] group_digits: bool,
+        [end] pad_to_word_size: bool,
+        [end] display: ResultDisplay,
+    }
+);
+
+/// How results are shown. The default format is used for results that don't have one of their
+/// own (e.g. through `in hex` or a hex literal). Decimal numbers are padded with zeros to the
+/// decimal places if `pad_trailing_zeros` is set, and shown in scientific notation if they are very
+/// large or small and `auto_scientific` is set. With `auto_prefix`, results with a unit like `m` or
+/// `V` get the SI prefix that makes them easiest to read (e.g. `1.5 km` instead of `1500 m`), unless
+/// the calculation converts into a unit. Hex and binary results have their digits grouped in fours
+/// (e.g. `0xFFFF_FFFF`) with `group_digits`, and are padded with zeros to the word size with
+/// `pad_to_word_size`. `display` decides what else results show (e.g. all digits or the spelled
+/// out unit).
+impl OutputSettings {
+    pub const fn default() -> Self {
+        Self {
+            default_format: Format::Decimal,
+            decimal_places: 10,
+            pad_trailing_zeros: false,
+            auto_scientific: false,
+            auto_prefix: false,
+            group_digits: false,
+            pad_to_word_size: false,
+            display: ResultDisplay::default(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ParseCurrencyCodeError(String);
+
+impl Error for ParseCurrencyCodeError {}
+
+impl Display for ParseCurrencyCodeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Unknown currency \"{}\". Expected an ISO 4217 code (e.g. USD)", self.0)
+    }
+}
+
+/// The ISO 4217 code of a currency (e.g. `USD`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct CurrencyCode([u8; 3]);
+
+impl CurrencyCode {
+    pub const EURO: Self = Self(*b"EUR");
+
+    pub fn as_str(&self) -> &str {
+        // Only created from valid currency codes, which are ASCII
+        std::str::from_utf8(&self.0).unwrap()
+    }
+}
+
+impl Display for CurrencyCode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl FromStr for CurrencyCode {
+    type Err = ParseCurrencyCodeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let code = s.to_uppercase();
+        match <[u8; 3]>::try_from(code.as_bytes()) {
+            Ok(bytes) if is_currency(&code) => Ok(Self(bytes)),
+            _ => Err(ParseCurrencyCodeError(s.to_string())),
+        }
+    }
+}
+
+settable!(
+    CurrencySettings {
+        [end] dollar: CurrencyCode,
+        [end] pound: CurrencyCode,
+        [end] yen: CurrencyCode,
+        [end] money_format: bool,
+        [end] vat_rate: f64,
+    }
+);
+
+/// The currencies that the ambiguous currency symbols (`$`, `£` and `¥`) stand for. `€` always
+/// stands for euros. If `money_format` is set, amounts of money are shown with two decimal places
+/// and the currency's symbol (e.g. `$1,234.50`). `vat_rate` is the percentage used by `tax(x)` and
+/// `net(x)` when no rate is given.
+impl CurrencySettings {
+    pub const fn default() -> Self {
+        Self {
+            dollar: CurrencyCode(*b"USD"),
+            pound: CurrencyCode(*b"GBP"),
+            yen: CurrencyCode(*b"JPY"),
+            money_format: false,
+            vat_rate: 19.0,
+        }
+    }
+
+    /// The currency that `symbol` stands for, if it is a currency symbol
+    pub fn currency_of_symbol(&self, symbol: &str) -> Option<CurrencyCode> {
+        match symbol {
+            "$" => Some(self.dollar),
+            "€" => Some(CurrencyCode::EURO),
+            "£" => Some(self.pound),
+            "¥" => Some(self.yen),
+            _ => None,
+        }
+    }
+
+    /// The symbol that stands for `currency`, if there is one
+    pub fn symbol_of(&self, currency: &str) -> Option<&'static str> {
+        CURRENCY_SYMBOLS.into_iter()
+            .find(|symbol| self.currency_of_symbol(symbol).map_or(false, |code| code.as_str() == currency))
+    }
+}
+
+#[derive(Debug)]
+pub struct ParseDataPrefixesError(&'static [&'static str]);
+
+impl Error for ParseDataPrefixesError {}
+
+impl Display for ParseDataPrefixesError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Invalid input. Options: {:?}", self.0)
+    }
+}
+
+/// What SI prefixes mean for data units. Binary prefixes (e.g. `Kib`) are always powers of 1024.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum DataPrefixes {
+    /// Powers of 1000 (e.g. `kb` = 1000 bytes)
+    Si,
+    /// Powers of 1024 (e.g. `kb` = 1024 bytes)
+    Binary,
+}
+
+impl Display for DataPrefixes {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Si => write!(f, "SI"),
+            Self::Binary => write!(f, "Binary"),
+        }
+    }
+}
+
+impl FromStr for DataPrefixes {
+    type Err = ParseDataPrefixesError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "si" => Ok(Self::Si),
+            "binary" => Ok(Self::Binary),
+            _ => Err(ParseDataPrefixesError(&["si", "binary"])),
+        }
+    }
+}
+
+impl DataPrefixes {
+    pub const fn default() -> Self {
+        Self::Si
+    }
+}
+
+#[derive(Debug)]
+pub struct ParseWordSizeError(&'static [&'static str]);
+
+impl Error for ParseWordSizeError {}
+
+impl Display for ParseWordSizeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Invalid input. Options: {:?}", self.0)
+    }
+}
+
+/// The number of bits the operands of integer operations (e.g. `&` or `<<`) have to fit into.
+/// Operands fit if they can be represented as either a signed or an unsigned integer of this size.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum WordSize {
+    /// Only limited by the integers an f64 can represent exactly
+    Unlimited,
+    Bits8,
+    Bits16,
+    Bits32,
+    Bits64,
+}
+
+impl Display for WordSize {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.bits() {
+            Some(bits) => write!(f, "{bits}"),
+            None => write!(f, "Unlimited"),
+        }
+    }
+}
+
+impl FromStr for WordSize {
+    type Err = ParseWordSizeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "unlimited" => Ok(Self::Unlimited),
+            "8" => Ok(Self::Bits8),
+            "16" => Ok(Self::Bits16),
+            "32" => Ok(Self::Bits32),
+            "64" => Ok(Self::Bits64),
+            _ => Err(ParseWordSizeError(&["unlimited", "8", "16", "32", "64"])),
+        }
+    }
+}
+
+impl WordSize {
+    pub const fn default() -> Self {
+        Self::Unlimited
+    }
+
+    pub const fn bits(&self) -> Option<u32> {
+        match self {
+            Self::Unlimited => None,
+            Self::Bits8 => Some(8),
+            Self::Bits16 => Some(16),
+            Self::Bits32 => Some(32),
+            Self::Bits64 => Some(64),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ParseModuloModeError(&'static [&'static str]);
+
+impl Error for ParseModuloModeError {}
+
+impl Display for ParseModuloModeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Invalid input. Options: {:?}", self.0)
+    }
+}
+
+/// How `mod` treats negative operands. Languages differ in this, so `-7 mod 3` is -1 when
+/// truncated (like `%` in C and Rust), 2 when floored (like `%` in Python) and 2 when euclidean.
+/// `rem` is always truncated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum ModuloMode {
+    /// The result has the sign of the dividend
+    Truncated,
+    /// The result has the sign of the divisor
+    Floored,
+    /// The result is never negative
+    Euclidean,
+}
+
+impl Display for ModuloMode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Truncated => write!(f, "Truncated"),
+            Self::Floored => write!(f, "Floored"),
+            Self::Euclidean => write!(f, "Euclidean"),
+        }
+    }
+}
+
+impl FromStr for ModuloMode {
+    type Err = ParseModuloModeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "truncated" => Ok(Self::Truncated),
+            "floored" => Ok(Self::Floored),
+            "euclidean" => Ok(Self::Euclidean),
+            _ => Err(ParseModuloModeError(&["truncated", "floored", "euclidean"])),
+        }
+    }
+}
+
+impl ModuloMode {
+    pub const fn default() -> Self {
+        Self::Truncated
+    }
+
+    /// Calculates `a mod b` with these semantics
+    pub fn apply(&self, a: f64, b: f64) -> f64 {
+        match self {
+            Self::Truncated => a % b,
+            Self::Floored => {
+                let remainder = a % b;
+                if remainder != 0.0 && (remainder < 0.0) != (b < 0.0) { remainder + b } else { remainder }
+            }
+            Self::Euclidean => a.rem_euclid(b),
+        }
+    }
+}
+
+settable!(
+    LimitSettings {
+        [end] max_evaluation_time: u64,
+        [end] max_recursion_depth: usize,
+        [end] max_factorial: u64,
+    }
+);
+
+/// Limits that keep single calculations from hanging the frontends. `max_evaluation_time` is the
+/// time in milliseconds a calculation can take, `max_recursion_depth` is how deeply calls of
+/// custom functions can be nested and `max_factorial` is the largest number whose factorial can be
+/// calculated.
+impl LimitSettings {
+    pub const fn default() -> Self {
+        Self {
+            max_evaluation_time: 5000,
+            max_recursion_depth: 500,
+            max_factorial: 1000,
+        }
+    }
+}
+
+settable!(
+    ToleranceSettings {
+        [end] absolute: f64,
+        [end] relative: f64,
+    }
+);
+
+/// How far apart the sides of an approximate equality check (`≈`) may be: at most `absolute` or
+/// `relative` times the larger side, whichever is larger. An explicit tolerance (e.g.
+/// `within 0.01`) is an absolute one.
+impl ToleranceSettings {
+    pub const fn default() -> Self {
+        Self {
+            absolute: 1e-9,
+            relative: 1e-3,
+        }
+    }
+
+    /// Whether `lhs` and `rhs` are within the tolerance of each other
+    pub fn are_close(&self, lhs: f64, rhs: f64) -> bool {
+        (lhs - rhs).abs() <= self.absolute.max(self.relative * lhs.abs().max(rhs.abs()))
+    }
+}
+
+settable!(
+    Settings {
+        date: DateSettings,
+        format: FormatStyle,
+        output: OutputSettings,
+        currency: CurrencySettings,
+        limits: LimitSettings,
+        tolerance: ToleranceSettings,
+        [end] input_mode: InputMode,
+        [end] precedence: Precedence,
+        [end] tight_implicit_multiplication: bool,
+        [end] data_prefixes: DataPrefixes,
+        [end] word_size: WordSize,
+        [end] modulo: ModuloMode,
+        [end] memoize_functions: bool,
+        [end] cache_constants: bool,
+    }
+);
 
 impl Settings {
     pub const fn default() -> Self {
         Self {
             date: DateSettings::default(),
+            format: FormatStyle::default(),
+            output: OutputSettings::default(),
+            currency: CurrencySettings::default(),
+            limits: LimitSettings::default(),
+            tolerance: ToleranceSettings::default(),
+            input_mode: InputMode::default(),
+            precedence: Precedence::default(),
+            tight_implicit_multiplication: false,
+            data_prefixes: DataPrefixes::default(),
+            word_size: WordSize::default(),
+            modulo: ModuloMode::default(),
+            memoize_functions: true,
+            cache_constants: true,
         }
     }
 }
@@ -0,0 +1,139 @@
+/*
+ * Copyright (c) 2023, david072
+ *
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+//! Calculates the `.fcly` files in `tests/corpus` and compares the results with the expected
+//! outputs written in them.
+//!
+//! Every line of a corpus file is calculated on its own, in the same calculator as the lines before
+//! it (so variables and functions can be defined and used in later lines). A line is written like
+//! `expression => expected output`. The expected output is the result as shown by the CLI (without
+//! the `= `), `True` or `False` for equality checks or `Error: <message>` for errors. Lines without
+//! `=>` (e.g. definitions) are calculated, but their result isn't checked. Empty lines and lines
+//! starting with `#` are skipped, and `set <setting> = <value>` changes a setting like in the CLI.
+//!
+//! Running the tests with `FUNCIALLY_BLESS=1` writes the actual results into the files instead of
+//! comparing them, which is useful after adding new cases (e.g. `2 + 2 =>`).
+
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+
+use funcially_core::{Calculator, Capabilities, ResultData, Settings, Verbosity};
+
+const CORPUS_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/corpus");
+const EXPECTED_SEPARATOR: &str = "=>";
+const BLESS_VARIABLE: &str = "FUNCIALLY_BLESS";
+
+/// A line whose result doesn't match the expected output
+struct Mismatch {
+    line: usize,
+    input: String,
+    expected: String,
+    actual: String,
+}
+
+fn corpus_files() -> Vec<PathBuf> {
+    let mut files = std::fs::read_dir(CORPUS_DIR)
+        .expect("Failed to read the corpus directory")
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().map_or(false, |extension| extension == "fcly"))
+        .collect::<Vec<_>>();
+    files.sort();
+    files
+}
+
+/// Calculates `input` and formats its result like the CLI
+fn calculate(calculator: &mut Calculator, input: &str) -> String {
+    let result = calculator.calculate(input).remove(0);
+    match result.data {
+        Ok((ResultData::Value(value) | ResultData::Slider { value, .. }, _)) => {
+            value.format(&calculator.context.borrow().settings, false).trim().to_string()
+        }
+        Ok((ResultData::Boolean { value, .. }, _)) => if value { "True" } else { "False" }.to_string(),
+        Ok((ResultData::Function { .. } | ResultData::FunctionRemoval(_) | ResultData::Nothing, _)) => String::new(),
+        Err(error) => format!("Error: {}", error.error),
+    }
+}
+
+/// Calculates the lines of `source`. Returns the lines that didn't match and `source` with the
+/// actual results.
+fn check_file(source: &str) -> (Vec<Mismatch>, String) {
+    // The results mustn't depend on the network or the clock, but the corpus defines variables
+    let capabilities = Capabilities { environment: true, ..Capabilities::none() };
+    let mut calculator = Calculator::with_capabilities(Verbosity::None, Settings::default(), capabilities);
+    let mut mismatches = vec![];
+    let mut blessed = String::new();
+
+    for (i, line) in source.lines().enumerate() {
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            writeln!(blessed, "{line}").unwrap();
+            continue;
+        }
+
+        if let Some(setting) = trimmed.strip_prefix("set ") {
+            let (path, value) = setting.split_once('=')
+                .unwrap_or_else(|| panic!("Line {}: Expected `set <setting> = <value>`", i + 1));
+            let path = path.trim().split('.').collect::<Vec<_>>();
+            if let Err(e) = calculator.context.borrow_mut().settings.set(&path, value.trim()) {
+                panic!("Line {}: Failed to change the setting: {e}", i + 1);
+            }
+            writeln!(blessed, "{line}").unwrap();
+            continue;
+        }
+
+        let Some((input, expected)) = line.rsplit_once(EXPECTED_SEPARATOR) else {
+            calculate(&mut calculator, trimmed);
+            writeln!(blessed, "{line}").unwrap();
+            continue;
+        };
+
+        let actual = calculate(&mut calculator, input.trim());
+        writeln!(blessed, "{input}{EXPECTED_SEPARATOR} {actual}").unwrap();
+        if actual != expected.trim() {
+            mismatches.push(Mismatch {
+                line: i + 1,
+                input: input.trim().to_string(),
+                expected: expected.trim().to_string(),
+                actual,
+            });
+        }
+    }
+
+    (mismatches, blessed)
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name().unwrap().to_string_lossy().to_string()
+}
+
+#[test]
+fn corpus() {
+    let bless = std::env::var_os(BLESS_VARIABLE).is_some();
+    let mut report = String::new();
+
+    for path in corpus_files() {
+        let source = std::fs::read_to_string(&path).unwrap();
+        let (mismatches, blessed) = check_file(&source);
+
+        if bless {
+            if blessed != source { std::fs::write(&path, blessed).unwrap(); }
+            continue;
+        }
+
+        for mismatch in mismatches {
+            writeln!(
+                report,
+                "{}:{}: {}\n    expected: {}\n    actual:   {}",
+                file_name(&path), mismatch.line, mismatch.input, mismatch.expected, mismatch.actual,
+            ).unwrap();
+        }
+    }
+
+    assert!(
+        report.is_empty(),
+        "Some results don't match the corpus (run with {BLESS_VARIABLE}=1 to update it):\n{report}",
+    );
+}
@@ -5,9 +5,11 @@
  */
 
 #![allow(dead_code)]
+#![allow(clippy::missing_safety_doc)]
 
 use std::alloc::{alloc, dealloc, Layout};
 use std::cell::RefCell;
+use std::collections::HashMap;
 use std::ffi::{c_double, CStr, CString};
 use std::mem;
 use std::ops::Range;
@@ -16,8 +18,8 @@ use std::rc::Rc;
 use std::sync::Arc;
 
 use funcially_core::{
-    Calculator, CalculatorResult, ContextData, Currencies, Environment, NumberValue,
-    Result as CalcResult, ResultData, Settings, SourceRange, Verbosity,
+    CancellationToken, Calculator, CalculatorResult, Capabilities, ConstantCache, ContextData, Currencies, Environment, NumberValue,
+    Postprocessors, Result as CalcResult, ResultData, Settings, SourceRange, TokenCache, Verbosity,
 };
 
 struct AllocatableContextData {
@@ -57,8 +59,16 @@ impl AllocatableCalculator {
                 env: self.context.env.clone(),
                 currencies: Arc::new(Currencies::new_load_only()),
                 settings: self.context.settings,
+                capabilities: Capabilities::default(),
+                cancellation: CancellationToken::default(),
+                constant_cache: ConstantCache::default(),
             })),
             verbosity: self.verbosity,
+            token_cache: TokenCache::default(),
+            base_env: Environment::new(),
+            slider_values: HashMap::new(),
+            profiling: false,
+            postprocessors: Postprocessors::default(),
         }
     }
 }
@@ -92,7 +102,7 @@ pub struct FfiVec<T> {
 impl<T> FfiVec<T> {
     fn empty() -> Self {
         Self {
-            array: 0 as *mut T,
+            array: std::ptr::null_mut(),
             len: 0,
         }
     }
@@ -208,9 +218,9 @@ fn result_to_ffi(
 ) -> FfiResultData {
     let str = calculator_result_to_string(&result.data, settings, use_thousands_separator);
     let cstr = CString::new(str).unwrap().into_raw();
-    let line_range = line_range_from_calculator_result(&result);
+    let line_range = line_range_from_calculator_result(result);
 
-    let mut function_name = 0 as *const c_char;
+    let mut function_name = std::ptr::null();
     let mut function_argument_count = 0usize;
     let mut function_was_defined = true;
 
@@ -240,15 +250,13 @@ fn result_to_ffi(
         function_argument_count,
         function_was_defined,
         is_error: result.data.is_err(),
-        error_ranges: if let Err(e) = &result.data {
-            e.ranges
-                .iter()
-                .map(common_c::SourceRange::from_core_source_range)
-                .collect::<Vec<_>>()
-                .into()
-        } else {
-            FfiVec::empty()
-        },
+        error_ranges: result
+            .errors()
+            .into_iter()
+            .flat_map(|e| &e.ranges)
+            .map(common_c::SourceRange::from_core_source_range)
+            .collect::<Vec<_>>()
+            .into(),
     }
 }
 
@@ -259,10 +267,16 @@ fn calculator_result_to_string(
 ) -> String {
     match data {
         Ok((data, _)) => match data {
-            ResultData::Value(number) => {
+            ResultData::Value(number) | ResultData::Slider { value: number, .. } => {
                 number.format(calculator_settings, use_thousands_separator)
             }
-            ResultData::Boolean(b) => (if *b { "True" } else { "False" }).to_string(),
+            ResultData::Boolean { value, difference } => {
+                let mut result = (if *value { "True" } else { "False" }).to_string();
+                if let Some(difference) = difference {
+                    result += &format!(" ({})", difference.format(calculator_settings, use_thousands_separator));
+                }
+                result
+            }
             _ => String::new(),
         },
         Err(e) => format!("{}", e.error),
@@ -287,7 +301,7 @@ pub unsafe extern "C" fn format(calculator: usize, input: *const c_char) -> *con
     let input = CStr::from_ptr(input);
     calc.0
         .format(input.to_str().unwrap())
-        .map_or(0 as *const c_char, |v| CString::new(v).unwrap().into_raw())
+        .map_or(std::ptr::null(), |v| CString::new(v).unwrap().into_raw())
 }
 
 #[no_mangle]
@@ -395,9 +409,10 @@ mod common_c {
             }
         }
 
-        pub(crate) unsafe fn to_core_settings(&self) -> funcially_core::Settings {
+        pub(crate) unsafe fn to_core_settings(self) -> funcially_core::Settings {
             funcially_core::Settings {
                 date: self.date.to_core_settings(),
+                ..funcially_core::Settings::default()
             }
         }
 
@@ -423,7 +438,7 @@ mod common_c {
             }
         }
 
-        pub(crate) unsafe fn to_core_settings(&self) -> funcially_core::DateSettings {
+        pub(crate) unsafe fn to_core_settings(self) -> funcially_core::DateSettings {
             funcially_core::DateSettings {
                 format: funcially_core::DateFormat::from_str(
                     CString::from_raw(self.format as *mut c_char)
@@ -432,6 +447,7 @@ mod common_c {
                 )
                 .unwrap(),
                 delimiter: self.delimiter as u8 as char,
+                ..funcially_core::DateSettings::default()
             }
         }
 
@@ -0,0 +1,106 @@
+/*
+ * Copyright (c) 2023, david072
+ *
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::Duration;
+
+use eframe::egui::Context;
+use funcially_core::{Calculator, Capabilities, Environment, ResultData, Settings, Verbosity};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+/// Copied text longer than this isn't calculated, since it's unlikely to be an expression
+const MAX_EXPRESSION_LENGTH: usize = 200;
+
+/// Watches the system clipboard on a background thread and calculates the copied text there, so
+/// that slow calculations don't freeze the UI. The most recent result is kept until it is taken.
+/// The thread stops when the watcher is dropped.
+pub struct ClipboardWatcher {
+    /// The copied expression and its result
+    result: Arc<Mutex<Option<(String, String)>>>,
+    /// The settings and whether to use a thousands separator for the next calculations
+    settings: Arc<Mutex<(Settings, bool)>>,
+    stop: Arc<AtomicBool>,
+}
+
+impl ClipboardWatcher {
+    /// Copied text is calculated in `env`, with capabilities for untrusted input, since it can come
+    /// from anywhere
+    pub fn spawn(ctx: Context, env: Environment) -> Self {
+        let result = Arc::new(Mutex::new(None));
+        let settings = Arc::new(Mutex::new((Settings::default(), false)));
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let shared_result = result.clone();
+        let shared_settings = settings.clone();
+        let should_stop = stop.clone();
+        thread::spawn(move || {
+            let Ok(mut clipboard) = arboard::Clipboard::new() else { return; };
+            let mut calculator = Calculator::with_capabilities(Verbosity::None, Settings::default(), Capabilities::sandboxed());
+            // Only text that's copied after the watcher was started is relevant
+            let mut last_text = clipboard.get_text().ok();
+
+            while !should_stop.load(Ordering::Relaxed) {
+                thread::sleep(POLL_INTERVAL);
+
+                // The clipboard might not contain text at all
+                let Ok(text) = clipboard.get_text() else { continue; };
+                if last_text.as_ref() == Some(&text) { continue; }
+                last_text = Some(text.clone());
+
+                let Ok((settings, use_thousands_separator)) = shared_settings.lock().map(|s| *s) else { return; };
+                {
+                    let mut context = calculator.context.borrow_mut();
+                    context.settings = settings;
+                    context.env = env.clone();
+                }
+                let expression = text.trim();
+                let Some(result) = calculate(&mut calculator, expression, use_thousands_separator) else { continue; };
+
+                let Ok(mut shared_result) = shared_result.lock() else { return; };
+                *shared_result = Some((expression.to_string(), result));
+                ctx.request_repaint();
+            }
+        });
+
+        Self { result, settings, stop }
+    }
+
+    /// Sets the settings used for the following calculations
+    pub fn set_settings(&self, settings: Settings, use_thousands_separator: bool) {
+        if let Ok(mut shared_settings) = self.settings.lock() {
+            *shared_settings = (settings, use_thousands_separator);
+        }
+    }
+
+    /// Returns the expression that was copied since the last call and its result, if there is one
+    pub fn take(&self) -> Option<(String, String)> {
+        self.result.lock().ok()?.take()
+    }
+}
+
+/// Calculates `expression` if it is a single calculation resulting in a value. Text that already is
+/// its own result (e.g. a plain number) is ignored.
+fn calculate(calculator: &mut Calculator, expression: &str, use_thousands_separator: bool) -> Option<String> {
+    if expression.is_empty() || expression.len() > MAX_EXPRESSION_LENGTH || expression.contains('\n') {
+        return None;
+    }
+
+    let mut results = calculator.calculate(expression);
+    if results.len() != 1 { return None; }
+
+    let Ok((ResultData::Value(value), _)) = results.remove(0).data else { return None; };
+    let settings = calculator.context.borrow().settings;
+    let result = value.format(&settings, use_thousands_separator).trim().to_string();
+    (result != expression).then_some(result)
+}
+
+impl Drop for ClipboardWatcher {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,202 @@
+/*
+ * Copyright (c) 2023, david072
+ *
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+#[cfg(not(target_arch = "wasm32"))]
+use std::collections::HashMap;
+#[cfg(not(target_arch = "wasm32"))]
+use std::sync::Arc;
+#[cfg(not(target_arch = "wasm32"))]
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender};
+use std::time::Duration;
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Instant;
+
+use eframe::egui::Context;
+use funcially_core::{Calculator, CancellationToken, LineResult};
+#[cfg(not(target_arch = "wasm32"))]
+use funcially_core::{CALCULATION_STACK_SIZE, Capabilities, Currencies, Environment, Postprocessors, Settings, Verbosity};
+
+/// Everything the background thread needs to calculate a revision of the source like the
+/// calculator of the UI would
+#[cfg(not(target_arch = "wasm32"))]
+struct Job {
+    revision: usize,
+    source: String,
+    settings: Settings,
+    capabilities: Capabilities,
+    currencies: Arc<Currencies>,
+    base_env: Environment,
+    slider_values: HashMap<String, f64>,
+    profiling: bool,
+    postprocessors: Postprocessors,
+    cancellation: CancellationToken,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+enum Message {
+    Line(usize, LineResult),
+    /// All lines of the revision were calculated, resulting in the environment
+    Finished(usize, Environment),
+}
+
+/// Calculates the source on a background thread, so that long calculations don't freeze the UI.
+/// The results are sent back line by line as soon as they are calculated. Every change of the
+/// source starts a new revision, which cancels the calculation of the previous one.
+///
+/// On the web, there are no threads, so the source is calculated right away.
+pub struct Evaluator {
+    revision: usize,
+    cancellation: CancellationToken,
+    /// The source of the current revision
+    source: String,
+    /// The results of the current revision that have been received so far
+    results: Vec<LineResult>,
+    is_finished: bool,
+    #[cfg(not(target_arch = "wasm32"))]
+    jobs: Sender<Job>,
+    #[cfg(not(target_arch = "wasm32"))]
+    messages: Receiver<Message>,
+}
+
+impl Evaluator {
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn spawn(ctx: Context) -> Self {
+        let (jobs, job_receiver) = channel::<Job>();
+        let (message_sender, messages) = channel();
+
+        // Deep recursions need more than the default stack size
+        let thread = std::thread::Builder::new().stack_size(CALCULATION_STACK_SIZE);
+        thread.spawn(move || {
+            let mut calculator = Calculator::with_capabilities(Verbosity::None, Settings::default(), Capabilities::none());
+            while let Ok(mut job) = job_receiver.recv() {
+                // Only the newest revision is relevant
+                while let Ok(newer_job) = job_receiver.try_recv() { job = newer_job; }
+
+                {
+                    let mut context = calculator.context.borrow_mut();
+                    context.settings = job.settings;
+                    context.capabilities = job.capabilities;
+                    context.currencies = job.currencies;
+                    context.cancellation = job.cancellation.clone();
+                }
+                calculator.base_env = job.base_env;
+                calculator.slider_values = job.slider_values;
+                calculator.profiling = job.profiling;
+                calculator.postprocessors = job.postprocessors;
+                calculator.reset();
+
+                calculator.calculate_all(&job.source, |line| {
+                    let _ = message_sender.send(Message::Line(job.revision, line));
+                    ctx.request_repaint();
+                });
+                if job.cancellation.is_cancelled() { continue; }
+
+                let env = calculator.context.borrow().env.clone();
+                if message_sender.send(Message::Finished(job.revision, env)).is_err() { return; }
+                ctx.request_repaint();
+            }
+        }).expect("failed to spawn the evaluation thread");
+
+        Self {
+            revision: 0,
+            cancellation: CancellationToken::default(),
+            source: String::new(),
+            results: Vec::new(),
+            is_finished: true,
+            jobs,
+            messages,
+        }
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    pub fn spawn(_ctx: Context) -> Self {
+        Self {
+            revision: 0,
+            cancellation: CancellationToken::default(),
+            source: String::new(),
+            results: Vec::new(),
+            is_finished: true,
+        }
+    }
+
+    /// Starts calculating `source` in the environment and with the settings of `calculator`,
+    /// cancelling the calculation of the previous revision
+    pub fn evaluate(&mut self, source: &str, calculator: &mut Calculator) {
+        self.cancellation.cancel();
+        self.cancellation = CancellationToken::default();
+        self.revision += 1;
+        self.source = source.to_owned();
+        self.results.clear();
+        self.is_finished = false;
+
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            let context = calculator.context.borrow();
+            let _ = self.jobs.send(Job {
+                revision: self.revision,
+                source: source.to_owned(),
+                settings: context.settings,
+                capabilities: context.capabilities,
+                currencies: context.currencies.clone(),
+                base_env: calculator.base_env.clone(),
+                slider_values: calculator.slider_values.clone(),
+                profiling: calculator.profiling,
+                postprocessors: calculator.postprocessors.clone(),
+                cancellation: self.cancellation.clone(),
+            });
+        }
+
+        #[cfg(target_arch = "wasm32")]
+        {
+            calculator.reset();
+            calculator.calculate_all(source, |line| self.results.push(line));
+            self.is_finished = true;
+        }
+    }
+
+    /// Receives the results of the current revision, waiting at most `timeout` for the calculation
+    /// to finish. Once it's finished, the environment of `calculator` is replaced by the one
+    /// resulting from the calculation. Returns whether new results were received.
+    #[cfg_attr(target_arch = "wasm32", allow(unused_variables))]
+    pub fn poll(&mut self, calculator: &mut Calculator, timeout: Duration) -> bool {
+        #[cfg(target_arch = "wasm32")]
+        return false;
+
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            let deadline = Instant::now() + timeout;
+            let mut received = false;
+            while !self.is_finished {
+                let message = match self.messages.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                    Ok(message) => message,
+                    Err(RecvTimeoutError::Timeout | RecvTimeoutError::Disconnected) => break,
+                };
+
+                match message {
+                    Message::Line(revision, line) if revision == self.revision => self.results.push(line),
+                    Message::Finished(revision, env) if revision == self.revision => {
+                        calculator.context.borrow_mut().env = env;
+                        self.is_finished = true;
+                    }
+                    // Results of outdated revisions
+                    _ => continue,
+                }
+                received = true;
+            }
+            received
+        }
+    }
+
+    /// The results of the current revision that have been calculated so far
+    pub fn results(&self) -> &[LineResult] { &self.results }
+
+    pub fn is_finished(&self) -> bool { self.is_finished }
+
+    /// The results of `source` if they have all been calculated
+    pub fn finished_results(&self, source: &str) -> Option<&[LineResult]> {
+        (self.is_finished && self.source == source).then_some(&self.results[..])
+    }
+}
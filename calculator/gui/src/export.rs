@@ -0,0 +1,480 @@
+/*
+ * Copyright (c) 2023, david072
+ *
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+use std::fmt::{Display, Formatter};
+use std::io::{Error, ErrorKind, Result};
+use std::ops::Range;
+use std::path::Path;
+
+use ab_glyph::{Font, FontRef, point, PxScale, ScaleFont};
+use eframe::egui::FontDefinitions;
+use image::{Rgb, RgbImage};
+
+use funcially_core::{Calculator, Function as CalcFn};
+
+const PLOT_X_RANGE: Range<f64> = -10.0..10.0;
+const PLOT_SAMPLES: usize = 512;
+/// Function values beyond this are left out, so that poles don't make the rest of the plot flat
+const PLOT_Y_LIMIT: f64 = 1000.0;
+/// The number of characters between the columns
+const COLUMN_GAP: usize = 3;
+
+type Color = [u8; 3];
+
+const NUMBER_COLOR: Color = [0x80, 0x80, 0x80];
+const INPUT_COLOR: Color = [0x00, 0x00, 0x00];
+const RESULT_COLOR: Color = [0x20, 0x70, 0x30];
+const ERROR_COLOR: Color = [0xD0, 0x20, 0x20];
+const AXIS_COLOR: Color = [0xA0, 0xA0, 0xA0];
+const CURVE_COLORS: [Color; 5] = [
+    [0x30, 0x60, 0xE0],
+    [0xE0, 0x50, 0x30],
+    [0x30, 0xA0, 0x50],
+    [0xA0, 0x40, 0xC0],
+    [0xE0, 0x90, 0x10],
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Pdf,
+    Png,
+}
+
+impl ExportFormat {
+    pub const ALL: [ExportFormat; 2] = [Self::Pdf, Self::Png];
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Pdf => "pdf",
+            Self::Png => "png",
+        }
+    }
+}
+
+impl Display for ExportFormat {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Pdf => write!(f, "PDF"),
+            Self::Png => write!(f, "PNG"),
+        }
+    }
+}
+
+/// The options of the export window
+pub struct ExportState {
+    pub format: ExportFormat,
+    pub include_plot: bool,
+    pub path: String,
+    /// The outcome of the last export
+    pub message: Option<std::result::Result<String, String>>,
+}
+
+impl Default for ExportState {
+    fn default() -> Self {
+        let dir = dirs::document_dir().or_else(dirs::home_dir).unwrap_or_default();
+        Self {
+            format: ExportFormat::Pdf,
+            include_plot: false,
+            path: dir.join("worksheet.pdf").display().to_string(),
+            message: None,
+        }
+    }
+}
+
+pub struct ExportLine {
+    pub number: usize,
+    pub input: String,
+    pub result: String,
+    pub is_error: bool,
+}
+
+pub struct PlotCurve {
+    name: String,
+    points: Vec<(f64, f64)>,
+}
+
+impl PlotCurve {
+    /// Samples the single argument function `f` over the x-range of the exported plot
+    pub fn new(calculator: &Calculator, name: String, f: &CalcFn) -> Self {
+        let points = calculator.sample_function(f, PLOT_X_RANGE.start..=PLOT_X_RANGE.end, PLOT_SAMPLES);
+        Self { name, points }
+    }
+}
+
+pub struct Worksheet {
+    pub lines: Vec<ExportLine>,
+    pub curves: Vec<PlotCurve>,
+}
+
+pub fn export(worksheet: &Worksheet, format: ExportFormat, path: &Path) -> Result<()> {
+    let layout = Layout::new(worksheet);
+    match format {
+        ExportFormat::Pdf => std::fs::write(path, pdf::render(&layout, &worksheet.curves)),
+        ExportFormat::Png => png::render(&layout, &worksheet.curves)?
+            .save(path)
+            .map_err(|e| Error::new(ErrorKind::Other, e)),
+    }
+}
+
+/// The worksheet laid out in columns of monospace characters (line numbers, inputs and results),
+/// which both formats are rendered from
+struct Layout<'a> {
+    lines: &'a [ExportLine],
+    number_width: usize,
+    input_width: usize,
+    result_width: usize,
+}
+
+impl<'a> Layout<'a> {
+    fn new(worksheet: &'a Worksheet) -> Self {
+        let width = |f: fn(&ExportLine) -> usize| worksheet.lines.iter().map(f).max().unwrap_or_default();
+        Self {
+            lines: &worksheet.lines,
+            number_width: width(|line| line.number.to_string().len()),
+            input_width: width(|line| line.input.chars().count()),
+            result_width: width(|line| line.result.chars().count()),
+        }
+    }
+
+    fn columns(&self) -> usize {
+        self.result_column() + self.result_width
+    }
+
+    fn input_column(&self) -> usize {
+        self.number_width + COLUMN_GAP
+    }
+
+    fn result_column(&self) -> usize {
+        self.input_column() + self.input_width + COLUMN_GAP
+    }
+
+    /// The texts of a line together with their columns and colors
+    fn segments(&self, line: &'a ExportLine) -> [(usize, String, Color); 3] {
+        [
+            (0, format!("{: >width$}", line.number, width = self.number_width), NUMBER_COLOR),
+            (self.input_column(), line.input.clone(), INPUT_COLOR),
+            (self.result_column(), line.result.clone(), if line.is_error { ERROR_COLOR } else { RESULT_COLOR }),
+        ]
+    }
+}
+
+/// The area of the function values that is plotted
+struct PlotBounds {
+    x: Range<f64>,
+    y: Range<f64>,
+}
+
+impl PlotBounds {
+    fn new(curves: &[PlotCurve]) -> Self {
+        let (min, max) = curves.iter()
+            .flat_map(|curve| &curve.points)
+            .map(|(_, y)| *y)
+            .filter(|y| y.is_finite() && y.abs() <= PLOT_Y_LIMIT)
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), y| (min.min(y), max.max(y)));
+
+        let y = if min > max {
+            PLOT_X_RANGE
+        } else if (max - min).abs() < f64::EPSILON {
+            min - 1.0..max + 1.0
+        } else {
+            min..max
+        };
+        Self { x: PLOT_X_RANGE, y }
+    }
+
+    /// Maps a point to coordinates relative to the plot area, where (0, 0) is the bottom left and
+    /// (1, 1) is the top right corner. Points outside of the plot result in [None].
+    fn map(&self, (x, y): (f64, f64)) -> Option<(f64, f64)> {
+        if !y.is_finite() || !self.y.contains(&y) && y != self.y.end { return None; }
+        Some((
+            (x - self.x.start) / (self.x.end - self.x.start),
+            (y - self.y.start) / (self.y.end - self.y.start),
+        ))
+    }
+
+    /// The parts of `curve` that can be drawn as connected lines, in relative coordinates
+    fn segments(&self, curve: &PlotCurve) -> Vec<Vec<(f64, f64)>> {
+        let mut segments = vec![vec![]];
+        for point in &curve.points {
+            match self.map(*point) {
+                Some(point) => segments.last_mut().unwrap().push(point),
+                None if !segments.last().unwrap().is_empty() => segments.push(vec![]),
+                None => {}
+            }
+        }
+        segments.retain(|segment| segment.len() > 1);
+        segments
+    }
+
+    /// The axes that lie in the plot, as lines in relative coordinates
+    fn axes(&self) -> Vec<[(f64, f64); 2]> {
+        [self.map((0.0, self.y.start)).zip(self.map((0.0, self.y.end))),
+            self.map((self.x.start, 0.0)).zip(self.map((self.x.end, 0.0)))]
+            .into_iter()
+            .flatten()
+            .map(|(start, end)| [start, end])
+            .collect()
+    }
+}
+
+mod pdf {
+    use super::*;
+
+    const PAGE_WIDTH: f64 = 595.0;
+    const PAGE_HEIGHT: f64 = 842.0;
+    const MARGIN: f64 = 50.0;
+    const MAX_FONT_SIZE: f64 = 10.0;
+    /// The width of a character of the Courier font relative to the font size
+    const CHAR_WIDTH: f64 = 0.6;
+    const LINE_SPACING: f64 = 1.4;
+
+    /// Renders the worksheet onto A4 pages using the standard Courier font, so that no font has to
+    /// be embedded. The font size shrinks for wide worksheets, so that every line fits onto the page.
+    pub fn render(layout: &Layout, curves: &[PlotCurve]) -> Vec<u8> {
+        let content_width = PAGE_WIDTH - 2.0 * MARGIN;
+        let font_size = (content_width / (layout.columns().max(1) as f64 * CHAR_WIDTH)).min(MAX_FONT_SIZE);
+        let line_height = font_size * LINE_SPACING;
+        let lines_per_page = ((PAGE_HEIGHT - 2.0 * MARGIN) / line_height) as usize;
+
+        let mut pages = layout.lines.chunks(lines_per_page.max(1))
+            .map(|lines| {
+                let mut content = String::new();
+                for (i, line) in lines.iter().enumerate() {
+                    let y = PAGE_HEIGHT - MARGIN - (i + 1) as f64 * line_height;
+                    for (column, text, color) in layout.segments(line) {
+                        let x = MARGIN + column as f64 * font_size * CHAR_WIDTH;
+                        content += &format!(
+                            "BT /F1 {font_size:.2} Tf {} rg {x:.2} {y:.2} Td ({}) Tj ET\n",
+                            rgb(color), escape(&text),
+                        );
+                    }
+                }
+                content
+            })
+            .collect::<Vec<_>>();
+
+        if !curves.is_empty() {
+            pages.push(plot(curves, content_width));
+        }
+        if pages.is_empty() {
+            pages.push(String::new());
+        }
+
+        document(&pages)
+    }
+
+    fn plot(curves: &[PlotCurve], width: f64) -> String {
+        let height = width * 0.75;
+        let (left, bottom) = (MARGIN, PAGE_HEIGHT - MARGIN - height);
+        let to_page = |(x, y): (f64, f64)| (left + x * width, bottom + y * height);
+        let path = |points: &[(f64, f64)]| points.iter()
+            .map(|point| to_page(*point))
+            .enumerate()
+            .map(|(i, (x, y))| format!("{x:.2} {y:.2} {}", if i == 0 { "m" } else { "l" }))
+            .collect::<Vec<_>>()
+            .join(" ") + " S\n";
+
+        let bounds = PlotBounds::new(curves);
+        let mut content = format!("0.5 w {} RG {left} {bottom} {width} {height} re S\n", rgb(AXIS_COLOR));
+        for axis in bounds.axes() {
+            content += &path(&axis);
+        }
+
+        content += "1 w\n";
+        for (i, curve) in curves.iter().enumerate() {
+            let color = CURVE_COLORS[i % CURVE_COLORS.len()];
+            content += &format!("{} RG\n", rgb(color));
+            for segment in bounds.segments(curve) {
+                content += &path(&segment);
+            }
+
+            let y = bottom - (i + 2) as f64 * MAX_FONT_SIZE * LINE_SPACING;
+            content += &format!("BT /F1 {MAX_FONT_SIZE} Tf {} rg {left} {y:.2} Td ({}) Tj ET\n", rgb(color), escape(&curve.name));
+        }
+
+        let y = bottom - MAX_FONT_SIZE * LINE_SPACING;
+        content += &format!(
+            "BT /F1 {MAX_FONT_SIZE} Tf {} rg {left} {y:.2} Td (x: {} to {}, y: {:.4} to {:.4}) Tj ET\n",
+            rgb(NUMBER_COLOR), bounds.x.start, bounds.x.end, bounds.y.start, bounds.y.end,
+        );
+        content
+    }
+
+    /// Assembles the PDF document with a page for every content stream in `pages`
+    fn document(pages: &[String]) -> Vec<u8> {
+        // 1: catalog, 2: page tree, 3: font, followed by every page and its content stream
+        let page_id = |i: usize| 4 + 2 * i;
+        let mut objects = vec![
+            "<< /Type /Catalog /Pages 2 0 R >>".to_string(),
+            format!(
+                "<< /Type /Pages /Kids [{}] /Count {} >>",
+                (0..pages.len()).map(|i| format!("{} 0 R", page_id(i))).collect::<Vec<_>>().join(" "),
+                pages.len(),
+            ),
+            "<< /Type /Font /Subtype /Type1 /BaseFont /Courier /Encoding /WinAnsiEncoding >>".to_string(),
+        ];
+        for (i, content) in pages.iter().enumerate() {
+            objects.push(format!(
+                "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {PAGE_WIDTH} {PAGE_HEIGHT}] \
+                /Resources << /Font << /F1 3 0 R >> >> /Contents {} 0 R >>",
+                page_id(i) + 1,
+            ));
+            objects.push(format!("<< /Length {} >>\nstream\n{content}endstream", content.len()));
+        }
+
+        let mut pdf = b"%PDF-1.4\n".to_vec();
+        let mut offsets = vec![];
+        for (i, object) in objects.iter().enumerate() {
+            offsets.push(pdf.len());
+            pdf.extend(format!("{} 0 obj\n{object}\nendobj\n", i + 1).bytes());
+        }
+
+        let xref_offset = pdf.len();
+        pdf.extend(format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1).bytes());
+        for offset in offsets {
+            pdf.extend(format!("{offset:010} 00000 n \n").bytes());
+        }
+        pdf.extend(format!(
+            "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{xref_offset}\n%%EOF\n",
+            objects.len() + 1,
+        ).bytes());
+        pdf
+    }
+
+    fn rgb(color: Color) -> String {
+        color.map(|c| format!("{:.3}", c as f64 / 255.0)).join(" ")
+    }
+
+    /// Escapes `text` for a PDF string. Characters outside of Latin-1 can't be displayed by the
+    /// standard fonts and are replaced with a question mark.
+    fn escape(text: &str) -> String {
+        let mut escaped = String::new();
+        for c in text.chars() {
+            match c {
+                '(' | ')' | '\\' => {
+                    escaped.push('\\');
+                    escaped.push(c);
+                }
+                ' '..='~' => escaped.push(c),
+                '\u{A0}'..='\u{FF}' => escaped += &format!("\\{:03o}", c as u32),
+                _ => escaped.push('?'),
+            }
+        }
+        escaped
+    }
+}
+
+mod png {
+    use super::*;
+
+    const FONT: &str = "Hack";
+    const FONT_SIZE: f32 = 16.0;
+    const LINE_SPACING: f32 = 1.4;
+    const PADDING: u32 = 20;
+    const PLOT_SIZE: (u32, u32) = (640, 480);
+    const BACKGROUND_COLOR: Color = [0xFF, 0xFF, 0xFF];
+
+    /// Renders the worksheet with egui's monospace font, followed by the plot
+    pub fn render(layout: &Layout, curves: &[PlotCurve]) -> Result<RgbImage> {
+        let fonts = FontDefinitions::default();
+        let font = fonts.font_data.get(FONT)
+            .ok_or_else(|| Error::new(ErrorKind::NotFound, "Font not found"))
+            .and_then(|data| FontRef::try_from_slice(&data.font).map_err(|e| Error::new(ErrorKind::InvalidData, e.to_string())))?;
+        let scaled_font = font.as_scaled(PxScale::from(FONT_SIZE));
+        let char_width = scaled_font.h_advance(font.glyph_id('0'));
+        let line_height = (scaled_font.height() * LINE_SPACING).ceil();
+
+        let text_width = (layout.columns() as f32 * char_width).ceil() as u32;
+        let text_height = layout.lines.len() as u32 * line_height as u32;
+        let plot_height = if curves.is_empty() { 0 } else { PLOT_SIZE.1 + PADDING };
+        let mut image = RgbImage::from_pixel(
+            text_width.max(PLOT_SIZE.0) + 2 * PADDING,
+            text_height + plot_height + 2 * PADDING,
+            Rgb(BACKGROUND_COLOR),
+        );
+
+        let mut canvas = Canvas { image: &mut image, font: &font, scale: PxScale::from(FONT_SIZE) };
+        for (i, line) in layout.lines.iter().enumerate() {
+            let baseline = PADDING as f32 + i as f32 * line_height + scaled_font.ascent();
+            for (column, text, color) in layout.segments(line) {
+                canvas.text(PADDING as f32 + column as f32 * char_width, baseline, &text, color);
+            }
+        }
+
+        if !curves.is_empty() {
+            canvas.plot(curves, PADDING as f32, (PADDING + text_height + PADDING) as f32, line_height);
+        }
+        Ok(image)
+    }
+
+    struct Canvas<'a> {
+        image: &'a mut RgbImage,
+        font: &'a FontRef<'a>,
+        scale: PxScale,
+    }
+
+    impl Canvas<'_> {
+        fn plot(&mut self, curves: &[PlotCurve], left: f32, top: f32, line_height: f32) {
+            let (width, height) = (PLOT_SIZE.0 as f32, PLOT_SIZE.1 as f32);
+            let to_image = |(x, y): (f64, f64)| (left + x as f32 * width, top + (1.0 - y as f32) * height);
+            let bounds = PlotBounds::new(curves);
+
+            let corners = [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0), (0.0, 0.0)];
+            self.polyline(&corners.map(to_image), AXIS_COLOR);
+            for axis in bounds.axes() {
+                self.polyline(&axis.map(to_image), AXIS_COLOR);
+            }
+
+            let ascent = self.font.as_scaled(self.scale).ascent();
+            for (i, curve) in curves.iter().enumerate() {
+                let color = CURVE_COLORS[i % CURVE_COLORS.len()];
+                for segment in bounds.segments(curve) {
+                    let points = segment.into_iter().map(to_image).collect::<Vec<_>>();
+                    self.polyline(&points, color);
+                }
+                self.text(left + 8.0, top + 8.0 + i as f32 * line_height + ascent, &curve.name, color);
+            }
+        }
+
+        fn text(&mut self, mut x: f32, baseline: f32, text: &str, color: Color) {
+            let scaled_font = self.font.as_scaled(self.scale);
+            for c in text.chars() {
+                let mut glyph = scaled_font.scaled_glyph(c);
+                glyph.position = point(x, baseline);
+                x += scaled_font.h_advance(glyph.id);
+
+                let Some(outline) = self.font.outline_glyph(glyph) else { continue; };
+                let bounds = outline.px_bounds();
+                outline.draw(|gx, gy, coverage| {
+                    self.blend(bounds.min.x as i64 + gx as i64, bounds.min.y as i64 + gy as i64, color, coverage);
+                });
+            }
+        }
+
+        fn polyline(&mut self, points: &[(f32, f32)], color: Color) {
+            for line in points.windows(2) {
+                let [(x0, y0), (x1, y1)] = [line[0], line[1]];
+                let steps = (x1 - x0).abs().max((y1 - y0).abs()).ceil().max(1.0);
+                for step in 0..=steps as usize {
+                    let t = step as f32 / steps;
+                    let (x, y) = (x0 + (x1 - x0) * t, y0 + (y1 - y0) * t);
+                    self.blend(x.round() as i64, y.round() as i64, color, 1.0);
+                }
+            }
+        }
+
+        /// Blends `color` with the pixel at (`x`, `y`), where `alpha` is the coverage of the pixel
+        fn blend(&mut self, x: i64, y: i64, color: Color, alpha: f32) {
+            if x < 0 || y < 0 || x >= self.image.width() as i64 || y >= self.image.height() as i64 { return; }
+
+            let alpha = alpha.clamp(0.0, 1.0);
+            let pixel = self.image.get_pixel_mut(x as u32, y as u32);
+            for (channel, c) in pixel.0.iter_mut().zip(color) {
+                *channel = (*channel as f32 * (1.0 - alpha) + c as f32 * alpha).round() as u8;
+            }
+        }
+    }
+}
@@ -0,0 +1,104 @@
+/*
+ * Copyright (c) 2023, david072
+ *
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+//! Editing a plain text file on disk instead of the app's own input. The file is watched for
+//! changes made by other programs, so that the app can be used to show the results of calculations
+//! that are edited somewhere else.
+
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use eframe::egui::Context;
+
+/// How often the file is checked for changes, in seconds
+const POLL_INTERVAL: f64 = 0.5;
+
+/// The options of the window for opening files
+pub struct OpenFileState {
+    pub path: String,
+    pub save_on_change: bool,
+    /// The error that occurred while opening, saving or reloading the file
+    pub error: Option<String>,
+}
+
+impl Default for OpenFileState {
+    fn default() -> Self {
+        let dir = dirs::document_dir().or_else(dirs::home_dir).unwrap_or_default();
+        Self {
+            path: dir.join("calculation.txt").display().to_string(),
+            save_on_change: false,
+            error: None,
+        }
+    }
+}
+
+/// A file that is edited in the input
+pub struct ExternalFile {
+    pub path: PathBuf,
+    /// Whether changes of the input are written to the file right away
+    pub save_on_change: bool,
+    /// The app's own source, which is shown again once the file is closed
+    pub own_source: String,
+    /// The contents of the file as of the last time it was read or written
+    pub contents: String,
+    /// The contents the file was changed to by another program while the input had unsaved
+    /// changes, if the user hasn't decided which ones to keep yet
+    pub changed_contents: Option<String>,
+    modified: Option<SystemTime>,
+    last_check: f64,
+}
+
+impl ExternalFile {
+    /// Opens the file at `path`. `own_source` is the source that was in the input before.
+    pub fn open(path: &Path, save_on_change: bool, own_source: String) -> std::io::Result<Self> {
+        let contents = std::fs::read_to_string(path)?;
+        Ok(Self {
+            path: path.to_owned(),
+            save_on_change,
+            own_source,
+            contents,
+            changed_contents: None,
+            modified: modified_time(path),
+            last_check: 0.0,
+        })
+    }
+
+    pub fn has_unsaved_changes(&self, source: &str) -> bool {
+        self.contents != source
+    }
+
+    /// Checks whether the file was changed by another program, at most every [POLL_INTERVAL]
+    /// seconds. `time` is the current time in seconds.
+    ///
+    /// **Returns:** The new contents of the file, if it was changed
+    pub fn poll(&mut self, ctx: &Context, time: f64) -> Option<String> {
+        ctx.request_repaint_after(Duration::from_secs_f64(POLL_INTERVAL));
+        if time - self.last_check < POLL_INTERVAL { return None; }
+        self.last_check = time;
+
+        let modified = modified_time(&self.path);
+        if modified == self.modified { return None; }
+        self.modified = modified;
+
+        let contents = std::fs::read_to_string(&self.path).ok()?;
+        if contents == self.contents { return None; }
+        Some(contents)
+    }
+
+    /// Writes `source` to the file
+    pub fn save(&mut self, source: &str) -> std::io::Result<()> {
+        std::fs::write(&self.path, source)?;
+        self.contents = source.to_owned();
+        self.changed_contents = None;
+        // The change was made by us, so it mustn't be reloaded
+        self.modified = modified_time(&self.path);
+        Ok(())
+    }
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
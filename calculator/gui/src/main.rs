@@ -7,23 +7,55 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")] // hide console window on Windows in release
 
 use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
 use std::ops::Range;
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 use eframe::{CreationContext, Frame, Storage};
 use eframe::egui;
 use eframe::egui::panel::PanelState;
-use eframe::egui::text::CCursorRange;
+use eframe::egui::text::{CCursor, CCursorRange};
 use eframe::egui::text_edit::{CursorRange, TextEditState};
 use eframe::epaint::Shadow;
-use eframe::epaint::text::cursor::Cursor;
+use eframe::epaint::text::cursor::{Cursor, PCursor};
 use egui::*;
 
-use funcially_core::{Calculator, CalculatorResult, Color as CalcColor, ColorSegment as CalcColorSegment, DateFormat, Function as CalcFn, Result as CalcResult, ResultData, Settings, Verbosity};
+use funcially_core::{Calculator, ColorSegment as CalcColorSegment, CurrencyCode, DataPrefixes, DateFormat, Document, DocumentCache, DocumentLine, DocumentLineKind, Format, Function as CalcFn, identifier_references, IdentifierReference, InputMode, MAX_DECIMAL_PLACES, ModuloMode, NumberValue, Precedence, Relation as CalcRelation, Result as CalcResult, ResultData, ResultDisplay, Settings, TimeZone, Verbosity, WordSize};
 
+use crate::evaluation::Evaluator;
 use crate::widgets::*;
+use crate::widgets::dependency_graph::show_dependency_graph;
+use crate::widgets::diff::{Diff, DiffLine, DiffResult};
+use crate::widgets::folding::{self, FoldedView};
+use crate::widgets::line_ids::LineIds;
+use crate::widgets::modal_editing::ModalEditor;
+use crate::widgets::profiling::ProfileTable;
+use crate::widgets::implicit_plot::ImplicitPlotCache;
+use crate::widgets::plot_axes::PlotAxes;
+#[cfg(not(target_arch = "wasm32"))]
+use funcially_core::PasteMode;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::clipboard_watcher::ClipboardWatcher;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::quick_calculator::QuickCalculator;
 
 mod widgets;
+mod evaluation;
+#[cfg(not(target_arch = "wasm32"))]
+mod clipboard_watcher;
+#[cfg(not(target_arch = "wasm32"))]
+mod export;
+#[cfg(not(target_arch = "wasm32"))]
+mod external_file;
+#[cfg(not(target_arch = "wasm32"))]
+mod quick_calculator;
+#[cfg(not(target_arch = "wasm32"))]
+mod recovery;
+#[cfg(target_arch = "wasm32")]
+mod share;
+mod plot_data;
+mod tutorial;
 
 #[cfg(not(target_arch = "wasm32"))]
 const GITHUB_TAGS_URL: &str = "https://api.github.com/repos/david072/funcially/tags";
@@ -33,10 +65,30 @@ const FONT_SIZE: f32 = 14.0;
 const FONT_ID: FontId = FontId::monospace(FONT_SIZE);
 const FOOTER_FONT_SIZE: f32 = 14.0;
 const TEXT_EDIT_MARGIN: Vec2 = Vec2::new(4.0, 2.0);
+/// The space between the end of a line and its inline result
+const INLINE_RESULT_SPACING: f32 = 16.0;
+/// How long the UI waits for the calculation of a changed source before showing the results
+/// calculated so far
+const EVALUATION_TIMEOUT: Duration = Duration::from_millis(30);
+/// How many conversions are suggested in the context menu of a result with a unit
+const CONVERSION_SUGGESTIONS: usize = 3;
+/// Windows narrower than this use the mobile layout, unless another layout is chosen
+const MOBILE_LAYOUT_MAX_WIDTH: f32 = 600.0;
+/// The minimum height of buttons in the mobile layout, so that they are easy to tap
+const TOUCH_TARGET_SIZE: f32 = 40.0;
+/// The characters in the button row of the mobile layout, which are hard to type on phones
+const TOUCH_KEYS: [&str; 9] = ["^", "(", ")", ":=", "=:", "#", "?", "%", "!"];
+/// The share of the window's width taken up by the results, until the divider is dragged
+const DEFAULT_OUTPUT_PANEL_FRACTION: f32 = 1.0 / 3.0;
+const RESULT_BELOW_LINE_FONT_ID: FontId = FontId::monospace(12.0);
 const ERROR_COLOR: Color32 = Color32::RED;
+const WARNING_COLOR: Color32 = Color32::from_rgb(0xE5, 0xC0, 0x7B);
+const HINT_COLOR: Color32 = Color32::from_rgb(0x5A, 0x9A, 0x5A);
 
 const INPUT_TEXT_EDIT_ID: &str = "input-text-edit";
+const LINE_NUMBERS_ID: &str = "line-numbers";
 const PLOT_PANEL_ID: &str = "plot_panel";
+const KEYPAD_PANEL_ID: &str = "keypad_panel";
 const OUTPUT_PANEL_ID: &str = "output_panel";
 const OUTPUT_PANEL_SCROLL_AREA_ID: &str = "output_panel_scroll_area";
 
@@ -46,9 +98,17 @@ const COPY_RESULT_SHORTCUT: KeyboardShortcut = KeyboardShortcut::new(Modifiers::
 const FORMAT_SHORTCUT: KeyboardShortcut = KeyboardShortcut::new(Modifiers::COMMAND.plus(Modifiers::ALT), Key::L);
 const LINE_PICKER_SHORTCUT: KeyboardShortcut = KeyboardShortcut::new(Modifiers::COMMAND, Key::G);
 const SEARCH_SHORTCUT: KeyboardShortcut = KeyboardShortcut::new(Modifiers::COMMAND, Key::F);
+const GO_TO_DEFINITION_SHORTCUT: KeyboardShortcut = KeyboardShortcut::new(Modifiers::NONE, Key::F12);
+const FIND_USAGES_SHORTCUT: KeyboardShortcut = KeyboardShortcut::new(Modifiers::SHIFT, Key::F12);
+const READ_RESULT_SHORTCUT: KeyboardShortcut = KeyboardShortcut::new(Modifiers::COMMAND.plus(Modifiers::ALT), Key::R);
+const PREVIOUS_RESULT_SHORTCUT: KeyboardShortcut = KeyboardShortcut::new(Modifiers::ALT, Key::ArrowUp);
+const NEXT_RESULT_SHORTCUT: KeyboardShortcut = KeyboardShortcut::new(Modifiers::ALT, Key::ArrowDown);
 
 const TAB_TEXT: &str = "    ";
 
+#[cfg(not(target_arch = "wasm32"))]
+const CLIPBOARD_RESULT_DURATION: f64 = 8.0;
+
 #[cfg(feature = "experimental")]
 fn app_key() -> String {
     eframe::APP_KEY.to_string() + "-experimental"
@@ -81,18 +141,36 @@ fn main() {
         }
     } else { None };
 
+    let icon_data = icon.map(|icon| {
+        let (icon_width, icon_height) = icon.dimensions();
+        eframe::IconData {
+            rgba: icon.into_raw(),
+            width: icon_width,
+            height: icon_height,
+        }
+    });
+
+    if std::env::args().any(|arg| arg == quick_calculator::QUICK_ARG) {
+        let options = eframe::NativeOptions {
+            initial_window_size: Some(quick_calculator::WINDOW_SIZE),
+            icon_data,
+            always_on_top: true,
+            decorated: false,
+            resizable: false,
+            centered: true,
+            ..Default::default()
+        };
+        eframe::run_native(
+            "Funcially",
+            options,
+            Box::new(|cc| Box::new(QuickCalculator::new(cc))),
+        ).expect("Failed to run application");
+        return;
+    }
+
     let options = eframe::NativeOptions {
         initial_window_size: Some(Vec2::new(500.0, 400.0)),
-        icon_data: {
-            if let Some(icon) = icon {
-                let (icon_width, icon_height) = icon.dimensions();
-                Some(eframe::IconData {
-                    rgba: icon.into_raw(),
-                    width: icon_width,
-                    height: icon_height,
-                })
-            } else { None }
-        },
+        icon_data,
         ..Default::default()
     };
     eframe::run_native(
@@ -130,16 +208,39 @@ struct GitHubApiResponseItem {
 #[derive(Debug, serde::Serialize, serde::Deserialize)]
 pub struct Function(String, usize, #[serde(skip)] CalcFn);
 
+/// An equation or inequality in `x` and `y` together with its source, which is also its name in
+/// the plot
+#[derive(Debug)]
+pub struct Relation(String, CalcRelation, ImplicitPlotCache);
+
+/// A variable defined as `name := slider(min, max, step)`
+#[derive(Debug)]
+pub struct VariableSlider {
+    name: String,
+    value: f64,
+    min: f64,
+    max: f64,
+    step: f64,
+}
+
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct ColorSegment {
     range: Range<usize>,
     color: Color32,
     is_error: bool,
+    /// Whether the segment is related to an error elsewhere, which underlines it without changing
+    /// its color
+    #[serde(default)]
+    is_related_error: bool,
 }
 
 impl ColorSegment {
     pub fn new(range: Range<usize>, color: Color32, is_error: bool) -> Self {
-        Self { range, color, is_error }
+        Self { range, color, is_error, is_related_error: false }
+    }
+
+    pub fn related_error(range: Range<usize>) -> Self {
+        Self { range, color: ERROR_COLOR, is_error: false, is_related_error: true }
     }
 
     pub fn from_calculator_color_segment(seg: CalcColorSegment, is_error: bool) -> Self {
@@ -152,11 +253,13 @@ impl ColorSegment {
                 seg.color.0[3],
             ),
             is_error,
+            is_related_error: false,
         }
     }
 }
 
 #[derive(Debug, serde::Serialize, serde::Deserialize)]
+#[allow(clippy::large_enum_variant)] // almost all lines are `Line::Line`s
 pub enum Line {
     Empty,
     WrappedLine,
@@ -166,52 +269,251 @@ pub enum Line {
         output_text: String,
         #[serde(skip)]
         color_segments: Vec<ColorSegment>,
+        /// The colors of the parts of `output_text`, which are used if results are highlighted
+        #[serde(skip)]
+        output_color_segments: Vec<ColorSegment>,
         /// `name`, `argument count`, `Function`.
         ///
         /// Store the function to be able to show redefinitions as well.
         function: Option<Function>,
+        /// Lines that fail because `x` and `y` aren't defined might be relations, which can be
+        /// plotted
+        #[serde(skip)]
+        relation: Option<Box<Relation>>,
+        /// The slider of a variable defined using `slider(min, max, step)`, which is shown instead
+        /// of the value
+        #[serde(skip)]
+        slider: Option<VariableSlider>,
         show_in_plot: bool,
         #[serde(skip)]
         is_error: bool,
+        /// The messages of the warnings of the calculation
+        #[serde(skip)]
+        warnings: Vec<String>,
+        /// The messages of the hints about the line (e.g. that its variable is never used), which
+        /// dim the result
+        #[serde(skip)]
+        hints: Vec<String>,
+        /// The number of the result, which the actions of the context menu work with
+        #[serde(skip)]
+        number: Option<NumberValue>,
+        /// The units suggested to convert the result into
+        #[serde(skip)]
+        conversions: Vec<String>,
     },
 }
 
+/// What the user chose in the context menu of a result. The line numbers start at one.
+enum LineAction {
+    /// Shows the result of the line in a different way (`None` for the display setting)
+    Display(usize, Option<ResultDisplay>),
+    /// Converts the result of the line by adding `in <target>` to its expression
+    Convert(usize, String),
+    /// Shows how the result of the line was calculated in the debug window
+    ShowSteps(usize),
+}
+
 impl Line {
     pub fn new_line(result_data: CalcResult<ResultData>, color_segments: Vec<ColorSegment>, calculator_settings: &Settings, use_thousands_separator: bool) -> Self {
         let mut function: Option<Function> = None;
-        let mut color_segments = color_segments;
+        let mut slider: Option<VariableSlider> = None;
+        let mut number: Option<NumberValue> = None;
         let mut is_error: bool = false;
 
         let output_text = match result_data {
             Ok(data) => {
                 match data {
-                    ResultData::Value(number) => number.format(calculator_settings, use_thousands_separator),
-                    ResultData::Boolean(b) => (if b { "True" } else { "False" }).to_string(),
+                    ResultData::Value(value) => {
+                        number = value.to_number().cloned();
+                        value.format(calculator_settings, use_thousands_separator)
+                    }
+                    ResultData::Boolean { value, difference } => {
+                        let mut result = (if value { "True" } else { "False" }).to_string();
+                        if let Some(difference) = difference {
+                            result += &format!(" ({})", difference.format(calculator_settings, use_thousands_separator));
+                        }
+                        result
+                    }
                     ResultData::Function { name, arg_count, function: f } => {
                         function = Some(Function(name, arg_count, f));
                         String::new()
                     }
-                    ResultData::Nothing => String::new(),
+                    ResultData::Slider { name, value, min, max, step } => {
+                        slider = Some(VariableSlider {
+                            name,
+                            value: value.to_number().map_or(min, |number| number.number),
+                            min,
+                            max,
+                            step,
+                        });
+                        value.format(calculator_settings, use_thousands_separator)
+                    }
+                    ResultData::Nothing | ResultData::FunctionRemoval(_) => String::new(),
                 }
             }
             Err(e) => {
                 is_error = true;
-                for range in e.ranges {
-                    let i = color_segments.iter()
-                        .position(|seg| seg.range.start >= range.start_char)
-                        .unwrap_or_default();
-                    color_segments.insert(i, ColorSegment::new(range.start_char..range.end_char, ERROR_COLOR, true));
-                }
                 format!("{}", e.error)
             }
         };
+        let output_color_segments = if is_error { CalcColorSegment::error(&output_text) } else { CalcColorSegment::output(&output_text) }
+            .into_iter()
+            .map(|seg| ColorSegment::from_calculator_color_segment(seg, false))
+            .collect();
 
         Self::Line {
             output_text,
             function,
+            relation: None,
+            slider,
             color_segments,
+            output_color_segments,
             is_error,
             show_in_plot: false,
+            warnings: vec![],
+            hints: vec![],
+            number,
+            conversions: vec![],
+        }
+    }
+
+    pub fn new_relation(relation: Relation, color_segments: Vec<ColorSegment>) -> Self {
+        Self::Line {
+            output_text: String::new(),
+            function: None,
+            relation: Some(Box::new(relation)),
+            slider: None,
+            color_segments,
+            output_color_segments: vec![],
+            is_error: false,
+            show_in_plot: false,
+            warnings: vec![],
+            hints: vec![],
+            number: None,
+            conversions: vec![],
+        }
+    }
+
+    /// The byte index at which the unit of the result starts (its length if it doesn't have a
+    /// unit), if the result is a number whose unit can be aligned with others
+    pub fn unit_start(&self) -> Option<usize> {
+        let Line::Line { output_text, number: Some(number), is_error: false, .. } = self else { return None; };
+        let unit = number.unit_string();
+        if unit.is_empty() { return Some(output_text.trim_end().len()); }
+        output_text.ends_with(&unit).then(|| output_text.len() - unit.len())
+    }
+
+    /// The name of the function or relation shown in the plot, if the line can be plotted
+    pub fn plot_name(&self) -> Option<&str> {
+        match self {
+            Line::Line { function: Some(Function(name, 1, _)), .. } => Some(name),
+            Line::Line { relation: Some(relation), .. } => Some(&relation.0),
+            _ => None,
+        }
+    }
+
+    /// Shows the actions for the result of the line `line_number`, whose display was chosen to be
+    /// `display`. Copying and plotting are done right away, the other actions are returned.
+    fn context_menu(&mut self, ui: &mut Ui, line_number: usize, display: Option<ResultDisplay>) -> Option<LineAction> {
+        let can_plot = self.plot_name().is_some();
+        let Line::Line { output_text, number, conversions, is_error, show_in_plot, .. } = self else { return None; };
+        let mut action = None;
+
+        let text = output_text.trim();
+        if !text.is_empty() && ui.button("Copy result").clicked() {
+            ui.output_mut(|out| out.copied_text = text.to_owned());
+            ui.close_menu();
+        }
+        if let Some(number) = number {
+            if ui.button("Copy as plain number").clicked() {
+                ui.output_mut(|out| out.copied_text = number.number.to_string());
+                ui.close_menu();
+            }
+
+            if !conversions.is_empty() {
+                ui.horizontal(|ui| {
+                    for unit in conversions.iter() {
+                        if ui.small_button(format!("in {unit}")).clicked() {
+                            action = Some(LineAction::Convert(line_number, unit.clone()));
+                        }
+                    }
+                });
+            }
+
+            let units = number.compatible_units();
+            if !units.is_empty() {
+                ui.menu_button("Convert to", |ui| {
+                    for unit in units {
+                        if ui.button(unit).clicked() { action = Some(LineAction::Convert(line_number, unit.to_string())); }
+                    }
+                });
+            }
+            ui.menu_button("Format", |ui| {
+                for format in [Format::Decimal, Format::Hex, Format::Binary, Format::Scientific] {
+                    if ui.button(format.to_string()).clicked() {
+                        action = Some(LineAction::Convert(line_number, format.to_string()));
+                    }
+                }
+            });
+        }
+        if can_plot && ui.checkbox(show_in_plot, "Plot").clicked() { ui.close_menu(); }
+        if !*is_error && !text.is_empty() {
+            ui.menu_button("Display", |ui| {
+                if ui.radio(display.is_none(), "Like the setting").clicked() {
+                    action = Some(LineAction::Display(line_number, None));
+                }
+                for option in ResultDisplay::ALL {
+                    if ui.radio(display == Some(option), option.to_string()).clicked() {
+                        action = Some(LineAction::Display(line_number, Some(option)));
+                    }
+                }
+            });
+            if ui.button("Show steps").clicked() { action = Some(LineAction::ShowSteps(line_number)); }
+        }
+
+        action
+    }
+}
+
+/// The result of an expression that was copied to the clipboard
+#[cfg(not(target_arch = "wasm32"))]
+struct ClipboardResult {
+    expression: String,
+    result: String,
+    /// The time the expression was copied at, in seconds (see [InputState::time])
+    time: f64,
+}
+
+/// The revision of the source the current source is compared against
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DiffBase {
+    LastSave,
+    SessionStart,
+}
+
+/// How the input and the results are arranged
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+enum LayoutMode {
+    /// The mobile layout in narrow windows and the desktop layout otherwise
+    #[default]
+    Auto,
+    /// The results are shown in a column next to the input
+    Desktop,
+    /// The results are shown below their lines and there is a row of buttons for characters that
+    /// are awkward to type on phones
+    Mobile,
+}
+
+impl LayoutMode {
+    const ALL: [LayoutMode; 3] = [LayoutMode::Auto, LayoutMode::Desktop, LayoutMode::Mobile];
+}
+
+impl std::fmt::Display for LayoutMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Auto => write!(f, "Automatic"),
+            Self::Desktop => write!(f, "Desktop"),
+            Self::Mobile => write!(f, "Mobile"),
         }
     }
 }
@@ -225,28 +527,146 @@ struct App {
     source: String,
     #[serde(skip)]
     source_old: String,
+    /// Calculates the source in the background, created on the first frame
+    #[serde(skip)]
+    evaluator: Option<Evaluator>,
+    /// The analysis of the source, which is reused while its results are coming in
+    #[serde(skip)]
+    document_cache: DocumentCache,
     lines: Vec<Line>,
     line_numbers_text: String,
+    #[serde(skip)]
+    line_ids: LineIds,
+    /// What the results of single lines show instead of the display setting, by the lines' ids
+    #[serde(skip)]
+    line_displays: HashMap<u64, ResultDisplay>,
 
     #[serde(skip)]
     is_ui_enabled: bool,
 
+    /// The values the sliders were dragged to, which are given to the calculator on startup
+    slider_values: HashMap<String, f64>,
+
     is_plot_open: bool,
+    plot_axes: PlotAxes,
+    is_keypad_open: bool,
     is_help_open: bool,
+    #[serde(skip)]
+    help_query: String,
+    /// The progress in the tutorial, if it's running
+    tutorial: Option<tutorial::Tutorial>,
     #[cfg(target_arch = "wasm32")]
     is_download_open: bool,
+    /// The link shown in the share window, which is open if there is one
+    #[cfg(target_arch = "wasm32")]
+    #[serde(skip)]
+    share_link: Option<String>,
+    /// The source of the link the app was opened with, if it should replace the current source
+    #[cfg(target_arch = "wasm32")]
+    #[serde(skip)]
+    shared_source: Option<String>,
     is_settings_open: bool,
 
     is_debug_info_open: bool,
     debug_information: Option<String>,
+    debug_verbosity: Verbosity,
+    /// Whether the window showing how long the lines took to calculate is open
+    #[serde(skip)]
+    is_profiling_open: bool,
+    is_dependency_graph_open: bool,
+    #[serde(skip)]
+    profile_table: ProfileTable,
+
+    is_diff_open: bool,
+    is_export_open: bool,
+    /// The options for exporting the plot's data, if the window is open
+    #[serde(skip)]
+    plot_data_export: Option<plot_data::PlotDataExport>,
+    #[cfg(not(target_arch = "wasm32"))]
+    #[serde(skip)]
+    export_state: export::ExportState,
+    #[serde(skip)]
+    diff_base: DiffBase,
+    #[serde(skip)]
+    diff_only_changes: bool,
+    /// The compared sources (base, current) and their diff
+    #[serde(skip)]
+    diff: Option<(String, String, Diff)>,
+    /// The source at the time of the last (auto)save
+    #[serde(skip)]
+    saved_source: String,
+    /// The lines of the source at the time of the last save together with their results, which the
+    /// comparison uses instead of recalculating the old source (whose results might have changed
+    /// since, e.g. with the exchange rates). Snapshots of older versions contained formatted
+    /// results, so they are stored under a new name.
+    #[serde(rename = "saved_results")]
+    saved_snapshot: Vec<DiffLine>,
+    /// The source when the app was started
+    #[serde(skip)]
+    session_start_source: String,
+    #[serde(skip)]
+    session_start_snapshot: Vec<DiffLine>,
 
     use_thousands_separator: bool,
+    /// Whether the result of the line with the cursor is shown at its end
+    show_inline_results: bool,
+    /// Whether the input is edited with Vim-style normal and insert modes
+    modal_editing: bool,
+    layout_mode: LayoutMode,
+    /// Whether the UI uses pure black and white and thicker strokes
+    high_contrast: bool,
+    /// The share of the window's width taken up by the results, which is changed by dragging the
+    /// divider between the input and the results
+    output_panel_fraction: f32,
+    /// Whether the units of the results are aligned in a column
+    align_result_units: bool,
+    /// Whether the parts of the results are colored like the input
+    highlight_results: bool,
+    #[serde(skip)]
+    modal_editor: ModalEditor,
+    /// The titles of the folded sections
+    folded_sections: HashSet<String>,
+    /// The lines hidden in the current frame, which are added back to the source at its end
+    #[serde(skip)]
+    folded_view: FoldedView,
 
     #[serde(skip)]
     search_state: helpers::SearchState,
+    /// The byte ranges of the references to the variable or function whose usages are highlighted
+    #[serde(skip)]
+    usages: Vec<Range<usize>>,
 
     #[serde(skip)]
     show_new_version_dialog: Arc<Mutex<bool>>,
+    /// The errors that occurred while loading the prelude, which haven't been dismissed yet
+    #[serde(skip)]
+    prelude_errors: Vec<String>,
+    #[cfg(not(target_arch = "wasm32"))]
+    watch_clipboard: bool,
+    #[cfg(not(target_arch = "wasm32"))]
+    #[serde(skip)]
+    clipboard_watcher: Option<ClipboardWatcher>,
+    #[cfg(not(target_arch = "wasm32"))]
+    #[serde(skip)]
+    clipboard_result: Option<ClipboardResult>,
+    #[cfg(not(target_arch = "wasm32"))]
+    #[serde(skip)]
+    recovery: recovery::Recovery,
+    /// The source that was recovered after the app wasn't closed properly, if the user hasn't
+    /// decided whether to restore it yet
+    #[cfg(not(target_arch = "wasm32"))]
+    #[serde(skip)]
+    recovered_source: Option<String>,
+    /// The file that is edited in the input instead of the app's own source, if one is open
+    #[cfg(not(target_arch = "wasm32"))]
+    #[serde(skip)]
+    external_file: Option<external_file::ExternalFile>,
+    #[cfg(not(target_arch = "wasm32"))]
+    #[serde(skip)]
+    is_open_file_open: bool,
+    #[cfg(not(target_arch = "wasm32"))]
+    #[serde(skip)]
+    open_file_state: external_file::OpenFileState,
     #[serde(skip)]
     first_frame: bool,
     #[serde(skip)]
@@ -257,6 +677,10 @@ struct App {
     should_scroll_to_input_text_cursor: bool,
     #[serde(skip)]
     bottom_text: String,
+    /// The text shown in the bottom bar for screen readers to read out, e.g. the result of the
+    /// current line
+    #[serde(skip)]
+    announcement: Option<String>,
 }
 
 impl Default for App {
@@ -264,41 +688,126 @@ impl Default for App {
         App {
             calculator: Calculator::default(),
             source_old: String::new(),
+            evaluator: None,
+            document_cache: DocumentCache::default(),
             source: String::new(),
             lines: Vec::new(),
             line_numbers_text: "1".to_string(),
+            line_ids: LineIds::default(),
+            line_displays: HashMap::new(),
             first_frame: true,
             input_should_request_focus: true,
             is_ui_enabled: true,
+            slider_values: HashMap::new(),
             is_plot_open: false,
+            is_keypad_open: false,
+            plot_axes: PlotAxes::default(),
             is_help_open: false,
+            help_query: String::new(),
+            tutorial: None,
             #[cfg(target_arch = "wasm32")]
             is_download_open: false,
+            #[cfg(target_arch = "wasm32")]
+            share_link: None,
+            #[cfg(target_arch = "wasm32")]
+            shared_source: None,
             show_new_version_dialog: Arc::new(Mutex::new(false)),
+            prelude_errors: Vec::new(),
+            #[cfg(not(target_arch = "wasm32"))]
+            watch_clipboard: false,
+            #[cfg(not(target_arch = "wasm32"))]
+            clipboard_watcher: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            clipboard_result: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            recovery: recovery::Recovery::default(),
+            #[cfg(not(target_arch = "wasm32"))]
+            recovered_source: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            external_file: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            is_open_file_open: false,
+            #[cfg(not(target_arch = "wasm32"))]
+            open_file_state: external_file::OpenFileState::default(),
             is_settings_open: false,
             is_debug_info_open: false,
+            is_diff_open: false,
+            is_export_open: false,
+            plot_data_export: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            export_state: export::ExportState::default(),
+            diff_base: DiffBase::LastSave,
+            diff_only_changes: false,
+            diff: None,
+            saved_source: String::new(),
+            saved_snapshot: Vec::new(),
+            session_start_source: String::new(),
+            session_start_snapshot: Vec::new(),
             search_state: helpers::SearchState::default(),
+            usages: Vec::new(),
             debug_information: None,
+            debug_verbosity: Verbosity::Ast,
+            is_profiling_open: false,
+            is_dependency_graph_open: false,
+            profile_table: ProfileTable::default(),
             use_thousands_separator: false,
+            show_inline_results: false,
+            modal_editing: false,
+            layout_mode: LayoutMode::Auto,
+            high_contrast: false,
+            output_panel_fraction: DEFAULT_OUTPUT_PANEL_FRACTION,
+            align_result_units: false,
+            highlight_results: false,
+            modal_editor: ModalEditor::default(),
+            folded_sections: HashSet::new(),
+            folded_view: FoldedView::default(),
             input_text_cursor_range: CursorRange::one(Cursor::default()),
             should_scroll_to_input_text_cursor: false,
             bottom_text: format!("v{VERSION}"),
+            announcement: None,
         }
     }
 }
 
 impl App {
     fn new(cc: &CreationContext<'_>) -> Self {
-        cc.egui_ctx.set_visuals(Visuals::dark());
+        #[cfg_attr(not(target_arch = "wasm32"), allow(unused_mut))]
+        let mut app = match cc.storage {
+            Some(storage) => {
+                let settings: Settings = eframe::get_value(storage, &settings_key()).unwrap_or_else(Settings::default);
+                let mut app: Self = eframe::get_value(storage, &app_key()).unwrap_or_else(|| {
+                    // Nothing has been saved yet, so this is the first start
+                    let mut app = App::default();
+                    app.start_tutorial();
+                    app
+                });
+                app.calculator.context.borrow_mut().settings = settings;
+                app.calculator.slider_values = app.slider_values.clone();
+                app.saved_source = app.source.clone();
+                app.session_start_source = app.source.clone();
+                app.session_start_snapshot = app.saved_snapshot.clone();
+                app
+            }
+            None => App::default(),
+        };
+        cc.egui_ctx.set_visuals(visuals(app.high_contrast));
+
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            app.recovered_source = recovery::recovered_source().filter(|source| *source != app.source);
+        }
 
-        if let Some(storage) = cc.storage {
-            let settings: Settings = eframe::get_value(storage, &settings_key()).unwrap_or_else(Settings::default);
-            let app: Self = eframe::get_value(storage, &app_key()).unwrap_or_default();
-            app.calculator.context.borrow_mut().settings = settings;
-            return app;
+        #[cfg(target_arch = "wasm32")]
+        if let Some(source) = share::take_shared_source() {
+            // Ask before replacing what the user has been working on
+            if app.source.trim().is_empty() {
+                app.source = source;
+            } else {
+                app.shared_source = Some(source);
+            }
         }
 
-        App::default()
+        app
     }
 
     #[cfg(not(target_arch = "wasm32"))]
@@ -341,9 +850,13 @@ impl App {
     }
 
     fn get_debug_info_for_current_line(&mut self) {
-        let input_text_paragraph = self.input_text_cursor_range.primary.pcursor.paragraph;
+        self.get_debug_info_for_line(self.input_text_cursor_range.primary.pcursor.paragraph);
+    }
+
+    /// Gets the debug information for the (zero-based) source line `line_index`
+    fn get_debug_info_for_line(&mut self, line_index: usize) {
         for (i, line) in self.source.lines().enumerate() {
-            if i != input_text_paragraph { continue; }
+            if i != line_index { continue; }
 
             if line.trim().starts_with('#') || line.is_empty() {
                 break;
@@ -354,34 +867,42 @@ impl App {
                 line = &line[0..comment_start];
             }
 
-            let debug_information = self.calculator.get_debug_info(line, Verbosity::Ast);
+            let debug_information = self.calculator.get_debug_info(line, self.debug_verbosity);
             self.debug_information = Some(debug_information);
             break;
         }
     }
 
-    fn update_lines(&mut self, galley: Arc<Galley>) {
-        if self.source == self.source_old { return; }
-
-        self.search_state.update(&self.source);
+    fn update_lines(&mut self, ctx: &Context, galley: Arc<Galley>) {
+        let evaluator = self.evaluator.get_or_insert_with(|| Evaluator::spawn(ctx.clone()));
+        // The folded sections are calculated as well
+        let source = self.folded_view.unfold(&self.source);
+        let source_changed = source != self.source_old;
+        if source_changed {
+            let (text, view) = FoldedView::new(&source, self.folded_view.folded().clone());
+            // The lines of a folded section whose header was removed can end up in another one,
+            // which doesn't match the galley anymore
+            if text != self.source {
+                self.source = text;
+                self.source_old.clear();
+            } else {
+                self.source_old = source.clone();
+            }
+            self.folded_view = view;
+            self.search_state.update(&self.source);
+            self.usages.clear();
+            self.announcement = None;
+            evaluator.evaluate(&source, &mut self.calculator);
+        }
 
-        self.source_old = self.source.clone();
-        // Since we re-calculate everything from the beginning,
-        // we need to start with a fresh environment
-        self.calculator.reset();
+        // Most calculations are fast enough to show their results right away, so only the
+        // slow ones show up line by line
+        let timeout = if source_changed { EVALUATION_TIMEOUT } else { Duration::ZERO };
+        if !evaluator.poll(&mut self.calculator, timeout) && !source_changed { return; }
 
-        let mut functions = self.lines.iter()
-            .filter(|l| {
-                match l {
-                    Line::Line { show_in_plot, .. } => *show_in_plot,
-                    _ => false,
-                }
-            })
-            .map(|l| {
-                if let Line::Line { function: Some(Function(name, ..)), show_in_plot, .. } = l {
-                    (name.clone(), *show_in_plot)
-                } else { unreachable!() }
-            })
+        let mut plotted = self.lines.iter()
+            .filter(|l| matches!(l, Line::Line { show_in_plot: true, .. }))
+            .filter_map(|l| l.plot_name().map(str::to_owned))
             .collect::<Vec<_>>();
         self.lines.clear();
         self.line_numbers_text.clear();
@@ -391,102 +912,306 @@ impl App {
             return;
         }
 
-        let max_line_number_length = self.source.split('\n').count().to_string().len();
+        let max_line_number_length = source.split('\n').count().to_string().len();
         let format_line_number = |n: usize| {
             format!("{: >width$}", n, width = max_line_number_length)
         };
 
-        let mut results = self.calculator.calculate(&self.source);
+        let document = Document::from_cached_results(&mut self.document_cache, &source, self.evaluator.as_ref().unwrap().results());
+        let source_lines = source.split('\n').collect::<Vec<_>>();
 
-        fn line_range(res: &CalculatorResult) -> Range<usize> {
-            match &res.data {
-                Ok((_, range)) => range.clone(),
-                Err(e) => {
-                    let lines = e.ranges.iter().flat_map(|r| vec![r.start_line, r.end_line]);
-                    let start = lines.clone().min().unwrap();
-                    let end = lines.max().unwrap();
-                    start..end
-                }
-            }
-        }
-
-        fn color_segments_for_line(result: &CalculatorResult, line: usize) -> Vec<ColorSegment> {
-            result.color_segments.clone().into_iter()
-                .zip(std::iter::repeat(false))
-                .chain(result.data.as_ref().err()
-                    .map_or_else(
-                        Vec::new,
-                        |e| e.ranges.clone()
-                            .into_iter()
-                            .filter(|r| r.contains_line(line))
-                            .map(|mut range| {
-                                if range.start_line == line {
-                                    range.end_line = range.start_line + 1;
-                                    // The range needs to extend to the end :shrug:
-                                    range.end_char = usize::MAX / 2;
-                                } else {
-                                    range.start_line = line;
-                                    range.end_line = line + 1;
-                                    range.start_char = 0;
-                                    // The range needs to extend to the end :shrug:
-                                    range.end_char = usize::MAX / 2;
-                                }
-
-                                (CalcColorSegment { color: CalcColor::RED, range }, true)
-                            })
-                            .collect::<Vec<_>>(),
-                    )
-                )
-                .filter(|(seg, _)| seg.range.start_line == line)
-                .map(|(seg, is_error)| ColorSegment::from_calculator_color_segment(seg, is_error))
+        fn color_segments_for_line(line: &DocumentLine) -> Vec<ColorSegment> {
+            // Error ranges come first, so that they take precedence over the normal colors. The
+            // related ones come last, so that they keep the colors of their tokens.
+            line.error_ranges.iter()
+                .map(|range| ColorSegment::new(range.start_char..range.end_char, ERROR_COLOR, true))
+                .chain(line.color_segments.iter().map(|seg| ColorSegment::from_calculator_color_segment(seg.clone(), false)))
+                .chain(line.related_error_ranges.iter().map(|range| ColorSegment::related_error(range.start_char..range.end_char)))
                 .collect::<Vec<_>>()
         }
 
         let mut current_line = 0usize;
-        let mut last_line = 0usize;
-        let mut current_result: Option<CalculatorResult> = None;
+        let mut is_first_row = true;
         self.line_numbers_text = format_line_number(1);
 
         for row in galley.rows.iter() {
-            if let Some(i) = results.iter().position(|res| line_range(res).start == current_line) {
-                let result = results.remove(i);
-                let color_segments = color_segments_for_line(&result, current_line);
-                let mut line = Line::new_line(
-                    result.data.clone().map(|(result, ..)| result),
-                    color_segments,
-                    &self.calculator.context.borrow().settings,
-                    self.use_thousands_separator,
-                );
-                current_result = Some(result);
+            let source_line = self.folded_view.source_line(current_line);
+            let document_line = document.lines().get(source_line);
+            let line = match document_line.map(|l| &l.kind) {
+                _ if !is_first_row => Line::WrappedLine,
+                // Lines ending with `;` or `@ignore` only show their errors
+                Some(DocumentLineKind::Result(Ok(_))) if document_line.unwrap().suppressed =>
+                    Line::Color(color_segments_for_line(document_line.unwrap())),
+                Some(DocumentLineKind::Result(result)) => {
+                    let relation = result.as_ref().err().and_then(|_| {
+                        let mut text = source_lines[source_line];
+                        if let Some(comment) = &document_line.unwrap().comment { text = &text[..comment.start]; }
+                        let text = text.trim();
+                        self.calculator.relation(text)
+                            .map(|relation| Relation(text.to_string(), relation, ImplicitPlotCache::default()))
+                    });
+
+                    let mut line = match relation {
+                        // The errors about `x` and `y` being undefined don't apply to relations
+                        Some(relation) => Line::new_relation(relation, document_line.unwrap().color_segments.iter()
+                            .map(|seg| ColorSegment::from_calculator_color_segment(seg.clone(), false))
+                            .collect()),
+                        None => {
+                            let mut settings = self.calculator.context.borrow().settings;
+                            if let Some(display) = self.line_ids.id(source_line).and_then(|id| self.line_displays.get(&id)) {
+                                settings.output.display = *display;
+                            }
+                            Line::new_line(
+                                result.clone(),
+                                color_segments_for_line(document_line.unwrap()),
+                                &settings,
+                                self.use_thousands_separator,
+                            )
+                        }
+                    };
 
-                if let Line::Line { function: Some(Function(name, ..)), show_in_plot, .. } = &mut line {
-                    if let Some(i) = functions.iter().position(|(n, _)| n == name) {
-                        *show_in_plot = functions[i].1;
-                        functions.remove(i);
+                    if let Some(i) = line.plot_name().and_then(|name| plotted.iter().position(|n| n == name)) {
+                        plotted.remove(i);
+                        if let Line::Line { show_in_plot, .. } = &mut line { *show_in_plot = true; }
+                    }
+                    if let Line::Line { warnings, hints, number, conversions, .. } = &mut line {
+                        let (line_hints, line_warnings) = document_line.unwrap().warnings.iter()
+                            .partition::<Vec<_>, _>(|warning| warning.warning.is_hint());
+                        *warnings = line_warnings.iter().map(|warning| warning.warning.to_string()).collect();
+                        *hints = line_hints.iter().map(|warning| warning.warning.to_string()).collect();
+                        if let Some(number) = number {
+                            *conversions = self.calculator.suggest_conversions(number, CONVERSION_SUGGESTIONS);
+                        }
                     }
+                    line
                 }
-                self.lines.push(line);
-            } else if current_result.as_ref().map(|r| line_range(r).contains(&current_line)).unwrap_or_default() {
-                let line = if last_line != current_line {
-                    Line::Color(color_segments_for_line(current_result.as_ref().unwrap(), current_line))
-                } else {
-                    Line::WrappedLine
-                };
-                self.lines.push(line);
-            } else {
-                self.lines.push(Line::Empty);
-            }
+                Some(DocumentLineKind::Continuation) => Line::Color(color_segments_for_line(document_line.unwrap())),
+                Some(DocumentLineKind::Empty) | None => Line::Empty,
+            };
+            self.lines.push(line);
 
+            is_first_row = row.ends_with_newline;
             if row.ends_with_newline {
-                last_line = current_line;
                 current_line += 1;
-                self.line_numbers_text += &format!("\n{}", format_line_number(current_line + 1));
+                self.line_numbers_text += &format!("\n{}", format_line_number(self.folded_view.source_line(current_line) + 1));
             } else {
                 self.line_numbers_text += "\n";
             }
         }
     }
 
+    /// Paints the result of the (zero-based) source line `line` at its end in the input, similar
+    /// to an inlay hint. `origin` is the position of the input's galley.
+    fn paint_inline_result(&self, ui: &Ui, galley: &Galley, origin: Pos2, line: usize) {
+        // Every source line has exactly one entry that isn't a `Line::WrappedLine`
+        let Some(Line::Line { output_text, is_error: false, .. }) = self.lines.iter()
+            .filter(|l| !matches!(l, Line::WrappedLine))
+            .nth(line) else { return; };
+        let text = output_text.trim();
+        if text.is_empty() { return; }
+
+        let Some(length) = self.source.split('\n').nth(line).map(|l| l.chars().count()) else { return; };
+        let end = galley.from_pcursor(PCursor { paragraph: line, offset: length, prefer_next_row: false });
+        let end = galley.pos_from_cursor(&end).translate(origin.to_vec2());
+
+        let job = text::LayoutJob::single_section(format!("= {text}"), TextFormat {
+            font_id: FONT_ID,
+            color: ui.visuals().weak_text_color(),
+            italics: true,
+            ..Default::default()
+        });
+        let result = ui.fonts(|fonts| fonts.layout_job(job));
+        ui.painter().galley(end.right_top() + vec2(INLINE_RESULT_SPACING, 0.0), result);
+    }
+
+    /// Whether the results are shown below their lines instead of in the output column
+    fn is_mobile_layout(&self, ctx: &Context) -> bool {
+        match self.layout_mode {
+            LayoutMode::Auto => ctx.screen_rect().width() < MOBILE_LAYOUT_MAX_WIDTH,
+            LayoutMode::Desktop => false,
+            LayoutMode::Mobile => true,
+        }
+    }
+
+    /// Paints the results below their lines in the input, which has room for them in the mobile
+    /// layout. `origin` is the position of the input's galley.
+    fn paint_results_below_lines(&self, ui: &Ui, galley: &Galley, origin: Pos2) {
+        let mut lines = self.lines.iter().filter(|l| !matches!(l, Line::WrappedLine));
+        let mut line = lines.next();
+        for (i, row) in galley.rows.iter().enumerate() {
+            // Results go below the last row of their line
+            let is_last_row = row.ends_with_newline || i + 1 == galley.rows.len();
+            if let (true, Some(Line::Line { output_text, is_error, warnings, hints, .. })) = (is_last_row, line) {
+                let text = output_text.trim();
+                if !text.is_empty() {
+                    let color = if *is_error {
+                        ERROR_COLOR
+                    } else if !warnings.is_empty() {
+                        WARNING_COLOR
+                    } else if !hints.is_empty() {
+                        HINT_COLOR
+                    } else {
+                        Color32::GREEN
+                    };
+                    let job = text::LayoutJob::single_section(format!("= {text}"), TextFormat {
+                        font_id: RESULT_BELOW_LINE_FONT_ID,
+                        color,
+                        ..Default::default()
+                    });
+                    let result = ui.fonts(|fonts| fonts.layout_job(job));
+                    ui.painter().galley(origin + vec2(row.rect.min.x, row.rect.max.y), result);
+                }
+            }
+            if row.ends_with_newline { line = lines.next(); }
+        }
+    }
+
+    /// The row of buttons for characters that are awkward to type on phones
+    fn touch_keys_ui(&mut self, ui: &mut Ui) {
+        ui.horizontal_wrapped(|ui| {
+            for key in TOUCH_KEYS {
+                let button = Button::new(RichText::new(key).font(FONT_ID))
+                    .min_size(vec2(TOUCH_TARGET_SIZE, TOUCH_TARGET_SIZE));
+                if ui.add(button).clicked() { self.replace_selection(ui.ctx(), key); }
+            }
+        });
+    }
+
+    fn apply_line_action(&mut self, action: LineAction) {
+        match action {
+            LineAction::Display(line_number, display) => {
+                let Some(id) = self.line_ids.id(self.folded_view.source_line(line_number - 1)) else { return; };
+                match display {
+                    Some(display) => self.line_displays.insert(id, display),
+                    None => self.line_displays.remove(&id),
+                };
+                // Make update_lines() format the results again
+                self.source_old.clear();
+            }
+            LineAction::Convert(line_number, target) => self.append_to_line(line_number - 1, &format!(" in {target}")),
+            LineAction::ShowSteps(line_number) => {
+                // The breakdown of the steps is part of the AST output
+                self.debug_verbosity = Verbosity::Ast;
+                self.get_debug_info_for_line(line_number - 1);
+                self.is_debug_info_open = true;
+            }
+        }
+    }
+
+    /// Adds `text` to the end of the expression in the (zero-based) source line `line`, in front of
+    /// its comment
+    fn append_to_line(&mut self, line: usize, text: &str) {
+        let mut lines = self.source.split('\n').map(str::to_string).collect::<Vec<_>>();
+        let Some(source_line) = lines.get_mut(line) else { return; };
+
+        let (expression, comment) = source_line.split_at(source_line.find('#').unwrap_or(source_line.len()));
+        let mut new_line = expression.trim_end().to_string() + text;
+        if !comment.is_empty() {
+            new_line.push(' ');
+            new_line += comment;
+        }
+        *source_line = new_line;
+        self.source = lines.join("\n");
+    }
+
+    /// Replaces the selection in the input with a reference to the line `line_number`
+    fn insert_line_reference(&mut self, ctx: &Context, line_number: usize) {
+        self.replace_selection(ctx, &format!("line({line_number})"));
+    }
+
+    /// Replaces the selection in the input with `text` and places the cursor after it
+    fn replace_selection(&mut self, ctx: &Context, text: &str) {
+        let [start, end] = self.input_text_cursor_range.as_ccursor_range().sorted();
+
+        let byte_index = |char_index: usize| self.source.char_indices()
+            .nth(char_index)
+            .map_or(self.source.len(), |(i, _)| i);
+        let range = byte_index(start.index)..byte_index(end.index);
+        self.source.replace_range(range, text);
+
+        let cursor = CCursor::new(start.index + text.chars().count());
+        self.set_input_text_edit_ccursor_range(ctx, CCursorRange::one(cursor));
+        self.input_should_request_focus = true;
+    }
+
+    /// Adds `text` as a new line at the end of the input and moves the cursor behind it
+    fn append_line(&mut self, ctx: &Context, text: &str) {
+        if !self.source.is_empty() && !self.source.ends_with('\n') { self.source.push('\n'); }
+        self.source += text;
+
+        let cursor = CCursor::new(self.source.chars().count());
+        self.set_input_text_edit_ccursor_range(ctx, CCursorRange::one(cursor));
+        self.input_should_request_focus = true;
+    }
+
+    /// Keeps line references (e.g. `line(12)`) pointing to the same lines when lines are inserted
+    /// or removed above them
+    fn update_line_references(&mut self, ctx: &Context) {
+        let cursor = TextEditState::load(ctx, Id::new(INPUT_TEXT_EDIT_ID))
+            .and_then(|state| state.ccursor_range())
+            .map(|range| range.primary.index);
+
+        let new_cursor = self.line_ids.update(&mut self.source, cursor.unwrap_or_default());
+        if cursor.map_or(false, |cursor| cursor != new_cursor) {
+            self.set_input_text_edit_ccursor_range(ctx, CCursorRange::one(CCursor::new(new_cursor)));
+        }
+    }
+
+    /// Removes the lines of the folded sections from the source at the start of a frame, so that
+    /// the input shows (and everything else works on) only the remaining lines
+    fn fold_source(&mut self, ctx: &Context) {
+        // The cursor is in the folded text of the last frame, while the line references need the
+        // whole source
+        let view = std::mem::take(&mut self.folded_view);
+        self.map_input_cursor(ctx, |source, index| view.source_index(source, index));
+        self.update_line_references(ctx);
+
+        // Searching and dialogs (e.g. the line picker) work on the whole source
+        let folded = if self.search_state.open || !self.is_ui_enabled {
+            HashSet::new()
+        } else {
+            self.folded_sections.clone()
+        };
+        if folded != *view.folded() { self.usages.clear(); }
+
+        let (text, view) = FoldedView::new(&self.source, folded);
+        self.map_input_cursor(ctx, |source, index| view.view_index(source, index));
+        self.source = text;
+        self.folded_view = view;
+    }
+
+    /// Adds the lines of the folded sections back to the source at the end of a frame
+    fn unfold_source(&mut self) {
+        self.source = self.folded_view.unfold(&self.source);
+    }
+
+    /// The whole source, including the lines of folded sections
+    fn full_source(&self) -> String {
+        self.folded_view.unfold(&self.source)
+    }
+
+    /// Replaces the whole source, including the lines of folded sections
+    ///
+    /// **Returns:** The previous source
+    fn replace_source(&mut self, source: String) -> String {
+        let previous = self.full_source();
+        self.source = source;
+        self.folded_view = FoldedView::default();
+        previous
+    }
+
+    /// Moves the cursor of the input from the index `i` to `f(source, i)`
+    fn map_input_cursor(&self, ctx: &Context, f: impl Fn(&str, usize) -> usize) {
+        let Some(range) = TextEditState::load(ctx, Id::new(INPUT_TEXT_EDIT_ID)).and_then(|state| state.ccursor_range()) else { return; };
+        let new_range = CCursorRange::two(
+            CCursor::new(f(&self.source, range.secondary.index)),
+            CCursor::new(f(&self.source, range.primary.index)),
+        );
+        if new_range.primary.index != range.primary.index || new_range.secondary.index != range.secondary.index {
+            self.set_input_text_edit_ccursor_range(ctx, new_range);
+        }
+    }
+
     fn set_input_text_edit_ccursor_range(&self, ctx: &Context, range: CCursorRange) {
         if let Some(mut state) = TextEditState::load(ctx, Id::new(INPUT_TEXT_EDIT_ID)) {
             state.set_ccursor_range(Some(range));
@@ -494,6 +1219,18 @@ impl App {
         }
     }
 
+    fn keypad_panel(&mut self, ctx: &Context) {
+        let mut inserted = None;
+        SidePanel::left(KEYPAD_PANEL_ID)
+            .resizable(self.is_ui_enabled)
+            .show(ctx, |ui| {
+                ui.set_enabled(self.is_ui_enabled);
+                ScrollArea::vertical().show(ui, |ui| inserted = keypad::keypad(ui, &self.calculator));
+            });
+
+        if let Some(text) = inserted { self.replace_selection(ctx, &text); }
+    }
+
     fn plot_panel(&mut self, ctx: &Context) {
         if FullScreenPlot::is_fullscreen(ctx) { return; }
 
@@ -502,7 +1239,9 @@ impl App {
             .show(ctx, |ui| {
                 ui.set_enabled(self.is_ui_enabled);
 
-                let response = plot(ui, &self.lines, &self.calculator);
+                let response = plot(ui, &self.lines, &self.calculator, &mut self.plot_axes);
+                let x_range = self.plot_axes.x_range(&response.inner.bounds);
+                if let Some(value) = response.inner.inserted_value { self.append_line(ui.ctx(), &value.to_string()); }
                 ui.allocate_ui_at_rect(
                     response.response.rect.shrink(10.0),
                     |ui| {
@@ -510,6 +1249,9 @@ impl App {
                             if ui.small_button("Fullscreen").clicked() {
                                 FullScreenPlot::set_fullscreen(ui.ctx(), true);
                             }
+                            if ui.small_button("Export data").clicked() {
+                                self.plot_data_export = Some(plot_data::PlotDataExport::new(x_range.clone()));
+                            }
                         });
                     },
                 );
@@ -517,26 +1259,87 @@ impl App {
     }
 
     fn help_window(&mut self, ctx: &Context) {
-        let is_help_open = &mut self.is_help_open;
+        let mut example = None;
         Window::new("Help")
-            .open(is_help_open)
+            .open(&mut self.is_help_open)
             .vscroll(true)
             .hscroll(true)
             .enabled(self.is_ui_enabled)
-            .show(ctx, build_help);
+            .show(ctx, |ui| example = help::build_help(ui, &mut self.help_query));
+
+        if let Some(example) = example {
+            self.replace_selection(ctx, &example);
+        }
     }
 
-    #[cfg(target_arch = "wasm32")]
-    fn download_window(&mut self, ctx: &Context) {
-        Window::new("Download")
-            .open(&mut self.is_download_open)
-            .collapsible(false)
+    /// Replaces the input with the tutorial's worksheet
+    fn start_tutorial(&mut self) {
+        let previous_source = self.replace_source(tutorial::worksheet());
+        self.tutorial = Some(tutorial::Tutorial::new(previous_source));
+    }
+
+    fn tutorial_window(&mut self, ctx: &Context) {
+        let Some(tutorial) = &self.tutorial else { return; };
+        let step = tutorial.current_step();
+        let is_solved = step.map_or(false, |step| {
+            step.is_solved(&self.source, &self.lines, &self.calculator, self.use_thousands_separator)
+        });
+
+        let mut should_advance = false;
+        let mut should_exit = false;
+        Window::new("Tutorial")
             .resizable(false)
             .enabled(self.is_ui_enabled)
             .show(ctx, |ui| {
-                ui.heading("Desktop App");
-                ui.horizontal(|ui| {
-                    ui.spacing_mut().item_spacing.x = 0.0;
+                match step {
+                    Some(step) => {
+                        ui.label(RichText::new(format!("Step {} of {}", tutorial.step + 1, tutorial::STEPS.len())).strong());
+                        ui.label(step.instruction);
+                        ui.add_space(5.0);
+                        if is_solved {
+                            ui.colored_label(Color32::GREEN, "✔ Well done!");
+                        } else {
+                            ui.label(RichText::new("Write your calculation below the step in the input").weak());
+                        }
+                    }
+                    None => { ui.label("You have completed the tutorial!"); }
+                }
+
+                ui.add_space(5.0);
+                ui.horizontal(|ui| {
+                    if step.is_some() {
+                        let text = if is_solved { "Next step" } else { "Skip step" };
+                        should_advance = ui.button(text).clicked();
+                    }
+                    should_exit = ui.button("Exit")
+                        .on_hover_text("Restores the input from before the tutorial")
+                        .clicked();
+                });
+            });
+
+        if should_exit {
+            let tutorial = self.tutorial.take().unwrap();
+            self.replace_source(tutorial.previous_source);
+        } else if should_advance {
+            let tutorial = self.tutorial.as_mut().unwrap();
+            tutorial.step += 1;
+            if let Some(step) = tutorial.current_step() {
+                self.append_line(ctx, &format!("\n{}\n", step.todo));
+            }
+        }
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn download_window(&mut self, ctx: &Context) {
+        Window::new("Download")
+            .open(&mut self.is_download_open)
+            .collapsible(false)
+            .resizable(false)
+            .enabled(self.is_ui_enabled)
+            .show(ctx, |ui| {
+                ui.heading("Desktop App");
+                ui.horizontal(|ui| {
+                    ui.spacing_mut().item_spacing.x = 0.0;
                     ui.label("If you're on desktop, you can download the ");
                     ui.hyperlink_to("desktop app", "https://github.com/david072/funcially/releases");
                     ui.label(".");
@@ -558,72 +1361,677 @@ impl App {
             });
     }
 
-    fn settings_window(&mut self, ctx: &Context) {
-        Window::new("Settings")
-            .open(&mut self.is_settings_open)
-            .vscroll(true)
+    #[cfg(target_arch = "wasm32")]
+    fn share_window(&mut self, ctx: &Context) {
+        let Some(link) = &mut self.share_link else { return; };
+
+        let mut is_open = true;
+        Window::new("Share")
+            .open(&mut is_open)
+            .resizable(false)
+            .enabled(self.is_ui_enabled)
+            .show(ctx, |ui| {
+                ui.label("Anyone with this link can open the current input:");
+                ui.horizontal(|ui| {
+                    TextEdit::singleline(link).interactive(false).show(ui);
+                    if ui.button("📋").clicked() {
+                        ui.output_mut(|out| out.copied_text = link.clone());
+                    }
+                });
+
+                if link.len() > share::MAX_LINK_LENGTH {
+                    ui.label(RichText::new(format!(
+                        "The link is {} characters long, which some browsers and messengers don't support.",
+                        link.len(),
+                    )).color(WARNING_COLOR));
+                }
+            });
+
+        if !is_open { self.share_link = None; }
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn shared_source_dialog(&mut self, ctx: &Context) {
+        if self.shared_source.is_none() { return; }
+
+        self.is_ui_enabled = false;
+        let mut should_open = None;
+        dialog(ctx, Some("Shared Calculation"), |ui| {
+            ui.vertical(|ui| {
+                ui.label("The link contains a calculation. Opening it replaces the current input.");
+
+                ui.add_space(15.0);
+                ui.horizontal(|ui| {
+                    if ui.button("Open").clicked() { should_open = Some(true); }
+                    if ui.button("Cancel").clicked() { should_open = Some(false); }
+                });
+            });
+
+            if helpers::is_key_pressed(ui, Key::Escape) { should_open = Some(false); }
+        });
+
+        if let Some(should_open) = should_open {
+            let source = self.shared_source.take().unwrap();
+            if should_open { self.replace_source(source); }
+            self.is_ui_enabled = true;
+        }
+    }
+
+    fn settings_window(&mut self, ctx: &Context) {
+        let mut input_mode_change: Option<(InputMode, InputMode)> = None;
+
+        Window::new("Settings")
+            .open(&mut self.is_settings_open)
+            .vscroll(true)
+            .resizable(false)
+            .enabled(self.is_ui_enabled)
+            .show(ctx, |ui| {
+                let mut update = false;
+
+                ui.heading("General");
+                ui.add_space(10.0);
+                update |= ui.checkbox(&mut self.use_thousands_separator, "Use thousands separator").clicked();
+                ui.checkbox(&mut self.show_inline_results, "Show results inline")
+                    .on_hover_text("Shows the result of the line with the cursor at its end");
+                ui.checkbox(&mut self.modal_editing, "Vim-style editing")
+                    .on_hover_text("Edits the input in a normal and an insert mode, like in Vim");
+                ComboBox::from_label("Layout")
+                    .selected_text(self.layout_mode.to_string())
+                    .show_ui(ui, |ui| {
+                        for mode in LayoutMode::ALL {
+                            ui.selectable_value(&mut self.layout_mode, mode, mode.to_string());
+                        }
+                    })
+                    .response
+                    .on_hover_text("The mobile layout shows the results below their lines");
+                ui.checkbox(&mut self.align_result_units, "Align units of results")
+                    .on_hover_text("Right-aligns the numbers of the results in front of a column with their units");
+                ui.checkbox(&mut self.highlight_results, "Highlight results")
+                    .on_hover_text("Colors the numbers, units and operators of the results like the ones of the input");
+                if ui.checkbox(&mut self.high_contrast, "High contrast").changed() {
+                    ui.ctx().set_visuals(visuals(self.high_contrast));
+                }
+                #[cfg(not(target_arch = "wasm32"))]
+                ui.checkbox(&mut self.watch_clipboard, "Calculate copied expressions")
+                    .on_hover_text("Shows the result of expressions copied in any application");
+
+                ui.separator();
+                ui.heading("Date format");
+                ui.add_space(10.0);
+
+                let settings = &mut self.calculator.context.borrow_mut().settings;
+
+                ComboBox::from_label("Format")
+                    .selected_text(settings.date.format.to_string())
+                    .show_ui(ui, |ui| {
+                        let current_format = &mut settings.date.format;
+                        update |= ui.selectable_value(current_format, DateFormat::Dmy, "DMY").clicked();
+                        update |= ui.selectable_value(current_format, DateFormat::Mdy, "MDY").clicked();
+                        update |= ui.selectable_value(current_format, DateFormat::Ymd, "YMD").clicked();
+                    });
+
+                ComboBox::from_label("Delimiter")
+                    .selected_text(settings.date.delimiter.to_string())
+                    .show_ui(ui, |ui| {
+                        const DELIMITERS: &str = ".,'/-";
+                        let current_del = &mut settings.date.delimiter;
+                        for char in DELIMITERS.chars() {
+                            update |= ui.selectable_value(current_del, char, char.to_string()).clicked();
+                        }
+                    });
+
+                ComboBox::from_label("Time zone")
+                    .selected_text(settings.date.timezone.to_string())
+                    .show_ui(ui, |ui| {
+                        let current_zone = &mut settings.date.timezone;
+                        update |= ui.selectable_value(current_zone, TimeZone::Local, "Local").clicked();
+                        for hours in -12..=14 {
+                            let zone = TimeZone::Utc(hours * 60);
+                            update |= ui.selectable_value(current_zone, zone, zone.to_string()).clicked();
+                        }
+                    });
+
+                ui.separator();
+                ui.heading("Results");
+                ui.add_space(10.0);
+
+                let output = &mut settings.output;
+                ComboBox::from_label("Default format")
+                    .selected_text(output.default_format.to_string())
+                    .show_ui(ui, |ui| {
+                        for format in [Format::Decimal, Format::Hex, Format::Binary, Format::Scientific] {
+                            update |= ui.selectable_value(&mut output.default_format, format, format.to_string()).clicked();
+                        }
+                    });
+                ui.horizontal(|ui| {
+                    update |= ui.add(DragValue::new(&mut output.decimal_places).clamp_range(0..=MAX_DECIMAL_PLACES)).changed();
+                    ui.label("Decimal places");
+                });
+                update |= ui.checkbox(&mut output.pad_trailing_zeros, "Pad decimal places with zeros").clicked();
+                update |= ui.checkbox(&mut output.auto_scientific, "Scientific notation for very large and small numbers")
+                    .on_hover_text("Numbers from 10^15 and below 10^-6 are shown in scientific notation")
+                    .clicked();
+                update |= ui.checkbox(&mut output.auto_prefix, "Pick unit prefixes automatically")
+                    .on_hover_text("E.g. 1500m is shown as 1.5km. Lines that are converted into a unit keep it")
+                    .clicked();
+                update |= ui.checkbox(&mut output.group_digits, "Group hex and binary digits")
+                    .on_hover_text("E.g. 0xFFFF_FFFF")
+                    .clicked();
+                update |= ui.checkbox(&mut output.pad_to_word_size, "Pad hex and binary to the word size")
+                    .on_hover_text("E.g. 0x00FF with a word size of 16 bits")
+                    .clicked();
+                ComboBox::from_label("Display")
+                    .selected_text(output.display.to_string())
+                    .show_ui(ui, |ui| {
+                        for display in ResultDisplay::ALL {
+                            update |= ui.selectable_value(&mut output.display, display, display.to_string()).clicked();
+                        }
+                    })
+                    .response
+                    .on_hover_text("Can be changed for single lines by right-clicking their results");
+                let tolerance = &mut settings.tolerance;
+                ui.horizontal(|ui| {
+                    let relative = DragValue::from_get_set(|value| {
+                        if let Some(value) = value { tolerance.relative = value / 100.0; }
+                        tolerance.relative * 100.0
+                    });
+                    update |= ui.add(relative.suffix("%").speed(0.01).clamp_range(0.0..=100.0)).changed();
+                    ui.label("Tolerance of ≈")
+                        .on_hover_text("How far apart the sides of approximate equality checks may be, relative to the larger \
+                            one. Single checks can use a different tolerance (e.g. a ≈ b within 0.01).");
+                });
+
+                ui.separator();
+                ui.heading("Currencies");
+                ui.add_space(10.0);
+
+                let currency = &mut settings.currency;
+                update |= ui.checkbox(&mut currency.money_format, "Show money with two decimal places and its symbol").clicked();
+                ui.horizontal(|ui| {
+                    update |= ui.add(DragValue::new(&mut currency.vat_rate).suffix("%").speed(0.1).clamp_range(0.0..=100.0)).changed();
+                    ui.label("VAT rate").on_hover_text("Used by tax(x) and net(x)");
+                });
+                for (label, code, options) in [
+                    ("$ stands for", &mut currency.dollar, ["USD", "CAD", "AUD", "NZD", "HKD", "SGD", "MXN"].as_slice()),
+                    ("£ stands for", &mut currency.pound, &["GBP", "EGP"]),
+                    ("¥ stands for", &mut currency.yen, &["JPY", "CNY"]),
+                ] {
+                    ComboBox::from_label(label)
+                        .selected_text(code.to_string())
+                        .show_ui(ui, |ui| {
+                            // Only currencies with known exchange rates can be used
+                            for option in options.iter().filter_map(|option| option.parse::<CurrencyCode>().ok()) {
+                                update |= ui.selectable_value(code, option, option.to_string()).clicked();
+                            }
+                        });
+                }
+
+                ui.separator();
+                ui.heading("Limits");
+                ui.add_space(10.0);
+
+                let limits = &mut settings.limits;
+                ui.horizontal(|ui| {
+                    update |= ui.add(DragValue::new(&mut limits.max_evaluation_time).suffix(" ms").speed(10.0).clamp_range(1..=60_000)).changed();
+                    ui.label("Maximum evaluation time per line");
+                });
+                ui.horizontal(|ui| {
+                    update |= ui.add(DragValue::new(&mut limits.max_recursion_depth).clamp_range(1..=1000)).changed();
+                    ui.label("Maximum recursion depth of functions");
+                });
+                ui.horizontal(|ui| {
+                    update |= ui.add(DragValue::new(&mut limits.max_factorial).clamp_range(0..=1_000_000)).changed();
+                    ui.label("Largest factorial");
+                });
+                update |= ui.checkbox(&mut settings.memoize_functions, "Remember the results of recursive functions").clicked();
+                update |= ui.checkbox(&mut settings.cache_constants, "Remember the results of constant calculations")
+                    .on_hover_text("Calculations without variables, custom functions or currencies aren't calculated again when another line changes")
+                    .clicked();
+
+                ui.separator();
+                ui.heading("Formatting");
+                ui.add_space(10.0);
+
+                let style = &mut settings.format;
+                ui.checkbox(&mut style.spaces_around_operators, "Spaces around operators");
+                ui.checkbox(&mut style.lowercase_hex, "Lowercase hexadecimal digits");
+                ui.checkbox(&mut style.canonical_units, "Canonical unit spelling");
+                ui.checkbox(&mut style.keep_literal_width, "Keep leading zeros in hex / binary literals");
+
+                let old_input_mode = settings.input_mode;
+                ComboBox::from_label("Input mode")
+                    .selected_text(settings.input_mode.to_string())
+                    .show_ui(ui, |ui| {
+                        let current_mode = &mut settings.input_mode;
+                        ui.selectable_value(current_mode, InputMode::Infix, "Infix");
+                        ui.selectable_value(current_mode, InputMode::Rpn, "RPN");
+                    });
+                if settings.input_mode != old_input_mode {
+                    input_mode_change = Some((old_input_mode, settings.input_mode));
+                }
+
+                ComboBox::from_label("Precedence")
+                    .selected_text(settings.precedence.to_string())
+                    .show_ui(ui, |ui| {
+                        let current_precedence = &mut settings.precedence;
+                        update |= ui.selectable_value(current_precedence, Precedence::Standard, "Standard").clicked();
+                        update |= ui.selectable_value(current_precedence, Precedence::Sequential, "Sequential").clicked();
+                    });
+                update |= ui.checkbox(&mut settings.tight_implicit_multiplication, "Implicit multiplication binds tighter than division").clicked();
+                ComboBox::from_label("Data size prefixes")
+                    .selected_text(settings.data_prefixes.to_string())
+                    .show_ui(ui, |ui| {
+                        let current_prefixes = &mut settings.data_prefixes;
+                        update |= ui.selectable_value(current_prefixes, DataPrefixes::Si, "SI (1kb = 1000b)").clicked();
+                        update |= ui.selectable_value(current_prefixes, DataPrefixes::Binary, "Binary (1kb = 1024b)").clicked();
+                    });
+                ComboBox::from_label("Word size")
+                    .selected_text(settings.word_size.to_string())
+                    .show_ui(ui, |ui| {
+                        let current_word_size = &mut settings.word_size;
+                        for word_size in [WordSize::Unlimited, WordSize::Bits8, WordSize::Bits16, WordSize::Bits32, WordSize::Bits64] {
+                            update |= ui.selectable_value(current_word_size, word_size, word_size.to_string()).clicked();
+                        }
+                    });
+                ComboBox::from_label("Modulo")
+                    .selected_text(settings.modulo.to_string())
+                    .show_ui(ui, |ui| {
+                        let current_modulo = &mut settings.modulo;
+                        for modulo in [ModuloMode::Truncated, ModuloMode::Floored, ModuloMode::Euclidean] {
+                            update |= ui.selectable_value(current_modulo, modulo, modulo.to_string()).clicked();
+                        }
+                    });
+
+                ui.separator();
+                CollapsingHeader::new("Debug").default_open(true).show(ui, |ui| {
+                    let mut debug_on_hover = ui.ctx().debug_on_hover();
+                    ui.checkbox(&mut debug_on_hover, "Debug On Hover");
+                    ui.ctx().set_debug_on_hover(debug_on_hover);
+
+                    let mut options = ui.ctx().options(|opt| opt.clone());
+                    ui.checkbox(&mut options.tessellation_options.debug_paint_clip_rects, "Paint clip rectangles");
+                    ui.checkbox(&mut options.tessellation_options.debug_paint_text_rects, "Paint text bounds");
+                    ui.ctx().options_mut(|opt| *opt = options);
+                });
+                ui.hyperlink_to("Source code", "https://github.com/david072/funcially");
+
+                if update {
+                    // Make update_lines() refresh on the next frame, since now source and source_old are not the same
+                    self.source_old.clear();
+                }
+            });
+
+        if let Some((from, to)) = input_mode_change {
+            self.convert_source(from, to);
+            self.source_old.clear();
+        }
+    }
+
+    fn diff_window(&mut self, ctx: &Context) {
+        let (base, snapshot) = match self.diff_base {
+            DiffBase::LastSave => (&self.saved_source, &self.saved_snapshot),
+            DiffBase::SessionStart => (&self.session_start_source, &self.session_start_snapshot),
+        };
+
+        let source = self.full_source();
+        let is_outdated = self.diff.as_ref()
+            .map_or(true, |(old, new, _)| old != base || *new != source);
+        if is_outdated {
+            // Use a separate calculator, so that the environment of the current source stays intact
+            let mut calculator = self.calculator.with_empty_environment();
+            // The snapshot is outdated if the source was changed outside the app
+            let old = if is_snapshot_of(snapshot, base) {
+                snapshot.clone()
+            } else {
+                self.diff_lines(&mut calculator, base)
+            };
+            let new = match self.evaluator.as_ref().and_then(|evaluator| evaluator.finished_results(&source)) {
+                Some(results) => document_snapshot(&source, &Document::from_cached_results(&mut self.document_cache, &source, results)),
+                None => self.diff_lines(&mut calculator, &source),
+            };
+            let settings = self.calculator.context.borrow().settings;
+            let diff = Diff::new(&old, &new, &settings, self.use_thousands_separator);
+            self.diff = Some((base.clone(), source, diff));
+        }
+
+        let diff_base = &mut self.diff_base;
+        let only_changes = &mut self.diff_only_changes;
+        let diff = &self.diff.as_ref().unwrap().2;
+
+        Window::new("Compare")
+            .open(&mut self.is_diff_open)
+            .vscroll(true)
+            .enabled(self.is_ui_enabled)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("Compare with:");
+                    ui.selectable_value(diff_base, DiffBase::LastSave, "Last save");
+                    ui.selectable_value(diff_base, DiffBase::SessionStart, "Start of session");
+                });
+                ui.checkbox(only_changes, "Only show changes");
+                ui.separator();
+
+                if diff.has_changes() {
+                    diff.show(ui, &FONT_ID, *only_changes);
+                } else {
+                    ui.label("No changes");
+                }
+            });
+    }
+
+    /// Calculates `source` and returns its lines together with their results
+    fn diff_lines(&self, calculator: &mut Calculator, source: &str) -> Vec<DiffLine> {
+        document_snapshot(source, &Document::new(calculator, source))
+    }
+
+    /// Calculates `source` and returns its lines together with their output texts and whether
+    /// they are errors
+    fn line_outputs<'a>(&self, calculator: &mut Calculator, source: &'a str) -> Vec<(&'a str, String, bool)> {
+        let document = Document::new(calculator, source);
+        let settings = calculator.context.borrow().settings;
+
+        source.split('\n').zip(document.lines())
+            .map(|(input, line)| {
+                let (result, is_error) = match &line.kind {
+                    DocumentLineKind::Result(Ok(_)) if line.suppressed => (String::new(), false),
+                    DocumentLineKind::Result(result) => {
+                        match Line::new_line(result.clone(), vec![], &settings, self.use_thousands_separator) {
+                            Line::Line { output_text, is_error, .. } => (output_text, is_error),
+                            _ => unreachable!(),
+                        }
+                    }
+                    _ => (String::new(), false),
+                };
+                (input, result, is_error)
+            })
+            .collect()
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn export_window(&mut self, ctx: &Context) {
+        let state = &mut self.export_state;
+        let mut should_export = false;
+
+        Window::new("Export")
+            .open(&mut self.is_export_open)
+            .resizable(false)
+            .enabled(self.is_ui_enabled)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("Format:");
+                    for format in export::ExportFormat::ALL {
+                        if ui.selectable_value(&mut state.format, format, format.to_string()).clicked() {
+                            state.path = std::path::Path::new(&state.path)
+                                .with_extension(format.extension())
+                                .display()
+                                .to_string();
+                        }
+                    }
+                });
+                ui.checkbox(&mut state.include_plot, "Include plot")
+                    .on_hover_text("Plots the functions that are selected to show in the plot");
+                ui.horizontal(|ui| {
+                    ui.label("File:");
+                    ui.text_edit_singleline(&mut state.path);
+                });
+
+                ui.add_space(5.0);
+                should_export = ui.button("Export").clicked();
+                match &state.message {
+                    Some(Ok(message)) => { ui.label(message); }
+                    Some(Err(error)) => { ui.label(RichText::new(error).color(ERROR_COLOR)); }
+                    None => {}
+                }
+            });
+
+        if should_export {
+            let worksheet = self.worksheet(self.export_state.include_plot);
+            let state = &mut self.export_state;
+            state.message = Some(match export::export(&worksheet, state.format, std::path::Path::new(&state.path)) {
+                Ok(()) => Ok(format!("Exported to {}", state.path)),
+                Err(e) => Err(format!("Failed to export: {e}")),
+            });
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn open_file_window(&mut self, ctx: &Context) {
+        let state = &mut self.open_file_state;
+        let mut should_open = false;
+        let mut should_save = false;
+        let mut should_close = false;
+        // Whether to replace the input with the contents the file was changed to on disk
+        let mut should_reload: Option<bool> = None;
+
+        Window::new("Open File")
+            .open(&mut self.is_open_file_open)
+            .resizable(false)
+            .enabled(self.is_ui_enabled)
+            .show(ctx, |ui| {
+                match &mut self.external_file {
+                    Some(file) => {
+                        ui.label(format!("Editing {}", file.path.display()));
+                        ui.checkbox(&mut file.save_on_change, "Save on change")
+                            .on_hover_text("Writes every change of the input to the file");
+
+                        if file.changed_contents.is_some() {
+                            ui.add_space(5.0);
+                            ui.label(RichText::new("The file was changed by another program.").color(WARNING_COLOR));
+                            ui.horizontal(|ui| {
+                                if ui.button("Reload").clicked() { should_reload = Some(true); }
+                                if ui.button("Keep the input").clicked() { should_reload = Some(false); }
+                            });
+                        }
+
+                        let has_unsaved_changes = file.has_unsaved_changes(&self.source);
+                        ui.add_space(5.0);
+                        ui.horizontal(|ui| {
+                            should_save = ui.add_enabled(has_unsaved_changes, Button::new("Save")).clicked();
+                            should_close = ui.button("Close file").clicked();
+                        });
+                        if has_unsaved_changes { ui.label("Unsaved changes are discarded when the file is closed."); }
+                    }
+                    None => {
+                        ui.horizontal(|ui| {
+                            ui.label("File:");
+                            ui.text_edit_singleline(&mut state.path);
+                        });
+                        ui.checkbox(&mut state.save_on_change, "Save on change")
+                            .on_hover_text("Writes every change of the input to the file");
+
+                        ui.add_space(5.0);
+                        should_open = ui.button("Open").clicked();
+                    }
+                }
+
+                if let Some(error) = &state.error {
+                    ui.label(RichText::new(error).color(ERROR_COLOR));
+                }
+            });
+
+        if should_open {
+            let state = &mut self.open_file_state;
+            match external_file::ExternalFile::open(std::path::Path::new(&state.path), state.save_on_change, self.source.clone()) {
+                Ok(file) => {
+                    self.source = file.contents.clone();
+                    self.external_file = Some(file);
+                    state.error = None;
+                }
+                Err(e) => state.error = Some(format!("Failed to open the file: {e}")),
+            }
+        }
+        if should_save { self.save_external_file(); }
+        if let (Some(should_reload), Some(file)) = (should_reload, &mut self.external_file) {
+            let contents = file.changed_contents.take().unwrap();
+            if should_reload { self.source = contents.clone(); }
+            // Saving the input overwrites the changes on disk from now on
+            file.contents = contents;
+        }
+        if should_close {
+            if let Some(file) = self.external_file.take() { self.source = file.own_source; }
+            self.open_file_state.error = None;
+        }
+    }
+
+    /// Reloads the open file if it was changed by another program and saves changes of the input
+    /// if they are saved on change
+    #[cfg(not(target_arch = "wasm32"))]
+    fn update_external_file(&mut self, ctx: &Context) {
+        let Some(file) = &mut self.external_file else { return; };
+
+        if let Some(contents) = file.poll(ctx, ctx.input(|i| i.time)) {
+            if file.has_unsaved_changes(&self.source) {
+                file.changed_contents = Some(contents);
+                self.is_open_file_open = true;
+            } else {
+                self.source = contents.clone();
+                file.contents = contents;
+            }
+        }
+
+        if file.save_on_change && file.changed_contents.is_none() && file.has_unsaved_changes(&self.source) {
+            self.save_external_file();
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn save_external_file(&mut self) {
+        let Some(file) = &mut self.external_file else { return; };
+        if let Err(e) = file.save(&self.source) {
+            self.open_file_state.error = Some(format!("Failed to save the file: {e}"));
+            // Don't try again on every frame
+            file.save_on_change = false;
+        }
+    }
+
+    fn plot_data_window(&mut self, ctx: &Context) {
+        let has_functions = !plotted_functions(&self.lines).is_empty();
+        let Some(export) = &mut self.plot_data_export else { return; };
+        #[cfg(not(target_arch = "wasm32"))]
+        let mut should_save = false;
+        let mut should_copy = false;
+
+        let mut is_open = true;
+        Window::new("Export Plot Data")
+            .open(&mut is_open)
+            .resizable(false)
+            .enabled(self.is_ui_enabled)
+            .show(ctx, |ui| {
+                if !has_functions {
+                    ui.label("Select functions to show in the plot to export their values.");
+                    return;
+                }
+
+                ui.horizontal(|ui| {
+                    ui.label("x from");
+                    ui.add(DragValue::new(&mut export.start).speed(0.1));
+                    ui.label("to");
+                    ui.add(DragValue::new(&mut export.end).speed(0.1));
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Samples:");
+                    ui.add(DragValue::new(&mut export.samples).clamp_range(2..=plot_data::MAX_SAMPLES));
+                });
+                #[cfg(not(target_arch = "wasm32"))]
+                ui.horizontal(|ui| {
+                    ui.label("File:");
+                    ui.text_edit_singleline(&mut export.path);
+                });
+
+                ui.add_space(5.0);
+                ui.horizontal(|ui| {
+                    #[cfg(not(target_arch = "wasm32"))]
+                    {
+                        should_save = ui.button("Save").clicked();
+                    }
+                    should_copy = ui.button("📋 Copy").clicked();
+                });
+                match &export.message {
+                    Some(Ok(message)) => { ui.label(message); }
+                    Some(Err(error)) => { ui.label(RichText::new(error).color(ERROR_COLOR)); }
+                    None => {}
+                }
+            });
+
+        if !is_open {
+            self.plot_data_export = None;
+            return;
+        }
+
+        let export = self.plot_data_export.as_ref().unwrap();
+        let csv = || plot_data::csv(&self.calculator, &plotted_functions(&self.lines), export.start..=export.end, export.samples);
+        let mut message = None;
+        #[cfg(not(target_arch = "wasm32"))]
+        if should_save {
+            message = Some(match std::fs::write(&export.path, csv()) {
+                Ok(()) => Ok(format!("Saved to {}", export.path)),
+                Err(e) => Err(format!("Failed to save: {e}")),
+            });
+        }
+        if should_copy {
+            ctx.output_mut(|out| out.copied_text = csv());
+            message = Some(Ok("Copied to the clipboard".to_string()));
+        }
+
+        if message.is_some() {
+            self.plot_data_export.as_mut().unwrap().message = message;
+        }
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn export_window(&mut self, ctx: &Context) {
+        Window::new("Export")
+            .open(&mut self.is_export_open)
             .resizable(false)
             .enabled(self.is_ui_enabled)
             .show(ctx, |ui| {
-                let mut update = false;
-
-                ui.heading("General");
-                ui.add_space(10.0);
-                update |= ui.checkbox(&mut self.use_thousands_separator, "Use thousands separator").clicked();
-
-                ui.separator();
-                ui.heading("Date format");
-                ui.add_space(10.0);
-
-                let settings = &mut self.calculator.context.borrow_mut().settings;
-
-                ComboBox::from_label("Format")
-                    .selected_text(settings.date.format.to_string())
-                    .show_ui(ui, |ui| {
-                        let current_format = &mut settings.date.format;
-                        update |= ui.selectable_value(current_format, DateFormat::Dmy, "DMY").clicked();
-                        update |= ui.selectable_value(current_format, DateFormat::Mdy, "MDY").clicked();
-                        update |= ui.selectable_value(current_format, DateFormat::Ymd, "YMD").clicked();
-                    });
-
-                ComboBox::from_label("Delimiter")
-                    .selected_text(settings.date.delimiter.to_string())
-                    .show_ui(ui, |ui| {
-                        const DELIMITERS: &str = ".,'/-";
-                        let current_del = &mut settings.date.delimiter;
-                        for char in DELIMITERS.chars() {
-                            update |= ui.selectable_value(current_del, char, char.to_string()).clicked();
-                        }
-                    });
+                ui.label(RichText::new("Exporting is only available in the desktop app.").color(ERROR_COLOR));
+                ui.hyperlink_to("Download the desktop app", "https://github.com/david072/funcially/releases");
+            });
+    }
 
-                ui.separator();
-                CollapsingHeader::new("Debug").default_open(true).show(ui, |ui| {
-                    let mut debug_on_hover = ui.ctx().debug_on_hover();
-                    ui.checkbox(&mut debug_on_hover, "Debug On Hover");
-                    ui.ctx().set_debug_on_hover(debug_on_hover);
+    /// Calculates the source for exporting it. The plot contains the functions that are shown in
+    /// the plot panel.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn worksheet(&self, include_plot: bool) -> export::Worksheet {
+        let mut calculator = self.calculator.with_empty_environment();
+        let lines = self.line_outputs(&mut calculator, &self.full_source()).into_iter()
+            .enumerate()
+            .map(|(i, (input, result, is_error))| export::ExportLine {
+                number: i + 1,
+                input: input.to_string(),
+                result,
+                is_error,
+            })
+            .collect();
 
-                    let mut options = ui.ctx().options(|opt| opt.clone());
-                    ui.checkbox(&mut options.tessellation_options.debug_paint_clip_rects, "Paint clip rectangles");
-                    ui.checkbox(&mut options.tessellation_options.debug_paint_text_rects, "Paint text bounds");
-                    ui.ctx().options_mut(|opt| *opt = options);
-                });
-                ui.hyperlink_to("Source code", "https://github.com/david072/funcially");
+        let curves = if include_plot {
+            plotted_functions(&self.lines).into_iter()
+                .map(|(name, f)| export::PlotCurve::new(&self.calculator, name.to_string(), f))
+                .collect()
+        } else { vec![] };
 
-                if update {
-                    // Make update_lines() refresh on the next frame, since now source and source_old are not the same
-                    self.source_old.clear();
-                }
-            });
+        export::Worksheet { lines, curves }
     }
 
     fn show_debug_information(&mut self, ctx: &Context) {
         let debug_information = &mut self.debug_information;
+        let debug_verbosity = &mut self.debug_verbosity;
+        let mut verbosity_changed = false;
 
         Window::new("Debug Information")
             .open(&mut self.is_debug_info_open)
             .vscroll(true)
             .enabled(self.is_ui_enabled)
             .show(ctx, |ui| {
+                ComboBox::from_label("Output")
+                    .selected_text(debug_verbosity.to_string())
+                    .show_ui(ui, |ui| {
+                        for verbosity in &Verbosity::ALL[1..] {
+                            verbosity_changed |= ui.selectable_value(debug_verbosity, *verbosity, verbosity.to_string()).clicked();
+                        }
+                    });
+
                 if let Some(debug_information) = debug_information {
                     if ui.button("📋").clicked() {
                         ui.output_mut(|out| out.copied_text = debug_information.clone());
@@ -634,6 +2042,36 @@ impl App {
                         .show(ui);
                 }
             });
+
+        if verbosity_changed { self.get_debug_info_for_current_line(); }
+    }
+
+    fn profiling_window(&mut self, ctx: &Context) {
+        // The timings are only recorded while the window is open
+        if !self.calculator.profiling {
+            self.calculator.profiling = true;
+            self.source_old.clear();
+        }
+
+        let source = self.full_source();
+        let profile_table = &mut self.profile_table;
+        let results = self.evaluator.as_ref().map_or(&[][..], Evaluator::results);
+        Window::new("Profiling")
+            .open(&mut self.is_profiling_open)
+            .vscroll(true)
+            .enabled(self.is_ui_enabled)
+            .show(ctx, |ui| profile_table.show(ui, &FONT_ID, &source, results));
+
+        if !self.is_profiling_open { self.calculator.profiling = false; }
+    }
+
+    fn dependency_graph_window(&mut self, ctx: &Context) {
+        let source = self.full_source();
+        Window::new("Line Dependencies")
+            .open(&mut self.is_dependency_graph_open)
+            .scroll2([true, true])
+            .enabled(self.is_ui_enabled)
+            .show(ctx, |ui| show_dependency_graph(ui, &FONT_ID, &source));
     }
 
     /// Handles shortcuts that modify what's inside the textedit => needs a cursor range
@@ -647,6 +2085,17 @@ impl App {
         if ui.input_mut(|i| i.consume_shortcut(&COPY_RESULT_SHORTCUT)) {
             self.copy_result(ui, cursor_range);
         }
+        if ui.input_mut(|i| i.consume_shortcut(&GO_TO_DEFINITION_SHORTCUT)) {
+            self.go_to_definition(ui.ctx(), cursor_range.primary.ccursor.index);
+        }
+        if ui.input_mut(|i| i.consume_shortcut(&FIND_USAGES_SHORTCUT)) {
+            self.find_usages(cursor_range.primary.ccursor.index);
+        }
+
+        let line = cursor_range.primary.pcursor.paragraph;
+        if ui.input_mut(|i| i.consume_shortcut(&READ_RESULT_SHORTCUT)) {
+            self.announcement = Some(self.result_description(line));
+        }
     }
 
     /// Handles shortcuts that are global => don't need a cursor range
@@ -660,6 +2109,90 @@ impl App {
             self.search_state.open = true;
             self.search_state.should_have_focus = true;
         }
+        if !self.usages.is_empty() && ui.input_mut(|i| i.consume_key(Modifiers::NONE, Key::Escape)) {
+            self.usages.clear();
+        }
+    }
+
+    /// Describes the result of the (zero-based) line `line` for screen readers
+    fn result_description(&self, line: usize) -> String {
+        let number = self.folded_view.source_line(line) + 1;
+        match self.lines.iter().filter(|l| !matches!(l, Line::WrappedLine)).nth(line) {
+            Some(Line::Line { output_text, is_error: true, .. }) => format!("Line {number}: error: {}", output_text.trim()),
+            Some(Line::Line { output_text, .. }) if !output_text.trim().is_empty() => {
+                format!("Line {number}: {}", output_text.trim())
+            }
+            _ => format!("Line {number} has no result"),
+        }
+    }
+
+    /// Finds the closest line with a result above (`up`) or below the (zero-based) line `line` and
+    /// reads out its result
+    ///
+    /// **Returns:** The character index of the end of the line
+    fn move_to_result(&mut self, line: usize, up: bool) -> Option<usize> {
+        let lines = self.lines.iter().filter(|l| !matches!(l, Line::WrappedLine)).collect::<Vec<_>>();
+        let has_result = |i: &usize| matches!(lines.get(*i), Some(Line::Line { output_text, .. }) if !output_text.trim().is_empty());
+        let target = if up {
+            (0..line).rev().find(has_result)
+        } else {
+            (line + 1..lines.len()).find(has_result)
+        };
+        let target = target?;
+
+        self.announcement = Some(self.result_description(target));
+        Some(self.source.split('\n')
+            .take(target + 1)
+            .map(|l| l.chars().count() + 1)
+            .sum::<usize>() - 1)
+    }
+
+    /// Returns the references to the variable or function whose name is at the character index
+    /// `index` in the input
+    fn references_at(&self, index: usize) -> Vec<IdentifierReference> {
+        let byte_index = self.source.char_indices().nth(index).map_or(self.source.len(), |(i, _)| i);
+        let line = self.source[..byte_index].matches('\n').count();
+        let column = byte_index - self.source[..byte_index].rfind('\n').map_or(0, |i| i + 1);
+
+        let references = identifier_references(&self.source);
+        let Some(name) = references.iter()
+            .find(|reference| reference.range.start_line == line &&
+                (reference.range.start_char..=reference.range.end_char).contains(&column))
+            .map(|reference| reference.name.clone()) else { return vec![]; };
+        references.into_iter().filter(|reference| reference.name == name).collect()
+    }
+
+    /// Returns the byte range of `reference` in the input
+    fn reference_byte_range(&self, reference: &IdentifierReference) -> Range<usize> {
+        let line_start = self.source.split('\n')
+            .take(reference.range.start_line)
+            .map(|line| line.len() + 1)
+            .sum::<usize>();
+        line_start + reference.range.start_char..line_start + reference.range.end_char
+    }
+
+    /// Moves the cursor to the definition of the variable or function at the character index
+    /// `index`. If it is defined multiple times, the last definition above the index is used.
+    fn go_to_definition(&mut self, ctx: &Context, index: usize) {
+        let references = self.references_at(index);
+        let line = self.source.chars().take(index).filter(|c| *c == '\n').count();
+        let mut definitions = references.iter().filter(|reference| reference.is_definition);
+        let Some(definition) = definitions.clone()
+            .rfind(|reference| reference.range.start_line <= line)
+            .or_else(|| definitions.next()) else { return; };
+
+        let start = self.reference_byte_range(definition).start;
+        let cursor = CCursor::new(self.source[..start].chars().count());
+        self.set_input_text_edit_ccursor_range(ctx, CCursorRange::one(cursor));
+        self.should_scroll_to_input_text_cursor = true;
+        self.input_should_request_focus = true;
+    }
+
+    /// Highlights the references to the variable or function at the character index `index`
+    fn find_usages(&mut self, index: usize) {
+        self.usages = self.references_at(index).iter()
+            .map(|reference| self.reference_byte_range(reference))
+            .collect();
     }
 
     fn toggle_commentation(&mut self, ctx: &Context, cursor_range: CursorRange) {
@@ -797,13 +2330,31 @@ impl App {
         }
     }
 
+    /// Replaces the selection with the values found in the text of the clipboard
+    #[cfg(not(target_arch = "wasm32"))]
+    fn paste_values(&mut self, ctx: &Context, mode: PasteMode) {
+        let Ok(text) = arboard::Clipboard::new().and_then(|mut clipboard| clipboard.get_text()) else { return; };
+        let values = funcially_core::paste_values(&text, mode);
+        if !values.is_empty() { self.replace_selection(ctx, &values); }
+    }
+
     fn format_source(&mut self) {
+        self.map_source_lines(|calculator, line| calculator.format(line));
+    }
+
+    /// Rewrites the source from the input mode `from` to `to`. Lines that cannot be converted are
+    /// left as they are.
+    fn convert_source(&mut self, from: InputMode, to: InputMode) {
+        self.map_source_lines(|calculator, line| calculator.convert(line, from, to));
+    }
+
+    fn map_source_lines(&mut self, f: impl Fn(&Calculator, &str) -> CalcResult<String>) {
         let mut new_source = String::new();
 
         let line_count = self.source.lines().count();
         for (i, line) in self.source.lines().enumerate() {
             if !line.is_empty() {
-                match self.calculator.format(line) {
+                match f(&self.calculator, line) {
                     Ok(fmt) => new_source += &fmt,
                     Err(_) => new_source += line,
                 }
@@ -835,6 +2386,125 @@ impl App {
     }
 
     #[cfg(not(target_arch = "wasm32"))]
+    fn prelude_errors_dialog(&mut self, ctx: &Context) {
+        if self.prelude_errors.is_empty() { return; }
+
+        self.is_ui_enabled = false;
+        let mut close = false;
+        dialog(ctx, Some("Prelude Errors"), |ui| {
+            ui.vertical(|ui| {
+                ui.label("The following errors occurred while loading the prelude:");
+                ui.add_space(5.0);
+                for error in &self.prelude_errors {
+                    ui.label(RichText::new(error).color(ERROR_COLOR));
+                }
+
+                ui.add_space(15.0);
+                ui.vertical_centered(|ui| {
+                    close = ui.button("Ok").clicked();
+                });
+            });
+
+            if helpers::is_key_pressed(ui, Key::Escape) { close = true; }
+        });
+
+        if close {
+            self.prelude_errors.clear();
+            self.is_ui_enabled = true;
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn recovered_source_dialog(&mut self, ctx: &Context) {
+        // Wait for the other dialogs to be closed
+        if self.recovered_source.is_none() || !self.prelude_errors.is_empty() { return; }
+
+        self.is_ui_enabled = false;
+        let mut should_restore = None;
+        dialog(ctx, Some("Unsaved Changes"), |ui| {
+            ui.vertical(|ui| {
+                ui.label("funcially wasn't closed properly last time. Restoring the changes that weren't saved replaces the current input.");
+
+                ui.add_space(15.0);
+                ui.horizontal(|ui| {
+                    if ui.button("Restore").clicked() { should_restore = Some(true); }
+                    if ui.button("Discard").clicked() { should_restore = Some(false); }
+                });
+            });
+        });
+
+        if let Some(should_restore) = should_restore {
+            let source = self.recovered_source.take().unwrap();
+            if should_restore {
+                self.source = source;
+            } else {
+                self.recovery.clear();
+            }
+            self.is_ui_enabled = true;
+        }
+    }
+
+    /// Starts or stops watching the clipboard depending on the setting and shows the results of
+    /// newly copied text, which the watcher calculates in the background
+    #[cfg(not(target_arch = "wasm32"))]
+    fn watch_clipboard(&mut self, ctx: &Context) {
+        if !self.watch_clipboard {
+            self.clipboard_watcher = None;
+            self.clipboard_result = None;
+            return;
+        }
+
+        // The copied text is calculated in the base environment, so that the environment of the
+        // current source stays intact
+        let watcher = self.clipboard_watcher
+            .get_or_insert_with(|| ClipboardWatcher::spawn(ctx.clone(), self.calculator.base_env.clone()));
+        watcher.set_settings(self.calculator.context.borrow().settings, self.use_thousands_separator);
+        let Some((expression, result)) = watcher.take() else { return; };
+        self.clipboard_result = Some(ClipboardResult {
+            expression,
+            result,
+            time: ctx.input(|i| i.time),
+        });
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn clipboard_result_toast(&mut self, ctx: &Context) {
+        let Some(ClipboardResult { expression, result, time }) = &self.clipboard_result else { return; };
+
+        let remaining = CLIPBOARD_RESULT_DURATION - (ctx.input(|i| i.time) - time);
+        if remaining <= 0.0 {
+            self.clipboard_result = None;
+            return;
+        }
+        ctx.request_repaint_after(std::time::Duration::from_secs_f64(remaining));
+
+        let mut paste = false;
+        let mut close = false;
+        Area::new("clipboard-result")
+            .anchor(Align2::RIGHT_BOTTOM, Vec2::new(-10.0, -30.0))
+            .order(Order::Foreground)
+            .show(ctx, |ui| {
+                egui::Frame::popup(ui.style()).show(ui, |ui| {
+                    ui.label(RichText::new(format!("{expression} = {result}")).font(FONT_ID));
+                    ui.horizontal(|ui| {
+                        paste = ui.button("Paste result").clicked();
+                        if ui.button("Copy result").clicked() {
+                            ui.output_mut(|out| out.copied_text = result.clone());
+                            close = true;
+                        }
+                        close |= ui.button("Dismiss").clicked();
+                    });
+                });
+            });
+
+        if paste {
+            let result = result.clone();
+            self.replace_selection(ctx, &result);
+            close = true;
+        }
+        if close { self.clipboard_result = None; }
+    }
+
     fn new_version_dialog(&mut self, ctx: &Context) {
         if let Ok(mut show_new_version_dialog) = self.show_new_version_dialog.lock() {
             if *show_new_version_dialog {
@@ -884,8 +2554,31 @@ impl App {
 
         ui.toggle_value(&mut self.search_state.match_case, "Aa")
             .on_hover_text("Match case");
+        ui.toggle_value(&mut self.search_state.whole_word, "W")
+            .on_hover_text("Match whole words");
+        ui.toggle_value(&mut self.search_state.use_regex, ".*")
+            .on_hover_text("Use a regular expression");
+        ui.toggle_value(&mut self.search_state.show_replace, "⇄")
+            .on_hover_text("Replace");
+
+        if self.search_state.show_replace {
+            TextEdit::singleline(&mut self.search_state.replace_text)
+                .font(FontSelection::from(FONT_ID))
+                .hint_text("Replace")
+                .desired_width(120.0)
+                .show(ui);
+            if ui.small_button("Replace").clicked() {
+                self.search_state.replace(&mut self.source);
+            }
+            if ui.small_button("Replace all").clicked() {
+                self.search_state.replace_all(&mut self.source);
+            }
+        }
 
         self.search_state.update(&self.source);
+        if let Some(error) = &self.search_state.error {
+            ui.label(RichText::new("Invalid regex").color(ERROR_COLOR)).on_hover_text(error);
+        }
 
         if ui.small_button("X").clicked() {
             self.search_state.open = false;
@@ -929,23 +2622,40 @@ impl eframe::App for App {
         {
             if self.first_frame {
                 self.check_for_update();
+                self.prelude_errors = self.calculator.load_prelude().iter().map(ToString::to_string).collect();
             }
 
             self.new_version_dialog(ctx);
+            self.prelude_errors_dialog(ctx);
+            self.recovered_source_dialog(ctx);
+            // An open file isn't the app's own source, which the recovery file is for
+            if self.external_file.is_none() {
+                self.recovery.update(ctx, &self.source, &self.saved_source, ctx.input(|i| i.time));
+            }
+            self.update_external_file(ctx);
+            if self.is_open_file_open { self.open_file_window(ctx); }
+            self.watch_clipboard(ctx);
+            self.clipboard_result_toast(ctx);
         }
 
+        self.fold_source(ctx);
+
         if !self.is_debug_info_open { self.debug_information = None; }
 
-        FullScreenPlot::new(
+        let inserted_value = FullScreenPlot::new(
             ctx.available_rect().size(),
             &self.lines,
             &self.calculator,
+            &mut self.plot_axes,
         ).maybe_show(ctx);
+        if let Some(value) = inserted_value { self.append_line(ctx, &value.to_string()); }
 
         self.line_picker_dialog(ctx);
 
+        let is_mobile = self.is_mobile_layout(ctx);
         TopBottomPanel::top("menu_bar").show(ctx, |ui| {
             ui.set_enabled(self.is_ui_enabled);
+            if is_mobile { ui.spacing_mut().interact_size.y = TOUCH_TARGET_SIZE; }
 
             menu::bar(ui, |ui| {
                 ui.menu_button("File", |ui| {
@@ -953,6 +2663,15 @@ impl eframe::App for App {
                         ui.close_menu();
                     }
 
+                    if ui.toggle_value(&mut self.is_export_open, "Export").clicked() {
+                        ui.close_menu();
+                    }
+
+                    #[cfg(not(target_arch = "wasm32"))]
+                    if ui.toggle_value(&mut self.is_open_file_open, "Open file").clicked() {
+                        ui.close_menu();
+                    }
+
                     if ui.button("Collapse side panels").clicked() {
                         fn collapse_panel_state(ctx: &Context, id: impl Into<Id>) {
                             let id = id.into();
@@ -962,7 +2681,7 @@ impl eframe::App for App {
                             }
                         }
 
-                        collapse_panel_state(ctx, OUTPUT_PANEL_ID);
+                        self.output_panel_fraction = 0.0;
                         collapse_panel_state(ctx, PLOT_PANEL_ID);
                         self.is_plot_open = false;
                     }
@@ -1000,6 +2719,19 @@ impl eframe::App for App {
                         self.format_source();
                         ui.close_menu();
                     }
+
+                    #[cfg(not(target_arch = "wasm32"))]
+                    {
+                        ui.separator();
+                        if ui.button("Paste values as lines").clicked() {
+                            self.paste_values(ctx, PasteMode::Lines);
+                            ui.close_menu();
+                        }
+                        if ui.button("Paste values as vector").clicked() {
+                            self.paste_values(ctx, PasteMode::Vector);
+                            ui.close_menu();
+                        }
+                    }
                 });
 
                 ui.menu_button("Navigate", |ui| {
@@ -1024,12 +2756,29 @@ impl eframe::App for App {
                         self.is_debug_info_open = true;
                         ui.close_menu();
                     }
+                    if ui.button("Profile lines").clicked() {
+                        self.is_profiling_open = true;
+                        ui.close_menu();
+                    }
+                    if ui.button("Show line dependencies").clicked() {
+                        self.is_dependency_graph_open = true;
+                        ui.close_menu();
+                    }
                 });
 
                 #[cfg(target_arch = "wasm32")]
                 ui.toggle_value(&mut self.is_download_open, "Download");
+                #[cfg(target_arch = "wasm32")]
+                if ui.button("Share").clicked() {
+                    self.share_link = share::link(&self.full_source());
+                }
 
+                ui.toggle_value(&mut self.is_diff_open, "Compare");
                 ui.toggle_value(&mut self.is_help_open, "Help");
+                ui.toggle_value(&mut self.is_keypad_open, "Keypad");
+                if ui.add_enabled(self.tutorial.is_none(), Button::new("Tutorial")).clicked() {
+                    self.start_tutorial();
+                }
 
                 ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
                     ui.toggle_value(&mut self.is_plot_open, "🗠 Plot");
@@ -1058,29 +2807,70 @@ impl eframe::App for App {
                         let bottom_text = RichText::new(&self.bottom_text)
                             .font(FontId::proportional(FOOTER_FONT_SIZE));
                         ui.label(bottom_text);
+                        if let Some(announcement) = &self.announcement {
+                            let response = ui.label(RichText::new(announcement).font(FontId::proportional(FOOTER_FONT_SIZE)));
+                            // Screen readers read the label out whenever it changes
+                            ui.ctx().accesskit_node_builder(response.id, |builder| builder.set_live(egui::accesskit::Live::Polite));
+                        }
+                        if self.modal_editing {
+                            ui.label(RichText::new(format!("-- {} --", self.modal_editor.mode)).font(FontId::monospace(FOOTER_FONT_SIZE)));
+                        }
+                        if self.evaluator.as_ref().map_or(false, |evaluator| !evaluator.is_finished()) {
+                            ui.spinner().on_hover_text("Calculating...");
+                        }
                     });
                 });
             });
 
+        if is_mobile {
+            TopBottomPanel::bottom("touch_keys").show(ctx, |ui| {
+                ui.set_enabled(self.is_ui_enabled);
+                ui.add_space(4.0);
+                self.touch_keys_ui(ui);
+                ui.add_space(4.0);
+            });
+        }
+
         // We wait for the second frame to have the lines updated if they've been loaded on startup
         if !self.first_frame && self.is_plot_open { self.plot_panel(ctx); }
+        if self.is_keypad_open { self.keypad_panel(ctx); }
 
         if self.is_help_open { self.help_window(ctx); }
+        self.tutorial_window(ctx);
         #[cfg(target_arch = "wasm32")]
         if self.is_download_open { self.download_window(ctx); }
+        #[cfg(target_arch = "wasm32")]
+        {
+            self.share_window(ctx);
+            self.shared_source_dialog(ctx);
+        }
         if self.is_settings_open { self.settings_window(ctx); }
         if self.is_debug_info_open { self.show_debug_information(ctx); }
+        if self.is_profiling_open { self.profiling_window(ctx); }
+        if self.is_dependency_graph_open { self.dependency_graph_window(ctx); }
+        if self.is_diff_open { self.diff_window(ctx); }
+        if self.is_export_open { self.export_window(ctx); }
+        self.plot_data_window(ctx);
 
         let mut output_scroll_area_id: Option<Id> = None;
+        // The name and new value of the slider the user dragged
+        let mut changed_slider: Option<(String, f64)> = None;
+        // What the user chose in the context menu of a result
+        let mut line_action: Option<LineAction> = None;
+        // The title of the section the user folded or unfolded
+        let mut toggled_section: Option<String> = None;
+
+        if !self.lines.is_empty() && !is_mobile {
+            // The panel keeps its share of the window when it's resized, so its width is only taken
+            // from egui while the divider is dragged
+            let available_width = ctx.available_rect().width();
+            let is_resizing = ctx.memory(|memory| memory.is_being_dragged(Id::new(OUTPUT_PANEL_ID).with("__resize")));
+            if !is_resizing {
+                let rect = Rect::from_min_size(Pos2::ZERO, vec2(available_width * self.output_panel_fraction, 0.0));
+                ctx.data_mut(|data| data.insert_persisted(Id::new(OUTPUT_PANEL_ID), PanelState { rect }));
+            }
 
-        if !self.lines.is_empty() {
-            #[cfg(not(target_arch = "wasm32"))]
-                let default_width = _frame.info().window_info.size.x * (1.0 / 3.0);
-            #[cfg(target_arch = "wasm32")]
-                let default_width = 40.0;
-
-            SidePanel::right(OUTPUT_PANEL_ID)
-                .default_width(default_width)
+            let panel = SidePanel::right(OUTPUT_PANEL_ID)
                 .show(ctx, |ui| {
                     ui.add_space(8.0);
                     ui.spacing_mut().item_spacing.y = 0.0;
@@ -1095,41 +2885,90 @@ impl eframe::App for App {
                         .enable_scrolling(false)
                         .show(ui, |ui| {
                             ui.reset_style();
+                            // The width of the column the units are aligned in, which fits the widest unit
+                            let unit_column_width = self.align_result_units.then(|| {
+                                self.lines.iter()
+                                    .filter_map(|line| match line {
+                                        Line::Line { output_text, .. } => Some(output_text[line.unit_start()?..].trim()),
+                                        _ => None,
+                                    })
+                                    .map(|unit| ui.fonts(|f| f.layout_no_wrap(unit.to_owned(), FONT_ID, Color32::WHITE)).size().x)
+                                    .fold(0.0, f32::max)
+                            });
                             let mut line_index = 1usize;
+                            let source_lines = self.source.split('\n').collect::<Vec<_>>();
                             for line in &mut self.lines {
+                                let source_line = self.folded_view.source_line(line_index - 1);
                                 if let Line::Line {
                                     output_text: text,
-                                    function,
-                                    is_error,
-                                    show_in_plot,
+                                    slider: Some(slider),
+                                    is_error: false,
                                     ..
                                 } = line {
-                                    if !*is_error {
-                                        if let Some(Function(_, arg_count, _)) = function {
-                                            if *arg_count == 1 {
-                                                ui.with_layout(Layout::right_to_left(Align::TOP), |ui| {
-                                                    let mut show_ui = |ui: &mut Ui| {
-                                                        ui.checkbox(show_in_plot, "Plot");
-                                                    };
-
-                                                    if ui.available_width() < 30.0 {
-                                                        ui.menu_button("☰", show_ui);
-                                                    } else {
-                                                        show_ui(ui);
-                                                    }
-                                                });
-
-                                                ui.add_space(-6.5);
-
-                                                if matches!(line, Line::Line { .. } | Line::Color(..) | Line::Empty) {
-                                                    line_index += 1;
-                                                }
-                                                continue;
-                                            }
+                                    ui.with_layout(Layout::right_to_left(Align::TOP), |ui| {
+                                        let response = ui.add(egui::Slider::new(&mut slider.value, slider.min..=slider.max)
+                                            .step_by(slider.step)
+                                            .show_value(false));
+                                        if response.changed() {
+                                            changed_slider = Some((slider.name.clone(), slider.value));
                                         }
-                                    }
+                                        ui.label(RichText::new(&*text).font(FONT_ID).color(Color32::GREEN));
+                                    });
 
-                                    output_text(ui, text, FONT_ID, line_index);
+                                    ui.add_space(-6.5);
+                                    line_index += 1;
+                                    continue;
+                                }
+
+                                let can_plot = matches!(line, Line::Line { is_error: false, .. }) && line.plot_name().is_some();
+                                let unit_column = unit_column_width
+                                    .and_then(|width| Some((line.unit_start()?, width)));
+                                if let Line::Line { output_text: text, output_color_segments, show_in_plot, warnings, hints, .. } = line {
+                                    let response = if can_plot {
+                                        let color = if *show_in_plot { Color32::GREEN } else { Color32::GRAY };
+                                        let response = ui.with_layout(Layout::right_to_left(Align::TOP), |ui| {
+                                            ui.add(Label::new(RichText::new("🗠").size(FONT_SIZE).color(color)).sense(Sense::click()))
+                                                .on_hover_text("Click to plot, right-click for more actions")
+                                        }).inner;
+                                        if response.clicked() { *show_in_plot = !*show_in_plot; }
+                                        response
+                                    } else if !warnings.is_empty() {
+                                        // The color shows that there are warnings
+                                        output_text(ui, text, FONT_ID, source_line + 1, WARNING_COLOR, &[], unit_column)
+                                            .on_hover_text(warnings.iter().chain(hints.iter()).cloned().collect::<Vec<_>>().join("\n"))
+                                    } else if !hints.is_empty() {
+                                        // Hints only dim the result
+                                        output_text(ui, text, FONT_ID, source_line + 1, HINT_COLOR, &[], unit_column)
+                                            .on_hover_text(hints.join("\n"))
+                                    } else {
+                                        let segments = if self.highlight_results { &output_color_segments[..] } else { &[] };
+                                        output_text(ui, text, FONT_ID, source_line + 1, Color32::GREEN, segments, unit_column)
+                                    };
+
+                                    let display = self.line_ids.id(source_line)
+                                        .and_then(|id| self.line_displays.get(&id))
+                                        .copied();
+                                    response.context_menu(|ui| {
+                                        if let Some(action) = line.context_menu(ui, line_index, display) {
+                                            line_action = Some(action);
+                                            ui.close_menu();
+                                        }
+                                    });
+                                    ui.add_space(-3.0);
+                                } else if let Some(title) = source_lines.get(line_index - 1)
+                                    .filter(|_| !matches!(line, Line::WrappedLine))
+                                    .and_then(|line| folding::section_title(line)) {
+                                    let hidden_line_count = self.folded_view.hidden_line_count(line_index - 1);
+                                    let text = match hidden_line_count {
+                                        Some(1) => "▶ 1 line".to_string(),
+                                        Some(count) => format!("▶ {count} lines"),
+                                        None => "▼".to_string(),
+                                    };
+                                    let response = ui.with_layout(Layout::right_to_left(Align::TOP), |ui| {
+                                        ui.add(Label::new(RichText::new(text).size(FONT_SIZE).color(Color32::GRAY)).sense(Sense::click()))
+                                            .on_hover_text(if hidden_line_count.is_some() { "Click to unfold" } else { "Click to fold" })
+                                    }).inner;
+                                    if response.clicked() { toggled_section = Some(title.to_string()); }
                                     ui.add_space(-3.0);
                                 } else {
                                     ui.add_space(FONT_SIZE + 2.0);
@@ -1144,6 +2983,24 @@ impl eframe::App for App {
                     output_scroll_area_id = Some(response.id);
                     ui.reset_style();
                 });
+
+            if is_resizing {
+                self.output_panel_fraction = (panel.response.rect.width() / available_width).clamp(0.0, 1.0);
+            }
+        }
+
+        if let Some(action) = line_action { self.apply_line_action(action); }
+        if let Some(title) = toggled_section {
+            if !self.folded_sections.remove(&title) { self.folded_sections.insert(title); }
+            // Make update_lines() match the lines to the input's rows again
+            self.source_old.clear();
+        }
+
+        if let Some((name, value)) = changed_slider {
+            self.calculator.set_slider_value(&name, value);
+            self.slider_values.insert(name, value);
+            // Recalculate everything depending on the variable, including the plotted functions
+            self.source_old.clear();
         }
 
         CentralPanel::default().show(ctx, |ui| {
@@ -1154,24 +3011,58 @@ impl eframe::App for App {
 
             let response = ScrollArea::vertical().show(ui, |ui| {
                 ui.with_layout(Layout::left_to_right(Align::TOP), |ui| {
-                    let char_width = ui.fonts(|f| f.glyph_width(&FONT_ID, '0')) + 2.0;
-
-                    let longest_row_chars = self.line_numbers_text.lines()
-                        .last()
-                        .map(str::len)
-                        .unwrap_or_default() as f32;
-
-                    TextEdit::multiline(&mut self.line_numbers_text)
-                        .frame(false)
-                        .font(FontSelection::from(FONT_ID))
-                        .interactive(false)
-                        .desired_width(longest_row_chars * char_width)
-                        .desired_rows(rows)
-                        .margin(vec2(0.0, 2.0))
-                        .show(ui);
+                    // The line numbers wouldn't line up with the results below the lines
+                    if !is_mobile {
+                        let char_width = ui.fonts(|f| f.glyph_width(&FONT_ID, '0')) + 2.0;
+
+                        let longest_row_chars = self.line_numbers_text.lines()
+                            .last()
+                            .map(str::len)
+                            .unwrap_or_default() as f32;
+
+                        let line_numbers = TextEdit::multiline(&mut self.line_numbers_text)
+                            .frame(false)
+                            .font(FontSelection::from(FONT_ID))
+                            .interactive(false)
+                            .desired_width(longest_row_chars * char_width)
+                            .desired_rows(rows)
+                            .margin(vec2(0.0, 2.0))
+                            .show(ui);
+
+                        let line_numbers_response = ui.interact(
+                            line_numbers.response.rect,
+                            Id::new(LINE_NUMBERS_ID),
+                            Sense::click(),
+                        ).on_hover_cursor(CursorIcon::PointingHand);
+                        if line_numbers_response.clicked() {
+                            let line_number = line_numbers_response.interact_pointer_pos()
+                                .map(|pos| line_numbers.galley.cursor_from_pos(pos - line_numbers.text_draw_pos))
+                                .and_then(|cursor| self.line_numbers_text.lines().nth(cursor.rcursor.row))
+                                .and_then(|number| number.trim().parse::<usize>().ok());
+                            if let Some(line_number) = line_number {
+                                self.insert_line_reference(ctx, line_number);
+                            }
+                        }
+                    }
 
                     if let Some(mut input_state) = TextEditState::load(ctx, Id::new(INPUT_TEXT_EDIT_ID)) {
                         if let Some(mut cursor_range) = input_state.ccursor_range() {
+                            // Handled before the input, which would move the cursor up or down otherwise
+                            if ctx.memory(|memory| memory.has_focus(Id::new(INPUT_TEXT_EDIT_ID))) {
+                                let line = self.source.chars().take(cursor_range.primary.index).filter(|c| *c == '\n').count();
+                                for (shortcut, up) in [(PREVIOUS_RESULT_SHORTCUT, true), (NEXT_RESULT_SHORTCUT, false)] {
+                                    if !ui.input_mut(|i| i.consume_shortcut(&shortcut)) { continue; }
+                                    if let Some(index) = self.move_to_result(line, up) {
+                                        cursor_range = CCursorRange::one(CCursor::new(index));
+                                        self.should_scroll_to_input_text_cursor = true;
+                                    }
+                                }
+                            }
+
+                            if self.modal_editing && ctx.memory(|memory| memory.has_focus(Id::new(INPUT_TEXT_EDIT_ID))) {
+                                ui.input_mut(|input| self.modal_editor.handle_events(&mut input.events, &mut self.source, &mut cursor_range));
+                            }
+
                             let mut i = 0usize;
                             ui.input_mut(|input| {
                                 const BRACKETS: [(char, char); 3] = [('(', ')'), ('[', ']'), ('{', '}')];
@@ -1254,12 +3145,38 @@ impl eframe::App for App {
                         .desired_rows(rows)
                         .layouter(&mut input_layouter(
                             lines,
-                            if self.search_state.open { Some(self.search_state.occurrences.clone()) } else { None },
+                            if self.search_state.open {
+                                Some(self.search_state.occurrences.clone())
+                            } else if !self.usages.is_empty() {
+                                Some(self.usages.clone())
+                            } else {
+                                None
+                            },
                             self.search_state.selected_range_if_open(),
+                            if is_mobile { ctx.fonts(|fonts| fonts.row_height(&RESULT_BELOW_LINE_FONT_ID)) } else { 0.0 },
                         ))
                         .show(ui);
 
-                    self.update_lines(output.galley.clone());
+                    self.update_lines(ui.ctx(), output.galley.clone());
+                    if let (true, Some(range)) = (output.response.clicked() && ui.input(|i| i.modifiers.command), output.cursor_range) {
+                        self.go_to_definition(ctx, range.primary.ccursor.index);
+                    }
+                    output.response.clone().context_menu(|ui| {
+                        let index = self.input_text_cursor_range.primary.ccursor.index;
+                        if ui.button("Go to definition").clicked() {
+                            self.go_to_definition(ui.ctx(), index);
+                            ui.close_menu();
+                        }
+                        if ui.button("Find usages").clicked() {
+                            self.find_usages(index);
+                            ui.close_menu();
+                        }
+                    });
+                    if is_mobile {
+                        self.paint_results_below_lines(ui, &output.galley, output.text_draw_pos);
+                    } else if let (true, Some(range)) = (self.show_inline_results, output.cursor_range) {
+                        self.paint_inline_result(ui, &output.galley, output.response.rect.min, range.primary.pcursor.paragraph);
+                    }
 
                     if let Some(range) = output.cursor_range {
                         self.input_text_cursor_range = range;
@@ -1313,19 +3230,83 @@ impl eframe::App for App {
             }
         });
 
+        self.unfold_source();
         self.first_frame = false;
     }
 
     fn save(&mut self, storage: &mut dyn Storage) {
+        // The contents of an open file are saved to the file instead
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(file) = &self.external_file {
+            let source = std::mem::replace(&mut self.source, file.own_source.clone());
+            eframe::set_value(storage, &app_key(), self);
+            eframe::set_value(storage, &settings_key(), &self.calculator.context.borrow().settings);
+            self.source = source;
+            return;
+        }
+
+        // The snapshot is taken from the results of the evaluator, so that saving doesn't calculate
+        // the source again. If they aren't finished yet, it's taken on one of the next saves.
+        if self.saved_source != self.source || !is_snapshot_of(&self.saved_snapshot, &self.source) {
+            if let Some(results) = self.evaluator.as_ref().and_then(|evaluator| evaluator.finished_results(&self.source)) {
+                let document = Document::from_cached_results(&mut self.document_cache, &self.source, results);
+                self.saved_snapshot = document_snapshot(&self.source, &document);
+            }
+        }
+        self.saved_source = self.source.clone();
+        // The recovery file is still needed if the user hasn't decided whether to restore it yet
+        #[cfg(not(target_arch = "wasm32"))]
+        if self.recovered_source.is_none() { self.recovery.clear(); }
         eframe::set_value(storage, &app_key(), self);
         eframe::set_value(storage, &settings_key(), &self.calculator.context.borrow().settings);
     }
 }
 
+/// The lines of `source` together with their results in `document`
+fn document_snapshot(source: &str, document: &Document) -> Vec<DiffLine> {
+    source.split('\n').zip(document.lines())
+        .map(|(input, line)| DiffLine {
+            input: input.to_string(),
+            result: match &line.kind {
+                DocumentLineKind::Result(Ok(_)) if line.suppressed => DiffResult::Nothing,
+                DocumentLineKind::Result(result) => DiffResult::new(result),
+                _ => DiffResult::Nothing,
+            },
+        })
+        .collect()
+}
+
+/// Whether `snapshot` contains the lines of `source`
+fn is_snapshot_of(snapshot: &[DiffLine], source: &str) -> bool {
+    snapshot.len() == source.split('\n').count() &&
+        snapshot.iter().zip(source.split('\n')).all(|(line, input)| line.input == input)
+}
+
+/// The visuals of the app. The high contrast ones use pure black and white and thicker strokes.
+fn visuals(high_contrast: bool) -> Visuals {
+    let mut visuals = Visuals::dark();
+    if high_contrast {
+        visuals.override_text_color = Some(Color32::WHITE);
+        visuals.panel_fill = Color32::BLACK;
+        visuals.window_fill = Color32::BLACK;
+        visuals.extreme_bg_color = Color32::BLACK;
+        visuals.window_stroke = Stroke::new(1.5, Color32::WHITE);
+        visuals.selection.stroke = Stroke::new(2.0, Color32::WHITE);
+        let widgets = &mut visuals.widgets;
+        for widget in [&mut widgets.noninteractive, &mut widgets.inactive, &mut widgets.hovered, &mut widgets.active, &mut widgets.open] {
+            widget.fg_stroke = Stroke::new(widget.fg_stroke.width.max(1.5), Color32::WHITE);
+            widget.bg_stroke = Stroke::new(widget.bg_stroke.width.max(1.0), Color32::WHITE);
+        }
+    }
+    visuals
+}
+
+/// `result_spacing` is the space added below every line to show its result there
 fn input_layouter(
     lines: &[Line],
     highlighted_ranges: Option<Vec<Range<usize>>>,
     selection_preview: Option<Range<usize>>,
+    result_spacing: f32,
 ) -> impl FnMut(&Ui, &str, f32) -> Arc<Galley> + '_ {
     // we need a Vec to chain it to the other iterators in `iter_over_all_ranges()`
     let selection_preview_vec = if let Some(sp) = &selection_preview {
@@ -1404,6 +3385,8 @@ fn input_layouter(
                             seg.range.contains(&(i_in_string - 1))
                         });
                         let segment = color_segment.map(|seg| seg.color);
+                        let is_related_error = segments.iter()
+                            .any(|seg| seg.is_related_error && seg.range.contains(&(i_in_string - 1)));
 
                         let highlighted = highlighted_ranges.iter()
                             // don't need to add offset here, since the highlighted_ranges already
@@ -1427,6 +3410,9 @@ fn input_layouter(
                                     Stroke::new(3.0, Color32::GOLD)
                                 } else if color_segment.map(|s| s.is_error).unwrap_or_default() {
                                     Stroke::new(2.0, Color32::RED)
+                                } else if is_related_error {
+                                    // Thinner than the underline of the error itself
+                                    Stroke::new(1.0, ERROR_COLOR)
                                 } else {
                                     Stroke::NONE
                                 },
@@ -1481,6 +3467,7 @@ fn input_layouter(
         }
 
         job.wrap.max_width = wrap_width;
-        ui.fonts(|fonts| fonts.layout_job(job))
+        let galley = ui.fonts(|fonts| fonts.layout_job(job));
+        if result_spacing > 0.0 { helpers::space_paragraphs(galley, result_spacing) } else { galley }
     }
 }
@@ -0,0 +1,67 @@
+/*
+ * Copyright (c) 2023, david072
+ *
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+use std::ops::RangeInclusive;
+
+use funcially_core::{Calculator, Function as CalcFn};
+
+pub const DEFAULT_SAMPLES: usize = 100;
+pub const MAX_SAMPLES: usize = 100_000;
+
+/// The options of the window exporting the values of the plotted functions
+pub struct PlotDataExport {
+    pub start: f64,
+    pub end: f64,
+    pub samples: usize,
+    #[cfg(not(target_arch = "wasm32"))]
+    pub path: String,
+    /// The outcome of the last export
+    pub message: Option<Result<String, String>>,
+}
+
+impl PlotDataExport {
+    pub fn new(x_range: RangeInclusive<f64>) -> Self {
+        Self {
+            start: *x_range.start(),
+            end: *x_range.end(),
+            samples: DEFAULT_SAMPLES,
+            #[cfg(not(target_arch = "wasm32"))]
+            path: dirs::document_dir().or_else(dirs::home_dir).unwrap_or_default()
+                .join("plot.csv")
+                .display()
+                .to_string(),
+            message: None,
+        }
+    }
+}
+
+/// Samples `functions` over `range` and returns the values as CSV, with a column for the
+/// arguments followed by a column for every function. Values that couldn't be calculated are
+/// left empty.
+pub fn csv(calculator: &Calculator, functions: &[(&str, &CalcFn)], range: RangeInclusive<f64>, samples: usize) -> String {
+    let columns = functions.iter()
+        .map(|(_, f)| calculator.sample_function(f, range.clone(), samples))
+        .collect::<Vec<_>>();
+
+    let mut csv = String::from("x");
+    for (name, _) in functions {
+        csv.push(',');
+        csv += name;
+    }
+    csv.push('\n');
+
+    let Some(arguments) = columns.first() else { return csv; };
+    for (i, (x, _)) in arguments.iter().enumerate() {
+        csv += &x.to_string();
+        for column in &columns {
+            csv.push(',');
+            let y = column[i].1;
+            if y.is_finite() { csv += &y.to_string(); }
+        }
+        csv.push('\n');
+    }
+    csv
+}
@@ -0,0 +1,116 @@
+/*
+ * Copyright (c) 2023, david072
+ *
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+use eframe::{CreationContext, Frame};
+use eframe::egui::*;
+
+use funcially_core::{Calculator, Document, Environment, ResultData, Settings};
+
+use crate::{App, app_key, ERROR_COLOR, FONT_ID, settings_key};
+
+/// The command line argument that starts the quick calculator instead of the editor
+pub const QUICK_ARG: &str = "--quick";
+pub const WINDOW_SIZE: Vec2 = Vec2::new(400.0, 70.0);
+
+/// A minimal window containing a single line input, meant to be summoned with a global shortcut.
+/// Calculations can use the variables and functions of the editor's (saved) input. Pressing enter
+/// copies the result and closes the window.
+pub struct QuickCalculator {
+    calculator: Calculator,
+    /// The environment after calculating the editor's input, which every calculation starts with
+    env: Environment,
+    use_thousands_separator: bool,
+    input: String,
+    result: Option<Result<String, String>>,
+}
+
+impl QuickCalculator {
+    pub fn new(cc: &CreationContext<'_>) -> Self {
+        cc.egui_ctx.set_visuals(Visuals::dark());
+
+        let mut calculator = Calculator::default();
+        let app = cc.storage.and_then(|storage| {
+            let settings: Settings = eframe::get_value(storage, &settings_key()).unwrap_or_else(Settings::default);
+            calculator.context.borrow_mut().settings = settings;
+            eframe::get_value::<App>(storage, &app_key())
+        });
+
+        // Errors in the prelude are reported by the editor
+        let _ = calculator.load_prelude();
+        let mut use_thousands_separator = false;
+        if let Some(app) = app {
+            Document::new(&mut calculator, &app.source);
+            use_thousands_separator = app.use_thousands_separator;
+        }
+
+        Self {
+            env: calculator.clone_env(),
+            calculator,
+            use_thousands_separator,
+            input: String::new(),
+            result: None,
+        }
+    }
+
+    fn calculate(&mut self) {
+        let input = self.input.trim();
+        if input.is_empty() {
+            self.result = None;
+            return;
+        }
+
+        self.calculator.context.borrow_mut().env = self.env.clone();
+        let settings = self.calculator.context.borrow().settings;
+        let mut results = self.calculator.calculate(input);
+        self.result = match results.pop().map(|result| result.data) {
+            Some(Ok((ResultData::Value(value), _))) =>
+                Some(Ok(value.format(&settings, self.use_thousands_separator).trim().to_string())),
+            Some(Ok((ResultData::Boolean { value, difference }, _))) => {
+                let mut result = (if value { "True" } else { "False" }).to_string();
+                if let Some(difference) = difference {
+                    result += &format!(" ({})", difference.format(&settings, self.use_thousands_separator));
+                }
+                Some(Ok(result))
+            }
+            Some(Ok(_)) | None => None,
+            Some(Err(e)) => Some(Err(e.error.to_string())),
+        };
+    }
+}
+
+impl eframe::App for QuickCalculator {
+    fn update(&mut self, ctx: &Context, frame: &mut Frame) {
+        CentralPanel::default().show(ctx, |ui| {
+            let response = TextEdit::singleline(&mut self.input)
+                .font(FONT_ID)
+                .hint_text("Calculate...")
+                .desired_width(f32::INFINITY)
+                .show(ui)
+                .response;
+            response.request_focus();
+            if response.changed() { self.calculate(); }
+
+            match &self.result {
+                Some(Ok(result)) => { ui.label(RichText::new(format!("= {result}")).font(FONT_ID)); }
+                Some(Err(error)) => { ui.label(RichText::new(error).font(FONT_ID).color(ERROR_COLOR)); }
+                None => {}
+            }
+
+            if ui.input(|i| i.key_pressed(Key::Enter)) {
+                if let Some(Ok(result)) = &self.result {
+                    ui.output_mut(|out| out.copied_text = result.clone());
+                    frame.close();
+                }
+            }
+            if ui.input(|i| i.key_pressed(Key::Escape)) { frame.close(); }
+        });
+    }
+
+    // The editor's state must not be touched
+    fn persist_native_window(&self) -> bool { false }
+
+    fn persist_egui_memory(&self) -> bool { false }
+}
@@ -0,0 +1,70 @@
+/*
+ * Copyright (c) 2023, david072
+ *
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+//! Crash recovery. eframe only saves the app every 30 seconds and on exit, so changes made since
+//! then would be lost in a crash. Because of this, the source is additionally written to a
+//! recovery file every few seconds while it has unsaved changes. The file is removed whenever the
+//! app is saved, so finding it on startup means that the app wasn't closed properly.
+
+use std::path::PathBuf;
+use std::time::Duration;
+
+use eframe::egui::Context;
+use funcially_core::data_dir;
+
+const RECOVERY_FILE_NAME: &str = "recovery.txt";
+/// How long changes of the source wait before they are written to the recovery file
+const AUTOSAVE_INTERVAL: f64 = 5.0;
+
+fn recovery_file() -> PathBuf {
+    data_dir().join(RECOVERY_FILE_NAME)
+}
+
+/// Returns the source in the recovery file of an earlier run, if there is one
+pub fn recovered_source() -> Option<String> {
+    std::fs::read_to_string(recovery_file()).ok()
+}
+
+/// Writes the source to the recovery file while it's different from the saved one
+#[derive(Debug, Default)]
+pub struct Recovery {
+    /// The source in the recovery file
+    written_source: Option<String>,
+    /// When the source was first found to be different from the one in the recovery file, in
+    /// seconds (see [InputState::time](eframe::egui::InputState::time))
+    changed_at: Option<f64>,
+}
+
+impl Recovery {
+    /// Writes `source` to the recovery file once it has been different from `saved_source` and the
+    /// written source for [AUTOSAVE_INTERVAL] seconds. `time` is the current time in seconds.
+    pub fn update(&mut self, ctx: &Context, source: &str, saved_source: &str, time: f64) {
+        if source == saved_source || self.written_source.as_deref() == Some(source) {
+            self.changed_at = None;
+            return;
+        }
+
+        let changed_at = *self.changed_at.get_or_insert(time);
+        let remaining = changed_at + AUTOSAVE_INTERVAL - time;
+        if remaining > 0.0 {
+            ctx.request_repaint_after(Duration::from_secs_f64(remaining));
+            return;
+        }
+
+        let _ = std::fs::create_dir_all(data_dir());
+        if std::fs::write(recovery_file(), source).is_ok() {
+            self.written_source = Some(source.to_owned());
+        }
+        self.changed_at = None;
+    }
+
+    /// Removes the recovery file, since the source was saved
+    pub fn clear(&mut self) {
+        let _ = std::fs::remove_file(recovery_file());
+        self.written_source = None;
+        self.changed_at = None;
+    }
+}
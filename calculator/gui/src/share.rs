@@ -0,0 +1,43 @@
+/*
+ * Copyright (c) 2023, david072
+ *
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+//! Shareable links, which contain the (compressed) source in the fragment of the web app's URL
+
+/// The fragment of a shared link starts with this, followed by the encoded source
+const FRAGMENT_PREFIX: &str = "source=";
+/// Links longer than this aren't supported by every browser and messenger
+pub const MAX_LINK_LENGTH: usize = 2000;
+/// The maximum size of a decoded source, so that a malicious link can't exhaust the memory
+const MAX_SOURCE_SIZE: usize = 1024 * 1024;
+const COMPRESSION_LEVEL: u8 = 9;
+
+fn encode(source: &str) -> String {
+    let compressed = miniz_oxide::deflate::compress_to_vec(source.as_bytes(), COMPRESSION_LEVEL);
+    base64::encode_config(compressed, base64::URL_SAFE_NO_PAD)
+}
+
+fn decode(fragment: &str) -> Option<String> {
+    let encoded = fragment.trim_start_matches('#').strip_prefix(FRAGMENT_PREFIX)?;
+    let compressed = base64::decode_config(encoded, base64::URL_SAFE_NO_PAD).ok()?;
+    let source = miniz_oxide::inflate::decompress_to_vec_with_limit(&compressed, MAX_SOURCE_SIZE).ok()?;
+    String::from_utf8(source).ok()
+}
+
+/// Returns a link to the current page that opens `source`
+pub fn link(source: &str) -> Option<String> {
+    let href = web_sys::window()?.location().href().ok()?;
+    let url = href.split('#').next().unwrap_or(&href);
+    Some(format!("{url}#{FRAGMENT_PREFIX}{}", encode(source)))
+}
+
+/// Returns the source contained in the URL the page was opened with (if any) and removes it from
+/// the URL, so that reloading the page doesn't open it again
+pub fn take_shared_source() -> Option<String> {
+    let location = web_sys::window()?.location();
+    let source = decode(&location.hash().ok()?)?;
+    let _ = location.set_hash("");
+    Some(source)
+}
@@ -0,0 +1,118 @@
+/*
+ * Copyright (c) 2023, david072
+ *
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+//! The tutorial, which replaces the input with a worksheet and guides the user through it step
+//! by step. Every step is checked by calculating what the user should have written.
+
+use funcially_core::Calculator;
+
+use crate::Line;
+
+const INTRODUCTION: &str = "# Welcome to funcially! Follow the steps in the tutorial window.\n\
+    # Write each calculation in the line below its step. Results appear on the right.";
+
+pub struct Step {
+    pub instruction: &'static str,
+    /// The comment added to the worksheet when the step starts
+    pub todo: &'static str,
+    /// Calculated in the user's environment to get the result the step expects
+    expected: &'static str,
+    /// What the line with the expected result has to contain, so that the result isn't just typed
+    /// in
+    required: &'static str,
+}
+
+pub const STEPS: [Step; 6] = [
+    Step {
+        instruction: "Calculations are written one per line. Calculate 3 + 4 * 2.",
+        todo: "# Step 1: Calculate 3 + 4 * 2",
+        expected: "3 + 4 * 2",
+        required: "+",
+    },
+    Step {
+        instruction: "Numbers can have units, which are converted using \"in\". Convert 5 km to miles.",
+        todo: "# Step 2: Convert 5 km to miles (5km in mi)",
+        expected: "5km in mi",
+        required: " in ",
+    },
+    Step {
+        instruction: "Variables store values for later calculations. Define a variable a with the value 20 \
+            and calculate a * 3.",
+        todo: "# Step 3: Define a := 20, then calculate a * 3",
+        expected: "a * 3",
+        required: "a",
+    },
+    Step {
+        instruction: "Functions are defined like variables, but take arguments. Define f(x) := x^2 + 1 and \
+            calculate f(3).",
+        todo: "# Step 4: Define f(x) := x^2 + 1, then calculate f(3)",
+        expected: "f(3)",
+        required: "f(",
+    },
+    Step {
+        instruction: "Equations are solved by writing a question mark instead of the unknown value. \
+            Solve 2 * ? + 4 = 10.",
+        todo: "# Step 5: Solve 2 * ? + 4 = 10",
+        expected: "2 * ? + 4 = 10",
+        required: "?",
+    },
+    Step {
+        instruction: "Percentages can be taken of values using \"of\". Calculate 20% of 150.",
+        todo: "# Step 6: Calculate 20% of 150",
+        expected: "20% of 150",
+        required: "of",
+    },
+];
+
+impl Step {
+    /// Whether a line of `source` (whose calculated lines are `lines`) contains what's required
+    /// and results in what the step expects in the environment of `calculator`
+    pub fn is_solved(&self, source: &str, lines: &[Line], calculator: &Calculator, use_thousands_separator: bool) -> bool {
+        let mut expected_calculator = calculator.with_empty_environment();
+        expected_calculator.context.borrow_mut().env = calculator.clone_env();
+        let Some(result) = expected_calculator.calculate(self.expected).pop() else { return false; };
+
+        let settings = calculator.context.borrow().settings;
+        let Line::Line { output_text: expected, is_error: false, .. } =
+            Line::new_line(result.data.map(|(data, _)| data), vec![], &settings, use_thousands_separator)
+            else { return false; };
+
+        // Every source line has exactly one entry that isn't a `Line::WrappedLine`
+        source.split('\n')
+            .zip(lines.iter().filter(|line| !matches!(line, Line::WrappedLine)))
+            .any(|(input, line)| {
+                let input = input.trim();
+                !input.starts_with('#') && input.contains(self.required) && matches!(
+                    line,
+                    Line::Line { output_text, is_error: false, .. } if output_text.trim() == expected.trim()
+                )
+            })
+    }
+}
+
+/// The progress in the tutorial, which is saved with the input
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct Tutorial {
+    /// The index of the current step, which is [STEPS]`.len()` when all steps are done
+    pub step: usize,
+    /// The input from before the tutorial, which is restored when it's exited
+    pub previous_source: String,
+}
+
+impl Tutorial {
+    pub fn new(previous_source: String) -> Self {
+        Self { step: 0, previous_source }
+    }
+
+    pub fn current_step(&self) -> Option<&'static Step> {
+        STEPS.get(self.step)
+    }
+}
+
+/// The input the tutorial starts with
+pub fn worksheet() -> String {
+    format!("{INTRODUCTION}\n\n{}\n", STEPS[0].todo)
+}
@@ -0,0 +1,134 @@
+/*
+ * Copyright (c) 2023, david072
+ *
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+use eframe::egui::*;
+use eframe::epaint::CubicBezierShape;
+use funcially_core::{Dependency, DependencyGraph, DependencyKind};
+
+/// How many characters of a line are shown next to its number
+const MAX_LINE_TEXT_LENGTH: usize = 32;
+/// How far an arc goes to the right for every row between its ends
+const ARC_WIDTH_PER_ROW: f32 = 12.0;
+
+/// Shows the dependencies between the lines of `source` as an arc diagram. Every line that uses or
+/// is used by another line is listed from top to bottom, and every dependency is an arc from the
+/// used line to the line using it, which ends in an arrow. Hovering a line highlights its arcs.
+pub fn show_dependency_graph(ui: &mut Ui, font_id: &FontId, source: &str) {
+    let graph = DependencyGraph::new(source);
+    if graph.dependencies.is_empty() {
+        ui.label("No line uses another line");
+        return;
+    }
+
+    let mut nodes = graph.dependencies.iter()
+        .flat_map(|dependency| [dependency.line, dependency.dependent])
+        .collect::<Vec<_>>();
+    nodes.sort_unstable();
+    nodes.dedup();
+    ui.label(format!("{} dependencies between {} lines", graph.dependencies.len(), nodes.len()));
+    ui.separator();
+
+    let source_lines = source.split('\n').collect::<Vec<_>>();
+    let labels = nodes.iter()
+        .map(|line| format!("{:>3}  {}", line + 1, truncate(source_lines.get(*line).copied().unwrap_or_default().trim())))
+        .collect::<Vec<_>>();
+    let label_width = labels.iter()
+        .map(|label| ui.fonts(|fonts| fonts.layout_no_wrap(label.clone(), font_id.clone(), Color32::WHITE).size().x))
+        .fold(0.0, f32::max);
+
+    let row_height = ui.fonts(|fonts| fonts.row_height(font_id)) + 6.0;
+    let max_span = graph.dependencies.iter()
+        .map(|dependency| row(&nodes, dependency.dependent) - row(&nodes, dependency.line))
+        .max()
+        .unwrap_or_default();
+    let size = vec2(
+        label_width + 16.0 + max_span as f32 * ARC_WIDTH_PER_ROW + 8.0,
+        nodes.len() as f32 * row_height,
+    );
+    let (rect, response) = ui.allocate_exact_size(size, Sense::hover());
+    let hovered = response.hover_pos()
+        .map(|pos| ((pos.y - rect.top()) / row_height) as usize)
+        .and_then(|row| nodes.get(row).copied());
+
+    let y = |line: usize| rect.top() + (row(&nodes, line) as f32 + 0.5) * row_height;
+    let arc_x = rect.left() + label_width + 16.0;
+    let painter = ui.painter_at(rect);
+    let visuals = ui.visuals();
+
+    for (line, label) in nodes.iter().zip(&labels) {
+        let pos = pos2(rect.left(), y(*line));
+        if hovered == Some(*line) {
+            let row_rect = Rect::from_min_max(pos2(rect.left(), pos.y - row_height / 2.0), pos2(rect.right(), pos.y + row_height / 2.0));
+            painter.rect_filled(row_rect, 2.0, visuals.faint_bg_color);
+        }
+        painter.text(pos, Align2::LEFT_CENTER, label, font_id.clone(), visuals.text_color());
+    }
+
+    for dependency in &graph.dependencies {
+        let is_highlighted = hovered.map_or(false, |line| line == dependency.line || line == dependency.dependent);
+        let color = if is_highlighted {
+            visuals.selection.bg_fill
+        } else if hovered.is_some() {
+            visuals.weak_text_color().gamma_multiply(0.4)
+        } else {
+            visuals.weak_text_color()
+        };
+        let stroke = Stroke::new(if is_highlighted { 2.0 } else { 1.0 }, color);
+
+        let (start, end) = (pos2(arc_x, y(dependency.line)), pos2(arc_x, y(dependency.dependent)));
+        let width = (row(&nodes, dependency.dependent) - row(&nodes, dependency.line)) as f32 * ARC_WIDTH_PER_ROW;
+        painter.add(CubicBezierShape::from_points_stroke(
+            [start, start + vec2(width, 0.0), end + vec2(width, 0.0), end],
+            false,
+            Color32::TRANSPARENT,
+            stroke,
+        ));
+        painter.add(Shape::convex_polygon(
+            vec![end, end + vec2(6.0, -3.5), end + vec2(6.0, 3.5)],
+            color,
+            Stroke::NONE,
+        ));
+    }
+
+    if let Some(line) = hovered {
+        response.on_hover_ui_at_pointer(|ui| {
+            ui.strong(format!("Line {}", line + 1));
+            let uses = describe(graph.dependencies_of(line), |dependency| dependency.line);
+            if !uses.is_empty() { ui.label(format!("Uses {uses}")); }
+            let used_by = describe(graph.dependents_of(line), |dependency| dependency.dependent);
+            if !used_by.is_empty() { ui.label(format!("Used by {used_by}")); }
+            let affected = graph.affected_lines(line).len();
+            if affected > 0 { ui.label(format!("Changing it affects {affected} lines")); }
+        });
+    }
+}
+
+/// The row of the (zero-based) line `line` in the diagram
+fn row(nodes: &[usize], line: usize) -> usize {
+    nodes.binary_search(&line).unwrap_or_default()
+}
+
+fn truncate(text: &str) -> String {
+    if text.chars().count() <= MAX_LINE_TEXT_LENGTH { return text.to_string(); }
+    text.chars().take(MAX_LINE_TEXT_LENGTH - 1).chain(std::iter::once('…')).collect()
+}
+
+/// Lists the dependencies as e.g. `line 1 (a), line 3 (ans)`, using `other` to get the line
+/// that isn't the one being described
+fn describe<'a>(dependencies: impl Iterator<Item=&'a Dependency>, other: fn(&Dependency) -> usize) -> String {
+    dependencies
+        .map(|dependency| {
+            let reason = match &dependency.kind {
+                DependencyKind::Identifier(name) => name.as_str(),
+                DependencyKind::LineReference => "line reference",
+                DependencyKind::Ans => "ans",
+                DependencyKind::Last => "last",
+            };
+            format!("line {} ({reason})", other(dependency) + 1)
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
@@ -0,0 +1,223 @@
+/*
+ * Copyright (c) 2023, david072
+ *
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+use eframe::egui::*;
+use funcially_core::{Result as CalcResult, ResultData, Settings, Value};
+
+const REMOVED_COLOR: Color32 = Color32::from_rgb(0xE0, 0x6C, 0x75);
+const ADDED_COLOR: Color32 = Color32::from_rgb(0x98, 0xC3, 0x79);
+const CHANGED_INPUT_COLOR: Color32 = Color32::from_rgb(0xE5, 0xC0, 0x7B);
+const CHANGED_RESULT_COLOR: Color32 = Color32::from_rgb(0x61, 0xAF, 0xEF);
+
+/// A line of a document, together with its result
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct DiffLine {
+    pub input: String,
+    pub result: DiffResult,
+}
+
+/// The result of a line. Values are only formatted when the diff is created, so that they follow
+/// the current settings.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum DiffResult {
+    Nothing,
+    Value(Value),
+    Boolean(bool),
+    Error(String),
+}
+
+impl DiffResult {
+    pub fn new(result: &CalcResult<ResultData>) -> Self {
+        match result {
+            Ok(ResultData::Value(value) | ResultData::Slider { value, .. }) => Self::Value(value.clone()),
+            Ok(ResultData::Boolean { value, .. }) => Self::Boolean(*value),
+            Ok(_) => Self::Nothing,
+            Err(e) => Self::Error(e.error.to_string()),
+        }
+    }
+
+    pub fn format(&self, settings: &Settings, use_thousands_separator: bool) -> String {
+        match self {
+            Self::Nothing => String::new(),
+            Self::Value(value) => value.format(settings, use_thousands_separator),
+            Self::Boolean(value) => (if *value { "True" } else { "False" }).to_string(),
+            Self::Error(message) => message.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DiffEntry {
+    /// The input didn't change, but the result might have
+    Unchanged { old: usize, new: usize },
+    /// The input of the line was edited
+    Changed { old: usize, new: usize },
+    Removed(usize),
+    Added(usize),
+}
+
+/// A line of a [Diff] with its formatted result
+#[derive(Debug, Clone, Default)]
+pub struct FormattedLine {
+    pub input: String,
+    pub result: String,
+}
+
+/// The differences between two revisions of a document
+#[derive(Debug, Clone, Default)]
+pub struct Diff {
+    pub old: Vec<FormattedLine>,
+    pub new: Vec<FormattedLine>,
+    pub entries: Vec<DiffEntry>,
+}
+
+impl Diff {
+    pub fn new(old: &[DiffLine], new: &[DiffLine], settings: &Settings, use_thousands_separator: bool) -> Self {
+        fn inputs(lines: &[DiffLine]) -> Vec<&str> {
+            lines.iter().map(|line| line.input.as_str()).collect()
+        }
+        let entries = align_lines(&inputs(old), &inputs(new));
+        let format = |lines: &[DiffLine]| lines.iter()
+            .map(|line| FormattedLine {
+                input: line.input.clone(),
+                result: line.result.format(settings, use_thousands_separator),
+            })
+            .collect();
+        Self { old: format(old), new: format(new), entries }
+    }
+
+    pub fn has_changes(&self) -> bool {
+        self.entries.iter().any(|entry| match entry {
+            DiffEntry::Unchanged { old, new } => self.old[*old].result != self.new[*new].result,
+            _ => true,
+        })
+    }
+
+    pub fn show(&self, ui: &mut Ui, font_id: &FontId, only_changes: bool) {
+        let text = |text: &str, color: Color32| RichText::new(text).font(font_id.clone()).color(color);
+
+        Grid::new("diff_view_grid")
+            .striped(true)
+            .num_columns(4)
+            .show(ui, |ui| {
+                ui.label(RichText::new("Old").strong());
+                ui.label(RichText::new("New").strong());
+                ui.label(RichText::new("Input").strong());
+                ui.label(RichText::new("Result").strong());
+                ui.end_row();
+
+                for entry in &self.entries {
+                    let (old, new) = match entry {
+                        DiffEntry::Unchanged { old, new } | DiffEntry::Changed { old, new } => (Some(*old), Some(*new)),
+                        DiffEntry::Removed(old) => (Some(*old), None),
+                        DiffEntry::Added(new) => (None, Some(*new)),
+                    };
+                    let old_line = old.map(|i| &self.old[i]);
+                    let new_line = new.map(|i| &self.new[i]);
+                    let result_changed = old_line.map(|l| &l.result) != new_line.map(|l| &l.result);
+
+                    if only_changes && matches!(entry, DiffEntry::Unchanged { .. }) && !result_changed {
+                        continue;
+                    }
+
+                    ui.label(text(&old.map(|i| (i + 1).to_string()).unwrap_or_default(), Color32::GRAY));
+                    ui.label(text(&new.map(|i| (i + 1).to_string()).unwrap_or_default(), Color32::GRAY));
+
+                    match (old_line, new_line) {
+                        (Some(old), Some(new)) if old.input != new.input => {
+                            ui.label(text(&format!("{} → {}", old.input, new.input), CHANGED_INPUT_COLOR));
+                        }
+                        (Some(line), None) => { ui.label(text(&line.input, REMOVED_COLOR).strikethrough()); }
+                        (None, Some(line)) => { ui.label(text(&line.input, ADDED_COLOR)); }
+                        (Some(line), Some(_)) => { ui.label(text(&line.input, Color32::GRAY)); }
+                        (None, None) => unreachable!(),
+                    }
+
+                    match (old_line, new_line) {
+                        (Some(old), Some(new)) if result_changed => {
+                            ui.label(text(&result_change(&old.result, &new.result), CHANGED_RESULT_COLOR));
+                        }
+                        (Some(line), None) => { ui.label(text(&line.result, REMOVED_COLOR)); }
+                        (None, Some(line)) => { ui.label(text(&line.result, ADDED_COLOR)); }
+                        (Some(line), Some(_)) => { ui.label(text(&line.result, Color32::GRAY)); }
+                        (None, None) => unreachable!(),
+                    }
+                    ui.end_row();
+                }
+            });
+    }
+}
+
+/// Describes how a result changed, e.g. "was 42.1, now 43.5"
+fn result_change(old: &str, new: &str) -> String {
+    match (old.is_empty(), new.is_empty()) {
+        (true, _) => format!("now {new}"),
+        (_, true) => format!("was {old}, now nothing"),
+        _ => format!("was {old}, now {new}"),
+    }
+}
+
+/// Aligns `old` and `new` using their longest common subsequence. Removed lines that are directly
+/// followed by added lines are paired up into changed lines.
+pub fn align_lines<T: PartialEq>(old: &[T], new: &[T]) -> Vec<DiffEntry> {
+    // Most edits are local, so the common start and end don't need to go through the (quadratic)
+    // LCS computation
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..].iter().rev().zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let (old_middle, new_middle) = (&old[prefix..old.len() - suffix], &new[prefix..new.len() - suffix]);
+
+    // lcs[i][j] is the length of the longest common subsequence of old_middle[i..] and new_middle[j..]
+    let mut lcs = vec![vec![0usize; new_middle.len() + 1]; old_middle.len() + 1];
+    for i in (0..old_middle.len()).rev() {
+        for j in (0..new_middle.len()).rev() {
+            lcs[i][j] = if old_middle[i] == new_middle[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut entries = (0..prefix)
+        .map(|i| DiffEntry::Unchanged { old: i, new: i })
+        .collect::<Vec<_>>();
+    let mut removed = vec![];
+    let mut added = vec![];
+
+    let flush = |entries: &mut Vec<DiffEntry>, removed: &mut Vec<usize>, added: &mut Vec<usize>| {
+        let paired = removed.len().min(added.len());
+        for (old, new) in removed.iter().zip(added.iter()) {
+            entries.push(DiffEntry::Changed { old: *old, new: *new });
+        }
+        entries.extend(removed.drain(..).skip(paired).map(DiffEntry::Removed));
+        entries.extend(added.drain(..).skip(paired).map(DiffEntry::Added));
+    };
+
+    let (mut i, mut j) = (0usize, 0usize);
+    while i < old_middle.len() || j < new_middle.len() {
+        if i < old_middle.len() && j < new_middle.len() && old_middle[i] == new_middle[j] {
+            flush(&mut entries, &mut removed, &mut added);
+            entries.push(DiffEntry::Unchanged { old: prefix + i, new: prefix + j });
+            i += 1;
+            j += 1;
+        } else if j < new_middle.len() && (i == old_middle.len() || lcs[i][j + 1] >= lcs[i + 1][j]) {
+            added.push(prefix + j);
+            j += 1;
+        } else {
+            removed.push(prefix + i);
+            i += 1;
+        }
+    }
+    flush(&mut entries, &mut removed, &mut added);
+
+    entries.extend((0..suffix).map(|k| DiffEntry::Unchanged {
+        old: old.len() - suffix + k,
+        new: new.len() - suffix + k,
+    }));
+    entries
+}
@@ -0,0 +1,153 @@
+/*
+ * Copyright (c) 2023, david072
+ *
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+//! Folding of sections. A comment starting with `##` (e.g. `## Budget`) is the header of a section,
+//! which reaches until the next header. The lines of folded sections are hidden in the input, but
+//! are still calculated. To keep everything working on the text that is shown, the hidden lines are
+//! removed from the source at the start of every frame and added back at its end.
+
+use std::collections::HashSet;
+
+/// Returns the title of the section whose header is `line`, if it is one
+pub fn section_title(line: &str) -> Option<&str> {
+    let title = line.trim_start().strip_prefix("##")?;
+    Some(title.trim_start_matches('#').trim())
+}
+
+/// The text shown in the input for a source with folded sections
+#[derive(Debug, Default)]
+pub struct FoldedView {
+    /// The titles of the folded sections
+    folded: HashSet<String>,
+    /// The (zero-based) source line of every line of the folded text
+    lines: Vec<usize>,
+    hidden: Vec<HiddenSection>,
+}
+
+#[derive(Debug)]
+struct HiddenSection {
+    /// The header line of the section
+    header: String,
+    /// The index of the header in the folded text
+    line: usize,
+    lines: Vec<String>,
+}
+
+impl FoldedView {
+    /// Removes the lines of the sections in `source` whose titles are in `folded`
+    ///
+    /// **Returns:** The folded text and the view to unfold it again
+    pub fn new(source: &str, folded: HashSet<String>) -> (String, Self) {
+        let mut text_lines = vec![];
+        let mut lines = vec![];
+        let mut hidden: Vec<HiddenSection> = vec![];
+        let mut is_folded = false;
+
+        for (i, line) in source.split('\n').enumerate() {
+            if let Some(title) = section_title(line) {
+                is_folded = folded.contains(title);
+                if is_folded {
+                    hidden.push(HiddenSection { header: line.to_owned(), line: text_lines.len(), lines: vec![] });
+                }
+            } else if is_folded {
+                hidden.last_mut().unwrap().lines.push(line.to_owned());
+                continue;
+            }
+            text_lines.push(line);
+            lines.push(i);
+        }
+
+        (text_lines.join("\n"), Self { folded, lines, hidden })
+    }
+
+    pub fn folded(&self) -> &HashSet<String> { &self.folded }
+
+    /// Adds the hidden lines back to `text`, which is the folded text after it was edited. The
+    /// lines of a section are added after its header or in its place, if the header was removed.
+    pub fn unfold(&self, text: &str) -> String {
+        if self.hidden.is_empty() { return text.to_owned(); }
+
+        let text_lines = text.split('\n').collect::<Vec<_>>();
+        let mut lines = vec![];
+        // The index of the first line of `text_lines` that wasn't added yet
+        let mut next = 0usize;
+        for section in &self.hidden {
+            // Search after the previous header, so that sections with the same title keep their order
+            let end = match text_lines[next..].iter().position(|line| *line == section.header) {
+                Some(i) => next + i + 1,
+                None => section.line.clamp(next, text_lines.len()),
+            };
+            lines.extend_from_slice(&text_lines[next..end]);
+            lines.extend(section.lines.iter().map(String::as_str));
+            next = end;
+        }
+        lines.extend_from_slice(&text_lines[next..]);
+        lines.join("\n")
+    }
+
+    /// Returns the source line of the (zero-based) line `line` of the folded text
+    pub fn source_line(&self, line: usize) -> usize {
+        match self.lines.get(line) {
+            Some(source_line) => *source_line,
+            None => self.lines.last().map_or(0, |last| last + 1) + line - self.lines.len(),
+        }
+    }
+
+    /// Returns how many lines are hidden after the (zero-based) line `line` of the folded text, if
+    /// it's the header of a folded section
+    pub fn hidden_line_count(&self, line: usize) -> Option<usize> {
+        self.hidden.iter()
+            .find(|section| section.line == line)
+            .map(|section| section.lines.len())
+    }
+
+    /// Converts the character index `index` in the folded text of `source` to one in `source`
+    pub fn source_index(&self, source: &str, index: usize) -> usize {
+        if self.hidden.is_empty() { return index; }
+
+        let line_lengths = line_lengths(source);
+        let mut remaining = index;
+        for line in &self.lines {
+            let Some(length) = line_lengths.get(*line) else { break; };
+            if remaining <= *length { return line_start(&line_lengths, *line) + remaining; }
+            remaining -= length + 1;
+        }
+        source.chars().count()
+    }
+
+    /// Converts the character index `index` in `source` to one in its folded text. Indices in
+    /// hidden lines are moved to the end of their section's header.
+    pub fn view_index(&self, source: &str, index: usize) -> usize {
+        if self.hidden.is_empty() { return index; }
+
+        let line_lengths = line_lengths(source);
+        let mut line = 0usize;
+        let mut column = index;
+        while line + 1 < line_lengths.len() && column > line_lengths[line] {
+            column -= line_lengths[line] + 1;
+            line += 1;
+        }
+
+        let shown = self.lines.partition_point(|l| *l <= line).saturating_sub(1);
+        let start = self.lines[..shown].iter()
+            .map(|l| line_lengths.get(*l).map_or(0, |length| length + 1))
+            .sum::<usize>();
+        match self.lines.get(shown) {
+            Some(l) if *l == line => start + column,
+            Some(l) => start + line_lengths.get(*l).copied().unwrap_or_default(),
+            None => start,
+        }
+    }
+}
+
+/// The number of characters of every line in `source`
+fn line_lengths(source: &str) -> Vec<usize> {
+    source.split('\n').map(|line| line.chars().count()).collect()
+}
+
+fn line_start(line_lengths: &[usize], line: usize) -> usize {
+    line_lengths[..line].iter().map(|length| length + 1).sum()
+}
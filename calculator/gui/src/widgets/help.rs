@@ -0,0 +1,908 @@
+/*
+ * Copyright (c) 2023, david072
+ *
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+//! The content of the help window, which is split into topics (one for every heading) that can be
+//! searched. Examples can be clicked to insert them into the input.
+
+use eframe::egui::*;
+use egui_commonmark::{CommonMarkCache, CommonMarkViewer};
+
+use funcially_core::{ConstantCategory, CONSTANTS, Environment, FunctionHelp};
+
+/// Replaced by the table of the standard functions, which is built from their [FunctionHelp]
+const STANDARD_FUNCTIONS_PLACEHOLDER: &str = "<standard-functions>";
+/// Replaced by the tables of the scientific constants
+const SCIENTIFIC_CONSTANTS_PLACEHOLDER: &str = "<scientific-constants>";
+
+enum HelpPart {
+    Markdown(String),
+    /// The lines of a code block, each of which is an example
+    Examples(Vec<String>),
+    StandardFunctions,
+}
+
+#[derive(Default)]
+struct HelpTopic {
+    parts: Vec<HelpPart>,
+    /// The lowercase markdown of the topic, which is searched
+    text: String,
+}
+
+impl HelpTopic {
+    fn matches(&self, query: &str) -> bool {
+        self.text.contains(query)
+    }
+
+    fn has_standard_functions(&self) -> bool {
+        self.parts.iter().any(|part| matches!(part, HelpPart::StandardFunctions))
+    }
+}
+
+/// Shows the help, filtered by the search `query`. Returns the example the user clicked on.
+pub fn build_help(ui: &mut Ui, query: &mut String) -> Option<String> {
+    ui.horizontal(|ui| {
+        ui.spacing_mut().item_spacing.x = 0.0;
+        ui.label("This is a brief overview of the features. A more detailed documentation can be found ");
+        ui.hyperlink_to("here", "https://github.com/david072/funcially/wiki");
+        ui.label(".");
+    });
+    ui.horizontal(|ui| {
+        ui.label("Search:");
+        ui.text_edit_singleline(query);
+        if !query.is_empty() && ui.small_button("✖").clicked() { query.clear(); }
+    });
+    ui.label(RichText::new("Click on an example to insert it into the input").small());
+    ui.separator();
+
+    let query = query.trim().to_lowercase();
+    let functions = Environment::new().function_signatures().into_iter()
+        .filter_map(|signature| signature.help)
+        .collect::<Vec<_>>();
+    let function_matches = |help: &FunctionHelp| help.usage.to_lowercase().contains(&query)
+        || help.description.to_lowercase().contains(&query);
+
+    let mut inserted = None;
+    let mut cache = CommonMarkCache::default();
+    let mut is_empty = true;
+    for (i, topic) in topics().iter().enumerate() {
+        // If only some of the standard functions match, only they are shown
+        let topic_matches = topic.matches(&query);
+        let functions = functions.iter()
+            .filter(|help| topic_matches || function_matches(help))
+            .collect::<Vec<_>>();
+        if !topic_matches && (!topic.has_standard_functions() || functions.is_empty()) { continue; }
+        is_empty = false;
+
+        for (j, part) in topic.parts.iter().enumerate() {
+            match part {
+                HelpPart::Markdown(markdown) => CommonMarkViewer::new(("help", i, j)).show(ui, &mut cache, markdown),
+                HelpPart::Examples(lines) => {
+                    Frame::group(ui.style()).show(ui, |ui| {
+                        ui.set_width(ui.available_width());
+                        for line in lines {
+                            example(ui, line, &mut inserted);
+                        }
+                    });
+                }
+                HelpPart::StandardFunctions => standard_functions(ui, &functions, &mut inserted),
+            }
+        }
+    }
+
+    if is_empty {
+        ui.label("Nothing found");
+    }
+    inserted
+}
+
+/// Splits the help into topics, each starting at a heading
+fn topics() -> Vec<HelpTopic> {
+    let markdown = HELP.replace(SCIENTIFIC_CONSTANTS_PLACEHOLDER, &scientific_constants_table());
+
+    let mut topics: Vec<HelpTopic> = vec![];
+    let mut is_in_code_block = false;
+    for line in markdown.lines() {
+        if (line.starts_with('#') && !is_in_code_block) || topics.is_empty() {
+            topics.push(HelpTopic::default());
+        }
+        let topic = topics.last_mut().unwrap();
+        topic.text += &line.to_lowercase();
+        topic.text.push('\n');
+
+        if line.starts_with("```") {
+            is_in_code_block = !is_in_code_block;
+            if is_in_code_block { topic.parts.push(HelpPart::Examples(vec![])); }
+            continue;
+        }
+
+        match topic.parts.last_mut() {
+            Some(HelpPart::Examples(lines)) if is_in_code_block => lines.push(line.to_string()),
+            _ if line.trim() == STANDARD_FUNCTIONS_PLACEHOLDER => topic.parts.push(HelpPart::StandardFunctions),
+            Some(HelpPart::Markdown(text)) => {
+                text.push_str(line);
+                text.push('\n');
+            }
+            _ => topic.parts.push(HelpPart::Markdown(format!("{line}\n"))),
+        }
+    }
+    topics
+}
+
+/// Shows an example that is inserted into the input when clicked. Explanations of the result
+/// following `=>` are not inserted.
+fn example(ui: &mut Ui, example: &str, inserted: &mut Option<String>) {
+    let input = example.split("=>").next().unwrap_or_default().trim();
+    let button = Button::new(RichText::new(example).monospace()).frame(false);
+    if ui.add(button).on_hover_text("Insert into the input").clicked() {
+        *inserted = Some(input.to_string());
+    }
+}
+
+fn standard_functions(ui: &mut Ui, functions: &[&FunctionHelp], inserted: &mut Option<String>) {
+    Grid::new("help_standard_functions").striped(true).num_columns(3).show(ui, |ui| {
+        ui.strong("Description");
+        ui.strong("Function");
+        ui.strong("Example");
+        ui.end_row();
+
+        for help in functions {
+            ui.label(help.description);
+            ui.monospace(help.usage);
+            example(ui, help.example, inserted);
+            ui.end_row();
+        }
+    });
+}
+
+/// Builds the markdown tables of the scientific constants, grouped by their category
+fn scientific_constants_table() -> String {
+    let mut result = String::new();
+    for category in ConstantCategory::ALL {
+        result += &format!("### {category}\n\n| Constant | Description | Value |\n|----------|-------------|-------|\n");
+        for constant in CONSTANTS.iter().filter(|constant| constant.category == category) {
+            let unit = constant.unit().map(|unit| format!(" {unit}")).unwrap_or_default();
+            result += &format!("| `{}` | {} | {:e}{unit} |\n", constant.name, constant.description, constant.value);
+        }
+        result.push('\n');
+    }
+    result
+}
+
+const HELP: &str = r#"# Literals
+
+Literals can be specified using different representations.
+
+```
+3
+0xFF
+0b110
+3.123
+.123
+```
+
+## Scientific notation
+
+Scientific notation can be used to input big numbers more easily.
+
+```
+1e2
+3e5
+```
+
+# Objects
+
+funcially supports using objects which encapsulate more complex data structures. Objects always follow the syntax:
+`{<name> <args>}`.
+
+The following objects can be used:
+
+| Name | Syntax                                           |
+|------|--------------------------------------------------|
+| date | `{date now}` / `{date day.month.year}`<sup>[1](#date-footnote)</sup> |
+
+---
+
+<sup><a name="date-footnote">1</a></sup>: This format can be changed in the [settings](#Settings).
+
+# Operators
+
+## Basic
+
+| Name           | Operator     |
+|----------------|--------------|
+| Addition       | `+`          |
+| Subtraction    | `-`          |
+| Multiplication | `*`          |
+| Division       | `/`          |
+
+## Extended
+
+| Name                            | Operator     |
+|---------------------------------|--------------|
+| Exponentiation                  | `^` or `**`  |
+| Bitwise AND                     | `&`          |
+| Bitwise OR                      | `\|`          |
+| Left Shift                      | `<<`         |
+| Right Shift                     | `>>`         |
+| Modulo                          | `mod`        |
+| Remainder                       | `rem`        |
+| Binomial coefficient            | `choose`     |
+| Taking a percentage of a number | `of`         |
+| Unit conversion                 | `in`         |
+
+The operands of the bitwise operators and of `mod` with integers have to be integers that can be represented exactly, i.e. between -2^53 and 2^53. With the "Word size" setting, they also have to fit into an integer of that many bits, either signed or unsigned (e.g. -128 to 255 for 8 bits). Otherwise, an error shows the supported range.
+
+The sign of `mod` with negative operands depends on the "Modulo" setting: "Truncated" gives the result the sign of the dividend (`-7 mod 3` = -1), "Floored" the sign of the divisor (`7 mod -3` = -2) and "Euclidean" never gives a negative result. `rem` is always truncated.
+
+## Modifiers
+
+Modifiers are similar to operators, except that they only have one operand.
+
+They change the value of what they were pre- or appended to.
+
+| Name        | Modifier        | Long form               |
+|-------------|-----------------|-------------------------|
+| Percent     | `%`             | `n / 100`               |
+| Per mille   | `‰`             | `n / 1000`              |
+| Bitwise NOT | `!` (prepended) | -                       |
+| Factorial   | `!` (appended)  | `n * (n - 1) * ... * 1` |
+
+```
+20%
+!0b101
+5!
+(50 / 2)%
+```
+
+## Taxes, tips and discounts
+
+A percentage that is added to or subtracted from a value can be followed by `tax`, `tip` or `discount`. The percentage is then taken of the value, so `120 + 19% tax` is `120 * 1.19`. The functions `tax`, `net`, `tip` and `discount` do the same. If no rate is given, `tax` and `net` use the VAT rate from the "Currencies" section of the settings. The steps of the calculation are shown in the debug information.
+
+```
+120 + 19% tax       => 142.8
+80 - 25% discount   => 60
+40 + 15% tip        => 46
+tax(100)            => 119
+net(119)            => 100
+discount(80, 25%)   => 60
+```
+
+## Uncertainties
+
+A value can be given an uncertainty with `±` (or `+-`), which is carried through the calculation. The uncertainties
+are propagated linearly, i.e. they are added up in the worst case, and are also propagated through functions. Integer
+operations (e.g. `&` or `!`) can't be applied to values with uncertainties.
+
+```
+(5.0 ± 0.1) * (2.0 ± 0.05)  => 10 ± 0.45
+(2 +- 0.1) + 3              => 5 ± 0.1
+5m ± 1cm in cm              => (500 ± 1)cm
+sqrt(4 ± 0.41)              => 2 ± 0.1052704679
+```
+
+## Intervals
+
+An interval (e.g. `[1, 2]`) stands for all values between its bounds. The basic operators and the functions `sin`,
+`cos`, `tan`, `cot`, `asin`, `acos`, `atan`, `ln`, `sqrt`, `cbrt`, `abs`, `floor`, `ceil` and `round` calculate the
+range of all possible results. Comparisons of intervals are only true if they are true for all of their values.
+
+```
+[1, 2] + [3, 4]     => [4, 6]
+[-1, 2] * [3, 4]    => [-4, 8]
+[-2, 3]^2           => [0, 9]
+sin([0, 3])         => [0, 1]
+[1, 2] < 3          => True
+[1, 3] < [2, 4]     => False
+```
+
+## Operator order
+
+1. Functions and variables
+2. Groups ("(...)")
+3. Uncertainties (`±`)
+4. Extended operators
+5. Multiplication and division
+6. Addition and subtraction
+7. `of` and `in`
+
+## Inferred multiplication
+
+The multiplication sign can be left out in the following scenarios:
+
+- Before variables
+- Before functions
+- Before groups
+
+```
+2pi
+2sin(30)
+2(3 + 4)
+(3 + 4)(1 + 2)
+```
+
+# Functions
+
+When a function, variable or unit is unknown, the error suggests similarly named ones, e.g. `sqr(4)` results in `Unknown function "sqr", did you mean "sqrt"?`.
+
+## Standard Functions
+
+Trigonometric functions use parameters in radians and their inverse functions also return values in radians. Angles in other units (e.g. `sin(90°)`) are converted to radians first. `atan2(y, x)` is the angle of the point (x, y) between -π and π, and `hypot(a, b)` is `sqrt(a^2 + b^2)` without overflowing for large numbers. `ln1p(x)` and `expm1(x)` are `ln(1 + x)` and `e^x - 1`, which stay precise for `x` close to 0.
+
+To use degrees, simply convert the units:
+```
+sin(30°)
+asin(.5) in °
+```
+
+<standard-functions>
+
+`float_bits` and `from_bits` take the width of the float as an optional second argument, which is either `32` (the default) or `64`. The bits are shown in hexadecimal. Precision might be lost with a width of 64, since the bits of a 64-bit float can have more significant digits than a number can hold.
+
+```
+float_bits(3.14)        => 0x4048F5C3
+from_bits(0x4048f5c3)   => 3.1400001049
+ulp(1)                  => 2.2204460493e-16
+```
+
+The number theory functions take integers up to 2^53, which are the integers that can be represented exactly. `isprime` returns 1 for primes and 0 otherwise, and `factorize` returns the prime factors of a positive integer as a vector.
+
+```
+isprime(97)                 => 1
+lcm(4, 6)                   => 12
+factorize(600851475143)     => [71; 839; 1471; 6857]
+```
+
+`ncr(n, k)` (or `n choose k`) is the number of ways to choose `k` of `n` elements and `npr(n, k)` the number of ways to arrange them. They are calculated without overflowing in between, so they are exact as long as the result can be represented exactly. Both are 0 if `k` is greater than `n`.
+
+The finance functions work like their counterparts in spreadsheets: `rate` is the interest rate per period, `n` the number of periods, `pmt` the payment per period and `pv` the present value. Money that is paid out is negative. `npv` and `irr` take the cash flows at the end of each period as a vector, and `npv` discounts the first cash flow as well.
+
+```
+fv(5%, 10, -100, -1000)                 => 2886.6838803323
+pmt(5% / 12, 360, 200000)               => -1073.6432460243
+npv(10%, [-10000; 3000; 4200; 6800])    => 1188.4434123352
+irr([-100; 60; 60])                     => 0.1306623863
+```
+
+`molarmass` takes a chemical formula as a string and returns its molar mass in g/mol, so that masses and amounts of substance can be converted into each other. Groups can be put in parentheses or square brackets, and the parts of hydrates are separated by `·`, `.` or `*` (e.g. `CuSO4·5H2O`).
+
+```
+molarmass("H2SO4")              => 98.072 g/mol
+9g / molarmass("H2O")           => 0.4995836803mol
+0.5mol * molarmass("NaCl") in kg => 0.02922kg
+```
+
+Functions that aren't called, like the `f` in `map(f, [1; 2])`, are values that can be passed to `map`, `filter`, `reduce` and `apply` or stored in variables. `reduce` calls a function of two arguments with the result so far and the next element, starting with `initial` or the first element.
+
+```
+sq(x) := x^2
+map(sq, [1; 2; 3])          => [1; 4; 9]
+add(a, b) := a + b
+reduce(add, [1; 2; 3], 10)  => 16
+```
+
+## Custom functions
+
+Syntax: `name(arg1, arg2, ...) := expr`
+
+A custom functions is defined with a name, a set of arguments separated by commas and an expression that evaluates to
+the function's value. In the expression, the names given to the arguments before the definition sign (`:=`) can be used.
+As such, this expression cannot be evaluated right away.
+
+If "expr" is empty, the function is removed.
+
+When a function is re-declared (i.e. the function already exists), one of two things happens:
+
+- If the function being declared is a standard function (e.g. `sin`), an error occurs, saying that standard functions
+  cannot be overridden.
+- Otherwise, the function's expression and arguments are updated.
+
+The same applies when removing a function.
+
+```
+f(x) := x * 3
+f(x, y) := x ^ y
+f(x, y) :=
+```
+
+# Variables
+
+## Constants
+
+| Constant | Value        |
+|----------|--------------|
+| pi or π  | 3.1415926536 |
+| e        | 2.7182818285 |
+| tau or τ | 6.2831853072 |
+
+## Scientific constants
+
+Scientific constants carry their units, so they can be converted like any other value (e.g. `c in km/h`).
+Unlike the constants above, they can be overridden by custom variables, which results in a warning.
+After a number, units and unit prefixes take precedence over them (e.g. `2h` is two hours).
+
+<scientific-constants>
+
+## `ans` Variable
+
+The `ans` variable contains the result of the previous calculation. It starts out at 0.
+
+## `last` Variable
+
+The `last` variable contains the result of the closest line above that has one. Unlike `ans`, this includes definitions, so after `x := 10`, `last` is 10. Using `last` without such a line is an error, which is always the case in the CLI, where every input is calculated on its own.
+
+A line starting with an operator applies it to `last`, so `* 1.19` is the same as `last * 1.19`. `+` and `-` only do this if a space follows them, so that `-4` is still a negative number.
+
+```
+100
+* 1.19      => 119
++ 5         => 124
+- 4         => 120
+-4          => -4
+x := 10
+/ 2         => 5
+last * 3    => 15
+```
+
+## Custom variables
+
+Syntax: `name := expr`
+
+A custom variable is defined with a name, and an expression that resolves to its value.
+When the variable is declared, its expression is evaluated and the result (including an optional unit)
+is stored for future use. Variable **do not** store formats.
+
+If "expr" is empty, the variable is removed.
+
+When a variable is re-declared (i.e. the variable already exists), one of two things happens:
+
+- If the variable being declared is a builtin variable (e.g. `pi`), an error occurs, saying that standard
+  variables cannot be overridden.
+- Otherwise, the variable's value is updated.
+
+The same applies when removing a variable.
+
+```
+x := 4 + 3
+pi := 4   => Error: ReservedVariable
+x := 20 * 2
+x :=
+```
+
+A variable with the name of a unit (e.g. `m := 5`) is used instead of the unit afterwards, so `2m` is `2 * m`. Variables like this, as well as variables and functions that no other line uses, get a hint. Unlike warnings, hints only dim the result.
+
+```
+m := 5           => 5 (Hint: "m" shadows the unit with the same name)
+unused := 3      => 3 (Hint: "unused" is never used)
+```
+
+## Updating variables
+
+Syntax: `name += expr` (also `-=`, `*=`, `/=` and `^=`)
+
+An existing variable can be updated using its current value. `x += 5` is the same as `x := x + (5)`, so
+units are converted like in any other calculation. Updating a variable that is not defined yet is an error.
+
+```
+total := 10
+total += 5       => 15
+total *= 1 + 1   => 30
+length := 2 m
+length += 50 cm  => 2.5 m
+count += 1       => Error: UndefinedVariableUpdate
+```
+
+## Hiding results
+
+A line ending with `;` or `@ignore` is calculated as usual, so that its definitions can be used in the following
+lines, but its result isn't shown. Errors are still shown.
+
+```
+width := 3 m;
+height := 2 m @ignore
+width * height   => 6m^2
+```
+
+# Strings
+
+Text in double quotes is a string, which can e.g. be used as a label. Strings are joined with `+`, and `fmt` puts
+values into a string in place of the `{}`s, formatted like results. Right after a value (e.g. `5"`), a double quote is
+still the unit of arc seconds. A string has to be closed on the same line.
+
+```
+"Total"                         => Total
+"speed: " + "fast"              => speed: fast
+fmt("speed: {} km/h", 25 * 2)   => speed: 50 km/h
+```
+
+# Equality checks
+
+An equals sign ("=") marks this line as an equality check. funcially then returns `True` or `False`, depending on whether
+the two expressions evaluate to the same value (including units).
+
+The equals sign **must** be at the top level and there can only be one equals sign in a line.
+
+If the two sides are numbers that aren't equal, the result additionally shows their difference (in the unit of the
+left side) and how large it is relative to the right side (e.g. `False (Δ = 0.02, 0.4%)`).
+
+```
+20 + 30 = 25 * 2
+20 * 5 = 10
+```
+
+## Approximate equality
+
+`≈` (or `~=`) checks whether the two sides are approximately equal, which avoids surprises from rounding errors. By
+default, they may differ by 0.1% of the larger side, which can be changed in the settings (`tolerance.relative`, and
+`tolerance.absolute` for comparisons with zero). `within` sets the largest allowed difference for a single check,
+optionally in a unit. Function conditions can use `≈` as well.
+
+```
+0.3333 ≈ 1/3
+sqrt(2) ≈ 1.41 within 0.01
+1 mi ≈ 1.6 km within 10 m
+```
+
+# Equation solving
+
+funcially can solve **linear** equations if there is a question mark (`?`) in either sides.
+
+- There can only be one question mark in an equation.
+- The question mark can only be a function argument of custom functions that are themselves linear.
+
+If the question mark is preceded by a variable name, its calculated value will be assigned to the given
+variable.
+
+```
+30 + ? = 100
+? * sin(30) = 1 + 1
+20 + x? = 100.5
+```
+
+# Units
+
+Units are defined with a name and an optional *unit prefix*.
+
+Units and unit prefixes are case-sensitive.
+
+| Prefix | Name  | Value |
+|--------|-------|-------|
+| `n`    | Nano  | 1e-9  |
+| `µ`    | Micro | 1e-6  |
+| `m`    | Milli | 1e-3  |
+| `c`    | Centi | 1e-2  |
+| `d`    | Deci  | 1e-1  |
+| `h`    | Hecto | 1e2   |
+| `k`    | Kilo  | 1e3   |
+| `M`    | Mega  | 1e6   |
+| `G`    | Giga  | 1e9   |
+| `T`    | Tera  | 1e12  |
+| `P`    | Peta  | 1e15  |
+| `E`    | Exa   | 1e18  |
+| `Z`    | Zetta | 1e21  |
+| `Y`    | Yotta | 1e24  |
+
+Unit prefixes can be used without a unit and thus act like a modifier (e.g. `3k = 3000`).
+
+Numbers with and without units can be mixed, resulting in the unit being carried to the result.<br>
+If the line consists **only** of a literal with a unit, the unit in the result is printed in it's long form. Otherwise,
+the abbreviated version is used.
+
+Two units can be combined using a `/` in between.
+
+funcially will automatically convert units to perform operations if needed. In that case, the right hand
+side's is converted into the left hand side's unit.
+
+```
+10m
+10km/h
+(10 + 10)km
+20 * 2min
+```
+
+Every unit has a dimension (e.g. length, time or energy). Only values with the same dimension can be added or
+subtracted, so `5m + 3s` results in an error. When multiplying or dividing, units with the same dimension cancel
+each other out:
+
+```
+10km/h * 30min = 5km
+3m * 50cm = 1.5m^2
+6m / 2m = 3
+```
+
+## Data sizes
+
+Bytes are written as `b` and bits as `B`. Besides the unit prefixes, data units can have the binary prefixes `Ki`,
+`Mi`, `Gi`, `Ti`, `Pi`, `Ei`, `Zi` and `Yi`, which are powers of 1024 (e.g. `1Kib = 1024b`). Whether the unit
+prefixes are powers of 1000 or 1024 for data units (i.e. whether `1kb` is 1000 or 1024 bytes) is set by the
+"Data size prefixes" setting.
+
+```
+1Kib in b           => 1024b
+1kb in b            => 1000b (1024b with binary data size prefixes)
+1GiB in Mib         => 128Mib
+```
+
+## Angles
+
+Angles can be written in degrees (`°` or `deg`), radians (`rad`), gradians (`gon` or `grad`), arcminutes (`'` or
+`arcmin`) and arcseconds (`"` or `arcsec`). With the `dms` format, angles are shown in degrees, minutes and seconds.
+
+```
+90deg in rad        => 1.5707963268rad
+30 arcmin in deg    => 0.5°
+45.5045° in dms     => 45° 30' 16.2"
+1rad in dms         => 57° 17' 44.8062470964"
+```
+
+## Currencies
+
+funcially supports currencies from [exchangerate.host](https://exchangerate.host), like `EUR` or `USD`.
+These can be used the same way as units. funcially updates the exchange rates on every startup and saves them
+for when there is no internet connection available.
+
+The symbols `$`, `€`, `£` and `¥` can be used instead of the currency codes, both before and after the amount. `€` stands for euros, while the currencies of the others can be changed in the "Currencies" section of the settings.
+
+By default, amounts of money are shown like other values with a unit (e.g. `$5` results in `5 USD`). If enabled in the "Currencies" section of the settings, they are shown with two decimal places and the currency's symbol, if it has one. The following examples have it enabled:
+
+```
+$5                  => $5.00
+5€ in $             => $5.40
+3 CHF               => 3.00 CHF
+```
+
+To convert with the exchange rates of a past day, add `at` and the date after the currency. These rates are downloaded from [frankfurter.app](https://www.frankfurter.app) and saved, so every day only has to be downloaded once. This isn't available on the web.
+
+```
+100 USD in EUR at {date 01.06.2023}     => €93.18 (with the money format enabled)
+```
+
+## "in" operator
+
+The `in operator` can be used to convert between units and formats.
+
+Syntax: `<expr> in <dec/decimal/bin/binary/hex/sci/scientific/dms> <unit>`
+where **either** the format or the unit can be left out. Conversions can also be chained, in which case the format
+and unit can be given in either order (e.g. `5mi in km in sci`).
+
+```
+0xFF in decimal	    => 255 (default)
+255 in hex		    => 0xFF
+6 in binary	        => 0b110
+255km in mi         => 158.4496540205mi
+255km in sci mi	    => 1.58e2mi
+5mi in km in sci    => 8.04672e0km
+0xff in binary in kb => 0b11111111kb
+```
+
+## Mixed measures
+
+Numbers with units of the same dimension that directly follow each other are added up, e.g. feet and inches or pounds
+and ounces. Right after a number, `in` means inches. Converting into units joined by underscores (e.g. `ft_in`) splits
+the result up into them.
+
+```
+5ft 11in in cm      => 180.3311998374cm
+3lb 4oz             => 3.25lb
+1h 30min in min     => 90min
+180cm in ft_in      => 5ft 10.866in
+100000s in d_h_min_s => 1d 3h 46min 40s
+```
+
+## Result format
+
+Results without an explicit format are shown in the default format of the "Results" settings (decimal by default).
+Decimal numbers are rounded to the chosen number of decimal places (10 by default, at most 15), can be padded with
+zeros to that many decimal places, and can automatically be shown in scientific notation if they are from 10^15 or
+below 10^-6. The digits of hex and binary results can be grouped in fours (e.g. `0xFFFF_FFFF`, which can be written in
+the input as well) and padded with zeros to the word size (e.g. `0x00FF` with 16 bits).
+
+With `in auto`, the unit of the result gets the prefix that keeps the number between 1 and 1000 where possible (e.g.
+`1500m + 500m in auto` is `2km`). Only the prefixes that are powers of 1000 are used, times only get smaller prefixes,
+and units that aren't usually written with prefixes (e.g. `ft`) are kept. The "Pick unit prefixes automatically"
+setting does this for every line that isn't converted into a unit with `in`.
+
+The "Display" setting chooses what results show: rounded numbers with abbreviated units, all digits of the numbers,
+units that are spelled out (e.g. `5 Kilometers per Hour`) or the values as they are stored internally. Right-clicking a
+result chooses this for its line only.
+
+# Dates and times
+
+Dates are written as objects with the day, month and year in the order and with the delimiter of the `date` settings
+(e.g. `{date 14.11.2023}`), or `{date now}` for today. Date times additionally have a time (`{datetime 14.11.2023 22:13}`
+or `{datetime now}`) and can be created from a unix timestamp in seconds (`{timestamp 1700000000}`). `unixtime` returns
+the timestamp of a date or date time. The name of the object can be followed by a colon (e.g.
+`{timestamp: 1700000000}`).
+
+Times are shown in the time zone of the `date.timezone` setting (`local` or an offset like `UTC+2` or `UTC-5:30`).
+Date times can be converted into other time zones with `in`.
+
+```
+{date 14.11.2023} + 3d                  => 17.11.2023
+{timestamp 1700000000}                  => 14.11.2023 22:13:20 UTC
+{timestamp 1700000000} in UTC+2         => 15.11.2023 00:13:20 UTC+2
+{datetime 14.11.2023 22:13} + 2h        => 15.11.2023 00:13:00 UTC
+unixtime({date 14.11.2023})             => 1699920000
+```
+
+# Settings
+
+There are some settings, which allow the user to customize funcially to their preferences.
+
+The settings can be accessed via `File>Settings` in the GUI, or via the `set` and `get` commands in the CLI.
+
+## `set` and `get` commands
+
+Syntax: `set <setting> = <value>`, `get <setting>`
+
+A setting is always specified by a "path", delimited by dots.
+
+For both, a question mark behind `<setting>` may be used to list available settings (e.g. `set ?` or `set date ?`).
+
+# GUI
+
+Other features:
+
+- Saving: The input field's text is saved across restarts. On desktop, unsaved changes are additionally written to a
+  recovery file every few seconds, which can be restored if the app wasn't closed properly (e.g. after a crash)
+- Syntax highlighting
+- Installation with installers
+- Available on Desktop and Web. The web version can be downloaded to be available offline, and can thus be used
+  on e.g. Android tablets
+- Searching and replacing, optionally matching the case, only whole words or a regular expression (whose groups can
+  be used in the replacement, e.g. `$1`)
+
+## Comments
+
+Calculations can be annotated using comments. Comments start with `#` and span the entire rest of the line.
+
+```
+# This is a comment spanning the entire line
+3 + 4
+sin(30)
+20% of 100 # This gives more details about the calculation
+```
+
+## Sections
+
+A comment starting with `##` (e.g. `## Budget`) is the header of a section, which reaches until the next header. Clicking
+the ▼ next to a header in the output column folds the section, which hides its lines in the input (they're still
+calculated) and shows how many lines are hidden instead. Clicking it again unfolds the section. Which sections are folded
+is saved with the input. While the search bar is open, all sections are unfolded.
+
+```
+## Budget
+rent := 900
+food := 350
+```
+
+## Opening files
+
+"File > Open file" edits a plain text file instead of the app's own input (desktop only). The file is reloaded whenever
+it's changed by another program, e.g. an editor, so that the app can show the results of calculations that are
+maintained somewhere else. If the input has unsaved changes at that point, the app asks whether to reload the file or to
+keep the input. With "Save on change", every change of the input is written to the file right away. Closing the file
+brings back the app's own input.
+
+## Result column
+
+The divider between the input and the results can be dragged to give either of them more room. The results keep their
+share of the window when it's resized, and the split is saved across restarts. With "Align units of results" in the
+settings, the numbers of the results are right-aligned in front of a column with their units, so that e.g. `5 m` and
+`120 cm` line up. "Highlight results" colors the numbers, units and operators of the results like the ones of the
+input. Results with warnings keep their color.
+
+## Result actions
+
+Right-clicking a result shows the actions for its line: copying the result or just its number, converting it into a
+compatible unit or another format (which adds e.g. `in mi` or `in hex` to the line), choosing how it is displayed,
+showing the steps of the calculation and plotting the function of the line. Results with a unit additionally get
+one-click conversions into the common units of the same dimension in which their number is easiest to read (e.g. `mi`,
+`m` and `yd` for `5 km`).
+
+## Comparing revisions
+
+"Compare" in the menu bar shows how the input differs from the last save or from the start of the session: added,
+removed and edited lines, as well as lines whose results changed (e.g. "was 42.1, now 43.5"). The results are saved
+together with the input, so that the comparison with the last save shows the results as they were at that time, even if
+e.g. exchange rates have changed since.
+
+## Mobile layout
+
+In narrow windows (e.g. on phones), the results are shown below their lines instead of in a column next to the input,
+and a row of buttons above the bottom bar inserts characters that are awkward to type on mobile keyboards (like `^`, `#`,
+`:=` and `?`). The buttons and the menu bar are larger to make them easier to tap. The layout can also be chosen in the
+settings ("Layout").
+
+## Keypad
+
+"Keypad" in the menu bar opens a panel with a calculator keypad, which inserts the text of its keys at the cursor. Besides
+the digits and operators, it has keys for all functions (including custom ones), common units and the scientific
+constants. Hovering over a function or constant shows what it does.
+
+## Go to definition
+
+Cmd+clicking the name of a variable or custom function, or pressing F12 while the cursor is on it, moves the cursor to
+the line where it's defined. If it's defined more than once, the last definition above the cursor is used. ⇧+F12
+highlights all usages of the name instead, until the input is changed or `Escape` is pressed. Both actions are also in
+the context menu of the input.
+
+## Accessibility
+
+Screen readers read out the results in the output column together with their line numbers. Cmd+Alt+R reads out the
+result of the current line, Alt+↑ and Alt+↓ move the cursor to the previous or next line with a result and read it out.
+`Tab` moves the keyboard focus between the controls outside of the input. "High contrast" in the settings switches to
+pure black and white with thicker outlines.
+
+## Plotting
+
+The GUI supports plotting **single argument** functions. When declaring a single argument function,
+a 🗠 icon appears in the output column, which selects the function to show in the plot when it's clicked.
+The plot can be brought up via the button in the top right ("🗠 Plot").
+
+If there are multiple re-declarations of a function, each re-declaration can be shown individually.
+
+Equality checks and inequalities in `x` and `y` (e.g. `x^2 + y^2 = 25` or `y > 2x + 1`) can be plotted as well, even
+though `x` and `y` aren't defined. Equations are drawn as curves, inequalities additionally shade the region in which
+they hold. The boundaries of strict inequalities (`<`, `>` and `!=`) are dashed.
+
+"Export data" in the plot calculates the plotted functions for evenly spaced arguments (by default over the visible
+x-range) and saves the values as CSV or copies them.
+
+The bar above the plot marks points on the selected function: "Roots", "Extrema" and "Intersections with" (another
+plotted function) find them numerically in the visible x-range, and "Add point" adds a point that can be dragged along
+the function. Clicking a point shows its coordinates, which can be inserted into the input as a new line.
+
+"Axes" above the plot switches each axis between a linear and a logarithmic (base 10) scale, fixes the visible range
+of an axis and locks the ratio between the units of the y- and x-axis. These settings are saved with the input.
+
+## Sliders
+
+Syntax: `name := slider(min, max, step)`
+
+A variable defined as a slider starts out at `min`. In the GUI, the output column shows a slider next to the value,
+which changes the variable in steps of `step` between `min` and `max`. Everything using the variable (including the
+plot) is updated while dragging the slider. The values of the sliders are saved with the input.
+
+```
+a := slider(0, 10, 0.5)
+f(x) := a * x^2
+```
+
+## Shortcuts
+
+"Cmd" is `ctrl` on Windows and Linux and `⌘` on macOS.
+
+| Description                         | Shortcut  |
+|-------------------------------------|-----------|
+| Comment/Uncomment selected lines    | Cmd+Alt+N |
+| Surround selection with parentheses | Cmd+B     |
+| Copy result of current line         | Cmd+⇧+C   |
+| Format input text                   | Cmd+Alt+L |
+| Go to definition                    | F12       |
+| Find usages                         | ⇧+F12     |
+| Read out result of current line     | Cmd+Alt+R |
+| Go to previous/next result          | Alt+↑/↓   |
+| Search                              | Cmd+F     |
+| Go to line                          | Cmd+G     |
+
+## Vim-style editing
+
+With "Vim-style editing" enabled in the settings, the input starts in normal mode, in which typing runs commands instead
+of inserting text. The current mode is shown in the bottom bar.
+
+| Command         | Action                                                        |
+|-----------------|---------------------------------------------------------------|
+| `h` `j` `k` `l` | Move left, down, up and right                                 |
+| `w` `b`         | Move to the next word and back to the previous one            |
+| `0` `$`         | Move to the start and end of the line                         |
+| `gg` `G`        | Move to the first and last line                               |
+| `i` `a` `I` `A` | Insert before or after the cursor, at the line's start or end |
+| `o` `O`         | Insert a new line below or above                              |
+| `x`             | Delete the character under the cursor                         |
+| `dd` `yy`       | Delete or copy the line                                       |
+| `p` `P`         | Paste the deleted or copied line below or above               |
+| `Escape`        | Go back to normal mode                                        |
+"#;
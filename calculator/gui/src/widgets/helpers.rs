@@ -5,10 +5,12 @@
  */
 
 use std::ops::Range;
+use std::sync::Arc;
 
-use eframe::egui::{Color32, Context, Event, FontId, Id, Key, Modifiers, text, TextFormat, Ui};
+use eframe::egui::{Color32, Context, Event, FontId, Galley, Id, Key, Modifiers, text, TextFormat, Ui, vec2};
 use eframe::egui::text::{CCursor, CCursorRange};
 use eframe::egui::text_edit::TextEditState;
+use regex::{NoExpand, Regex, RegexBuilder};
 
 #[derive(Debug, Default)]
 pub struct SearchState {
@@ -17,10 +19,27 @@ pub struct SearchState {
     pub text: String,
     pub old_text: String,
     pub match_case: bool,
+    /// Whether only occurrences that aren't part of a longer word are found
+    pub whole_word: bool,
+    /// Whether the text is a regular expression instead of being searched literally
+    pub use_regex: bool,
+    /// Whether the field for the replacement is shown
+    pub show_replace: bool,
+    /// The text occurrences are replaced with. With [SearchState::use_regex], it can refer to the
+    /// groups of the regex (e.g. `$1`).
+    pub replace_text: String,
+    /// The error of the regex, if it is invalid
+    pub error: Option<String>,
     pub occurrences: Vec<Range<usize>>,
     pub selected_range: Option<usize>,
+    /// The regex that's searched for together with the search text and the options it was built
+    /// from, so that it's only built again when they change
+    regex: Option<(SearchOptions, Regex)>,
 }
 
+/// The text and the options of a search
+type SearchOptions = (String, bool, bool, bool);
+
 impl SearchState {
     pub fn update(&mut self, searched_text: &str) {
         if self.text != self.old_text {
@@ -31,17 +50,88 @@ impl SearchState {
             return;
         }
 
-        let text = self.text.trim();
-        if text.is_empty() { return; }
+        let Some(regex) = self.regex() else {
+            self.occurrences.clear();
+            return;
+        };
+        self.occurrences = regex.find_iter(searched_text)
+            .map(|found| found.range())
+            .filter(|range| !range.is_empty())
+            .collect::<Vec<_>>();
+        if let Some(selected) = self.selected_range {
+            self.selected_range = if self.occurrences.is_empty() { None } else { Some(selected.min(self.occurrences.len() - 1)) };
+        }
+    }
+
+    /// Returns the regex searched for, which is built again if the search text or the options
+    /// changed
+    fn regex(&mut self) -> Option<&Regex> {
+        let text = if self.use_regex { self.text.as_str() } else { self.text.trim() };
+        if text.is_empty() {
+            self.error = None;
+            return None;
+        }
+
+        let options = (text.to_string(), self.match_case, self.whole_word, self.use_regex);
+        if self.regex.as_ref().map(|(old_options, _)| old_options) != Some(&options) {
+            let pattern = if self.use_regex { text.to_string() } else { regex::escape(text) };
+            let pattern = if self.whole_word { format!(r"\b(?:{pattern})\b") } else { pattern };
+            match RegexBuilder::new(&pattern).case_insensitive(!self.match_case).build() {
+                Ok(regex) => {
+                    self.regex = Some((options, regex));
+                    self.error = None;
+                }
+                Err(e) => {
+                    self.regex = None;
+                    self.error = Some(e.to_string());
+                }
+            }
+        }
+        self.regex.as_ref().map(|(_, regex)| regex)
+    }
+
+    /// Replaces the selected occurrence (or the first one if none is selected) in `searched_text`
+    ///
+    /// **Returns:** Whether an occurrence was replaced
+    pub fn replace(&mut self, searched_text: &mut String) -> bool {
+        let index = self.selected_range.unwrap_or_default();
+        let replace_text = self.replace_text.clone();
+        let use_regex = self.use_regex;
+        let Some(regex) = self.regex() else { return false; };
+        let Some(captures) = regex.captures_iter(searched_text)
+            .filter(|captures| !captures.get(0).unwrap().range().is_empty())
+            .nth(index) else { return false; };
+
+        let range = captures.get(0).unwrap().range();
+        let mut replacement = String::new();
+        if use_regex {
+            captures.expand(&replace_text, &mut replacement);
+        } else {
+            replacement = replace_text;
+        }
+        searched_text.replace_range(range, &replacement);
+        self.update(searched_text);
+        true
+    }
 
-        let (searched_text, text) = if self.match_case {
-            (searched_text.to_string(), text.to_string())
+    /// Replaces all occurrences in `searched_text`
+    ///
+    /// **Returns:** The number of replaced occurrences
+    pub fn replace_all(&mut self, searched_text: &mut String) -> usize {
+        let count = self.occurrences.len();
+        let replace_text = self.replace_text.clone();
+        let use_regex = self.use_regex;
+        let Some(regex) = self.regex() else { return 0; };
+
+        let replaced = if use_regex {
+            regex.replace_all(searched_text, replace_text.as_str())
         } else {
-            (searched_text.to_lowercase(), text.to_lowercase())
+            regex.replace_all(searched_text, NoExpand(&replace_text))
         };
-        self.occurrences = searched_text.match_indices(&text)
-            .map(|(i, str)| i..i + str.len())
-            .collect::<Vec<_>>();
+        *searched_text = replaced.into_owned();
+        self.selected_range = None;
+        self.update(searched_text);
+        count
     }
 
     pub fn set_range_in_text_edit_state(&self, ctx: &Context, id: &str) {
@@ -118,3 +208,23 @@ pub fn section(range: Range<usize>, font_id: FontId, color: Color32) -> text::La
         },
     }
 }
+
+/// Adds `spacing` below every paragraph of `galley`, e.g. to make room for something shown below
+/// each line
+pub fn space_paragraphs(galley: Arc<Galley>, spacing: f32) -> Arc<Galley> {
+    let mut galley = (*galley).clone();
+    let mut offset = 0.0;
+    for row in &mut galley.rows {
+        let delta = vec2(0.0, offset);
+        row.rect = row.rect.translate(delta);
+        row.visuals.mesh.translate(delta);
+        row.visuals.mesh_bounds = row.visuals.mesh_bounds.translate(delta);
+        for glyph in &mut row.glyphs { glyph.pos += delta; }
+        if row.ends_with_newline { offset += spacing; }
+    }
+    // The last paragraph doesn't end with a newline
+    offset += spacing;
+    galley.rect.max.y += offset;
+    galley.mesh_bounds.max.y += offset;
+    Arc::new(galley)
+}
@@ -0,0 +1,175 @@
+/*
+ * Copyright (c) 2023, david072
+ *
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+//! Plotting relations between `x` and `y` (e.g. `x^2 + y^2 = 25` or `y > 2x + 1`) by calculating
+//! them on a grid covering the visible bounds. The curves are traced using marching squares and
+//! the regions in which inequalities hold are shaded.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use eframe::egui::*;
+use eframe::egui::plot::{LineStyle, PlotBounds, PlotPoints, PlotUi, Polygon};
+
+use funcially_core::{BooleanOperator, Calculator, Relation as CalcRelation, RelationEvaluator};
+
+use super::plot_axes::{PlotAxes, Scale};
+
+/// The number of cells in each direction
+const GRID_SIZE: usize = 80;
+const SHADE_ALPHA: f32 = 0.2;
+
+type Point = [f64; 2];
+
+#[derive(Debug)]
+struct Geometry {
+    /// The line segments making up the curve on which both sides are equal
+    segments: Vec<[Point; 2]>,
+    /// The rectangles (min, max) in which an inequality holds
+    regions: Vec<[Point; 2]>,
+}
+
+#[derive(Debug, PartialEq)]
+struct CacheKey {
+    bounds: [f64; 4],
+    scales: (Scale, Scale),
+}
+
+/// The geometry of a plotted relation, which only has to be recalculated when the visible bounds
+/// change. Since relations are recreated whenever the input changes, changed variables can't make
+/// it outdated.
+#[derive(Debug, Default)]
+pub struct ImplicitPlotCache(RefCell<Option<(CacheKey, Rc<Geometry>)>>);
+
+/// Draws the curve of `relation` and, for inequalities, shades the region in which it holds
+pub fn plot_relation(
+    plot_ui: &mut PlotUi,
+    name: &str,
+    relation: &CalcRelation,
+    cache: &ImplicitPlotCache,
+    calculator: &Calculator,
+    axes: &PlotAxes,
+    color: Color32,
+) {
+    let bounds = plot_ui.plot_bounds();
+    let key = CacheKey {
+        bounds: [bounds.min()[0], bounds.min()[1], bounds.max()[0], bounds.max()[1]],
+        scales: (axes.x_scale, axes.y_scale),
+    };
+
+    let mut cache = cache.0.borrow_mut();
+    let geometry = match &*cache {
+        Some((cached_key, geometry)) if *cached_key == key => geometry.clone(),
+        _ => {
+            let evaluator = calculator.relation_evaluator(relation);
+            let geometry = Rc::new(geometry(&evaluator, relation.operator(), &bounds, axes));
+            *cache = Some((key, geometry.clone()));
+            geometry
+        }
+    };
+
+    for [min, max] in &geometry.regions {
+        plot_ui.polygon(Polygon::new(PlotPoints::new(vec![*min, [max[0], min[1]], *max, [min[0], max[1]]]))
+            .color(color)
+            .width(0.0)
+            .fill_alpha(SHADE_ALPHA)
+            .name(name));
+    }
+
+    // Points on the boundary of strict inequalities don't belong to the solutions
+    let style = match relation.operator() {
+        BooleanOperator::Equal | BooleanOperator::ApproximatelyEqual | BooleanOperator::GreaterThanEqual
+        | BooleanOperator::LessThanEqual => LineStyle::Solid,
+        BooleanOperator::NotEqual | BooleanOperator::GreaterThan | BooleanOperator::LessThan => LineStyle::dashed_loose(),
+    };
+    for segment in &geometry.segments {
+        plot_ui.line(plot::Line::new(PlotPoints::new(segment.to_vec()))
+            .color(color)
+            .style(style)
+            .name(name));
+    }
+}
+
+fn geometry(evaluator: &RelationEvaluator, operator: BooleanOperator, bounds: &PlotBounds, axes: &PlotAxes) -> Geometry {
+    let [x_min, y_min] = bounds.min();
+    let cell_width = bounds.width() / GRID_SIZE as f64;
+    let cell_height = bounds.height() / GRID_SIZE as f64;
+    let point = |i: usize, j: usize| [x_min + i as f64 * cell_width, y_min + j as f64 * cell_height];
+
+    // values[i][j] is the difference between both sides at the i-th column and j-th row
+    let values = (0..=GRID_SIZE)
+        .map(|i| (0..=GRID_SIZE)
+            .map(|j| {
+                let [x, y] = point(i, j);
+                evaluator.difference(axes.x_scale.value_at(x), axes.y_scale.value_at(y))
+            })
+            .collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+
+    let mut segments = vec![];
+    let mut regions = vec![];
+    for i in 0..GRID_SIZE {
+        // The row at which the current region in this column started
+        let mut region_start = None;
+        for j in 0..GRID_SIZE {
+            let corners = [
+                (point(i, j), values[i][j]),
+                (point(i + 1, j), values[i + 1][j]),
+                (point(i + 1, j + 1), values[i + 1][j + 1]),
+                (point(i, j + 1), values[i][j + 1]),
+            ];
+            segments.extend(cell_segments(corners));
+
+            if !matches!(operator, BooleanOperator::Equal | BooleanOperator::ApproximatelyEqual) {
+                let center = corners.iter().map(|(_, value)| value).sum::<f64>() / 4.0;
+                match (evaluator.holds(center), region_start) {
+                    (true, None) => region_start = Some(j),
+                    (false, Some(start)) => {
+                        regions.push([point(i, start), point(i + 1, j)]);
+                        region_start = None;
+                    }
+                    _ => {}
+                }
+            }
+        }
+        if let Some(start) = region_start {
+            regions.push([point(i, start), point(i + 1, GRID_SIZE)]);
+        }
+    }
+
+    Geometry { segments, regions }
+}
+
+/// Returns the parts of the curve on which the value is zero inside a cell, given its corners in
+/// counterclockwise order, starting at the bottom left
+fn cell_segments(corners: [(Point, f64); 4]) -> Vec<[Point; 2]> {
+    if corners.iter().any(|(_, value)| !value.is_finite()) { return vec![]; }
+
+    // The points at which the edges (bottom, right, top, left) cross zero
+    let crossings = (0..4)
+        .map(|edge| {
+            let ((start, a), (end, b)) = (corners[edge], corners[(edge + 1) % 4]);
+            if (a < 0.0) == (b < 0.0) { return None; }
+            let t = a / (a - b);
+            Some([start[0] + t * (end[0] - start[0]), start[1] + t * (end[1] - start[1])])
+        })
+        .collect::<Vec<_>>();
+
+    match crossings.iter().flatten().copied().collect::<Vec<_>>().as_slice() {
+        [a, b] => vec![[*a, *b]],
+        [bottom, right, top, left] => {
+            // A saddle: If the center has the same sign as the bottom left corner, the curve
+            // separates the bottom right and top left corners from it.
+            let center = corners.iter().map(|(_, value)| value).sum::<f64>() / 4.0;
+            if (center < 0.0) == (corners[0].1 < 0.0) {
+                vec![[*bottom, *right], [*top, *left]]
+            } else {
+                vec![[*bottom, *left], [*right, *top]]
+            }
+        }
+        _ => vec![],
+    }
+}
@@ -0,0 +1,79 @@
+/*
+ * Copyright (c) 2023, david072
+ *
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+//! A classic calculator keypad, which inserts text at the cursor. Besides the digits and operators,
+//! it has buttons for the functions, common units and constants, which are built from the same
+//! metadata as the help.
+
+use eframe::egui::*;
+
+use funcially_core::{Calculator, COMMON_UNITS, CONSTANTS, FunctionKind};
+
+const KEY_SIZE: Vec2 = Vec2::new(36.0, 28.0);
+
+/// The rows of digits and operators. The text of a key is what it inserts.
+const BASIC_KEYS: [[&str; 5]; 5] = [
+    ["7", "8", "9", "/", "("],
+    ["4", "5", "6", "*", ")"],
+    ["1", "2", "3", "-", "^"],
+    ["0", ".", "%", "+", "!"],
+    ["π", "e", "ans", ":=", " in "],
+];
+
+/// Shows the keypad. `calculator` provides the functions, so that custom functions are included.
+///
+/// **Returns:** The text of the key the user clicked on
+pub fn keypad(ui: &mut Ui, calculator: &Calculator) -> Option<String> {
+    let mut inserted = None;
+
+    Grid::new("keypad_basic_keys").spacing(vec2(4.0, 4.0)).show(ui, |ui| {
+        for row in BASIC_KEYS {
+            for text in row {
+                key(ui, text.trim(), text, None, &mut inserted);
+            }
+            ui.end_row();
+        }
+    });
+    ui.add_space(5.0);
+
+    CollapsingHeader::new("Functions").default_open(true).show(ui, |ui| {
+        ui.horizontal_wrapped(|ui| {
+            for signature in calculator.context.borrow().env.function_signatures() {
+                let hover_text = match signature.help {
+                    Some(help) => format!("{}\n{}", help.usage, help.description),
+                    None if signature.kind == FunctionKind::Script => "Function of a script".to_string(),
+                    None => "Custom function".to_string(),
+                };
+                key(ui, &signature.name, &format!("{}(", signature.name), Some(&hover_text), &mut inserted);
+            }
+        });
+    });
+
+    CollapsingHeader::new("Units").show(ui, |ui| {
+        ui.horizontal_wrapped(|ui| {
+            for unit in COMMON_UNITS {
+                key(ui, unit, unit, None, &mut inserted);
+            }
+        });
+    });
+
+    CollapsingHeader::new("Constants").show(ui, |ui| {
+        ui.horizontal_wrapped(|ui| {
+            for constant in CONSTANTS.iter() {
+                key(ui, constant.name, constant.name, Some(constant.description), &mut inserted);
+            }
+        });
+    });
+
+    inserted
+}
+
+/// A key with the label `label`, which sets `inserted` to `text` when it is clicked
+fn key(ui: &mut Ui, label: &str, text: &str, hover_text: Option<&str>, inserted: &mut Option<String>) {
+    let mut response = ui.add(Button::new(RichText::new(label).monospace()).min_size(KEY_SIZE));
+    if let Some(hover_text) = hover_text { response = response.on_hover_text(hover_text); }
+    if response.clicked() { *inserted = Some(text.to_string()); }
+}
@@ -0,0 +1,138 @@
+/*
+ * Copyright (c) 2023, david072
+ *
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+use std::collections::HashMap;
+use std::ops::Range;
+
+use super::diff::{align_lines, DiffEntry};
+
+const LINE_REFERENCE_START: &str = "line(";
+
+/// Gives every line of the source an id that stays the same when lines are inserted or removed
+/// around it. This is used to keep line references (e.g. `line(12)`) pointing to the same line
+/// when the line numbers change.
+#[derive(Debug, Default)]
+pub struct LineIds {
+    lines: Vec<String>,
+    ids: Vec<u64>,
+    next_id: u64,
+}
+
+impl LineIds {
+    /// Assigns ids to the lines of `source` and updates the line references in it, so that they
+    /// still point to the same lines as before the last change.
+    ///
+    /// **Returns:** The new index of the character at `cursor`, which moves if references before
+    /// it changed their length
+    pub fn update(&mut self, source: &mut String, cursor: usize) -> usize {
+        let old_lines = std::mem::take(&mut self.lines);
+        let new_lines = source.split('\n').map(str::to_string).collect::<Vec<_>>();
+        if old_lines == new_lines {
+            self.lines = old_lines;
+            return cursor;
+        }
+
+        let entries = align_lines(&old_lines, &new_lines);
+
+        // Lines that still exist keep their ids, even if they were edited
+        let old_ids = std::mem::take(&mut self.ids);
+        let mut ids = vec![0u64; new_lines.len()];
+        let mut kept_lines = vec![false; new_lines.len()];
+        for entry in entries {
+            match entry {
+                DiffEntry::Unchanged { old, new } | DiffEntry::Changed { old, new } => {
+                    ids[new] = old_ids[old];
+                    kept_lines[new] = true;
+                }
+                DiffEntry::Added(new) => {
+                    ids[new] = self.next_id;
+                    self.next_id += 1;
+                }
+                DiffEntry::Removed(_) => {}
+            }
+        }
+        self.ids = ids;
+
+        let new_indices = self.ids.iter().enumerate()
+            .map(|(i, id)| (*id, i))
+            .collect::<HashMap<_, _>>();
+        let new_line_number = |line_number: usize| -> Option<usize> {
+            let id = old_ids.get(line_number.checked_sub(1)?)?;
+            new_indices.get(id).map(|i| i + 1)
+        };
+
+        let is_renumbered = (1..=old_ids.len())
+            .any(|n| new_line_number(n).map_or(false, |new| new != n));
+        if !is_renumbered {
+            self.lines = new_lines;
+            return cursor;
+        }
+
+        let mut new_source = String::new();
+        let mut new_cursor = cursor;
+        let mut char_index = 0usize;
+        for (i, line) in new_lines.iter().enumerate() {
+            if i != 0 {
+                new_source.push('\n');
+                char_index += 1;
+            }
+
+            let mut last_end = 0usize;
+            // References in lines that were just added already use the new line numbers
+            let references = if kept_lines[i] { line_references(line) } else { vec![] };
+            for (range, line_number) in references {
+                let Some(new_line_number) = new_line_number(line_number) else { continue; };
+                if new_line_number == line_number { continue; }
+
+                new_source += &line[last_end..range.start];
+                char_index += line[last_end..range.start].chars().count();
+
+                let new_number = new_line_number.to_string();
+                if char_index + range.len() <= cursor {
+                    new_cursor = (new_cursor + new_number.len()).saturating_sub(range.len());
+                }
+                new_source += &new_number;
+                char_index += range.len();
+                last_end = range.end;
+            }
+            new_source += &line[last_end..];
+            char_index += line[last_end..].chars().count();
+        }
+
+        *source = new_source;
+        self.lines = source.split('\n').map(str::to_string).collect();
+        new_cursor
+    }
+
+    /// The id of the (zero-based) line `line`
+    pub fn id(&self, line: usize) -> Option<u64> {
+        self.ids.get(line).copied()
+    }
+}
+
+/// Finds the line references (`line(<number>)`) in `line` and returns the ranges of their numbers
+/// together with the numbers
+fn line_references(line: &str) -> Vec<(Range<usize>, usize)> {
+    let mut references = vec![];
+    for (start, _) in line.match_indices(LINE_REFERENCE_START) {
+        // Don't match functions ending with "line" (e.g. `myline(3)`)
+        let is_part_of_identifier = line[..start].chars().last()
+            .map_or(false, |c| c.is_alphanumeric() || c == '_');
+        if is_part_of_identifier { continue; }
+
+        let number_start = start + LINE_REFERENCE_START.len();
+        let number_length = line[number_start..].chars()
+            .take_while(char::is_ascii_digit)
+            .count();
+        let number_end = number_start + number_length;
+        if number_length == 0 || !line[number_end..].starts_with(')') { continue; }
+
+        let Ok(number) = line[number_start..number_end].parse::<usize>() else { continue; };
+        if number == 0 { continue; }
+        references.push((number_start..number_end, number));
+    }
+    references
+}
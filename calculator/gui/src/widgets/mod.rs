@@ -10,13 +10,21 @@ use eframe::egui::*;
 use eframe::egui::style::Margin;
 use eframe::egui::text_edit::TextEditState;
 use eframe::epaint::Shadow;
-use egui_commonmark::{CommonMarkCache, CommonMarkViewer};
 
-use funcially_core::{Calculator, SourceRange};
+use funcially_core::{Calculator, Function as CalcFn, SourceRange};
 
-use crate::Line;
+use crate::{ColorSegment, Function, Line, Relation};
+use plot_axes::PlotAxes;
 
+pub mod dependency_graph;
+pub mod diff;
+pub mod folding;
+pub mod help;
 pub mod helpers;
+pub mod keypad;
+pub mod line_ids;
+pub mod modal_editing;
+pub mod profiling;
 
 macro_rules! storable {
     ($st:ident) => {
@@ -34,8 +42,13 @@ macro_rules! storable {
     }
 }
 
+pub mod implicit_plot;
+pub mod plot_axes;
+mod plot_tools;
+
 const LINE_PICKER_ID: &str = "line-picker-dialog";
 const FULL_SCREEN_PLOT_ID: &str = "full-screen-plot";
+const PLOT_TOOLS_ID: &str = "plot-tools";
 
 #[derive(Default, Clone, serde::Serialize, serde::Deserialize)]
 pub struct LinePickerDialogState {
@@ -58,9 +71,14 @@ pub fn dialog<R>(ctx: &Context, title: Option<&str>, add_contents: impl FnOnce(&
         .resizable(false)
         .scroll2([false, false])
         .collapsible(false)
-        .show(ctx, add_contents);
+        .show(ctx, add_contents)
+        .unwrap();
 
-    response.unwrap().inner.unwrap()
+    ctx.accesskit_node_builder(response.response.id, |builder| {
+        builder.set_role(accesskit::Role::Dialog);
+        if let Some(title) = title { builder.set_name(title); }
+    });
+    response.inner.unwrap()
 }
 
 impl<'a> LinePickerDialog<'a> {
@@ -156,6 +174,7 @@ pub struct FullScreenPlot<'a> {
     full_size: Vec2,
     lines: &'a Vec<Line>,
     calculator: &'a Calculator,
+    axes: &'a mut PlotAxes,
 }
 
 impl<'a> FullScreenPlot<'a> {
@@ -173,23 +192,27 @@ impl<'a> FullScreenPlot<'a> {
         full_size: Vec2,
         lines: &'a Vec<Line>,
         calculator: &'a Calculator,
+        axes: &'a mut PlotAxes,
     ) -> Self {
         Self {
             full_size,
             lines,
             calculator,
+            axes,
         }
     }
 
-    pub fn maybe_show(&self, ctx: &Context) {
+    /// Returns the coordinate of a marked point that the user chose to insert into the input
+    pub fn maybe_show(self, ctx: &Context) -> Option<f64> {
         let mut state = FullScreenPlotState::load(ctx, FULL_SCREEN_PLOT_ID);
 
         // if we're not in full, stop showing
         if !state.is_full_screen {
             state.store(ctx, FULL_SCREEN_PLOT_ID);
-            return;
+            return None;
         }
 
+        let mut inserted_value = None;
         Window::new("__full_screen_plot_window")
             .title_bar(false)
             .frame(Frame {
@@ -204,7 +227,8 @@ impl<'a> FullScreenPlot<'a> {
             .resizable(false)
             .fixed_size(self.full_size)
             .show(ctx, |ui| {
-                let response = plot(ui, self.lines, self.calculator);
+                let response = plot(ui, self.lines, self.calculator, self.axes);
+                inserted_value = response.inner.inserted_value;
 
                 // only show this is we're in fullscreen and the animation has finished
                 ui.allocate_ui_at_rect(
@@ -220,70 +244,174 @@ impl<'a> FullScreenPlot<'a> {
             });
 
         state.store(ctx, FULL_SCREEN_PLOT_ID);
+        inserted_value
     }
 }
 
-pub fn plot(ui: &mut Ui, lines: &Vec<Line>, calculator: &Calculator) -> InnerResponse<()> {
-    plot::Plot::new("calculator_plot")
-        .data_aspect(1.0)
-        .coordinates_formatter(
-            plot::Corner::LeftBottom, plot::CoordinatesFormatter::default(),
-        )
+pub struct PlotOutput {
+    /// The bounds that are visible in the plot
+    pub bounds: plot::PlotBounds,
+    /// The coordinate of a marked point that the user chose to insert into the input
+    pub inserted_value: Option<f64>,
+}
+
+/// Plots the functions that are selected to be shown, together with the tools for marking points
+/// on them
+pub fn plot(ui: &mut Ui, lines: &[Line], calculator: &Calculator, axes: &mut PlotAxes) -> InnerResponse<PlotOutput> {
+    let functions = plotted_functions(lines);
+    let mut tools = plot_tools::PlotTools::load(ui.ctx(), PLOT_TOOLS_ID);
+    tools.update_functions(&functions);
+
+    ui.horizontal_wrapped(|ui| {
+        axes.menu(ui, tools.bounds());
+        if !functions.is_empty() {
+            ui.separator();
+            tools.buttons(ui, &functions, calculator, axes);
+        }
+    });
+    let inserted_value = tools.selected_point(ui);
+
+    let (x_scale, y_scale) = (axes.x_scale, axes.y_scale);
+    let response = axes.configure(plot::Plot::new("calculator_plot"))
+        .allow_drag(!tools.is_dragging())
         .legend(plot::Legend::default().position(plot::Corner::RightBottom))
         .show(ui, |plot_ui| {
-            for line in lines {
-                if let Line::Line { function, show_in_plot, .. } = line {
-                    if !show_in_plot { continue; }
-                    if let Some(function) = function {
-                        if function.1 != 1 { continue; }
-
-                        let env = calculator.clone_env();
-                        let currencies = calculator.context.borrow().currencies.clone();
-                        let settings = calculator.context.borrow().settings.clone();
-                        let f = function.2.clone();
-
-                        plot_ui.line(plot::Line::new(
-                            plot::PlotPoints::from_explicit_callback(move |x| {
-                                match env.resolve_specific_function(
-                                    &f,
-                                    &[(funcially_core::NumberValue::new(x), SourceRange::empty())],
-                                    SourceRange::empty(),
-                                    Rc::new(RefCell::new(funcially_core::ContextData {
-                                        env: env.clone(),
-                                        currencies: currencies.clone(),
-                                        settings: settings.clone(),
-                                    })),
-                                ) {
-                                    Ok(v) => v.to_number()
-                                        .map(|num| num.number)
-                                        .unwrap_or(f64::NAN),
-                                    Err(_) => f64::NAN,
-                                }
-                            }, .., 512)
-                        ).name(&function.0));
-                    }
-                }
+            axes.apply_bounds(plot_ui);
+
+            for (name, function) in &functions {
+                let f = function_evaluator(calculator, function);
+                plot_ui.line(plot::Line::new(
+                    plot::PlotPoints::from_explicit_callback(move |x| y_scale.to_plot(f(x_scale.value_at(x))), .., 512)
+                ).name(name));
             }
+            for (i, Relation(name, relation, cache)) in plotted_relations(lines).into_iter().enumerate() {
+                let color = auto_color(functions.len() + i);
+                implicit_plot::plot_relation(plot_ui, name, relation, cache, calculator, axes, color);
+            }
+
+            tools.show(plot_ui, &functions, calculator, axes);
+            plot_ui.plot_bounds()
+        });
+
+    tools.store(ui.ctx(), PLOT_TOOLS_ID);
+    InnerResponse::new(PlotOutput { bounds: response.inner, inserted_value }, response.response)
+}
+
+/// The names of the functions that are selected to show in the plot, together with the functions
+pub fn plotted_functions(lines: &[Line]) -> Vec<(&str, &CalcFn)> {
+    lines.iter()
+        .filter_map(|line| match line {
+            Line::Line { function: Some(Function(name, 1, f)), show_in_plot: true, .. } => Some((name.as_str(), f)),
+            _ => None,
+        })
+        .collect()
+}
+
+/// The relations that are selected to show in the plot
+pub fn plotted_relations(lines: &[Line]) -> Vec<&Relation> {
+    lines.iter()
+        .filter_map(|line| match line {
+            Line::Line { relation: Some(relation), show_in_plot: true, .. } => Some(relation.as_ref()),
+            _ => None,
         })
+        .collect()
 }
 
-pub fn output_text(ui: &mut Ui, str: &str, font_id: FontId, index: usize) -> Response {
-    let text: WidgetText = str.into();
-    let valign = ui.layout().vertical_align();
+/// The color the plot gives to its `index`th item, if the item doesn't have a color. Items that
+/// are made up of multiple plot items use it to give all of them the same color.
+fn auto_color(index: usize) -> Color32 {
+    let golden_ratio = (5f32.sqrt() - 1.0) / 2.0;
+    eframe::epaint::Hsva::new((index as f32 * golden_ratio).fract(), 0.85, 0.5, 1.0).into()
+}
 
-    let mut text_job = text.into_text_job(
-        ui.style(), FontSelection::FontId(font_id.clone()), valign,
-    );
-    text_job.job.wrap.max_width = f32::INFINITY;
-    text_job.job.halign = Align::RIGHT;
-    let galley = ui.fonts(|fonts| text_job.into_galley(fonts));
+/// Returns a closure calculating the single argument function `f` in the environment of
+/// `calculator`. Arguments for which the function can't be calculated result in NaN.
+pub fn function_evaluator(calculator: &Calculator, f: &CalcFn) -> impl Fn(f64) -> f64 {
+    let env = calculator.clone_env();
+    let currencies = calculator.context.borrow().currencies.clone();
+    let settings = calculator.context.borrow().settings;
+    let capabilities = calculator.context.borrow().capabilities;
+    let f = f.clone();
+
+    move |x| {
+        match env.resolve_specific_function(
+            &f,
+            &[(funcially_core::NumberValue::new(x), SourceRange::empty())],
+            SourceRange::empty(),
+            Rc::new(RefCell::new(funcially_core::ContextData {
+                env: env.clone(),
+                currencies: currencies.clone(),
+                settings,
+                capabilities,
+                cancellation: funcially_core::CancellationToken::default(),
+                constant_cache: funcially_core::ConstantCache::default(),
+            })),
+        ) {
+            Ok(v) => v.to_number()
+                .map(|num| num.number)
+                .unwrap_or(f64::NAN),
+            Err(_) => f64::NAN,
+        }
+    }
+}
+
+/// Shows a result right-aligned with its line number. With a `unit_column` (the byte index at which
+/// the unit of `str` starts and the width of the column), the number is right-aligned in front of
+/// the column and the unit is left-aligned in it, so that the units of all results line up. The
+/// parts of `str` are shown in the colors of `color_segments`, and in `color` if there are none.
+pub fn output_text(
+    ui: &mut Ui,
+    str: &str,
+    font_id: FontId,
+    index: usize,
+    color: Color32,
+    color_segments: &[ColorSegment],
+    unit_column: Option<(usize, f32)>,
+) -> Response {
+    // The ranges of the segments are in characters
+    let byte_index = |char_index: usize| str.char_indices().nth(char_index).map_or(str.len(), |(i, _)| i);
+    let layout = |ui: &Ui, range: std::ops::Range<usize>, halign: Align| {
+        let mut job = text::LayoutJob { halign, ..Default::default() };
+        job.wrap.max_width = f32::INFINITY;
+        let mut append = |text: &str, color: Color32| if !text.is_empty() {
+            job.append(text, 0.0, TextFormat { font_id: font_id.clone(), color, ..Default::default() });
+        };
+
+        let mut end = range.start;
+        for segment in color_segments {
+            let start = byte_index(segment.range.start).max(end);
+            let segment_end = byte_index(segment.range.end).min(range.end);
+            if start >= segment_end { continue; }
+            append(&str[end..start], color);
+            append(&str[start..segment_end], segment.color);
+            end = segment_end;
+        }
+        append(&str[end..range.end], color);
+        ui.fonts(|fonts| fonts.layout_job(job))
+    };
+
+    let (galley, unit_galley) = match unit_column {
+        Some((unit_start, column_width)) => {
+            let unit = &str[unit_start..];
+            let unit_range = unit_start + unit.len() - unit.trim_start().len()..unit_start + unit.trim_end().len();
+            let unit = layout(ui, unit_range, Align::LEFT);
+            (layout(ui, 0..str[..unit_start].trim_end().len(), Align::RIGHT), Some((unit, column_width)))
+        }
+        None => (layout(ui, 0..str.len(), Align::RIGHT), None),
+    };
+    // The space between the number and the unit column
+    let unit_gap = ui.fonts(|f| f.glyph_width(&font_id, ' '));
+    let unit_column_width = unit_galley.as_ref().map_or(0.0, |(_, width)| width + unit_gap);
 
     let glyph_width = ui.fonts(|f| f.glyph_width(&font_id, '0'));
     let index = index.to_string();
     let index_str_width = index.len() as f32 * glyph_width;
 
     let (full_rect, response) = ui.allocate_exact_size(
-        vec2(ui.available_width(), galley.size().y), Sense::click());
+        vec2(ui.available_width(), galley.size().y.max(unit_galley.as_ref().map_or(0.0, |(unit, _)| unit.size().y))),
+        Sense::click(),
+    );
+    response.widget_info(|| WidgetInfo::labeled(WidgetType::Label, format!("Line {index}: {str}")));
 
     let index_rect = Rect::from_min_max(
         full_rect.left_top(),
@@ -296,7 +424,7 @@ pub fn output_text(ui: &mut Ui, str: &str, font_id: FontId, index: usize) -> Res
     );
 
     let bg_rect = Rect::from_min_max(
-        pos2(text_max_rect.right_top().x - galley.size().x, text_max_rect.right_top().y),
+        pos2(text_max_rect.right_top().x - unit_column_width - galley.size().x, text_max_rect.right_top().y),
         text_max_rect.right_bottom(),
     ).expand(1.5);
 
@@ -311,10 +439,11 @@ pub fn output_text(ui: &mut Ui, str: &str, font_id: FontId, index: usize) -> Res
             );
         }
 
-        let mut text_color = Color32::GREEN;
+        // Hovered results are shown in black on the background
+        let mut text_color = None;
         if let Some(hover_pos) = response.hover_pos() {
             if bg_rect.contains(hover_pos) {
-                text_color = Color32::BLACK;
+                text_color = Some(Color32::BLACK);
                 ui.painter()
                     .with_clip_rect(Rect::from_min_max(
                         pos2(
@@ -326,16 +455,23 @@ pub fn output_text(ui: &mut Ui, str: &str, font_id: FontId, index: usize) -> Res
                     .rect(
                         bg_rect,
                         0.5 * full_rect.height(),
-                        Color32::GREEN,
+                        color,
                         Stroke::NONE,
                     );
             }
         }
 
-        let galley_length = galley.size().x;
-        ui.painter()
-            .with_clip_rect(text_max_rect)
-            .galley_with_color(text_max_rect.right_top(), galley.galley, text_color);
+        let galley_length = galley.size().x + unit_column_width;
+        let painter = ui.painter().with_clip_rect(text_max_rect);
+        let number_pos = text_max_rect.right_top() - vec2(unit_column_width, 0.0);
+        let paint = |pos, galley| match text_color {
+            Some(text_color) => painter.galley_with_color(pos, galley, text_color),
+            None => painter.galley(pos, galley),
+        };
+        paint(number_pos, galley);
+        if let Some((unit, _)) = unit_galley {
+            paint(number_pos + vec2(unit_gap, 0.0), unit);
+        }
 
         let mut show_copied_text = false;
         if response.clicked() && bg_rect.contains(response.hover_pos().unwrap()) {
@@ -417,365 +553,3 @@ pub fn shortcut_button(ui: &mut Ui, text: &str, shortcut: &str) -> Response {
 
     response
 }
-
-pub fn build_help(ui: &mut Ui) {
-    ui.horizontal(|ui| {
-        ui.spacing_mut().item_spacing.x = 0.0;
-        ui.label("This is a brief overview of the features. A more detailed documentation can be found ");
-        ui.hyperlink_to("here", "https://github.com/david072/funcially/wiki");
-        ui.label(".");
-    });
-
-    let markdown =
-        r#"
-# Literals
-
-Literals can be specified using different representations.
-
-```
-3
-0xFF
-0b110
-3.123
-.123
-```
-
-## Scientific notation
-
-Scientific notation can be used to input big numbers more easily.
-
-```
-1e2
-3e5
-```
-
-# Objects
-
-funcially supports using objects which encapsulate more complex data structures. Objects always follow the syntax:
-`{<name> <args>}`.
-
-The following objects can be used:
-
-| Name | Syntax                                           |
-|------|--------------------------------------------------|
-| date | `{date now}` / `{date day.month.year}`<sup>[1](#date-footnote)</sup> |
-
----
-
-<sup><a name="date-footnote">1</a></sup>: This format can be changed in the [settings](#Settings).
-
-# Operators
-
-## Basic
-
-| Name           | Operator     |
-|----------------|--------------|
-| Addition       | `+`          |
-| Subtraction    | `-`          |
-| Multiplication | `*`          |
-| Division       | `/`          |
-
-## Extended
-
-| Name                            | Operator     |
-|---------------------------------|--------------|
-| Exponentiation                  | `^`          |
-| Bitwise AND                     | `&`          |
-| Bitwise OR                      | `\|`          |
-| Left Shift                      | `<<`         |
-| Right Shift                     | `>>`         |
-| Modulo                          | `mod`        |
-| Taking a percentage of a number | `of`         |
-| Unit conversion                 | `in`         |
-
-## Modifiers
-
-Modifiers are similar to operators, except that they only have one operand.
-
-They change the value of what they were pre- or appended to.
-
-| Name        | Modifier        | Long form               |
-|-------------|-----------------|-------------------------|
-| Percent     | `%`             | `n / 100`               |
-| Bitwise NOT | `!` (prepended) | -                       |
-| Factorial   | `!` (appended)  | `n * (n - 1) * ... * 1` |
-
-```
-20%
-!0b101
-5!
-(50 / 2)%
-```
-
-## Operator order
-
-1. Functions and variables
-2. Groups ("(...)")
-3. Extended operators
-4. Multiplication and division
-5. Addition and subtraction
-6. `of` and `in`
-
-## Inferred multiplication
-
-The multiplication sign can be left out in the following scenarios:
-
-- Before variables
-- Before functions
-- Before groups
-
-```
-2pi
-2sin(30)
-2(3 + 4)
-(3 + 4)(1 + 2)
-```
-
-# Functions
-
-## Standard Functions
-
-Trigonometric functions use parameters in radians and their inverse functions also return values in radians.
-
-To use degrees, simply convert the units:
-```
-sin(30°)
-asin(.5) in °
-```
-
-| Description                        | Function                              | Example                  |
-|------------------------------------|---------------------------------------|--------------------------|
-| Sine                               | sin                                   | `sin(1)`                 |
-| Arcsine                            | asin                                  | `asin(.5)`               |
-| Cosine                             | cos                                   | `cos(1)`                 |
-| Arccosine                          | acos                                  | `acos(.5)`               |
-| Tangent                            | tan                                   | `tan(1)`                 |
-| Arctangent                         | atan                                  | `atan(.5)`               |
-| Cotangent                          | cot                                   | `cot(1)`                 |
-| Arccotangent                       | acot                                  | `acot(.5)`               |
-| Natural logarithm                  | ln                                    | `ln(3)`                  |
-| Logarithm                          | log(base, n)                          | `log(2, 8)`              |
-| Square root                        | sqrt                                  | `sqrt(25)`               |
-| Cube root                          | cbrt                                  | `cbrt(1000)`             |
-| Root                               | root(index, n)                        | `root(2, 8)`             |
-| Absolute value                     | abs                                   | `abs(-10)`               |
-| Flooring                           | floor                                 | `floor(3.5)`             |
-| Lerping                            | lerp(a, b, t)                         | `lerp(0, 50, .5)`        |
-| Ceiling                            | ceil                                  | `ceil(20.2)`             |
-| Clamping                           | clamp(n, start, end)                  | `clamp(5, 0, 2)`         |
-| Map from one range to another      | map(n, start1, end1, start2, end2)    | `map(5, 0, 10, 20, 100)` |
-| Rounding (optional decimal places) | round(n) / round(n, decimal places)   | `round(5.2)`             |
-
-## Custom functions
-
-Syntax: `name(arg1, arg2, ...) := expr`
-
-A custom functions is defined with a name, a set of arguments separated by commas and an expression that evaluates to
-the function's value. In the expression, the names given to the arguments before the definition sign (`:=`) can be used.
-As such, this expression cannot be evaluated right away.
-
-If "expr" is empty, the function is removed.
-
-When a function is re-declared (i.e. the function already exists), one of two things happens:
-
-- If the function being declared is a standard function (e.g. `sin`), an error occurs, saying that standard functions
-  cannot be overridden.
-- Otherwise, the function's expression and arguments are updated.
-
-The same applies when removing a function.
-
-```
-f(x) := x * 3
-f(x, y) := x ^ y
-f(x, y) :=
-```
-
-# Variables
-
-## Constants
-
-| Constant | Value        |
-|----------|--------------|
-| pi       | 3.1415926536 |
-| e        | 2.7182818285 |
-| tau      | 6.2831853072 |
-
-## `ans` Variable
-
-The `ans` variable contains the result of the previous calculation. It starts out at 0.
-
-## Custom variables
-
-Syntax: `name := expr`
-
-A custom variable is defined with a name, and an expression that resolves to its value.
-When the variable is declared, its expression is evaluated and the result (including an optional unit)
-is stored for future use. Variable **do not** store formats.
-
-If "expr" is empty, the variable is removed.
-
-When a variable is re-declared (i.e. the variable already exists), one of two things happens:
-
-- If the variable being declared is a builtin variable (e.g. `pi`), an error occurs, saying that standard
-  variables cannot be overridden.
-- Otherwise, the variable's value is updated.
-
-The same applies when removing a variable.
-
-```
-x := 4 + 3
-pi := 4   => Error: ReservedVariable
-x := 20 * 2
-x :=
-```
-
-# Equality checks
-
-An equals sign ("=") marks this line as an equality check. funcially then returns `True` or `False`, depending on whether
-the two expressions evaluate to the same value (including units).
-
-The equals sign **must** be at the top level and there can only be one equals sign in a line.
-
-```
-20 + 30 = 25 * 2
-20 * 5 = 10
-```
-
-# Equation solving
-
-funcially can solve **linear** equations if there is a question mark (`?`) in either sides.
-
-- There can only be one question mark in an equation.
-- The question mark can only be a function argument of custom functions that are themselves linear.
-
-If the question mark is preceded by a variable name, its calculated value will be assigned to the given
-variable.
-
-```
-30 + ? = 100
-? * sin(30) = 1 + 1
-20 + x? = 100.5
-```
-
-# Units
-
-Units are defined with a name and an optional *unit prefix*.
-
-Units and unit prefixes are case-sensitive.
-
-| Prefix | Name  | Value |
-|--------|-------|-------|
-| `n`    | Nano  | 1e-9  |
-| `m`    | Milli | 1e-3  |
-| `c`    | Centi | 1e-2  |
-| `d`    | Deci  | 1e-1  |
-| `h`    | Hecto | 1e2   |
-| `k`    | Kilo  | 1e3   |
-| `M`    | Mega  | 1e6   |
-| `G`    | Giga  | 1e9   |
-| `T`    | Tera  | 1e12  |
-| `P`    | Peta  | 1e15  |
-| `E`    | Exa   | 1e18  |
-| `Z`    | Zetta | 1e21  |
-| `Y`    | Yotta | 1e24  |
-
-Unit prefixes can be used without a unit and thus act like a modifier (e.g. `3k = 3000`).
-
-Numbers with and without units can be mixed, resulting in the unit being carried to the result.<br>
-If the line consists **only** of a literal with a unit, the unit in the result is printed in it's long form. Otherwise,
-the abbreviated version is used.
-
-Two units can be combined using a `/` in between.
-
-funcially will automatically convert units to perform operations if needed. In that case, the right hand
-side's is converted into the left hand side's unit.
-
-```
-10m
-10km/h
-(10 + 10)km
-20 * 2min
-```
-
-## Currencies
-
-funcially supports currencies from [exchangerate.host](https://exchangerate.host), like `EUR` or `USD`.
-These can be used the same way as units. funcially updates the exchange rates on every startup and saves them
-for when there is no internet connection available.
-
-## "in" operator
-
-The `in operator` can be used to convert between units and formats.
-
-Syntax: `<expr> in <dec/decimal/bin/binary/hex/sci/scientific> <unit>`
-where **either** the format or the unit can be left out.
-
-```
-0xFF in decimal	    => 255 (default)
-255 in hex		    => 0xFF
-6 in binary	        => 0b110
-255km in mi         => 158.4496540205mi
-255km in sci mi	    => 1.58e2mi
-```
-
-# Settings
-
-There are some settings, which allow the user to customize funcially to their preferences.
-
-The settings can be accessed via `File>Settings` in the GUI, or via the `set` and `get` commands in the CLI.
-
-## `set` and `get` commands
-
-Syntax: `set <setting> = <value>`, `get <setting>`
-
-A setting is always specified by a "path", delimited by dots.
-
-For both, a question mark behind `<setting>` may be used to list available settings (e.g. `set ?` or `set date ?`).
-
-# GUI
-
-Other features:
-
-- Saving: The input field's text is saved across restarts
-- Syntax highlighting
-- Installation with installers
-- Available on Desktop and Web. The web version can be downloaded to be available offline, and can thus be used
-  on e.g. Android tablets
-- Searching
-
-## Comments
-
-Calculations can be annotated using comments. Comments start with `#` and span the entire rest of the line.
-
-```
-# This is a comment spanning the entire line
-3 + 4
-sin(30)
-20% of 100 # This gives more details about the calculation
-```
-
-## Plotting
-
-The GUI supports plotting **single argument** functions. When declaring a single argument function,
-a checkbox appears in the output column, allowing the function to be selected to show in the plot.
-The plot can be brought up via the button in the top right ("🗠 Plot").
-
-If there are multiple re-declarations of a function, each re-declaration can be shown individually.
-
-## Shortcuts
-
-"Cmd" is `ctrl` on Windows and Linux and `⌘` on macOS.
-
-| Description                         | Shortcut  |
-|-------------------------------------|-----------|
-| Comment/Uncomment selected lines    | Cmd+Alt+N |
-| Surround selection with parentheses | Cmd+B     |
-| Copy result of current line         | Cmd+⇧+C   |
-| Format input text                   | Cmd+Alt+L |
-| Search                              | Cmd+F     |
-| Go to line                          | Cmd+G     |
-"#;
-
-    let mut cache = CommonMarkCache::default();
-    CommonMarkViewer::new("viewer").show(ui, &mut cache, markdown);
-}
@@ -0,0 +1,202 @@
+/*
+ * Copyright (c) 2023, david072
+ *
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+//! Vim-style modal editing of the input. The events are handled before they reach the input's
+//! `TextEdit`: in normal mode, typed text is interpreted as commands (e.g. `hjkl` to move the
+//! cursor or `dd` to delete a line) instead of being inserted, while insert mode works like normal
+//! editing until `Escape` is pressed.
+
+use eframe::egui::*;
+use eframe::egui::text::{CCursor, CCursorRange};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    #[default]
+    Normal,
+    Insert,
+}
+
+impl std::fmt::Display for Mode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Normal => write!(f, "NORMAL"),
+            Self::Insert => write!(f, "INSERT"),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ModalEditor {
+    pub mode: Mode,
+    /// The first character of a command consisting of two (e.g. the first `d` of `dd`)
+    pending: Option<char>,
+    /// The lines that were yanked or deleted last, including their line break
+    register: String,
+}
+
+impl ModalEditor {
+    /// Handles the events meant for the input, whose text is `source` and whose cursor is at
+    /// `cursor`. Events that are commands are removed, so that the `TextEdit` doesn't see them.
+    pub fn handle_events(&mut self, events: &mut Vec<Event>, source: &mut String, cursor: &mut CCursorRange) {
+        let mut chars = source.chars().collect::<Vec<_>>();
+        let mut index = cursor.primary.index.min(chars.len());
+        let mut changed = false;
+        let mut moved = false;
+
+        events.retain(|event| {
+            match (self.mode, event) {
+                (Mode::Insert, Event::Key { key: Key::Escape, pressed: true, .. }) => {
+                    self.mode = Mode::Normal;
+                    false
+                }
+                (Mode::Insert, _) => true,
+                (Mode::Normal, Event::Text(text)) => {
+                    for c in text.chars() {
+                        let (command_changed, command_moved) = self.command(c, &mut chars, &mut index);
+                        changed |= command_changed;
+                        moved |= command_moved;
+                    }
+                    false
+                }
+                // Keys that would change the text, which would be surprising in normal mode
+                (Mode::Normal, Event::Key { key: Key::Enter | Key::Backspace | Key::Delete | Key::Tab | Key::Escape, .. }) => {
+                    self.pending = None;
+                    false
+                }
+                (Mode::Normal, Event::Paste(_) | Event::Cut) => false,
+                (Mode::Normal, _) => true,
+            }
+        });
+
+        if changed { *source = chars.iter().collect(); }
+        if changed || moved { *cursor = CCursorRange::one(CCursor::new(index)); }
+    }
+
+    /// Runs the command `c` (or the second part of a pending one) on `chars` with the cursor at
+    /// `index`.
+    ///
+    /// **Returns:** Whether the text was changed and whether the cursor was moved
+    fn command(&mut self, c: char, chars: &mut Vec<char>, index: &mut usize) -> (bool, bool) {
+        let (start, end) = line_bounds(chars, *index);
+
+        if let Some(pending) = self.pending.take() {
+            return match (pending, c) {
+                ('d', 'd') => {
+                    // Include the line break after the line, or the one before it for the last line
+                    let (remove_start, remove_end) = if end < chars.len() {
+                        (start, end + 1)
+                    } else {
+                        (start.saturating_sub(1), end)
+                    };
+                    self.register = line_with_break(&chars[start..end]);
+                    chars.drain(remove_start..remove_end);
+                    *index = line_bounds(chars, remove_start.min(chars.len())).0;
+                    (true, true)
+                }
+                ('y', 'y') => {
+                    self.register = line_with_break(&chars[start..end]);
+                    (false, false)
+                }
+                ('g', 'g') => {
+                    *index = 0;
+                    (false, true)
+                }
+                _ => (false, false),
+            };
+        }
+
+        match c {
+            'd' | 'y' | 'g' => {
+                self.pending = Some(c);
+                return (false, false);
+            }
+            'i' => self.mode = Mode::Insert,
+            'a' => {
+                self.mode = Mode::Insert;
+                *index = (*index + 1).min(end);
+            }
+            'I' => {
+                self.mode = Mode::Insert;
+                *index = start;
+            }
+            'A' => {
+                self.mode = Mode::Insert;
+                *index = end;
+            }
+            'o' | 'O' => {
+                self.mode = Mode::Insert;
+                let position = if c == 'o' { end } else { start };
+                chars.insert(position, '\n');
+                *index = if c == 'o' { end + 1 } else { start };
+                return (true, true);
+            }
+            'h' => *index = index.saturating_sub(1).max(start),
+            'l' => *index = (*index + 1).min(end),
+            '0' => *index = start,
+            '$' => *index = end,
+            'j' | 'k' => {
+                let column = *index - start;
+                let target = if c == 'j' {
+                    if end == chars.len() { return (false, false); }
+                    end + 1
+                } else {
+                    if start == 0 { return (false, false); }
+                    start - 1
+                };
+                let (target_start, target_end) = line_bounds(chars, target);
+                *index = (target_start + column).min(target_end);
+            }
+            'w' => {
+                let is_word = |c: char| c.is_alphanumeric() || c == '_';
+                let mut i = *index;
+                while i < chars.len() && is_word(chars[i]) { i += 1; }
+                while i < chars.len() && !is_word(chars[i]) { i += 1; }
+                *index = i;
+            }
+            'b' => {
+                let is_word = |c: char| c.is_alphanumeric() || c == '_';
+                let mut i = *index;
+                while i > 0 && !is_word(chars[i - 1]) { i -= 1; }
+                while i > 0 && is_word(chars[i - 1]) { i -= 1; }
+                *index = i;
+            }
+            'G' => *index = line_bounds(chars, chars.len()).0,
+            'x' => {
+                if *index >= end { return (false, false); }
+                chars.remove(*index);
+                return (true, true);
+            }
+            'p' | 'P' => {
+                if self.register.is_empty() { return (false, false); }
+                let (position, text) = if c == 'P' {
+                    (start, self.register.clone())
+                } else if end < chars.len() {
+                    (end + 1, self.register.clone())
+                } else {
+                    // The last line doesn't end with a line break
+                    (end, format!("\n{}", self.register.trim_end_matches('\n')))
+                };
+                chars.splice(position..position, text.chars());
+                *index = if text.starts_with('\n') { position + 1 } else { position };
+                return (true, true);
+            }
+            _ => return (false, false),
+        }
+        (false, true)
+    }
+}
+
+/// Returns the indices of the first character of the line containing `index` and of its end
+/// (without the line break)
+fn line_bounds(chars: &[char], index: usize) -> (usize, usize) {
+    let start = chars[..index].iter().rposition(|c| *c == '\n').map_or(0, |i| i + 1);
+    let end = chars[index..].iter().position(|c| *c == '\n').map_or(chars.len(), |i| index + i);
+    (start, end)
+}
+
+fn line_with_break(line: &[char]) -> String {
+    line.iter().collect::<String>() + "\n"
+}
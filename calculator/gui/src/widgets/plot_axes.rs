@@ -0,0 +1,200 @@
+/*
+ * Copyright (c) 2023, david072
+ *
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+//! The scaling, bounds and aspect ratio of the plot's axes
+
+use std::ops::RangeInclusive;
+
+use eframe::egui::*;
+use eframe::egui::plot::{CoordinatesFormatter, Plot, PlotBounds, PlotPoint, PlotUi};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum Scale {
+    #[default]
+    Linear,
+    /// Base 10
+    Logarithmic,
+}
+
+impl Scale {
+    /// Converts a value to the coordinate it's drawn at. Non-positive values can't be drawn on a
+    /// logarithmic axis and result in NaN.
+    pub fn to_plot(self, value: f64) -> f64 {
+        match self {
+            Scale::Linear => value,
+            Scale::Logarithmic => value.log10(),
+        }
+    }
+
+    /// Converts a coordinate in the plot to the value drawn there
+    pub fn value_at(self, coordinate: f64) -> f64 {
+        match self {
+            Scale::Linear => coordinate,
+            Scale::Logarithmic => 10f64.powf(coordinate),
+        }
+    }
+
+    fn format(self, coordinate: f64) -> String {
+        let value = self.value_at(coordinate);
+        if self == Scale::Linear || (1e-3..1e5).contains(&value) {
+            let formatted = format!("{value:.3}");
+            formatted.trim_end_matches('0').trim_end_matches('.').to_string()
+        } else {
+            format!("{value:.1e}")
+        }
+    }
+}
+
+/// The range of an axis that is always shown, regardless of panning and zooming
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct FixedBounds {
+    pub min: f64,
+    pub max: f64,
+}
+
+impl FixedBounds {
+    /// Returns the range of coordinates on an axis with `scale`, if it can be shown
+    fn to_plot(self, scale: Scale) -> Option<(f64, f64)> {
+        let (min, max) = (scale.to_plot(self.min), scale.to_plot(self.max));
+        (min.is_finite() && max.is_finite() && min < max).then_some((min, max))
+    }
+}
+
+/// The settings of the plot's axes, which are saved with the input
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct PlotAxes {
+    pub x_scale: Scale,
+    pub y_scale: Scale,
+    pub x_bounds: Option<FixedBounds>,
+    pub y_bounds: Option<FixedBounds>,
+    /// The ratio of a unit on the y-axis to a unit on the x-axis (on the screen), if it's locked.
+    /// Fixed bounds on both axes take precedence.
+    pub aspect_ratio: Option<f32>,
+}
+
+impl Default for PlotAxes {
+    fn default() -> Self {
+        Self {
+            x_scale: Scale::Linear,
+            y_scale: Scale::Linear,
+            x_bounds: None,
+            y_bounds: None,
+            aspect_ratio: Some(1.0),
+        }
+    }
+}
+
+impl PlotAxes {
+    /// Converts a point to the coordinates it's drawn at
+    pub fn to_plot(&self, x: f64, y: f64) -> PlotPoint {
+        PlotPoint::new(self.x_scale.to_plot(x), self.y_scale.to_plot(y))
+    }
+
+    /// Converts a coordinate on the x-axis to the value drawn there
+    pub fn x_from_plot(&self, x: f64) -> f64 {
+        self.x_scale.value_at(x)
+    }
+
+    /// The values shown on the x-axis in `bounds`
+    pub fn x_range(&self, bounds: &PlotBounds) -> RangeInclusive<f64> {
+        self.x_from_plot(bounds.min()[0])..=self.x_from_plot(bounds.max()[0])
+    }
+
+    /// Applies the scaling and aspect ratio to `plot`
+    pub fn configure(&self, mut plot: Plot) -> Plot {
+        let (x_scale, y_scale) = (self.x_scale, self.y_scale);
+        if let Some(aspect_ratio) = self.aspect_ratio {
+            if self.x_bounds.is_none() || self.y_bounds.is_none() {
+                plot = plot.data_aspect(aspect_ratio);
+            }
+        }
+
+        plot
+            .x_axis_formatter(move |x, _| x_scale.format(x))
+            .y_axis_formatter(move |y, _| y_scale.format(y))
+            .coordinates_formatter(
+                plot::Corner::LeftBottom,
+                CoordinatesFormatter::new(move |point, _| format!(
+                    "x: {}\ny: {}",
+                    x_scale.format(point.x), y_scale.format(point.y),
+                )),
+            )
+    }
+
+    /// Keeps the fixed bounds visible
+    pub fn apply_bounds(&self, plot_ui: &mut PlotUi) {
+        let x_bounds = self.x_bounds.and_then(|bounds| bounds.to_plot(self.x_scale));
+        let y_bounds = self.y_bounds.and_then(|bounds| bounds.to_plot(self.y_scale));
+        if x_bounds.is_none() && y_bounds.is_none() { return; }
+
+        let bounds = plot_ui.plot_bounds();
+        let (x_min, x_max) = x_bounds.unwrap_or((bounds.min()[0], bounds.max()[0]));
+        let (y_min, y_max) = y_bounds.unwrap_or((bounds.min()[1], bounds.max()[1]));
+        plot_ui.set_plot_bounds(PlotBounds::from_min_max([x_min, y_min], [x_max, y_max]));
+    }
+
+    /// Shows the menu for changing the settings. `bounds` are the currently visible bounds, which
+    /// fixed bounds start with.
+    pub fn menu(&mut self, ui: &mut Ui, bounds: Option<&PlotBounds>) {
+        ui.menu_button("Axes", |ui| {
+            Grid::new("plot_axes_grid").num_columns(2).show(ui, |ui| {
+                ui.label("x-axis:");
+                scale_selection(ui, &mut self.x_scale);
+                ui.end_row();
+                ui.label("y-axis:");
+                scale_selection(ui, &mut self.y_scale);
+                ui.end_row();
+            });
+            ui.separator();
+
+            let visible = |axis: usize, scale: Scale| bounds.map_or(FixedBounds { min: -1.0, max: 1.0 }, |bounds| FixedBounds {
+                min: scale.value_at(bounds.min()[axis]),
+                max: scale.value_at(bounds.max()[axis]),
+            });
+            fixed_bounds(ui, "Fixed x-range", &mut self.x_bounds, visible(0, self.x_scale), self.x_scale);
+            fixed_bounds(ui, "Fixed y-range", &mut self.y_bounds, visible(1, self.y_scale), self.y_scale);
+            ui.separator();
+
+            ui.horizontal(|ui| {
+                let mut is_locked = self.aspect_ratio.is_some();
+                if ui.checkbox(&mut is_locked, "Lock aspect ratio").changed() {
+                    self.aspect_ratio = is_locked.then_some(1.0);
+                }
+                if let Some(aspect_ratio) = &mut self.aspect_ratio {
+                    ui.add(DragValue::new(aspect_ratio).speed(0.01).clamp_range(0.01..=100.0));
+                }
+            });
+            if self.x_bounds.is_some() && self.y_bounds.is_some() && self.aspect_ratio.is_some() {
+                ui.label(RichText::new("The fixed ranges take precedence").small());
+            }
+        });
+    }
+}
+
+fn scale_selection(ui: &mut Ui, scale: &mut Scale) {
+    ui.horizontal(|ui| {
+        ui.selectable_value(scale, Scale::Linear, "Linear");
+        ui.selectable_value(scale, Scale::Logarithmic, "Logarithmic");
+    });
+}
+
+fn fixed_bounds(ui: &mut Ui, label: &str, bounds: &mut Option<FixedBounds>, visible: FixedBounds, scale: Scale) {
+    ui.horizontal(|ui| {
+        let mut is_fixed = bounds.is_some();
+        if ui.checkbox(&mut is_fixed, label).changed() {
+            *bounds = is_fixed.then_some(visible);
+        }
+
+        let Some(bounds) = bounds else { return; };
+        ui.add(DragValue::new(&mut bounds.min).speed(0.1));
+        ui.label("to");
+        ui.add(DragValue::new(&mut bounds.max).speed(0.1));
+        if bounds.to_plot(scale).is_none() {
+            ui.label(RichText::new("Invalid range").color(crate::ERROR_COLOR));
+        }
+    });
+}
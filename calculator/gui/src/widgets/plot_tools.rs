@@ -0,0 +1,280 @@
+/*
+ * Copyright (c) 2023, david072
+ *
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+//! Marked points in the plot, which can be found numerically (roots, extrema and intersections)
+//! or added and dragged along a function by the user
+
+use eframe::egui::*;
+use eframe::egui::plot::{MarkerShape, PlotBounds, PlotUi, Points, Text};
+
+use funcially_core::{Calculator, ExtremumKind, Function as CalcFn};
+
+use super::plot_axes::PlotAxes;
+
+/// How many evenly spaced arguments in the visible range are sampled to find points
+const ANALYSIS_SAMPLES: usize = 1000;
+/// How close (in points on the screen) the pointer has to be to a marked point to drag it
+const GRAB_RADIUS: f32 = 8.0;
+const POINT_RADIUS: f32 = 5.0;
+const SELECTED_POINT_COLOR: Color32 = Color32::GOLD;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum PointKind {
+    Point,
+    Root,
+    Minimum,
+    Maximum,
+    Intersection,
+}
+
+impl PointKind {
+    fn name(&self) -> &'static str {
+        match self {
+            PointKind::Point => "Point",
+            PointKind::Root => "Root",
+            PointKind::Minimum => "Minimum",
+            PointKind::Maximum => "Maximum",
+            PointKind::Intersection => "Intersection",
+        }
+    }
+
+    fn shape(&self) -> MarkerShape {
+        match self {
+            PointKind::Point => MarkerShape::Circle,
+            PointKind::Root => MarkerShape::Diamond,
+            PointKind::Minimum => MarkerShape::Down,
+            PointKind::Maximum => MarkerShape::Up,
+            PointKind::Intersection => MarkerShape::Cross,
+        }
+    }
+}
+
+impl From<ExtremumKind> for PointKind {
+    fn from(kind: ExtremumKind) -> Self {
+        match kind {
+            ExtremumKind::Minimum => PointKind::Minimum,
+            ExtremumKind::Maximum => PointKind::Maximum,
+        }
+    }
+}
+
+#[derive(Clone)]
+struct MarkedPoint {
+    kind: PointKind,
+    /// The name of the function the point lies on. Dragging the point moves it along the function.
+    function: String,
+    x: f64,
+    y: f64,
+}
+
+#[derive(Default, Clone)]
+pub struct PlotTools {
+    /// The function that is analyzed
+    function: Option<String>,
+    /// The function whose intersections with [Self::function] are found
+    other_function: Option<String>,
+    points: Vec<MarkedPoint>,
+    selected_point: Option<usize>,
+    dragged_point: Option<usize>,
+    /// The bounds that were visible in the last frame. Points are searched in their x-range.
+    bounds: Option<PlotBounds>,
+}
+
+storable!(PlotTools);
+
+impl PlotTools {
+    pub fn is_dragging(&self) -> bool {
+        self.dragged_point.is_some()
+    }
+
+    pub fn bounds(&self) -> Option<&PlotBounds> {
+        self.bounds.as_ref()
+    }
+
+    /// Removes points on functions that aren't plotted anymore and makes sure the selected
+    /// functions are plotted
+    pub fn update_functions(&mut self, functions: &[(&str, &CalcFn)]) {
+        let is_plotted = |name: &str| functions.iter().any(|(f, _)| *f == name);
+
+        let point_count = self.points.len();
+        self.points.retain(|point| is_plotted(&point.function));
+        if self.points.len() != point_count {
+            self.selected_point = None;
+            self.dragged_point = None;
+        }
+
+        if !self.function.as_deref().map_or(false, is_plotted) {
+            self.function = functions.first().map(|(name, _)| name.to_string());
+        }
+        if !self.other_function.as_deref().map_or(false, is_plotted) || self.other_function == self.function {
+            self.other_function = functions.iter()
+                .map(|(name, _)| name.to_string())
+                .find(|name| Some(name) != self.function.as_ref());
+        }
+    }
+
+    /// Shows the function selection and the buttons to find points
+    pub fn buttons(&mut self, ui: &mut Ui, functions: &[(&str, &CalcFn)], calculator: &Calculator, axes: &PlotAxes) {
+        function_selection(ui, "plot_tools_function", &mut self.function, functions);
+
+        let evaluator_of = |name: &Option<String>| functions.iter()
+            .find(|(f, _)| Some(*f) == name.as_deref())
+            .and_then(|(_, f)| calculator.function_evaluator(f));
+        let range = self.bounds.map_or(-1.0..=1.0, |bounds| axes.x_range(&bounds));
+
+        if let (Some(function), Some(evaluator)) = (self.function.clone(), evaluator_of(&self.function)) {
+            if ui.button("Roots").clicked() {
+                let points = evaluator.roots(range.clone(), ANALYSIS_SAMPLES)
+                    .into_iter()
+                    .map(|x| MarkedPoint { kind: PointKind::Root, function: function.clone(), x, y: 0.0 });
+                self.replace_points(&[PointKind::Root], points);
+            }
+            if ui.button("Extrema").clicked() {
+                let points = evaluator.extrema(range.clone(), ANALYSIS_SAMPLES)
+                    .into_iter()
+                    .map(|extremum| MarkedPoint {
+                        kind: extremum.kind.into(),
+                        function: function.clone(),
+                        x: extremum.x,
+                        y: extremum.y,
+                    });
+                self.replace_points(&[PointKind::Minimum, PointKind::Maximum], points);
+            }
+            if ui.button("Add point").on_hover_text("Drag the point to move it along the function").clicked() {
+                let x = self.bounds.map_or(0.0, |bounds| axes.x_from_plot((bounds.min()[0] + bounds.max()[0]) / 2.0));
+                self.points.push(MarkedPoint { kind: PointKind::Point, function: function.clone(), x, y: evaluator.evaluate(x) });
+                self.selected_point = Some(self.points.len() - 1);
+            }
+
+            if functions.len() > 1 {
+                ui.separator();
+                let find_intersections = ui.button("Intersections with").clicked();
+                let others = functions.iter()
+                    .filter(|(name, _)| *name != function)
+                    .copied()
+                    .collect::<Vec<_>>();
+                function_selection(ui, "plot_tools_other_function", &mut self.other_function, &others);
+
+                if let (true, Some(other)) = (find_intersections, evaluator_of(&self.other_function)) {
+                    let points = evaluator.intersections(&other, range, ANALYSIS_SAMPLES)
+                        .into_iter()
+                        .map(|(x, y)| MarkedPoint { kind: PointKind::Intersection, function: function.clone(), x, y });
+                    self.replace_points(&[PointKind::Intersection], points);
+                }
+            }
+        }
+
+        if !self.points.is_empty() && ui.button("Clear").clicked() {
+            self.points.clear();
+            self.selected_point = None;
+        }
+    }
+
+    /// Shows the coordinates of the selected point. Returns the coordinate the user chose to
+    /// insert into the input.
+    pub fn selected_point(&mut self, ui: &mut Ui) -> Option<f64> {
+        let mut inserted_value = None;
+        let index = self.selected_point?;
+        let point = &self.points[index];
+        let mut should_remove = false;
+        ui.horizontal_wrapped(|ui| {
+            ui.label(format!(
+                "{} of {}: ({}, {})",
+                point.kind.name(), point.function, format_coordinate(point.x), format_coordinate(point.y),
+            ));
+            if ui.small_button("Insert x").clicked() { inserted_value = Some(point.x); }
+            if ui.small_button("Insert y").clicked() { inserted_value = Some(point.y); }
+            should_remove = ui.small_button("Remove").clicked();
+        });
+
+        if should_remove {
+            self.points.remove(index);
+            self.selected_point = None;
+        }
+        inserted_value
+    }
+
+    /// Shows the marked points and a crosshair under the pointer, and lets the user select and
+    /// drag points
+    pub fn show(&mut self, plot_ui: &mut PlotUi, functions: &[(&str, &CalcFn)], calculator: &Calculator, axes: &PlotAxes) {
+        self.bounds = Some(plot_ui.plot_bounds());
+        self.interact(plot_ui, functions, calculator, axes);
+
+        if let Some(pointer) = plot_ui.pointer_coordinate() {
+            let color = plot_ui.ctx().style().visuals.weak_text_color();
+            plot_ui.vline(plot::VLine::new(pointer.x).color(color));
+            plot_ui.hline(plot::HLine::new(pointer.y).color(color));
+        }
+
+        for (i, point) in self.points.iter().enumerate() {
+            let position = axes.to_plot(point.x, point.y);
+            let mut points = Points::new(vec![[position.x, position.y]])
+                .shape(point.kind.shape())
+                .radius(POINT_RADIUS)
+                .filled(true);
+            if self.selected_point == Some(i) {
+                points = points.color(SELECTED_POINT_COLOR);
+                plot_ui.text(Text::new(
+                    position,
+                    format!("  ({}, {})", format_coordinate(point.x), format_coordinate(point.y)),
+                ).color(SELECTED_POINT_COLOR).anchor(Align2::LEFT_BOTTOM));
+            }
+            plot_ui.points(points);
+        }
+    }
+
+    fn interact(&mut self, plot_ui: &mut PlotUi, functions: &[(&str, &CalcFn)], calculator: &Calculator, axes: &PlotAxes) {
+        let (pressed, down, pointer) = plot_ui.ctx().input(|i| (
+            i.pointer.primary_pressed(),
+            i.pointer.primary_down(),
+            i.pointer.interact_pos(),
+        ));
+        if !down { self.dragged_point = None; }
+
+        if let (true, true, Some(pointer)) = (pressed, plot_ui.plot_hovered(), pointer) {
+            self.dragged_point = self.points.iter().position(|point| {
+                plot_ui.screen_from_plot(axes.to_plot(point.x, point.y)).distance(pointer) <= GRAB_RADIUS
+            });
+            self.selected_point = self.dragged_point;
+        }
+
+        let (Some(index), Some(pointer)) = (self.dragged_point, plot_ui.pointer_coordinate()) else { return; };
+        let point = &mut self.points[index];
+        let Some(evaluator) = functions.iter()
+            .find(|(name, _)| *name == point.function)
+            .and_then(|(_, f)| calculator.function_evaluator(f)) else { return; };
+
+        // A dragged point generally isn't a root or extremum anymore
+        point.kind = PointKind::Point;
+        point.x = axes.x_from_plot(pointer.x);
+        point.y = evaluator.evaluate(point.x);
+    }
+
+    /// Replaces the points of `kinds` with `points`
+    fn replace_points(&mut self, kinds: &[PointKind], points: impl Iterator<Item=MarkedPoint>) {
+        self.points.retain(|point| !kinds.contains(&point.kind));
+        self.points.extend(points);
+        self.selected_point = None;
+        self.dragged_point = None;
+    }
+}
+
+fn function_selection(ui: &mut Ui, id: &str, selected: &mut Option<String>, functions: &[(&str, &CalcFn)]) {
+    ComboBox::from_id_source(id)
+        .selected_text(selected.as_deref().unwrap_or("-"))
+        .show_ui(ui, |ui| {
+            for (name, _) in functions {
+                ui.selectable_value(selected, Some(name.to_string()), *name);
+            }
+        });
+}
+
+/// Formats a coordinate with up to 6 decimal places
+fn format_coordinate(value: f64) -> String {
+    let formatted = format!("{value:.6}");
+    let formatted = formatted.trim_end_matches('0').trim_end_matches('.');
+    if formatted == "-0" { "0".to_string() } else { formatted.to_string() }
+}
@@ -0,0 +1,123 @@
+/*
+ * Copyright (c) 2023, david072
+ *
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+use std::time::Duration;
+
+use eframe::egui::*;
+use funcially_core::{LineResult, LineTiming};
+
+/// The columns of the [ProfileTable]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ProfileColumn {
+    #[default]
+    Line,
+    Tokenization,
+    Parsing,
+    Evaluation,
+    Total,
+}
+
+impl ProfileColumn {
+    const ALL: [ProfileColumn; 5] = [
+        ProfileColumn::Line,
+        ProfileColumn::Tokenization,
+        ProfileColumn::Parsing,
+        ProfileColumn::Evaluation,
+        ProfileColumn::Total,
+    ];
+
+    fn name(&self) -> &'static str {
+        match self {
+            ProfileColumn::Line => "Line",
+            ProfileColumn::Tokenization => "Tokenize",
+            ProfileColumn::Parsing => "Parse",
+            ProfileColumn::Evaluation => "Evaluate",
+            ProfileColumn::Total => "Total",
+        }
+    }
+
+    fn duration(&self, timing: &LineTiming) -> Duration {
+        match self {
+            ProfileColumn::Line | ProfileColumn::Total => timing.total(),
+            ProfileColumn::Tokenization => timing.tokenization,
+            ProfileColumn::Parsing => timing.parsing,
+            ProfileColumn::Evaluation => timing.evaluation,
+        }
+    }
+}
+
+/// Shows how long the calculations of the lines took, sorted by one of its columns
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ProfileTable {
+    sort_column: ProfileColumn,
+    descending: bool,
+}
+
+impl ProfileTable {
+    pub fn show(&mut self, ui: &mut Ui, font_id: &FontId, source: &str, results: &[LineResult]) {
+        let mut rows = results.iter()
+            .filter_map(|result| Some((result.line_range.start, result.timing?)))
+            .collect::<Vec<_>>();
+        if rows.is_empty() {
+            ui.label("No lines were calculated yet");
+            return;
+        }
+
+        let total = rows.iter().map(|(_, timing)| timing.total()).sum::<Duration>();
+        ui.label(format!("{} calculations took {} in total", rows.len(), format_duration(total)));
+        #[cfg(target_arch = "wasm32")]
+        ui.label(RichText::new("Time can't be measured on the web").color(Color32::GRAY));
+        ui.separator();
+
+        match self.sort_column {
+            ProfileColumn::Line => rows.sort_by_key(|(line, _)| *line),
+            column => rows.sort_by_key(|(_, timing)| column.duration(timing)),
+        }
+        if self.descending { rows.reverse(); }
+
+        let source_lines = source.split('\n').collect::<Vec<_>>();
+        let text = |text: &str, color: Color32| RichText::new(text).font(font_id.clone()).color(color);
+
+        Grid::new("profile_table_grid")
+            .striped(true)
+            .num_columns(ProfileColumn::ALL.len() + 1)
+            .show(ui, |ui| {
+                for column in ProfileColumn::ALL {
+                    let mut title = column.name().to_owned();
+                    if column == self.sort_column {
+                        title += if self.descending { " ⏷" } else { " ⏶" };
+                    }
+                    if ui.selectable_label(column == self.sort_column, RichText::new(title).strong()).clicked() {
+                        // Sorting by the same column again reverses the order. The slowest lines
+                        // are the interesting ones, so durations are sorted descending first.
+                        if column == self.sort_column {
+                            self.descending = !self.descending;
+                        } else {
+                            self.sort_column = column;
+                            self.descending = column != ProfileColumn::Line;
+                        }
+                    }
+                    if column == ProfileColumn::Line {
+                        ui.label(RichText::new("Input").strong());
+                    }
+                }
+                ui.end_row();
+
+                for (line, timing) in rows {
+                    ui.label(text(&(line + 1).to_string(), Color32::GRAY));
+                    ui.label(text(source_lines.get(line).copied().unwrap_or_default().trim(), Color32::GRAY));
+                    for duration in [timing.tokenization, timing.parsing, timing.evaluation, timing.total()] {
+                        ui.label(text(&format_duration(duration), ui.visuals().text_color()));
+                    }
+                    ui.end_row();
+                }
+            });
+    }
+}
+
+fn format_duration(duration: Duration) -> String {
+    format!("{:.3} ms", duration.as_secs_f64() * 1000.0)
+}